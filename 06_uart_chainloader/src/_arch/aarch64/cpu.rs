@@ -11,7 +11,9 @@
 //!
 //! crate::cpu::arch_cpu
 
-use cortex_a::asm;
+use core::time::Duration;
+use cortex_a::{asm, registers::*};
+use tock_registers::interfaces::Readable;
 
 //--------------------------------------------------------------------------------------------------
 // Public Code
@@ -28,6 +30,18 @@ pub fn spin_for_cycles(n: usize) {
     }
 }
 
+/// Return the time since the core's generic timer started counting.
+#[inline(always)]
+pub fn uptime() -> Duration {
+    // Prevent that the counter is read ahead of time due to out-of-order execution.
+    unsafe { asm::barrier::isb(asm::barrier::SY) };
+
+    let ticks = CNTPCT_EL0.get();
+    let frq = CNTFRQ_EL0.get();
+
+    Duration::from_nanos((ticks as u128 * 1_000_000_000 / frq as u128) as u64)
+}
+
 /// Pause execution on the core.
 #[inline(always)]
 pub fn wait_forever() -> ! {
@@ -21,7 +21,9 @@ core::arch::global_asm!(include_str!("boot.s"));
 /// The Rust entry of the `kernel` binary.
 ///
 /// The function is called from the assembly `_start` function.
+///
+/// The firmware passes the physical address of a DTB in `x0`, which the assembly hands over here.
 #[no_mangle]
-pub unsafe fn _start_rust() -> ! {
-    crate::kernel_init()
+pub unsafe extern "C" fn _start_rust(firmware_dtb_addr: usize) -> ! {
+    crate::kernel_init(firmware_dtb_addr)
 }
@@ -13,7 +13,7 @@ use crate::{
     bsp::device_driver::common::MMIODerefWrapper, console, cpu, driver, synchronization,
     synchronization::NullLock,
 };
-use core::fmt;
+use core::{fmt, time::Duration};
 use tock_registers::{
     interfaces::{Readable, Writeable},
     register_bitfields, register_structs,
@@ -376,6 +376,19 @@ impl console::interface::Read for PL011Uart {
             .lock(|inner| inner.read_char(BlockingMode::Blocking).unwrap())
     }
 
+    fn read_char_timeout(&self, timeout: Duration) -> Option<char> {
+        let deadline = cpu::uptime() + timeout;
+
+        loop {
+            let c = self
+                .inner
+                .lock(|inner| inner.read_char(BlockingMode::NonBlocking));
+            if c.is_some() || cpu::uptime() >= deadline {
+                return c;
+            }
+        }
+    }
+
     fn clear_rx(&self) {
         // Read from the RX FIFO until it is indicating empty.
         while self
@@ -4,6 +4,14 @@
 
 //! BSP Memory Management.
 
+use core::{cell::UnsafeCell, ops::Range};
+
+// Symbols from the linker script.
+extern "Rust" {
+    static __chainloader_start: UnsafeCell<()>;
+    static __bss_end_exclusive: UnsafeCell<()>;
+}
+
 //--------------------------------------------------------------------------------------------------
 // Public Definitions
 //--------------------------------------------------------------------------------------------------
@@ -11,7 +19,8 @@
 /// The board's physical memory map.
 #[rustfmt::skip]
 pub(super) mod map {
-    pub const BOARD_DEFAULT_LOAD_ADDRESS: usize =        0x8_0000;
+    /// Where a DTB that is pushed alongside the kernel is placed. Above the relocated chainloader.
+    pub const DTB_LOAD_ADDRESS:           usize =        0x0300_0000;
 
     pub const GPIO_OFFSET:                usize =        0x0020_0000;
     pub const UART_OFFSET:                usize =        0x0020_1000;
@@ -41,8 +50,13 @@ pub(super) mod map {
 // Public Code
 //--------------------------------------------------------------------------------------------------
 
-/// The address on which the Raspberry firmware loads every binary by default.
+/// The memory that the relocated chainloader occupies, including its stack.
+pub fn chainloader_range() -> Range<usize> {
+    unsafe { __chainloader_start.get() as usize..__bss_end_exclusive.get() as usize }
+}
+
+/// The address on which a DTB that is pushed alongside the kernel is loaded.
 #[inline(always)]
-pub fn board_default_load_addr() -> *const u64 {
-    map::BOARD_DEFAULT_LOAD_ADDRESS as _
+pub fn dtb_load_addr() -> *mut u8 {
+    map::DTB_LOAD_ADDRESS as _
 }
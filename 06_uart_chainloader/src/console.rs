@@ -10,7 +10,7 @@
 
 /// Console interfaces.
 pub mod interface {
-    use core::fmt;
+    use core::{fmt, time::Duration};
 
     /// Console write functions.
     pub trait Write {
@@ -31,6 +31,11 @@ pub mod interface {
             ' '
         }
 
+        /// Read a single character, or give up if none arrives within `timeout`.
+        fn read_char_timeout(&self, _timeout: Duration) -> Option<char> {
+            None
+        }
+
         /// Clear RX buffers, if any.
         fn clear_rx(&self);
     }
@@ -0,0 +1,87 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! CRC-32 checksums.
+//!
+//! This is the CRC-32 of zlib and Ethernet, so that `Minipush` can use Ruby's `Zlib.crc32`.
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The reversed representation of the polynomial.
+const POLYNOMIAL: u32 = 0xedb8_8320;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A CRC-32 that is computed byte by byte.
+pub struct Crc32(u32);
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static TABLE: [u32; 256] = make_table();
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// The CRC of each possible byte, for processing a byte at a time instead of a bit at a time.
+const fn make_table() -> [u32; 256] {
+    let mut table = [0; 256];
+
+    let mut i = 0;
+    while i < table.len() {
+        let mut crc = i as u32;
+
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl Crc32 {
+    /// Create an instance.
+    pub const fn new() -> Self {
+        Self(0xffff_ffff)
+    }
+
+    /// Add a byte to the checksum.
+    pub fn update(&mut self, byte: u8) {
+        self.0 = TABLE[((self.0 ^ u32::from(byte)) & 0xff) as usize] ^ (self.0 >> 8);
+    }
+
+    /// The checksum of the bytes so far.
+    pub fn finish(&self) -> u32 {
+        !self.0
+    }
+}
+
+/// The checksum of `bytes`.
+pub fn checksum(bytes: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    for byte in bytes {
+        crc.update(*byte);
+    }
+
+    crc.finish()
+}
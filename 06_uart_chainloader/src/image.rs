@@ -0,0 +1,86 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! The header that `Minipush` sends in front of a kernel.
+//!
+//! It tells where the kernel must be loaded and where execution starts, so that kernels linked to
+//! other addresses than the firmware's default load address can be pushed. All fields are little
+//! endian:
+//!
+//! | Offset | Size | Field                                |
+//! |--------|------|--------------------------------------|
+//! | 0      | 4    | Magic, `RPIK`                        |
+//! | 4      | 4    | Size of the kernel in bytes          |
+//! | 8      | 8    | Load address                         |
+//! | 16     | 8    | Offset of the entry point            |
+//! | 24     | 4    | CRC-32 of the whole kernel           |
+
+use core::ops::Range;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A parsed and validated image header.
+pub struct ImageHeader {
+    /// Size of the kernel in bytes.
+    pub size: usize,
+
+    /// The address that the kernel must be loaded to.
+    pub load_addr: usize,
+
+    /// Offset of the entry point from the load address.
+    pub entry_offset: usize,
+
+    /// CRC-32 of the whole kernel, which is checked after loading.
+    pub checksum: u32,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl ImageHeader {
+    /// Size of the header in bytes.
+    pub const SIZE: usize = 28;
+
+    const MAGIC: &'static [u8; 4] = b"RPIK";
+
+    /// Parse and validate a header.
+    pub fn parse(bytes: &[u8; Self::SIZE]) -> Result<Self, &'static str> {
+        if &bytes[0..4] != Self::MAGIC {
+            return Err("Wrong magic. Is Minipush up to date?");
+        }
+
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        let u64_at = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+
+        let header = Self {
+            size: u32_at(4) as usize,
+            load_addr: u64_at(8) as usize,
+            entry_offset: u64_at(16) as usize,
+            checksum: u32_at(24),
+        };
+
+        if header.load_addr.checked_add(header.size).is_none() {
+            return Err("Kernel exceeds the address space");
+        }
+
+        if header.entry_offset >= header.size || header.entry_addr() % 4 != 0 {
+            return Err("Entry point is not an aligned address within the kernel");
+        }
+
+        Ok(header)
+    }
+
+    /// The memory that the kernel is loaded to.
+    pub fn load_range(&self) -> Range<usize> {
+        self.load_addr..(self.load_addr + self.size)
+    }
+
+    /// The address where execution starts.
+    pub fn entry_addr(&self) -> usize {
+        self.load_addr + self.entry_offset
+    }
+}
@@ -3,7 +3,9 @@
 // Copyright (c) 2018-2022 Andre Richter <andre.o.richter@gmail.com>
 
 // Rust embedded logo for `make doc`.
-#![doc(html_logo_url = "https://raw.githubusercontent.com/rust-embedded/wg/master/assets/logo/ewg-logo-blue-white-on-transparent.png")]
+#![doc(
+    html_logo_url = "https://raw.githubusercontent.com/rust-embedded/wg/master/assets/logo/ewg-logo-blue-white-on-transparent.png"
+)]
 
 //! The `kernel` binary.
 //!
@@ -114,18 +116,22 @@
 mod bsp;
 mod console;
 mod cpu;
+mod crc32;
 mod driver;
+mod image;
 mod panic_wait;
 mod print;
 mod synchronization;
 
+use core::{ops::Range, time::Duration};
+
 /// Early init code.
 ///
 /// # Safety
 ///
 /// - Only a single core must be active and running this function.
 /// - The init calls in this function must appear in the correct order.
-unsafe fn kernel_init() -> ! {
+unsafe fn kernel_init(firmware_dtb_addr: usize) -> ! {
     use driver::interface::DriverManager;
 
     for i in bsp::driver::driver_manager().all_device_drivers().iter() {
@@ -137,7 +143,112 @@ unsafe fn kernel_init() -> ! {
     // println! is usable from here on.
 
     // Transition from unsafe to safe.
-    kernel_main()
+    kernel_main(firmware_dtb_addr)
+}
+
+/// The maximum size of a frame. Larger binaries are sent in several frames.
+const FRAME_SIZE: usize = 1024;
+
+/// Reply for a frame that arrived intact.
+const FRAME_ACK: char = 'A';
+
+/// Reply for a corrupted or incomplete frame, which `Minipush` sends again.
+const FRAME_NAK: char = 'N';
+
+/// How long to wait for the next byte of a frame. `Minipush` sends a frame in one go, so a longer
+/// pause means that bytes were lost, e.g. in a receive overrun.
+const BYTE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Receive the `i`-th byte of a frame. Only the first byte is waited for indefinitely. Returns
+/// `None` if the byte does not arrive in time.
+fn receive_frame_byte(i: usize) -> Option<u8> {
+    use bsp::console::console;
+    use console::interface::Read;
+
+    match i {
+        0 => Some(console().read_char() as u8),
+        _ => console().read_char_timeout(BYTE_TIMEOUT).map(|c| c as u8),
+    }
+}
+
+/// Receive `len` bytes to `dst`, followed by their CRC-32. Returns `None` if bytes were lost or
+/// the frame is corrupted.
+///
+/// # Safety
+///
+/// - `dst` must be valid for writing `len` bytes.
+unsafe fn try_receive_frame(dst: *mut u8, len: usize) -> Option<()> {
+    let mut crc = crc32::Crc32::new();
+    for i in 0..len {
+        let byte = receive_frame_byte(i)?;
+
+        crc.update(byte);
+        core::ptr::write_volatile(dst.add(i), byte);
+    }
+
+    let mut expected = [0_u8; 4];
+    for (i, byte) in expected.iter_mut().enumerate() {
+        *byte = receive_frame_byte(len + i)?;
+    }
+
+    if u32::from_le_bytes(expected) != crc.finish() {
+        return None;
+    }
+
+    Some(())
+}
+
+/// Receive `len` bytes to `dst`, followed by their CRC-32, and acknowledge them. Corrupted and
+/// incomplete frames are received again until they arrive intact.
+///
+/// # Safety
+///
+/// - `dst` must be valid for writing `len` bytes.
+unsafe fn receive_frame(dst: *mut u8, len: usize) {
+    use bsp::console::console;
+    use console::interface::All;
+
+    while try_receive_frame(dst, len).is_none() {
+        // Drop whatever is left of the frame, so that the retransmission starts aligned.
+        console().clear_rx();
+        console().write_char(FRAME_NAK);
+    }
+
+    console().write_char(FRAME_ACK);
+}
+
+/// Receive the size of an image.
+fn receive_size() -> usize {
+    let mut size = [0_u8; 4];
+    unsafe { receive_frame(size.as_mut_ptr(), size.len()) };
+
+    u32::from_le_bytes(size) as usize
+}
+
+/// Receive `size` bytes of an image to `dst`, in frames.
+///
+/// # Safety
+///
+/// - `dst` must be valid for writing `size` bytes.
+unsafe fn receive_image(dst: *mut u8, size: usize) {
+    for offset in (0..size).step_by(FRAME_SIZE) {
+        let len = core::cmp::min(FRAME_SIZE, size - offset);
+
+        receive_frame(dst.add(offset), len);
+    }
+}
+
+/// Panic if `range` overlaps memory that is in use.
+fn check_free(name: &str, range: &Range<usize>, used: &[Range<usize>]) {
+    if let Some(x) = used
+        .iter()
+        .find(|x| range.start < x.end && x.start < range.end)
+    {
+        panic!(
+            "{} at {:#x}..{:#x} overlaps {:#x}..{:#x}",
+            name, range.start, range.end, x.start, x.end
+        );
+    }
 }
 
 const MINILOAD_LOGO: &str = r#"
@@ -148,7 +259,10 @@ const MINILOAD_LOGO: &str = r#"
 "#;
 
 /// The main function running after the early init.
-fn kernel_main() -> ! {
+///
+/// The pushed kernel receives the address of a DTB in `x0`, like from the firmware. It is the
+/// pushed DTB, if `Minipush` sent one, or else the one from the firmware.
+fn kernel_main(firmware_dtb_addr: usize) -> ! {
     use bsp::console::console;
     use console::interface::All;
 
@@ -166,30 +280,52 @@ fn kernel_main() -> ! {
         console().write_char(3 as char);
     }
 
-    // Read the binary's size.
-    let mut size: u32 = u32::from(console().read_char() as u8);
-    size |= u32::from(console().read_char() as u8) << 8;
-    size |= u32::from(console().read_char() as u8) << 16;
-    size |= u32::from(console().read_char() as u8) << 24;
-
-    // Trust it's not too big.
-    console().write_char('O');
-    console().write_char('K');
-
-    let kernel_addr: *mut u8 = bsp::memory::board_default_load_addr() as *mut u8;
-    unsafe {
-        // Read the kernel byte by byte.
-        for i in 0..size {
-            core::ptr::write_volatile(kernel_addr.offset(i as isize), console().read_char() as u8)
+    let mut header = [0_u8; image::ImageHeader::SIZE];
+    unsafe { receive_frame(header.as_mut_ptr(), header.len()) };
+    let header = match image::ImageHeader::parse(&header) {
+        Err(x) => panic!("Invalid image header: {}", x),
+        Ok(x) => x,
+    };
+
+    let kernel_range = header.load_range();
+    check_free("Kernel", &kernel_range, &[bsp::memory::chainloader_range()]);
+    unsafe { receive_image(header.load_addr as *mut u8, header.size) };
+
+    // An empty DTB means that none was pushed.
+    let dtb_size = receive_size();
+    let dtb_addr = match dtb_size {
+        0 => firmware_dtb_addr,
+        _ => {
+            let dtb_addr = bsp::memory::dtb_load_addr();
+            let dtb_range = dtb_addr as usize..(dtb_addr as usize + dtb_size);
+            check_free(
+                "DTB",
+                &dtb_range,
+                &[bsp::memory::chainloader_range(), kernel_range.clone()],
+            );
+            unsafe { receive_image(dtb_addr, dtb_size) };
+
+            dtb_addr as usize
         }
+    };
+
+    // Frames are checked on arrival. This also catches kernels that were overwritten afterwards.
+    let loaded = unsafe { core::slice::from_raw_parts(header.load_addr as *const u8, header.size) };
+    if crc32::checksum(loaded) != header.checksum {
+        panic!("Kernel checksum mismatch");
     }
 
+    println!(
+        "[ML] Kernel at {:#x}, entry at {:#x}",
+        header.load_addr,
+        header.entry_addr()
+    );
     println!("[ML] Loaded! Executing the payload now\n");
     console().flush();
 
     // Use black magic to create a function pointer.
-    let kernel: fn() -> ! = unsafe { core::mem::transmute(kernel_addr) };
+    let kernel: extern "C" fn(usize) -> ! = unsafe { core::mem::transmute(header.entry_addr()) };
 
-    // Jump to loaded kernel!
-    kernel()
+    // Jump to loaded kernel, with the DTB address in x0!
+    kernel(dtb_addr)
 }
@@ -1,6 +1,8 @@
 use std::{env, fs, process};
 
 fn main() {
+    println!("cargo:rerun-if-env-changed=KERNEL_TEST_FILTER");
+
     let ld_script_path = match env::var("LD_SCRIPT_PATH") {
         Ok(var) => var,
         _ => process::exit(0),
@@ -0,0 +1,44 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Architectural backtracing support.
+//!
+//! # Orientation
+//!
+//! Since arch modules are imported into generic modules using the path attribute, the path of this
+//! file is:
+//!
+//! crate::backtrace::arch_backtrace
+
+use core::arch::asm;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// An AArch64 frame record, as pointed to by the frame pointer (x29).
+#[repr(C)]
+pub struct FrameRecord {
+    /// The frame pointer of the calling function.
+    pub next: usize,
+
+    /// The return address into the calling function.
+    pub return_addr: usize,
+}
+
+/// Return addresses point to the instruction after the call. Subtracting this yields the call.
+pub const CALL_INSTRUCTION_SIZE: usize = 4;
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// The frame pointer of the executing function.
+#[inline(always)]
+pub fn frame_pointer() -> usize {
+    let fp: usize;
+    unsafe { asm!("mov {}, x29", out(reg) fp, options(nomem, nostack, preserves_flags)) };
+
+    fp
+}
@@ -0,0 +1,53 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+.section .text
+
+//------------------------------------------------------------------------------
+// fn __context_switch(prev: *mut ThreadContext, next: *const ThreadContext)
+//
+// Layout of ThreadContext:
+//
+//   [16 * 0]  x19, x20
+//   [16 * 1]  x21, x22
+//   [16 * 2]  x23, x24
+//   [16 * 3]  x25, x26
+//   [16 * 4]  x27, x28
+//   [16 * 5]  x29 (frame pointer), x30 (link register)
+//   [16 * 6]  SP, TPIDR_EL1
+//
+// Since this is a function call, the caller already saved all other general purpose registers as
+// demanded by the procedure call standard. The kernel does not use FP/SIMD registers.
+//------------------------------------------------------------------------------
+__context_switch:
+	// Save the state of the previous context. The link register is where it continues once it is
+	// switched to again.
+	stp	x19, x20, [x0, #16 * 0]
+	stp	x21, x22, [x0, #16 * 1]
+	stp	x23, x24, [x0, #16 * 2]
+	stp	x25, x26, [x0, #16 * 3]
+	stp	x27, x28, [x0, #16 * 4]
+	stp	x29, x30, [x0, #16 * 5]
+	mov	x9,  sp
+	mrs	x10, TPIDR_EL1
+	stp	x9,  x10, [x0, #16 * 6]
+
+	// Restore the state of the next context and return into it.
+	ldp	x19, x20, [x1, #16 * 0]
+	ldp	x21, x22, [x1, #16 * 1]
+	ldp	x23, x24, [x1, #16 * 2]
+	ldp	x25, x26, [x1, #16 * 3]
+	ldp	x27, x28, [x1, #16 * 4]
+	ldp	x29, x30, [x1, #16 * 5]
+	ldp	x9,  x10, [x1, #16 * 6]
+	mov	sp,  x9
+	msr	TPIDR_EL1, x10
+
+	ret
+
+.size	__context_switch, . - __context_switch
+.type	__context_switch, function
@@ -11,7 +11,36 @@
 //!
 //! crate::cpu::arch_cpu
 
-use cortex_a::asm;
+use core::arch::{asm, global_asm};
+use cortex_a::{asm, asm::barrier};
+
+// Assembly counterpart to this file.
+global_asm!(include_str!("context_switch.s"));
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+extern "C" {
+    fn __context_switch(prev: *mut ThreadContext, next: *const ThreadContext);
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The CPU state of a thread of execution that is not running.
+///
+/// Only the callee-saved registers need to be saved, because execution only ever leaves a context
+/// by calling `switch_to()`. The thread pointer register (TPIDR_EL1) is saved as well, so that it
+/// can identify the running thread.
+#[repr(C)]
+pub struct ThreadContext {
+    /// x19 to x30.
+    callee_saved: [u64; 12],
+    sp: u64,
+    tpidr: u64,
+}
 
 //--------------------------------------------------------------------------------------------------
 // Public Code
@@ -27,23 +56,206 @@ pub fn wait_forever() -> ! {
     }
 }
 
-//--------------------------------------------------------------------------------------------------
-// Testing
-//--------------------------------------------------------------------------------------------------
-#[cfg(feature = "test_build")]
-use qemu_exit::QEMUExit;
+/// Wake cores that wait for an event.
+#[inline(always)]
+pub fn send_event() {
+    asm::sev()
+}
+
+/// Ask the firmware to start a core through the PSCI `CPU_ON` call, with `smc` as the conduit.
+///
+/// Returns the PSCI status code, which is zero on success.
+///
+/// # Safety
+///
+/// - The entry must be ready to execute with the MMU off.
+pub unsafe fn psci_cpu_on(target_mpidr: u64, phys_entry_addr: u64) -> i64 {
+    const PSCI_CPU_ON_64: u64 = 0xC400_0003;
 
-#[cfg(feature = "test_build")]
-const QEMU_EXIT_HANDLE: qemu_exit::AArch64 = qemu_exit::AArch64::new();
+    let mut status = PSCI_CPU_ON_64;
+    asm!(
+        "smc #0",
+        inout("x0") status,
+        in("x1") target_mpidr,
+        in("x2") phys_entry_addr,
+        in("x3") 0_u64,
+        clobber_abi("C"),
+        options(nostack)
+    );
 
-/// Make the host QEMU binary execute `exit(1)`.
-#[cfg(feature = "test_build")]
-pub fn qemu_exit_failure() -> ! {
-    QEMU_EXIT_HANDLE.exit_failure()
+    status as i64
 }
 
-/// Make the host QEMU binary execute `exit(0)`.
-#[cfg(feature = "test_build")]
-pub fn qemu_exit_success() -> ! {
-    QEMU_EXIT_HANDLE.exit_success()
+/// Wait until an interrupt is pending.
+///
+/// Also returns if the interrupt is masked, which allows checking for work with IRQs masked before
+/// sleeping, without missing the wakeup.
+#[inline(always)]
+pub fn wait_for_interrupt() {
+    asm::wfi()
+}
+
+/// Size of the smallest data cache line in the system, in bytes.
+#[inline(always)]
+fn dcache_line_size() -> usize {
+    let ctr_el0: u64;
+
+    unsafe { asm!("mrs {}, CTR_EL0", out(reg) ctr_el0) };
+
+    // DminLine, bits [19:16], is the log2 of the number of words in the smallest cache line.
+    4 << ((ctr_el0 >> 16) & 0xF)
+}
+
+/// Clean the data cache lines covering the given virtual address range to the point of coherency.
+///
+/// Use before a device reads memory that was written by the CPU.
+pub fn clean_dcache_range(start_addr: usize, size: usize) {
+    let line_size = dcache_line_size();
+    let end_addr = start_addr + size;
+    let mut addr = start_addr & !(line_size - 1);
+
+    while addr < end_addr {
+        unsafe { asm!("dc cvac, {}", in(reg) addr) };
+        addr += line_size;
+    }
+
+    unsafe { barrier::dsb(barrier::SY) };
+}
+
+/// Invalidate the data cache lines covering the given virtual address range.
+///
+/// Use before the CPU reads memory that was written by a device. Dirty lines that share the range
+/// are discarded, so buffers used this way should be cache-line aligned.
+pub fn invalidate_dcache_range(start_addr: usize, size: usize) {
+    let line_size = dcache_line_size();
+    let end_addr = start_addr + size;
+    let mut addr = start_addr & !(line_size - 1);
+
+    while addr < end_addr {
+        unsafe { asm!("dc ivac, {}", in(reg) addr) };
+        addr += line_size;
+    }
+
+    unsafe { barrier::dsb(barrier::SY) };
+}
+
+/// Invalidate the instruction cache lines covering the given virtual address range, on all cores.
+///
+/// Use after code was modified. The new instructions must have been cleaned from the data cache
+/// before.
+pub fn invalidate_icache_range(start_addr: usize, size: usize) {
+    // The instruction cache line size is in bits [3:0] of CTR_EL0, as log2 of the number of words.
+    let ctr_el0: u64;
+    unsafe { asm!("mrs {}, CTR_EL0", out(reg) ctr_el0) };
+    let line_size = 4 << (ctr_el0 & 0xF);
+
+    let end_addr = start_addr + size;
+    let mut addr = start_addr & !(line_size - 1);
+
+    while addr < end_addr {
+        unsafe { asm!("ic ivau, {}", in(reg) addr) };
+        addr += line_size;
+    }
+
+    unsafe {
+        barrier::dsb(barrier::ISH);
+        barrier::isb(barrier::SY);
+    }
+}
+
+impl ThreadContext {
+    /// Create an empty context. It is filled when switching away from it.
+    pub const fn new() -> Self {
+        Self {
+            callee_saved: [0; 12],
+            sp: 0,
+            tpidr: 0,
+        }
+    }
+
+    /// Create a context that did not run yet. Switching to it calls `start` on the given stack,
+    /// with the thread pointer set to `thread_pointer`.
+    pub fn new_for_start(
+        start: extern "C" fn() -> !,
+        stack_end_exclusive: usize,
+        thread_pointer: u64,
+    ) -> Self {
+        let mut context = Self::new();
+
+        // x29 (frame pointer) stays zero, which terminates backtraces. x30 (link register) is where
+        // `switch_to()` returns to.
+        context.callee_saved[11] = start as usize as u64;
+        context.sp = stack_end_exclusive as u64;
+        context.tpidr = thread_pointer;
+
+        context
+    }
+}
+
+/// Save the executing context to `prev` and continue with the context saved in `next`.
+///
+/// Returns when another context switches back to `prev`.
+///
+/// # Safety
+///
+/// - `prev` and `next` must stay valid until the switch back.
+/// - `next` must hold a context that is not running.
+/// - Interrupts must be masked if they can switch contexts as well.
+#[inline(always)]
+pub unsafe fn switch_to(prev: *mut ThreadContext, next: *const ThreadContext) {
+    __context_switch(prev, next)
+}
+
+/// Return the executing core's thread pointer (TPIDR_EL1).
+#[inline(always)]
+pub fn thread_pointer() -> u64 {
+    let value: u64;
+    unsafe { asm!("mrs {}, TPIDR_EL1", out(reg) value, options(nomem, nostack)) };
+
+    value
+}
+
+/// Set the executing core's thread pointer (TPIDR_EL1).
+///
+/// # Safety
+///
+/// - Code that identifies the running thread through the thread pointer must agree with the value.
+#[inline(always)]
+pub unsafe fn set_thread_pointer(value: u64) {
+    asm!("msr TPIDR_EL1, {}", in(reg) value, options(nomem, nostack));
+}
+
+/// Return the part number field of the executing core's MIDR_EL1, e.g. 0xD03 for a Cortex-A53.
+#[inline(always)]
+pub fn part_number() -> u16 {
+    let value: u64;
+    unsafe { asm!("mrs {}, MIDR_EL1", out(reg) value, options(nomem, nostack)) };
+
+    ((value >> 4) & 0xFFF) as u16
+}
+
+/// Start the 64 bit cycle counter of the executing core's PMU, counting at EL0 and EL1.
+pub fn enable_cycle_counter() {
+    const PMCR_EL0_E: u64 = 1 << 0;
+    const PMCR_EL0_LC: u64 = 1 << 6;
+    const PMCNTENSET_EL0_C: u64 = 1 << 31;
+
+    unsafe {
+        let pmcr: u64;
+        asm!("mrs {}, PMCR_EL0", out(reg) pmcr, options(nomem, nostack));
+
+        asm!("msr PMCCFILTR_EL0, xzr", options(nomem, nostack));
+        asm!("msr PMCNTENSET_EL0, {}", in(reg) PMCNTENSET_EL0_C, options(nomem, nostack));
+        asm!("msr PMCR_EL0, {}", in(reg) pmcr | PMCR_EL0_E | PMCR_EL0_LC, options(nomem, nostack));
+        barrier::isb(barrier::SY);
+    }
+}
+
+/// Return the cycle counter of the executing core's PMU. See [`enable_cycle_counter()`].
+#[inline(always)]
+pub fn cycle_counter() -> u64 {
+    let value: u64;
+    unsafe { asm!("mrs {}, PMCCNTR_EL0", out(reg) value, options(nomem, nostack)) };
+
+    value
 }
@@ -11,18 +11,86 @@
 //!
 //! crate::cpu::boot::arch_boot
 
-use crate::{memory, memory::Address};
-use core::arch::global_asm;
+use crate::{
+    bsp, cpu, dtb, memory,
+    memory::{Address, Physical},
+};
+use core::{
+    arch::global_asm,
+    cell::UnsafeCell,
+    sync::atomic::{AtomicU64, Ordering},
+};
 use cortex_a::{asm, registers::*};
 use tock_registers::interfaces::Writeable;
 
 // Assembly counterpart to this file.
 global_asm!(include_str!("boot.s"));
 
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const SECONDARY_STACK_SIZE: usize = 64 * 1024;
+
+#[repr(C, align(16))]
+struct SecondaryStack(UnsafeCell<[u8; SECONDARY_STACK_SIZE]>);
+
+/// The arguments that `_start_secondary` reads for a core. The layout is shared with `boot.s`.
+#[repr(C)]
+struct SecondaryBootArgs {
+    phys_stack_end_exclusive: AtomicU64,
+    virt_stack_end_exclusive: AtomicU64,
+}
+
+extern "Rust" {
+    static _start_secondary: UnsafeCell<()>;
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_STACK: SecondaryStack = SecondaryStack(UnsafeCell::new([0; SECONDARY_STACK_SIZE]));
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_ENTRY: AtomicU64 = AtomicU64::new(0);
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_BOOT_ARGS: SecondaryBootArgs = SecondaryBootArgs {
+    phys_stack_end_exclusive: AtomicU64::new(0),
+    virt_stack_end_exclusive: AtomicU64::new(0),
+};
+
+/// One stack per core. The boot core's entry is unused.
+///
+/// Stacks are written before they are read, so `_start` does not need to zero them.
+#[link_section = ".bss.noinit"]
+static SECONDARY_STACKS: [SecondaryStack; bsp::cpu::NUM_CORES] = [EMPTY_STACK; bsp::cpu::NUM_CORES];
+
+/// The entry addresses of the secondary cores that wait in `_start`, indexed by core. Zero while a
+/// core must keep waiting.
+///
+/// The cores poll it while the boot core clears `.bss`, so it must live in `.data`.
+#[no_mangle]
+#[link_section = ".data"]
+static SPIN_TABLE: [AtomicU64; bsp::cpu::NUM_CORES] = [NO_ENTRY; bsp::cpu::NUM_CORES];
+
+/// The value of `CurrentEL` when the boot core was started.
+static BOOT_CURRENT_EL: AtomicU64 = AtomicU64::new(0);
+
+/// Read by `_start_secondary`, indexed by core.
+#[no_mangle]
+static SECONDARY_BOOT_ARGS: [SecondaryBootArgs; bsp::cpu::NUM_CORES] =
+    [NO_BOOT_ARGS; bsp::cpu::NUM_CORES];
+
 //--------------------------------------------------------------------------------------------------
 // Private Code
 //--------------------------------------------------------------------------------------------------
 
+// Each stack is only used by the core it belongs to.
+unsafe impl Sync for SecondaryStack {}
+
 /// Prepares the transition from EL2 to EL1.
 ///
 /// # Safety
@@ -79,7 +147,12 @@ pub unsafe extern "C" fn _start_rust(
     phys_kernel_tables_base_addr: u64,
     virt_boot_core_stack_end_exclusive_addr: u64,
     virt_kernel_init_addr: u64,
+    phys_dtb_addr: u64,
+    boot_current_el: u64,
 ) -> ! {
+    dtb::set_boot_dtb_phys_addr(phys_dtb_addr as usize);
+    BOOT_CURRENT_EL.store(boot_current_el, Ordering::Relaxed);
+
     prepare_el2_to_el1_transition(
         virt_boot_core_stack_end_exclusive_addr,
         virt_kernel_init_addr,
@@ -93,3 +166,93 @@ pub unsafe extern "C" fn _start_rust(
     // execution of kernel_init() in EL1 from its _virtual address_.
     asm::eret()
 }
+
+/// The Rust entry of the secondary cores.
+///
+/// The function is called from the assembly `_start_secondary` function.
+///
+/// # Safety
+///
+/// - Exception return from EL2 must must continue execution in EL1 with `kernel_init_secondary()`.
+#[no_mangle]
+pub unsafe extern "C" fn _start_rust_secondary(
+    phys_kernel_tables_base_addr: u64,
+    virt_stack_end_exclusive_addr: u64,
+    virt_kernel_init_secondary_addr: u64,
+) -> ! {
+    prepare_el2_to_el1_transition(
+        virt_stack_end_exclusive_addr,
+        virt_kernel_init_secondary_addr,
+    );
+
+    // Turn on the MMU for EL1.
+    let addr = Address::new(phys_kernel_tables_base_addr as usize);
+    memory::mmu::enable_mmu_and_caching(addr).unwrap();
+
+    asm::eret()
+}
+
+/// The privilege level that the boot core was started in, before the boot code dropped to EL1.
+pub fn boot_privilege_level() -> &'static str {
+    match BOOT_CURRENT_EL.load(Ordering::Relaxed) >> 2 {
+        3 => "EL3",
+        2 => "EL2",
+        1 => "EL1",
+        _ => "Unknown",
+    }
+}
+
+/// Release a secondary core that waits in the kernel's spin-table, so that it starts executing at
+/// the given physical address.
+///
+/// Secondary cores that were started in `_start`, e.g. because the firmware releases all cores at
+/// once, wait there until this is called. Cores that wait in the firmware's spin-table are not
+/// affected.
+///
+/// # Safety
+///
+/// - The entry must be ready to execute with the MMU off, in EL2.
+pub unsafe fn boot_secondary(
+    core: usize,
+    phys_entry_addr: Address<Physical>,
+) -> Result<(), &'static str> {
+    if core == bsp::cpu::BOOT_CORE_ID as usize {
+        return Err("The boot core is not in the spin-table");
+    }
+
+    let slot = SPIN_TABLE.get(core).ok_or("Core does not exist")?;
+    slot.store(phys_entry_addr.as_usize() as u64, Ordering::Relaxed);
+
+    // The waiting core polls with its caches off.
+    cpu::clean_dcache_range(slot as *const _ as usize, core::mem::size_of::<u64>());
+    cpu::send_event();
+
+    Ok(())
+}
+
+/// Hand a stack to a secondary core, and return the physical address of its entry.
+pub fn prepare_secondary_core(core: usize) -> Result<Address<Physical>, &'static str> {
+    let stack = SECONDARY_STACKS
+        .get(core)
+        .ok_or("Core does not exist")?
+        .0
+        .get() as usize;
+    let phys_stack = memory::mmu::try_kernel_virt_addr_to_phys_addr(Address::new(stack))?;
+
+    let args = &SECONDARY_BOOT_ARGS[core];
+    args.phys_stack_end_exclusive.store(
+        (phys_stack.as_usize() + SECONDARY_STACK_SIZE) as u64,
+        Ordering::Relaxed,
+    );
+    args.virt_stack_end_exclusive
+        .store((stack + SECONDARY_STACK_SIZE) as u64, Ordering::Relaxed);
+
+    // The core reads its arguments with the caches off.
+    cpu::clean_dcache_range(
+        args as *const _ as usize,
+        core::mem::size_of::<SecondaryBootArgs>(),
+    );
+
+    let entry = unsafe { _start_secondary.get() as usize };
+    memory::mmu::try_kernel_virt_addr_to_phys_addr(Address::new(entry))
+}
@@ -30,9 +30,50 @@
 	movk	\register, #:abs_g0_nc:\symbol
 .endm
 
+// Leave EL3 for EL2, if the core executes in EL3.
+//
+// Some firmware configurations, and other boards, start the kernel in EL3. Program the secure
+// configuration so that EL2 is enabled and the lower exception levels execute non-secure and in
+// AArch64, then drop to EL2h with all interrupts masked. Execution continues after the macro.
+//
+// Clobbers x0.
+.macro DROP_FROM_EL3_TO_EL2
+	mrs	x0, CurrentEL
+	cmp	x0, _EL3
+	b.ne	1f
+
+	mov	x0, _SCR_EL3
+	msr	SCR_EL3, x0
+
+	// Do not trap FP/SIMD and other accesses to EL3.
+	msr	CPTR_EL3, xzr
+
+	// SCTLR_EL2 is UNKNOWN after reset. Start EL2 with the MMU and caches off.
+	mov	x0, #(_SCTLR_EL2_RES1 & 0xFFFF)
+	movk	x0, #(_SCTLR_EL2_RES1 >> 16), lsl #16
+	msr	SCTLR_EL2, x0
+
+	mov	x0, _SPSR_EL2H_MASKED
+	msr	SPSR_EL3, x0
+	adr	x0, 1f
+	msr	ELR_EL3, x0
+	eret
+1:
+.endm
+
 .equ _EL2, 0x8
+.equ _EL3, 0xC
 .equ _core_id_mask, 0b11
 
+// SCR_EL3: RW (EL2 is AArch64), HCE (HVC enabled), SMD (SMC disabled), RES1 bits 5 and 4, NS.
+.equ _SCR_EL3, 0x5B1
+
+// SCTLR_EL2: Only the RES1 bits set.
+.equ _SCTLR_EL2_RES1, 0x30C50830
+
+// SPSR_EL3: D, A, I and F masked, EL2h.
+.equ _SPSR_EL2H_MASKED, 0x3C9
+
 //--------------------------------------------------------------------------------------------------
 // Public Code
 //--------------------------------------------------------------------------------------------------
@@ -42,29 +83,54 @@
 // fn _start()
 //------------------------------------------------------------------------------
 _start:
-	// Only proceed if the core executes in EL2. Park it otherwise.
+	// The firmware passes the physical address of the device tree blob in x0. Keep it in a
+	// callee-saved register until it is handed to Rust code.
+	mov	x19, x0
+
+	// Remember the exception level that the core was started in, for diagnostics.
+	mrs	x20, CurrentEL
+
+	DROP_FROM_EL3_TO_EL2
+
+	// Only proceed if the core executes in EL2. Park it otherwise, e.g. if it was started in EL1.
 	mrs	x0, CurrentEL
 	cmp	x0, _EL2
 	b.ne	.L_parking_loop
 
-	// Only proceed on the boot core. Park it otherwise.
+	// Only proceed on the boot core. Let the others wait in the spin-table otherwise.
 	mrs	x1, MPIDR_EL1
 	and	x1, x1, _core_id_mask
 	ldr	x2, BOOT_CORE_ID      // provided by bsp/__board_name__/cpu.rs
 	cmp	x1, x2
-	b.ne	.L_parking_loop
+	b.ne	.L_spin_table_loop
 
 	// If execution reaches here, it is the boot core.
 
 	// Initialize DRAM.
+	//
+	// The MMU is still off, so data accesses are Device-nGnRnE. This rules out `dc zva`, which
+	// faults on Device memory. Zero 64 bytes per iteration instead, and the remaining 16 byte
+	// pairs at the end. Buffers that do not need zeroing are linked to .bss.noinit, which lies
+	// before __bss_start.
 	ADR_REL	x0, __bss_start
 	ADR_REL x1, __bss_end_exclusive
+	sub	x2, x1, #64
 
 .L_bss_init_loop:
+	cmp	x0, x2
+	b.hi	.L_bss_init_tail
+	stp	xzr, xzr, [x0]
+	stp	xzr, xzr, [x0, #16]
+	stp	xzr, xzr, [x0, #32]
+	stp	xzr, xzr, [x0, #48]
+	add	x0, x0, #64
+	b	.L_bss_init_loop
+
+.L_bss_init_tail:
 	cmp	x0, x1
 	b.eq	.L_prepare_rust
 	stp	xzr, xzr, [x0], #16
-	b	.L_bss_init_loop
+	b	.L_bss_init_tail
 
 	// Prepare the jump to Rust code.
 .L_prepare_rust:
@@ -76,6 +142,12 @@ _start:
 	ADR_ABS	x1, __boot_core_stack_end_exclusive
 	ADR_ABS	x2, kernel_init
 
+	// The device tree blob's physical address.
+	mov	x3, x19
+
+	// The exception level that the core was started in.
+	mov	x4, x20
+
 	// Load the PC-relative address of the stack and set the stack pointer.
 	//
 	// Since _start() is the first function that runs after the firmware has loaded the kernel
@@ -84,12 +156,22 @@ _start:
 	// Setting the stack pointer to this value ensures that anything that still runs in EL2,
 	// until the kernel returns to EL1 with the MMU enabled, works as well. After the return to
 	// EL1, the virtual address of the stack retrieved above will be used.
-	ADR_REL	x4, __boot_core_stack_end_exclusive
-	mov	sp, x4
+	ADR_REL	x5, __boot_core_stack_end_exclusive
+	mov	sp, x5
 
-	// Jump to Rust code. x0, x1 and x2 hold the function arguments provided to _start_rust().
+	// Jump to Rust code. x0 to x4 hold the function arguments provided to _start_rust().
 	b	_start_rust
 
+	// Wait until the boot core writes an entry address into the core's slot of the spin-table, and
+	// jump to it. x1 holds the core id.
+.L_spin_table_loop:
+	ADR_REL	x2, SPIN_TABLE        // provided by _arch/aarch64/cpu/boot.rs
+	add	x2, x2, x1, lsl #3
+1:	wfe
+	ldr	x3, [x2]
+	cbz	x3, 1b
+	br	x3
+
 	// Infinitely wait for events (aka "park the core").
 .L_parking_loop:
 	wfe
@@ -98,3 +180,37 @@ _start:
 .size	_start, . - _start
 .type	_start, function
 .global	_start
+
+//------------------------------------------------------------------------------
+// fn _start_secondary()
+//------------------------------------------------------------------------------
+// The firmware releases the secondary cores to this function. Like _start(), it executes from the
+// physical address, with the MMU off.
+_start_secondary:
+	DROP_FROM_EL3_TO_EL2
+
+	// Only proceed if the core executes in EL2. Park it otherwise.
+	mrs	x0, CurrentEL
+	cmp	x0, _EL2
+	b.ne	.L_parking_loop
+
+	// Each core has an entry of two stack end addresses in SECONDARY_BOOT_ARGS: The physical one,
+	// used until the return to EL1, and the virtual one.
+	mrs	x1, MPIDR_EL1
+	and	x1, x1, _core_id_mask
+	ADR_REL	x2, SECONDARY_BOOT_ARGS       // provided by _arch/aarch64/cpu/boot.rs
+	add	x2, x2, x1, lsl #4
+	ldp	x3, x1, [x2]
+	mov	sp, x3
+
+	// Prepare the jump to Rust code. x1 holds the virtual stack end already.
+	ldr	x0, PHYS_KERNEL_TABLES_BASE_ADDR // provided by bsp/__board_name__/memory/mmu.rs
+	ADR_ABS	x2, kernel_init_secondary
+
+	// Jump to Rust code. x0 to x2 hold the function arguments provided to
+	// _start_rust_secondary().
+	b	_start_rust_secondary
+
+.size	_start_secondary, . - _start_secondary
+.type	_start_secondary, function
+.global	_start_secondary
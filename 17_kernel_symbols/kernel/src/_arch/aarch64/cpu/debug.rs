@@ -0,0 +1,382 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Architectural hardware breakpoints and watchpoints.
+//!
+//! # Orientation
+//!
+//! Since arch modules are imported into generic modules using the path attribute, the path of this
+//! file is:
+//!
+//! crate::cpu::debug::arch_debug
+
+use super::WatchKind;
+use crate::memory::{Address, Virtual};
+use core::{
+    arch::asm,
+    sync::atomic::{AtomicU16, AtomicUsize, Ordering},
+};
+use cortex_a::asm::barrier;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+mod mdscr_bits {
+    /// Software step enable.
+    pub const SS: u64 = 1 << 0;
+
+    /// Local (kernel) debug enable.
+    pub const KDE: u64 = 1 << 13;
+
+    /// Monitor debug events, i.e. breakpoints and watchpoints.
+    pub const MDE: u64 = 1 << 15;
+}
+
+mod control_bits {
+    /// Enable.
+    pub const E: u64 = 1 << 0;
+
+    /// Privileged mode control: Match accesses from EL1 only.
+    pub const PRIV_EL1: u64 = 0b01 << 1;
+
+    /// Breakpoint byte address select: Match any A64 instruction at the address.
+    pub const BCR_BAS_A64: u64 = 0b1111 << 5;
+
+    /// Watchpoint load/store control.
+    pub const WCR_LSC_SHIFT: u64 = 3;
+
+    /// Watchpoint byte address select within the watched doubleword.
+    pub const WCR_BAS_SHIFT: u64 = 5;
+
+    /// Watchpoint address mask, i.e. log2 of the size of the watched region.
+    pub const WCR_MASK_SHIFT: u64 = 24;
+}
+
+/// Generate accessors for one of the banks of numbered debug registers.
+///
+/// The register number is part of the instruction encoding, so there is one `mrs`/`msr` per
+/// register.
+macro_rules! debug_register_bank {
+    ($read:ident, $write:ident, $name:literal, [$($n:literal),*]) => {
+        fn $read(n: usize) -> u64 {
+            let value: u64;
+            match n {
+                $($n => unsafe {
+                    asm!(
+                        concat!("mrs {}, ", $name, stringify!($n), "_EL1"),
+                        out(reg) value,
+                        options(nomem, nostack)
+                    )
+                },)*
+                _ => unreachable!(),
+            }
+
+            value
+        }
+
+        fn $write(n: usize, value: u64) {
+            match n {
+                $($n => unsafe {
+                    asm!(
+                        concat!("msr ", $name, stringify!($n), "_EL1, {}"),
+                        in(reg) value,
+                        options(nomem, nostack)
+                    )
+                },)*
+                _ => unreachable!(),
+            }
+        }
+    };
+}
+
+debug_register_bank!(
+    read_dbgbvr,
+    write_dbgbvr,
+    "DBGBVR",
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]
+);
+debug_register_bank!(
+    read_dbgbcr,
+    write_dbgbcr,
+    "DBGBCR",
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]
+);
+debug_register_bank!(
+    read_dbgwvr,
+    write_dbgwvr,
+    "DBGWVR",
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]
+);
+debug_register_bank!(
+    read_dbgwcr,
+    write_dbgwcr,
+    "DBGWCR",
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]
+);
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+/// Breakpoints and watchpoints that are disabled while stepping over the instruction that hit one.
+static SUSPENDED_BREAKPOINTS: AtomicU16 = AtomicU16::new(0);
+static SUSPENDED_WATCHPOINTS: AtomicU16 = AtomicU16::new(0);
+
+static NUM_HITS: AtomicUsize = AtomicUsize::new(0);
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+#[inline(always)]
+fn id_aa64dfr0() -> u64 {
+    let value: u64;
+    unsafe { asm!("mrs {}, ID_AA64DFR0_EL1", out(reg) value, options(nomem, nostack)) };
+
+    value
+}
+
+#[inline(always)]
+fn read_mdscr() -> u64 {
+    let value: u64;
+    unsafe { asm!("mrs {}, MDSCR_EL1", out(reg) value, options(nomem, nostack)) };
+
+    value
+}
+
+#[inline(always)]
+fn write_mdscr(value: u64) {
+    unsafe {
+        asm!("msr MDSCR_EL1, {}", in(reg) value, options(nomem, nostack));
+        barrier::isb(barrier::SY);
+    }
+}
+
+/// Compute the control register value of a watchpoint, or `None` if the region can't be watched
+/// with a single watchpoint.
+///
+/// Regions of up to eight bytes must be contained in an aligned doubleword. Larger regions must be
+/// naturally aligned and their size a power of two.
+fn watchpoint_control(addr: usize, size: usize, kind: WatchKind) -> Option<(usize, u64)> {
+    use control_bits::*;
+
+    let lsc: u64 = match kind {
+        WatchKind::Load => 0b01,
+        WatchKind::Store => 0b10,
+        WatchKind::LoadStore => 0b11,
+    };
+    let common = E | PRIV_EL1 | (lsc << WCR_LSC_SHIFT);
+
+    if size == 0 {
+        return None;
+    }
+
+    let offset = addr % 8;
+    if offset + size <= 8 {
+        let bas = ((1_u64 << size) - 1) << offset;
+
+        return Some((addr - offset, common | (bas << WCR_BAS_SHIFT)));
+    }
+
+    if !size.is_power_of_two() || addr % size != 0 || size > (1 << 31) {
+        return None;
+    }
+
+    let mask = size.trailing_zeros() as u64;
+    Some((
+        addr,
+        common | (0xFF << WCR_BAS_SHIFT) | (mask << WCR_MASK_SHIFT),
+    ))
+}
+
+/// Return the number of bytes that a watchpoint covers, counting from its value register.
+fn watched_span(wcr: u64) -> usize {
+    let mask = (wcr >> control_bits::WCR_MASK_SHIFT) & 0b1_1111;
+    if mask != 0 {
+        return 1 << mask;
+    }
+
+    8
+}
+
+fn check_slot(slot: usize, num_slots: usize) -> Result<(), &'static str> {
+    if slot >= num_slots {
+        return Err("Slot not implemented");
+    }
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Return the number of implemented hardware breakpoints.
+pub fn num_breakpoints() -> usize {
+    ((id_aa64dfr0() >> 12) & 0xF) as usize + 1
+}
+
+/// Return the number of implemented hardware watchpoints.
+pub fn num_watchpoints() -> usize {
+    ((id_aa64dfr0() >> 20) & 0xF) as usize + 1
+}
+
+/// Enable breakpoint and watchpoint exceptions for kernel code on the executing core.
+///
+/// # Safety
+///
+/// - Changes the HW state of the executing core.
+/// - Exception handling must be initialized already.
+pub unsafe fn init() {
+    for i in 0..num_breakpoints() {
+        write_dbgbcr(i, 0);
+    }
+    for i in 0..num_watchpoints() {
+        write_dbgwcr(i, 0);
+    }
+
+    // Clear the OS lock, which is set on cold reset and blocks debug exceptions.
+    asm!("msr OSLAR_EL1, {}", in(reg) 0_u64, options(nomem, nostack));
+
+    write_mdscr((read_mdscr() | mdscr_bits::MDE | mdscr_bits::KDE) & !mdscr_bits::SS);
+
+    // Unmask debug exceptions.
+    asm!("msr DAIFClr, #8", options(nomem, nostack, preserves_flags));
+}
+
+/// Trap execution of the instruction at `addr`.
+pub fn set_breakpoint(slot: usize, addr: Address<Virtual>) -> Result<(), &'static str> {
+    use control_bits::*;
+
+    check_slot(slot, num_breakpoints())?;
+
+    if addr.as_usize() % 4 != 0 {
+        return Err("Instruction address not aligned");
+    }
+
+    write_dbgbcr(slot, 0);
+    write_dbgbvr(slot, addr.as_usize() as u64);
+    write_dbgbcr(slot, E | PRIV_EL1 | BCR_BAS_A64);
+    unsafe { barrier::isb(barrier::SY) };
+
+    Ok(())
+}
+
+/// Remove a breakpoint.
+pub fn clear_breakpoint(slot: usize) -> Result<(), &'static str> {
+    check_slot(slot, num_breakpoints())?;
+
+    write_dbgbcr(slot, 0);
+    unsafe { barrier::isb(barrier::SY) };
+
+    Ok(())
+}
+
+/// Trap accesses of the given kind to the region of `size` bytes at `addr`.
+pub fn set_watchpoint(
+    slot: usize,
+    addr: Address<Virtual>,
+    size: usize,
+    kind: WatchKind,
+) -> Result<(), &'static str> {
+    check_slot(slot, num_watchpoints())?;
+
+    let (value, control) = watchpoint_control(addr.as_usize(), size, kind)
+        .ok_or("Region can not be covered by a single watchpoint")?;
+
+    write_dbgwcr(slot, 0);
+    write_dbgwvr(slot, value as u64);
+    write_dbgwcr(slot, control);
+    unsafe { barrier::isb(barrier::SY) };
+
+    Ok(())
+}
+
+/// Remove a watchpoint.
+pub fn clear_watchpoint(slot: usize) -> Result<(), &'static str> {
+    check_slot(slot, num_watchpoints())?;
+
+    write_dbgwcr(slot, 0);
+    unsafe { barrier::isb(barrier::SY) };
+
+    Ok(())
+}
+
+/// Return the slot of the enabled breakpoint matching the instruction at `addr`.
+pub fn breakpoint_slot(addr: Address<Virtual>) -> Option<usize> {
+    (0..num_breakpoints()).find(|&i| {
+        (read_dbgbcr(i) & control_bits::E != 0) && read_dbgbvr(i) as usize == addr.as_usize()
+    })
+}
+
+/// Return the slot of the enabled watchpoint covering the data address `addr`.
+pub fn watchpoint_slot(addr: Address<Virtual>) -> Option<usize> {
+    (0..num_watchpoints()).find(|&i| {
+        let wcr = read_dbgwcr(i);
+        let start = read_dbgwvr(i) as usize;
+
+        (wcr & control_bits::E != 0)
+            && (start..start.saturating_add(watched_span(wcr))).contains(&addr.as_usize())
+    })
+}
+
+/// Disable all breakpoints and watchpoints and enable software step.
+///
+/// Used by the exception handler to execute the instruction that caused a hit without triggering
+/// it again. The handler must also set `PSTATE.SS` for the return.
+pub fn suspend_for_step() {
+    let mut breakpoints = 0;
+    for i in 0..num_breakpoints() {
+        let bcr = read_dbgbcr(i);
+        if bcr & control_bits::E != 0 {
+            write_dbgbcr(i, bcr & !control_bits::E);
+            breakpoints |= 1 << i;
+        }
+    }
+
+    let mut watchpoints = 0;
+    for i in 0..num_watchpoints() {
+        let wcr = read_dbgwcr(i);
+        if wcr & control_bits::E != 0 {
+            write_dbgwcr(i, wcr & !control_bits::E);
+            watchpoints |= 1 << i;
+        }
+    }
+
+    SUSPENDED_BREAKPOINTS.fetch_or(breakpoints, Ordering::Relaxed);
+    SUSPENDED_WATCHPOINTS.fetch_or(watchpoints, Ordering::Relaxed);
+    NUM_HITS.fetch_add(1, Ordering::Relaxed);
+
+    write_mdscr(read_mdscr() | mdscr_bits::SS);
+}
+
+/// Undo `suspend_for_step()` after the step completed.
+///
+/// Returns false if no step was in progress.
+pub fn resume_after_step() -> bool {
+    let mdscr = read_mdscr();
+    if mdscr & mdscr_bits::SS == 0 {
+        return false;
+    }
+    write_mdscr(mdscr & !mdscr_bits::SS);
+
+    let breakpoints = SUSPENDED_BREAKPOINTS.swap(0, Ordering::Relaxed);
+    for i in (0..num_breakpoints()).filter(|i| breakpoints & (1 << i) != 0) {
+        write_dbgbcr(i, read_dbgbcr(i) | control_bits::E);
+    }
+
+    let watchpoints = SUSPENDED_WATCHPOINTS.swap(0, Ordering::Relaxed);
+    for i in (0..num_watchpoints()).filter(|i| watchpoints & (1 << i) != 0) {
+        write_dbgwcr(i, read_dbgwcr(i) | control_bits::E);
+    }
+    unsafe { barrier::isb(barrier::SY) };
+
+    true
+}
+
+/// Return the number of breakpoint and watchpoint hits since boot.
+pub fn num_hits() -> usize {
+    NUM_HITS.load(Ordering::Relaxed)
+}
@@ -11,11 +11,19 @@
 //!
 //! crate::exception::arch_exception
 
-use crate::{bsp, exception, memory, symbols};
-use core::{arch::global_asm, cell::UnsafeCell, fmt};
+use crate::{
+    backtrace, bsp, common, cpu, exception, info, kprobes, memory, scheduler, symbols, task, warn,
+};
+use core::{
+    arch::global_asm,
+    cell::UnsafeCell,
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+};
 use cortex_a::{asm::barrier, registers::*};
 use tock_registers::{
-    interfaces::{Readable, Writeable},
+    interfaces::{ReadWriteable, Readable, Writeable},
+    register_bitfields,
     registers::InMemoryRegister,
 };
 
@@ -26,6 +34,68 @@ global_asm!(include_str!("exception.s"));
 // Private Definitions
 //--------------------------------------------------------------------------------------------------
 
+register_bitfields! {
+    u64,
+
+    /// Instruction Specific Syndrome of instruction and data aborts.
+    ///
+    /// The access details are only valid for data aborts.
+    ISS_ABORT [
+        /// Instruction Syndrome Valid. The access details below are valid.
+        ISV OFFSET(24) NUMBITS(1) [],
+
+        /// Syndrome Access Size.
+        SAS OFFSET(22) NUMBITS(2) [
+            Byte = 0b00,
+            Halfword = 0b01,
+            Word = 0b10,
+            Doubleword = 0b11
+        ],
+
+        /// Syndrome Register Transfer. The register number of the access.
+        SRT OFFSET(16) NUMBITS(5) [],
+
+        /// FAR not Valid.
+        FnV OFFSET(10) NUMBITS(1) [],
+
+        /// External Abort.
+        EA OFFSET(9) NUMBITS(1) [],
+
+        /// Stage 2 fault during a stage 1 translation table walk.
+        S1PTW OFFSET(7) NUMBITS(1) [],
+
+        /// Write not Read.
+        WnR OFFSET(6) NUMBITS(1) [],
+
+        /// Data or Instruction Fault Status Code.
+        FSC OFFSET(0) NUMBITS(6) []
+    ],
+
+    /// Instruction Specific Syndrome of trapped floating-point exceptions.
+    ISS_FP [
+        /// Trapped Fault Valid. The flags below are valid.
+        TFV OFFSET(23) NUMBITS(1) [],
+
+        /// Input Denormal.
+        IDF OFFSET(7) NUMBITS(1) [],
+
+        /// Inexact.
+        IXF OFFSET(4) NUMBITS(1) [],
+
+        /// Underflow.
+        UFF OFFSET(3) NUMBITS(1) [],
+
+        /// Overflow.
+        OFF OFFSET(2) NUMBITS(1) [],
+
+        /// Divide by Zero.
+        DZF OFFSET(1) NUMBITS(1) [],
+
+        /// Invalid Operation.
+        IOF OFFSET(0) NUMBITS(1) []
+    ]
+}
+
 /// Wrapper structs for memory copies of registers.
 #[repr(transparent)]
 struct SpsrEL1(InMemoryRegister<u64, SPSR_EL1::Register>);
@@ -50,6 +120,70 @@ struct ExceptionContext {
     esr_el1: EsrEL1,
 }
 
+/// State of a user task that was redirected to its notification handler.
+struct InterruptedUserContext {
+    gpr: [u64; 30],
+    lr: u64,
+    elr_el1: u64,
+    spsr_el1: u64,
+    sp_el0: u64,
+}
+
+struct InterruptedUserContextCell(UnsafeCell<InterruptedUserContext>);
+
+/// The memory windows printed for a fatal exception.
+struct MemoryWindows {
+    sp: MemoryWindow,
+    far: Option<MemoryWindow>,
+}
+
+/// Hexdump of the memory around an address of interest.
+struct MemoryWindow {
+    name: &'static str,
+    addr: usize,
+}
+
+/// Number of bytes that a `MemoryWindow` shows before and after its address.
+const MEMORY_WINDOW_RADIUS: usize = 64;
+
+/// Software step bit of SPSR_EL1. Executes a single instruction after the exception return.
+const SPSR_SS: u64 = 1 << 21;
+
+/// Fault status code of an alignment fault.
+const FSC_ALIGNMENT_FAULT: u64 = 0b10_0001;
+
+/// Write not Read bit of the instruction specific syndrome of a watchpoint exception.
+const ISS_WATCHPOINT_WNR: u64 = 1 << 6;
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static INTERRUPTED_USER_CONTEXT: InterruptedUserContextCell =
+    InterruptedUserContextCell(UnsafeCell::new(InterruptedUserContext {
+        gpr: [0; 30],
+        lr: 0,
+        elr_el1: 0,
+        spsr_el1: 0,
+        sp_el0: 0,
+    }));
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_STEP_UNMASK: AtomicBool = AtomicBool::new(false);
+
+/// Whether IRQs must be unmasked again after a step. Each core steps on its own.
+static STEP_UNMASKS_IRQ: cpu::PerCpu<AtomicBool> =
+    cpu::PerCpu::new([NO_STEP_UNMASK; bsp::cpu::NUM_CORES]);
+
+#[cfg(feature = "gdbstub")]
+#[allow(clippy::declare_interior_mutable_const)]
+const NOT_STEPPING: AtomicBool = AtomicBool::new(false);
+
+/// Whether the GDB stub must be entered again after the current step.
+#[cfg(feature = "gdbstub")]
+static GDB_STEPPING: cpu::PerCpu<AtomicBool> =
+    cpu::PerCpu::new([NOT_STEPPING; bsp::cpu::NUM_CORES]);
+
 //--------------------------------------------------------------------------------------------------
 // Private Code
 //--------------------------------------------------------------------------------------------------
@@ -58,8 +192,23 @@ struct ExceptionContext {
 fn default_exception_handler(exc: &ExceptionContext) {
     panic!(
         "CPU Exception!\n\n\
+        {}\n\n\
+        {}",
+        exc,
+        exc.memory_windows()
+    );
+}
+
+/// Like `default_exception_handler()`, but also prints a backtrace of the interrupted kernel code.
+fn kernel_exception_handler(exc: &ExceptionContext) {
+    panic!(
+        "CPU Exception!\n\n\
+        {}\n\n\
+        {}\n\n\
         {}",
-        exc
+        exc,
+        exc.memory_windows(),
+        exc.backtrace()
     );
 }
 
@@ -88,26 +237,64 @@ unsafe extern "C" fn current_el0_serror(_e: &mut ExceptionContext) {
 
 #[no_mangle]
 unsafe extern "C" fn current_elx_synchronous(e: &mut ExceptionContext) {
-    #[cfg(feature = "test_build")]
-    {
-        const TEST_SVC_ID: u64 = 0x1337;
+    use ESR_EL1::EC::Value::*;
+
+    e.record_stats();
 
-        if let Some(ESR_EL1::EC::Value::SVC64) = e.esr_el1.exception_class() {
-            if e.esr_el1.iss() == TEST_SVC_ID {
-                return;
+    match e.exception_class() {
+        Some(SVC64) => {
+            e.handle_syscall();
+            return;
+        }
+        Some(BreakpointCurrentEL) | Some(WatchpointCurrentEL) => {
+            e.handle_debug_hit();
+            return;
+        }
+        Some(SoftwareStepCurrentEL) if cpu::debug::resume_after_step() => {
+            e.finish_debug_step();
+            kprobes::finish_step();
+
+            #[cfg(feature = "gdbstub")]
+            if GDB_STEPPING.get().swap(false, Ordering::Relaxed) {
+                e.enter_gdb();
             }
+            return;
         }
+        Some(Brk64) if e.brk_comment() == kprobes::PROBE_COMMENT => {
+            e.handle_kprobe();
+            return;
+        }
+        #[cfg(feature = "gdbstub")]
+        Some(Brk64) if crate::gdb::is_enabled() => {
+            e.enter_gdb();
+            return;
+        }
+        _ => (),
     }
 
-    default_exception_handler(e);
+    kernel_exception_handler(e);
 }
 
 #[no_mangle]
 unsafe extern "C" fn current_elx_irq(_e: &mut ExceptionContext) {
     use exception::asynchronous::interface::IRQManager;
 
+    exception::asynchronous::record_irq();
+
     let token = &exception::asynchronous::IRQContext::new();
     bsp::exception::asynchronous::irq_manager().handle_pending_irqs(token);
+
+    // The exception context lives on the interrupted thread's stack. Switching threads here means
+    // that the context of the next thread is restored when it returns from its own exception.
+    scheduler::preempt_if_requested();
+}
+
+#[no_mangle]
+unsafe extern "C" fn current_elx_fiq(_e: &mut ExceptionContext) {
+    use exception::asynchronous::interface::IRQManager;
+
+    let token = &exception::asynchronous::IRQContext::new();
+    bsp::exception::asynchronous::irq_manager().handle_pending_fiq(token);
 }
 
 #[no_mangle]
@@ -121,12 +308,44 @@ unsafe extern "C" fn current_elx_serror(e: &mut ExceptionContext) {
 
 #[no_mangle]
 unsafe extern "C" fn lower_aarch64_synchronous(e: &mut ExceptionContext) {
+    e.record_stats();
+
+    if let Some(ESR_EL1::EC::Value::SVC64) = e.exception_class() {
+        let number = e.gpr[8];
+        e.handle_syscall();
+
+        // A finished notification handler continues where the task was interrupted.
+        if number == exception::syscall::number::NOTIFY_RETURN && e.gpr[0] == 0 {
+            e.restore_interrupted_user_context();
+        }
+
+        e.deliver_notification();
+        return;
+    }
+
+    // Faults of a user task, e.g. accesses to kernel memory or undefined instructions, end the task
+    // instead of the kernel.
+    warn!(
+        "User task caused an exception. Terminating it\n\n{}\n\n{}",
+        e,
+        e.memory_windows()
+    );
+    task::exit_current(task::EXIT_CODE_FAULT);
+
     default_exception_handler(e);
 }
 
 #[no_mangle]
 unsafe extern "C" fn lower_aarch64_irq(e: &mut ExceptionContext) {
-    default_exception_handler(e);
+    use exception::asynchronous::interface::IRQManager;
+
+    exception::asynchronous::record_irq();
+
+    let token = &exception::asynchronous::IRQContext::new();
+    bsp::exception::asynchronous::irq_manager().handle_pending_irqs(token);
+
+    scheduler::preempt_if_requested();
+    e.deliver_notification();
 }
 
 #[no_mangle]
@@ -190,16 +409,100 @@ impl fmt::Display for SpsrEL1 {
     }
 }
 
+/// Human readable fault status code of an abort.
+fn fault_status_str(fsc: u64) -> &'static str {
+    match fsc {
+        0b00_0000..=0b00_0011 => "Address size fault",
+        0b00_0100..=0b00_0111 => "Translation fault",
+        0b00_1000..=0b00_1011 => "Access flag fault",
+        0b00_1100..=0b00_1111 => "Permission fault",
+        0b01_0000 => "Synchronous external abort",
+        0b01_0100..=0b01_0111 => "Synchronous external abort on translation table walk",
+        FSC_ALIGNMENT_FAULT => "Alignment fault",
+        0b11_0000 => "TLB conflict abort",
+        _ => "N/A",
+    }
+}
+
 impl EsrEL1 {
     #[inline(always)]
     fn exception_class(&self) -> Option<ESR_EL1::EC::Value> {
         self.0.read_as_enum(ESR_EL1::EC)
     }
 
-    #[cfg(feature = "test_build")]
-    #[inline(always)]
-    fn iss(&self) -> u64 {
-        self.0.read(ESR_EL1::ISS)
+    /// Whether an abort was caused by a misaligned access.
+    fn is_alignment_fault(&self) -> bool {
+        let iss: InMemoryRegister<u64, ISS_ABORT::Register> =
+            InMemoryRegister::new(self.0.read(ESR_EL1::ISS));
+
+        iss.read(ISS_ABORT::FSC) == FSC_ALIGNMENT_FAULT
+    }
+
+    /// Decode the ISS of an instruction or data abort.
+    #[rustfmt::skip]
+    fn fmt_abort_iss(&self, f: &mut fmt::Formatter, is_data_abort: bool) -> fmt::Result {
+        let iss: InMemoryRegister<u64, ISS_ABORT::Register> =
+            InMemoryRegister::new(self.0.read(ESR_EL1::ISS));
+        let fsc = iss.read(ISS_ABORT::FSC);
+
+        write!(f, "\n            Fault Status Code (FSC): {:#04x} - {}", fsc, fault_status_str(fsc))?;
+
+        // The translation table level is encoded in the lower two bits for these faults.
+        if fsc < 0b01_0000 || (0b01_0100..=0b01_0111).contains(&fsc) {
+            write!(f, ", level {}", fsc & 0b11)?;
+        }
+
+        write!(f, "\n            FAR valid: {}", !iss.is_set(ISS_ABORT::FnV))?;
+        write!(f, "\n            External abort: {}", iss.is_set(ISS_ABORT::EA))?;
+
+        if !is_data_abort {
+            return Ok(());
+        }
+
+        let access = if iss.is_set(ISS_ABORT::WnR) { "Write" } else { "Read" };
+        write!(f, "\n            Access: {}", access)?;
+
+        if iss.is_set(ISS_ABORT::ISV) {
+            let size = match iss.read_as_enum(ISS_ABORT::SAS) {
+                Some(ISS_ABORT::SAS::Value::Byte) => "Byte",
+                Some(ISS_ABORT::SAS::Value::Halfword) => "Halfword",
+                Some(ISS_ABORT::SAS::Value::Word) => "Word",
+                _ => "Doubleword",
+            };
+            write!(f, " of {}, register x{}", size, iss.read(ISS_ABORT::SRT))?;
+        }
+
+        if iss.is_set(ISS_ABORT::S1PTW) {
+            write!(f, "\n            Stage 2 fault during a stage 1 translation table walk")?;
+        }
+
+        Ok(())
+    }
+
+    /// Decode the ISS of a trapped floating-point exception.
+    fn fmt_fp_iss(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let iss: InMemoryRegister<u64, ISS_FP::Register> =
+            InMemoryRegister::new(self.0.read(ESR_EL1::ISS));
+
+        if !iss.is_set(ISS_FP::TFV) {
+            return write!(f, "\n            Trapped exception: Unknown");
+        }
+
+        let flags = [
+            (ISS_FP::IOF, "Invalid operation"),
+            (ISS_FP::DZF, "Divide by zero"),
+            (ISS_FP::OFF, "Overflow"),
+            (ISS_FP::UFF, "Underflow"),
+            (ISS_FP::IXF, "Inexact"),
+            (ISS_FP::IDF, "Input denormal"),
+        ];
+
+        write!(f, "\n            Trapped exception:")?;
+        for (_, name) in flags.iter().filter(|(field, _)| iss.is_set(*field)) {
+            write!(f, " {}", name)?;
+        }
+
+        Ok(())
     }
 }
 
@@ -207,6 +510,8 @@ impl EsrEL1 {
 #[rustfmt::skip]
 impl fmt::Display for EsrEL1 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ESR_EL1::EC::Value::*;
+
         // Raw print of whole register.
         writeln!(f, "ESR_EL1: {:#010x}", self.0.get())?;
 
@@ -215,13 +520,56 @@ impl fmt::Display for EsrEL1 {
 
         // Exception class.
         let ec_translation = match self.exception_class() {
-            Some(ESR_EL1::EC::Value::DataAbortCurrentEL) => "Data Abort, current EL",
+            Some(Unknown) => "Unknown reason, e.g. an undefined instruction",
+            Some(TrappedWFIorWFE) => "Trapped WFI or WFE",
+            Some(TrappedFP) => "Trapped access to SVE, Advanced SIMD or floating-point",
+            Some(IllegalExecutionState) => "Illegal execution state",
+            Some(SVC64) => "Supervisor call",
+            Some(HVC64) => "Hypervisor call",
+            Some(SMC64) => "Secure monitor call",
+            Some(TrappedMsrMrs) => "Trapped MSR, MRS or system instruction",
+            Some(TrappedSve) => "Trapped access to SVE",
+            Some(InstrAbortLowerEL) => "Instruction Abort, lower EL",
+            Some(InstrAbortCurrentEL) => "Instruction Abort, current EL",
+            Some(PCAlignmentFault) => "PC alignment fault",
+            Some(DataAbortLowerEL) => "Data Abort, lower EL",
+            Some(DataAbortCurrentEL) => "Data Abort, current EL",
+            Some(SPAlignmentFault) => "SP alignment fault",
+            Some(TrappedFP64) => "Trapped floating-point exception",
+            Some(SError) => "SError interrupt",
+            Some(BreakpointLowerEL) => "Breakpoint, lower EL",
+            Some(BreakpointCurrentEL) => "Breakpoint, current EL",
+            Some(SoftwareStepLowerEL) => "Software step, lower EL",
+            Some(SoftwareStepCurrentEL) => "Software step, current EL",
+            Some(WatchpointLowerEL) => "Watchpoint, lower EL",
+            Some(WatchpointCurrentEL) => "Watchpoint, current EL",
+            Some(Brk64) => "BRK instruction",
             _ => "N/A",
         };
         writeln!(f, " - {}", ec_translation)?;
 
         // Raw print of instruction specific syndrome.
-        write!(f, "      Instr Specific Syndrome (ISS): {:#x}", self.0.read(ESR_EL1::ISS))
+        write!(f, "      Instr Specific Syndrome (ISS): {:#x}", self.0.read(ESR_EL1::ISS))?;
+
+        // Decoded instruction specific syndrome.
+        match self.exception_class() {
+            Some(InstrAbortLowerEL) | Some(InstrAbortCurrentEL) => self.fmt_abort_iss(f, false),
+            Some(DataAbortLowerEL) | Some(DataAbortCurrentEL) => self.fmt_abort_iss(f, true),
+            Some(TrappedFP64) => self.fmt_fp_iss(f),
+            Some(TrappedFP) | Some(TrappedSve) => {
+                write!(f, "\n            Check the trap controls in CPACR_EL1")
+            }
+            Some(PCAlignmentFault) => {
+                write!(f, "\n            The faulting PC is in FAR_EL1")
+            }
+            Some(SPAlignmentFault) => {
+                write!(f, "\n            Misaligned SP used as a load/store base address")
+            }
+            Some(Brk64) => {
+                write!(f, "\n            Comment: {:#06x}", self.0.read(ESR_EL1::ISS) & 0xFFFF)
+            }
+            _ => Ok(()),
+        }
     }
 }
 
@@ -231,6 +579,222 @@ impl ExceptionContext {
         self.esr_el1.exception_class()
     }
 
+    /// The immediate of a `BRK` instruction.
+    #[inline(always)]
+    fn brk_comment(&self) -> u32 {
+        (self.esr_el1.0.read(ESR_EL1::ISS) & 0xFFFF) as u32
+    }
+
+    /// Count the exception in the exception statistics.
+    fn record_stats(&self) {
+        use exception::SyncExceptionClass;
+        use ESR_EL1::EC::Value::*;
+
+        let class = match self.exception_class() {
+            Some(DataAbortLowerEL) | Some(DataAbortCurrentEL)
+                if self.esr_el1.is_alignment_fault() =>
+            {
+                SyncExceptionClass::AlignmentFault
+            }
+            Some(DataAbortLowerEL) | Some(DataAbortCurrentEL) => SyncExceptionClass::DataAbort,
+            Some(InstrAbortLowerEL) | Some(InstrAbortCurrentEL) => {
+                SyncExceptionClass::InstructionAbort
+            }
+            Some(SVC64) => SyncExceptionClass::SystemCall,
+            Some(PCAlignmentFault) | Some(SPAlignmentFault) => SyncExceptionClass::AlignmentFault,
+            _ => return,
+        };
+
+        exception::record_sync_exception(class);
+    }
+
+    /// Execute the system call requested with `svc` and place the result in `x0`.
+    ///
+    /// ELR_EL1 already points to the instruction following `svc`, so it does not need adjustment.
+    fn handle_syscall(&mut self) {
+        use exception::syscall::Caller;
+
+        let mut args: exception::syscall::Args = [0; exception::syscall::NUM_ARGS];
+        args.copy_from_slice(&self.gpr[0..exception::syscall::NUM_ARGS]);
+
+        let caller = if self.spsr_el1.0.matches_all(SPSR_EL1::M::EL0t) {
+            Caller::User
+        } else {
+            Caller::Kernel
+        };
+
+        self.gpr[0] = exception::syscall::dispatch(caller, self.gpr[8], &args);
+    }
+
+    /// Redirect the interrupted user task to its notification handler, if a notification can be
+    /// delivered.
+    ///
+    /// The handler runs on the task's stack, below the interrupted code's stack pointer.
+    fn deliver_notification(&mut self) {
+        let (handler, notification) = match task::take_deliverable_notification() {
+            None => return,
+            Some(x) => x,
+        };
+
+        let saved = unsafe { &mut *INTERRUPTED_USER_CONTEXT.0.get() };
+        saved.gpr = self.gpr;
+        saved.lr = self.lr;
+        saved.elr_el1 = self.elr_el1;
+        saved.spsr_el1 = self.spsr_el1.0.get();
+        saved.sp_el0 = SP_EL0.get();
+
+        self.gpr[0] = notification as u64;
+        self.elr_el1 = handler.as_usize() as u64;
+    }
+
+    /// Undo `deliver_notification()`.
+    fn restore_interrupted_user_context(&mut self) {
+        let saved = unsafe { &*INTERRUPTED_USER_CONTEXT.0.get() };
+
+        self.gpr = saved.gpr;
+        self.lr = saved.lr;
+        self.elr_el1 = saved.elr_el1;
+        self.spsr_el1.0.set(saved.spsr_el1);
+        SP_EL0.set(saved.sp_el0);
+    }
+
+    /// Report a breakpoint or watchpoint hit and step over the instruction that caused it.
+    fn handle_debug_hit(&mut self) {
+        let elr = memory::Address::new(self.elr_el1 as usize);
+
+        if let Some(ESR_EL1::EC::Value::WatchpointCurrentEL) = self.exception_class() {
+            let far = memory::Address::new(FAR_EL1.get() as usize);
+            let access = if self.esr_el1.0.read(ESR_EL1::ISS) & ISS_WATCHPOINT_WNR != 0 {
+                "Store"
+            } else {
+                "Load"
+            };
+
+            info!("Hardware watchpoint hit: {} at {}", access, far);
+            if let Some(slot) = cpu::debug::watchpoint_slot(far) {
+                info!("      Slot: {}", slot);
+            }
+        } else {
+            info!("Hardware breakpoint hit");
+            if let Some(slot) = cpu::debug::breakpoint_slot(elr) {
+                info!("      Slot: {}", slot);
+            }
+        }
+        info!("{}", self.backtrace());
+
+        self.step();
+    }
+
+    /// Execute a single instruction after the exception return, and then take a software step
+    /// exception.
+    ///
+    /// IRQs stay masked for the step, so that it executes the interrupted instruction and not the
+    /// IRQ vector.
+    fn step(&mut self) {
+        cpu::debug::suspend_for_step();
+
+        STEP_UNMASKS_IRQ
+            .get()
+            .store(!self.spsr_el1.0.is_set(SPSR_EL1::I), Ordering::Relaxed);
+        self.spsr_el1.0.modify(SPSR_EL1::I::Masked);
+        self.spsr_el1.0.set(self.spsr_el1.0.get() | SPSR_SS);
+    }
+
+    /// Run the handler of the kprobe at ELR_EL1, and execute the probed instruction.
+    fn handle_kprobe(&mut self) {
+        let mut x = [0; 31];
+        x[..30].copy_from_slice(&self.gpr);
+        x[30] = self.lr;
+
+        let regs = kprobes::Registers {
+            x,
+            sp: self.interrupted_sp() as u64,
+            pc: self.elr_el1,
+        };
+        let can_step = !self.spsr_el1.0.is_set(SPSR_EL1::D);
+
+        if kprobes::handle_hit(&regs, can_step) == kprobes::Resume::Step {
+            self.step();
+        }
+    }
+
+    /// Stop in the GDB stub, and resume as it requests.
+    #[cfg(feature = "gdbstub")]
+    fn enter_gdb(&mut self) {
+        use crate::gdb;
+
+        let mut x = [0; 31];
+        x[..30].copy_from_slice(&self.gpr);
+        x[30] = self.lr;
+
+        let mut regs = gdb::Registers {
+            x,
+            sp: self.interrupted_sp() as u64,
+            pc: self.elr_el1,
+            cpsr: self.spsr_el1.0.get() as u32,
+        };
+        let resume = gdb::handle_stop(&mut regs);
+
+        self.gpr.copy_from_slice(&regs.x[..30]);
+        self.lr = regs.x[30];
+        self.elr_el1 = regs.pc;
+        self.spsr_el1.0.set(regs.cpsr.into());
+
+        if resume == gdb::Resume::Step {
+            GDB_STEPPING.get().store(true, Ordering::Relaxed);
+            self.step();
+        }
+    }
+
+    /// Continue normally after `step()`.
+    fn finish_debug_step(&mut self) {
+        self.spsr_el1.0.set(self.spsr_el1.0.get() & !SPSR_SS);
+
+        if STEP_UNMASKS_IRQ.get().swap(false, Ordering::Relaxed) {
+            self.spsr_el1.0.modify(SPSR_EL1::I::Unmasked);
+        }
+    }
+
+    /// The stack pointer of the interrupted code.
+    fn interrupted_sp(&self) -> usize {
+        if self.spsr_el1.0.matches_all(SPSR_EL1::M::EL0t) {
+            return SP_EL0.get() as usize;
+        }
+
+        // The context was pushed onto the interrupted code's stack.
+        self as *const Self as usize + core::mem::size_of::<Self>()
+    }
+
+    /// Return the memory windows around the interrupted stack pointer and, if valid, the fault
+    /// address.
+    fn memory_windows(&self) -> MemoryWindows {
+        let far = if self.fault_address_valid() {
+            Some(MemoryWindow {
+                name: "FAR_EL1",
+                addr: FAR_EL1.get() as usize,
+            })
+        } else {
+            None
+        };
+
+        MemoryWindows {
+            sp: MemoryWindow {
+                name: "SP",
+                addr: self.interrupted_sp(),
+            },
+            far,
+        }
+    }
+
+    /// Return the backtrace of the interrupted kernel code.
+    fn backtrace(&self) -> backtrace::Backtrace {
+        backtrace::Backtrace::from_registers(
+            self.elr_el1 as usize,
+            self.lr as usize,
+            self.gpr[29] as usize,
+        )
+    }
+
     #[inline(always)]
     fn fault_address_valid(&self) -> bool {
         use ESR_EL1::EC::Value::*;
@@ -262,12 +826,14 @@ impl fmt::Display for ExceptionContext {
 
         writeln!(f, "{}", self.spsr_el1)?;
         writeln!(f, "ELR_EL1: {:#018x}", self.elr_el1)?;
-        writeln!(
-            f,
-            "      Symbol: {}",
-            symbols::lookup_symbol(memory::Address::new(self.elr_el1 as usize))
-                .unwrap_or("Symbol not found")
-        )?;
+        let elr = memory::Address::new(self.elr_el1 as usize);
+        match symbols::lookup_symbol(elr) {
+            None => writeln!(f, "      Symbol: Symbol not found")?,
+            Some(sym) => writeln!(f, "      Symbol: {}", sym)?,
+        }
+        if let Some(line) = symbols::lookup_line(elr) {
+            writeln!(f, "      Source: {}", line)?;
+        }
         writeln!(f)?;
         writeln!(f, "General purpose register:")?;
 
@@ -284,6 +850,38 @@ impl fmt::Display for ExceptionContext {
     }
 }
 
+// Only a single task runs at a time, on a single core.
+unsafe impl Sync for InterruptedUserContextCell {}
+
+impl fmt::Display for MemoryWindow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Show whole lines around the line of the address.
+        let addr_line = common::align_down(self.addr, 16);
+        let start = addr_line.saturating_sub(MEMORY_WINDOW_RADIUS);
+        let end = addr_line.saturating_add(MEMORY_WINDOW_RADIUS + 16);
+
+        write!(
+            f,
+            "Memory around {} ({:#018x}):\n{}",
+            self.name,
+            self.addr,
+            common::hexdump(start, end - start).mark(self.addr)
+        )
+    }
+}
+
+impl fmt::Display for MemoryWindows {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.sp)?;
+
+        if let Some(far) = &self.far {
+            write!(f, "\n\n{}", far)?;
+        }
+
+        Ok(())
+    }
+}
+
 //--------------------------------------------------------------------------------------------------
 // Public Code
 //--------------------------------------------------------------------------------------------------
@@ -91,7 +91,7 @@ __exception_vector_start:
 .org 0x280
 	CALL_WITH_CONTEXT current_elx_irq
 .org 0x300
-	FIQ_SUSPEND
+	CALL_WITH_CONTEXT current_elx_fiq
 .org 0x380
 	CALL_WITH_CONTEXT current_elx_serror
 
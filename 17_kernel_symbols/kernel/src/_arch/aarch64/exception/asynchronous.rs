@@ -21,6 +21,7 @@ use tock_registers::interfaces::{Readable, Writeable};
 
 mod daif_bits {
     pub const IRQ: u8 = 0b0010;
+    pub const FIQ: u8 = 0b0001;
 }
 
 trait DaifField {
@@ -136,6 +137,41 @@ pub unsafe fn local_irq_restore(saved: u64) {
     DAIF.set(saved);
 }
 
+/// Unmask FIQs on the executing core.
+///
+/// # Safety
+///
+/// - Changes the HW state of the executing core.
+#[inline(always)]
+pub unsafe fn local_fiq_unmask() {
+    #[rustfmt::skip]
+    asm!(
+        "msr DAIFClr, {arg}",
+        arg = const daif_bits::FIQ,
+        options(nomem, nostack, preserves_flags)
+    );
+}
+
+/// Mask IRQs and FIQs on the executing core and return the previously saved interrupt mask bits
+/// (DAIF).
+///
+/// # Safety
+///
+/// - Changes the HW state of the executing core.
+#[inline(always)]
+pub unsafe fn local_irq_fiq_mask_save() -> u64 {
+    let saved = DAIF.get();
+
+    #[rustfmt::skip]
+    asm!(
+        "msr DAIFSet, {arg}",
+        arg = const daif_bits::IRQ | daif_bits::FIQ,
+        options(nomem, nostack, preserves_flags)
+    );
+
+    saved
+}
+
 /// Print the AArch64 exceptions status.
 #[rustfmt::skip]
 pub fn print_state() {
@@ -0,0 +1,95 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Architectural GDB stub support.
+//!
+//! # Orientation
+//!
+//! Since arch modules are imported into generic modules using the path attribute, the path of this
+//! file is:
+//!
+//! crate::gdb::arch_gdb
+
+use core::arch::asm;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The `BRK` immediate of breakpoints inserted by GDB.
+const INSERTED_BRK_IMM: u32 = 0x400;
+
+/// The `BRK` immediate of [`breakpoint()`].
+const COMPILED_BRK_IMM: u32 = 0x401;
+
+const fn brk(imm: u32) -> u32 {
+    0xd420_0000 | (imm << 5)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The registers of the stopped code, in the order of GDB's `org.gnu.gdb.aarch64.core` feature.
+#[allow(missing_docs)]
+pub struct Registers {
+    /// `x0` to `x30`.
+    pub x: [u64; 31],
+    pub sp: u64,
+    pub pc: u64,
+    pub cpsr: u32,
+}
+
+/// The number of registers that GDB knows.
+pub const NUM_REGISTERS: usize = 34;
+
+/// The instruction that GDB breakpoints are replaced with.
+pub const BREAKPOINT_INSTRUCTION: u32 = brk(INSERTED_BRK_IMM);
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl Registers {
+    /// Return the value and the size in bytes of register `n`.
+    pub fn get(&self, n: usize) -> Option<(u64, usize)> {
+        match n {
+            0..=30 => Some((self.x[n], 8)),
+            31 => Some((self.sp, 8)),
+            32 => Some((self.pc, 8)),
+            33 => Some((self.cpsr.into(), 4)),
+            _ => None,
+        }
+    }
+
+    /// Set register `n`. Returns false if it does not exist, or can not be changed.
+    ///
+    /// The stack pointer can not be changed, because the exception context is stored on the stack.
+    pub fn set(&mut self, n: usize, value: u64) -> bool {
+        match n {
+            0..=30 => self.x[n] = value,
+            32 => self.pc = value,
+            33 => self.cpsr = value as u32,
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// Advance the program counter to the next instruction.
+    pub fn skip_instruction(&mut self) {
+        self.pc += core::mem::size_of::<u32>() as u64;
+    }
+}
+
+/// Return whether an instruction is a `BRK`.
+pub fn is_breakpoint_instruction(instruction: u32) -> bool {
+    instruction & 0xffe0_001f == brk(0)
+}
+
+/// Stop in the GDB stub.
+#[inline(always)]
+pub fn breakpoint() {
+    unsafe { asm!("brk #{}", const COMPILED_BRK_IMM, options(nomem, nostack)) };
+}
@@ -0,0 +1,62 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Architectural kprobe support.
+//!
+//! # Orientation
+//!
+//! Since arch modules are imported into generic modules using the path attribute, the path of this
+//! file is:
+//!
+//! crate::kprobes::arch_kprobes
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const fn brk(imm: u32) -> u32 {
+    0xd420_0000 | (imm << 5)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The registers at a probed instruction.
+#[allow(missing_docs)]
+pub struct Registers {
+    /// `x0` to `x30`.
+    pub x: [u64; 31],
+    pub sp: u64,
+    pub pc: u64,
+}
+
+/// The `BRK` immediate of kprobes. The exception handler uses it to tell kprobes apart from other
+/// `BRK` instructions.
+pub const PROBE_COMMENT: u32 = 0x500;
+
+/// The instruction that probed instructions are replaced with.
+pub const PROBE_INSTRUCTION: u32 = brk(PROBE_COMMENT);
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl Registers {
+    /// Return argument `n` of a probed function. Only valid at the function's entry, and for the
+    /// arguments that are passed in registers.
+    pub fn argument(&self, n: usize) -> Option<u64> {
+        (n < 8).then(|| self.x[n])
+    }
+
+    /// Return the address that a probed function returns to. Only valid at the function's entry.
+    pub fn return_address(&self) -> u64 {
+        self.x[30]
+    }
+}
+
+/// Return whether an instruction is a `BRK`.
+pub fn is_brk_instruction(instruction: u32) -> bool {
+    instruction & 0xffe0_001f == brk(0)
+}
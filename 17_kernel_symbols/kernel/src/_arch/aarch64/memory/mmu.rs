@@ -108,6 +108,17 @@ pub fn mmu() -> &'static impl memory::mmu::interface::MMU {
     &MMU
 }
 
+/// Invalidate all EL1 TLB entries of the executing core.
+#[inline(always)]
+pub fn invalidate_local_tlb() {
+    unsafe {
+        barrier::dsb(barrier::ISHST);
+        core::arch::asm!("tlbi vmalle1", options(nostack));
+        barrier::dsb(barrier::NSH);
+        barrier::isb(barrier::SY);
+    }
+}
+
 //------------------------------------------------------------------------------
 // OS Interface Code
 //------------------------------------------------------------------------------
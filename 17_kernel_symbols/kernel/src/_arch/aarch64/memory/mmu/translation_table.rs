@@ -201,17 +201,28 @@ impl convert::From<AttributeFields>
         desc += match attribute_fields.acc_perms {
             AccessPermissions::ReadOnly => STAGE1_PAGE_DESCRIPTOR::AP::RO_EL1,
             AccessPermissions::ReadWrite => STAGE1_PAGE_DESCRIPTOR::AP::RW_EL1,
+            AccessPermissions::UserReadOnly => STAGE1_PAGE_DESCRIPTOR::AP::RO_EL1_EL0,
+            AccessPermissions::UserReadWrite => STAGE1_PAGE_DESCRIPTOR::AP::RW_EL1_EL0,
         };
 
-        // The execute-never attribute is mapped to PXN in AArch64.
-        desc += if attribute_fields.execute_never {
-            STAGE1_PAGE_DESCRIPTOR::PXN::True
+        // The execute-never attribute is mapped to PXN for kernel memory and to UXN for user
+        // memory. The kernel never executes user memory, and user space never executes kernel
+        // memory.
+        if attribute_fields.acc_perms.is_user() {
+            desc += STAGE1_PAGE_DESCRIPTOR::PXN::True;
+            desc += if attribute_fields.execute_never {
+                STAGE1_PAGE_DESCRIPTOR::UXN::True
+            } else {
+                STAGE1_PAGE_DESCRIPTOR::UXN::False
+            };
         } else {
-            STAGE1_PAGE_DESCRIPTOR::PXN::False
-        };
-
-        // Always set unprivileged exectue-never as long as userspace is not implemented yet.
-        desc += STAGE1_PAGE_DESCRIPTOR::UXN::True;
+            desc += if attribute_fields.execute_never {
+                STAGE1_PAGE_DESCRIPTOR::PXN::True
+            } else {
+                STAGE1_PAGE_DESCRIPTOR::PXN::False
+            };
+            desc += STAGE1_PAGE_DESCRIPTOR::UXN::True;
+        }
 
         desc
     }
@@ -233,10 +244,16 @@ impl convert::TryFrom<InMemoryRegister<u64, STAGE1_PAGE_DESCRIPTOR::Register>> f
         let acc_perms = match desc.read_as_enum(STAGE1_PAGE_DESCRIPTOR::AP) {
             Some(STAGE1_PAGE_DESCRIPTOR::AP::Value::RO_EL1) => AccessPermissions::ReadOnly,
             Some(STAGE1_PAGE_DESCRIPTOR::AP::Value::RW_EL1) => AccessPermissions::ReadWrite,
+            Some(STAGE1_PAGE_DESCRIPTOR::AP::Value::RO_EL1_EL0) => AccessPermissions::UserReadOnly,
+            Some(STAGE1_PAGE_DESCRIPTOR::AP::Value::RW_EL1_EL0) => AccessPermissions::UserReadWrite,
             _ => return Err("Unexpected access permission"),
         };
 
-        let execute_never = desc.read(STAGE1_PAGE_DESCRIPTOR::PXN) > 0;
+        let execute_never = if acc_perms.is_user() {
+            desc.read(STAGE1_PAGE_DESCRIPTOR::UXN) > 0
+        } else {
+            desc.read(STAGE1_PAGE_DESCRIPTOR::PXN) > 0
+        };
 
         Ok(AttributeFields {
             mem_attributes,
@@ -345,17 +362,22 @@ impl<const NUM_TABLES: usize, const START_FROM_TOP: bool>
         &self,
         virt_page_addr: PageAddress<Virtual>,
     ) -> Result<(usize, usize), &'static str> {
-        let mut addr = virt_page_addr.into_inner();
+        const OUT_OF_BOUNDS: &str = "Virtual page is out of bounds of translation table";
 
+        let mut addr = virt_page_addr.into_inner().as_usize();
+
+        // Addresses below the start of a table at the top are out of bounds as well.
         if START_FROM_TOP {
-            addr = addr - Self::START_FROM_TOP_OFFSET;
+            addr = addr
+                .checked_sub(Self::START_FROM_TOP_OFFSET.as_usize())
+                .ok_or(OUT_OF_BOUNDS)?;
         }
 
-        let lvl2_index = addr.as_usize() >> Granule512MiB::SHIFT;
-        let lvl3_index = (addr.as_usize() & Granule512MiB::MASK) >> Granule64KiB::SHIFT;
+        let lvl2_index = addr >> Granule512MiB::SHIFT;
+        let lvl3_index = (addr & Granule512MiB::MASK) >> Granule64KiB::SHIFT;
 
         if lvl2_index > (NUM_TABLES - 1) {
-            return Err("Virtual page is out of bounds of translation table");
+            return Err(OUT_OF_BOUNDS);
         }
 
         Ok((lvl2_index, lvl3_index))
@@ -499,8 +521,162 @@ pub type MinSizeTranslationTable = FixedSizeTranslationTable<1, true>;
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::memory::mmu::translation_table::interface::TranslationTable;
     use test_macros::kernel_test;
 
+    /// The number of pages that a single lvl3 table covers.
+    const PAGES_PER_TABLE: usize = Granule512MiB::SIZE >> Granule64KiB::SHIFT;
+
+    /// The number of disjoint slots that the random regions of one table are placed in.
+    const NUM_SLOTS: usize = 16;
+
+    /// A xorshift PRNG. It is seeded with a constant, so that failures are reproducible.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        /// Return a number in `0..bound`.
+        fn below(&mut self, bound: usize) -> usize {
+            (self.next() % bound as u64) as usize
+        }
+
+        fn attributes(&mut self) -> AttributeFields {
+            let mem_attributes = [MemAttributes::CacheableDRAM, MemAttributes::Device];
+            let acc_perms = [
+                AccessPermissions::ReadOnly,
+                AccessPermissions::ReadWrite,
+                AccessPermissions::UserReadOnly,
+                AccessPermissions::UserReadWrite,
+            ];
+
+            AttributeFields {
+                mem_attributes: mem_attributes[self.below(mem_attributes.len())],
+                acc_perms: acc_perms[self.below(acc_perms.len())],
+                execute_never: self.below(2) == 1,
+            }
+        }
+    }
+
+    /// A region and the attributes it was mapped with.
+    #[derive(Copy, Clone)]
+    struct Mapping {
+        virt_region: MemoryRegion<Virtual>,
+        phys_region: MemoryRegion<Physical>,
+        attr: AttributeFields,
+    }
+
+    /// Map a random region into each slot of the table. Slots are disjoint, but regions of
+    /// neighbouring slots can touch.
+    fn map_random_regions<const TOP: bool>(
+        tables: &mut FixedSizeTranslationTable<1, TOP>,
+        table_start: PageAddress<Virtual>,
+        rng: &mut Rng,
+    ) -> [Mapping; NUM_SLOTS] {
+        // The topmost page can not be part of a region, because region ends are exclusive.
+        let slot_pages = (PAGES_PER_TABLE - 1) / NUM_SLOTS;
+        let phys_pages = bsp::memory::phys_addr_space_end_exclusive_addr()
+            .into_inner()
+            .as_usize()
+            >> Granule64KiB::SHIFT;
+
+        let mut mappings = [None; NUM_SLOTS];
+        for (slot, mapping) in mappings.iter_mut().enumerate() {
+            let num_pages = 1 + rng.below(slot_pages);
+            let virt_page = slot * slot_pages + rng.below(slot_pages - num_pages + 1);
+            let phys_page = rng.below(phys_pages - num_pages + 1);
+
+            let virt_start = table_start.checked_offset(virt_page as isize).unwrap();
+            let phys_start = PageAddress::from(phys_page << Granule64KiB::SHIFT);
+            let x = Mapping {
+                virt_region: MemoryRegion::new(
+                    virt_start,
+                    virt_start.checked_offset(num_pages as isize).unwrap(),
+                ),
+                phys_region: MemoryRegion::new(
+                    phys_start,
+                    phys_start.checked_offset(num_pages as isize).unwrap(),
+                ),
+                attr: rng.attributes(),
+            };
+
+            unsafe {
+                assert_eq!(
+                    tables.map_at(&x.virt_region, &x.phys_region, &x.attr),
+                    Ok(())
+                )
+            };
+            *mapping = Some(x);
+        }
+
+        mappings.map(Option::unwrap)
+    }
+
+    /// Check that the table translates exactly the mapped regions, with their attributes.
+    fn check_mappings<const TOP: bool>(
+        tables: &mut FixedSizeTranslationTable<1, TOP>,
+        table_start: PageAddress<Virtual>,
+        mappings: &[Mapping],
+        rng: &mut Rng,
+    ) {
+        for x in mappings {
+            let iter = x.virt_region.into_iter().zip(x.phys_region.into_iter());
+            for (virt_page, phys_page) in iter {
+                assert_eq!(
+                    tables.try_virt_page_addr_to_phys_page_addr(virt_page),
+                    Ok(phys_page)
+                );
+                assert_eq!(tables.try_page_attributes(virt_page), Ok(x.attr));
+
+                let offset = rng.below(Granule64KiB::SIZE);
+                assert_eq!(
+                    tables.try_virt_addr_to_phys_addr(virt_page.into_inner() + offset),
+                    Ok(phys_page.into_inner() + offset)
+                );
+            }
+
+            let empty = PageDescriptor::new_zeroed();
+            let virt_start = x.virt_region.start_page_addr();
+            assert_eq!(
+                tables.set_page_descriptor_from_page_addr(virt_start, &empty),
+                Err("Virtual page is already mapped")
+            );
+        }
+
+        // No page outside of the regions may have been mapped by accident.
+        let num_mapped = (0..PAGES_PER_TABLE)
+            .filter_map(|i| table_start.checked_offset(i as isize))
+            .filter(|&page| tables.try_virt_page_addr_to_phys_page_addr(page).is_ok())
+            .count();
+        let expected: usize = mappings.iter().map(|x| x.virt_region.num_pages()).sum();
+        assert_eq!(num_mapped, expected);
+    }
+
+    /// Map random regions into a fresh table, and check the result.
+    fn check_random_mappings<const TOP: bool>(table_start: PageAddress<Virtual>, rng: &mut Rng) {
+        // This will occupy a lot of space on the stack.
+        let mut tables = FixedSizeTranslationTable::<1, TOP>::new_for_runtime();
+        assert!(tables.init().is_ok());
+
+        let mappings = map_random_regions(&mut tables, table_start, rng);
+        check_mappings(&mut tables, table_start, &mappings, rng);
+
+        // The pages right outside of the table must be rejected.
+        let below = table_start.checked_offset(-1);
+        let above = table_start.checked_offset(PAGES_PER_TABLE as isize);
+        for page in [below, above].into_iter().flatten() {
+            assert_eq!(
+                tables.try_virt_page_addr_to_phys_page_addr(page),
+                Err("Virtual page is out of bounds of translation table")
+            );
+        }
+    }
+
     /// Check if the size of `struct TableDescriptor` is as expected.
     #[kernel_test]
     fn size_of_tabledescriptor_equals_64_bit() {
@@ -518,4 +694,20 @@ mod tests {
             core::mem::size_of::<u64>()
         );
     }
+
+    /// Random regions, sizes and attributes must survive a round trip through the tables, for
+    /// both tables at the top and at the bottom of the address space.
+    #[kernel_test]
+    fn random_mappings_round_trip() {
+        const ROUNDS: usize = 4;
+
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        let top_start = PageAddress::from(MinSizeTranslationTable::START_FROM_TOP_OFFSET);
+        let bottom_start = PageAddress::from(0);
+
+        for _ in 0..ROUNDS {
+            check_random_mappings::<true>(top_start, &mut rng);
+            check_random_mappings::<false>(bottom_start, &mut rng);
+        }
+    }
 }
@@ -0,0 +1,24 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Architectural QEMU exit.
+//!
+//! # Orientation
+//!
+//! Since arch modules are imported into generic modules using the path attribute, the path of this
+//! file is:
+//!
+//! crate::qemu_exit::arch_qemu_exit
+
+use qemu_exit::QEMUExit;
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Make the host QEMU binary execute `exit(code)` with the `SYS_EXIT` semihosting call. QEMU must
+/// run with `-semihosting`.
+pub fn semihosting_exit(code: u32) -> ! {
+    qemu_exit::AArch64::new().exit(code)
+}
@@ -0,0 +1,37 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Architectural semihosting.
+//!
+//! # Orientation
+//!
+//! Since arch modules are imported into generic modules using the path attribute, the path of this
+//! file is:
+//!
+//! crate::semihosting::arch_semihosting
+
+use core::arch::asm;
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Execute the semihosting operation `op` with the parameter block at `param`.
+///
+/// # Safety
+///
+/// - The parameter block must match what the operation expects.
+/// - The executing core halts if no debugger or emulator handles semihosting.
+pub unsafe fn call(op: usize, param: *const usize) -> isize {
+    let result: isize;
+
+    asm!(
+        "hlt #0xf000",
+        inout("x0") op => result,
+        in("x1") param,
+        options(nostack)
+    );
+
+    result
+}
@@ -0,0 +1,105 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Architectural user task support.
+//!
+//! # Orientation
+//!
+//! Since arch modules are imported into generic modules using the path attribute, the path of this
+//! file is:
+//!
+//! crate::task::arch_task
+
+use crate::memory::{mmu::MemoryRegion, Address, Virtual};
+use core::{arch::global_asm, cell::UnsafeCell};
+
+// Assembly counterpart to this file.
+global_asm!(include_str!("task.s"));
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Kernel state that is saved when entering user space, and restored when the task exits.
+#[repr(C)]
+struct KernelContext {
+    /// x19 to x30.
+    callee_saved: [u64; 12],
+    sp: u64,
+    daif: u64,
+}
+
+struct KernelContextCell(UnsafeCell<KernelContext>);
+
+extern "Rust" {
+    static __user_demo_start: UnsafeCell<()>;
+    static __user_demo_entry: UnsafeCell<()>;
+    static __user_demo_end_exclusive: UnsafeCell<()>;
+}
+
+extern "C" {
+    fn __task_enter_user(entry: u64, user_sp: u64, kernel_context: *mut KernelContext) -> u64;
+    fn __task_return_to_kernel(kernel_context: *const KernelContext, exit_code: u64) -> !;
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static KERNEL_CONTEXT: KernelContextCell = KernelContextCell(UnsafeCell::new(KernelContext {
+    callee_saved: [0; 12],
+    sp: 0,
+    daif: 0,
+}));
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+// Only a single task runs at a time, on a single core.
+unsafe impl Sync for KernelContextCell {}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Execute user code at EL0 until it exits, and return its exit code.
+///
+/// # Safety
+///
+/// - `entry` and `user_sp` must point to memory that is mapped for EL0.
+/// - Must not be called again before `return_to_kernel()` was called.
+pub unsafe fn enter_user(entry: Address<Virtual>, user_sp: Address<Virtual>) -> u64 {
+    __task_enter_user(
+        entry.as_usize() as u64,
+        user_sp.as_usize() as u64,
+        KERNEL_CONTEXT.0.get(),
+    )
+}
+
+/// Abandon the exception context of the user task and return from `enter_user()`.
+///
+/// # Safety
+///
+/// - Must only be called from a system call issued by the task that was started with
+///   `enter_user()`, or from the handler of an exception that the task caused.
+pub unsafe fn return_to_kernel(exit_code: u64) -> ! {
+    __task_return_to_kernel(KERNEL_CONTEXT.0.get(), exit_code)
+}
+
+/// The kernel virtual address region of the demo program, and its entry point.
+pub fn demo_program() -> (MemoryRegion<Virtual>, Address<Virtual>) {
+    let (start, entry, end_exclusive) = unsafe {
+        (
+            __user_demo_start.get() as usize,
+            __user_demo_entry.get() as usize,
+            __user_demo_end_exclusive.get() as usize,
+        )
+    };
+
+    (
+        MemoryRegion::new(start.into(), end_exclusive.into()),
+        Address::new(entry),
+    )
+}
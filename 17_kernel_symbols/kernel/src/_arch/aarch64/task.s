@@ -0,0 +1,127 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+.section .text
+
+//------------------------------------------------------------------------------
+// fn __task_enter_user(entry: u64, user_sp: u64, kernel_context: *mut KernelContext) -> u64
+//------------------------------------------------------------------------------
+__task_enter_user:
+	// Save the callee-saved registers, the stack pointer and the interrupt mask bits. They are
+	// restored by `__task_return_to_kernel`, which appears to the caller as if this function
+	// returned.
+	stp	x19, x20, [x2, #16 * 0]
+	stp	x21, x22, [x2, #16 * 1]
+	stp	x23, x24, [x2, #16 * 2]
+	stp	x25, x26, [x2, #16 * 3]
+	stp	x27, x28, [x2, #16 * 4]
+	stp	x29, x30, [x2, #16 * 5]
+	mov	x9,  sp
+	mrs	x10, DAIF
+	stp	x9,  x10, [x2, #16 * 6]
+
+	// Return to EL0 with all interrupts unmasked, using SP_EL0.
+	msr	SP_EL0,   x1
+	msr	ELR_EL1,  x0
+	msr	SPSR_EL1, xzr
+
+	// Do not leak kernel register contents to user space.
+	mov	x0,  xzr
+	mov	x1,  xzr
+	mov	x2,  xzr
+	mov	x3,  xzr
+	mov	x4,  xzr
+	mov	x5,  xzr
+	mov	x6,  xzr
+	mov	x7,  xzr
+	mov	x8,  xzr
+	mov	x9,  xzr
+	mov	x10, xzr
+	mov	x11, xzr
+	mov	x12, xzr
+	mov	x13, xzr
+	mov	x14, xzr
+	mov	x15, xzr
+	mov	x16, xzr
+	mov	x17, xzr
+	mov	x18, xzr
+	mov	x19, xzr
+	mov	x20, xzr
+	mov	x21, xzr
+	mov	x22, xzr
+	mov	x23, xzr
+	mov	x24, xzr
+	mov	x25, xzr
+	mov	x26, xzr
+	mov	x27, xzr
+	mov	x28, xzr
+	mov	x29, xzr
+	mov	x30, xzr
+
+	eret
+
+.size	__task_enter_user, . - __task_enter_user
+.type	__task_enter_user, function
+
+//------------------------------------------------------------------------------
+// fn __task_return_to_kernel(kernel_context: *const KernelContext, exit_code: u64) -> !
+//------------------------------------------------------------------------------
+__task_return_to_kernel:
+	ldp	x19, x20, [x0, #16 * 0]
+	ldp	x21, x22, [x0, #16 * 1]
+	ldp	x23, x24, [x0, #16 * 2]
+	ldp	x25, x26, [x0, #16 * 3]
+	ldp	x27, x28, [x0, #16 * 4]
+	ldp	x29, x30, [x0, #16 * 5]
+	ldp	x9,  x10, [x0, #16 * 6]
+	mov	sp,  x9
+	msr	DAIF, x10
+
+	// The exit code becomes the return value of `__task_enter_user`.
+	mov	x0,  x1
+	ret
+
+.size	__task_return_to_kernel, . - __task_return_to_kernel
+.type	__task_return_to_kernel, function
+
+//------------------------------------------------------------------------------
+// The demo user program
+//------------------------------------------------------------------------------
+
+// The program gets pages of its own, so that mapping it into user space does not expose kernel code.
+.section .text._user_demo, "ax"
+.balign 0x10000
+
+__user_demo_start:
+
+__user_demo_msg:
+	.ascii	"Hello from user space!\n"
+.equ __user_demo_msg_len, . - __user_demo_msg
+
+.balign 4
+__user_demo_entry:
+	// write(msg, len)
+	adr	x0,  __user_demo_msg
+	mov	x1,  #__user_demo_msg_len
+	mov	x8,  #0
+	svc	#0
+
+	// sleep(50 ms)
+	mov	x0,  #50000
+	mov	x8,  #1
+	svc	#0
+
+	// exit(42)
+	mov	x0,  #42
+	mov	x8,  #2
+	svc	#0
+
+	// Not reached.
+1:	b	1b
+
+.balign 0x10000
+__user_demo_end_exclusive:
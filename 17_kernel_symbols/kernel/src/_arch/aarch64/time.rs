@@ -11,8 +11,11 @@
 //!
 //! crate::time::arch_time
 
-use crate::{time, warn};
-use core::time::Duration;
+use crate::time;
+use core::{
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::Duration,
+};
 use cortex_a::{asm::barrier, registers::*};
 use tock_registers::interfaces::{ReadWriteable, Readable, Writeable};
 
@@ -31,6 +34,16 @@ struct GenericTimer;
 
 static TIME_MANAGER: GenericTimer = GenericTimer;
 
+/// The Unix time at uptime zero, in nanoseconds.
+static WALL_CLOCK_OFFSET_NS: AtomicU64 = AtomicU64::new(0);
+static WALL_CLOCK_SET: AtomicBool = AtomicBool::new(false);
+
+/// The counter frequency of the boot core. All cores use it, in case their `CNTFRQ_EL0` differs.
+static BOOT_CORE_FREQUENCY: AtomicU64 = AtomicU64::new(0);
+
+/// The tick period in counter cycles.
+static TICK_PERIOD_CYCLES: AtomicU64 = AtomicU64::new(0);
+
 //--------------------------------------------------------------------------------------------------
 // Private Code
 //--------------------------------------------------------------------------------------------------
@@ -44,78 +57,201 @@ impl GenericTimer {
     }
 }
 
+/// The counter frequency of the boot core, or of the executing core before it was recorded.
+fn counter_frequency() -> u64 {
+    match BOOT_CORE_FREQUENCY.load(Ordering::Relaxed) {
+        0 => CNTFRQ_EL0.get(),
+        x => x,
+    }
+}
+
 //--------------------------------------------------------------------------------------------------
 // Public Code
 //--------------------------------------------------------------------------------------------------
 
+/// Record the counter frequency of the boot core.
+pub fn init() {
+    BOOT_CORE_FREQUENCY.store(CNTFRQ_EL0.get(), Ordering::Relaxed);
+}
+
+/// Check that the counters of the executing core agree with those of the boot core.
+pub fn check_counter_consistency() -> Result<(), &'static str> {
+    let frq = counter_frequency();
+
+    // Conversions use the boot core's frequency already, so this is corrected.
+    if CNTFRQ_EL0.get() != frq {
+        return Err("CNTFRQ_EL0 differs from the boot core, using the boot core's frequency");
+    }
+
+    // The physical counter is shared by all cores. The virtual counter must not be offset against
+    // it, which was ensured by zeroing CNTVOFF_EL2 before leaving EL2.
+    let phys = TIME_MANAGER.read_cntpct();
+    let virt = CNTVCT_EL0.get();
+    if phys.max(virt) - phys.min(virt) > frq / 1000 {
+        return Err("Virtual counter is offset from the physical counter");
+    }
+
+    Ok(())
+}
+
 /// Return a reference to the time manager.
 pub fn time_manager() -> &'static impl time::interface::TimeManager {
     &TIME_MANAGER
 }
 
+/// Return the raw value of the physical counter, which counts at the frequency in `CNTFRQ_EL0`.
+pub fn counter_ticks() -> u64 {
+    TIME_MANAGER.read_cntpct()
+}
+
+/// Convert a number of counter ticks to a duration.
+///
+/// Whole seconds and the remainder are converted separately, so that the result is exact for the
+/// full range of the 64 bit counter.
+pub fn ticks_to_duration(ticks: u64) -> Duration {
+    let frq = counter_frequency();
+    let subsec_nanos = (ticks % frq) as u128 * NS_PER_S as u128 / frq as u128;
+
+    Duration::new(ticks / frq, subsec_nanos as u32)
+}
+
+/// Convert a duration to a number of counter ticks, rounded down.
+///
+/// Returns an error if the result does not fit the 64 bit counter.
+pub fn duration_to_ticks(duration: Duration) -> Result<u64, &'static str> {
+    let ticks = counter_frequency() as u128 * duration.as_nanos() / NS_PER_S as u128;
+
+    ticks
+        .try_into()
+        .map_err(|_| "Duration too long for the counter")
+}
+
+/// Spin for at least `ns` nanoseconds.
+///
+/// Only reads system registers, so it also works before the MMU is on, when atomics and statics
+/// can not be relied on. For the same reason, it uses the executing core's counter frequency.
+#[inline(never)]
+pub fn delay_ns(ns: u64) {
+    let frq = CNTFRQ_EL0.get();
+
+    // Counter ticks per nanosecond, as a 32.32 fixed-point number.
+    let ticks_per_ns = (frq << 32) / NS_PER_S;
+
+    // Round up, and add one tick because the counter might increment right after it was read.
+    let ticks = ((ns as u128 * ticks_per_ns as u128 + u32::MAX as u128) >> 32) as u64 + 1;
+
+    let start = TIME_MANAGER.read_cntpct();
+    while TIME_MANAGER.read_cntpct().wrapping_sub(start) < ticks {
+        core::hint::spin_loop();
+    }
+}
+
+/// Set the tick period of all cores. Takes effect with the next tick of each core.
+pub fn set_tick_period(period: Duration) -> Result<(), &'static str> {
+    let cycles = duration_to_ticks(period)?;
+
+    if cycles == 0 || cycles > u32::MAX.into() {
+        return Err("Tick period not supported");
+    }
+
+    TICK_PERIOD_CYCLES.store(cycles, Ordering::Relaxed);
+
+    Ok(())
+}
+
+/// Return whether the tick period was set.
+pub fn tick_period_set() -> bool {
+    TICK_PERIOD_CYCLES.load(Ordering::Relaxed) != 0
+}
+
+/// Let the virtual timer interrupt the executing core every tick period.
+///
+/// The physical timer is left alone, because `spin_for()` uses it.
+pub fn start_tick() {
+    CNTV_TVAL_EL0.set(TICK_PERIOD_CYCLES.load(Ordering::Relaxed));
+    CNTV_CTL_EL0.write(CNTV_CTL_EL0::ENABLE::SET + CNTV_CTL_EL0::IMASK::CLEAR);
+}
+
+/// Let the next tick of the executing core arrive after `delay`, instead of after one period.
+///
+/// The delay is capped at what the 32 bit `CNTV_TVAL_EL0` can hold, which is more than 30 seconds
+/// for common counter frequencies. [`start_tick()`] goes back to periodic ticks.
+pub fn delay_next_tick(delay: Duration) {
+    let cycles = duration_to_ticks(delay).unwrap_or(u64::MAX);
+
+    CNTV_TVAL_EL0.set(cycles.clamp(1, i32::MAX as u64));
+}
+
+/// Return whether the tick interrupt of the executing core is pending.
+pub fn tick_pending() -> bool {
+    CNTV_CTL_EL0.matches_all(CNTV_CTL_EL0::ISTATUS::SET)
+}
+
+/// Schedule the next tick, which also deasserts the timer interrupt.
+///
+/// The compare value advances by exactly one period, so ticks do not drift due to IRQ latency.
+pub fn rearm_tick() {
+    let next = CNTV_CVAL_EL0.get() + TICK_PERIOD_CYCLES.load(Ordering::Relaxed);
+
+    CNTV_CVAL_EL0.set(next);
+}
+
 //------------------------------------------------------------------------------
 // OS Interface Code
 //------------------------------------------------------------------------------
 
 impl time::interface::TimeManager for GenericTimer {
     fn resolution(&self) -> Duration {
-        Duration::from_nanos(NS_PER_S / (CNTFRQ_EL0.get() as u64))
+        Duration::from_nanos(NS_PER_S / counter_frequency())
     }
 
     fn uptime(&self) -> Duration {
-        let current_count: u64 = self.read_cntpct() * NS_PER_S;
-        let frq: u64 = CNTFRQ_EL0.get() as u64;
-
-        Duration::from_nanos(current_count / frq)
+        ticks_to_duration(self.read_cntpct())
     }
 
-    fn spin_for(&self, duration: Duration) {
+    fn spin_for(&self, duration: Duration) -> Result<(), &'static str> {
         // Instantly return on zero.
-        if duration.as_nanos() == 0 {
-            return;
+        if duration.is_zero() {
+            return Ok(());
         }
 
-        // Calculate the register compare value.
-        let frq = CNTFRQ_EL0.get();
-        let x = match frq.checked_mul(duration.as_nanos() as u64) {
-            #[allow(unused_imports)]
-            None => {
-                warn!("Spin duration too long, skipping");
-                return;
-            }
-            Some(val) => val,
-        };
-        let tval = x / NS_PER_S;
-
-        // Check if it is within supported bounds.
-        let warn: Option<&str> = if tval == 0 {
-            Some("smaller")
-        // The upper 32 bits of CNTP_TVAL_EL0 are reserved.
-        } else if tval > u32::max_value().into() {
-            Some("bigger")
-        } else {
-            None
-        };
-
-        #[allow(unused_imports)]
-        if let Some(w) = warn {
-            warn!(
-                "Spin duration {} than architecturally supported, skipping",
-                w
-            );
-            return;
+        let ticks = duration_to_ticks(duration)?;
+        if ticks == 0 {
+            return Err("Spin duration smaller than the timer resolution");
         }
 
-        // Set the compare value register.
-        CNTP_TVAL_EL0.set(tval);
+        // Use the 64 bit compare value instead of the 32 bit TVAL, so that long spins do not wrap.
+        let compare_value = self
+            .read_cntpct()
+            .checked_add(ticks)
+            .ok_or("Spin duration too long for the counter")?;
+        CNTP_CVAL_EL0.set(compare_value);
 
         // Kick off the counting.                       // Disable timer interrupt.
         CNTP_CTL_EL0.modify(CNTP_CTL_EL0::ENABLE::SET + CNTP_CTL_EL0::IMASK::SET);
 
-        // ISTATUS will be '1' when cval ticks have passed. Busy-check it.
+        // ISTATUS will be '1' when the counter reached the compare value. Busy-check it.
         while !CNTP_CTL_EL0.matches_all(CNTP_CTL_EL0::ISTATUS::SET) {}
 
         // Disable counting again.
         CNTP_CTL_EL0.modify(CNTP_CTL_EL0::ENABLE::CLEAR);
+
+        Ok(())
+    }
+
+    fn set_wall_clock(&self, unix_time: Duration) {
+        let offset = unix_time.saturating_sub(self.uptime());
+
+        WALL_CLOCK_OFFSET_NS.store(offset.as_nanos() as u64, Ordering::Relaxed);
+        WALL_CLOCK_SET.store(true, Ordering::Release);
+    }
+
+    fn wall_clock(&self) -> Option<Duration> {
+        if !WALL_CLOCK_SET.load(Ordering::Acquire) {
+            return None;
+        }
+
+        let offset = Duration::from_nanos(WALL_CLOCK_OFFSET_NS.load(Ordering::Relaxed));
+        Some(offset + self.uptime())
     }
 }
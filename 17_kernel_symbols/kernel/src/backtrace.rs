@@ -0,0 +1,224 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Stack unwinding.
+//!
+//! The kernel is compiled with frame pointers (`-C force-frame-pointers=yes`), so each function
+//! pushes a frame record that links to the record of its caller. Walking this chain yields the
+//! return addresses of all active calls, without any unwind tables.
+//!
+//! The walk must not fault, because it runs in the panic handler and in exception handlers. Each
+//! frame record is therefore checked to be mapped before it is read, and each return address to
+//! point into the kernel's code. The walk stops at the first record that fails the checks.
+
+#[cfg(target_arch = "aarch64")]
+#[path = "_arch/aarch64/backtrace.rs"]
+mod arch_backtrace;
+
+use crate::{bsp, common, memory, symbols};
+use arch_backtrace::FrameRecord;
+use core::fmt;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Stop walking the stack after this many frames, e.g. in case of a corrupted frame record chain.
+const MAX_FRAMES: usize = 32;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Iterator over the return addresses of a frame record chain, innermost first.
+pub struct ReturnAddresses {
+    /// The address of the next frame record. Zero once the walk ended.
+    frame: usize,
+}
+
+/// Human readable backtrace, starting with the interrupted instruction of an exception or the
+/// caller of [`Backtrace::capture()`].
+pub struct Backtrace {
+    /// The interrupted instruction, if any.
+    pc: Option<usize>,
+
+    /// The return address into the innermost caller, if it might not be in a frame record.
+    lr: Option<usize>,
+
+    /// The frame pointer where the walk starts.
+    fp: usize,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// Return a reference to the frame record at `addr`, if it can be safely dereferenced.
+fn frame_record(addr: usize) -> Option<&'static FrameRecord> {
+    if addr == 0 || !common::is_aligned(addr, core::mem::align_of::<FrameRecord>()) {
+        return None;
+    }
+
+    // Frame records are 16 bytes and 8 byte aligned, so they never cross a page boundary.
+    memory::mmu::try_kernel_virt_addr_to_phys_addr(memory::Address::new(addr)).ok()?;
+
+    Some(unsafe { &*(addr as *const FrameRecord) })
+}
+
+/// Print a single backtrace line.
+fn write_line(
+    f: &mut fmt::Formatter,
+    index: usize,
+    addr: usize,
+    lookup_addr: usize,
+) -> fmt::Result {
+    let lookup_addr = memory::Address::new(lookup_addr);
+
+    match symbols::lookup_symbol(lookup_addr) {
+        None => write!(f, "      {: >2}. {:#018x} - Symbol not found", index, addr)?,
+        Some(sym) => write!(f, "      {: >2}. {:#018x} - {}", index, addr, sym)?,
+    }
+    if let Some(line) = symbols::lookup_line(lookup_addr) {
+        write!(f, " at {}", line)?;
+    }
+
+    writeln!(f)
+}
+
+impl Iterator for ReturnAddresses {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let record = frame_record(self.frame)?;
+        let return_addr = record.return_addr;
+
+        if !bsp::memory::is_kernel_code(memory::Address::new(return_addr)) {
+            self.frame = 0;
+            return None;
+        }
+
+        // The stack grows downwards, so the caller's frame record must be at a higher address.
+        self.frame = if record.next > self.frame {
+            record.next
+        } else {
+            0
+        };
+
+        Some(return_addr)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl ReturnAddresses {
+    /// Walk the frame record chain that starts at the frame pointer `fp`.
+    pub fn from_frame_pointer(fp: usize) -> Self {
+        Self { frame: fp }
+    }
+}
+
+impl Backtrace {
+    /// Capture the backtrace of the caller.
+    ///
+    /// Must not be inlined, so that its own frame record can be skipped reliably. The record is
+    /// gone once this function returns, so the return address into the caller is kept separately.
+    #[inline(never)]
+    pub fn capture() -> Self {
+        let (lr, fp) = frame_record(arch_backtrace::frame_pointer())
+            .map_or((0, 0), |record| (record.return_addr, record.next));
+
+        Self {
+            pc: None,
+            lr: Some(lr),
+            fp,
+        }
+    }
+
+    /// Create the backtrace of interrupted code from its program counter, link register and frame
+    /// pointer.
+    ///
+    /// A leaf function might not have pushed a frame record, in which case the link register is the
+    /// only hint to its caller.
+    pub fn from_registers(pc: usize, lr: usize, fp: usize) -> Self {
+        Self {
+            pc: Some(pc),
+            lr: Some(lr),
+            fp,
+        }
+    }
+}
+
+impl fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use arch_backtrace::CALL_INSTRUCTION_SIZE;
+
+        writeln!(f, "Backtrace:")?;
+
+        let mut index = 0;
+        if let Some(pc) = self.pc {
+            write_line(f, index, pc, pc)?;
+            index += 1;
+        }
+
+        // Return addresses point to the instruction after the call. Look up the call itself, which
+        // could be the last instruction of a function.
+        let first_return_addr = frame_record(self.fp).map(|record| record.return_addr);
+        match self.lr {
+            Some(lr) if lr != 0 && first_return_addr != Some(lr) => {
+                write_line(f, index, lr, lr.wrapping_sub(CALL_INSTRUCTION_SIZE))?;
+                index += 1;
+            }
+            _ => (),
+        }
+
+        for return_addr in ReturnAddresses::from_frame_pointer(self.fp) {
+            if index >= MAX_FRAMES {
+                return write!(f, "      ...");
+            }
+
+            write_line(f, index, return_addr, return_addr - CALL_INSTRUCTION_SIZE)?;
+            index += 1;
+        }
+
+        write!(f, "      End of backtrace")
+    }
+}
+
+/// Walk the frame record chain of the caller. The first item is the return address into the
+/// caller's caller.
+///
+/// Must not be inlined, so that its own frame record can be skipped reliably. The record is gone
+/// once this function returns.
+#[inline(never)]
+pub fn return_addresses() -> ReturnAddresses {
+    let fp = arch_backtrace::frame_pointer();
+
+    ReturnAddresses::from_frame_pointer(frame_record(fp).map_or(0, |record| record.next))
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
+
+    #[inline(never)]
+    fn callee() -> Option<usize> {
+        return_addresses().next()
+    }
+
+    /// The first return address must point into the caller of the function that walked the stack.
+    #[kernel_test]
+    fn return_address_points_to_caller() {
+        let return_addr = callee().unwrap();
+        let sym = symbols::lookup_symbol(memory::Address::new(return_addr - 4)).unwrap();
+
+        assert!(sym.name.ends_with("return_address_points_to_caller"));
+    }
+}
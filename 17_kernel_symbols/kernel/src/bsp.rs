@@ -6,8 +6,14 @@
 
 mod device_driver;
 
-#[cfg(any(feature = "bsp_rpi3", feature = "bsp_rpi4"))]
+#[cfg(any(feature = "bsp_rpi3", feature = "bsp_rpi4", feature = "bsp_rpi5"))]
 mod raspberrypi;
 
-#[cfg(any(feature = "bsp_rpi3", feature = "bsp_rpi4"))]
+#[cfg(any(feature = "bsp_rpi3", feature = "bsp_rpi4", feature = "bsp_rpi5"))]
 pub use raspberrypi::*;
+
+#[cfg(feature = "bsp_qemu_virt")]
+mod qemu_virt;
+
+#[cfg(feature = "bsp_qemu_virt")]
+pub use qemu_virt::*;
@@ -4,13 +4,37 @@
 
 //! Device driver.
 
-#[cfg(feature = "bsp_rpi4")]
+#[cfg(any(
+    feature = "bsp_rpi3",
+    feature = "bsp_rpi4",
+    feature = "bsp_rpi5",
+    feature = "bsp_qemu_virt"
+))]
 mod arm;
-#[cfg(any(feature = "bsp_rpi3", feature = "bsp_rpi4"))]
+#[cfg(any(
+    feature = "bsp_rpi3",
+    feature = "bsp_rpi4",
+    feature = "bsp_rpi5",
+    feature = "bsp_qemu_virt"
+))]
 mod bcm;
 mod common;
+#[cfg(any(feature = "bsp_rpi3", feature = "bsp_rpi4", feature = "bsp_rpi5"))]
+mod synopsys;
 
-#[cfg(feature = "bsp_rpi4")]
+#[cfg(any(
+    feature = "bsp_rpi3",
+    feature = "bsp_rpi4",
+    feature = "bsp_rpi5",
+    feature = "bsp_qemu_virt"
+))]
 pub use arm::*;
-#[cfg(any(feature = "bsp_rpi3", feature = "bsp_rpi4"))]
+#[cfg(any(
+    feature = "bsp_rpi3",
+    feature = "bsp_rpi4",
+    feature = "bsp_rpi5",
+    feature = "bsp_qemu_virt"
+))]
 pub use bcm::*;
+#[cfg(any(feature = "bsp_rpi3", feature = "bsp_rpi4", feature = "bsp_rpi5"))]
+pub use synopsys::*;
@@ -88,6 +88,12 @@ use core::sync::atomic::{AtomicBool, Ordering};
 
 type HandlerTable = [Option<exception::asynchronous::IRQDescriptor>; GICv2::NUM_IRQS];
 
+/// Returned instead of an IRQ number if no IRQ is pending.
+const SPURIOUS_IRQ_NUMBER: usize = 1023;
+
+/// Priority assigned to all interrupts during init. Leaves room for more and less urgent ones.
+const DEFAULT_PRIORITY: u8 = 0xA0;
+
 //--------------------------------------------------------------------------------------------------
 // Public Definitions
 //--------------------------------------------------------------------------------------------------
@@ -97,8 +103,8 @@ pub type IRQNumber = exception::asynchronous::IRQNumber<{ GICv2::MAX_IRQ_NUMBER
 
 /// Representation of the GIC.
 pub struct GICv2 {
-    gicd_mmio_descriptor: memory::mmu::MMIODescriptor,
-    gicc_mmio_descriptor: memory::mmu::MMIODescriptor,
+    gicd_mmio_descriptor: InitStateLock<memory::mmu::MMIODescriptor>,
+    gicc_mmio_descriptor: InitStateLock<memory::mmu::MMIODescriptor>,
 
     /// The Distributor.
     gicd: gicd::GICD,
@@ -120,6 +126,7 @@ pub struct GICv2 {
 impl GICv2 {
     const MAX_IRQ_NUMBER: usize = 300; // Normally 1019, but keep it lower to save some space.
     const NUM_IRQS: usize = Self::MAX_IRQ_NUMBER + 1;
+    const MAX_CPU_INTERFACES: usize = 8;
 
     /// Create an instance.
     ///
@@ -131,14 +138,40 @@ impl GICv2 {
         gicc_mmio_descriptor: memory::mmu::MMIODescriptor,
     ) -> Self {
         Self {
-            gicd_mmio_descriptor,
-            gicc_mmio_descriptor,
+            gicd_mmio_descriptor: InitStateLock::new(gicd_mmio_descriptor),
+            gicc_mmio_descriptor: InitStateLock::new(gicc_mmio_descriptor),
             gicd: gicd::GICD::new(gicd_mmio_descriptor.start_addr().as_usize()),
             gicc: gicc::GICC::new(gicc_mmio_descriptor.start_addr().as_usize()),
             is_mmio_remapped: AtomicBool::new(false),
             handler_table: InitStateLock::new([None; Self::NUM_IRQS]),
         }
     }
+
+    /// Use different MMIO descriptors than the ones the instance was created with, e.g. the ones
+    /// that the device tree tells.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide correct MMIO descriptors.
+    /// - Only has an effect if called before `init()`.
+    pub unsafe fn set_mmio_descriptors(
+        &self,
+        gicd_mmio_descriptor: memory::mmu::MMIODescriptor,
+        gicc_mmio_descriptor: memory::mmu::MMIODescriptor,
+    ) {
+        self.gicd_mmio_descriptor
+            .write(|x| *x = gicd_mmio_descriptor);
+        self.gicc_mmio_descriptor
+            .write(|x| *x = gicc_mmio_descriptor);
+    }
+
+    /// Only signal interrupts to the executing core that are more urgent than `mask`.
+    ///
+    /// Lower values mean higher priority. Interrupts whose priority value is equal or greater than
+    /// the mask are held back.
+    pub fn set_priority_mask(&self, mask: u8) {
+        self.gicc.set_priority_mask(mask);
+    }
 }
 
 //------------------------------------------------------------------------------
@@ -155,11 +188,13 @@ impl driver::interface::DeviceDriver for GICv2 {
         let remapped = self.is_mmio_remapped.load(Ordering::Relaxed);
         if !remapped {
             // GICD
-            let mut virt_addr = memory::mmu::kernel_map_mmio("GICD", &self.gicd_mmio_descriptor)?;
+            let descriptor = self.gicd_mmio_descriptor.read(|x| *x);
+            let mut virt_addr = memory::mmu::kernel_map_mmio("GICD", &descriptor)?;
             self.gicd.set_mmio(virt_addr.as_usize());
 
             // GICC
-            virt_addr = memory::mmu::kernel_map_mmio("GICC", &self.gicc_mmio_descriptor)?;
+            let descriptor = self.gicc_mmio_descriptor.read(|x| *x);
+            virt_addr = memory::mmu::kernel_map_mmio("GICC", &descriptor)?;
             self.gicc.set_mmio(virt_addr.as_usize());
 
             // Conclude remapping.
@@ -170,7 +205,9 @@ impl driver::interface::DeviceDriver for GICv2 {
             self.gicd.boot_core_init();
         }
 
+        self.gicd.local_core_init();
         self.gicc.priority_accept_all();
+        self.gicc.set_binary_point(0);
         self.gicc.enable();
 
         Ok(())
@@ -202,13 +239,74 @@ impl exception::asynchronous::interface::IRQManager for GICv2 {
         self.gicd.enable(irq_number);
     }
 
+    fn set_priority(
+        &self,
+        irq_number: Self::IRQNumberType,
+        priority: u8,
+    ) -> Result<(), &'static str> {
+        self.gicd.set_priority(irq_number, priority);
+
+        Ok(())
+    }
+
+    fn set_target_core(
+        &self,
+        irq_number: Self::IRQNumberType,
+        target_core: usize,
+    ) -> Result<(), &'static str> {
+        // The Raspberry Pi's cores are connected to the CPU interfaces with the same number.
+        if target_core >= Self::MAX_CPU_INTERFACES {
+            return Err("Target core has no GIC CPU interface");
+        }
+
+        self.gicd.set_target(irq_number, target_core)
+    }
+
+    /// FIQs are signaled for Group 0 interrupts, which can only be configured from Secure state.
+    /// The Raspberry Pi firmware hands over to the kernel in Non-secure state.
+    fn route_to_fiq(&self, _irq_number: Self::IRQNumberType) -> Result<(), &'static str> {
+        Err("GICv2: FIQs need Secure state access")
+    }
+
+    fn send_ipi(
+        &self,
+        target_core: usize,
+        msg: exception::asynchronous::IPIMessage,
+    ) -> Result<(), &'static str> {
+        // The Raspberry Pi's cores are connected to the CPU interfaces with the same number.
+        if target_core >= Self::MAX_CPU_INTERFACES {
+            return Err("Target core has no GIC CPU interface");
+        }
+
+        self.gicd.send_sgi(msg.get() as u8, target_core);
+
+        Ok(())
+    }
+
+    fn check_pending_irq(&self) -> Option<Result<(), &'static str>> {
+        let irq_number = self.gicc.highest_pending_irq_number();
+
+        if irq_number == SPURIOUS_IRQ_NUMBER {
+            return Some(Ok(()));
+        }
+
+        // Would be dropped as spurious by `handle_pending_irqs()`.
+        if irq_number > GICv2::MAX_IRQ_NUMBER {
+            return Some(Err("Pending IRQ number out of range"));
+        }
+
+        let has_handler = self.handler_table.read(|table| table[irq_number].is_some());
+
+        Some(has_handler.then(|| ()).ok_or("Pending IRQ has no handler"))
+    }
+
     fn handle_pending_irqs<'irq_context>(
         &'irq_context self,
         ic: &exception::asynchronous::IRQContext<'irq_context>,
     ) {
         // Extract the highest priority pending IRQ number from the Interrupt Acknowledge Register
         // (IAR).
-        let irq_number = self.gicc.pending_irq_number(ic);
+        let (irq_number, source_cpu_interface) = self.gicc.pending_irq_number(ic);
 
         // Guard against spurious interrupts.
         if irq_number > GICv2::MAX_IRQ_NUMBER {
@@ -227,15 +325,24 @@ impl exception::asynchronous::interface::IRQManager for GICv2 {
         });
 
         // Signal completion of handling.
-        self.gicc.mark_comleted(irq_number as u32, ic);
+        self.gicc
+            .mark_comleted(irq_number as u32, source_cpu_interface, ic);
     }
 
     fn print_handler(&self) {
         use crate::info;
 
-        info!("      Peripheral handler:");
-
         self.handler_table.read(|table| {
+            info!("      IPI handler:");
+
+            for (i, opt) in table.iter().take(16).enumerate() {
+                if let Some(handler) = opt {
+                    info!("            {: >3}. {}", i, handler.name);
+                }
+            }
+
+            info!("      Peripheral handler:");
+
             for (i, opt) in table.iter().skip(32).enumerate() {
                 if let Some(handler) = opt {
                     info!("            {: >3}. {}", i + 32, handler.name);
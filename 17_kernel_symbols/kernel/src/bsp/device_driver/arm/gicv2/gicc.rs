@@ -10,7 +10,7 @@ use crate::{
 use tock_registers::{
     interfaces::{Readable, Writeable},
     register_bitfields, register_structs,
-    registers::ReadWrite,
+    registers::{ReadOnly, ReadWrite},
 };
 
 //--------------------------------------------------------------------------------------------------
@@ -30,14 +30,27 @@ register_bitfields! {
         Priority OFFSET(0) NUMBITS(8) []
     ],
 
+    /// Binary Point Register
+    BPR [
+        BinaryPoint OFFSET(0) NUMBITS(3) []
+    ],
+
     /// Interrupt Acknowledge Register
     IAR [
+        CPUID OFFSET(10) NUMBITS(3) [],
         InterruptID OFFSET(0) NUMBITS(10) []
     ],
 
     /// End of Interrupt Register
     EOIR [
+        CPUID OFFSET(10) NUMBITS(3) [],
         EOIINTID OFFSET(0) NUMBITS(10) []
+    ],
+
+    /// Highest Priority Pending Interrupt Register
+    HPPIR [
+        CPUID OFFSET(10) NUMBITS(3) [],
+        PENDINTID OFFSET(0) NUMBITS(10) []
     ]
 }
 
@@ -46,10 +59,12 @@ register_structs! {
     pub RegisterBlock {
         (0x000 => CTLR: ReadWrite<u32, CTLR::Register>),
         (0x004 => PMR: ReadWrite<u32, PMR::Register>),
-        (0x008 => _reserved1),
+        (0x008 => BPR: ReadWrite<u32, BPR::Register>),
         (0x00C => IAR: ReadWrite<u32, IAR::Register>),
         (0x010 => EOIR: ReadWrite<u32, EOIR::Register>),
-        (0x014  => @END),
+        (0x014 => _reserved1),
+        (0x018 => HPPIR: ReadOnly<u32, HPPIR::Register>),
+        (0x01C  => @END),
     }
 }
 
@@ -104,6 +119,39 @@ impl GICC {
         });
     }
 
+    /// Only signal interrupts to the executing core whose priority value is lower than `mask`.
+    ///
+    /// # Safety
+    ///
+    /// - GICC MMIO registers are banked per CPU core. It is therefore safe to have `&self` instead
+    ///   of `&mut self`.
+    pub fn set_priority_mask(&self, mask: u8) {
+        self.registers.read(|regs| {
+            regs.PMR.write(PMR::Priority.val(mask as u32));
+        });
+    }
+
+    /// Set the binary point, which splits priority values into the group priority, which decides
+    /// about preemption, and the subpriority.
+    ///
+    /// Quoting the GICv2 Architecture Specification:
+    ///
+    ///   "If the GIC implements fewer than 256 priority levels, the low-order bits of the priority
+    ///    fields are RAZ/WI."
+    ///
+    /// Therefore, a binary point of zero makes all implemented priority bits count for preemption.
+    /// Values below the implemented minimum are raised by the hardware.
+    ///
+    /// # Safety
+    ///
+    /// - GICC MMIO registers are banked per CPU core. It is therefore safe to have `&self` instead
+    ///   of `&mut self`.
+    pub fn set_binary_point(&self, binary_point: u8) {
+        self.registers.read(|regs| {
+            regs.BPR.write(BPR::BinaryPoint.val(binary_point as u32));
+        });
+    }
+
     /// Enable the interface - start accepting IRQs.
     ///
     /// # Safety
@@ -118,6 +166,9 @@ impl GICC {
 
     /// Extract the number of the highest-priority pending IRQ.
     ///
+    /// For SGIs, the number of the CPU interface that requested the interrupt is returned as well.
+    /// It is zero for all other IRQs.
+    ///
     /// Can only be called from IRQ context, which is ensured by taking an `IRQContext` token.
     ///
     /// # Safety
@@ -128,16 +179,32 @@ impl GICC {
     pub fn pending_irq_number<'irq_context>(
         &self,
         _ic: &exception::asynchronous::IRQContext<'irq_context>,
-    ) -> usize {
+    ) -> (usize, u32) {
+        self.registers.read(|regs| {
+            let iar = regs.IAR.extract();
+
+            (iar.read(IAR::InterruptID) as usize, iar.read(IAR::CPUID))
+        })
+    }
+
+    /// Peek at the number of the highest-priority pending IRQ, without acknowledging it.
+    ///
+    /// Returns the spurious interrupt number 1023 if no IRQ is pending.
+    ///
+    /// # Safety
+    ///
+    /// - GICC MMIO registers are banked per CPU core. It is therefore safe to have `&self` instead
+    ///   of `&mut self`.
+    pub fn highest_pending_irq_number(&self) -> usize {
         self.registers
-            .read(|regs| regs.IAR.read(IAR::InterruptID) as usize)
+            .read(|regs| regs.HPPIR.read(HPPIR::PENDINTID) as usize)
     }
 
     /// Complete handling of the currently active IRQ.
     ///
     /// Can only be called from IRQ context, which is ensured by taking an `IRQContext` token.
     ///
-    /// To be called after `pending_irq_number()`.
+    /// To be called after `pending_irq_number()`, with the values it returned.
     ///
     /// # Safety
     ///
@@ -147,10 +214,12 @@ impl GICC {
     pub fn mark_comleted<'irq_context>(
         &self,
         irq_number: u32,
+        source_cpu_interface: u32,
         _ic: &exception::asynchronous::IRQContext<'irq_context>,
     ) {
         self.registers.read(|regs| {
-            regs.EOIR.write(EOIR::EOIINTID.val(irq_number));
+            regs.EOIR
+                .write(EOIR::CPUID.val(source_cpu_interface) + EOIR::EOIINTID.val(irq_number));
         });
     }
 }
@@ -10,12 +10,12 @@
 use crate::{
     bsp::device_driver::common::MMIODerefWrapper,
     state, synchronization,
-    synchronization::{IRQSafeNullLock, InitStateLock},
+    synchronization::{InitStateLock, SpinLock},
 };
 use tock_registers::{
-    interfaces::{Readable, Writeable},
+    interfaces::{ReadWriteable, Readable, Writeable},
     register_bitfields, register_structs,
-    registers::{ReadOnly, ReadWrite},
+    registers::{ReadOnly, ReadWrite, WriteOnly},
 };
 
 //--------------------------------------------------------------------------------------------------
@@ -41,6 +41,17 @@ register_bitfields! {
         Offset2 OFFSET(16) NUMBITS(8) [],
         Offset1 OFFSET(8)  NUMBITS(8) [],
         Offset0 OFFSET(0)  NUMBITS(8) []
+    ],
+
+    /// Software Generated Interrupt Register
+    SGIR [
+        TargetListFilter OFFSET(24) NUMBITS(2) [
+            TargetList = 0b00,
+            AllOtherCores = 0b01,
+            RequestingCore = 0b10
+        ],
+        CPUTargetList OFFSET(16) NUMBITS(8) [],
+        SGIINTID OFFSET(0) NUMBITS(4) []
     ]
 }
 
@@ -51,9 +62,13 @@ register_structs! {
         (0x004 => TYPER: ReadOnly<u32, TYPER::Register>),
         (0x008 => _reserved1),
         (0x104 => ISENABLER: [ReadWrite<u32>; 31]),
-        (0x108 => _reserved2),
-        (0x820 => ITARGETSR: [ReadWrite<u32, ITARGETSR::Register>; 248]),
-        (0x824 => @END),
+        (0x180 => _reserved2),
+        (0x420 => IPRIORITYR: [ReadWrite<u32>; 247]),
+        (0x7FC => _reserved3),
+        (0x820 => ITARGETSR: [ReadWrite<u32, ITARGETSR::Register>; 247]),
+        (0xBFC => _reserved4),
+        (0xF00 => SGIR: WriteOnly<u32, SGIR::Register>),
+        (0xF04 => @END),
     }
 }
 
@@ -63,8 +78,10 @@ register_structs! {
         (0x000 => _reserved1),
         (0x100 => ISENABLER: ReadWrite<u32>),
         (0x104 => _reserved2),
+        (0x400 => IPRIORITYR: [ReadWrite<u32>; 8]),
+        (0x420 => _reserved3),
         (0x800 => ITARGETSR: [ReadOnly<u32, ITARGETSR::Register>; 8]),
-        (0x804 => @END),
+        (0x820 => @END),
     }
 }
 
@@ -81,7 +98,7 @@ type BankedRegisters = MMIODerefWrapper<BankedRegisterBlock>;
 /// Representation of the GIC Distributor.
 pub struct GICD {
     /// Access to shared registers is guarded with a lock.
-    shared_registers: IRQSafeNullLock<SharedRegisters>,
+    shared_registers: SpinLock<SharedRegisters>,
 
     /// Access to banked registers is unguarded.
     banked_registers: InitStateLock<BankedRegisters>,
@@ -116,6 +133,28 @@ impl SharedRegisters {
         // Rust automatically inserts slice range sanity check, i.e. max >= min.
         &self.ITARGETSR[0..spi_itargetsr_max_index]
     }
+
+    /// Return a slice of the implemented IPRIORITYR.
+    ///
+    /// Like ITARGETSR, each register holds the byte-sized fields of four IRQs.
+    #[inline(always)]
+    fn implemented_ipriority_slice(&mut self) -> &[ReadWrite<u32>] {
+        let spi_ipriorityr_count = (self.num_irqs() - 32) >> 2;
+
+        &self.IPRIORITYR[0..spi_ipriorityr_count]
+    }
+}
+
+/// Replace the byte-sized priority field of `irq_num` in its IPRIORITYR.
+fn update_priority_field(reg: &ReadWrite<u32>, irq_num: usize, priority: u8) {
+    let shift = (irq_num % 4) * 8;
+
+    reg.set((reg.get() & !(0xFF << shift)) | ((priority as u32) << shift));
+}
+
+/// Value for all four byte-sized fields of an IPRIORITYR.
+const fn priority_for_all_fields(priority: u8) -> u32 {
+    (priority as u32) * 0x0101_0101
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -132,7 +171,7 @@ impl GICD {
     /// - The user must ensure to provide a correct MMIO start address.
     pub const unsafe fn new(mmio_start_addr: usize) -> Self {
         Self {
-            shared_registers: IRQSafeNullLock::new(SharedRegisters::new(mmio_start_addr)),
+            shared_registers: SpinLock::new(SharedRegisters::new(mmio_start_addr)),
             banked_registers: InitStateLock::new(BankedRegisters::new(mmio_start_addr)),
         }
     }
@@ -155,7 +194,19 @@ impl GICD {
             .read(|regs| regs.ITARGETSR[0].read(ITARGETSR::Offset0))
     }
 
-    /// Route all SPIs to the boot core and enable the distributor.
+    /// Set the default priority for the executing core's private interrupts.
+    pub fn local_core_init(&self) {
+        let value = priority_for_all_fields(super::DEFAULT_PRIORITY);
+
+        self.banked_registers.read(|regs| {
+            for i in regs.IPRIORITYR.iter() {
+                i.set(value);
+            }
+        });
+    }
+
+    /// Route all SPIs to the boot core, set them to the default priority and enable the
+    /// distributor.
     pub fn boot_core_init(&self) {
         assert!(
             state::state_manager().is_init(),
@@ -175,6 +226,11 @@ impl GICD {
                 );
             }
 
+            let priority = priority_for_all_fields(super::DEFAULT_PRIORITY);
+            for i in regs.implemented_ipriority_slice().iter() {
+                i.set(priority);
+            }
+
             regs.CTLR.write(CTLR::Enable::SET);
         });
     }
@@ -206,4 +262,73 @@ impl GICD {
             }
         }
     }
+
+    /// Set the priority of an interrupt.
+    pub fn set_priority(&self, irq_num: super::IRQNumber, priority: u8) {
+        let irq_num = irq_num.get();
+
+        // Each u32 priority register holds the fields of four IRQs.
+        let priority_reg_index = irq_num >> 2;
+
+        // Check if we are handling a private or shared IRQ.
+        match irq_num {
+            // Private.
+            0..=31 => self.banked_registers.read(|regs| {
+                update_priority_field(&regs.IPRIORITYR[priority_reg_index], irq_num, priority);
+            }),
+            // Shared.
+            _ => {
+                let priority_reg_index_shared = priority_reg_index - 8;
+
+                self.shared_registers.lock(|regs| {
+                    update_priority_field(
+                        &regs.IPRIORITYR[priority_reg_index_shared],
+                        irq_num,
+                        priority,
+                    );
+                });
+            }
+        }
+    }
+
+    /// Route a shared interrupt to the CPU interface with the given number.
+    ///
+    /// Private interrupts are always delivered to the core they belong to.
+    pub fn set_target(
+        &self,
+        irq_num: super::IRQNumber,
+        target_cpu_interface: usize,
+    ) -> Result<(), &'static str> {
+        let irq_num = irq_num.get();
+
+        if irq_num < 32 {
+            return Err("Private interrupts can not be routed");
+        }
+
+        // Each u32 target register holds the fields of four IRQs.
+        let target_reg_index_shared = (irq_num >> 2) - 8;
+        let field = match irq_num % 4 {
+            0 => ITARGETSR::Offset0,
+            1 => ITARGETSR::Offset1,
+            2 => ITARGETSR::Offset2,
+            _ => ITARGETSR::Offset3,
+        };
+
+        self.shared_registers.lock(|regs| {
+            regs.ITARGETSR[target_reg_index_shared].modify(field.val(1 << target_cpu_interface));
+        });
+
+        Ok(())
+    }
+
+    /// Send a software-generated interrupt to the CPU interface with the given number.
+    pub fn send_sgi(&self, sgi_num: u8, target_cpu_interface: usize) {
+        self.shared_registers.lock(|regs| {
+            regs.SGIR.write(
+                SGIR::TargetListFilter::TargetList
+                    + SGIR::CPUTargetList.val(1 << target_cpu_interface)
+                    + SGIR::SGIINTID.val(sgi_num as u32),
+            );
+        });
+    }
 }
@@ -4,12 +4,22 @@
 
 //! BCM driver top level.
 
+#[cfg(any(feature = "bsp_rpi3", feature = "bsp_rpi4", feature = "bsp_rpi5"))]
+mod bcm2711_genet;
+#[cfg(any(feature = "bsp_rpi3", feature = "bsp_rpi4", feature = "bsp_rpi5"))]
 mod bcm2xxx_gpio;
-#[cfg(feature = "bsp_rpi3")]
+#[cfg(any(feature = "bsp_rpi3", feature = "bsp_rpi4", feature = "bsp_rpi5"))]
 mod bcm2xxx_interrupt_controller;
+#[cfg(any(feature = "bsp_rpi3", feature = "bsp_rpi4", feature = "bsp_rpi5"))]
+mod bcm2xxx_mailbox;
 mod bcm2xxx_pl011_uart;
 
+#[cfg(any(feature = "bsp_rpi3", feature = "bsp_rpi4", feature = "bsp_rpi5"))]
+pub use bcm2711_genet::*;
+#[cfg(any(feature = "bsp_rpi3", feature = "bsp_rpi4", feature = "bsp_rpi5"))]
 pub use bcm2xxx_gpio::*;
-#[cfg(feature = "bsp_rpi3")]
+#[cfg(any(feature = "bsp_rpi3", feature = "bsp_rpi4", feature = "bsp_rpi5"))]
 pub use bcm2xxx_interrupt_controller::*;
+#[cfg(any(feature = "bsp_rpi3", feature = "bsp_rpi4", feature = "bsp_rpi5"))]
+pub use bcm2xxx_mailbox::*;
 pub use bcm2xxx_pl011_uart::*;
@@ -0,0 +1,841 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! GENET Driver - Broadcom Gigabit Ethernet MAC, v5 as found in the BCM2711.
+//!
+//! The MAC is attached to an external BCM54213PE PHY via RGMII. The PHY is configured for
+//! auto-negotiation through the MAC's MDIO master.
+//!
+//! Only the default queue (ring 16) is used for both directions, and all operations are polled.
+//! Descriptors live in the MAC's register space. Frame buffers are statically allocated in kernel
+//! RAM, which is mapped cacheable, so explicit cache maintenance is done around every DMA access.
+
+use crate::{
+    bsp::device_driver::common::MMIODerefWrapper,
+    cpu, driver,
+    memory::{self, Address, Virtual},
+    net::{self, LinkStatus, MacAddress},
+    synchronization,
+    synchronization::{InitStateLock, SpinLock},
+    time, warn,
+};
+use core::time::Duration;
+use tock_registers::{
+    interfaces::{ReadWriteable, Readable, Writeable},
+    register_bitfields, register_structs,
+    registers::{ReadOnly, ReadWrite},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+// GENET registers.
+//
+// There is no public datasheet. Descriptions taken from
+// - https://github.com/torvalds/linux/blob/master/drivers/net/ethernet/broadcom/genet/bcmgenet.h
+register_bitfields! {
+    u32,
+
+    /// Revision Control Register
+    SYS_REV_CTRL [
+        /// Major revision. GENET v5 reports 6.
+        MAJOR OFFSET(24) NUMBITS(4) []
+    ],
+
+    /// Port Control Register
+    SYS_PORT_CTRL [
+        PORT_MODE OFFSET(0) NUMBITS(2) [
+            IntEphy = 0,
+            IntGphy = 1,
+            ExtEphy = 2,
+            ExtGphy = 3
+        ]
+    ],
+
+    /// RX Buffer Flush Control Register
+    SYS_RBUF_FLUSH_CTRL [
+        /// Reset the RX buffer and the UniMAC
+        RESET OFFSET(1) NUMBITS(1) []
+    ],
+
+    /// RGMII Out-Of-Band Control Register
+    EXT_RGMII_OOB_CTRL [
+        /// Disable internal RGMII delays
+        ID_MODE_DIS OFFSET(16) NUMBITS(1) [],
+
+        RGMII_MODE_EN OFFSET(6) NUMBITS(1) [],
+
+        OOB_DISABLE OFFSET(5) NUMBITS(1) [],
+
+        RGMII_LINK OFFSET(4) NUMBITS(1) []
+    ],
+
+    /// RX Buffer Control Register
+    RBUF_CTRL [
+        /// Prepend a 2 byte gap so that the IP header is 4 byte aligned
+        ALIGN_2B OFFSET(1) NUMBITS(1) [],
+
+        /// Prepend a 64 byte status block to received frames
+        STATUS_64B_EN OFFSET(0) NUMBITS(1) []
+    ],
+
+    /// UniMAC Command Register
+    UMAC_CMD [
+        LCL_LOOP_EN OFFSET(15) NUMBITS(1) [],
+
+        SW_RESET OFFSET(13) NUMBITS(1) [],
+
+        /// Forward the FCS to the host
+        CRC_FWD OFFSET(6) NUMBITS(1) [],
+
+        PROMISC OFFSET(4) NUMBITS(1) [],
+
+        SPEED OFFSET(2) NUMBITS(2) [
+            Speed10 = 0,
+            Speed100 = 1,
+            Speed1000 = 2
+        ],
+
+        RX_EN OFFSET(1) NUMBITS(1) [],
+
+        TX_EN OFFSET(0) NUMBITS(1) []
+    ],
+
+    /// UniMAC MIB Control Register
+    UMAC_MIB_CTRL [
+        RESET_RUNT OFFSET(2) NUMBITS(1) [],
+
+        RESET_TX OFFSET(1) NUMBITS(1) [],
+
+        RESET_RX OFFSET(0) NUMBITS(1) []
+    ],
+
+    /// MDIO Command Register
+    MDIO_CMD [
+        START_BUSY OFFSET(29) NUMBITS(1) [],
+
+        READ_FAIL OFFSET(28) NUMBITS(1) [],
+
+        OP OFFSET(26) NUMBITS(2) [
+            Write = 1,
+            Read = 2
+        ],
+
+        PHY_ADDR OFFSET(21) NUMBITS(5) [],
+
+        REG_ADDR OFFSET(16) NUMBITS(5) [],
+
+        DATA OFFSET(0) NUMBITS(16) []
+    ],
+
+    /// DMA Control Register
+    DMA_CTRL [
+        /// Enable ring 16, the default queue
+        RING16_BUF_EN OFFSET(17) NUMBITS(1) [],
+
+        DMA_EN OFFSET(0) NUMBITS(1) []
+    ],
+
+    /// DMA Ring Configuration Register
+    DMA_RING_CFG [
+        RING16_EN OFFSET(16) NUMBITS(1) []
+    ],
+
+    /// DMA Ring Buffer Size Register
+    DMA_RING_BUF_SIZE [
+        /// Number of descriptors in the ring
+        SIZE OFFSET(16) NUMBITS(16) [],
+
+        /// Size of each buffer in bytes
+        BUF_LENGTH OFFSET(0) NUMBITS(16) []
+    ],
+
+    /// Length/Status word of a DMA descriptor
+    DMA_DESC_LENGTH_STATUS [
+        BUF_LENGTH OFFSET(16) NUMBITS(12) [],
+
+        OWN OFFSET(15) NUMBITS(1) [],
+
+        /// End of packet
+        EOP OFFSET(14) NUMBITS(1) [],
+
+        /// Start of packet
+        SOP OFFSET(13) NUMBITS(1) [],
+
+        /// TX only: Queue tag
+        TX_QTAG OFFSET(7) NUMBITS(6) [],
+
+        /// TX only: Let the MAC append the FCS
+        TX_APPEND_CRC OFFSET(6) NUMBITS(1) [],
+
+        /// RX only: Frame too long, no EOP, receive error, CRC error or FIFO overflow
+        RX_ERRORS OFFSET(0) NUMBITS(5) []
+    ]
+}
+
+register_structs! {
+    #[allow(non_snake_case)]
+    RegisterBlock {
+        (0x000 => SYS_REV_CTRL: ReadOnly<u32, SYS_REV_CTRL::Register>),
+        (0x004 => SYS_PORT_CTRL: ReadWrite<u32, SYS_PORT_CTRL::Register>),
+        (0x008 => SYS_RBUF_FLUSH_CTRL: ReadWrite<u32, SYS_RBUF_FLUSH_CTRL::Register>),
+        (0x00C => _reserved1),
+        (0x08C => EXT_RGMII_OOB_CTRL: ReadWrite<u32, EXT_RGMII_OOB_CTRL::Register>),
+        (0x090 => _reserved2),
+        (0x208 => INTRL2_0_CPU_CLEAR: ReadWrite<u32>),
+        (0x20C => _reserved3),
+        (0x210 => INTRL2_0_CPU_MASK_SET: ReadWrite<u32>),
+        (0x214 => _reserved4),
+        (0x248 => INTRL2_1_CPU_CLEAR: ReadWrite<u32>),
+        (0x24C => _reserved5),
+        (0x250 => INTRL2_1_CPU_MASK_SET: ReadWrite<u32>),
+        (0x254 => _reserved6),
+        (0x300 => RBUF_CTRL: ReadWrite<u32, RBUF_CTRL::Register>),
+        (0x304 => _reserved7),
+        (0x3B4 => RBUF_TBUF_SIZE_CTRL: ReadWrite<u32>),
+        (0x3B8 => _reserved8),
+        (0x808 => UMAC_CMD: ReadWrite<u32, UMAC_CMD::Register>),
+        (0x80C => UMAC_MAC0: ReadWrite<u32>),
+        (0x810 => UMAC_MAC1: ReadWrite<u32>),
+        (0x814 => UMAC_MAX_FRAME_LEN: ReadWrite<u32>),
+        (0x818 => _reserved9),
+        (0xB34 => UMAC_TX_FLUSH: ReadWrite<u32>),
+        (0xB38 => _reserved10),
+        (0xD80 => UMAC_MIB_CTRL: ReadWrite<u32, UMAC_MIB_CTRL::Register>),
+        (0xD84 => _reserved11),
+        (0xE14 => MDIO_CMD: ReadWrite<u32, MDIO_CMD::Register>),
+        (0xE18 => _reserved12),
+        (0xE50 => UMAC_MDF_CTRL: ReadWrite<u32>),
+        (0xE54 => @END),
+    }
+}
+
+register_structs! {
+    #[allow(non_snake_case)]
+    RxDmaRegisterBlock {
+        (0x0000 => DESC: [ReadWrite<u32>; TOTAL_DESC * DESC_WORDS]),
+        (0x0C00 => _reserved1),
+        (0x1000 => RING16_WRITE_PTR: ReadWrite<u32>),
+        (0x1004 => RING16_WRITE_PTR_HI: ReadWrite<u32>),
+        (0x1008 => RING16_PROD_INDEX: ReadWrite<u32>),
+        (0x100C => RING16_CONS_INDEX: ReadWrite<u32>),
+        (0x1010 => RING16_BUF_SIZE: ReadWrite<u32, DMA_RING_BUF_SIZE::Register>),
+        (0x1014 => RING16_START_ADDR: ReadWrite<u32>),
+        (0x1018 => RING16_START_ADDR_HI: ReadWrite<u32>),
+        (0x101C => RING16_END_ADDR: ReadWrite<u32>),
+        (0x1020 => RING16_END_ADDR_HI: ReadWrite<u32>),
+        (0x1024 => _reserved2),
+        (0x1028 => RING16_XON_XOFF_THRESH: ReadWrite<u32>),
+        (0x102C => RING16_READ_PTR: ReadWrite<u32>),
+        (0x1030 => RING16_READ_PTR_HI: ReadWrite<u32>),
+        (0x1034 => _reserved3),
+        (0x1040 => RING_CFG: ReadWrite<u32, DMA_RING_CFG::Register>),
+        (0x1044 => CTRL: ReadWrite<u32, DMA_CTRL::Register>),
+        (0x1048 => _reserved4),
+        (0x104C => SCB_BURST_SIZE: ReadWrite<u32>),
+        (0x1050 => @END),
+    }
+}
+
+register_structs! {
+    #[allow(non_snake_case)]
+    TxDmaRegisterBlock {
+        (0x0000 => DESC: [ReadWrite<u32>; TOTAL_DESC * DESC_WORDS]),
+        (0x0C00 => _reserved1),
+        (0x1000 => RING16_READ_PTR: ReadWrite<u32>),
+        (0x1004 => RING16_READ_PTR_HI: ReadWrite<u32>),
+        (0x1008 => RING16_CONS_INDEX: ReadWrite<u32>),
+        (0x100C => RING16_PROD_INDEX: ReadWrite<u32>),
+        (0x1010 => RING16_BUF_SIZE: ReadWrite<u32, DMA_RING_BUF_SIZE::Register>),
+        (0x1014 => RING16_START_ADDR: ReadWrite<u32>),
+        (0x1018 => RING16_START_ADDR_HI: ReadWrite<u32>),
+        (0x101C => RING16_END_ADDR: ReadWrite<u32>),
+        (0x1020 => RING16_END_ADDR_HI: ReadWrite<u32>),
+        (0x1024 => RING16_MBUF_DONE_THRESH: ReadWrite<u32>),
+        (0x1028 => RING16_FLOW_PERIOD: ReadWrite<u32>),
+        (0x102C => RING16_WRITE_PTR: ReadWrite<u32>),
+        (0x1030 => RING16_WRITE_PTR_HI: ReadWrite<u32>),
+        (0x1034 => _reserved2),
+        (0x1040 => RING_CFG: ReadWrite<u32, DMA_RING_CFG::Register>),
+        (0x1044 => CTRL: ReadWrite<u32, DMA_CTRL::Register>),
+        (0x1048 => _reserved3),
+        (0x104C => SCB_BURST_SIZE: ReadWrite<u32>),
+        (0x1050 => @END),
+    }
+}
+
+/// Abstraction for the associated MMIO registers.
+type Registers = MMIODerefWrapper<RegisterBlock>;
+type RxDmaRegisters = MMIODerefWrapper<RxDmaRegisterBlock>;
+type TxDmaRegisters = MMIODerefWrapper<TxDmaRegisterBlock>;
+
+const RDMA_OFFSET: usize = 0x2000;
+const TDMA_OFFSET: usize = 0x4000;
+
+/// Number of descriptors provided by the hardware, shared by all rings.
+const TOTAL_DESC: usize = 256;
+
+/// A descriptor consists of three words: Length/status, address low and address high.
+const DESC_WORDS: usize = 3;
+const DESC_LENGTH_STATUS: usize = 0;
+const DESC_ADDRESS_LO: usize = 1;
+const DESC_ADDRESS_HI: usize = 2;
+
+const NUM_RX_DESC: usize = 32;
+const NUM_TX_DESC: usize = 32;
+
+/// Size of a frame buffer. Must be large enough for a full-sized frame including FCS.
+const BUF_LENGTH: usize = 2048;
+
+/// The hardware producer/consumer indices are 16 bit wide.
+const DMA_INDEX_MASK: u32 = 0xFFFF;
+
+/// Frames shorter than this are padded before transmission.
+const MIN_FRAME_SIZE: usize = 60;
+
+/// MDIO address of the BCM54213PE on the Raspberry Pi 4.
+const PHY_ADDR: u32 = 1;
+
+// Standard PHY registers.
+const MII_BMCR: u32 = 0x00;
+const MII_BMSR: u32 = 0x01;
+const MII_ADVERTISE: u32 = 0x04;
+const MII_CTRL1000: u32 = 0x09;
+
+const BMCR_RESET: u32 = 1 << 15;
+const BMCR_ANENABLE: u32 = 1 << 12;
+const BMCR_ANRESTART: u32 = 1 << 9;
+const BMSR_LSTATUS: u32 = 1 << 2;
+const ADVERTISE_ALL_10_100: u32 = 0x01E1;
+const ADVERTISE_1000_FULL_HALF: u32 = 0x0300;
+
+/// Broadcom specific auxiliary status summary register.
+const BCM_AUX_STATUS: u32 = 0x19;
+
+const REGISTER_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Fallback if neither firmware nor anybody else programmed a MAC address. Locally administered.
+const FALLBACK_MAC_ADDRESS: MacAddress = MacAddress::new([0x02, 0x00, 0x00, 0x00, 0x00, 0x01]);
+
+#[repr(C, align(64))]
+struct DMABuffers {
+    rx: [[u8; BUF_LENGTH]; NUM_RX_DESC],
+    tx: [[u8; BUF_LENGTH]; NUM_TX_DESC],
+}
+
+struct GENETInner {
+    registers: Registers,
+    rdma: RxDmaRegisters,
+    tdma: TxDmaRegisters,
+    mac_address: MacAddress,
+    link_status: LinkStatus,
+    rx_cons_index: u32,
+    tx_prod_index: u32,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Representation of the GENET Ethernet MAC.
+pub struct GENET {
+    mmio_descriptor: InitStateLock<memory::mmu::MMIODescriptor>,
+    inner: SpinLock<GENETInner>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+/// Linked to `.bss.noinit`, which takes no space in the kernel image and is skipped when `_start`
+/// zeroes `.bss`. Zeroing is not needed, since the controller writes the RX buffers and
+/// `transmit()` pads the TX frames.
+#[link_section = ".bss.noinit"]
+static mut DMA_BUFFERS: DMABuffers = DMABuffers {
+    rx: [[0; BUF_LENGTH]; NUM_RX_DESC],
+    tx: [[0; BUF_LENGTH]; NUM_TX_DESC],
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// Spin until `condition` evaluates to true or `timeout` has passed.
+fn poll_until(mut condition: impl FnMut() -> bool, timeout: Duration) -> Result<(), &'static str> {
+    time::with_timeout(timeout, || condition().then(|| ()))
+        .map_err(|_| "Timeout while polling GENET register")
+}
+
+fn delay(duration: Duration) {
+    time::sleep(duration);
+}
+
+/// Translate a frame buffer's kernel virtual address into the address used by the DMA engine.
+///
+/// On the BCM2711, GENET sees the full 35 bit ARM physical address space.
+fn dma_addr(buf: &[u8]) -> Result<u64, &'static str> {
+    let virt_addr = Address::<Virtual>::new(buf.as_ptr() as usize);
+    let phys_addr = memory::mmu::try_kernel_virt_addr_to_phys_addr(virt_addr)?;
+
+    Ok(phys_addr.as_usize() as u64)
+}
+
+impl GENETInner {
+    /// Create an instance.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide a correct MMIO start address.
+    pub const unsafe fn new(mmio_start_addr: usize) -> Self {
+        Self {
+            registers: Registers::new(mmio_start_addr),
+            rdma: RxDmaRegisters::new(mmio_start_addr + RDMA_OFFSET),
+            tdma: TxDmaRegisters::new(mmio_start_addr + TDMA_OFFSET),
+            mac_address: MacAddress::new([0; 6]),
+            link_status: LinkStatus::Down,
+            rx_cons_index: 0,
+            tx_prod_index: 0,
+        }
+    }
+
+    fn set_mmio_start_addr(&mut self, addr: usize) {
+        self.registers = Registers::new(addr);
+        self.rdma = RxDmaRegisters::new(addr + RDMA_OFFSET);
+        self.tdma = TxDmaRegisters::new(addr + TDMA_OFFSET);
+    }
+
+    fn mdio_read(&self, reg: u32) -> Result<u32, &'static str> {
+        self.registers.MDIO_CMD.write(
+            MDIO_CMD::OP::Read + MDIO_CMD::PHY_ADDR.val(PHY_ADDR) + MDIO_CMD::REG_ADDR.val(reg),
+        );
+        self.registers.MDIO_CMD.modify(MDIO_CMD::START_BUSY::SET);
+
+        poll_until(
+            || !self.registers.MDIO_CMD.is_set(MDIO_CMD::START_BUSY),
+            REGISTER_TIMEOUT,
+        )?;
+
+        if self.registers.MDIO_CMD.is_set(MDIO_CMD::READ_FAIL) {
+            return Err("MDIO read failed");
+        }
+
+        Ok(self.registers.MDIO_CMD.read(MDIO_CMD::DATA))
+    }
+
+    fn mdio_write(&self, reg: u32, val: u32) -> Result<(), &'static str> {
+        self.registers.MDIO_CMD.write(
+            MDIO_CMD::OP::Write
+                + MDIO_CMD::PHY_ADDR.val(PHY_ADDR)
+                + MDIO_CMD::REG_ADDR.val(reg)
+                + MDIO_CMD::DATA.val(val),
+        );
+        self.registers.MDIO_CMD.modify(MDIO_CMD::START_BUSY::SET);
+
+        poll_until(
+            || !self.registers.MDIO_CMD.is_set(MDIO_CMD::START_BUSY),
+            REGISTER_TIMEOUT,
+        )
+    }
+
+    fn reset_umac(&self) {
+        self.registers
+            .SYS_RBUF_FLUSH_CTRL
+            .write(SYS_RBUF_FLUSH_CTRL::RESET::SET);
+        delay(Duration::from_micros(10));
+        self.registers.SYS_RBUF_FLUSH_CTRL.set(0);
+        delay(Duration::from_micros(10));
+
+        // A soft reset with local loopback enabled ensures a stable RX clock during the reset.
+        self.registers.UMAC_CMD.set(0);
+        self.registers
+            .UMAC_CMD
+            .write(UMAC_CMD::SW_RESET::SET + UMAC_CMD::LCL_LOOP_EN::SET);
+        delay(Duration::from_micros(2));
+        self.registers.UMAC_CMD.set(0);
+    }
+
+    fn init_umac(&self) {
+        self.registers.UMAC_MIB_CTRL.write(
+            UMAC_MIB_CTRL::RESET_RX::SET
+                + UMAC_MIB_CTRL::RESET_TX::SET
+                + UMAC_MIB_CTRL::RESET_RUNT::SET,
+        );
+        self.registers.UMAC_MIB_CTRL.set(0);
+
+        self.registers.UMAC_MAX_FRAME_LEN.set(BUF_LENGTH as u32);
+
+        // No status block, no alignment gap. Received frames start at offset zero of the buffer.
+        self.registers.RBUF_CTRL.set(0);
+        self.registers.RBUF_TBUF_SIZE_CTRL.set(1);
+
+        // Mask and acknowledge all interrupts. This driver polls.
+        self.registers.INTRL2_0_CPU_MASK_SET.set(u32::MAX);
+        self.registers.INTRL2_0_CPU_CLEAR.set(u32::MAX);
+        self.registers.INTRL2_1_CPU_MASK_SET.set(u32::MAX);
+        self.registers.INTRL2_1_CPU_CLEAR.set(u32::MAX);
+    }
+
+    /// Use the address that was programmed by the firmware, if any.
+    fn init_mac_address(&mut self) {
+        let mac0 = self.registers.UMAC_MAC0.get().to_be_bytes();
+        let mac1 = self.registers.UMAC_MAC1.get().to_be_bytes();
+        let mut addr = MacAddress::new([mac0[0], mac0[1], mac0[2], mac0[3], mac1[2], mac1[3]]);
+
+        if addr.is_zero() {
+            warn!(
+                "GENET: No MAC address configured, using {}",
+                FALLBACK_MAC_ADDRESS
+            );
+            addr = FALLBACK_MAC_ADDRESS;
+        }
+
+        let b = addr.as_bytes();
+        self.registers
+            .UMAC_MAC0
+            .set(u32::from_be_bytes([b[0], b[1], b[2], b[3]]));
+        self.registers
+            .UMAC_MAC1
+            .set(u32::from_be_bytes([0, 0, b[4], b[5]]));
+
+        self.mac_address = addr;
+    }
+
+    fn init_phy(&self) -> Result<(), &'static str> {
+        self.registers
+            .SYS_PORT_CTRL
+            .write(SYS_PORT_CTRL::PORT_MODE::ExtGphy);
+
+        // The Raspberry Pi 4 uses "rgmii-rxid", so keep the internal TX delay enabled.
+        self.registers.EXT_RGMII_OOB_CTRL.modify(
+            EXT_RGMII_OOB_CTRL::RGMII_MODE_EN::SET + EXT_RGMII_OOB_CTRL::ID_MODE_DIS::CLEAR,
+        );
+
+        self.mdio_write(MII_BMCR, BMCR_RESET)?;
+        poll_until(
+            || matches!(self.mdio_read(MII_BMCR), Ok(x) if (x & BMCR_RESET) == 0),
+            REGISTER_TIMEOUT,
+        )?;
+
+        self.mdio_write(MII_ADVERTISE, ADVERTISE_ALL_10_100)?;
+        self.mdio_write(MII_CTRL1000, ADVERTISE_1000_FULL_HALF)?;
+        self.mdio_write(MII_BMCR, BMCR_ANENABLE | BMCR_ANRESTART)
+    }
+
+    fn init_dma(&mut self) -> Result<(), &'static str> {
+        self.rdma.CTRL.set(0);
+        self.tdma.CTRL.set(0);
+        self.registers.UMAC_TX_FLUSH.set(1);
+        delay(Duration::from_micros(10));
+        self.registers.UMAC_TX_FLUSH.set(0);
+
+        let buf_size = DMA_RING_BUF_SIZE::BUF_LENGTH.val(BUF_LENGTH as u32);
+
+        // RX ring, using descriptors 0..NUM_RX_DESC.
+        self.rdma.RING16_WRITE_PTR.set(0);
+        self.rdma.RING16_WRITE_PTR_HI.set(0);
+        self.rdma.RING16_PROD_INDEX.set(0);
+        self.rdma.RING16_CONS_INDEX.set(0);
+        self.rdma
+            .RING16_BUF_SIZE
+            .write(DMA_RING_BUF_SIZE::SIZE.val(NUM_RX_DESC as u32) + buf_size);
+        self.rdma.RING16_START_ADDR.set(0);
+        self.rdma.RING16_START_ADDR_HI.set(0);
+        self.rdma
+            .RING16_END_ADDR
+            .set((NUM_RX_DESC * DESC_WORDS - 1) as u32);
+        self.rdma.RING16_END_ADDR_HI.set(0);
+        self.rdma
+            .RING16_XON_XOFF_THRESH
+            .set((5 << 16) | (NUM_RX_DESC as u32 >> 4));
+        self.rdma.RING16_READ_PTR.set(0);
+        self.rdma.RING16_READ_PTR_HI.set(0);
+        self.rx_cons_index = 0;
+
+        let rx_bufs = unsafe { &mut DMA_BUFFERS.rx };
+        for (i, buf) in rx_bufs.iter_mut().enumerate() {
+            let addr = dma_addr(buf)?;
+
+            cpu::invalidate_dcache_range(buf.as_ptr() as usize, BUF_LENGTH);
+            self.rdma.DESC[(i * DESC_WORDS) + DESC_ADDRESS_LO].set(addr as u32);
+            self.rdma.DESC[(i * DESC_WORDS) + DESC_ADDRESS_HI].set((addr >> 32) as u32);
+        }
+
+        // TX ring, also using descriptors 0..NUM_TX_DESC, but of the TX DMA engine.
+        self.tdma.RING16_READ_PTR.set(0);
+        self.tdma.RING16_READ_PTR_HI.set(0);
+        self.tdma.RING16_CONS_INDEX.set(0);
+        self.tdma.RING16_PROD_INDEX.set(0);
+        self.tdma
+            .RING16_BUF_SIZE
+            .write(DMA_RING_BUF_SIZE::SIZE.val(NUM_TX_DESC as u32) + buf_size);
+        self.tdma.RING16_START_ADDR.set(0);
+        self.tdma.RING16_START_ADDR_HI.set(0);
+        self.tdma
+            .RING16_END_ADDR
+            .set((NUM_TX_DESC * DESC_WORDS - 1) as u32);
+        self.tdma.RING16_END_ADDR_HI.set(0);
+        self.tdma.RING16_MBUF_DONE_THRESH.set(1);
+        self.tdma.RING16_FLOW_PERIOD.set(0);
+        self.tdma.RING16_WRITE_PTR.set(0);
+        self.tdma.RING16_WRITE_PTR_HI.set(0);
+        self.tx_prod_index = 0;
+
+        self.rdma.SCB_BURST_SIZE.set(0x08);
+        self.tdma.SCB_BURST_SIZE.set(0x08);
+
+        self.rdma.RING_CFG.write(DMA_RING_CFG::RING16_EN::SET);
+        self.tdma.RING_CFG.write(DMA_RING_CFG::RING16_EN::SET);
+        self.rdma
+            .CTRL
+            .write(DMA_CTRL::DMA_EN::SET + DMA_CTRL::RING16_BUF_EN::SET);
+        self.tdma
+            .CTRL
+            .write(DMA_CTRL::DMA_EN::SET + DMA_CTRL::RING16_BUF_EN::SET);
+
+        Ok(())
+    }
+
+    fn init(&mut self) -> Result<(), &'static str> {
+        // GENET v5 identifies itself as major revision 6.
+        if self.registers.SYS_REV_CTRL.read(SYS_REV_CTRL::MAJOR) != 6 {
+            return Err("Unsupported GENET revision");
+        }
+
+        self.reset_umac();
+        self.init_umac();
+        self.init_mac_address();
+        self.init_phy()?;
+        self.init_dma()?;
+
+        // Speed is programmed once the link comes up. Promiscuous mode spares setting up the
+        // destination address filters.
+        self.registers
+            .UMAC_CMD
+            .write(UMAC_CMD::PROMISC::SET + UMAC_CMD::CRC_FWD::CLEAR + UMAC_CMD::SPEED::Speed1000);
+        self.registers.UMAC_MDF_CTRL.set(0);
+
+        Ok(())
+    }
+
+    /// Query the PHY and reprogram the MAC if the link state changed.
+    fn update_link(&mut self) -> LinkStatus {
+        // The link status bit is latched low. Read twice to get the current state.
+        let _ = self.mdio_read(MII_BMSR);
+        let link_up = matches!(self.mdio_read(MII_BMSR), Ok(x) if (x & BMSR_LSTATUS) != 0);
+
+        let new_status = if !link_up {
+            LinkStatus::Down
+        } else {
+            // Auto-negotiation highest common denominator, bits [10:8].
+            let hcd = self.mdio_read(BCM_AUX_STATUS).map(|x| (x >> 8) & 0x7);
+
+            match hcd {
+                Ok(7) => LinkStatus::Up {
+                    speed_mbps: 1000,
+                    full_duplex: true,
+                },
+                Ok(6) => LinkStatus::Up {
+                    speed_mbps: 1000,
+                    full_duplex: false,
+                },
+                Ok(5) => LinkStatus::Up {
+                    speed_mbps: 100,
+                    full_duplex: true,
+                },
+                Ok(3) | Ok(4) => LinkStatus::Up {
+                    speed_mbps: 100,
+                    full_duplex: false,
+                },
+                Ok(2) => LinkStatus::Up {
+                    speed_mbps: 10,
+                    full_duplex: true,
+                },
+                Ok(1) => LinkStatus::Up {
+                    speed_mbps: 10,
+                    full_duplex: false,
+                },
+                _ => LinkStatus::Down,
+            }
+        };
+
+        if new_status == self.link_status {
+            return new_status;
+        }
+
+        match new_status {
+            LinkStatus::Down => {
+                self.registers
+                    .UMAC_CMD
+                    .modify(UMAC_CMD::TX_EN::CLEAR + UMAC_CMD::RX_EN::CLEAR);
+                self.registers
+                    .EXT_RGMII_OOB_CTRL
+                    .modify(EXT_RGMII_OOB_CTRL::RGMII_LINK::CLEAR);
+            }
+            LinkStatus::Up { speed_mbps, .. } => {
+                let speed = match speed_mbps {
+                    1000 => UMAC_CMD::SPEED::Speed1000,
+                    100 => UMAC_CMD::SPEED::Speed100,
+                    _ => UMAC_CMD::SPEED::Speed10,
+                };
+
+                self.registers.EXT_RGMII_OOB_CTRL.modify(
+                    EXT_RGMII_OOB_CTRL::OOB_DISABLE::CLEAR + EXT_RGMII_OOB_CTRL::RGMII_LINK::SET,
+                );
+                self.registers
+                    .UMAC_CMD
+                    .modify(speed + UMAC_CMD::TX_EN::SET + UMAC_CMD::RX_EN::SET);
+            }
+        }
+
+        self.link_status = new_status;
+        new_status
+    }
+
+    fn transmit(&mut self, frame: &[u8]) -> Result<(), &'static str> {
+        if frame.len() > net::MAX_FRAME_SIZE {
+            return Err("Frame too large");
+        }
+
+        if !matches!(self.link_status, LinkStatus::Up { .. }) {
+            return Err("Link down");
+        }
+
+        let cons_index = self.tdma.RING16_CONS_INDEX.get() & DMA_INDEX_MASK;
+        let in_flight = self.tx_prod_index.wrapping_sub(cons_index) & DMA_INDEX_MASK;
+        if in_flight as usize >= NUM_TX_DESC {
+            return Err("TX ring full");
+        }
+
+        let desc_index = (self.tx_prod_index as usize) % NUM_TX_DESC;
+        let buf = unsafe { &mut DMA_BUFFERS.tx[desc_index] };
+        let len = core::cmp::max(frame.len(), MIN_FRAME_SIZE);
+
+        buf[..frame.len()].copy_from_slice(frame);
+        buf[frame.len()..len].fill(0);
+        cpu::clean_dcache_range(buf.as_ptr() as usize, len);
+
+        let addr = dma_addr(buf)?;
+        let desc = &self.tdma.DESC[(desc_index * DESC_WORDS)..((desc_index + 1) * DESC_WORDS)];
+        desc[DESC_ADDRESS_LO].set(addr as u32);
+        desc[DESC_ADDRESS_HI].set((addr >> 32) as u32);
+        desc[DESC_LENGTH_STATUS].set(
+            (DMA_DESC_LENGTH_STATUS::BUF_LENGTH.val(len as u32)
+                + DMA_DESC_LENGTH_STATUS::SOP::SET
+                + DMA_DESC_LENGTH_STATUS::EOP::SET
+                + DMA_DESC_LENGTH_STATUS::TX_APPEND_CRC::SET
+                + DMA_DESC_LENGTH_STATUS::TX_QTAG.val(0x3F))
+            .value,
+        );
+
+        self.tx_prod_index = (self.tx_prod_index + 1) & DMA_INDEX_MASK;
+        self.tdma.RING16_PROD_INDEX.set(self.tx_prod_index);
+
+        Ok(())
+    }
+
+    fn receive(&mut self, out: &mut [u8]) -> Option<usize> {
+        let prod_index = self.rdma.RING16_PROD_INDEX.get() & DMA_INDEX_MASK;
+
+        loop {
+            if prod_index == self.rx_cons_index {
+                return None;
+            }
+
+            let desc_index = (self.rx_cons_index as usize) % NUM_RX_DESC;
+            let status = self.rdma.DESC[(desc_index * DESC_WORDS) + DESC_LENGTH_STATUS].get();
+            let buf = unsafe { &DMA_BUFFERS.rx[desc_index] };
+            let len = ((status >> 16) & 0xFFF) as usize;
+
+            let complete = (status & DMA_DESC_LENGTH_STATUS::SOP::SET.value) != 0
+                && (status & DMA_DESC_LENGTH_STATUS::EOP::SET.value) != 0;
+            let errors = (status & DMA_DESC_LENGTH_STATUS::RX_ERRORS.mask) != 0;
+
+            let result = if complete && !errors && (len <= out.len()) && (len <= BUF_LENGTH) {
+                cpu::invalidate_dcache_range(buf.as_ptr() as usize, len);
+                out[..len].copy_from_slice(&buf[..len]);
+
+                Some(len)
+            } else {
+                None
+            };
+
+            // Hand the buffer back to the hardware.
+            self.rx_cons_index = (self.rx_cons_index + 1) & DMA_INDEX_MASK;
+            self.rdma.RING16_CONS_INDEX.set(self.rx_cons_index);
+
+            if result.is_some() {
+                return result;
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl GENET {
+    /// Create an instance.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide correct MMIO descriptors.
+    pub const unsafe fn new(mmio_descriptor: memory::mmu::MMIODescriptor) -> Self {
+        Self {
+            mmio_descriptor: InitStateLock::new(mmio_descriptor),
+            inner: SpinLock::new(GENETInner::new(mmio_descriptor.start_addr().as_usize())),
+        }
+    }
+
+    /// Use a different MMIO descriptor than the one the instance was created with, e.g. the one
+    /// that the device tree tells.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide a correct MMIO descriptor.
+    /// - Only has an effect if called before `init()`.
+    pub unsafe fn set_mmio_descriptor(&self, mmio_descriptor: memory::mmu::MMIODescriptor) {
+        self.mmio_descriptor.write(|x| *x = mmio_descriptor);
+    }
+}
+
+//------------------------------------------------------------------------------
+// OS Interface Code
+//------------------------------------------------------------------------------
+use synchronization::interface::{Mutex, ReadWriteEx};
+
+impl driver::interface::DeviceDriver for GENET {
+    fn compatible(&self) -> &'static str {
+        "BCM GENET v5 Ethernet"
+    }
+
+    unsafe fn init(&self) -> Result<(), &'static str> {
+        let virt_addr =
+            memory::mmu::kernel_map_mmio(self.compatible(), &self.mmio_descriptor.read(|x| *x))?;
+
+        self.inner.lock(|inner| {
+            inner.set_mmio_start_addr(virt_addr.as_usize());
+            inner.init()
+        })
+    }
+}
+
+impl net::interface::NetworkDevice for GENET {
+    fn mac_address(&self) -> MacAddress {
+        self.inner.lock(|inner| inner.mac_address)
+    }
+
+    fn link_status(&self) -> LinkStatus {
+        self.inner.lock(|inner| inner.update_link())
+    }
+
+    fn transmit(&self, frame: &[u8]) -> Result<(), &'static str> {
+        self.inner.lock(|inner| inner.transmit(frame))
+    }
+
+    fn receive(&self, buf: &mut [u8]) -> Option<usize> {
+        self.inner.lock(|inner| inner.receive(buf))
+    }
+}
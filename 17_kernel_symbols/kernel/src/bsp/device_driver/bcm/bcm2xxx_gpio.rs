@@ -5,14 +5,17 @@
 //! GPIO Driver.
 
 use crate::{
-    bsp::device_driver::common::MMIODerefWrapper, driver, memory, synchronization,
-    synchronization::IRQSafeNullLock,
+    bsp::device_driver::common::MMIODerefWrapper,
+    driver,
+    gpio::{self, Level, Pull},
+    memory, synchronization,
+    synchronization::{InitStateLock, SpinLock},
 };
-use core::sync::atomic::{AtomicUsize, Ordering};
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use tock_registers::{
-    interfaces::{ReadWriteable, Writeable},
+    interfaces::{Readable, Writeable},
     register_bitfields, register_structs,
-    registers::ReadWrite,
+    registers::{ReadOnly, ReadWrite, WriteOnly},
 };
 
 //--------------------------------------------------------------------------------------------------
@@ -27,24 +30,6 @@ use tock_registers::{
 register_bitfields! {
     u32,
 
-    /// GPIO Function Select 1
-    GPFSEL1 [
-        /// Pin 15
-        FSEL15 OFFSET(15) NUMBITS(3) [
-            Input = 0b000,
-            Output = 0b001,
-            AltFunc0 = 0b100  // PL011 UART RX
-
-        ],
-
-        /// Pin 14
-        FSEL14 OFFSET(12) NUMBITS(3) [
-            Input = 0b000,
-            Output = 0b001,
-            AltFunc0 = 0b100  // PL011 UART TX
-        ]
-    ],
-
     /// GPIO Pull-up/down Register
     ///
     /// BCM2837 only.
@@ -55,66 +40,63 @@ register_bitfields! {
             PullDown = 0b01,
             PullUp = 0b10
         ]
-    ],
-
-    /// GPIO Pull-up/down Clock Register 0
-    ///
-    /// BCM2837 only.
-    GPPUDCLK0 [
-        /// Pin 15
-        PUDCLK15 OFFSET(15) NUMBITS(1) [
-            NoEffect = 0,
-            AssertClock = 1
-        ],
-
-        /// Pin 14
-        PUDCLK14 OFFSET(14) NUMBITS(1) [
-            NoEffect = 0,
-            AssertClock = 1
-        ]
-    ],
-
-    /// GPIO Pull-up / Pull-down Register 0
-    ///
-    /// BCM2711 only.
-    GPIO_PUP_PDN_CNTRL_REG0 [
-        /// Pin 15
-        GPIO_PUP_PDN_CNTRL15 OFFSET(30) NUMBITS(2) [
-            NoResistor = 0b00,
-            PullUp = 0b01
-        ],
-
-        /// Pin 14
-        GPIO_PUP_PDN_CNTRL14 OFFSET(28) NUMBITS(2) [
-            NoResistor = 0b00,
-            PullUp = 0b01
-        ]
     ]
 }
 
+// The function select registers hold 3 bits per pin, the pull-up/down registers of the BCM2711
+// hold 2 bits per pin. All other registers hold one bit per pin.
 register_structs! {
     #[allow(non_snake_case)]
     RegisterBlock {
-        (0x00 => _reserved1),
-        (0x04 => GPFSEL1: ReadWrite<u32, GPFSEL1::Register>),
-        (0x08 => _reserved2),
+        (0x00 => GPFSEL: [ReadWrite<u32>; 6]),
+        (0x18 => _reserved1),
+        (0x1C => GPSET: [WriteOnly<u32>; 2]),
+        (0x24 => _reserved2),
+        (0x28 => GPCLR: [WriteOnly<u32>; 2]),
+        (0x30 => _reserved3),
+        (0x34 => GPLEV: [ReadOnly<u32>; 2]),
+        (0x3C => _reserved4),
         (0x94 => GPPUD: ReadWrite<u32, GPPUD::Register>),
-        (0x98 => GPPUDCLK0: ReadWrite<u32, GPPUDCLK0::Register>),
-        (0x9C => _reserved3),
-        (0xE4 => GPIO_PUP_PDN_CNTRL_REG0: ReadWrite<u32, GPIO_PUP_PDN_CNTRL_REG0::Register>),
-        (0xE8 => @END),
+        (0x98 => GPPUDCLK: [ReadWrite<u32>; 2]),
+        (0xA0 => _reserved5),
+        (0xE4 => GPIO_PUP_PDN_CNTRL_REG: [ReadWrite<u32>; 4]),
+        (0xF4 => @END),
     }
 }
 
 /// Abstraction for the associated MMIO registers.
 type Registers = MMIODerefWrapper<RegisterBlock>;
 
+/// Pin functions, with their encoding in the function select registers.
+#[allow(dead_code)]
+#[derive(Copy, Clone)]
+enum Function {
+    Input = 0b000,
+    Output = 0b001,
+    Alt0 = 0b100,
+    Alt1 = 0b101,
+    Alt2 = 0b110,
+    Alt3 = 0b111,
+    Alt4 = 0b011,
+    Alt5 = 0b010,
+}
+
 //--------------------------------------------------------------------------------------------------
 // Public Definitions
 //--------------------------------------------------------------------------------------------------
 
+/// The SoCs whose GPIO controllers the driver supports.
+///
+/// They differ in the number of pins and in how the pull resistors are configured.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum GPIOModel {
+    BCM2837,
+    BCM2711,
+}
+
 pub struct GPIOInner {
     registers: Registers,
+    model: GPIOModel,
 }
 
 // Export the inner struct so that BSPs can use it for the panic handler.
@@ -122,9 +104,127 @@ pub use GPIOInner as PanicGPIO;
 
 /// Representation of the GPIO HW.
 pub struct GPIO {
-    mmio_descriptor: memory::mmu::MMIODescriptor,
+    mmio_descriptor: InitStateLock<memory::mmu::MMIODescriptor>,
     virt_mmio_start_addr: AtomicUsize,
-    inner: IRQSafeNullLock<GPIOInner>,
+    claimed_pins: AtomicU64,
+    inner: SpinLock<GPIOInner>,
+}
+
+/// An exclusively owned, not yet configured pin.
+///
+/// The pin is released when the object is dropped.
+pub struct Pin {
+    gpio: &'static GPIO,
+    number: usize,
+}
+
+/// A pin configured as output.
+pub struct OutputPin(Pin);
+
+/// A pin configured as input.
+pub struct InputPin(Pin);
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl GPIOModel {
+    /// The number of pins of the controller.
+    fn num_pins(self) -> usize {
+        match self {
+            GPIOModel::BCM2837 => 54,
+            GPIOModel::BCM2711 => 58,
+        }
+    }
+}
+
+impl GPIOInner {
+    fn set_function(&mut self, pin: usize, function: Function) {
+        let reg = &self.registers.GPFSEL[pin / 10];
+        let shift = (pin % 10) * 3;
+
+        reg.set((reg.get() & !(0b111 << shift)) | ((function as u32) << shift));
+    }
+
+    /// Configure the pull resistor of a pin.
+    fn set_pull(&mut self, pin: usize, pull: Pull) {
+        match self.model {
+            GPIOModel::BCM2837 => self.set_pull_bcm2837(pin, pull),
+            GPIOModel::BCM2711 => self.set_pull_bcm2711(pin, pull),
+        }
+    }
+
+    /// Configure the pull resistor of a pin with the BCM2837's sequence.
+    fn set_pull_bcm2837(&mut self, pin: usize, pull: Pull) {
+        use crate::time;
+        use core::time::Duration;
+
+        // The Linux 2837 GPIO driver waits 1 µs between the steps.
+        const DELAY: Duration = Duration::from_micros(1);
+
+        let pud = match pull {
+            Pull::None => GPPUD::PUD::Off,
+            Pull::Up => GPPUD::PUD::PullUp,
+            Pull::Down => GPPUD::PUD::PullDown,
+        };
+
+        self.registers.GPPUD.write(pud);
+        time::sleep(DELAY);
+
+        self.registers.GPPUDCLK[pin / 32].set(1 << (pin % 32));
+        time::sleep(DELAY);
+
+        self.registers.GPPUD.write(GPPUD::PUD::Off);
+        self.registers.GPPUDCLK[pin / 32].set(0);
+    }
+
+    /// Configure the pull resistor of a pin with the BCM2711's dedicated registers.
+    fn set_pull_bcm2711(&mut self, pin: usize, pull: Pull) {
+        let value = match pull {
+            Pull::None => 0b00,
+            Pull::Up => 0b01,
+            Pull::Down => 0b10,
+        };
+
+        let reg = &self.registers.GPIO_PUP_PDN_CNTRL_REG[pin / 16];
+        let shift = (pin % 16) * 2;
+
+        reg.set((reg.get() & !(0b11 << shift)) | (value << shift));
+    }
+
+    fn set_level(&mut self, pin: usize, level: Level) {
+        let bit = 1 << (pin % 32);
+
+        match level {
+            Level::High => self.registers.GPSET[pin / 32].set(bit),
+            Level::Low => self.registers.GPCLR[pin / 32].set(bit),
+        }
+    }
+
+    fn level(&self, pin: usize) -> Level {
+        Level::from(self.registers.GPLEV[pin / 32].get() & (1 << (pin % 32)) != 0)
+    }
+}
+
+impl GPIO {
+    fn claim_pins(&self, mask: u64) -> Result<(), &'static str> {
+        let previous = self.claimed_pins.fetch_or(mask, Ordering::AcqRel);
+
+        if previous & mask != 0 {
+            // Give back only those pins that were not claimed before.
+            self.claimed_pins
+                .fetch_and(!(mask & !previous), Ordering::AcqRel);
+
+            return Err("GPIO: Pin already claimed");
+        }
+
+        Ok(())
+    }
+
+    fn release_pin(&self, number: usize) {
+        self.claimed_pins
+            .fetch_and(!(1 << number), Ordering::AcqRel);
+    }
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -137,9 +237,10 @@ impl GPIOInner {
     /// # Safety
     ///
     /// - The user must ensure to provide a correct MMIO start address.
-    pub const unsafe fn new(mmio_start_addr: usize) -> Self {
+    pub const unsafe fn new(mmio_start_addr: usize, model: GPIOModel) -> Self {
         Self {
             registers: Registers::new(mmio_start_addr),
+            model,
         }
     }
 
@@ -156,52 +257,32 @@ impl GPIOInner {
         Ok(())
     }
 
-    /// Disable pull-up/down on pins 14 and 15.
-    #[cfg(feature = "bsp_rpi3")]
-    fn disable_pud_14_15_bcm2837(&mut self) {
-        use crate::{time, time::interface::TimeManager};
-        use core::time::Duration;
-
-        // The Linux 2837 GPIO driver waits 1 µs between the steps.
-        const DELAY: Duration = Duration::from_micros(1);
-
-        self.registers.GPPUD.write(GPPUD::PUD::Off);
-        time::time_manager().spin_for(DELAY);
-
-        self.registers
-            .GPPUDCLK0
-            .write(GPPUDCLK0::PUDCLK15::AssertClock + GPPUDCLK0::PUDCLK14::AssertClock);
-        time::time_manager().spin_for(DELAY);
-
-        self.registers.GPPUD.write(GPPUD::PUD::Off);
-        self.registers.GPPUDCLK0.set(0);
-    }
-
-    /// Disable pull-up/down on pins 14 and 15.
-    #[cfg(feature = "bsp_rpi4")]
-    fn disable_pud_14_15_bcm2711(&mut self) {
-        self.registers.GPIO_PUP_PDN_CNTRL_REG0.write(
-            GPIO_PUP_PDN_CNTRL_REG0::GPIO_PUP_PDN_CNTRL15::PullUp
-                + GPIO_PUP_PDN_CNTRL_REG0::GPIO_PUP_PDN_CNTRL14::PullUp,
-        );
-    }
-
     /// Map PL011 UART as standard output.
     ///
     /// TX to pin 14
     /// RX to pin 15
     pub fn map_pl011_uart(&mut self) {
-        // Select the UART on pins 14 and 15.
-        self.registers
-            .GPFSEL1
-            .modify(GPFSEL1::FSEL15::AltFunc0 + GPFSEL1::FSEL14::AltFunc0);
-
-        // Disable pull-up/down on pins 14 and 15.
-        #[cfg(feature = "bsp_rpi3")]
-        self.disable_pud_14_15_bcm2837();
+        for pin in [14, 15] {
+            self.set_function(pin, Function::Alt0);
+
+            // The BCM2711 reset default for RX and TX is a pull-up, which does no harm.
+            let pull = match self.model {
+                GPIOModel::BCM2837 => Pull::None,
+                GPIOModel::BCM2711 => Pull::Up,
+            };
+            self.set_pull(pin, pull);
+        }
+    }
 
-        #[cfg(feature = "bsp_rpi4")]
-        self.disable_pud_14_15_bcm2711();
+    /// Map the PL011 UART's hardware flow control lines.
+    ///
+    /// CTS to pin 16
+    /// RTS to pin 17
+    pub fn map_pl011_uart_flow_control(&mut self) {
+        for pin in [16, 17] {
+            self.set_function(pin, Function::Alt3);
+            self.set_pull(pin, Pull::None);
+        }
     }
 }
 
@@ -211,24 +292,124 @@ impl GPIO {
     /// # Safety
     ///
     /// - The user must ensure to provide correct MMIO descriptors.
-    pub const unsafe fn new(mmio_descriptor: memory::mmu::MMIODescriptor) -> Self {
+    pub const unsafe fn new(
+        mmio_descriptor: memory::mmu::MMIODescriptor,
+        model: GPIOModel,
+    ) -> Self {
         Self {
-            mmio_descriptor,
+            mmio_descriptor: InitStateLock::new(mmio_descriptor),
             virt_mmio_start_addr: AtomicUsize::new(0),
-            inner: IRQSafeNullLock::new(GPIOInner::new(mmio_descriptor.start_addr().as_usize())),
+            claimed_pins: AtomicU64::new(0),
+            inner: SpinLock::new(GPIOInner::new(
+                mmio_descriptor.start_addr().as_usize(),
+                model,
+            )),
         }
     }
 
+    /// Use a different MMIO descriptor than the one the instance was created with, e.g. the one
+    /// that the device tree tells.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide a correct MMIO descriptor.
+    /// - Only has an effect if called before `init()`.
+    pub unsafe fn set_mmio_descriptor(&self, mmio_descriptor: memory::mmu::MMIODescriptor) {
+        self.mmio_descriptor.write(|x| *x = mmio_descriptor);
+    }
+
+    /// Drive a different model of the controller than the one the instance was created for.
+    ///
+    /// Must be called before any pins are configured.
+    pub fn set_model(&self, model: GPIOModel) {
+        self.inner.lock(|inner| inner.model = model);
+    }
+
+    /// Take exclusive ownership of a pin.
+    pub fn claim_pin(&'static self, number: usize) -> Result<Pin, &'static str> {
+        if number >= self.inner.lock(|inner| inner.model.num_pins()) {
+            return Err("GPIO: Pin does not exist");
+        }
+
+        self.claim_pins(1 << number)?;
+
+        Ok(Pin { gpio: self, number })
+    }
+
     /// Concurrency safe version of `GPIOInner.map_pl011_uart()`
-    pub fn map_pl011_uart(&self) {
-        self.inner.lock(|inner| inner.map_pl011_uart())
+    ///
+    /// The pins stay claimed for the UART.
+    pub fn map_pl011_uart(&self) -> Result<(), &'static str> {
+        self.claim_pins((1 << 14) | (1 << 15))?;
+        self.inner.lock(|inner| inner.map_pl011_uart());
+
+        Ok(())
+    }
+
+    /// Concurrency safe version of `GPIOInner.map_pl011_uart_flow_control()`
+    ///
+    /// The pins stay claimed for the UART.
+    pub fn map_pl011_uart_flow_control(&self) -> Result<(), &'static str> {
+        self.claim_pins((1 << 16) | (1 << 17))?;
+        self.inner.lock(|inner| inner.map_pl011_uart_flow_control());
+
+        Ok(())
+    }
+}
+
+impl Pin {
+    /// The pin's number.
+    pub fn number(&self) -> usize {
+        self.number
+    }
+
+    /// Configure the pin as output, driving the given level.
+    pub fn into_output(self, initial_level: Level) -> OutputPin {
+        self.gpio.inner.lock(|inner| {
+            // Set the level first, so that the pin does not glitch.
+            inner.set_pull(self.number, Pull::None);
+            inner.set_level(self.number, initial_level);
+            inner.set_function(self.number, Function::Output);
+        });
+
+        OutputPin(self)
+    }
+
+    /// Configure the pin as input with the given pull resistor.
+    pub fn into_input(self, pull: Pull) -> InputPin {
+        self.gpio.inner.lock(|inner| {
+            inner.set_function(self.number, Function::Input);
+            inner.set_pull(self.number, pull);
+        });
+
+        InputPin(self)
+    }
+}
+
+impl Drop for Pin {
+    fn drop(&mut self) {
+        self.gpio.release_pin(self.number);
+    }
+}
+
+impl OutputPin {
+    /// Switch the pin over to input mode.
+    pub fn into_input(self, pull: Pull) -> InputPin {
+        self.0.into_input(pull)
+    }
+}
+
+impl InputPin {
+    /// Switch the pin over to output mode.
+    pub fn into_output(self, initial_level: Level) -> OutputPin {
+        self.0.into_output(initial_level)
     }
 }
 
 //------------------------------------------------------------------------------
 // OS Interface Code
 //------------------------------------------------------------------------------
-use synchronization::interface::Mutex;
+use synchronization::interface::{Mutex, ReadWriteEx};
 
 impl driver::interface::DeviceDriver for GPIO {
     fn compatible(&self) -> &'static str {
@@ -236,7 +417,8 @@ impl driver::interface::DeviceDriver for GPIO {
     }
 
     unsafe fn init(&self) -> Result<(), &'static str> {
-        let virt_addr = memory::mmu::kernel_map_mmio(self.compatible(), &self.mmio_descriptor)?;
+        let virt_addr =
+            memory::mmu::kernel_map_mmio(self.compatible(), &self.mmio_descriptor.read(|x| *x))?;
 
         self.inner
             .lock(|inner| inner.init(Some(virt_addr.as_usize())))?;
@@ -257,3 +439,28 @@ impl driver::interface::DeviceDriver for GPIO {
         Some(addr)
     }
 }
+
+impl gpio::interface::OutputPin for OutputPin {
+    fn set(&mut self, level: Level) {
+        let number = self.0.number;
+
+        self.0
+            .gpio
+            .inner
+            .lock(|inner| inner.set_level(number, level))
+    }
+
+    fn level(&self) -> Level {
+        let number = self.0.number;
+
+        self.0.gpio.inner.lock(|inner| inner.level(number))
+    }
+}
+
+impl gpio::interface::InputPin for InputPin {
+    fn read(&self) -> Level {
+        let number = self.0.number;
+
+        self.0.gpio.inner.lock(|inner| inner.level(number))
+    }
+}
@@ -4,6 +4,7 @@
 
 //! Interrupt Controller Driver.
 
+mod local_ic;
 mod peripheral_ic;
 
 use crate::{driver, exception, memory};
@@ -28,13 +29,14 @@ pub type PeripheralIRQ =
 
 /// Used for the associated type of trait [`exception::asynchronous::interface::IRQManager`].
 #[derive(Copy, Clone)]
-pub enum IRQNumber {
+pub enum BCMIRQNumber {
     Local(LocalIRQ),
     Peripheral(PeripheralIRQ),
 }
 
 /// Representation of the Interrupt Controller.
 pub struct InterruptController {
+    local: local_ic::LocalIC,
     periph: peripheral_ic::PeripheralIC,
 }
 
@@ -71,6 +73,7 @@ impl Iterator for PendingIRQs {
 
 impl InterruptController {
     const MAX_LOCAL_IRQ_NUMBER: usize = 11;
+    const NUM_LOCAL_IRQS: usize = Self::MAX_LOCAL_IRQ_NUMBER + 1;
     const MAX_PERIPHERAL_IRQ_NUMBER: usize = 63;
     const NUM_PERIPHERAL_IRQS: usize = Self::MAX_PERIPHERAL_IRQ_NUMBER + 1;
 
@@ -80,13 +83,35 @@ impl InterruptController {
     ///
     /// - The user must ensure to provide correct MMIO descriptors.
     pub const unsafe fn new(
-        _local_mmio_descriptor: memory::mmu::MMIODescriptor,
+        local_mmio_descriptor: memory::mmu::MMIODescriptor,
         periph_mmio_descriptor: memory::mmu::MMIODescriptor,
     ) -> Self {
         Self {
+            local: local_ic::LocalIC::new(local_mmio_descriptor),
             periph: peripheral_ic::PeripheralIC::new(periph_mmio_descriptor),
         }
     }
+
+    /// Use a different MMIO descriptor for the local interrupt controller.
+    ///
+    /// # Safety
+    ///
+    /// - See `LocalIC::set_mmio_descriptor()`.
+    pub unsafe fn set_local_mmio_descriptor(&self, mmio_descriptor: memory::mmu::MMIODescriptor) {
+        self.local.set_mmio_descriptor(mmio_descriptor);
+    }
+
+    /// Use a different MMIO descriptor for the peripheral interrupt controller.
+    ///
+    /// # Safety
+    ///
+    /// - See `PeripheralIC::set_mmio_descriptor()`.
+    pub unsafe fn set_peripheral_mmio_descriptor(
+        &self,
+        mmio_descriptor: memory::mmu::MMIODescriptor,
+    ) {
+        self.periph.set_mmio_descriptor(mmio_descriptor);
+    }
 }
 
 //------------------------------------------------------------------------------
@@ -99,12 +124,13 @@ impl driver::interface::DeviceDriver for InterruptController {
     }
 
     unsafe fn init(&self) -> Result<(), &'static str> {
+        self.local.init()?;
         self.periph.init()
     }
 }
 
 impl exception::asynchronous::interface::IRQManager for InterruptController {
-    type IRQNumberType = IRQNumber;
+    type IRQNumberType = BCMIRQNumber;
 
     fn register_handler(
         &self,
@@ -112,15 +138,35 @@ impl exception::asynchronous::interface::IRQManager for InterruptController {
         descriptor: exception::asynchronous::IRQDescriptor,
     ) -> Result<(), &'static str> {
         match irq {
-            IRQNumber::Local(_) => unimplemented!("Local IRQ controller not implemented."),
-            IRQNumber::Peripheral(pirq) => self.periph.register_handler(pirq, descriptor),
+            BCMIRQNumber::Local(lirq) => self.local.register_handler(lirq, descriptor),
+            BCMIRQNumber::Peripheral(pirq) => self.periph.register_handler(pirq, descriptor),
         }
     }
 
     fn enable(&self, irq: Self::IRQNumberType) {
         match irq {
-            IRQNumber::Local(_) => unimplemented!("Local IRQ controller not implemented."),
-            IRQNumber::Peripheral(pirq) => self.periph.enable(pirq),
+            BCMIRQNumber::Local(lirq) => self.local.enable(lirq),
+            BCMIRQNumber::Peripheral(pirq) => self.periph.enable(pirq),
+        }
+    }
+
+    /// Peripheral IRQs can only be routed as a whole. Routing one of them moves all the others as
+    /// well.
+    fn set_target_core(
+        &self,
+        irq: Self::IRQNumberType,
+        target_core: usize,
+    ) -> Result<(), &'static str> {
+        match irq {
+            BCMIRQNumber::Local(_) => Err("Local IRQs can not be routed"),
+            BCMIRQNumber::Peripheral(_) => self.local.route_peripheral_irqs(target_core),
+        }
+    }
+
+    fn route_to_fiq(&self, irq: Self::IRQNumberType) -> Result<(), &'static str> {
+        match irq {
+            BCMIRQNumber::Local(_) => Err("Local IRQs can not be delivered as FIQ"),
+            BCMIRQNumber::Peripheral(pirq) => self.periph.route_to_fiq(pirq),
         }
     }
 
@@ -128,11 +174,21 @@ impl exception::asynchronous::interface::IRQManager for InterruptController {
         &'irq_context self,
         ic: &exception::asynchronous::IRQContext<'irq_context>,
     ) {
-        // It can only be a peripheral IRQ pending because enable() does not support local IRQs yet.
+        // Pending peripheral IRQs are signaled through a local IRQ source, and handled by the
+        // peripheral controller.
+        self.local.handle_pending_irqs(ic);
         self.periph.handle_pending_irqs(ic)
     }
 
+    fn handle_pending_fiq<'irq_context>(
+        &'irq_context self,
+        ic: &exception::asynchronous::IRQContext<'irq_context>,
+    ) {
+        self.periph.handle_pending_fiq(ic)
+    }
+
     fn print_handler(&self) {
+        self.local.print_handler();
         self.periph.print_handler();
     }
 }
@@ -0,0 +1,232 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Local Interrupt Controller Driver.
+//!
+//! The local interrupt controller routes the peripheral interrupts to the cores, and delivers the
+//! per-core interrupts, e.g. those of the ARM generic timer. Only the timer interrupts can be
+//! enabled so far.
+
+use super::{InterruptController, LocalIRQ, PendingIRQs};
+use crate::{
+    bsp::device_driver::common::MMIODerefWrapper,
+    cpu, driver, exception, memory, synchronization,
+    synchronization::{InitStateLock, SpinLock},
+};
+use tock_registers::{
+    interfaces::{ReadWriteable, Readable},
+    register_bitfields, register_structs,
+    registers::{ReadOnly, ReadWrite},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+register_bitfields! {
+    u32,
+
+    /// GPU Interrupts Routing
+    GPU_INT_ROUTING [
+        /// The core that receives the peripheral FIQs.
+        FIQ OFFSET(2) NUMBITS(2) [],
+
+        /// The core that receives the peripheral IRQs.
+        IRQ OFFSET(0) NUMBITS(2) []
+    ]
+}
+
+register_structs! {
+    #[allow(non_snake_case)]
+    RegisterBlock {
+        (0x00 => _reserved1),
+        (0x0C => GPU_INT_ROUTING: ReadWrite<u32, GPU_INT_ROUTING::Register>),
+        (0x10 => _reserved2),
+        (0x40 => CORE_TIMER_INT_CONTROL: [ReadWrite<u32>; 4]),
+        (0x50 => _reserved3),
+        (0x60 => CORE_IRQ_SOURCE: [ReadOnly<u32>; 4]),
+        (0x70 => @END),
+    }
+}
+
+/// Abstraction for the associated MMIO registers.
+type Registers = MMIODerefWrapper<RegisterBlock>;
+
+type HandlerTable =
+    [Option<exception::asynchronous::IRQDescriptor>; InterruptController::NUM_LOCAL_IRQS];
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Representation of the local interrupt controller.
+pub struct LocalIC {
+    mmio_descriptor: InitStateLock<memory::mmu::MMIODescriptor>,
+    registers: SpinLock<Registers>,
+
+    /// Stores registered IRQ handlers. Writable only during kernel init. RO afterwards.
+    handler_table: InitStateLock<HandlerTable>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl LocalIC {
+    const NUM_CORES: usize = 4;
+
+    /// The local IRQs 0..=3 are the interrupts of the ARM generic timer.
+    const MAX_TIMER_IRQ_NUMBER: usize = 3;
+
+    /// Pending peripheral IRQs show up as this local IRQ.
+    pub const PERIPHERAL_IRQ_SOURCE: usize = 8;
+
+    /// Create an instance.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide a correct MMIO descriptor.
+    pub const unsafe fn new(mmio_descriptor: memory::mmu::MMIODescriptor) -> Self {
+        Self {
+            mmio_descriptor: InitStateLock::new(mmio_descriptor),
+            registers: SpinLock::new(Registers::new(mmio_descriptor.start_addr().as_usize())),
+            handler_table: InitStateLock::new([None; InterruptController::NUM_LOCAL_IRQS]),
+        }
+    }
+
+    /// Use a different MMIO descriptor than the one the instance was created with, e.g. the one
+    /// that the device tree tells.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide a correct MMIO descriptor.
+    /// - Only has an effect if called before `init()`.
+    pub unsafe fn set_mmio_descriptor(&self, mmio_descriptor: memory::mmu::MMIODescriptor) {
+        self.mmio_descriptor.write(|x| *x = mmio_descriptor);
+    }
+
+    /// Query the list of IRQs pending on the executing core.
+    fn pending_irqs(&self) -> PendingIRQs {
+        let core: usize = cpu::smp::core_id();
+
+        self.registers
+            .lock(|regs| PendingIRQs::new(u64::from(regs.CORE_IRQ_SOURCE[core].get())))
+    }
+
+    /// Deliver the peripheral IRQs to the given core.
+    ///
+    /// The hardware routes all peripheral IRQs as a whole, not individually.
+    pub fn route_peripheral_irqs(&self, target_core: usize) -> Result<(), &'static str> {
+        if target_core >= Self::NUM_CORES {
+            return Err("Target core does not exist");
+        }
+
+        self.registers.lock(|regs| {
+            regs.GPU_INT_ROUTING
+                .modify(GPU_INT_ROUTING::IRQ.val(target_core as u32))
+        });
+
+        Ok(())
+    }
+}
+
+//------------------------------------------------------------------------------
+// OS Interface Code
+//------------------------------------------------------------------------------
+use synchronization::interface::{Mutex, ReadWriteEx};
+
+impl driver::interface::DeviceDriver for LocalIC {
+    fn compatible(&self) -> &'static str {
+        "BCM Local Interrupt Controller"
+    }
+
+    unsafe fn init(&self) -> Result<(), &'static str> {
+        let virt_addr =
+            memory::mmu::kernel_map_mmio(self.compatible(), &self.mmio_descriptor.read(|x| *x))?
+                .as_usize();
+
+        self.registers
+            .lock(|regs| *regs = Registers::new(virt_addr));
+
+        Ok(())
+    }
+}
+
+impl exception::asynchronous::interface::IRQManager for LocalIC {
+    type IRQNumberType = LocalIRQ;
+
+    fn register_handler(
+        &self,
+        irq: Self::IRQNumberType,
+        descriptor: exception::asynchronous::IRQDescriptor,
+    ) -> Result<(), &'static str> {
+        self.handler_table.write(|table| {
+            let irq_number = irq.get();
+
+            if irq_number == Self::PERIPHERAL_IRQ_SOURCE {
+                return Err("Register peripheral IRQs with the peripheral controller");
+            }
+
+            if table[irq_number].is_some() {
+                return Err("IRQ handler already registered");
+            }
+
+            table[irq_number] = Some(descriptor);
+
+            Ok(())
+        })
+    }
+
+    /// Enable a timer interrupt for the executing core.
+    fn enable(&self, irq: Self::IRQNumberType) {
+        let irq_number = irq.get();
+        assert!(
+            irq_number <= Self::MAX_TIMER_IRQ_NUMBER,
+            "Only the timer IRQs can be enabled"
+        );
+
+        let core: usize = cpu::smp::core_id();
+        self.registers.lock(|regs| {
+            let control = &regs.CORE_TIMER_INT_CONTROL[core];
+
+            // Bits 0..=3 are the IRQ enables of the four timer interrupts.
+            control.set(control.get() | (1 << irq_number));
+        });
+    }
+
+    /// Handle the pending local IRQs, except the peripheral IRQ source.
+    fn handle_pending_irqs<'irq_context>(
+        &'irq_context self,
+        _ic: &exception::asynchronous::IRQContext<'irq_context>,
+    ) {
+        self.handler_table.read(|table| {
+            for irq_number in self
+                .pending_irqs()
+                .filter(|irq| *irq != Self::PERIPHERAL_IRQ_SOURCE)
+            {
+                match table[irq_number] {
+                    None => panic!("No handler registered for local IRQ {}", irq_number),
+                    Some(descriptor) => {
+                        // Call the IRQ handler. Panics on failure.
+                        descriptor.handler.handle().expect("Error handling IRQ");
+                    }
+                }
+            }
+        })
+    }
+
+    fn print_handler(&self) {
+        use crate::info;
+
+        info!("      Local handler:");
+
+        self.handler_table.read(|table| {
+            for (i, opt) in table.iter().enumerate() {
+                if let Some(handler) = opt {
+                    info!("            {: >3}. {}", i, handler.name);
+                }
+            }
+        });
+    }
+}
@@ -8,11 +8,11 @@ use super::{InterruptController, PendingIRQs, PeripheralIRQ};
 use crate::{
     bsp::device_driver::common::MMIODerefWrapper,
     driver, exception, memory, synchronization,
-    synchronization::{IRQSafeNullLock, InitStateLock},
+    synchronization::{InitStateLock, SpinLock},
 };
 use tock_registers::{
     interfaces::{Readable, Writeable},
-    register_structs,
+    register_bitfields, register_structs,
     registers::{ReadOnly, WriteOnly},
 };
 
@@ -20,10 +20,24 @@ use tock_registers::{
 // Private Definitions
 //--------------------------------------------------------------------------------------------------
 
+register_bitfields! {
+    u32,
+
+    /// FIQ control
+    FIQ_CONTROL [
+        /// Deliver the selected source as FIQ.
+        Enable OFFSET(7) NUMBITS(1) [],
+
+        /// The interrupt source. 0..=63 are the peripheral IRQs.
+        Source OFFSET(0) NUMBITS(7) []
+    ]
+}
+
 register_structs! {
     #[allow(non_snake_case)]
     WORegisterBlock {
         (0x00 => _reserved1),
+        (0x0C => FIQ_CONTROL: WriteOnly<u32, FIQ_CONTROL::Register>),
         (0x10 => ENABLE_1: WriteOnly<u32>),
         (0x14 => ENABLE_2: WriteOnly<u32>),
         (0x24 => @END),
@@ -55,16 +69,19 @@ type HandlerTable =
 
 /// Representation of the peripheral interrupt controller.
 pub struct PeripheralIC {
-    mmio_descriptor: memory::mmu::MMIODescriptor,
+    mmio_descriptor: InitStateLock<memory::mmu::MMIODescriptor>,
 
     /// Access to write registers is guarded with a lock.
-    wo_registers: IRQSafeNullLock<WriteOnlyRegisters>,
+    wo_registers: SpinLock<WriteOnlyRegisters>,
 
     /// Register read access is unguarded.
     ro_registers: InitStateLock<ReadOnlyRegisters>,
 
     /// Stores registered IRQ handlers. Writable only during kernel init. RO afterwards.
     handler_table: InitStateLock<HandlerTable>,
+
+    /// The IRQ that is delivered as FIQ, if any. Writable only during kernel init. RO afterwards.
+    fiq: InitStateLock<Option<PeripheralIRQ>>,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -81,13 +98,25 @@ impl PeripheralIC {
         let addr = mmio_descriptor.start_addr().as_usize();
 
         Self {
-            mmio_descriptor,
-            wo_registers: IRQSafeNullLock::new(WriteOnlyRegisters::new(addr)),
+            mmio_descriptor: InitStateLock::new(mmio_descriptor),
+            wo_registers: SpinLock::new(WriteOnlyRegisters::new(addr)),
             ro_registers: InitStateLock::new(ReadOnlyRegisters::new(addr)),
             handler_table: InitStateLock::new([None; InterruptController::NUM_PERIPHERAL_IRQS]),
+            fiq: InitStateLock::new(None),
         }
     }
 
+    /// Use a different MMIO descriptor than the one the instance was created with, e.g. the one
+    /// that the device tree tells.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide a correct MMIO descriptor.
+    /// - Only has an effect if called before `init()`.
+    pub unsafe fn set_mmio_descriptor(&self, mmio_descriptor: memory::mmu::MMIODescriptor) {
+        self.mmio_descriptor.write(|x| *x = mmio_descriptor);
+    }
+
     /// Query the list of pending IRQs.
     fn pending_irqs(&self) -> PendingIRQs {
         self.ro_registers.read(|regs| {
@@ -111,7 +140,8 @@ impl driver::interface::DeviceDriver for PeripheralIC {
 
     unsafe fn init(&self) -> Result<(), &'static str> {
         let virt_addr =
-            memory::mmu::kernel_map_mmio(self.compatible(), &self.mmio_descriptor)?.as_usize();
+            memory::mmu::kernel_map_mmio(self.compatible(), &self.mmio_descriptor.read(|x| *x))?
+                .as_usize();
 
         self.wo_registers
             .lock(|regs| *regs = WriteOnlyRegisters::new(virt_addr));
@@ -159,12 +189,30 @@ impl exception::asynchronous::interface::IRQManager for PeripheralIC {
         });
     }
 
+    /// Only a single IRQ can be delivered as FIQ. Routing another one replaces the previous choice.
+    fn route_to_fiq(&self, irq: Self::IRQNumberType) -> Result<(), &'static str> {
+        if self.handler_table.read(|table| table[irq.get()].is_none()) {
+            return Err("No handler registered for FIQ");
+        }
+
+        self.fiq.write(|fiq| *fiq = Some(irq));
+        self.wo_registers.lock(|regs| {
+            regs.FIQ_CONTROL
+                .write(FIQ_CONTROL::Enable::SET + FIQ_CONTROL::Source.val(irq.get() as u32));
+        });
+
+        Ok(())
+    }
+
     fn handle_pending_irqs<'irq_context>(
         &'irq_context self,
         _ic: &exception::asynchronous::IRQContext<'irq_context>,
     ) {
+        let fiq = self.fiq.read(|fiq| fiq.map(|irq| irq.get()));
+
         self.handler_table.read(|table| {
-            for irq_number in self.pending_irqs() {
+            // The FIQ source is serviced from the FIQ vector.
+            for irq_number in self.pending_irqs().filter(|irq| Some(*irq) != fiq) {
                 match table[irq_number] {
                     None => panic!("No handler registered for IRQ {}", irq_number),
                     Some(descriptor) => {
@@ -176,6 +224,23 @@ impl exception::asynchronous::interface::IRQManager for PeripheralIC {
         })
     }
 
+    fn handle_pending_fiq<'irq_context>(
+        &'irq_context self,
+        _ic: &exception::asynchronous::IRQContext<'irq_context>,
+    ) {
+        let fiq = match self.fiq.read(|fiq| *fiq) {
+            None => return,
+            Some(x) => x,
+        };
+
+        self.handler_table.read(|table| {
+            if let Some(descriptor) = table[fiq.get()] {
+                // Call the FIQ handler. Panics on failure.
+                descriptor.handler.handle().expect("Error handling FIQ");
+            }
+        })
+    }
+
     fn print_handler(&self) {
         use crate::info;
 
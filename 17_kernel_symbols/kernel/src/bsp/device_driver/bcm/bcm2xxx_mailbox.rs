@@ -0,0 +1,270 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! VideoCore mailbox driver.
+//!
+//! Only the property channel is supported, which is used to query the firmware.
+//!
+//! # Resources
+//!
+//! - <https://github.com/raspberrypi/firmware/wiki/Mailboxes>
+//! - <https://github.com/raspberrypi/firmware/wiki/Mailbox-property-interface>
+
+use crate::{
+    bsp::device_driver::common::MMIODerefWrapper,
+    cpu, driver,
+    memory::{self, Address, Virtual},
+    synchronization,
+    synchronization::{InitStateLock, SpinLock},
+    time,
+};
+use core::{mem::size_of, time::Duration};
+use tock_registers::{
+    interfaces::{Readable, Writeable},
+    register_bitfields, register_structs,
+    registers::{ReadOnly, WriteOnly},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+register_bitfields! {
+    u32,
+
+    /// Mailbox Status Register.
+    STATUS [
+        /// The mailbox cannot take more messages.
+        FULL OFFSET(31) NUMBITS(1) [],
+
+        /// The mailbox holds no messages.
+        EMPTY OFFSET(30) NUMBITS(1) []
+    ],
+
+    /// Format of messages passed through the mailbox.
+    MESSAGE [
+        /// The upper 28 bits of a 16 byte aligned buffer address.
+        DATA OFFSET(4) NUMBITS(28) [],
+
+        /// The mailbox channel.
+        CHANNEL OFFSET(0) NUMBITS(4) [
+            Property = 8
+        ]
+    ]
+}
+
+// Mailbox 0 is used to receive responses from the VideoCore, mailbox 1 to send requests.
+register_structs! {
+    #[allow(non_snake_case)]
+    RegisterBlock {
+        (0x00 => READ: ReadOnly<u32, MESSAGE::Register>),
+        (0x04 => _reserved1),
+        (0x18 => STATUS0: ReadOnly<u32, STATUS::Register>),
+        (0x1C => _reserved2),
+        (0x20 => WRITE: WriteOnly<u32, MESSAGE::Register>),
+        (0x24 => _reserved3),
+        (0x38 => STATUS1: ReadOnly<u32, STATUS::Register>),
+        (0x3C => @END),
+    }
+}
+
+/// Abstraction for the associated MMIO registers.
+type Registers = MMIODerefWrapper<RegisterBlock>;
+
+const PROPERTY_BUFFER_WORDS: usize = 32;
+
+/// Property buffers must be 16 byte aligned. Aligning to a cache line additionally ensures that
+/// cache maintenance on the buffer does not affect neighbouring data.
+#[repr(C, align(64))]
+struct PropertyBuffer([u32; PROPERTY_BUFFER_WORDS]);
+
+const CODE_REQUEST: u32 = 0x0000_0000;
+const CODE_RESPONSE_SUCCESS: u32 = 0x8000_0000;
+const TAG_END: u32 = 0;
+
+/// Set by the firmware in the tag's request/response code once it processed the tag.
+const TAG_RESPONSE: u32 = 0x8000_0000;
+const TAG_GET_CLOCK_RATE: u32 = 0x0003_0002;
+
+/// The VideoCore sees DRAM at this bus address alias, bypassing its L2 cache.
+const VC_DRAM_ALIAS: usize = 0xC000_0000;
+
+/// How long the firmware may take to accept a request and to answer it.
+const FIRMWARE_TIMEOUT: Duration = Duration::from_millis(100);
+
+struct MailboxInner {
+    registers: Registers,
+    buffer: PropertyBuffer,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Clock identifiers of the property interface.
+#[allow(missing_docs)]
+#[derive(Copy, Clone)]
+#[repr(u32)]
+pub enum ClockId {
+    Emmc = 1,
+    Uart = 2,
+    Arm = 3,
+    Core = 4,
+}
+
+/// Representation of the mailbox.
+pub struct Mailbox {
+    mmio_descriptor: InitStateLock<memory::mmu::MMIODescriptor>,
+    inner: SpinLock<MailboxInner>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl MailboxInner {
+    const fn new(mmio_start_addr: usize) -> Self {
+        Self {
+            registers: Registers::new(mmio_start_addr),
+            buffer: PropertyBuffer([0; PROPERTY_BUFFER_WORDS]),
+        }
+    }
+
+    fn init(&mut self, new_mmio_start_addr: usize) {
+        self.registers = Registers::new(new_mmio_start_addr);
+    }
+
+    /// Pass the property buffer to the firmware and wait for the answer.
+    fn call_property_channel(&mut self) -> Result<(), &'static str> {
+        let virt_addr = self.buffer.0.as_ptr() as usize;
+        let phys_addr =
+            memory::mmu::try_kernel_virt_addr_to_phys_addr(Address::<Virtual>::new(virt_addr))?;
+        let bus_addr = (phys_addr.as_usize() | VC_DRAM_ALIAS) as u32;
+
+        // Make the request visible to the VideoCore.
+        cpu::clean_dcache_range(virt_addr, size_of::<PropertyBuffer>());
+
+        time::with_timeout(FIRMWARE_TIMEOUT, || {
+            (!self.registers.STATUS1.is_set(STATUS::FULL)).then(|| ())
+        })
+        .map_err(|_| "Mailbox: Timeout while sending the request")?;
+        self.registers
+            .WRITE
+            .write(MESSAGE::DATA.val(bus_addr >> 4) + MESSAGE::CHANNEL::Property);
+
+        // Responses for other channels are dropped.
+        time::with_timeout(FIRMWARE_TIMEOUT, || {
+            let response = !self.registers.STATUS0.is_set(STATUS::EMPTY)
+                && self.registers.READ.matches_all(MESSAGE::CHANNEL::Property);
+
+            response.then(|| ())
+        })
+        .map_err(|_| "Mailbox: Timeout while waiting for the response")?;
+
+        cpu::invalidate_dcache_range(virt_addr, size_of::<PropertyBuffer>());
+
+        if self.buffer.0[1] != CODE_RESPONSE_SUCCESS {
+            return Err("Mailbox: Property request failed");
+        }
+
+        Ok(())
+    }
+
+    /// Execute a property request consisting of a single tag and return the value buffer.
+    ///
+    /// `request` is copied into the value buffer, which is sized to hold `response_words`.
+    fn property(
+        &mut self,
+        tag: u32,
+        request: &[u32],
+        response_words: usize,
+    ) -> Result<&[u32], &'static str> {
+        let value_words = request.len().max(response_words);
+        // Header, tag header, values and end tag.
+        let total_words = 2 + 3 + value_words + 1;
+        if total_words > PROPERTY_BUFFER_WORDS {
+            return Err("Mailbox: Property request too big");
+        }
+
+        let b = &mut self.buffer.0;
+        b.fill(0);
+        b[0] = (total_words * 4) as u32;
+        b[1] = CODE_REQUEST;
+        b[2] = tag;
+        b[3] = (value_words * 4) as u32;
+        b[4] = 0;
+        b[5..(5 + request.len())].copy_from_slice(request);
+        b[5 + value_words] = TAG_END;
+
+        self.call_property_channel()?;
+
+        if self.buffer.0[4] & TAG_RESPONSE == 0 {
+            return Err("Mailbox: Firmware did not process the tag");
+        }
+
+        Ok(&self.buffer.0[5..(5 + value_words)])
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl Mailbox {
+    /// Create an instance.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide a correct MMIO descriptor.
+    pub const unsafe fn new(mmio_descriptor: memory::mmu::MMIODescriptor) -> Self {
+        Self {
+            mmio_descriptor: InitStateLock::new(mmio_descriptor),
+            inner: SpinLock::new(MailboxInner::new(mmio_descriptor.start_addr().as_usize())),
+        }
+    }
+
+    /// Use a different MMIO descriptor than the one the instance was created with, e.g. the one
+    /// that the device tree tells.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide a correct MMIO descriptor.
+    /// - Only has an effect if called before `init()`.
+    pub unsafe fn set_mmio_descriptor(&self, mmio_descriptor: memory::mmu::MMIODescriptor) {
+        self.mmio_descriptor.write(|x| *x = mmio_descriptor);
+    }
+
+    /// Query the firmware for the current rate of a clock, in Hz.
+    pub fn clock_rate(&self, clock: ClockId) -> Result<u32, &'static str> {
+        self.inner.lock(|inner| {
+            let response = inner.property(TAG_GET_CLOCK_RATE, &[clock as u32], 2)?;
+
+            match (response[0], response[1]) {
+                (id, _) if id != clock as u32 => Err("Mailbox: Response for a different clock"),
+                (_, 0) => Err("Mailbox: Clock does not exist"),
+                (_, rate) => Ok(rate),
+            }
+        })
+    }
+}
+
+//------------------------------------------------------------------------------
+// OS Interface Code
+//------------------------------------------------------------------------------
+use synchronization::interface::{Mutex, ReadWriteEx};
+
+impl driver::interface::DeviceDriver for Mailbox {
+    fn compatible(&self) -> &'static str {
+        "BCM VideoCore Mailbox"
+    }
+
+    unsafe fn init(&self) -> Result<(), &'static str> {
+        let virt_addr =
+            memory::mmu::kernel_map_mmio(self.compatible(), &self.mmio_descriptor.read(|x| *x))?;
+
+        self.inner.lock(|inner| inner.init(virt_addr.as_usize()));
+
+        Ok(())
+    }
+}
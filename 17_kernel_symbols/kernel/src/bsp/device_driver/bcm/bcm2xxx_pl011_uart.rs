@@ -10,12 +10,16 @@
 //! - <https://developer.arm.com/documentation/ddi0183/latest>
 
 use crate::{
-    bsp, bsp::device_driver::common::MMIODerefWrapper, console, cpu, driver, exception, memory,
-    synchronization, synchronization::IRQSafeNullLock,
+    bsp,
+    bsp::device_driver::common::MMIODerefWrapper,
+    console, cpu, driver, exception, memory, scheduler, synchronization,
+    synchronization::{InitStateLock, TicketLock, WaitQueue},
+    task, time,
 };
 use core::{
     fmt,
     sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
 };
 use tock_registers::{
     interfaces::{Readable, Writeable},
@@ -104,6 +108,21 @@ register_bitfields! {
 
     /// Control Register.
     CR [
+        /// CTS hardware flow control enable. If this bit is set to 1, CTS hardware flow control is
+        /// enabled. Data is only transmitted when the nUARTCTS signal is asserted.
+        CTSEN OFFSET(15) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1
+        ],
+
+        /// RTS hardware flow control enable. If this bit is set to 1, RTS hardware flow control is
+        /// enabled. Data is only requested when there is space in the receive FIFO for it to be
+        /// received.
+        RTSEN OFFSET(14) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1
+        ],
+
         /// Receive enable. If this bit is set to 1, the receive section of the UART is enabled.
         /// Data reception occurs for either UART signals or SIR signals depending on the setting of
         /// the SIREN bit. When the UART is disabled in the middle of reception, it completes the
@@ -122,6 +141,13 @@ register_bitfields! {
             Enabled = 1
         ],
 
+        /// Loopback enable. If this bit is set to 1, the UARTTXD path is fed through to the
+        /// UARTRXD path.
+        LBE OFFSET(7) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1
+        ],
+
         /// UART enable:
         ///
         /// 0 = UART is disabled. If the UART is disabled in the middle of transmission or
@@ -197,10 +223,10 @@ register_structs! {
         (0x04 => _reserved1),
         (0x18 => FR: ReadOnly<u32, FR::Register>),
         (0x1c => _reserved2),
-        (0x24 => IBRD: WriteOnly<u32, IBRD::Register>),
-        (0x28 => FBRD: WriteOnly<u32, FBRD::Register>),
+        (0x24 => IBRD: ReadWrite<u32, IBRD::Register>),
+        (0x28 => FBRD: ReadWrite<u32, FBRD::Register>),
         (0x2c => LCR_H: WriteOnly<u32, LCR_H::Register>),
-        (0x30 => CR: WriteOnly<u32, CR::Register>),
+        (0x30 => CR: ReadWrite<u32, CR::Register>),
         (0x34 => IFLS: ReadWrite<u32, IFLS::Register>),
         (0x38 => IMSC: ReadWrite<u32, IMSC::Register>),
         (0x3C => _reserved3),
@@ -213,6 +239,25 @@ register_structs! {
 /// Abstraction for the associated MMIO registers.
 type Registers = MMIODerefWrapper<RegisterBlock>;
 
+/// Rate of the UART clock, as set by `init_uart_clock` in `config.txt`. QEMU ignores it.
+const DEFAULT_CLOCK_RATE_HZ: u32 = 48_000_000;
+
+const DEFAULT_BAUD_RATE: u32 = 921_600;
+
+/// Enough to send a full TX FIFO at low baud rates.
+const FLUSH_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The RX FIFO holds up to 16 characters, or 32 in newer revisions of the PL011.
+const RX_FIFO_DEPTH: usize = 32;
+
+/// The byte sent by the loopback self test. Alternating bits catch stuck data lines.
+#[cfg(feature = "post")]
+const LOOPBACK_PATTERN: u32 = 0x55;
+
+/// Enough to send a single byte at low baud rates.
+#[cfg(feature = "post")]
+const LOOPBACK_TIMEOUT: Duration = Duration::from_millis(10);
+
 #[derive(PartialEq)]
 enum BlockingMode {
     Blocking,
@@ -225,6 +270,9 @@ enum BlockingMode {
 
 pub struct PL011UartInner {
     registers: Registers,
+    clock_rate_hz: u32,
+    baud_rate: u32,
+    flow_control: bool,
     chars_written: usize,
     chars_read: usize,
 }
@@ -234,10 +282,48 @@ pub use PL011UartInner as PanicUart;
 
 /// Representation of the UART.
 pub struct PL011Uart {
-    mmio_descriptor: memory::mmu::MMIODescriptor,
+    mmio_descriptor: InitStateLock<memory::mmu::MMIODescriptor>,
     virt_mmio_start_addr: AtomicUsize,
-    inner: IRQSafeNullLock<PL011UartInner>,
-    irq_number: bsp::device_driver::IRQNumber,
+    inner: TicketLock<PL011UartInner>,
+    irq_number: InitStateLock<bsp::exception::asynchronous::IRQNumber>,
+
+    /// Threads blocked in `read_char()`.
+    rx_waiters: WaitQueue,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// Compute the integer and fractional baud rate divisors.
+///
+/// The divisor is `clock_rate / (16 * baud_rate)`, and its fractional part is stored in units of
+/// 1/64. For example, with the default 48 MHz clock and 921_600 baud:
+///
+/// `(48_000_000 / 16) / 921_600 = 3.2552083`.
+///
+/// This means the integer part is `3` and goes into the `IBRD`. `FBRD` calculation according to
+/// the PL011 Technical Reference Manual: `INTEGER((0.2552083 * 64) + 0.5) = 16`.
+///
+/// Therefore, the generated baud rate divider is: `3 + 16/64 = 3.25`. Which results in a
+/// generated baud rate of `48_000_000 / (16 * 3.25) = 923_077`, an error of 0.16%.
+fn baud_rate_divisors(clock_rate_hz: u32, baud_rate: u32) -> Result<(u32, u32), &'static str> {
+    if baud_rate == 0 {
+        return Err("Baud rate must not be zero");
+    }
+
+    // Divisor in units of 1/64, rounded to nearest: (clock_rate * 64) / (16 * baud_rate).
+    let clock_rate_hz = clock_rate_hz as u64;
+    let baud_rate = baud_rate as u64;
+    let divisor = (clock_rate_hz * 4 + baud_rate / 2) / baud_rate;
+
+    let ibrd = divisor >> 6;
+    let fbrd = divisor & 0x3F;
+    if ibrd == 0 || ibrd > 0xFFFF {
+        return Err("Baud rate not reachable with the current UART clock");
+    }
+
+    Ok((ibrd as u32, fbrd as u32))
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -253,6 +339,9 @@ impl PL011UartInner {
     pub const unsafe fn new(mmio_start_addr: usize) -> Self {
         Self {
             registers: Registers::new(mmio_start_addr),
+            clock_rate_hz: DEFAULT_CLOCK_RATE_HZ,
+            baud_rate: DEFAULT_BAUD_RATE,
+            flow_control: false,
             chars_written: 0,
             chars_read: 0,
         }
@@ -260,21 +349,8 @@ impl PL011UartInner {
 
     /// Set up baud rate and characteristics.
     ///
-    /// This results in 8N1 and 921_600 baud.
-    ///
-    /// The calculation for the BRD is (we set the clock to 48 MHz in config.txt):
-    /// `(48_000_000 / 16) / 921_600 = 3.2552083`.
-    ///
-    /// This means the integer part is `3` and goes into the `IBRD`.
-    /// The fractional part is `0.2552083`.
-    ///
-    /// `FBRD` calculation according to the PL011 Technical Reference Manual:
-    /// `INTEGER((0.2552083 * 64) + 0.5) = 16`.
-    ///
-    /// Therefore, the generated baud rate divider is: `3 + 16/64 = 3.25`. Which results in a
-    /// genrated baud rate of `48_000_000 / (16 * 3.25) = 923_077`.
-    ///
-    /// Error = `((923_077 - 921_600) / 921_600) * 100 = 0.16%`.
+    /// This results in 8N1 and the most recently configured baud rate, which is
+    /// `DEFAULT_BAUD_RATE` unless changed with `set_baud_rate()`.
     ///
     /// # Safety
     ///
@@ -284,6 +360,8 @@ impl PL011UartInner {
             self.registers = Registers::new(addr);
         }
 
+        let (ibrd, fbrd) = baud_rate_divisors(self.clock_rate_hz, self.baud_rate)?;
+
         // Execution can arrive here while there are still characters queued in the TX FIFO and
         // actively being sent out by the UART hardware. If the UART is turned off in this case,
         // those queued characters would be lost.
@@ -291,8 +369,9 @@ impl PL011UartInner {
         // For example, this can happen during runtime on a call to panic!(), because panic!()
         // initializes its own UART instance and calls init().
         //
-        // Hence, flush first to ensure all pending characters are transmitted.
-        self.flush();
+        // Hence, flush first to ensure all pending characters are transmitted. If that does not
+        // finish in time, the characters are lost anyway.
+        let _ = self.flush();
 
         // Turn the UART off temporarily.
         self.registers.CR.set(0);
@@ -300,18 +379,8 @@ impl PL011UartInner {
         // Clear all pending interrupts.
         self.registers.ICR.write(ICR::ALL::CLEAR);
 
-        // From the PL011 Technical Reference Manual:
-        //
-        // The LCR_H, IBRD, and FBRD registers form the single 30-bit wide LCR Register that is
-        // updated on a single write strobe generated by a LCR_H write. So, to internally update the
-        // contents of IBRD or FBRD, a LCR_H write must always be performed at the end.
-        //
         // Set the baud rate, 8N1 and FIFO enabled.
-        self.registers.IBRD.write(IBRD::BAUD_DIVINT.val(3));
-        self.registers.FBRD.write(FBRD::BAUD_DIVFRAC.val(16));
-        self.registers
-            .LCR_H
-            .write(LCR_H::WLEN::EightBit + LCR_H::FEN::FifosEnabled);
+        self.program_line_control(ibrd, fbrd);
 
         // Set RX FIFO fill level at 1/8.
         self.registers.IFLS.write(IFLS::RXIFLSEL::OneEigth);
@@ -322,13 +391,125 @@ impl PL011UartInner {
             .write(IMSC::RXIM::Enabled + IMSC::RTIM::Enabled);
 
         // Turn the UART on.
-        self.registers
-            .CR
-            .write(CR::UARTEN::Enabled + CR::TXE::Enabled + CR::RXE::Enabled);
+        self.enable();
 
         Ok(())
     }
 
+    /// Use a different clock rate and baud rate than `DEFAULT_CLOCK_RATE_HZ` and
+    /// `DEFAULT_BAUD_RATE`, for UARTs that are fed by a different clock.
+    ///
+    /// Does not touch the hardware, so it only has an effect if called before `init()`.
+    pub fn set_initial_rates(&mut self, clock_rate_hz: u32, baud_rate: u32) {
+        self.clock_rate_hz = clock_rate_hz;
+        self.baud_rate = baud_rate;
+    }
+
+    /// Change the baud rate at runtime.
+    ///
+    /// Pending output is sent out with the old rate first. Input that is still sitting in the RX
+    /// FIFO is discarded, because the other side is probably switching rates at the same time.
+    pub fn set_baud_rate(&mut self, baud_rate: u32) -> Result<(), &'static str> {
+        let (ibrd, fbrd) = baud_rate_divisors(self.clock_rate_hz, baud_rate)?;
+
+        self.flush()?;
+        self.registers.CR.set(0);
+        self.drain_rx_fifo();
+
+        self.program_line_control(ibrd, fbrd);
+        self.enable();
+        self.baud_rate = baud_rate;
+
+        Ok(())
+    }
+
+    /// Tell the driver the rate of the clock that feeds the UART and adapt the baud rate divisors
+    /// accordingly.
+    ///
+    /// The default is `DEFAULT_CLOCK_RATE_HZ`.
+    pub fn set_clock_rate(&mut self, clock_rate_hz: u32) -> Result<(), &'static str> {
+        let old_clock_rate_hz = self.clock_rate_hz;
+
+        self.clock_rate_hz = clock_rate_hz;
+        if let Err(x) = self.set_baud_rate(self.baud_rate) {
+            self.clock_rate_hz = old_clock_rate_hz;
+            return Err(x);
+        }
+
+        Ok(())
+    }
+
+    /// Turn RTS/CTS hardware flow control on or off.
+    ///
+    /// The BSP must route the RTS and CTS lines to the UART before enabling it. Pending output is
+    /// sent out before the switch, input that is still sitting in the RX FIFO is discarded.
+    pub fn set_flow_control(&mut self, enabled: bool) {
+        // A flush that does not finish might be the reason for turning flow control off.
+        let _ = self.flush();
+
+        // From the PL011 Technical Reference Manual, for reprogramming the Control Register:
+        //
+        // 1. Disable the UART.
+        // 2. Wait for the end of transmission or reception of the current character.
+        // 3. Flush the transmit FIFO by setting the FEN bit to 0 in the Line Control Register.
+        // 4. Reprogram the Control Register.
+        // 5. Enable the UART.
+        //
+        // Writing LCR_H latches IBRD and FBRD again, which still hold the current divisors.
+        self.registers.CR.set(0);
+        let _ = self.flush();
+        self.registers
+            .LCR_H
+            .write(LCR_H::WLEN::EightBit + LCR_H::FEN::FifosDisabled);
+        self.drain_rx_fifo();
+        self.registers
+            .LCR_H
+            .write(LCR_H::WLEN::EightBit + LCR_H::FEN::FifosEnabled);
+
+        self.flow_control = enabled;
+        self.enable();
+    }
+
+    /// Discard the input that waits in the RX FIFO.
+    ///
+    /// Reads at most a FIFO's worth of characters, so that a receiver that never reports an empty
+    /// FIFO can not hang the caller.
+    fn drain_rx_fifo(&mut self) {
+        for _ in 0..RX_FIFO_DEPTH {
+            if self.registers.FR.matches_all(FR::RXFE::SET) {
+                break;
+            }
+
+            self.registers.DR.get();
+        }
+    }
+
+    /// Program the baud rate divisors, 8N1 and FIFO enabled.
+    fn program_line_control(&mut self, ibrd: u32, fbrd: u32) {
+        // From the PL011 Technical Reference Manual:
+        //
+        // The LCR_H, IBRD, and FBRD registers form the single 30-bit wide LCR Register that is
+        // updated on a single write strobe generated by a LCR_H write. So, to internally update the
+        // contents of IBRD or FBRD, a LCR_H write must always be performed at the end.
+        self.registers.IBRD.write(IBRD::BAUD_DIVINT.val(ibrd));
+        self.registers.FBRD.write(FBRD::BAUD_DIVFRAC.val(fbrd));
+        self.registers
+            .LCR_H
+            .write(LCR_H::WLEN::EightBit + LCR_H::FEN::FifosEnabled);
+    }
+
+    fn enable(&mut self) {
+        let flow_control = if self.flow_control {
+            CR::CTSEN::Enabled + CR::RTSEN::Enabled
+        } else {
+            CR::CTSEN::Disabled + CR::RTSEN::Disabled
+        };
+
+        self.registers
+            .CR
+            .write(CR::UARTEN::Enabled + CR::TXE::Enabled + CR::RXE::Enabled + flow_control);
+    }
+
     /// Send a character.
     fn write_char(&mut self, c: char) {
         // Spin while TX FIFO full is set, waiting for an empty slot.
@@ -342,18 +523,86 @@ impl PL011UartInner {
         self.chars_written += 1;
     }
 
+    /// Send bytes as they are, until all were sent or the TX FIFO is full.
+    ///
+    /// Returns the number of bytes sent.
+    fn write_bytes_burst(&mut self, bytes: &[u8]) -> usize {
+        let mut sent = 0;
+
+        for &b in bytes {
+            if self.registers.FR.matches_all(FR::TXFF::SET) {
+                break;
+            }
+
+            self.registers.DR.set(b as u32);
+            sent += 1;
+        }
+
+        self.chars_written += sent;
+        sent
+    }
+
     /// Block execution until the last buffered character has been physically put on the TX wire.
-    fn flush(&self) {
+    ///
+    /// Fails if the UART stays busy, e.g. because flow control holds the output back forever.
+    fn flush(&self) -> Result<(), &'static str> {
         // Spin until the busy bit is cleared.
-        while self.registers.FR.matches_all(FR::BUSY::SET) {
-            cpu::nop();
+        time::with_timeout(FLUSH_TIMEOUT, || {
+            (!self.registers.FR.matches_all(FR::BUSY::SET)).then(|| ())
+        })
+        .map_err(|_| "PL011: Timeout while flushing")
+    }
+
+    /// Check that the baud rate divisors read back as programmed, and that a byte sent in loopback
+    /// mode is received unaltered.
+    ///
+    /// Input that waits in the RX FIFO would get mixed up with the test byte, so the test fails
+    /// instead of discarding it.
+    #[cfg(feature = "post")]
+    fn self_test(&mut self) -> Result<(), &'static str> {
+        use tock_registers::interfaces::ReadWriteable;
+
+        let (ibrd, fbrd) = baud_rate_divisors(self.clock_rate_hz, self.baud_rate)?;
+
+        if (self.registers.IBRD.read(IBRD::BAUD_DIVINT) != ibrd)
+            || (self.registers.FBRD.read(FBRD::BAUD_DIVFRAC) != fbrd)
+        {
+            return Err("PL011: Baud rate divisors read back wrong");
+        }
+
+        if !self.rx_fifo_empty() {
+            return Err("PL011: RX FIFO not empty");
         }
+
+        self.flush()?;
+        self.registers.CR.modify(CR::LBE::Enabled);
+        self.registers.DR.set(LOOPBACK_PATTERN);
+
+        let received = time::with_timeout(LOOPBACK_TIMEOUT, || {
+            (!self.rx_fifo_empty()).then(|| self.registers.DR.get())
+        });
+
+        // Leave loopback mode only after the byte left the transmitter.
+        let _ = self.flush();
+        self.enable();
+
+        // The bits above the data byte flag framing, parity, break and overrun errors.
+        match received {
+            Err(_) => Err("PL011: Loopback byte not received"),
+            Ok(x) if (x & 0xFFF) != LOOPBACK_PATTERN => Err("PL011: Loopback byte corrupted"),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    /// Return whether the RX FIFO is empty.
+    fn rx_fifo_empty(&self) -> bool {
+        self.registers.FR.matches_all(FR::RXFE::SET)
     }
 
     /// Retrieve a character.
     fn read_char_converting(&mut self, blocking_mode: BlockingMode) -> Option<char> {
         // If RX FIFO is empty,
-        if self.registers.FR.matches_all(FR::RXFE::SET) {
+        if self.rx_fifo_empty() {
             // immediately return in non-blocking mode.
             if blocking_mode == BlockingMode::NonBlocking {
                 return None;
@@ -378,6 +627,30 @@ impl PL011UartInner {
 
         Some(ret)
     }
+
+    /// Receive bytes as they are, until `buf` is full or the RX FIFO is empty.
+    ///
+    /// In blocking mode, wait for the first byte. Returns the number of bytes received.
+    fn read_bytes_burst(&mut self, buf: &mut [u8], blocking_mode: BlockingMode) -> usize {
+        if blocking_mode == BlockingMode::Blocking {
+            while self.rx_fifo_empty() {
+                cpu::nop();
+            }
+        }
+
+        let mut received = 0;
+        for byte in buf.iter_mut() {
+            if self.rx_fifo_empty() {
+                break;
+            }
+
+            *byte = self.registers.DR.get() as u8;
+            received += 1;
+        }
+
+        self.chars_read += received;
+        received
+    }
 }
 
 /// Implementing `core::fmt::Write` enables usage of the `format_args!` macros, which in turn are
@@ -408,23 +681,86 @@ impl PL011Uart {
     /// - The user must ensure to provide correct IRQ numbers.
     pub const unsafe fn new(
         mmio_descriptor: memory::mmu::MMIODescriptor,
-        irq_number: bsp::device_driver::IRQNumber,
+        irq_number: bsp::exception::asynchronous::IRQNumber,
     ) -> Self {
         Self {
-            mmio_descriptor,
+            mmio_descriptor: InitStateLock::new(mmio_descriptor),
             virt_mmio_start_addr: AtomicUsize::new(0),
-            inner: IRQSafeNullLock::new(PL011UartInner::new(
-                mmio_descriptor.start_addr().as_usize(),
-            )),
-            irq_number,
+            inner: TicketLock::new(PL011UartInner::new(mmio_descriptor.start_addr().as_usize())),
+            irq_number: InitStateLock::new(irq_number),
+            rx_waiters: WaitQueue::new(),
         }
     }
+
+    /// Use a different MMIO descriptor than the one the instance was created with, e.g. the one
+    /// that the device tree tells.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide a correct MMIO descriptor.
+    /// - Only has an effect if called before `init()`.
+    pub unsafe fn set_mmio_descriptor(&self, mmio_descriptor: memory::mmu::MMIODescriptor) {
+        self.mmio_descriptor.write(|x| *x = mmio_descriptor);
+    }
+
+    /// Use a different IRQ number than the one the instance was created with, e.g. the one that the
+    /// device tree tells.
+    ///
+    /// Only has an effect if called before the driver manager registers the IRQ handlers.
+    pub fn set_irq_number(&self, irq_number: bsp::exception::asynchronous::IRQNumber) {
+        self.irq_number.write(|x| *x = irq_number);
+    }
+
+    /// Set the clock rate and baud rate that `init()` starts out with.
+    ///
+    /// See `PL011UartInner::set_initial_rates()`.
+    pub fn set_initial_rates(&self, clock_rate_hz: u32, baud_rate: u32) {
+        self.inner
+            .lock(|inner| inner.set_initial_rates(clock_rate_hz, baud_rate))
+    }
+
+    /// Change the baud rate at runtime.
+    ///
+    /// See `PL011UartInner::set_baud_rate()`.
+    pub fn set_baud_rate(&self, baud_rate: u32) -> Result<(), &'static str> {
+        self.inner.lock(|inner| inner.set_baud_rate(baud_rate))
+    }
+
+    /// Turn RTS/CTS hardware flow control on or off.
+    ///
+    /// See `PL011UartInner::set_flow_control()`.
+    pub fn set_flow_control(&self, enabled: bool) {
+        self.inner.lock(|inner| inner.set_flow_control(enabled))
+    }
+
+    /// Set the rate of the clock that feeds the UART.
+    ///
+    /// See `PL011UartInner::set_clock_rate()`.
+    pub fn set_clock_rate(&self, clock_rate_hz: u32) -> Result<(), &'static str> {
+        self.inner.lock(|inner| inner.set_clock_rate(clock_rate_hz))
+    }
+
+    /// Run the loopback self test.
+    ///
+    /// See `PL011UartInner::self_test()`.
+    #[cfg(feature = "post")]
+    pub fn self_test(&self) -> Result<(), &'static str> {
+        self.inner.lock(|inner| inner.self_test())
+    }
+
+    /// Handle the UART's interrupts, and therefore console input, on the given core.
+    pub fn set_irq_target_core(&self, target_core: usize) -> Result<(), &'static str> {
+        use bsp::exception::asynchronous::irq_manager;
+        use exception::asynchronous::interface::IRQManager;
+
+        irq_manager().set_target_core(self.irq_number.read(|x| *x), target_core)
+    }
 }
 
 //------------------------------------------------------------------------------
 // OS Interface Code
 //------------------------------------------------------------------------------
-use synchronization::interface::Mutex;
+use synchronization::interface::{Mutex, ReadWriteEx};
 
 impl driver::interface::DeviceDriver for PL011Uart {
     fn compatible(&self) -> &'static str {
@@ -432,7 +768,8 @@ impl driver::interface::DeviceDriver for PL011Uart {
     }
 
     unsafe fn init(&self) -> Result<(), &'static str> {
-        let virt_addr = memory::mmu::kernel_map_mmio(self.compatible(), &self.mmio_descriptor)?;
+        let virt_addr =
+            memory::mmu::kernel_map_mmio(self.compatible(), &self.mmio_descriptor.read(|x| *x))?;
 
         self.inner
             .lock(|inner| inner.init(Some(virt_addr.as_usize())))?;
@@ -452,8 +789,9 @@ impl driver::interface::DeviceDriver for PL011Uart {
             handler: self,
         };
 
-        irq_manager().register_handler(self.irq_number, descriptor)?;
-        irq_manager().enable(self.irq_number);
+        let irq_number = self.irq_number.read(|x| *x);
+        irq_manager().register_handler(irq_number, descriptor)?;
+        irq_manager().enable(irq_number);
 
         Ok(())
     }
@@ -482,25 +820,85 @@ impl console::interface::Write for PL011Uart {
         self.inner.lock(|inner| fmt::Write::write_fmt(inner, args))
     }
 
+    fn write_bytes(&self, bytes: &[u8]) {
+        // Lock once per FIFO burst instead of once per byte.
+        let mut sent = 0;
+        while sent < bytes.len() {
+            sent += self
+                .inner
+                .lock(|inner| inner.write_bytes_burst(&bytes[sent..]));
+        }
+    }
+
     fn flush(&self) {
-        // Spin until TX FIFO empty is set.
-        self.inner.lock(|inner| inner.flush());
+        // Spin until TX FIFO empty is set. There is no one to report a timeout to.
+        let _ = self.inner.lock(|inner| inner.flush());
     }
 }
 
 impl console::interface::Read for PL011Uart {
     fn read_char(&self) -> char {
+        // Blocking needs the RX IRQ to wake up the thread. Spin if it can not arrive.
+        if !scheduler::is_initialized() || exception::asynchronous::is_local_irq_masked() {
+            return self
+                .inner
+                .lock(|inner| inner.read_char_converting(BlockingMode::Blocking).unwrap());
+        }
+
+        let mut c = None;
+        self.rx_waiters.wait_until(|| {
+            c = self
+                .inner
+                .lock(|inner| inner.read_char_converting(BlockingMode::NonBlocking));
+
+            c.is_some()
+        });
+
+        c.unwrap()
+    }
+
+    fn read_exact(&self, buf: &mut [u8]) {
+        let mut received = 0;
+
+        // Blocking needs the RX IRQ to wake up the thread. Spin if it can not arrive.
+        if !scheduler::is_initialized() || exception::asynchronous::is_local_irq_masked() {
+            while received < buf.len() {
+                received += self.inner.lock(|inner| {
+                    inner.read_bytes_burst(&mut buf[received..], BlockingMode::Blocking)
+                });
+            }
+            return;
+        }
+
+        while received < buf.len() {
+            self.rx_waiters.wait_until(|| {
+                let n = self.inner.lock(|inner| {
+                    inner.read_bytes_burst(&mut buf[received..], BlockingMode::NonBlocking)
+                });
+                received += n;
+
+                n > 0
+            });
+        }
+    }
+
+    fn read_char_nonblock(&self) -> Option<char> {
         self.inner
-            .lock(|inner| inner.read_char_converting(BlockingMode::Blocking).unwrap())
+            .lock(|inner| inner.read_char_converting(BlockingMode::NonBlocking))
+    }
+
+    fn bytes_available(&self) -> usize {
+        // The FIFO only tells whether it is empty.
+        if self.inner.lock(|inner| inner.rx_fifo_empty()) {
+            0
+        } else {
+            1
+        }
     }
 
     fn clear_rx(&self) {
         // Read from the RX FIFO until it is indicating empty.
-        while self
-            .inner
-            .lock(|inner| inner.read_char_converting(BlockingMode::NonBlocking))
-            .is_some()
-        {}
+        while self.read_char_nonblock().is_some() {}
     }
 }
 
@@ -524,13 +922,43 @@ impl exception::asynchronous::interface::IRQHandler for PL011Uart {
 
             // Check for any kind of RX interrupt.
             if pending.matches_any(MIS::RXMIS::SET + MIS::RTMIS::SET) {
-                // Echo any received characters.
-                while let Some(c) = inner.read_char_converting(BlockingMode::NonBlocking) {
-                    inner.write_char(c)
+                // Leave received characters to blocked readers. Otherwise, echo them.
+                if self.rx_waiters.has_waiters() {
+                    self.rx_waiters.wake_all();
+                } else {
+                    while let Some(c) = inner.read_char_converting(BlockingMode::NonBlocking) {
+                        inner.write_char(c)
+                    }
                 }
+
+                task::notify(task::Notification::CharAvailable);
             }
         });
 
         Ok(())
     }
 }
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
+
+    /// Check the divisor calculation against values from the PL011 Technical Reference Manual.
+    #[kernel_test]
+    fn baud_rate_divisors_are_correct() {
+        assert_eq!(baud_rate_divisors(48_000_000, 921_600), Ok((3, 16)));
+        assert_eq!(baud_rate_divisors(4_000_000, 230_400), Ok((1, 5)));
+        assert_eq!(baud_rate_divisors(4_000_000, 115_200), Ok((2, 11)));
+        assert_eq!(baud_rate_divisors(48_000_000, 3_000_000), Ok((1, 0)));
+        assert_eq!(baud_rate_divisors(9_216_000, 115_200), Ok((5, 0)));
+
+        assert!(baud_rate_divisors(48_000_000, 0).is_err());
+        assert!(baud_rate_divisors(48_000_000, 4_000_000).is_err());
+        assert!(baud_rate_divisors(48_000_000, 10).is_err());
+    }
+}
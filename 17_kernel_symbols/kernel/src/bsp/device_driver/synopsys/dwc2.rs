@@ -0,0 +1,781 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! DWC2 Driver - Synopsys DesignWare Hi-Speed USB 2.0 On-The-Go Controller.
+//!
+//! The controller is operated in host mode. Transfers are done in Slave mode, meaning that the CPU
+//! moves all data through the channel FIFOs by itself instead of letting the core's internal DMA
+//! engine do it. This is slow, but it does not require any knowledge about bus addresses or cache
+//! maintenance, which is fine for enumerating and talking to devices on endpoint zero.
+//!
+//! Only host channel 0 is used, and all operations are polled.
+
+use crate::{
+    bsp::device_driver::common::MMIODerefWrapper,
+    driver, memory, synchronization,
+    synchronization::{InitStateLock, SpinLock},
+    time,
+    usb::{self, Speed},
+    warn,
+};
+use core::time::Duration;
+use tock_registers::{
+    interfaces::{ReadWriteable, Readable, Writeable},
+    register_bitfields, register_structs,
+    registers::{ReadOnly, ReadWrite},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+// DWC2 registers.
+//
+// Descriptions taken from
+// - DesignWare Cores USB 2.0 Hi-Speed On-The-Go (OTG) Programmer's Guide
+// - https://github.com/torvalds/linux/blob/master/drivers/usb/dwc2/hw.h
+register_bitfields! {
+    u32,
+
+    /// AHB Configuration Register
+    GAHBCFG [
+        /// DMA Enable
+        DMAEn OFFSET(5) NUMBITS(1) [],
+
+        /// Global Interrupt Mask
+        GlblIntrMsk OFFSET(0) NUMBITS(1) []
+    ],
+
+    /// USB Configuration Register
+    GUSBCFG [
+        /// Force Device Mode
+        ForceDevMode OFFSET(30) NUMBITS(1) [],
+
+        /// Force Host Mode
+        ForceHstMode OFFSET(29) NUMBITS(1) []
+    ],
+
+    /// Reset Register
+    GRSTCTL [
+        /// AHB Master Idle
+        AHBIdle OFFSET(31) NUMBITS(1) [],
+
+        /// TxFIFO Number
+        TxFNum OFFSET(6) NUMBITS(5) [
+            All = 0x10
+        ],
+
+        /// TxFIFO Flush
+        TxFFlsh OFFSET(5) NUMBITS(1) [],
+
+        /// RxFIFO Flush
+        RxFFlsh OFFSET(4) NUMBITS(1) [],
+
+        /// Core Soft Reset
+        CSftRst OFFSET(0) NUMBITS(1) []
+    ],
+
+    /// Interrupt Register
+    GINTSTS [
+        /// RxFIFO Non-Empty
+        RxFLvl OFFSET(4) NUMBITS(1) [],
+
+        /// Current Mode of Operation
+        CurMod OFFSET(0) NUMBITS(1) [
+            Device = 0,
+            Host = 1
+        ]
+    ],
+
+    /// Receive Status Debug Read/Status Read and Pop Registers
+    GRXSTS [
+        /// Packet Status
+        PktSts OFFSET(17) NUMBITS(4) [
+            InDataReceived = 2,
+            InTransferCompleted = 3,
+            DataToggleError = 5,
+            ChannelHalted = 7
+        ],
+
+        /// Byte Count
+        BCnt OFFSET(4) NUMBITS(11) [],
+
+        /// Channel Number
+        ChNum OFFSET(0) NUMBITS(4) []
+    ],
+
+    /// Non-Periodic Transmit FIFO/Queue Status Register
+    GNPTXSTS [
+        /// Non-Periodic Transmit Request Queue Space Available
+        NPTxQSpcAvail OFFSET(16) NUMBITS(8) [],
+
+        /// Non-Periodic TxFIFO Space Avail (in words)
+        NPTxFSpcAvail OFFSET(0) NUMBITS(16) []
+    ],
+
+    /// Host Configuration Register
+    HCFG [
+        /// FS/LS PHY Clock Select
+        FSLSPclkSel OFFSET(0) NUMBITS(2) [
+            Clock30_60MHz = 0,
+            Clock48MHz = 1
+        ]
+    ],
+
+    /// Host Port Control and Status Register
+    HPRT [
+        /// Port Speed
+        PrtSpd OFFSET(17) NUMBITS(2) [
+            High = 0,
+            Full = 1,
+            Low = 2
+        ],
+
+        /// Port Power
+        PrtPwr OFFSET(12) NUMBITS(1) [],
+
+        /// Port Reset
+        PrtRst OFFSET(8) NUMBITS(1) [],
+
+        /// Port Overcurrent Change (write 1 to clear)
+        PrtOvrCurrChng OFFSET(5) NUMBITS(1) [],
+
+        /// Port Enable/Disable Change (write 1 to clear)
+        PrtEnChng OFFSET(3) NUMBITS(1) [],
+
+        /// Port Enable (write 1 to clear)
+        PrtEna OFFSET(2) NUMBITS(1) [],
+
+        /// Port Connect Detected (write 1 to clear)
+        PrtConnDet OFFSET(1) NUMBITS(1) [],
+
+        /// Port Connect Status
+        PrtConnSts OFFSET(0) NUMBITS(1) []
+    ],
+
+    /// Host Channel Characteristics Register
+    HCCHAR [
+        /// Channel Enable
+        ChEna OFFSET(31) NUMBITS(1) [],
+
+        /// Channel Disable
+        ChDis OFFSET(30) NUMBITS(1) [],
+
+        /// Device Address
+        DevAddr OFFSET(22) NUMBITS(7) [],
+
+        /// Multi Count
+        MC OFFSET(20) NUMBITS(2) [],
+
+        /// Endpoint Type
+        EPType OFFSET(18) NUMBITS(2) [
+            Control = 0,
+            Isochronous = 1,
+            Bulk = 2,
+            Interrupt = 3
+        ],
+
+        /// Low-Speed Device
+        LSpdDev OFFSET(17) NUMBITS(1) [],
+
+        /// Endpoint Direction
+        EPDir OFFSET(15) NUMBITS(1) [
+            Out = 0,
+            In = 1
+        ],
+
+        /// Endpoint Number
+        EPNum OFFSET(11) NUMBITS(4) [],
+
+        /// Maximum Packet Size
+        MPS OFFSET(0) NUMBITS(11) []
+    ],
+
+    /// Host Channel Interrupt Register
+    HCINT [
+        /// Data Toggle Error
+        DataTglErr OFFSET(10) NUMBITS(1) [],
+
+        /// Frame Overrun
+        FrmOvrun OFFSET(9) NUMBITS(1) [],
+
+        /// Babble Error
+        BblErr OFFSET(8) NUMBITS(1) [],
+
+        /// Transaction Error
+        XactErr OFFSET(7) NUMBITS(1) [],
+
+        /// NAK Response Received
+        NAK OFFSET(4) NUMBITS(1) [],
+
+        /// STALL Response Received
+        STALL OFFSET(3) NUMBITS(1) [],
+
+        /// AHB Error
+        AHBErr OFFSET(2) NUMBITS(1) [],
+
+        /// Channel Halted
+        ChHltd OFFSET(1) NUMBITS(1) [],
+
+        /// Transfer Completed
+        XferCompl OFFSET(0) NUMBITS(1) []
+    ],
+
+    /// Host Channel Transfer Size Register
+    HCTSIZ [
+        /// PID
+        Pid OFFSET(29) NUMBITS(2) [
+            Data0 = 0,
+            Data2 = 1,
+            Data1 = 2,
+            Setup = 3
+        ],
+
+        /// Packet Count
+        PktCnt OFFSET(19) NUMBITS(10) [],
+
+        /// Transfer Size
+        XferSize OFFSET(0) NUMBITS(19) []
+    ]
+}
+
+register_structs! {
+    #[allow(non_snake_case)]
+    RegisterBlock {
+        (0x000 => _reserved1),
+        (0x008 => GAHBCFG: ReadWrite<u32, GAHBCFG::Register>),
+        (0x00C => GUSBCFG: ReadWrite<u32, GUSBCFG::Register>),
+        (0x010 => GRSTCTL: ReadWrite<u32, GRSTCTL::Register>),
+        (0x014 => GINTSTS: ReadWrite<u32, GINTSTS::Register>),
+        (0x018 => GINTMSK: ReadWrite<u32>),
+        (0x01C => _reserved2),
+        (0x020 => GRXSTSP: ReadOnly<u32, GRXSTS::Register>),
+        (0x024 => GRXFSIZ: ReadWrite<u32>),
+        (0x028 => GNPTXFSIZ: ReadWrite<u32>),
+        (0x02C => GNPTXSTS: ReadOnly<u32, GNPTXSTS::Register>),
+        (0x030 => _reserved3),
+        (0x040 => GSNPSID: ReadOnly<u32>),
+        (0x044 => _reserved4),
+        (0x100 => HPTXFSIZ: ReadWrite<u32>),
+        (0x104 => _reserved5),
+        (0x400 => HCFG: ReadWrite<u32, HCFG::Register>),
+        (0x404 => _reserved6),
+        (0x440 => HPRT: ReadWrite<u32, HPRT::Register>),
+        (0x444 => _reserved7),
+        (0x500 => HCCHAR0: ReadWrite<u32, HCCHAR::Register>),
+        (0x504 => _reserved8),
+        (0x508 => HCINT0: ReadWrite<u32, HCINT::Register>),
+        (0x50C => _reserved9),
+        (0x510 => HCTSIZ0: ReadWrite<u32, HCTSIZ::Register>),
+        (0x514 => _reserved10),
+        (0xE00 => PCGCCTL: ReadWrite<u32>),
+        (0xE04 => _reserved11),
+        (0x1000 => FIFO0: ReadWrite<u32>),
+        (0x1004 => @END),
+    }
+}
+
+/// Abstraction for the associated MMIO registers.
+type Registers = MMIODerefWrapper<RegisterBlock>;
+
+/// The upper half of GSNPSID reads "OT" for all revisions of the core.
+const GSNPSID_PRODUCT_ID: u32 = 0x4F54;
+
+// FIFO RAM partitioning, in 32 bit words.
+const RX_FIFO_SIZE: u32 = 1024;
+const NON_PERIODIC_TX_FIFO_SIZE: u32 = 1024;
+const PERIODIC_TX_FIFO_SIZE: u32 = 1024;
+
+/// Timeout for register handshakes.
+const REGISTER_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Timeout for a single stage of a transfer.
+const TRANSFER_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How often a stage is retried if the device answers with NAK.
+const MAX_NAK_RETRIES: usize = 100;
+
+struct DWC2Inner {
+    registers: Registers,
+    core_present: bool,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Direction {
+    Out,
+    In,
+}
+
+enum StageError {
+    Nak,
+    Fatal(&'static str),
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Representation of the DWC2 USB host controller.
+pub struct DWC2 {
+    mmio_descriptor: InitStateLock<memory::mmu::MMIODescriptor>,
+    inner: SpinLock<DWC2Inner>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// Spin until `condition` evaluates to true or `timeout` has passed.
+fn poll_until(mut condition: impl FnMut() -> bool, timeout: Duration) -> Result<(), &'static str> {
+    time::with_timeout(timeout, || condition().then(|| ()))
+        .map_err(|_| "Timeout while polling DWC2 register")
+}
+
+fn delay(duration: Duration) {
+    time::sleep(duration);
+}
+
+impl DWC2Inner {
+    /// Create an instance.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide a correct MMIO start address.
+    pub const unsafe fn new(mmio_start_addr: usize) -> Self {
+        Self {
+            registers: Registers::new(mmio_start_addr),
+            core_present: false,
+        }
+    }
+
+    /// Write HPRT without accidentally acknowledging any of the write-1-to-clear bits.
+    fn modify_hprt(&self, field: tock_registers::fields::FieldValue<u32, HPRT::Register>) {
+        let w1c_bits = HPRT::PrtEna::SET
+            + HPRT::PrtConnDet::SET
+            + HPRT::PrtEnChng::SET
+            + HPRT::PrtOvrCurrChng::SET;
+
+        let val = self.registers.HPRT.get() & !w1c_bits.mask();
+        self.registers.HPRT.set(field.modify(val));
+    }
+
+    fn core_reset(&self) -> Result<(), &'static str> {
+        poll_until(
+            || self.registers.GRSTCTL.is_set(GRSTCTL::AHBIdle),
+            REGISTER_TIMEOUT,
+        )?;
+
+        self.registers.GRSTCTL.write(GRSTCTL::CSftRst::SET);
+        poll_until(
+            || !self.registers.GRSTCTL.is_set(GRSTCTL::CSftRst),
+            REGISTER_TIMEOUT,
+        )?;
+
+        // The programmer's guide asks for at least three PHY clocks. Be generous.
+        delay(Duration::from_millis(10));
+
+        Ok(())
+    }
+
+    fn flush_fifos(&self) -> Result<(), &'static str> {
+        self.registers
+            .GRSTCTL
+            .write(GRSTCTL::TxFNum::All + GRSTCTL::TxFFlsh::SET);
+        poll_until(
+            || !self.registers.GRSTCTL.is_set(GRSTCTL::TxFFlsh),
+            REGISTER_TIMEOUT,
+        )?;
+
+        self.registers.GRSTCTL.write(GRSTCTL::RxFFlsh::SET);
+        poll_until(
+            || !self.registers.GRSTCTL.is_set(GRSTCTL::RxFFlsh),
+            REGISTER_TIMEOUT,
+        )
+    }
+
+    /// Bring the core into host mode and power the root port.
+    fn init_host(&mut self) -> Result<(), &'static str> {
+        let id = self.registers.GSNPSID.get();
+        #[cfg(feature = "fault_inject")]
+        let id = crate::fault_inject::mmio_read(id);
+
+        if (id >> 16) != GSNPSID_PRODUCT_ID {
+            warn!("DWC2: Core not responding (GSNPSID = {:#010x})", id);
+            return Ok(());
+        }
+        self.core_present = true;
+
+        // Mask everything. This driver polls.
+        self.registers.GAHBCFG.set(0);
+        self.registers.GINTMSK.set(0);
+
+        self.core_reset()?;
+
+        self.registers
+            .GUSBCFG
+            .modify(GUSBCFG::ForceDevMode::CLEAR + GUSBCFG::ForceHstMode::SET);
+        poll_until(
+            || self.registers.GINTSTS.matches_all(GINTSTS::CurMod::Host),
+            REGISTER_TIMEOUT,
+        )?;
+
+        // Restart the PHY clock.
+        self.registers.PCGCCTL.set(0);
+
+        self.registers.HCFG.write(HCFG::FSLSPclkSel::Clock30_60MHz);
+
+        self.registers.GRXFSIZ.set(RX_FIFO_SIZE);
+        self.registers
+            .GNPTXFSIZ
+            .set((NON_PERIODIC_TX_FIFO_SIZE << 16) | RX_FIFO_SIZE);
+        self.registers
+            .HPTXFSIZ
+            .set((PERIODIC_TX_FIFO_SIZE << 16) | (RX_FIFO_SIZE + NON_PERIODIC_TX_FIFO_SIZE));
+        self.flush_fifos()?;
+
+        // Slave mode, no interrupts to the CPU.
+        self.registers.GAHBCFG.write(GAHBCFG::DMAEn::CLEAR);
+
+        if !self.registers.HPRT.is_set(HPRT::PrtPwr) {
+            self.modify_hprt(HPRT::PrtPwr::SET);
+        }
+
+        // Give a device on the port time to signal its connection.
+        delay(Duration::from_millis(100));
+
+        Ok(())
+    }
+
+    fn root_port_connected(&self) -> bool {
+        self.core_present && self.registers.HPRT.is_set(HPRT::PrtConnSts)
+    }
+
+    fn reset_root_port(&self) -> Result<Speed, &'static str> {
+        if !self.core_present {
+            return Err("DWC2 core not present");
+        }
+
+        self.modify_hprt(HPRT::PrtRst::SET);
+        delay(Duration::from_millis(50));
+        self.modify_hprt(HPRT::PrtRst::CLEAR);
+
+        // Recovery interval.
+        delay(Duration::from_millis(20));
+
+        if !self.registers.HPRT.is_set(HPRT::PrtEna) {
+            return Err("Root port did not enable after reset");
+        }
+
+        let speed = match self.registers.HPRT.read_as_enum(HPRT::PrtSpd) {
+            Some(HPRT::PrtSpd::Value::High) => Speed::High,
+            Some(HPRT::PrtSpd::Value::Full) => Speed::Full,
+            Some(HPRT::PrtSpd::Value::Low) => Speed::Low,
+            None => return Err("Unknown root port speed"),
+        };
+
+        Ok(speed)
+    }
+
+    /// Halt channel 0 after an unsuccessful stage so that it can be reprogrammed.
+    fn halt_channel(&self) -> Result<(), &'static str> {
+        if !self.registers.HCCHAR0.is_set(HCCHAR::ChEna) {
+            return Ok(());
+        }
+
+        self.registers
+            .HCCHAR0
+            .modify(HCCHAR::ChEna::SET + HCCHAR::ChDis::SET);
+
+        let res = poll_until(
+            || self.registers.HCINT0.is_set(HCINT::ChHltd),
+            REGISTER_TIMEOUT,
+        );
+        self.registers.HCINT0.set(u32::MAX);
+
+        res
+    }
+
+    fn check_errors(&self) -> Result<(), StageError> {
+        let hcint = self.registers.HCINT0.extract();
+
+        if hcint.is_set(HCINT::STALL) {
+            return Err(StageError::Fatal("Endpoint stalled"));
+        }
+
+        if hcint.is_set(HCINT::AHBErr)
+            || hcint.is_set(HCINT::XactErr)
+            || hcint.is_set(HCINT::BblErr)
+            || hcint.is_set(HCINT::DataTglErr)
+            || hcint.is_set(HCINT::FrmOvrun)
+        {
+            return Err(StageError::Fatal("USB transaction error"));
+        }
+
+        if hcint.is_set(HCINT::NAK) {
+            return Err(StageError::Nak);
+        }
+
+        Ok(())
+    }
+
+    /// Push `data` into the non-periodic TX FIFO of channel 0.
+    fn write_fifo(&self, data: &[u8]) -> Result<(), StageError> {
+        for chunk in data.chunks(4) {
+            poll_until(
+                || self.registers.GNPTXSTS.read(GNPTXSTS::NPTxFSpcAvail) > 0,
+                TRANSFER_TIMEOUT,
+            )
+            .map_err(StageError::Fatal)?;
+
+            let mut word = [0_u8; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.registers.FIFO0.set(u32::from_le_bytes(word));
+        }
+
+        Ok(())
+    }
+
+    /// Pop `len` bytes from the RX FIFO into `buf`.
+    fn read_fifo(&self, buf: &mut [u8], len: usize) {
+        let num_words = (len + 3) / 4;
+
+        for i in 0..num_words {
+            let word = self.registers.FIFO0.get().to_le_bytes();
+
+            // All words must be popped, even if the caller's buffer is too small.
+            for (j, byte) in word.iter().enumerate() {
+                let pos = (i * 4) + j;
+
+                if pos < len {
+                    if let Some(b) = buf.get_mut(pos) {
+                        *b = *byte;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Run a single stage of a control transfer.
+    fn stage(
+        &self,
+        device: &usb::Device,
+        dir: Direction,
+        pid: tock_registers::fields::FieldValue<u32, HCTSIZ::Register>,
+        buf: &mut [u8],
+    ) -> Result<usize, StageError> {
+        let mps = device.ep0_max_packet_size() as usize;
+        if mps == 0 {
+            return Err(StageError::Fatal("Invalid max packet size"));
+        }
+        let packet_count = core::cmp::max(1, (buf.len() + mps - 1) / mps);
+
+        self.registers.HCINT0.set(u32::MAX);
+        self.registers.HCTSIZ0.write(
+            HCTSIZ::XferSize.val(buf.len() as u32) + HCTSIZ::PktCnt.val(packet_count as u32) + pid,
+        );
+
+        let ep_dir = match dir {
+            Direction::Out => HCCHAR::EPDir::Out,
+            Direction::In => HCCHAR::EPDir::In,
+        };
+        let low_speed = if device.speed() == Speed::Low {
+            HCCHAR::LSpdDev::SET
+        } else {
+            HCCHAR::LSpdDev::CLEAR
+        };
+
+        self.registers.HCCHAR0.write(
+            HCCHAR::MPS.val(mps as u32)
+                + HCCHAR::EPNum.val(0)
+                + ep_dir
+                + low_speed
+                + HCCHAR::EPType::Control
+                + HCCHAR::MC.val(1)
+                + HCCHAR::DevAddr.val(device.address() as u32)
+                + HCCHAR::ChEna::SET,
+        );
+
+        let mut received = 0;
+        match dir {
+            Direction::Out => self.write_fifo(buf)?,
+            Direction::In => {
+                let mut finished = false;
+
+                while !finished {
+                    poll_until(
+                        || {
+                            self.registers.GINTSTS.is_set(GINTSTS::RxFLvl)
+                                || self.registers.HCINT0.get() != 0
+                        },
+                        TRANSFER_TIMEOUT,
+                    )
+                    .map_err(StageError::Fatal)?;
+
+                    if !self.registers.GINTSTS.is_set(GINTSTS::RxFLvl) {
+                        self.check_errors()?;
+                        finished = self.registers.HCINT0.is_set(HCINT::XferCompl);
+                        continue;
+                    }
+
+                    let status = self.registers.GRXSTSP.extract();
+                    let count = status.read(GRXSTS::BCnt) as usize;
+                    match status.read_as_enum(GRXSTS::PktSts) {
+                        Some(GRXSTS::PktSts::Value::InDataReceived) => {
+                            let start = core::cmp::min(received, buf.len());
+                            self.read_fifo(&mut buf[start..], count);
+                            received += count;
+                        }
+                        Some(GRXSTS::PktSts::Value::InTransferCompleted) => finished = true,
+                        Some(GRXSTS::PktSts::Value::DataToggleError) => {
+                            return Err(StageError::Fatal("USB data toggle error"))
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+
+        poll_until(|| self.registers.HCINT0.get() != 0, TRANSFER_TIMEOUT)
+            .map_err(StageError::Fatal)?;
+        self.check_errors()?;
+
+        Ok(match dir {
+            Direction::Out => buf.len(),
+            Direction::In => received,
+        })
+    }
+
+    /// Run a stage and transparently retry it as long as the device is not ready.
+    fn stage_with_retry(
+        &self,
+        device: &usb::Device,
+        dir: Direction,
+        pid: tock_registers::fields::FieldValue<u32, HCTSIZ::Register>,
+        buf: &mut [u8],
+    ) -> Result<usize, &'static str> {
+        for _ in 0..MAX_NAK_RETRIES {
+            match self.stage(device, dir, pid, buf) {
+                Ok(x) => return Ok(x),
+                Err(StageError::Nak) => {
+                    self.halt_channel()?;
+                    delay(Duration::from_millis(1));
+                }
+                Err(StageError::Fatal(x)) => {
+                    self.halt_channel()?;
+                    return Err(x);
+                }
+            }
+        }
+
+        Err("Device kept NAKing")
+    }
+
+    fn control_transfer(
+        &self,
+        device: &usb::Device,
+        setup: &usb::SetupPacket,
+        data: &mut [u8],
+    ) -> Result<usize, &'static str> {
+        if !self.core_present {
+            return Err("DWC2 core not present");
+        }
+
+        let mut setup_bytes = setup.to_bytes();
+        self.stage_with_retry(device, Direction::Out, HCTSIZ::Pid::Setup, &mut setup_bytes)?;
+
+        let data_dir = if setup.is_dir_in() {
+            Direction::In
+        } else {
+            Direction::Out
+        };
+
+        let len = core::cmp::min(data.len(), setup.length as usize);
+        let mut transferred = 0;
+        if len > 0 {
+            transferred =
+                self.stage_with_retry(device, data_dir, HCTSIZ::Pid::Data1, &mut data[..len])?;
+        }
+
+        // The status stage goes in the opposite direction of the data stage, or IN if there was
+        // none.
+        let status_dir = if (len > 0) && (data_dir == Direction::In) {
+            Direction::Out
+        } else {
+            Direction::In
+        };
+        self.stage_with_retry(device, status_dir, HCTSIZ::Pid::Data1, &mut [])?;
+
+        Ok(transferred)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl DWC2 {
+    /// Create an instance.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide correct MMIO descriptors.
+    pub const unsafe fn new(mmio_descriptor: memory::mmu::MMIODescriptor) -> Self {
+        Self {
+            mmio_descriptor: InitStateLock::new(mmio_descriptor),
+            inner: SpinLock::new(DWC2Inner::new(mmio_descriptor.start_addr().as_usize())),
+        }
+    }
+
+    /// Use a different MMIO descriptor than the one the instance was created with, e.g. the one
+    /// that the device tree tells.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide a correct MMIO descriptor.
+    /// - Only has an effect if called before `init()`.
+    pub unsafe fn set_mmio_descriptor(&self, mmio_descriptor: memory::mmu::MMIODescriptor) {
+        self.mmio_descriptor.write(|x| *x = mmio_descriptor);
+    }
+}
+
+//------------------------------------------------------------------------------
+// OS Interface Code
+//------------------------------------------------------------------------------
+use synchronization::interface::{Mutex, ReadWriteEx};
+
+impl driver::interface::DeviceDriver for DWC2 {
+    fn compatible(&self) -> &'static str {
+        "Synopsys DWC2 USB Host"
+    }
+
+    unsafe fn init(&self) -> Result<(), &'static str> {
+        let virt_addr =
+            memory::mmu::kernel_map_mmio(self.compatible(), &self.mmio_descriptor.read(|x| *x))?;
+
+        self.inner.lock(|inner| {
+            inner.registers = Registers::new(virt_addr.as_usize());
+            inner.init_host()
+        })
+    }
+}
+
+impl usb::interface::HostController for DWC2 {
+    fn root_port_connected(&self) -> bool {
+        self.inner.lock(|inner| inner.root_port_connected())
+    }
+
+    fn reset_root_port(&self) -> Result<Speed, &'static str> {
+        self.inner.lock(|inner| inner.reset_root_port())
+    }
+
+    fn control_transfer(
+        &self,
+        device: &usb::Device,
+        setup: &usb::SetupPacket,
+        data: &mut [u8],
+    ) -> Result<usize, &'static str> {
+        self.inner
+            .lock(|inner| inner.control_transfer(device, setup, data))
+    }
+}
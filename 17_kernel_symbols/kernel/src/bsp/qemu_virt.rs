@@ -0,0 +1,70 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Top-level BSP file for QEMU's `virt` machine.
+//!
+//! The machine has no Raspberry Pi heritage. It is configured by the Makefile with a GICv2, EL2 for
+//! the boot code and four Cortex-A53 cores, which are started through PSCI. QEMU passes a device
+//! tree, which tells the enabled devices and the size of the DRAM.
+
+pub mod console;
+pub mod cpu;
+pub mod driver;
+pub mod exception;
+pub mod memory;
+pub mod net;
+pub mod usb;
+
+use super::device_driver;
+use crate::{dtb, memory::mmu::MMIODescriptor};
+use memory::map::mmio;
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static PL011_UART: device_driver::PL011Uart = unsafe {
+    device_driver::PL011Uart::new(
+        MMIODescriptor::new(mmio::PL011_UART_START, mmio::PL011_UART_SIZE),
+        exception::asynchronous::irq_map::PL011_UART,
+    )
+};
+
+static INTERRUPT_CONTROLLER: device_driver::GICv2 = unsafe {
+    device_driver::GICv2::new(
+        MMIODescriptor::new(mmio::GICD_START, mmio::GICD_SIZE),
+        MMIODescriptor::new(mmio::GICC_START, mmio::GICC_SIZE),
+    )
+};
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Board identification.
+pub fn board_name() -> &'static str {
+    "QEMU virt"
+}
+
+/// Detect the machine the kernel is running on.
+///
+/// The core's part number does not help here, since the cores are configured by the command line.
+/// Hence, only the device tree's root `compatible` property is checked.
+pub fn detect_soc() -> Option<&'static str> {
+    let dt = dtb::boot_device_tree()?;
+
+    dt.root()
+        .is_compatible("linux,dummy-virt")
+        .then(|| "QEMU virt")
+}
+
+/// Check that the kernel is running on the machine it was built for.
+///
+/// Without a device tree, the machine cannot be told apart and the check passes.
+pub fn verify_board() -> Result<(), &'static str> {
+    match dtb::boot_device_tree() {
+        Some(_) if detect_soc().is_none() => Err("Kernel image built for a different machine"),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,83 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! BSP console facilities.
+
+use crate::{bsp::device_driver, console, cpu, driver};
+
+#[cfg(feature = "test_build")]
+use crate::qemu_exit;
+use core::fmt;
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// In case of a panic, the panic handler uses this function to take a last shot at printing
+/// something before the system is halted.
+///
+/// We try to init a panic-version of the UART. The panic version is not protected with
+/// synchronization primitives, which increases chances that we get to print something, even when
+/// the kernel's default UART instance happens to be locked at the time of the panic.
+///
+/// # Safety
+///
+/// - Use only for printing during a panic.
+pub unsafe fn panic_console_out() -> impl fmt::Write {
+    use driver::interface::DeviceDriver;
+
+    // If remapping of the driver's MMIO hasn't already happened, we won't be able to print. Just
+    // park the CPU core in this case.
+    let uart_mmio_start_addr = match super::PL011_UART.virt_mmio_start_addr() {
+        None => cpu::wait_forever(),
+        Some(x) => x,
+    };
+    let mut panic_uart = device_driver::PanicUart::new(uart_mmio_start_addr);
+
+    #[cfg(not(feature = "test_build"))]
+    panic_uart
+        .init(None)
+        .unwrap_or_else(|_| cpu::wait_forever());
+
+    #[cfg(feature = "test_build")]
+    panic_uart
+        .init(None)
+        .unwrap_or_else(|_| qemu_exit::exit_failure());
+
+    panic_uart
+}
+
+/// Run the loopback self test of the board's UART.
+#[cfg(feature = "post")]
+pub fn uart_self_test() -> Result<(), &'static str> {
+    super::PL011_UART.self_test()
+}
+
+/// Register the board's UART as a console sink.
+///
+/// # Safety
+///
+/// - Must only be called during kernel init, after the UART was initialized.
+pub unsafe fn register_console_sinks() -> Result<(), &'static str> {
+    console::register_sink("PL011", &super::PL011_UART)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+/// Minimal code needed to bring up the console in QEMU (for testing only).
+#[cfg(feature = "test_build")]
+pub fn qemu_bring_up_console() {
+    use driver::interface::DeviceDriver;
+
+    // Calling the UART's init ensures that the BSP's instance of the UART does remap the MMIO
+    // addresses.
+    unsafe {
+        super::PL011_UART
+            .init()
+            .unwrap_or_else(|_| qemu_exit::exit_failure());
+        register_console_sinks().unwrap_or_else(|_| qemu_exit::exit_failure());
+    }
+}
@@ -0,0 +1,54 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! BSP Processor code.
+
+use crate::{
+    cpu,
+    memory::{Address, Physical},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The number of processor cores, as set with `-smp` by the Makefile.
+pub const NUM_CORES: usize = 4;
+
+/// Used by `arch` code to find the early boot core.
+#[no_mangle]
+#[link_section = ".text._start_arguments"]
+pub static BOOT_CORE_ID: u64 = 0;
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Power on a secondary core, so that it starts executing at the given physical address.
+///
+/// QEMU implements PSCI itself and starts the core in EL2. The affinity of the machine's cores is
+/// their number.
+///
+/// # Safety
+///
+/// - The entry must be ready to execute with the MMU off.
+pub unsafe fn release_secondary_core(
+    core: usize,
+    phys_entry_addr: Address<Physical>,
+) -> Result<(), &'static str> {
+    // Failure shows as a core that does not reach the boot barrier.
+    let _ = cpu::psci_cpu_on(core as u64, phys_entry_addr.as_usize() as u64);
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+/// The way test builds make QEMU exit.
+#[cfg(feature = "test_build")]
+pub fn qemu_exit_backend() -> &'static (dyn crate::qemu_exit::interface::Exit + Sync) {
+    &crate::qemu_exit::Semihosting
+}
@@ -0,0 +1,216 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! BSP driver support.
+
+use crate::{
+    driver::{self, DeviceDriverDescriptor, DeviceTreeMatch},
+    dtb,
+    memory::mmu::MMIODescriptor,
+    synchronization::{interface::ReadWriteEx, RwLock},
+    warn,
+};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Maximum number of drivers that can be registered.
+const NUM_DRIVERS: usize = 4;
+
+/// Device tree compatible string of the GICv3, which QEMU uses with `gic-version=3`.
+const GICV3_COMPATIBLE: &str = "arm,gic-v3";
+
+struct DriverManagerInner {
+    next_index: usize,
+    descriptors: [Option<DeviceDriverDescriptor>; NUM_DRIVERS],
+}
+
+/// Device Driver Manager type.
+struct BSPDriverManager {
+    inner: RwLock<DriverManagerInner>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static BSP_DRIVER_MANAGER: BSPDriverManager = BSPDriverManager {
+    inner: RwLock::new(DriverManagerInner::new()),
+};
+
+/// QEMU's UART needs neither pin muxing nor a clock rate.
+static PL011_UART_DESCRIPTOR: DeviceDriverDescriptor =
+    DeviceDriverDescriptor::new(&super::PL011_UART, &[], Some(post_init_pl011_uart));
+
+static INTERRUPT_CONTROLLER_DESCRIPTOR: DeviceDriverDescriptor =
+    DeviceDriverDescriptor::new(&super::INTERRUPT_CONTROLLER, &[], None);
+
+/// Device tree compatible strings of the board's devices and the drivers that serve them.
+///
+/// Only the GICv2 is supported, which QEMU describes as a Cortex-A15 GIC.
+static DEVICE_TREE_MATCHES: [DeviceTreeMatch; 2] = [
+    DeviceTreeMatch::new(&["arm,pl011"], probe_pl011_uart),
+    DeviceTreeMatch::new(&["arm,cortex-a15-gic"], probe_interrupt_controller),
+];
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// Make the UART a console sink.
+unsafe fn post_init_pl011_uart() -> Result<(), &'static str> {
+    super::console::register_console_sinks()
+}
+
+/// Return the MMIO descriptor of the node's `index`-th `reg` entry, if there is a usable one.
+fn dt_mmio_descriptor(node: Option<&dtb::Node>, index: usize) -> Option<MMIODescriptor> {
+    node.and_then(|node| driver::mmio_descriptor_from_dt(node, index))
+}
+
+/// Take the UART's MMIO range and IRQ number from the device tree.
+///
+/// Whatever the device tree does not tell, the UART keeps from the board's memory and IRQ maps.
+fn probe_pl011_uart(node: Option<&dtb::Node>) -> Result<DeviceDriverDescriptor, &'static str> {
+    if let Some(x) = dt_mmio_descriptor(node, 0) {
+        unsafe { super::PL011_UART.set_mmio_descriptor(x) };
+    }
+
+    if let Some(irq_number) = node.and_then(super::exception::asynchronous::irq_number_from_dt) {
+        super::PL011_UART.set_irq_number(irq_number);
+    }
+
+    Ok(PL011_UART_DESCRIPTOR)
+}
+
+/// Take the GIC's distributor and CPU interface MMIO ranges from the device tree.
+///
+/// They are the first two `reg` entries. Both are needed, otherwise the static ones are kept. A
+/// machine with a GICv3 is rejected, because the GICv2 driver would program the wrong registers.
+fn probe_interrupt_controller(
+    node: Option<&dtb::Node>,
+) -> Result<DeviceDriverDescriptor, &'static str> {
+    let has_gicv3 =
+        dtb::boot_device_tree().map_or(false, |dt| dt.find_compatible(GICV3_COMPATIBLE).is_some());
+    if node.is_none() && has_gicv3 {
+        return Err("GICv3 not supported, QEMU must be started with gic-version=2");
+    }
+
+    if let (Some(gicd), Some(gicc)) = (dt_mmio_descriptor(node, 0), dt_mmio_descriptor(node, 1)) {
+        unsafe { super::INTERRUPT_CONTROLLER.set_mmio_descriptors(gicd, gicc) };
+    }
+
+    Ok(INTERRUPT_CONTROLLER_DESCRIPTOR)
+}
+
+impl DriverManagerInner {
+    pub const fn new() -> Self {
+        Self {
+            next_index: 0,
+            descriptors: [None; NUM_DRIVERS],
+        }
+    }
+
+    fn register_driver(&mut self, descriptor: DeviceDriverDescriptor) -> Result<(), &'static str> {
+        let slot = self
+            .descriptors
+            .get_mut(self.next_index)
+            .ok_or("Storage for device drivers exhausted")?;
+
+        *slot = Some(descriptor);
+        self.next_index += 1;
+
+        Ok(())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Return a reference to the driver manager.
+pub fn driver_manager() -> &'static impl driver::interface::DriverManager {
+    &BSP_DRIVER_MANAGER
+}
+
+/// Register the board's device drivers with the driver manager.
+///
+/// Devices that QEMU's device tree disables are left out. The MMIO ranges and IRQ numbers are taken
+/// from the device tree where possible, and from the board's static maps otherwise.
+///
+/// # Safety
+///
+/// - Must only be called during kernel init, after `dtb::init()`.
+pub unsafe fn init() -> Result<(), &'static str> {
+    static INIT_DONE: AtomicBool = AtomicBool::new(false);
+    if INIT_DONE.load(Ordering::Relaxed) {
+        return Err("Init already done");
+    }
+
+    driver::probe_device_tree(
+        driver_manager(),
+        dtb::boot_device_tree().as_ref(),
+        &DEVICE_TREE_MATCHES,
+    );
+
+    INIT_DONE.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+//------------------------------------------------------------------------------
+// OS Interface Code
+//------------------------------------------------------------------------------
+
+impl driver::interface::DriverManager for BSPDriverManager {
+    fn register_driver(&self, descriptor: DeviceDriverDescriptor) -> Result<(), &'static str> {
+        self.inner.write(|inner| inner.register_driver(descriptor))
+    }
+
+    fn for_each_device_driver(&self, mut f: impl FnMut(&DeviceDriverDescriptor)) {
+        self.inner
+            .read(|inner| inner.descriptors.iter().flatten().for_each(|x| f(x)))
+    }
+
+    unsafe fn init_drivers_and_irqs(&self) {
+        // Work on a copy, so that the lock is not held while the drivers initialize.
+        let descriptors = self.inner.read(|inner| inner.descriptors);
+
+        let mut order = [0; NUM_DRIVERS];
+        let num_drivers = driver::compute_init_order(&descriptors, &mut order)
+            .unwrap_or_else(|x| panic!("Error ordering drivers: {}", x));
+
+        // The console might not be up yet, but the warnings are kept in the kernel log.
+        driver::warn_unordered_drivers(&descriptors, &order[..num_drivers]);
+
+        for i in order[..num_drivers].iter() {
+            let descriptor = descriptors[*i].unwrap();
+            let driver = descriptor.device_driver();
+
+            // Errors before the console is up cannot be printed, obviously. The panic handler
+            // will just safely park the CPU in this case.
+            if let Err(x) = driver.init() {
+                panic!("Error loading driver: {}: {}", driver.compatible(), x);
+            }
+
+            if let Some(callback) = descriptor.post_init_callback() {
+                if let Err(x) = callback() {
+                    panic!(
+                        "Error during driver post-init callback: {}: {}",
+                        driver.compatible(),
+                        x
+                    );
+                }
+            }
+        }
+
+        // All drivers, including the interrupt controller, are up now.
+        for i in order[..num_drivers].iter() {
+            let descriptor = descriptors[*i].unwrap();
+            if let Err(x) = descriptor.device_driver().register_and_enable_irq_handler() {
+                warn!("Error registering IRQ handler: {}", x);
+            }
+        }
+    }
+}
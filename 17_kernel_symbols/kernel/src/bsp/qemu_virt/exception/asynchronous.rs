@@ -0,0 +1,70 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! BSP asynchronous exception handling.
+
+use crate::{bsp, dtb, exception, exception::asynchronous::IPIMessage};
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The IRQ number type of the board's interrupt controller, the GICv2.
+pub use crate::bsp::device_driver::IRQNumber;
+
+pub(in crate::bsp) mod irq_map {
+    use super::IRQNumber;
+
+    pub const IPI: IRQNumber = IRQNumber::new(1);
+    pub const VIRTUAL_TIMER: IRQNumber = IRQNumber::new(27);
+    pub const PL011_UART: IRQNumber = IRQNumber::new(33);
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Return a reference to the IRQ manager.
+pub fn irq_manager(
+) -> &'static impl exception::asynchronous::interface::IRQManager<IRQNumberType = IRQNumber> {
+    &super::super::INTERRUPT_CONTROLLER
+}
+
+/// Prepare the interrupt controller for use by the executing secondary core.
+///
+/// # Safety
+///
+/// - Must be called once on each secondary core, after the boot core initialized the interrupt
+///   controller.
+pub unsafe fn init_secondary_core() -> Result<(), &'static str> {
+    use crate::driver::interface::DeviceDriver;
+
+    // The GIC's init sets up the banked registers and the CPU interface of the executing core.
+    super::super::INTERRUPT_CONTROLLER.init()
+}
+
+/// Return the IRQ number that carries inter-processor messages, and the message that raises it.
+pub fn ipi_irq() -> Option<(IRQNumber, IPIMessage)> {
+    Some((irq_map::IPI, IPIMessage::new(irq_map::IPI.get())))
+}
+
+/// Translate the first interrupt of a device tree node's `interrupts` property to an IRQ number.
+///
+/// The GIC's interrupts are described by three cells: The type, which is 0 for SPIs and 1 for PPIs,
+/// the number relative to the first interrupt of the type, and flags.
+pub(in crate::bsp) fn irq_number_from_dt(node: &dtb::Node) -> Option<IRQNumber> {
+    let interrupts = node.property("interrupts")?;
+    let first = match interrupts.cell(0)? {
+        0 => 32,
+        1 => 16,
+        _ => return None,
+    };
+
+    IRQNumber::checked_new(first + interrupts.cell(1)? as usize)
+}
+
+/// Return the IRQ number of the ARM generic timer's virtual timer, which drives the timer tick.
+pub fn tick_irq() -> IRQNumber {
+    irq_map::VIRTUAL_TIMER
+}
@@ -0,0 +1,244 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! BSP Memory Management.
+//!
+//! The physical memory layout.
+//!
+//! DRAM starts at 0x4000_0000. QEMU loads a kernel binary without a Linux image header to offset
+//! 0x8_0000 into it, and places the device tree blob far behind the kernel. The region preceding
+//! the kernel will be used as the boot core's stack.
+//!
+//! +---------------------------------------+
+//! |                                       | boot_core_stack_start @ 0x4000_0000
+//! |                                       |                                ^
+//! | Boot-core Stack                       |                                | stack
+//! |                                       |                                | growth
+//! |                                       |                                | direction
+//! +---------------------------------------+
+//! |                                       | code_start @ 0x4008_0000 == boot_core_stack_end_exclusive
+//! | .text                                 |
+//! | .rodata                               |
+//! | .got                                  |
+//! | .kernel_symbols                       |
+//! |                                       |
+//! +---------------------------------------+
+//! |                                       | data_start == code_end_exclusive
+//! | .data                                 |
+//! | .bss                                  |
+//! |                                       |
+//! +---------------------------------------+
+//! |                                       | data_end_exclusive
+//! |                                       |
+//!
+//!
+//!
+//!
+//!
+//! The virtual memory layout is as follows:
+//!
+//! +---------------------------------------+
+//! |                                       | code_start @ __kernel_virt_start_addr
+//! | .text                                 |
+//! | .rodata                               |
+//! | .got                                  |
+//! | .kernel_symbols                       |
+//! |                                       |
+//! +---------------------------------------+
+//! |                                       | data_start == code_end_exclusive
+//! | .data                                 |
+//! | .bss                                  |
+//! |                                       |
+//! +---------------------------------------+
+//! |                                       |  mmio_remap_start == data_end_exclusive
+//! | VA region for MMIO remapping          |
+//! |                                       |
+//! +---------------------------------------+
+//! |                                       |  mmio_remap_end_exclusive
+//! | Unmapped guard page                   |
+//! |                                       |
+//! +---------------------------------------+
+//! |                                       | boot_core_stack_start
+//! |                                       |                                ^
+//! | Boot-core Stack                       |                                | stack
+//! |                                       |                                | growth
+//! |                                       |                                | direction
+//! +---------------------------------------+
+//! |                                       | boot_core_stack_end_exclusive
+//! |                                       |
+pub mod mmu;
+
+use crate::{
+    dtb,
+    memory::{mmu::PageAddress, Address, Physical, Virtual},
+};
+use core::cell::UnsafeCell;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+// Symbols from the linker script.
+extern "Rust" {
+    static __code_start: UnsafeCell<()>;
+    static __code_end_exclusive: UnsafeCell<()>;
+
+    static __data_start: UnsafeCell<()>;
+    static __data_end_exclusive: UnsafeCell<()>;
+
+    static __mmio_remap_start: UnsafeCell<()>;
+    static __mmio_remap_end_exclusive: UnsafeCell<()>;
+
+    static __boot_core_stack_start: UnsafeCell<()>;
+    static __boot_core_stack_end_exclusive: UnsafeCell<()>;
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The board's physical memory map.
+#[rustfmt::skip]
+pub(super) mod map {
+    use super::*;
+
+    /// Physical devices, as placed by QEMU's `virt` machine.
+    pub mod mmio {
+        use super::*;
+
+        pub const GICD_START:       Address<Physical> = Address::new(0x0800_0000);
+        pub const GICD_SIZE:        usize             =              0xF04;
+
+        pub const GICC_START:       Address<Physical> = Address::new(0x0801_0000);
+        pub const GICC_SIZE:        usize             =              0x14;
+
+        pub const PL011_UART_START: Address<Physical> = Address::new(0x0900_0000);
+        pub const PL011_UART_SIZE:  usize             =              0x48;
+    }
+
+    /// The DRAM's start address.
+    pub const DRAM_START: usize = 0x4000_0000;
+
+    /// The DRAM's size as configured by the Makefile, for when the device tree does not tell.
+    pub const DRAM_SIZE: usize = 1024 * 1024 * 1024;
+
+    /// The end of the DRAM as configured by the Makefile.
+    pub const END: Address<Physical> = Address::new(DRAM_START + DRAM_SIZE);
+
+    /// The end of the 40 bit physical address space that the MMU is configured for.
+    pub const MAX_END: usize = 1 << 40;
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// Return the DRAM's start address and size from the `/memory` node of the device tree.
+fn dt_dram_region() -> Option<(usize, usize)> {
+    let (start, size) = dtb::boot_device_tree()?.find_node("/memory")?.phys_reg(0)?;
+
+    Some((usize::try_from(start).ok()?, usize::try_from(size).ok()?))
+}
+
+/// Start page address of the code segment.
+///
+/// # Safety
+///
+/// - Value is provided by the linker script and must be trusted as-is.
+#[inline(always)]
+fn virt_code_start() -> PageAddress<Virtual> {
+    PageAddress::from(unsafe { __code_start.get() as usize })
+}
+
+/// Size of the code segment.
+///
+/// # Safety
+///
+/// - Value is provided by the linker script and must be trusted as-is.
+#[inline(always)]
+fn code_size() -> usize {
+    unsafe { (__code_end_exclusive.get() as usize) - (__code_start.get() as usize) }
+}
+
+/// Start page address of the data segment.
+#[inline(always)]
+fn virt_data_start() -> PageAddress<Virtual> {
+    PageAddress::from(unsafe { __data_start.get() as usize })
+}
+
+/// Size of the data segment.
+///
+/// # Safety
+///
+/// - Value is provided by the linker script and must be trusted as-is.
+#[inline(always)]
+fn data_size() -> usize {
+    unsafe { (__data_end_exclusive.get() as usize) - (__data_start.get() as usize) }
+}
+
+/// Start page address of the MMIO remap reservation.
+///
+/// # Safety
+///
+/// - Value is provided by the linker script and must be trusted as-is.
+#[inline(always)]
+fn virt_mmio_remap_start() -> PageAddress<Virtual> {
+    PageAddress::from(unsafe { __mmio_remap_start.get() as usize })
+}
+
+/// Size of the MMIO remap reservation.
+///
+/// # Safety
+///
+/// - Value is provided by the linker script and must be trusted as-is.
+#[inline(always)]
+fn mmio_remap_size() -> usize {
+    unsafe { (__mmio_remap_end_exclusive.get() as usize) - (__mmio_remap_start.get() as usize) }
+}
+
+/// Start page address of the boot core's stack.
+#[inline(always)]
+fn virt_boot_core_stack_start() -> PageAddress<Virtual> {
+    PageAddress::from(unsafe { __boot_core_stack_start.get() as usize })
+}
+
+/// Size of the boot core's stack.
+#[inline(always)]
+fn boot_core_stack_size() -> usize {
+    unsafe {
+        (__boot_core_stack_end_exclusive.get() as usize) - (__boot_core_stack_start.get() as usize)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Return whether a virtual address points into the kernel's code segment.
+pub fn is_kernel_code(addr: Address<Virtual>) -> bool {
+    let start = virt_code_start().into_inner().as_usize();
+
+    (start..start + code_size()).contains(&addr.as_usize())
+}
+
+/// Size of the board's DRAM.
+///
+/// The size is a command line option of QEMU, so it is taken from the device tree. Without one,
+/// the size that the Makefile configures is assumed.
+pub fn board_dram_size() -> Option<usize> {
+    Some(dt_dram_region().map_or(map::DRAM_SIZE, |(_, size)| size))
+}
+
+/// Exclusive end address of the physical address space.
+///
+/// This is the end of the DRAM that the device tree describes, if it fits the MMU's physical
+/// address space. Otherwise, the end of the DRAM that the Makefile configures.
+pub fn phys_addr_space_end_exclusive_addr() -> PageAddress<Physical> {
+    let end = dt_dram_region()
+        .and_then(|(start, size)| start.checked_add(size))
+        .filter(|end| *end > map::DRAM_START && *end <= map::MAX_END)
+        .map_or(map::END, |end| Address::new(end).align_down_page());
+
+    PageAddress::from(end)
+}
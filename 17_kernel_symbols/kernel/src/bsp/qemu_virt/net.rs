@@ -0,0 +1,18 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! BSP network facilities.
+
+use crate::net;
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Return a reference to the on-board Ethernet device, if the board has one that is supported.
+///
+/// The machine's network devices are virtio devices, which are not supported.
+pub fn network_device() -> Option<&'static (dyn net::interface::NetworkDevice + Sync)> {
+    None
+}
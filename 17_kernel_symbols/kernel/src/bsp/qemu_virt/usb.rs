@@ -0,0 +1,18 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! BSP USB facilities.
+
+use crate::usb;
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Return a reference to the USB host controller, if the board has one that is supported.
+///
+/// The machine has no USB host controller unless one is added on QEMU's command line.
+pub fn host_controller() -> Option<&'static (dyn usb::interface::HostController + Sync)> {
+    None
+}
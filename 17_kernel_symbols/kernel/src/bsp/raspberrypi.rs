@@ -2,61 +2,238 @@
 //
 // Copyright (c) 2018-2022 Andre Richter <andre.o.richter@gmail.com>
 
-//! Top-level BSP file for the Raspberry Pi 3 and 4.
+//! Top-level BSP file for the Raspberry Pi 3, 4 and 5.
+//!
+//! One kernel image supports all three boards. [`verify_board()`] detects the SoC at runtime, which
+//! then selects the memory map, the interrupt controller and the drivers. The `bsp_rpi3`,
+//! `bsp_rpi4` and `bsp_rpi5` features select the CPU that the image is optimized for, and the SoC
+//! that is assumed if detection fails. An image built for the Raspberry Pi 3's Cortex-A53 boots on
+//! all three boards.
+//!
+//! The Raspberry Pi Zero 2 W's BCM2710A1 is the BCM2837 of the Raspberry Pi 3 in a different
+//! package. It is told apart by the device tree, or by the `bsp_rpizero2w` feature if there is
+//! none. Only the board's DRAM size and its lack of on-board USB devices differ. The UART is on the
+//! same header pins 8 and 10, i.e. GPIO 14 and 15.
+//!
+//! Support for the Raspberry Pi 5 is limited to the boot core, the console, the timer and the
+//! interrupt controller. The UART on header pins 8 and 10 is part of the RP1 I/O controller, which
+//! sits behind PCIe and is not supported. The console instead uses the BCM2712's own debug UART on
+//! the dedicated 3-pin connector, which runs at 115_200 baud. The interrupt controller is a
+//! GIC-400, as on the RPi4, but in the BCM2712's peripheral window above 64 GiB. The firmware must
+//! load the kernel to the address it is linked for, i.e. `kernel_address=0x80000` in `config.txt`.
 
 pub mod console;
 pub mod cpu;
 pub mod driver;
 pub mod exception;
+pub mod gpio;
 pub mod memory;
+pub mod net;
+pub mod usb;
 
 use super::device_driver;
-use crate::memory::mmu::MMIODescriptor;
-use memory::map::mmio;
+use crate::{dtb, memory::mmu::MMIODescriptor};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use memory::map::mmio::{bcm2711, bcm2837};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The SoCs of the supported boards.
+///
+/// The discriminants are indices into `SOC_IDS`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Soc {
+    BCM2837 = 0,
+    BCM2711 = 1,
+    BCM2712 = 2,
+}
+
+/// Properties that identify a SoC at runtime.
+struct SocId {
+    soc: Soc,
+    name: &'static str,
+    dt_compatible: &'static str,
+    cpu_part_number: u16,
+}
+
+static SOC_IDS: [SocId; 3] = [
+    SocId {
+        soc: Soc::BCM2837,
+        name: "BCM2837",
+        dt_compatible: "brcm,bcm2837",
+        cpu_part_number: 0xD03, // Cortex-A53
+    },
+    SocId {
+        soc: Soc::BCM2711,
+        name: "BCM2711",
+        dt_compatible: "brcm,bcm2711",
+        cpu_part_number: 0xD08, // Cortex-A72
+    },
+    SocId {
+        soc: Soc::BCM2712,
+        name: "BCM2712",
+        dt_compatible: "brcm,bcm2712",
+        cpu_part_number: 0xD0B, // Cortex-A76
+    },
+];
+
+/// The SoC that is assumed if detection fails.
+#[cfg(feature = "bsp_rpi3")]
+const DEFAULT_SOC: Soc = Soc::BCM2837;
+
+#[cfg(feature = "bsp_rpi4")]
+const DEFAULT_SOC: Soc = Soc::BCM2711;
+
+#[cfg(feature = "bsp_rpi5")]
+const DEFAULT_SOC: Soc = Soc::BCM2712;
+
+/// Device tree compatible string of the Raspberry Pi Zero 2 W.
+const RPIZERO2W_DT_COMPATIBLE: &str = "raspberrypi,model-zero-2-w";
 
 //--------------------------------------------------------------------------------------------------
 // Global instances
 //--------------------------------------------------------------------------------------------------
 
-static GPIO: device_driver::GPIO =
-    unsafe { device_driver::GPIO::new(MMIODescriptor::new(mmio::GPIO_START, mmio::GPIO_SIZE)) };
+/// The SoC the kernel is running on, plus one. Zero until it was detected.
+static DETECTED_SOC: AtomicUsize = AtomicUsize::new(0);
+
+// The drivers are created with the MMIO ranges of the BCM2711, or of the BCM2837 for its interrupt
+// controller. `driver::init()` sets the ones of the SoC the kernel is running on.
+
+static GPIO: device_driver::GPIO = unsafe {
+    device_driver::GPIO::new(
+        MMIODescriptor::new(bcm2711::GPIO_START, bcm2711::GPIO_SIZE),
+        device_driver::GPIOModel::BCM2711,
+    )
+};
 
 static PL011_UART: device_driver::PL011Uart = unsafe {
     device_driver::PL011Uart::new(
-        MMIODescriptor::new(mmio::PL011_UART_START, mmio::PL011_UART_SIZE),
-        exception::asynchronous::irq_map::PL011_UART,
+        MMIODescriptor::new(bcm2711::PL011_UART_START, bcm2711::PL011_UART_SIZE),
+        exception::asynchronous::irq_map::gic::PL011_UART,
     )
 };
 
-#[cfg(feature = "bsp_rpi3")]
-static INTERRUPT_CONTROLLER: device_driver::InterruptController = unsafe {
+static MAILBOX: device_driver::Mailbox = unsafe {
+    device_driver::Mailbox::new(MMIODescriptor::new(
+        bcm2711::MAILBOX_START,
+        bcm2711::MAILBOX_SIZE,
+    ))
+};
+
+static USB_HOST: device_driver::DWC2 =
+    unsafe { device_driver::DWC2::new(MMIODescriptor::new(bcm2711::USB_START, bcm2711::USB_SIZE)) };
+
+static ETHERNET: device_driver::GENET = unsafe {
+    device_driver::GENET::new(MMIODescriptor::new(
+        bcm2711::GENET_START,
+        bcm2711::GENET_SIZE,
+    ))
+};
+
+/// The BCM2837's interrupt controller.
+static BCM_INTERRUPT_CONTROLLER: device_driver::InterruptController = unsafe {
     device_driver::InterruptController::new(
-        MMIODescriptor::new(mmio::LOCAL_IC_START, mmio::LOCAL_IC_SIZE),
-        MMIODescriptor::new(mmio::PERIPHERAL_IC_START, mmio::PERIPHERAL_IC_SIZE),
+        MMIODescriptor::new(bcm2837::LOCAL_IC_START, bcm2837::LOCAL_IC_SIZE),
+        MMIODescriptor::new(bcm2837::PERIPHERAL_IC_START, bcm2837::PERIPHERAL_IC_SIZE),
     )
 };
 
-#[cfg(feature = "bsp_rpi4")]
-static INTERRUPT_CONTROLLER: device_driver::GICv2 = unsafe {
+/// The GIC-400 of the BCM2711 and BCM2712.
+static GIC: device_driver::GICv2 = unsafe {
     device_driver::GICv2::new(
-        MMIODescriptor::new(mmio::GICD_START, mmio::GICD_SIZE),
-        MMIODescriptor::new(mmio::GICC_START, mmio::GICC_SIZE),
+        MMIODescriptor::new(bcm2711::GICD_START, bcm2711::GICD_SIZE),
+        MMIODescriptor::new(bcm2711::GICC_START, bcm2711::GICC_SIZE),
     )
 };
 
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// Look up the SoC the kernel is running on.
+///
+/// The device tree's root node names the SoC in its `compatible` property. Without a device tree,
+/// or if it names an unknown SoC, the part number of the executing core tells the SoCs apart.
+fn find_soc_id() -> Option<&'static SocId> {
+    let from_dt = dtb::boot_device_tree().and_then(|dt| {
+        let root = dt.root();
+        SOC_IDS.iter().find(|x| root.is_compatible(x.dt_compatible))
+    });
+
+    from_dt.or_else(|| {
+        let part_number = crate::cpu::part_number();
+        SOC_IDS.iter().find(|x| x.cpu_part_number == part_number)
+    })
+}
+
+/// Detect the SoC and remember it for `soc()`. An unknown SoC is taken for `DEFAULT_SOC`.
+fn store_soc() -> Soc {
+    let soc = find_soc_id().map_or(DEFAULT_SOC, |x| x.soc);
+    DETECTED_SOC.store(soc as usize + 1, Ordering::Relaxed);
+
+    soc
+}
+
+/// The SoC the kernel is running on.
+///
+/// Before `verify_board()` ran, it might have been detected without the device tree.
+fn soc() -> Soc {
+    match DETECTED_SOC.load(Ordering::Relaxed) {
+        0 => store_soc(),
+        x => SOC_IDS[x - 1].soc,
+    }
+}
+
+/// The model of the SoC's GPIO controller. `None` if it is not supported.
+fn gpio_model() -> Option<device_driver::GPIOModel> {
+    match soc() {
+        Soc::BCM2837 => Some(device_driver::GPIOModel::BCM2837),
+        Soc::BCM2711 => Some(device_driver::GPIOModel::BCM2711),
+        Soc::BCM2712 => None,
+    }
+}
+
+/// Check if the board is a Raspberry Pi Zero 2 W rather than a Raspberry Pi 3.
+fn is_rpizero2w() -> bool {
+    match dtb::boot_device_tree() {
+        Some(dt) => dt.root().is_compatible(RPIZERO2W_DT_COMPATIBLE),
+        None => cfg!(feature = "bsp_rpizero2w"),
+    }
+}
+
 //--------------------------------------------------------------------------------------------------
 // Public Code
 //--------------------------------------------------------------------------------------------------
 
 /// Board identification.
 pub fn board_name() -> &'static str {
-    #[cfg(feature = "bsp_rpi3")]
-    {
-        "Raspberry Pi 3"
+    match soc() {
+        Soc::BCM2837 if is_rpizero2w() => "Raspberry Pi Zero 2 W",
+        Soc::BCM2837 => "Raspberry Pi 3",
+        Soc::BCM2711 if cfg!(feature = "rpi4_peri_high") => "Raspberry Pi 4 (high peripherals)",
+        Soc::BCM2711 => "Raspberry Pi 4",
+        Soc::BCM2712 => "Raspberry Pi 5",
     }
+}
 
-    #[cfg(feature = "bsp_rpi4")]
-    {
-        "Raspberry Pi 4"
-    }
+/// Detect the SoC the kernel is running on.
+///
+/// `None` if it is none of the supported ones.
+pub fn detect_soc() -> Option<&'static str> {
+    find_soc_id().map(|x| x.name)
+}
+
+/// Detect the SoC the kernel is running on, which selects the memory map, the interrupt controller
+/// and the drivers.
+///
+/// Must be called after `dtb::init()`, so that the device tree is used. An unknown SoC is not
+/// treated as an error, since it might be a newer revision or an emulator. It is driven like
+/// `DEFAULT_SOC`.
+pub fn verify_board() -> Result<(), &'static str> {
+    store_soc();
+
+    Ok(())
 }
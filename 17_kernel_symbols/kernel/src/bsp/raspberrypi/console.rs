@@ -4,9 +4,23 @@
 
 //! BSP console facilities.
 
+use super::Soc;
 use crate::{bsp::device_driver, console, cpu, driver};
+
+#[cfg(feature = "test_build")]
+use crate::qemu_exit;
 use core::fmt;
 
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Rate of the fixed clock of the BCM2712's debug UART.
+pub(super) const DEBUG_UART_CLOCK_RATE_HZ: u32 = 9_216_000;
+
+/// The fastest standard rate that the debug UART's clock can produce.
+pub(super) const DEBUG_UART_BAUD_RATE: u32 = 115_200;
+
 //--------------------------------------------------------------------------------------------------
 // Public Code
 //--------------------------------------------------------------------------------------------------
@@ -27,23 +41,30 @@ pub unsafe fn panic_console_out() -> impl fmt::Write {
 
     // If remapping of the driver's MMIO hasn't already happened, we won't be able to print. Just
     // park the CPU core in this case.
-    let gpio_mmio_start_addr = match super::GPIO.virt_mmio_start_addr() {
-        None => cpu::wait_forever(),
-        Some(x) => x,
-    };
+    // The BCM2712's debug UART has dedicated pins.
+    if let Some(gpio_model) = super::gpio_model() {
+        let gpio_mmio_start_addr = match super::GPIO.virt_mmio_start_addr() {
+            None => cpu::wait_forever(),
+            Some(x) => x,
+        };
+
+        let mut panic_gpio = device_driver::PanicGPIO::new(gpio_mmio_start_addr, gpio_model);
+        panic_gpio
+            .init(None)
+            .unwrap_or_else(|_| cpu::wait_forever());
+        panic_gpio.map_pl011_uart();
+    }
 
     let uart_mmio_start_addr = match super::PL011_UART.virt_mmio_start_addr() {
         None => cpu::wait_forever(),
         Some(x) => x,
     };
 
-    let mut panic_gpio = device_driver::PanicGPIO::new(gpio_mmio_start_addr);
     let mut panic_uart = device_driver::PanicUart::new(uart_mmio_start_addr);
+    if super::soc() == Soc::BCM2712 {
+        panic_uart.set_initial_rates(DEBUG_UART_CLOCK_RATE_HZ, DEBUG_UART_BAUD_RATE);
+    }
 
-    panic_gpio
-        .init(None)
-        .unwrap_or_else(|_| cpu::wait_forever());
-    panic_gpio.map_pl011_uart();
     panic_uart
         .init(None)
         .unwrap_or_else(|_| cpu::wait_forever());
@@ -68,14 +89,40 @@ pub unsafe fn panic_console_out() -> impl fmt::Write {
 
     panic_uart
         .init(None)
-        .unwrap_or_else(|_| cpu::qemu_exit_failure());
+        .unwrap_or_else(|_| qemu_exit::exit_failure());
 
     panic_uart
 }
 
-/// Return a reference to the console.
-pub fn console() -> &'static impl console::interface::All {
-    &super::PL011_UART
+/// Route the UART's RTS and CTS lines to pins 17 and 16 and turn on hardware flow control.
+///
+/// Helps with long transfers over USB-serial adapters that cannot keep up with the baud rate. The
+/// other side must use flow control as well, or output will stall. The connector of the BCM2712's
+/// debug UART has no RTS and CTS lines.
+pub fn enable_hw_flow_control() -> Result<(), &'static str> {
+    if super::soc() == Soc::BCM2712 {
+        return Err("The debug UART has no flow control lines");
+    }
+
+    super::GPIO.map_pl011_uart_flow_control()?;
+    super::PL011_UART.set_flow_control(true);
+
+    Ok(())
+}
+
+/// Run the loopback self test of the board's UART.
+#[cfg(feature = "post")]
+pub fn uart_self_test() -> Result<(), &'static str> {
+    super::PL011_UART.self_test()
+}
+
+/// Register the board's UART as a console sink.
+///
+/// # Safety
+///
+/// - Must only be called during kernel init, after the UART was initialized.
+pub unsafe fn register_console_sinks() -> Result<(), &'static str> {
+    console::register_sink("PL011", &super::PL011_UART)
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -93,6 +140,7 @@ pub fn qemu_bring_up_console() {
     unsafe {
         super::PL011_UART
             .init()
-            .unwrap_or_else(|_| cpu::qemu_exit_failure());
+            .unwrap_or_else(|_| qemu_exit::exit_failure());
+        register_console_sinks().unwrap_or_else(|_| qemu_exit::exit_failure());
     }
 }
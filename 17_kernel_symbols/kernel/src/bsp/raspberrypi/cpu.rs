@@ -4,11 +4,62 @@
 
 //! BSP Processor code.
 
+use crate::{
+    cpu,
+    memory::{Address, Physical},
+};
+
 //--------------------------------------------------------------------------------------------------
 // Public Definitions
 //--------------------------------------------------------------------------------------------------
 
+/// The number of processor cores.
+pub const NUM_CORES: usize = 4;
+
 /// Used by `arch` code to find the early boot core.
 #[no_mangle]
 #[link_section = ".text._start_arguments"]
 pub static BOOT_CORE_ID: u64 = 0;
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Release a secondary core from the firmware's spin-table, so that it starts executing at the
+/// given physical address.
+///
+/// The BCM2712's firmware starts secondary cores with PSCI instead of a spin-table, and its
+/// Cortex-A76 cores report their number in MPIDR_EL1's Aff1 instead of Aff0. Until both are
+/// supported, only the boot core runs there.
+///
+/// # Safety
+///
+/// - The entry must be ready to execute with the MMU off.
+pub unsafe fn release_secondary_core(
+    core: usize,
+    phys_entry_addr: Address<Physical>,
+) -> Result<(), &'static str> {
+    if super::soc() == super::Soc::BCM2712 {
+        return Err("Secondary cores of the BCM2712 not supported");
+    }
+
+    let release_addr = super::memory::virt_spin_table_release_addr(core).as_usize();
+
+    core::ptr::write_volatile(release_addr as *mut u64, phys_entry_addr.as_usize() as u64);
+
+    // The parked core polls with its caches off.
+    cpu::clean_dcache_range(release_addr, core::mem::size_of::<u64>());
+    cpu::send_event();
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+/// The way test builds make QEMU exit. The Raspberry Pi machines have no exit device.
+#[cfg(feature = "test_build")]
+pub fn qemu_exit_backend() -> &'static (dyn crate::qemu_exit::interface::Exit + Sync) {
+    &crate::qemu_exit::Semihosting
+}
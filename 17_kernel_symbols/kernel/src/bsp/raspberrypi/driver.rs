@@ -4,15 +4,31 @@
 
 //! BSP driver support.
 
-use crate::driver;
+use super::{exception::asynchronous, memory::mmio_map, Soc};
+use crate::{
+    driver::{self, DeviceDriverDescriptor, DeviceTreeMatch},
+    dtb,
+    memory::mmu::MMIODescriptor,
+    synchronization::{interface::ReadWriteEx, RwLock},
+    warn,
+};
+use core::sync::atomic::{AtomicBool, Ordering};
 
 //--------------------------------------------------------------------------------------------------
 // Private Definitions
 //--------------------------------------------------------------------------------------------------
 
+/// Maximum number of drivers that can be registered.
+const NUM_DRIVERS: usize = 8;
+
+struct DriverManagerInner {
+    next_index: usize,
+    descriptors: [Option<DeviceDriverDescriptor>; NUM_DRIVERS],
+}
+
 /// Device Driver Manager type.
 struct BSPDriverManager {
-    device_drivers: [&'static (dyn DeviceDriver + Sync); 3],
+    inner: RwLock<DriverManagerInner>,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -20,13 +36,306 @@ struct BSPDriverManager {
 //--------------------------------------------------------------------------------------------------
 
 static BSP_DRIVER_MANAGER: BSPDriverManager = BSPDriverManager {
-    device_drivers: [
-        &super::GPIO,
-        &super::PL011_UART,
-        &super::INTERRUPT_CONTROLLER,
-    ],
+    inner: RwLock::new(DriverManagerInner::new()),
 };
 
+static GPIO_DESCRIPTOR: DeviceDriverDescriptor =
+    DeviceDriverDescriptor::new(&super::GPIO, &[], Some(post_init_gpio));
+
+static MAILBOX_DESCRIPTOR: DeviceDriverDescriptor =
+    DeviceDriverDescriptor::new(&super::MAILBOX, &[], None);
+
+static PL011_UART_DESCRIPTOR: DeviceDriverDescriptor = DeviceDriverDescriptor::new(
+    &super::PL011_UART,
+    &[GPIO_COMPATIBLE, MAILBOX_COMPATIBLE],
+    Some(post_init_pl011_uart),
+);
+
+/// Without the mailbox, the UART keeps the default clock rate.
+static PL011_UART_DEFAULT_CLOCK_DESCRIPTOR: DeviceDriverDescriptor = DeviceDriverDescriptor::new(
+    &super::PL011_UART,
+    &[GPIO_COMPATIBLE],
+    Some(post_init_pl011_uart_default_clock),
+);
+
+/// The BCM2712's debug UART has dedicated pins and a fixed clock.
+static DEBUG_UART_DESCRIPTOR: DeviceDriverDescriptor = DeviceDriverDescriptor::new(
+    &super::PL011_UART,
+    &[],
+    Some(post_init_pl011_uart_default_clock),
+);
+
+static BCM_INTERRUPT_CONTROLLER_DESCRIPTOR: DeviceDriverDescriptor =
+    DeviceDriverDescriptor::new(&super::BCM_INTERRUPT_CONTROLLER, &[], None);
+
+static GIC_DESCRIPTOR: DeviceDriverDescriptor = DeviceDriverDescriptor::new(&super::GIC, &[], None);
+
+static USB_HOST_DESCRIPTOR: DeviceDriverDescriptor =
+    DeviceDriverDescriptor::new(&super::USB_HOST, &[], None);
+
+static ETHERNET_DESCRIPTOR: DeviceDriverDescriptor =
+    DeviceDriverDescriptor::new(&super::ETHERNET, &[], None);
+
+/// Device tree compatible strings of the BCM2837's devices and the drivers that serve them.
+static BCM2837_DEVICE_TREE_MATCHES: [DeviceTreeMatch; 5] = [
+    DeviceTreeMatch::new(&["brcm,bcm2835-gpio"], probe_gpio),
+    DeviceTreeMatch::new(&["brcm,bcm2835-mbox"], probe_mailbox),
+    DeviceTreeMatch::new(&["arm,pl011"], probe_pl011_uart),
+    DeviceTreeMatch::new(
+        &["brcm,bcm2836-armctrl-ic", "brcm,bcm2835-armctrl-ic"],
+        probe_bcm_interrupt_controller,
+    ),
+    DeviceTreeMatch::new(&["brcm,bcm2708-usb", "brcm,bcm2835-usb"], probe_usb_host),
+];
+
+/// Device tree compatible strings of the BCM2711's devices and the drivers that serve them.
+static BCM2711_DEVICE_TREE_MATCHES: [DeviceTreeMatch; 6] = [
+    DeviceTreeMatch::new(&["brcm,bcm2711-gpio"], probe_gpio),
+    DeviceTreeMatch::new(&["brcm,bcm2835-mbox"], probe_mailbox),
+    DeviceTreeMatch::new(&["arm,pl011"], probe_pl011_uart),
+    DeviceTreeMatch::new(&["arm,gic-400"], probe_gic),
+    DeviceTreeMatch::new(&["brcm,bcm2708-usb", "brcm,bcm2835-usb"], probe_usb_host),
+    DeviceTreeMatch::new(&["brcm,bcm2711-genet-v5"], probe_ethernet),
+];
+
+/// Device tree compatible strings of the BCM2712's devices and the drivers that serve them.
+///
+/// The debug UART comes before the UARTs of the RP1 in the device tree, which use the same
+/// compatible string.
+static BCM2712_DEVICE_TREE_MATCHES: [DeviceTreeMatch; 2] = [
+    DeviceTreeMatch::new(&["arm,pl011-axi", "arm,pl011"], probe_debug_uart),
+    DeviceTreeMatch::new(&["arm,gic-400"], probe_gic),
+];
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// The UART's pins are muxed by the GPIO's post-init callback, so the UART depends on it.
+const GPIO_COMPATIBLE: &str = "BCM GPIO";
+
+/// The UART's clock rate is queried from the firmware, so the UART depends on the mailbox.
+const MAILBOX_COMPATIBLE: &str = "BCM VideoCore Mailbox";
+
+/// Configure PL011Uart's output pins.
+unsafe fn post_init_gpio() -> Result<(), &'static str> {
+    super::GPIO.map_pl011_uart()
+}
+
+/// Adapt the PL011Uart's baud rate divisors to the actual UART clock.
+///
+/// Not fatal if it fails, because the default divisors match the clock set up in `config.txt`.
+unsafe fn post_init_pl011_uart() -> Result<(), &'static str> {
+    use crate::bsp::device_driver::ClockId;
+
+    super::console::register_console_sinks()?;
+
+    let result = super::MAILBOX
+        .clock_rate(ClockId::Uart)
+        .and_then(|rate| super::PL011_UART.set_clock_rate(rate));
+
+    if let Err(x) = result {
+        warn!("UART: Keeping the default clock rate: {}", x);
+    }
+
+    #[cfg(feature = "uart_flow_control")]
+    enable_uart_flow_control();
+
+    Ok(())
+}
+
+/// Make the UART a console sink, without asking the firmware for the actual UART clock.
+unsafe fn post_init_pl011_uart_default_clock() -> Result<(), &'static str> {
+    super::console::register_console_sinks()?;
+
+    #[cfg(feature = "uart_flow_control")]
+    enable_uart_flow_control();
+
+    Ok(())
+}
+
+/// Turn on the UART's hardware flow control, as requested by the build.
+///
+/// Not fatal if it fails, the console then keeps working without flow control.
+#[cfg(feature = "uart_flow_control")]
+fn enable_uart_flow_control() {
+    if let Err(x) = super::console::enable_hw_flow_control() {
+        warn!("UART: No hardware flow control: {}", x);
+    }
+}
+
+/// Check if a driver with the given `compatible()` string has been registered.
+fn is_registered(compatible: &str) -> bool {
+    use driver::interface::DriverManager;
+
+    let mut found = false;
+    driver_manager()
+        .for_each_device_driver(|x| found |= x.device_driver().compatible() == compatible);
+
+    found
+}
+
+/// Return the MMIO descriptor of the node's `index`-th `reg` entry, if there is a usable one.
+fn dt_mmio_descriptor(node: Option<&dtb::Node>, index: usize) -> Option<MMIODescriptor> {
+    node.and_then(|node| driver::mmio_descriptor_from_dt(node, index))
+}
+
+/// Return the MMIO descriptor of the node's `index`-th `reg` entry if there is a usable one, and
+/// the one from the SoC's memory map otherwise.
+fn mmio_descriptor(
+    node: Option<&dtb::Node>,
+    index: usize,
+    from_map: Option<MMIODescriptor>,
+) -> Result<MMIODescriptor, &'static str> {
+    dt_mmio_descriptor(node, index)
+        .or(from_map)
+        .ok_or("MMIO range unknown")
+}
+
+/// Take the GPIO's MMIO range from the device tree.
+fn probe_gpio(node: Option<&dtb::Node>) -> Result<DeviceDriverDescriptor, &'static str> {
+    let model = super::gpio_model().ok_or("GPIO not supported on this SoC")?;
+    let mmio_descriptor = mmio_descriptor(node, 0, mmio_map().gpio)?;
+
+    unsafe { super::GPIO.set_mmio_descriptor(mmio_descriptor) };
+    super::GPIO.set_model(model);
+
+    Ok(GPIO_DESCRIPTOR)
+}
+
+/// Take the mailbox's MMIO range from the device tree.
+fn probe_mailbox(node: Option<&dtb::Node>) -> Result<DeviceDriverDescriptor, &'static str> {
+    let mmio_descriptor = mmio_descriptor(node, 0, mmio_map().mailbox)?;
+    unsafe { super::MAILBOX.set_mmio_descriptor(mmio_descriptor) };
+
+    Ok(MAILBOX_DESCRIPTOR)
+}
+
+/// Take the UART's MMIO range and IRQ number from the device tree.
+///
+/// Whatever the device tree does not tell, the UART takes from the SoC's memory and IRQ maps.
+fn set_up_pl011_uart(node: Option<&dtb::Node>) {
+    let mmio_descriptor = dt_mmio_descriptor(node, 0).unwrap_or(mmio_map().pl011_uart);
+    unsafe { super::PL011_UART.set_mmio_descriptor(mmio_descriptor) };
+
+    let irq_number = node
+        .and_then(asynchronous::irq_number_from_dt)
+        .unwrap_or_else(asynchronous::pl011_uart_irq);
+    super::PL011_UART.set_irq_number(irq_number);
+}
+
+/// Probe the UART on header pins 8 and 10 of the BCM2837 and BCM2711 boards.
+///
+/// The mailbox is probed before, so that the UART can do without it if it is missing.
+fn probe_pl011_uart(node: Option<&dtb::Node>) -> Result<DeviceDriverDescriptor, &'static str> {
+    set_up_pl011_uart(node);
+
+    if !is_registered(MAILBOX_COMPATIBLE) {
+        warn!("UART: No mailbox, keeping the default clock rate");
+        return Ok(PL011_UART_DEFAULT_CLOCK_DESCRIPTOR);
+    }
+
+    Ok(PL011_UART_DESCRIPTOR)
+}
+
+/// Probe the BCM2712's debug UART, whose clock differs from the driver's default.
+fn probe_debug_uart(node: Option<&dtb::Node>) -> Result<DeviceDriverDescriptor, &'static str> {
+    set_up_pl011_uart(node);
+    super::PL011_UART.set_initial_rates(
+        super::console::DEBUG_UART_CLOCK_RATE_HZ,
+        super::console::DEBUG_UART_BAUD_RATE,
+    );
+
+    Ok(DEBUG_UART_DESCRIPTOR)
+}
+
+/// Take the BCM2837's peripheral interrupt controller's MMIO range from the device tree.
+///
+/// The local interrupt controller has a node of its own, which is looked up separately.
+fn probe_bcm_interrupt_controller(
+    node: Option<&dtb::Node>,
+) -> Result<DeviceDriverDescriptor, &'static str> {
+    let map = mmio_map();
+
+    let peripheral = mmio_descriptor(node, 0, map.peripheral_ic)?;
+    unsafe { super::BCM_INTERRUPT_CONTROLLER.set_peripheral_mmio_descriptor(peripheral) };
+
+    let local_ic_node = dtb::boot_device_tree().and_then(|dt| {
+        dt.nodes()
+            .find(|x| x.is_enabled() && x.is_compatible("brcm,bcm2836-l1-intc"))
+    });
+
+    let local = mmio_descriptor(local_ic_node.as_ref(), 0, map.local_ic)?;
+    unsafe { super::BCM_INTERRUPT_CONTROLLER.set_local_mmio_descriptor(local) };
+
+    Ok(BCM_INTERRUPT_CONTROLLER_DESCRIPTOR)
+}
+
+/// Take the GIC's distributor and CPU interface MMIO ranges from the device tree.
+///
+/// They are the first two `reg` entries. Both are needed, otherwise the ones from the SoC's memory
+/// map are used.
+fn probe_gic(node: Option<&dtb::Node>) -> Result<DeviceDriverDescriptor, &'static str> {
+    let map = mmio_map();
+
+    let (gicd, gicc) = match (dt_mmio_descriptor(node, 0), dt_mmio_descriptor(node, 1)) {
+        (Some(gicd), Some(gicc)) => (gicd, gicc),
+        _ => (
+            map.gicd.ok_or("MMIO range unknown")?,
+            map.gicc.ok_or("MMIO range unknown")?,
+        ),
+    };
+    unsafe { super::GIC.set_mmio_descriptors(gicd, gicc) };
+
+    Ok(GIC_DESCRIPTOR)
+}
+
+/// Take the USB host controller's MMIO range from the device tree.
+fn probe_usb_host(node: Option<&dtb::Node>) -> Result<DeviceDriverDescriptor, &'static str> {
+    let mmio_descriptor = mmio_descriptor(node, 0, mmio_map().usb)?;
+    unsafe { super::USB_HOST.set_mmio_descriptor(mmio_descriptor) };
+
+    Ok(USB_HOST_DESCRIPTOR)
+}
+
+/// Take the Ethernet MAC's MMIO range from the device tree.
+fn probe_ethernet(node: Option<&dtb::Node>) -> Result<DeviceDriverDescriptor, &'static str> {
+    let mmio_descriptor = mmio_descriptor(node, 0, mmio_map().genet)?;
+    unsafe { super::ETHERNET.set_mmio_descriptor(mmio_descriptor) };
+
+    Ok(ETHERNET_DESCRIPTOR)
+}
+
+/// Device tree compatible strings of the SoC's devices and the drivers that serve them.
+fn device_tree_matches() -> &'static [DeviceTreeMatch] {
+    match super::soc() {
+        Soc::BCM2837 => &BCM2837_DEVICE_TREE_MATCHES,
+        Soc::BCM2711 => &BCM2711_DEVICE_TREE_MATCHES,
+        Soc::BCM2712 => &BCM2712_DEVICE_TREE_MATCHES,
+    }
+}
+
+impl DriverManagerInner {
+    pub const fn new() -> Self {
+        Self {
+            next_index: 0,
+            descriptors: [None; NUM_DRIVERS],
+        }
+    }
+
+    fn register_driver(&mut self, descriptor: DeviceDriverDescriptor) -> Result<(), &'static str> {
+        let slot = self
+            .descriptors
+            .get_mut(self.next_index)
+            .ok_or("Storage for device drivers exhausted")?;
+
+        *slot = Some(descriptor);
+        self.next_index += 1;
+
+        Ok(())
+    }
+}
+
 //--------------------------------------------------------------------------------------------------
 // Public Code
 //--------------------------------------------------------------------------------------------------
@@ -36,26 +345,82 @@ pub fn driver_manager() -> &'static impl driver::interface::DriverManager {
     &BSP_DRIVER_MANAGER
 }
 
+/// Register the device drivers of the SoC the kernel is running on with the driver manager.
+///
+/// Devices that the firmware's device tree disables are left out. The MMIO ranges and IRQ numbers
+/// are taken from the device tree where possible, and from the SoC's static maps otherwise.
+///
+/// # Safety
+///
+/// - Must only be called during kernel init, after `dtb::init()` and `bsp::verify_board()`.
+pub unsafe fn init() -> Result<(), &'static str> {
+    static INIT_DONE: AtomicBool = AtomicBool::new(false);
+    if INIT_DONE.load(Ordering::Relaxed) {
+        return Err("Init already done");
+    }
+
+    driver::probe_device_tree(
+        driver_manager(),
+        dtb::boot_device_tree().as_ref(),
+        device_tree_matches(),
+    );
+
+    INIT_DONE.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
 //------------------------------------------------------------------------------
 // OS Interface Code
 //------------------------------------------------------------------------------
-use driver::interface::DeviceDriver;
 
 impl driver::interface::DriverManager for BSPDriverManager {
-    fn all_device_drivers(&self) -> &[&'static (dyn DeviceDriver + Sync)] {
-        &self.device_drivers[..]
+    fn register_driver(&self, descriptor: DeviceDriverDescriptor) -> Result<(), &'static str> {
+        self.inner.write(|inner| inner.register_driver(descriptor))
     }
 
-    fn early_print_device_drivers(&self) -> &[&'static (dyn DeviceDriver + Sync)] {
-        &self.device_drivers[0..=1]
+    fn for_each_device_driver(&self, mut f: impl FnMut(&DeviceDriverDescriptor)) {
+        self.inner
+            .read(|inner| inner.descriptors.iter().flatten().for_each(|x| f(x)))
     }
 
-    fn non_early_print_device_drivers(&self) -> &[&'static (dyn DeviceDriver + Sync)] {
-        &self.device_drivers[2..]
-    }
+    unsafe fn init_drivers_and_irqs(&self) {
+        // Work on a copy, so that the lock is not held while the drivers initialize.
+        let descriptors = self.inner.read(|inner| inner.descriptors);
+
+        let mut order = [0; NUM_DRIVERS];
+        let num_drivers = driver::compute_init_order(&descriptors, &mut order)
+            .unwrap_or_else(|x| panic!("Error ordering drivers: {}", x));
+
+        // The console might not be up yet, but the warnings are kept in the kernel log.
+        driver::warn_unordered_drivers(&descriptors, &order[..num_drivers]);
+
+        for i in order[..num_drivers].iter() {
+            let descriptor = descriptors[*i].unwrap();
+            let driver = descriptor.device_driver();
+
+            // Errors before the console is up cannot be printed, obviously. The panic handler
+            // will just safely park the CPU in this case.
+            if let Err(x) = driver.init() {
+                panic!("Error loading driver: {}: {}", driver.compatible(), x);
+            }
+
+            if let Some(callback) = descriptor.post_init_callback() {
+                if let Err(x) = callback() {
+                    panic!(
+                        "Error during driver post-init callback: {}: {}",
+                        driver.compatible(),
+                        x
+                    );
+                }
+            }
+        }
 
-    fn post_early_print_device_driver_init(&self) {
-        // Configure PL011Uart's output pins.
-        super::GPIO.map_pl011_uart();
+        // All drivers, including the interrupt controller, are up now.
+        for i in order[..num_drivers].iter() {
+            let descriptor = descriptors[*i].unwrap();
+            if let Err(x) = descriptor.device_driver().register_and_enable_irq_handler() {
+                warn!("Error registering IRQ handler: {}", x);
+            }
+        }
     }
 }
@@ -4,24 +4,104 @@
 
 //! BSP asynchronous exception handling.
 
-use crate::{bsp, exception};
+use super::super::{Soc, BCM_INTERRUPT_CONTROLLER, GIC};
+use crate::{
+    bsp::device_driver::{self, BCMIRQNumber, LocalIRQ, PeripheralIRQ},
+    dtb,
+    exception::asynchronous::{interface::IRQManager, IPIMessage, IRQContext, IRQDescriptor},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Forwards to the interrupt controller of the SoC the kernel is running on.
+struct SocIRQManager;
 
 //--------------------------------------------------------------------------------------------------
 // Public Definitions
 //--------------------------------------------------------------------------------------------------
 
-#[cfg(feature = "bsp_rpi3")]
+/// An IRQ number of one of the supported interrupt controllers.
+///
+/// IRQ numbers are only handed out for the interrupt controller of the SoC the kernel is running
+/// on, so the IRQ manager can forward them without checking.
+#[derive(Copy, Clone)]
+pub enum IRQNumber {
+    BCM(BCMIRQNumber),
+    GIC(device_driver::IRQNumber),
+}
+
 pub(in crate::bsp) mod irq_map {
-    use super::bsp::device_driver::{IRQNumber, PeripheralIRQ};
+    use super::*;
 
-    pub const PL011_UART: IRQNumber = IRQNumber::Peripheral(PeripheralIRQ::new(57));
+    /// The BCM2837's interrupt controller.
+    pub mod bcm2837 {
+        use super::*;
+
+        pub const VIRTUAL_TIMER: IRQNumber = IRQNumber::BCM(BCMIRQNumber::Local(LocalIRQ::new(3)));
+        pub const PL011_UART: IRQNumber =
+            IRQNumber::BCM(BCMIRQNumber::Peripheral(PeripheralIRQ::new(57)));
+    }
+
+    /// The GIC-400 of the BCM2711 and BCM2712.
+    pub mod gic {
+        use super::*;
+
+        pub const IPI: device_driver::IRQNumber = device_driver::IRQNumber::new(1);
+        pub const VIRTUAL_TIMER: IRQNumber = IRQNumber::GIC(device_driver::IRQNumber::new(27));
+
+        // On the RPi5, the debug UART.
+        pub const PL011_UART: IRQNumber = IRQNumber::GIC(device_driver::IRQNumber::new(153));
+    }
 }
 
-#[cfg(feature = "bsp_rpi4")]
-pub(in crate::bsp) mod irq_map {
-    use super::bsp::device_driver::IRQNumber;
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static SOC_IRQ_MANAGER: SocIRQManager = SocIRQManager;
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// Check if the SoC the kernel is running on has a GIC, instead of the BCM2837's controller.
+fn has_gic() -> bool {
+    super::super::soc() != Soc::BCM2837
+}
+
+/// Translate the first interrupt of a device tree node's `interrupts` property for the BCM2837's
+/// interrupt controller.
+///
+/// The peripheral IRQs are described by two cells: The bank, which is 1 for IRQs 0..=31 and 2 for
+/// IRQs 32..=63, and the number within the bank. Bank 0 holds the ARM-specific IRQs, which are not
+/// supported.
+fn bcm_irq_number_from_dt(node: &dtb::Node) -> Option<IRQNumber> {
+    let interrupts = node.property("interrupts")?;
+    let first = match interrupts.cell(0)? {
+        1 => 0,
+        2 => 32,
+        _ => return None,
+    };
+
+    PeripheralIRQ::checked_new(first + interrupts.cell(1)? as usize)
+        .map(|x| IRQNumber::BCM(BCMIRQNumber::Peripheral(x)))
+}
+
+/// Translate the first interrupt of a device tree node's `interrupts` property for the GIC.
+///
+/// The GIC's interrupts are described by three cells: The type, which is 0 for SPIs and 1 for PPIs,
+/// the number relative to the first interrupt of the type, and flags.
+fn gic_irq_number_from_dt(node: &dtb::Node) -> Option<IRQNumber> {
+    let interrupts = node.property("interrupts")?;
+    let first = match interrupts.cell(0)? {
+        0 => 32,
+        1 => 16,
+        _ => return None,
+    };
 
-    pub const PL011_UART: IRQNumber = IRQNumber::new(153);
+    device_driver::IRQNumber::checked_new(first + interrupts.cell(1)? as usize).map(IRQNumber::GIC)
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -29,8 +109,158 @@ pub(in crate::bsp) mod irq_map {
 //--------------------------------------------------------------------------------------------------
 
 /// Return a reference to the IRQ manager.
-pub fn irq_manager() -> &'static impl exception::asynchronous::interface::IRQManager<
-    IRQNumberType = bsp::device_driver::IRQNumber,
-> {
-    &super::super::INTERRUPT_CONTROLLER
+pub fn irq_manager() -> &'static impl IRQManager<IRQNumberType = IRQNumber> {
+    &SOC_IRQ_MANAGER
+}
+
+/// Prepare the interrupt controller for use by the executing secondary core.
+///
+/// # Safety
+///
+/// - Must be called once on each secondary core, after the boot core initialized the interrupt
+///   controller.
+pub unsafe fn init_secondary_core() -> Result<(), &'static str> {
+    use crate::driver::interface::DeviceDriver;
+
+    // The BCM2837's local interrupt controller has no per-core state that needs initialization.
+    // The GIC's init sets up the banked registers and the CPU interface of the executing core.
+    if !has_gic() {
+        return Ok(());
+    }
+
+    GIC.init()
+}
+
+/// Return the IRQ number that carries inter-processor messages, and the message that raises it.
+///
+/// The BCM2837's local interrupt controller has no software-generated interrupts.
+pub fn ipi_irq() -> Option<(IRQNumber, IPIMessage)> {
+    has_gic().then(|| {
+        (
+            IRQNumber::GIC(irq_map::gic::IPI),
+            IPIMessage::new(irq_map::gic::IPI.get()),
+        )
+    })
+}
+
+/// Translate the first interrupt of a device tree node's `interrupts` property to an IRQ number.
+pub(in crate::bsp) fn irq_number_from_dt(node: &dtb::Node) -> Option<IRQNumber> {
+    if has_gic() {
+        gic_irq_number_from_dt(node)
+    } else {
+        bcm_irq_number_from_dt(node)
+    }
+}
+
+/// Return the IRQ number of the ARM generic timer's virtual timer, which drives the timer tick.
+pub fn tick_irq() -> IRQNumber {
+    if has_gic() {
+        irq_map::gic::VIRTUAL_TIMER
+    } else {
+        irq_map::bcm2837::VIRTUAL_TIMER
+    }
+}
+
+/// Return the IRQ number of the board's UART.
+pub(in crate::bsp) fn pl011_uart_irq() -> IRQNumber {
+    if has_gic() {
+        irq_map::gic::PL011_UART
+    } else {
+        irq_map::bcm2837::PL011_UART
+    }
+}
+
+//------------------------------------------------------------------------------
+// OS Interface Code
+//------------------------------------------------------------------------------
+
+impl IRQManager for SocIRQManager {
+    type IRQNumberType = IRQNumber;
+
+    fn register_handler(
+        &self,
+        irq_number: Self::IRQNumberType,
+        descriptor: IRQDescriptor,
+    ) -> Result<(), &'static str> {
+        match irq_number {
+            IRQNumber::BCM(x) => BCM_INTERRUPT_CONTROLLER.register_handler(x, descriptor),
+            IRQNumber::GIC(x) => GIC.register_handler(x, descriptor),
+        }
+    }
+
+    fn enable(&self, irq_number: Self::IRQNumberType) {
+        match irq_number {
+            IRQNumber::BCM(x) => BCM_INTERRUPT_CONTROLLER.enable(x),
+            IRQNumber::GIC(x) => GIC.enable(x),
+        }
+    }
+
+    fn set_priority(
+        &self,
+        irq_number: Self::IRQNumberType,
+        priority: u8,
+    ) -> Result<(), &'static str> {
+        match irq_number {
+            IRQNumber::BCM(x) => BCM_INTERRUPT_CONTROLLER.set_priority(x, priority),
+            IRQNumber::GIC(x) => GIC.set_priority(x, priority),
+        }
+    }
+
+    fn set_target_core(
+        &self,
+        irq_number: Self::IRQNumberType,
+        target_core: usize,
+    ) -> Result<(), &'static str> {
+        match irq_number {
+            IRQNumber::BCM(x) => BCM_INTERRUPT_CONTROLLER.set_target_core(x, target_core),
+            IRQNumber::GIC(x) => GIC.set_target_core(x, target_core),
+        }
+    }
+
+    fn route_to_fiq(&self, irq_number: Self::IRQNumberType) -> Result<(), &'static str> {
+        match irq_number {
+            IRQNumber::BCM(x) => BCM_INTERRUPT_CONTROLLER.route_to_fiq(x),
+            IRQNumber::GIC(x) => GIC.route_to_fiq(x),
+        }
+    }
+
+    fn send_ipi(&self, target_core: usize, msg: IPIMessage) -> Result<(), &'static str> {
+        if has_gic() {
+            GIC.send_ipi(target_core, msg)
+        } else {
+            BCM_INTERRUPT_CONTROLLER.send_ipi(target_core, msg)
+        }
+    }
+
+    fn check_pending_irq(&self) -> Option<Result<(), &'static str>> {
+        if has_gic() {
+            GIC.check_pending_irq()
+        } else {
+            BCM_INTERRUPT_CONTROLLER.check_pending_irq()
+        }
+    }
+
+    fn handle_pending_irqs<'irq_context>(&'irq_context self, ic: &IRQContext<'irq_context>) {
+        if has_gic() {
+            GIC.handle_pending_irqs(ic)
+        } else {
+            BCM_INTERRUPT_CONTROLLER.handle_pending_irqs(ic)
+        }
+    }
+
+    fn handle_pending_fiq<'irq_context>(&'irq_context self, ic: &IRQContext<'irq_context>) {
+        if has_gic() {
+            GIC.handle_pending_fiq(ic)
+        } else {
+            BCM_INTERRUPT_CONTROLLER.handle_pending_fiq(ic)
+        }
+    }
+
+    fn print_handler(&self) {
+        if has_gic() {
+            GIC.print_handler()
+        } else {
+            BCM_INTERRUPT_CONTROLLER.print_handler()
+        }
+    }
 }
@@ -0,0 +1,46 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! BSP GPIO facilities.
+
+use crate::gpio::{self, Level, Pull};
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// Fail on SoCs whose GPIO is not supported, i.e. the BCM2712, whose pins belong to the RP1.
+fn check_supported() -> Result<(), &'static str> {
+    super::gpio_model()
+        .map(|_| ())
+        .ok_or("GPIO not supported on this SoC")
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Claim a pin and configure it as output, driving the given level.
+///
+/// Fails if the pin is already in use, or if the SoC's GPIO is not supported.
+pub fn output_pin(
+    number: usize,
+    initial_level: Level,
+) -> Result<impl gpio::interface::OutputPin, &'static str> {
+    check_supported()?;
+
+    Ok(super::GPIO.claim_pin(number)?.into_output(initial_level))
+}
+
+/// Claim a pin and configure it as input with the given pull resistor.
+///
+/// Fails if the pin is already in use, or if the SoC's GPIO is not supported.
+pub fn input_pin(
+    number: usize,
+    pull: Pull,
+) -> Result<impl gpio::interface::InputPin, &'static str> {
+    check_supported()?;
+
+    Ok(super::GPIO.claim_pin(number)?.into_input(pull))
+}
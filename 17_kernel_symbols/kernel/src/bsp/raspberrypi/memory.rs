@@ -68,7 +68,11 @@
 //! |                                       |
 pub mod mmu;
 
-use crate::memory::{mmu::PageAddress, Address, Physical, Virtual};
+use super::Soc;
+use crate::memory::{
+    mmu::{MMIODescriptor, PageAddress},
+    Address, Physical, Virtual,
+};
 use core::cell::UnsafeCell;
 
 //--------------------------------------------------------------------------------------------------
@@ -94,52 +98,136 @@ extern "Rust" {
 // Public Definitions
 //--------------------------------------------------------------------------------------------------
 
-/// The board's physical memory map.
+/// MMIO ranges of a SoC's devices. `None` for devices that the SoC does not have, or that are not
+/// supported.
+#[derive(Copy, Clone)]
+pub(super) struct MMIOMap {
+    pub gpio: Option<MMIODescriptor>,
+    pub mailbox: Option<MMIODescriptor>,
+    pub pl011_uart: MMIODescriptor,
+    pub usb: Option<MMIODescriptor>,
+    pub genet: Option<MMIODescriptor>,
+    pub local_ic: Option<MMIODescriptor>,
+    pub peripheral_ic: Option<MMIODescriptor>,
+    pub gicd: Option<MMIODescriptor>,
+    pub gicc: Option<MMIODescriptor>,
+}
+
+/// The physical memory maps of the supported SoCs.
 #[rustfmt::skip]
 pub(super) mod map {
     use super::*;
 
     /// Physical devices.
-    #[cfg(feature = "bsp_rpi3")]
     pub mod mmio {
         use super::*;
 
-        pub const PERIPHERAL_IC_START: Address<Physical> = Address::new(0x3F00_B200);
-        pub const PERIPHERAL_IC_SIZE:  usize             =              0x24;
+        /// The BCM2837 of the Raspberry Pi 3 and Zero 2 W.
+        pub mod bcm2837 {
+            use super::*;
 
-        pub const GPIO_START:          Address<Physical> = Address::new(0x3F20_0000);
-        pub const GPIO_SIZE:           usize             =              0xA0;
+            pub const PERIPHERAL_IC_START: Address<Physical> = Address::new(0x3F00_B200);
+            pub const PERIPHERAL_IC_SIZE:  usize             =              0x24;
 
-        pub const PL011_UART_START:    Address<Physical> = Address::new(0x3F20_1000);
-        pub const PL011_UART_SIZE:     usize             =              0x48;
+            pub const MAILBOX_START:       Address<Physical> = Address::new(0x3F00_B880);
+            pub const MAILBOX_SIZE:        usize             =              0x3C;
 
-        pub const LOCAL_IC_START:      Address<Physical> = Address::new(0x4000_0000);
-        pub const LOCAL_IC_SIZE:       usize             =              0x100;
+            pub const GPIO_START:          Address<Physical> = Address::new(0x3F20_0000);
+            pub const GPIO_SIZE:           usize             =              0xF4;
 
-        pub const END:                 Address<Physical> = Address::new(0x4001_0000);
-    }
+            pub const PL011_UART_START:    Address<Physical> = Address::new(0x3F20_1000);
+            pub const PL011_UART_SIZE:     usize             =              0x48;
 
-    /// Physical devices.
-    #[cfg(feature = "bsp_rpi4")]
-    pub mod mmio {
-        use super::*;
+            pub const USB_START:           Address<Physical> = Address::new(0x3F98_0000);
+            pub const USB_SIZE:            usize             =              0x1004;
+
+            pub const LOCAL_IC_START:      Address<Physical> = Address::new(0x4000_0000);
+            pub const LOCAL_IC_SIZE:       usize             =              0x100;
+
+            pub const END:                 Address<Physical> = Address::new(0x4001_0000);
+        }
+
+        /// The BCM2711 of the Raspberry Pi 4.
+        #[cfg(not(feature = "rpi4_peri_high"))]
+        pub mod bcm2711 {
+            use super::*;
+
+            pub const GENET_START:      Address<Physical> = Address::new(0xFD58_0000);
+            pub const GENET_SIZE:       usize             =              0x10000;
+
+            pub const MAILBOX_START:    Address<Physical> = Address::new(0xFE00_B880);
+            pub const MAILBOX_SIZE:     usize             =              0x3C;
+
+            pub const GPIO_START:       Address<Physical> = Address::new(0xFE20_0000);
+            pub const GPIO_SIZE:        usize             =              0xF4;
+
+            pub const PL011_UART_START: Address<Physical> = Address::new(0xFE20_1000);
+            pub const PL011_UART_SIZE:  usize             =              0x48;
+
+            pub const USB_START:        Address<Physical> = Address::new(0xFE98_0000);
+            pub const USB_SIZE:         usize             =              0x1004;
+
+            pub const GICD_START:       Address<Physical> = Address::new(0xFF84_1000);
+            pub const GICD_SIZE:        usize             =              0xF04;
+
+            pub const GICC_START:       Address<Physical> = Address::new(0xFF84_2000);
+            pub const GICC_SIZE:        usize             =              0x14;
+
+            pub const END:              Address<Physical> = Address::new(0xFF85_0000);
+        }
+
+        /// The BCM2711 of the Raspberry Pi 4, if the firmware runs with `arm_peri_high=1`.
+        ///
+        /// The low peripheral window at `0xFC00_0000` moves to `0x4_7C00_0000`, and the ARM local
+        /// peripherals, i.e. the GIC, from `0xFF80_0000` to `0x4_C000_0000`.
+        #[cfg(feature = "rpi4_peri_high")]
+        pub mod bcm2711 {
+            use super::*;
+
+            pub const GENET_START:      Address<Physical> = Address::new(0x4_7D58_0000);
+            pub const GENET_SIZE:       usize             =                0x10000;
+
+            pub const MAILBOX_START:    Address<Physical> = Address::new(0x4_7E00_B880);
+            pub const MAILBOX_SIZE:     usize             =                0x3C;
+
+            pub const GPIO_START:       Address<Physical> = Address::new(0x4_7E20_0000);
+            pub const GPIO_SIZE:        usize             =                0xF4;
+
+            pub const PL011_UART_START: Address<Physical> = Address::new(0x4_7E20_1000);
+            pub const PL011_UART_SIZE:  usize             =                0x48;
 
-        pub const GPIO_START:       Address<Physical> = Address::new(0xFE20_0000);
-        pub const GPIO_SIZE:        usize             =              0xA0;
+            pub const USB_START:        Address<Physical> = Address::new(0x4_7E98_0000);
+            pub const USB_SIZE:         usize             =                0x1004;
 
-        pub const PL011_UART_START: Address<Physical> = Address::new(0xFE20_1000);
-        pub const PL011_UART_SIZE:  usize             =              0x48;
+            pub const GICD_START:       Address<Physical> = Address::new(0x4_C004_1000);
+            pub const GICD_SIZE:        usize             =                0xF04;
 
-        pub const GICD_START:       Address<Physical> = Address::new(0xFF84_1000);
-        pub const GICD_SIZE:        usize             =              0x824;
+            pub const GICC_START:       Address<Physical> = Address::new(0x4_C004_2000);
+            pub const GICC_SIZE:        usize             =                0x14;
 
-        pub const GICC_START:       Address<Physical> = Address::new(0xFF84_2000);
-        pub const GICC_SIZE:        usize             =              0x14;
+            pub const END:              Address<Physical> = Address::new(0x4_C005_0000);
+        }
 
-        pub const END:              Address<Physical> = Address::new(0xFF85_0000);
+        /// The BCM2712 of the Raspberry Pi 5.
+        pub mod bcm2712 {
+            use super::*;
+
+            pub const PL011_UART_START: Address<Physical> = Address::new(0x10_7D00_1000);
+            pub const PL011_UART_SIZE:  usize             =                 0x48;
+
+            pub const GICD_START:       Address<Physical> = Address::new(0x10_7FFF_9000);
+            pub const GICD_SIZE:        usize             =                 0xF04;
+
+            pub const GICC_START:       Address<Physical> = Address::new(0x10_7FFF_A000);
+            pub const GICC_SIZE:        usize             =                 0x14;
+
+            pub const END:              Address<Physical> = Address::new(0x10_8000_0000);
+        }
     }
 
-    pub const END: Address<Physical> = mmio::END;
+    /// Size of the DRAM of the boards that are only sold with one size.
+    pub const RPI3_DRAM_SIZE:      usize = 1024 * 1024 * 1024;
+    pub const RPIZERO2W_DRAM_SIZE: usize = 512 * 1024 * 1024;
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -220,8 +308,88 @@ fn boot_core_stack_size() -> usize {
 // Public Code
 //--------------------------------------------------------------------------------------------------
 
+/// Virtual address of a secondary core's release address in the firmware's spin-table.
+///
+/// The firmware parks the secondary cores in a loop that polls the release addresses, which lie in
+/// the first page of DRAM. The boot core's stack is mapped onto the start of DRAM, and grows down
+/// from its end, so this page is reachable through the stack's mapping without ever being used by
+/// the stack.
+pub fn virt_spin_table_release_addr(core: usize) -> Address<Virtual> {
+    const SPIN_TABLE_START: usize = 0xd8;
+
+    let stack_start = virt_boot_core_stack_start().into_inner().as_usize();
+
+    Address::new(stack_start + SPIN_TABLE_START + core * core::mem::size_of::<u64>())
+}
+
+/// Return whether a virtual address points into the kernel's code segment.
+pub fn is_kernel_code(addr: Address<Virtual>) -> bool {
+    let start = virt_code_start().into_inner().as_usize();
+
+    (start..start + code_size()).contains(&addr.as_usize())
+}
+
+/// Size of the board's DRAM, including the part that the firmware reserves for the GPU.
+///
+/// `None` if it differs between models of the board. The device tree then is the only source.
+pub fn board_dram_size() -> Option<usize> {
+    match super::soc() {
+        Soc::BCM2837 if super::is_rpizero2w() => Some(map::RPIZERO2W_DRAM_SIZE),
+        Soc::BCM2837 => Some(map::RPI3_DRAM_SIZE),
+        Soc::BCM2711 | Soc::BCM2712 => None,
+    }
+}
+
+/// MMIO ranges of the devices of the SoC the kernel is running on.
+pub(super) fn mmio_map() -> MMIOMap {
+    use map::mmio::{bcm2711, bcm2712, bcm2837};
+
+    let d = MMIODescriptor::new;
+    match super::soc() {
+        Soc::BCM2837 => MMIOMap {
+            gpio: Some(d(bcm2837::GPIO_START, bcm2837::GPIO_SIZE)),
+            mailbox: Some(d(bcm2837::MAILBOX_START, bcm2837::MAILBOX_SIZE)),
+            pl011_uart: d(bcm2837::PL011_UART_START, bcm2837::PL011_UART_SIZE),
+            usb: Some(d(bcm2837::USB_START, bcm2837::USB_SIZE)),
+            genet: None,
+            local_ic: Some(d(bcm2837::LOCAL_IC_START, bcm2837::LOCAL_IC_SIZE)),
+            peripheral_ic: Some(d(bcm2837::PERIPHERAL_IC_START, bcm2837::PERIPHERAL_IC_SIZE)),
+            gicd: None,
+            gicc: None,
+        },
+        Soc::BCM2711 => MMIOMap {
+            gpio: Some(d(bcm2711::GPIO_START, bcm2711::GPIO_SIZE)),
+            mailbox: Some(d(bcm2711::MAILBOX_START, bcm2711::MAILBOX_SIZE)),
+            pl011_uart: d(bcm2711::PL011_UART_START, bcm2711::PL011_UART_SIZE),
+            usb: Some(d(bcm2711::USB_START, bcm2711::USB_SIZE)),
+            genet: Some(d(bcm2711::GENET_START, bcm2711::GENET_SIZE)),
+            local_ic: None,
+            peripheral_ic: None,
+            gicd: Some(d(bcm2711::GICD_START, bcm2711::GICD_SIZE)),
+            gicc: Some(d(bcm2711::GICC_START, bcm2711::GICC_SIZE)),
+        },
+        // The UARTs, GPIOs, USB and Ethernet controllers of the RP1 are not supported.
+        Soc::BCM2712 => MMIOMap {
+            gpio: None,
+            mailbox: None,
+            pl011_uart: d(bcm2712::PL011_UART_START, bcm2712::PL011_UART_SIZE),
+            usb: None,
+            genet: None,
+            local_ic: None,
+            peripheral_ic: None,
+            gicd: Some(d(bcm2712::GICD_START, bcm2712::GICD_SIZE)),
+            gicc: Some(d(bcm2712::GICC_START, bcm2712::GICC_SIZE)),
+        },
+    }
+}
+
 /// Exclusive end address of the physical address space.
-#[inline(always)]
 pub fn phys_addr_space_end_exclusive_addr() -> PageAddress<Physical> {
-    PageAddress::from(map::END)
+    let end = match super::soc() {
+        Soc::BCM2837 => map::mmio::bcm2837::END,
+        Soc::BCM2711 => map::mmio::bcm2711::END,
+        Soc::BCM2712 => map::mmio::bcm2712::END,
+    };
+
+    PageAddress::from(end)
 }
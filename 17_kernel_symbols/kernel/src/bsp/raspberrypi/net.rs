@@ -0,0 +1,24 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! BSP network facilities.
+
+use super::Soc;
+use crate::net;
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Return a reference to the on-board Ethernet device, if the board has one that is supported.
+pub fn network_device() -> Option<&'static (dyn net::interface::NetworkDevice + Sync)> {
+    match super::soc() {
+        // The Raspberry Pi 3's Ethernet is a USB device behind the on-board hub, and the Raspberry
+        // Pi Zero 2 W only has WiFi.
+        Soc::BCM2837 => None,
+        Soc::BCM2711 => Some(&super::ETHERNET),
+        // The Ethernet MAC is part of the RP1.
+        Soc::BCM2712 => None,
+    }
+}
@@ -0,0 +1,21 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! BSP USB facilities.
+
+use super::Soc;
+use crate::usb;
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Return a reference to the USB host controller, if the board has one that is supported.
+pub fn host_controller() -> Option<&'static (dyn usb::interface::HostController + Sync)> {
+    match super::soc() {
+        Soc::BCM2837 | Soc::BCM2711 => Some(&super::USB_HOST),
+        // The USB controllers are part of the RP1.
+        Soc::BCM2712 => None,
+    }
+}
@@ -4,6 +4,17 @@
 
 //! General purpose code.
 
+mod hexdump;
+
+//--------------------------------------------------------------------------------------------------
+// Public Reexports
+//--------------------------------------------------------------------------------------------------
+pub use hexdump::{hexdump, Hexdump};
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
 /// Check if a value is aligned to a given size.
 #[inline(always)]
 pub const fn is_aligned(value: usize, alignment: usize) -> bool {
@@ -0,0 +1,283 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Hexdumps of kernel memory.
+//!
+//! Lines show the address, 16 bytes in hex, and the bytes as ASCII. Only lines that are mapped as
+//! normal memory are read by default. Device memory is read on request only, with 32-bit accesses,
+//! because reads of device registers can have side effects.
+
+use super::{align_down, align_up};
+use crate::{memory, print};
+use core::fmt;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const BYTES_PER_LINE: usize = 16;
+
+const ANSI_BOLD: &str = "\x1b[1m";
+const ANSI_RESET: &str = "\x1b[0m";
+
+/// How the bytes of a line are read.
+enum Access {
+    Normal,
+    Device,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A hexdump of a range of kernel virtual memory, which is read when it is displayed.
+///
+/// Lines are aligned to 16 bytes. Bytes outside of the range are left blank. When bringing up a
+/// driver, snapshot its registers with [`Hexdump::read`] before poking the device, and print the
+/// registers again with [`Hexdump::highlight_changes`] to see which ones changed.
+pub struct Hexdump<'a> {
+    start: usize,
+    len: usize,
+    marked: Option<usize>,
+    previous: Option<&'a [u8]>,
+    device_memory: bool,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn access(line: usize, device_memory: bool) -> Result<Access, &'static str> {
+    let page = memory::Address::<memory::Virtual>::new(line).align_down_page();
+    let attributes =
+        memory::mmu::try_kernel_page_attributes(page.into()).map_err(|_| "Not mapped")?;
+
+    match attributes.mem_attributes {
+        memory::mmu::MemAttributes::CacheableDRAM => Ok(Access::Normal),
+        memory::mmu::MemAttributes::Device if device_memory => Ok(Access::Device),
+        memory::mmu::MemAttributes::Device => Err("Device memory"),
+    }
+}
+
+fn ascii(byte: u8) -> char {
+    if byte.is_ascii_graphic() || byte == b' ' {
+        byte as char
+    } else {
+        '.'
+    }
+}
+
+impl<'a> Hexdump<'a> {
+    fn end(&self) -> usize {
+        self.start.saturating_add(self.len)
+    }
+
+    fn lines(&self) -> impl Iterator<Item = usize> {
+        (align_down(self.start, BYTES_PER_LINE)..self.end()).step_by(BYTES_PER_LINE)
+    }
+
+    fn contains(&self, addr: usize) -> bool {
+        (self.start..self.end()).contains(&addr)
+    }
+
+    /// Read the bytes of the line at `line` that are in the range.
+    ///
+    /// Lines are aligned to their size, so they never cross a page boundary.
+    fn read_line(&self, line: usize) -> Result<[Option<u8>; BYTES_PER_LINE], &'static str> {
+        let mut bytes = [None; BYTES_PER_LINE];
+
+        match access(line, self.device_memory)? {
+            Access::Normal => {
+                for (i, byte) in bytes.iter_mut().enumerate() {
+                    if self.contains(line + i) {
+                        *byte = Some(unsafe { core::ptr::read_volatile((line + i) as *const u8) });
+                    }
+                }
+            }
+            Access::Device => {
+                let start = align_down(self.start, 4);
+                let end = align_up(self.end(), 4);
+
+                for (i, word) in bytes.chunks_exact_mut(4).enumerate() {
+                    let addr = line + i * 4;
+                    if !(start..end).contains(&addr) {
+                        continue;
+                    }
+
+                    let value = unsafe { core::ptr::read_volatile(addr as *const u32) };
+                    for (byte, value) in word.iter_mut().zip(value.to_le_bytes()) {
+                        *byte = Some(value);
+                    }
+                }
+            }
+        }
+
+        Ok(bytes)
+    }
+
+    fn is_changed(&self, line: usize, bytes: &[Option<u8>]) -> bool {
+        let previous = match self.previous {
+            None => return false,
+            Some(x) => x,
+        };
+
+        bytes.iter().enumerate().any(|(i, byte)| match byte {
+            Some(byte) if self.contains(line + i) => {
+                previous.get(line + i - self.start) != Some(byte)
+            }
+            _ => false,
+        })
+    }
+
+    fn write_line(&self, f: &mut fmt::Formatter, line: usize, bytes: &[Option<u8>]) -> fmt::Result {
+        let changed = self.is_changed(line, bytes);
+        let highlight = changed && print::colors_enabled();
+
+        if highlight {
+            write!(f, "{}", ANSI_BOLD)?;
+        }
+
+        for byte in bytes {
+            match byte {
+                None => write!(f, "   ")?,
+                Some(x) => write!(f, " {:02x}", x)?,
+            }
+        }
+
+        write!(f, "  |")?;
+        for byte in bytes {
+            write!(f, "{}", byte.map_or(' ', ascii))?;
+        }
+        write!(f, "|")?;
+
+        if highlight {
+            write!(f, "{}", ANSI_RESET)?;
+        }
+
+        if changed {
+            write!(f, " *")?;
+        }
+
+        Ok(())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl<'a> Hexdump<'a> {
+    /// Mark the line that contains `addr` with an arrow.
+    pub fn mark(mut self, addr: usize) -> Self {
+        self.marked = Some(addr);
+        self
+    }
+
+    /// Highlight the lines that differ from `previous`, a snapshot of the same range taken with
+    /// [`Hexdump::read`].
+    pub fn highlight_changes(mut self, previous: &'a [u8]) -> Self {
+        self.previous = Some(previous);
+        self
+    }
+
+    /// Also read lines that are mapped as device memory.
+    pub fn allow_device_memory(mut self) -> Self {
+        self.device_memory = true;
+        self
+    }
+
+    /// Copy the range into `buf`, e.g. to highlight changes in a later dump.
+    pub fn read(&self, buf: &mut [u8]) -> Result<(), &'static str> {
+        if buf.len() != self.len {
+            return Err("Buffer size does not match the range");
+        }
+
+        for line in self.lines() {
+            for (i, byte) in self.read_line(line)?.iter().enumerate() {
+                if let Some(x) = byte {
+                    if self.contains(line + i) {
+                        buf[line + i - self.start] = *x;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Hexdump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, line) in self.lines().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "      {:#018x}:", line)?;
+
+            match self.read_line(line) {
+                Err(x) => write!(f, " {}", x)?,
+                Ok(bytes) => self.write_line(f, line, &bytes)?,
+            }
+
+            if matches!(self.marked, Some(addr) if align_down(addr, BYTES_PER_LINE) == line) {
+                write!(f, " <--")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Return a hexdump of `len` bytes starting at `addr`, for printing with `{}`.
+pub fn hexdump<'a>(addr: usize, len: usize) -> Hexdump<'a> {
+    Hexdump {
+        start: addr,
+        len,
+        marked: None,
+        previous: None,
+        device_memory: false,
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
+
+    /// Only the bytes of the range are read, and changes are detected against a snapshot.
+    #[kernel_test]
+    fn ranges_are_read_and_compared() {
+        let mut data = [0_u8; 40];
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+
+        let start = data.as_ptr() as usize + 3;
+        let dump = hexdump(start, 20);
+
+        let mut snapshot = [0_u8; 20];
+        assert_eq!(dump.read(&mut snapshot), Ok(()));
+        assert_eq!(snapshot[..], data[3..23]);
+        assert!(dump.read(&mut [0_u8; 4]).is_err());
+
+        let line = align_down(start, BYTES_PER_LINE);
+        let bytes = dump.read_line(line).unwrap();
+        assert!(bytes
+            .iter()
+            .enumerate()
+            .all(|(i, byte)| byte.is_some() == dump.contains(line + i)));
+
+        let unchanged = hexdump(start, 20).highlight_changes(&snapshot);
+        assert!(!unchanged.is_changed(line, &bytes));
+
+        let mut modified = snapshot;
+        modified[0] ^= 0xff;
+        let changed = hexdump(start, 20).highlight_changes(&modified);
+        assert!(changed.is_changed(line, &bytes));
+    }
+}
@@ -3,6 +3,47 @@
 // Copyright (c) 2018-2022 Andre Richter <andre.o.richter@gmail.com>
 
 //! System console.
+//!
+//! The console fans out writes to all registered and enabled sinks, e.g. a UART and a framebuffer.
+//! Reads and statistics are served by the first enabled sink. [`readline()`] adds line editing on
+//! top.
+//!
+//! In test builds, [`test_console`] can capture the output instead.
+
+mod readline;
+
+#[cfg(feature = "test_build")]
+pub mod test_console;
+
+use crate::synchronization::{interface::ReadWriteEx, InitStateLock};
+use core::{
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Public Reexports
+//--------------------------------------------------------------------------------------------------
+pub use readline::readline;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The maximum number of console sinks.
+const MAX_SINKS: usize = 4;
+
+/// A registered console sink.
+#[derive(Copy, Clone)]
+struct Sink {
+    name: &'static str,
+    writer: &'static (dyn interface::Write + Sync),
+    reader: &'static (dyn interface::Read + Sync),
+    statistics: &'static (dyn interface::Statistics + Sync),
+}
+
+/// The console that all printing goes through.
+struct BroadcastConsole;
 
 //--------------------------------------------------------------------------------------------------
 // Public Definitions
@@ -20,6 +61,13 @@ pub mod interface {
         /// Write a Rust format string.
         fn write_fmt(&self, args: fmt::Arguments) -> fmt::Result;
 
+        /// Write bytes as they are, e.g. binary data.
+        fn write_bytes(&self, bytes: &[u8]) {
+            for &b in bytes {
+                self.write_char(b as char);
+            }
+        }
+
         /// Block until the last buffered character has been physically put on the TX wire.
         fn flush(&self);
     }
@@ -31,6 +79,28 @@ pub mod interface {
             ' '
         }
 
+        /// Block until `buf` is filled, and store the bytes as they were received.
+        ///
+        /// Unlike [`Read::read_char()`], implementations should not convert line breaks. The
+        /// default implementation falls back to `read_char()`, though.
+        fn read_exact(&self, buf: &mut [u8]) {
+            for byte in buf.iter_mut() {
+                *byte = self.read_char() as u8;
+            }
+        }
+
+        /// Read a single character if one is available, without blocking.
+        fn read_char_nonblock(&self) -> Option<char> {
+            None
+        }
+
+        /// Return the number of characters that can be read without blocking.
+        ///
+        /// Devices that can not tell the exact number report a lower bound.
+        fn bytes_available(&self) -> usize {
+            0
+        }
+
         /// Clear RX buffers, if any.
         fn clear_rx(&self);
     }
@@ -51,3 +121,185 @@ pub mod interface {
     /// Trait alias for a full-fledged console.
     pub trait All = Write + Read + Statistics;
 }
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static SINKS: InitStateLock<[Option<Sink>; MAX_SINKS]> = InitStateLock::new([None; MAX_SINKS]);
+
+#[allow(clippy::declare_interior_mutable_const)]
+const DISABLED: AtomicBool = AtomicBool::new(false);
+
+static SINK_ENABLED: [AtomicBool; MAX_SINKS] = [DISABLED; MAX_SINKS];
+
+static BROADCAST_CONSOLE: BroadcastConsole = BroadcastConsole;
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// Call `f` for each enabled sink.
+fn for_each_enabled_sink(mut f: impl FnMut(&Sink)) {
+    SINKS.read(|sinks| {
+        for (i, sink) in sinks.iter().enumerate() {
+            if let Some(sink) = sink {
+                if SINK_ENABLED[i].load(Ordering::Relaxed) {
+                    f(sink);
+                }
+            }
+        }
+    })
+}
+
+/// Return the first enabled sink.
+fn primary_sink() -> Option<Sink> {
+    let mut primary = None;
+    for_each_enabled_sink(|sink| {
+        if primary.is_none() {
+            primary = Some(*sink);
+        }
+    });
+
+    primary
+}
+
+/// Return the console that captures the output instead of the sinks, if any.
+fn capturing_console() -> Option<&'static (dyn interface::Write + Sync)> {
+    #[cfg(feature = "test_build")]
+    if let Some(x) = test_console::active() {
+        return Some(x);
+    }
+
+    None
+}
+
+impl interface::Write for BroadcastConsole {
+    fn write_char(&self, c: char) {
+        if let Some(x) = capturing_console() {
+            return x.write_char(c);
+        }
+
+        for_each_enabled_sink(|sink| sink.writer.write_char(c));
+    }
+
+    fn write_fmt(&self, args: fmt::Arguments) -> fmt::Result {
+        if let Some(x) = capturing_console() {
+            return x.write_fmt(args);
+        }
+
+        let mut result = Ok(());
+        for_each_enabled_sink(|sink| {
+            if let Err(x) = sink.writer.write_fmt(args) {
+                result = Err(x);
+            }
+        });
+
+        result
+    }
+
+    fn write_bytes(&self, bytes: &[u8]) {
+        if let Some(x) = capturing_console() {
+            return x.write_bytes(bytes);
+        }
+
+        for_each_enabled_sink(|sink| sink.writer.write_bytes(bytes));
+    }
+
+    fn flush(&self) {
+        for_each_enabled_sink(|sink| sink.writer.flush());
+    }
+}
+
+impl interface::Read for BroadcastConsole {
+    fn read_char(&self) -> char {
+        match primary_sink() {
+            None => ' ',
+            Some(sink) => sink.reader.read_char(),
+        }
+    }
+
+    fn read_exact(&self, buf: &mut [u8]) {
+        if let Some(sink) = primary_sink() {
+            sink.reader.read_exact(buf);
+        }
+    }
+
+    fn read_char_nonblock(&self) -> Option<char> {
+        primary_sink().and_then(|sink| sink.reader.read_char_nonblock())
+    }
+
+    fn bytes_available(&self) -> usize {
+        primary_sink().map_or(0, |sink| sink.reader.bytes_available())
+    }
+
+    fn clear_rx(&self) {
+        if let Some(sink) = primary_sink() {
+            sink.reader.clear_rx();
+        }
+    }
+}
+
+impl interface::Statistics for BroadcastConsole {
+    fn chars_written(&self) -> usize {
+        primary_sink().map_or(0, |sink| sink.statistics.chars_written())
+    }
+
+    fn chars_read(&self) -> usize {
+        primary_sink().map_or(0, |sink| sink.statistics.chars_read())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Register a console sink and enable it.
+///
+/// # Safety
+///
+/// - Must only be called during kernel init.
+pub unsafe fn register_sink(
+    name: &'static str,
+    sink: &'static (impl interface::All + Sync),
+) -> Result<(), &'static str> {
+    let index = SINKS.write(|sinks| {
+        let index = sinks
+            .iter()
+            .position(|s| s.is_none())
+            .ok_or("No free console sink slot")?;
+
+        sinks[index] = Some(Sink {
+            name,
+            writer: sink,
+            reader: sink,
+            statistics: sink,
+        });
+
+        Ok(index)
+    })?;
+
+    SINK_ENABLED[index].store(true, Ordering::Relaxed);
+
+    Ok(())
+}
+
+/// Enable or disable the sink with the given name.
+pub fn set_sink_enabled(name: &str, enabled: bool) -> Result<(), &'static str> {
+    let index = SINKS
+        .read(|sinks| {
+            sinks
+                .iter()
+                .position(|s| s.map_or(false, |sink| sink.name == name))
+        })
+        .ok_or("No console sink with this name")?;
+
+    SINK_ENABLED[index].store(enabled, Ordering::Relaxed);
+
+    Ok(())
+}
+
+/// Return a reference to the console.
+pub fn console() -> &'static impl interface::All {
+    &BROADCAST_CONSOLE
+}
@@ -0,0 +1,294 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Line editing.
+//!
+//! [`readline()`] reads a line from the console and supports the following keys:
+//!
+//! - Backspace deletes the character before the cursor.
+//! - Left and right move the cursor.
+//! - Up and down browse the history of previously entered lines.
+//!
+//! Terminals send the arrow keys as ANSI escape sequences, and the cursor is moved with them as
+//! well.
+
+use super::interface::Read;
+use crate::{
+    print, println,
+    synchronization::{interface::Mutex, SpinLock},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The maximum length of a line, in bytes.
+const MAX_LINE_LEN: usize = 128;
+
+/// The number of lines kept in the history.
+const HISTORY_LEN: usize = 8;
+
+const BACKSPACE: char = '\x08';
+const DELETE: char = '\x7f';
+const ESCAPE: char = '\x1b';
+
+#[derive(Copy, Clone)]
+struct Line {
+    buf: [u8; MAX_LINE_LEN],
+    len: usize,
+}
+
+/// The most recently entered lines.
+struct History {
+    lines: [Line; HISTORY_LEN],
+
+    /// The total number of lines ever added.
+    num_added: usize,
+}
+
+/// A line that is being edited.
+struct Editor<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+    cursor: usize,
+
+    /// How many lines back in the history the user browsed. Zero is the line being entered.
+    history_index: usize,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static HISTORY: SpinLock<History> = SpinLock::new(History::new());
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl Line {
+    const fn new() -> Self {
+        Self {
+            buf: [0; MAX_LINE_LEN],
+            len: 0,
+        }
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let mut line = Self::new();
+        line.len = bytes.len().min(MAX_LINE_LEN);
+        line.buf[..line.len].copy_from_slice(&bytes[..line.len]);
+
+        line
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+impl History {
+    const fn new() -> Self {
+        Self {
+            lines: [Line::new(); HISTORY_LEN],
+            num_added: 0,
+        }
+    }
+
+    /// Add a line, unless it is empty or repeats the most recent one.
+    fn add(&mut self, line: &[u8]) {
+        if line.is_empty() || self.get(1) == Some(line) {
+            return;
+        }
+
+        self.lines[self.num_added % HISTORY_LEN] = Line::from_bytes(line);
+        self.num_added += 1;
+    }
+
+    /// Return the line `back` entries ago, starting with 1 for the most recent one.
+    fn get(&self, back: usize) -> Option<&[u8]> {
+        if back == 0 || back > self.num_added.min(HISTORY_LEN) {
+            return None;
+        }
+
+        Some(self.lines[(self.num_added - back) % HISTORY_LEN].as_bytes())
+    }
+}
+
+impl<'a> Editor<'a> {
+    fn new(buf: &'a mut [u8]) -> Self {
+        Self {
+            buf,
+            len: 0,
+            cursor: 0,
+            history_index: 0,
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.buf.len().min(MAX_LINE_LEN)
+    }
+
+    /// Print the line from the cursor on, and move the cursor back to where it was.
+    fn redraw_tail(&self, erase_after: bool) {
+        for &b in &self.buf[self.cursor..self.len] {
+            print!("{}", b as char);
+        }
+
+        let mut back = self.len - self.cursor;
+        if erase_after {
+            print!(" ");
+            back += 1;
+        }
+
+        if back > 0 {
+            print!("\x1b[{}D", back);
+        }
+    }
+
+    fn insert(&mut self, c: char) {
+        if self.len == self.capacity() {
+            return;
+        }
+
+        self.buf.copy_within(self.cursor..self.len, self.cursor + 1);
+        self.buf[self.cursor] = c as u8;
+        self.len += 1;
+
+        print!("{}", c);
+        self.cursor += 1;
+        self.redraw_tail(false);
+    }
+
+    fn backspace(&mut self) {
+        if self.cursor == 0 {
+            return;
+        }
+
+        self.buf.copy_within(self.cursor..self.len, self.cursor - 1);
+        self.len -= 1;
+        self.cursor -= 1;
+
+        print!("{}", BACKSPACE);
+        self.redraw_tail(true);
+    }
+
+    fn move_left(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            print!("\x1b[D");
+        }
+    }
+
+    fn move_right(&mut self) {
+        if self.cursor < self.len {
+            self.cursor += 1;
+            print!("\x1b[C");
+        }
+    }
+
+    /// Replace the line with the one `back` entries ago in the history, or with an empty line.
+    fn recall(&mut self, back: usize) {
+        let line = if back == 0 {
+            Line::new()
+        } else {
+            match HISTORY.lock(|history| history.get(back).map(Line::from_bytes)) {
+                None => return,
+                Some(line) => line,
+            }
+        };
+        self.history_index = back;
+
+        if self.cursor > 0 {
+            print!("\x1b[{}D", self.cursor);
+        }
+
+        self.len = line.len.min(self.capacity());
+        self.buf[..self.len].copy_from_slice(&line.buf[..self.len]);
+        self.cursor = self.len;
+
+        for &b in &self.buf[..self.len] {
+            print!("{}", b as char);
+        }
+        print!("\x1b[K");
+    }
+
+    fn handle_escape_sequence(&mut self, console: &impl Read) {
+        if console.read_char() != '[' {
+            return;
+        }
+
+        match console.read_char() {
+            'A' => self.recall(self.history_index + 1),
+            'B' if self.history_index > 0 => self.recall(self.history_index - 1),
+            'C' => self.move_right(),
+            'D' => self.move_left(),
+            _ => (),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Print `prompt`, then read and edit a line until Enter is pressed.
+///
+/// The line is stored in `buf`, without the line break. It is at most 128 bytes long, and only
+/// contains printable ASCII characters.
+pub fn readline<'a>(prompt: &str, buf: &'a mut [u8]) -> &'a str {
+    let console = super::console();
+    let mut editor = Editor::new(buf);
+
+    print!("{}", prompt);
+
+    loop {
+        match console.read_char() {
+            '\n' | '\r' => break,
+            BACKSPACE | DELETE => editor.backspace(),
+            ESCAPE => editor.handle_escape_sequence(console),
+            c if (' '..='~').contains(&c) => editor.insert(c),
+            _ => (),
+        }
+    }
+    println!();
+
+    let len = editor.len;
+    let buf: &'a [u8] = editor.buf;
+    HISTORY.lock(|history| history.add(&buf[..len]));
+
+    // Only printable ASCII was inserted.
+    core::str::from_utf8(&buf[..len]).unwrap_or("")
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
+
+    /// The history must return the most recent lines first, and skip empty and repeated lines.
+    #[kernel_test]
+    fn history_keeps_recent_lines() {
+        let mut history = History::new();
+
+        history.add(b"first");
+        history.add(b"");
+        history.add(b"second");
+        history.add(b"second");
+
+        assert_eq!(history.get(1), Some(&b"second"[..]));
+        assert_eq!(history.get(2), Some(&b"first"[..]));
+        assert_eq!(history.get(3), None);
+
+        for _ in 0..HISTORY_LEN {
+            history.add(b"x");
+            history.add(b"y");
+        }
+        assert_eq!(history.get(HISTORY_LEN + 1), None);
+    }
+}
@@ -0,0 +1,220 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! A console that records output, for tests.
+//!
+//! While a capture runs, everything that is printed through the console, e.g. with `info!`, goes
+//! into a buffer instead of the registered sinks. Output of `panic_println!` goes into the buffer
+//! as well, but is still printed on the panic console, so that a failing test stays visible.
+//!
+//! ```ignore
+//! let output = test_console::capture(|| info!("Hello"));
+//! assert!(output.as_str().contains("Hello"));
+//! ```
+//!
+//! Integration tests that expect a panic can call [`start()`] instead, and check the output with
+//! [`stop()`] in their `_panic_exit()`.
+//!
+//! The buffer is not protected by a lock, so that it can be written from the panic handler.
+//! Concurrent writers reserve their bytes atomically, so output is never lost, but characters of
+//! different cores can interleave. Output that does not fit is dropped.
+
+use super::interface;
+use core::{
+    fmt,
+    sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The size of the capture buffer, in bytes.
+const BUFFER_SIZE: usize = 4096;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The console that records output.
+pub struct TestConsole {
+    buf: [AtomicU8; BUFFER_SIZE],
+    len: AtomicUsize,
+    capturing: AtomicBool,
+}
+
+/// The output of a capture.
+pub struct Captured {
+    buf: [u8; BUFFER_SIZE],
+    len: usize,
+    truncated: bool,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY: AtomicU8 = AtomicU8::new(0);
+
+static TEST_CONSOLE: TestConsole = TestConsole {
+    buf: [EMPTY; BUFFER_SIZE],
+    len: AtomicUsize::new(0),
+    capturing: AtomicBool::new(false),
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl TestConsole {
+    fn reset(&self) {
+        self.len.store(0, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> Captured {
+        let reserved = self.len.load(Ordering::Relaxed);
+        let len = reserved.min(BUFFER_SIZE);
+
+        let mut buf = [0; BUFFER_SIZE];
+        for (dst, src) in buf[..len].iter_mut().zip(self.buf.iter()) {
+            *dst = src.load(Ordering::Relaxed);
+        }
+
+        Captured {
+            buf,
+            len,
+            truncated: reserved > BUFFER_SIZE,
+        }
+    }
+}
+
+/// Adapter for `core::fmt`, which wants a mutable writer.
+impl fmt::Write for &TestConsole {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        interface::Write::write_bytes(*self, s.as_bytes());
+        Ok(())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl Captured {
+    /// The captured output. If a character was cut in half at the end of a full buffer, it is left
+    /// out.
+    pub fn as_str(&self) -> &str {
+        let bytes = &self.buf[..self.len];
+
+        match core::str::from_utf8(bytes) {
+            Ok(s) => s,
+            // Everything up to the error is valid UTF-8.
+            Err(e) => core::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap(),
+        }
+    }
+
+    /// Whether output was dropped because the buffer was full.
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+}
+
+impl interface::Write for TestConsole {
+    fn write_char(&self, c: char) {
+        let mut utf8 = [0; 4];
+        self.write_bytes(c.encode_utf8(&mut utf8).as_bytes());
+    }
+
+    fn write_fmt(&self, args: fmt::Arguments) -> fmt::Result {
+        fmt::Write::write_fmt(&mut &*self, args)
+    }
+
+    fn write_bytes(&self, bytes: &[u8]) {
+        let start = self.len.fetch_add(bytes.len(), Ordering::Relaxed);
+
+        for (i, &b) in bytes.iter().enumerate() {
+            if let Some(slot) = self.buf.get(start + i) {
+                slot.store(b, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+impl interface::Read for TestConsole {
+    fn clear_rx(&self) {}
+}
+
+impl interface::Statistics for TestConsole {
+    fn chars_written(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+}
+
+/// Return the test console if a capture runs.
+pub fn active() -> Option<&'static TestConsole> {
+    TEST_CONSOLE
+        .capturing
+        .load(Ordering::Relaxed)
+        .then(|| &TEST_CONSOLE)
+}
+
+/// Start capturing console output into an empty buffer.
+pub fn start() {
+    TEST_CONSOLE.reset();
+    TEST_CONSOLE.capturing.store(true, Ordering::Relaxed);
+}
+
+/// Stop capturing, and return what was captured.
+pub fn stop() -> Captured {
+    TEST_CONSOLE.capturing.store(false, Ordering::Relaxed);
+    TEST_CONSOLE.snapshot()
+}
+
+/// Run `f`, and return what it printed.
+pub fn capture(f: impl FnOnce()) -> Captured {
+    start();
+    f();
+    stop()
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{info, println};
+    use test_macros::kernel_test;
+
+    /// Printed output is recorded instead of shown, and recording ends with the capture.
+    #[kernel_test]
+    fn output_is_captured() {
+        let output = capture(|| {
+            println!("Captured line");
+            info!("Captured message {}", 42);
+        });
+
+        assert!(output.as_str().starts_with("Captured line\n"));
+        assert!(output.as_str().contains("Captured message 42"));
+        assert!(!output.truncated());
+        assert!(active().is_none());
+    }
+
+    /// Output beyond the buffer is dropped, and reported.
+    #[kernel_test]
+    fn overflow_is_truncated() {
+        let output = capture(|| {
+            for _ in 0..BUFFER_SIZE / 8 + 1 {
+                println!("1234567");
+            }
+        });
+
+        assert_eq!(output.as_str().len(), BUFFER_SIZE);
+        assert!(output.truncated());
+    }
+}
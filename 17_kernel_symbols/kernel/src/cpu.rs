@@ -9,13 +9,23 @@
 mod arch_cpu;
 
 mod boot;
+mod per_cpu;
 
+pub mod code_patch;
+pub mod debug;
 pub mod smp;
 
 //--------------------------------------------------------------------------------------------------
 // Architectural Public Reexports
 //--------------------------------------------------------------------------------------------------
-pub use arch_cpu::{nop, wait_forever};
+pub use arch_cpu::{
+    clean_dcache_range, cycle_counter, enable_cycle_counter, invalidate_dcache_range,
+    invalidate_icache_range, nop, part_number, psci_cpu_on, send_event, set_thread_pointer,
+    switch_to, thread_pointer, wait_for_interrupt, wait_forever, ThreadContext,
+};
 
-#[cfg(feature = "test_build")]
-pub use arch_cpu::{qemu_exit_failure, qemu_exit_success};
+//--------------------------------------------------------------------------------------------------
+// Public Reexports
+//--------------------------------------------------------------------------------------------------
+pub use boot::{boot_privilege_level, boot_secondary};
+pub use per_cpu::PerCpu;
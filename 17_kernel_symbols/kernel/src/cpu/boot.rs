@@ -7,3 +7,8 @@
 #[cfg(target_arch = "aarch64")]
 #[path = "../_arch/aarch64/cpu/boot.rs"]
 mod arch_boot;
+
+//--------------------------------------------------------------------------------------------------
+// Architectural Public Reexports
+//--------------------------------------------------------------------------------------------------
+pub use arch_boot::{boot_privilege_level, boot_secondary, prepare_secondary_core};
@@ -0,0 +1,108 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Patching of kernel code at runtime.
+//!
+//! The kernel's code is mapped read-only. [`init()`] maps it a second time, writable, and patches
+//! are written through this alias. Afterwards, the caches are maintained so that all cores fetch
+//! the new instruction.
+//!
+//! A patch is a single aligned instruction, which the architecture guarantees to be fetched either
+//! entirely old or entirely new by cores that execute it concurrently.
+
+use crate::{bsp, common, memory};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const INSTRUCTION_SIZE: usize = core::mem::size_of::<u32>();
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+/// Offset from an address in the kernel's code to its writable alias. Zero until [`init()`] ran.
+static ALIAS_OFFSET: AtomicUsize = AtomicUsize::new(0);
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn check_patchable(addr: memory::Address<memory::Virtual>) -> Result<(), &'static str> {
+    if !bsp::memory::is_kernel_code(addr) {
+        return Err("Address is not in the kernel's code");
+    }
+
+    if !common::is_aligned(addr.as_usize(), INSTRUCTION_SIZE) {
+        return Err("Instruction address is not aligned");
+    }
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Map the writable alias of the kernel's code. Does nothing if it was mapped before.
+///
+/// # Safety
+///
+/// - Modifies the kernel translation tables. Only call during kernel init.
+pub unsafe fn init() -> Result<(), &'static str> {
+    if ALIAS_OFFSET.load(Ordering::Relaxed) != 0 {
+        return Ok(());
+    }
+
+    let code_region = bsp::memory::mmu::virt_code_region();
+    let alias_region = memory::mmu::kernel_map_rw_alias("Kernel code patch alias", &code_region)?;
+
+    let offset = alias_region.start_addr().as_usize() - code_region.start_addr().as_usize();
+    ALIAS_OFFSET.store(offset, Ordering::Relaxed);
+
+    Ok(())
+}
+
+/// Return whether [`init()`] ran.
+pub fn is_initialized() -> bool {
+    ALIAS_OFFSET.load(Ordering::Relaxed) != 0
+}
+
+/// Read the instruction at `addr`.
+pub fn read_instruction(addr: memory::Address<memory::Virtual>) -> Result<u32, &'static str> {
+    check_patchable(addr)?;
+
+    Ok(unsafe { core::ptr::read_volatile(addr.as_usize() as *const u32) })
+}
+
+/// Replace the instruction at `addr`. Returns the previous instruction.
+///
+/// The caller is responsible that the new instruction is valid in all contexts that can execute
+/// it.
+pub fn write_instruction(
+    addr: memory::Address<memory::Virtual>,
+    instruction: u32,
+) -> Result<u32, &'static str> {
+    check_patchable(addr)?;
+
+    let offset = ALIAS_OFFSET.load(Ordering::Relaxed);
+    if offset == 0 {
+        return Err("Code patching not initialized");
+    }
+
+    let alias = addr.as_usize().wrapping_add(offset);
+    let previous = unsafe {
+        let previous = core::ptr::read_volatile(alias as *const u32);
+        core::ptr::write_volatile(alias as *mut u32, instruction);
+
+        previous
+    };
+
+    super::clean_dcache_range(alias, INSTRUCTION_SIZE);
+    super::invalidate_icache_range(addr.as_usize(), INSTRUCTION_SIZE);
+
+    Ok(previous)
+}
@@ -0,0 +1,69 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Hardware breakpoints and watchpoints.
+//!
+//! Breakpoints trap the execution of an instruction, watchpoints trap data accesses. Both only
+//! match kernel code. A hit is reported on the console together with a backtrace, and execution
+//! continues afterwards.
+//!
+//! Debug exceptions are masked while exception handlers run, so hits in handlers go unnoticed.
+//!
+//! The settings apply to the executing core only.
+
+#[cfg(target_arch = "aarch64")]
+#[path = "../_arch/aarch64/cpu/debug.rs"]
+mod arch_debug;
+
+//--------------------------------------------------------------------------------------------------
+// Architectural Public Reexports
+//--------------------------------------------------------------------------------------------------
+pub use arch_debug::{
+    breakpoint_slot, clear_breakpoint, clear_watchpoint, init, num_breakpoints, num_hits,
+    num_watchpoints, resume_after_step, set_breakpoint, set_watchpoint, suspend_for_step,
+    watchpoint_slot,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The kind of data access that triggers a watchpoint.
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum WatchKind {
+    Load,
+    Store,
+    LoadStore,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::Address;
+    use core::sync::atomic::{AtomicU64, Ordering};
+    use test_macros::kernel_test;
+
+    static WATCHED: AtomicU64 = AtomicU64::new(0);
+
+    /// Check that a store to a watched variable is trapped and then completes normally.
+    #[kernel_test]
+    fn watchpoint_traps_store() {
+        unsafe { init() };
+
+        let addr = Address::new(&WATCHED as *const _ as usize);
+        set_watchpoint(0, addr, 8, WatchKind::Store).unwrap();
+
+        let hits = num_hits();
+        WATCHED.store(0x1337, Ordering::Relaxed);
+        clear_watchpoint(0).unwrap();
+
+        assert_eq!(num_hits(), hits + 1);
+        assert_eq!(WATCHED.load(Ordering::Relaxed), 0x1337);
+    }
+}
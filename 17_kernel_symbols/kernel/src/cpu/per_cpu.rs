@@ -0,0 +1,81 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Per-core data.
+//!
+//! A [`PerCpu`] holds one instance of its data for each core. A core usually only accesses its own
+//! instance, so no lock is needed as long as the data is only modified with IRQs masked, or
+//! consists of atomics.
+//!
+//! The executing core is identified by its core ID. The thread pointer register is not used,
+//! because it identifies the running thread.
+
+use super::smp;
+use crate::bsp;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// One instance of `T` per core.
+pub struct PerCpu<T> {
+    data: [T; bsp::cpu::NUM_CORES],
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl<T> PerCpu<T> {
+    /// Create an instance from the data of all cores.
+    pub const fn new(data: [T; bsp::cpu::NUM_CORES]) -> Self {
+        Self { data }
+    }
+
+    /// Return the instance of the executing core.
+    ///
+    /// A preemptible thread can migrate to another core right after this returns. If the instance
+    /// must belong to the executing core for longer, IRQs must be masked while using it.
+    pub fn get(&self) -> &T {
+        &self.data[smp::core_id::<usize>()]
+    }
+
+    /// Return the instance of the given core.
+    pub fn for_core(&self, core: usize) -> Option<&T> {
+        self.data.get(core)
+    }
+
+    /// Return an iterator over the instances of all cores.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.data.iter()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use test_macros::kernel_test;
+
+    #[allow(clippy::declare_interior_mutable_const)]
+    const ZERO: AtomicUsize = AtomicUsize::new(0);
+
+    static COUNTERS: PerCpu<AtomicUsize> = PerCpu::new([ZERO; bsp::cpu::NUM_CORES]);
+
+    /// Check that the executing core's instance is the one selected by its core ID.
+    #[kernel_test]
+    fn get_selects_executing_core() {
+        COUNTERS.get().fetch_add(1, Ordering::Relaxed);
+
+        let core: usize = smp::core_id();
+        let total: usize = COUNTERS.iter().map(|c| c.load(Ordering::Relaxed)).sum();
+
+        assert_eq!(COUNTERS.for_core(core).unwrap().load(Ordering::Relaxed), 1);
+        assert_eq!(total, 1);
+    }
+}
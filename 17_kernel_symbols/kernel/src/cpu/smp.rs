@@ -3,12 +3,160 @@
 // Copyright (c) 2018-2022 Andre Richter <andre.o.richter@gmail.com>
 
 //! Symmetric multiprocessing.
+//!
+//! The boot core starts the secondary cores early with [`start_secondary_cores()`]. Depending on
+//! the firmware, they are released from the firmware's spin-table or from the kernel's, see
+//! [`super::boot_secondary()`]. They wait in [`wait_for_boot_core()`] until the boot core concluded
+//! its init with [`conclude_boot_core_init()`], so that they never see partially initialized
+//! globals. Each core then reports in with [`mark_online()`] once it finished its own init.
 
 #[cfg(target_arch = "aarch64")]
 #[path = "../_arch/aarch64/cpu/smp.rs"]
 mod arch_smp;
 
+mod barrier;
+
+pub mod ipi;
+
 //--------------------------------------------------------------------------------------------------
 // Architectural Public Reexports
 //--------------------------------------------------------------------------------------------------
 pub use arch_smp::core_id;
+
+//--------------------------------------------------------------------------------------------------
+// Public Reexports
+//--------------------------------------------------------------------------------------------------
+pub use barrier::Barrier;
+
+use crate::{bsp, time};
+use core::{
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// How long a secondary core may take to reach the boot barrier, and to come online afterwards.
+const ONLINE_TIMEOUT: Duration = Duration::from_millis(100);
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+#[allow(clippy::declare_interior_mutable_const)]
+const OFFLINE: AtomicBool = AtomicBool::new(false);
+
+static ONLINE: [AtomicBool; bsp::cpu::NUM_CORES] = [OFFLINE; bsp::cpu::NUM_CORES];
+
+/// Holds the secondary cores back until the boot core concluded its init.
+static BOOT_BARRIER: Barrier = Barrier::new(bsp::cpu::NUM_CORES);
+
+/// The number of secondary cores that reached the boot barrier.
+static NUM_STARTED: AtomicUsize = AtomicUsize::new(0);
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Report that the executing core finished its init.
+pub fn mark_online() {
+    ONLINE[core_id::<usize>()].store(true, Ordering::Release);
+}
+
+/// Report that the executing core stopped for good.
+pub fn mark_offline() {
+    ONLINE[core_id::<usize>()].store(false, Ordering::Release);
+}
+
+/// Return whether the given core is online.
+pub fn is_online(core: usize) -> bool {
+    ONLINE
+        .get(core)
+        .map_or(false, |online| online.load(Ordering::Acquire))
+}
+
+/// Return the number of online cores.
+pub fn num_online() -> usize {
+    (0..bsp::cpu::NUM_CORES)
+        .filter(|&core| is_online(core))
+        .count()
+}
+
+/// Start all secondary cores, one after the other, and wait until each of them reached the boot
+/// barrier.
+///
+/// Cores that fail to start are left out, so that they do not hold back the others.
+///
+/// # Safety
+///
+/// - Must be called only once, from the boot core, during kernel init.
+pub unsafe fn start_secondary_cores() -> Result<(), &'static str> {
+    use time::interface::TimeManager;
+
+    let boot_core = bsp::cpu::BOOT_CORE_ID as usize;
+    let mut result = Ok(());
+
+    for core in (0..bsp::cpu::NUM_CORES).filter(|&core| core != boot_core) {
+        let started = result.and_then(|_| {
+            let entry = super::boot::prepare_secondary_core(core)?;
+
+            // The core waits either in the firmware's spin-table, or in the kernel's.
+            bsp::cpu::release_secondary_core(core, entry)?;
+            super::boot_secondary(core, entry)?;
+
+            let expected = NUM_STARTED.load(Ordering::Relaxed) + 1;
+            let deadline = time::time_manager().uptime() + ONLINE_TIMEOUT;
+            while BOOT_BARRIER.num_waiting() < expected {
+                if time::time_manager().uptime() > deadline {
+                    return Err("Secondary core did not start");
+                }
+            }
+
+            Ok(())
+        });
+
+        match started {
+            Ok(()) => {
+                NUM_STARTED.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(x) => {
+                BOOT_BARRIER.leave();
+                result = Err(x);
+            }
+        }
+    }
+
+    result
+}
+
+/// Called by a secondary core right after it started. Returns once the boot core concluded its
+/// init.
+pub fn wait_for_boot_core() {
+    BOOT_BARRIER.wait();
+}
+
+/// Let the secondary cores continue their init.
+///
+/// # Safety
+///
+/// - Must be called only once, from the boot core, at the end of its init.
+pub unsafe fn conclude_boot_core_init() {
+    BOOT_BARRIER.wait();
+}
+
+/// Wait until all started secondary cores are online.
+pub fn wait_for_secondary_cores() -> Result<(), &'static str> {
+    use time::interface::TimeManager;
+
+    let expected = NUM_STARTED.load(Ordering::Relaxed) + 1;
+    let deadline = time::time_manager().uptime() + ONLINE_TIMEOUT;
+    while num_online() < expected {
+        if time::time_manager().uptime() > deadline {
+            return Err("Secondary core did not come online");
+        }
+    }
+
+    Ok(())
+}
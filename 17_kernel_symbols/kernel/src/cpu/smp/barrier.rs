@@ -0,0 +1,95 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! A barrier for cores.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Lets a number of cores wait for each other.
+///
+/// Each participant calls [`Barrier::wait()`], which returns once all participants arrived. The
+/// barrier can then be used again.
+pub struct Barrier {
+    participants: AtomicUsize,
+    arrived: AtomicUsize,
+
+    /// Incremented each time all participants arrived.
+    generation: AtomicUsize,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl Barrier {
+    /// Create an instance.
+    pub const fn new(participants: usize) -> Self {
+        Self {
+            participants: AtomicUsize::new(participants),
+            arrived: AtomicUsize::new(0),
+            generation: AtomicUsize::new(0),
+        }
+    }
+
+    /// Wait until all participants arrived. Returns true on exactly one of them, the last one.
+    ///
+    /// Spins with IRQs unchanged, so it must not be used by threads that share a core.
+    pub fn wait(&self) -> bool {
+        let generation = self.generation.load(Ordering::Acquire);
+
+        if self.arrived.fetch_add(1, Ordering::AcqRel) + 1
+            == self.participants.load(Ordering::Relaxed)
+        {
+            self.arrived.store(0, Ordering::Relaxed);
+            self.generation.fetch_add(1, Ordering::Release);
+
+            return true;
+        }
+
+        while self.generation.load(Ordering::Acquire) == generation {
+            core::hint::spin_loop();
+        }
+
+        false
+    }
+
+    /// Remove a participant that will never arrive, e.g. a core that failed to start.
+    ///
+    /// Must be called by a participant that did not arrive yet, so that the others can not be
+    /// released concurrently.
+    pub fn leave(&self) {
+        self.participants.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// The number of participants that are waiting.
+    pub fn num_waiting(&self) -> usize {
+        self.arrived.load(Ordering::Acquire)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
+
+    /// The last participant must pass right away, and participants that left must not be waited
+    /// for.
+    #[kernel_test]
+    fn barrier_releases_last_participant() {
+        let barrier = Barrier::new(2);
+
+        barrier.leave();
+        assert!(barrier.wait());
+        assert!(barrier.wait());
+        assert_eq!(barrier.num_waiting(), 0);
+    }
+}
@@ -0,0 +1,149 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Inter-processor interrupts.
+//!
+//! Cores signal each other with typed [`Message`]s. Each core has a queue of pending messages, and
+//! a single software-generated interrupt tells the target core to drain it. This way, subsystems
+//! share one IRQ instead of each claiming their own.
+
+use crate::{bsp, cpu, exception, memory, scheduler, synchronization::RingBuffer, trace};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The maximum number of pending messages per core.
+const QUEUE_SIZE: usize = 16;
+
+struct IpiHandler;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A message for another core.
+#[derive(Copy, Clone)]
+pub enum Message {
+    /// Reschedule when returning from the IRQ.
+    Reschedule,
+
+    /// Invalidate the core's TLB after translation tables were changed.
+    TlbShootdown,
+
+    /// Take the core offline and park it for good.
+    Halt,
+
+    /// Call the function in IRQ context.
+    CallFunction(fn()),
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY: RingBuffer<Message, QUEUE_SIZE> = RingBuffer::new();
+
+static QUEUES: cpu::PerCpu<RingBuffer<Message, QUEUE_SIZE>> =
+    cpu::PerCpu::new([EMPTY; bsp::cpu::NUM_CORES]);
+
+static IPI_HANDLER: IpiHandler = IpiHandler;
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn handle_message(msg: Message) {
+    match msg {
+        Message::Reschedule => scheduler::request_resched(),
+        Message::TlbShootdown => memory::mmu::invalidate_local_tlb(),
+        Message::Halt => {
+            cpu::smp::mark_offline();
+            cpu::wait_forever()
+        }
+        Message::CallFunction(func) => func(),
+    }
+}
+
+impl exception::asynchronous::interface::IRQHandler for IpiHandler {
+    fn handle(&self) -> Result<(), &'static str> {
+        while let Some(msg) = QUEUES.get().pop() {
+            handle_message(msg);
+        }
+
+        Ok(())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Register and enable the IPI handler on the boot core.
+///
+/// # Safety
+///
+/// - Must be called only once, during kernel init.
+pub unsafe fn init() -> Result<(), &'static str> {
+    use exception::asynchronous::{interface::IRQManager, IRQDescriptor};
+
+    let (irq, _) = bsp::exception::asynchronous::ipi_irq().ok_or("No IPI available")?;
+    let irq_manager = bsp::exception::asynchronous::irq_manager();
+
+    irq_manager.register_handler(
+        irq,
+        IRQDescriptor {
+            name: "IPI",
+            handler: &IPI_HANDLER,
+        },
+    )?;
+    irq_manager.enable(irq);
+
+    Ok(())
+}
+
+/// Enable the IPI on a secondary core. The IRQ handler was registered by [`init()`].
+///
+/// # Safety
+///
+/// - Must only be called during the init of a secondary core.
+pub unsafe fn init_secondary() -> Result<(), &'static str> {
+    use exception::asynchronous::interface::IRQManager;
+
+    let (irq, _) = bsp::exception::asynchronous::ipi_irq().ok_or("No IPI available")?;
+    bsp::exception::asynchronous::irq_manager().enable(irq);
+
+    Ok(())
+}
+
+/// Send a message to the given core.
+///
+/// The message is handled asynchronously. Can be called from IRQ handlers.
+pub fn send(core: usize, msg: Message) -> Result<(), &'static str> {
+    let (_, ipi) = bsp::exception::asynchronous::ipi_irq().ok_or("No IPI available")?;
+
+    if !super::is_online(core) {
+        return Err("Target core is not online");
+    }
+
+    trace!("IPI to core {}", core);
+
+    QUEUES
+        .for_core(core)
+        .ok_or("Target core does not exist")?
+        .push(msg)
+        .map_err(|_| "IPI queue full")?;
+
+    exception::asynchronous::send_ipi(core, ipi)
+}
+
+/// Send a message to all other online cores.
+pub fn broadcast(msg: Message) -> Result<(), &'static str> {
+    let own_core: usize = super::core_id();
+
+    (0..bsp::cpu::NUM_CORES)
+        .filter(|&core| core != own_core && super::is_online(core))
+        .try_for_each(|core| send(core, msg))
+}
@@ -4,6 +4,12 @@
 
 //! Driver support.
 
+use crate::{
+    bsp, dtb,
+    memory::{mmu::MMIODescriptor, Address},
+    warn,
+};
+
 //--------------------------------------------------------------------------------------------------
 // Public Definitions
 //--------------------------------------------------------------------------------------------------
@@ -24,7 +30,11 @@ pub mod interface {
             Ok(())
         }
 
-        /// Called by the kernel to register and enable the device's IRQ handlers, if any.
+        /// Called by the driver manager to register and enable the device's IRQ handlers, if any.
+        ///
+        /// The driver manager guarantees that this is only called after all drivers, including
+        /// the interrupt controller, have been initialized. Drivers that use interrupts are
+        /// expected to know their own IRQ numbers and to register their handlers here.
         ///
         /// Rust's type system will prevent a call to this function unless the calling instance
         /// itself has static lifetime.
@@ -45,18 +55,299 @@ pub mod interface {
     ///
     /// The `BSP` is supposed to supply one global instance.
     pub trait DriverManager {
-        /// Return a slice of references to all `BSP`-instantiated drivers.
-        fn all_device_drivers(&self) -> &[&'static (dyn DeviceDriver + Sync)];
+        /// Register a device driver with the manager.
+        ///
+        /// Drivers are initialized in the order they were registered in.
+        fn register_driver(
+            &self,
+            descriptor: super::DeviceDriverDescriptor,
+        ) -> Result<(), &'static str>;
 
-        /// Return only those drivers needed for the BSP's early printing functionality.
+        /// Call `f` for each registered driver, in registration order.
+        fn for_each_device_driver(&self, f: impl FnMut(&super::DeviceDriverDescriptor));
+
+        /// Initialize all registered drivers, then let them register their IRQ handlers.
+        ///
+        /// A driver is only initialized after all drivers it depends on have been initialized and
+        /// their post-init callbacks have run. Drivers without mutual dependencies are initialized
+        /// in registration order. Drivers whose dependencies can not be satisfied are skipped with
+        /// a warning.
+        ///
+        /// IRQ handler registration is a separate step that starts only after every driver has
+        /// been initialized, so that the interrupt controller is guaranteed to be up.
         ///
-        /// For example, the default UART.
-        fn early_print_device_drivers(&self) -> &[&'static (dyn DeviceDriver + Sync)];
+        /// # Safety
+        ///
+        /// - During init, drivers might do stuff with system-wide impact.
+        unsafe fn init_drivers_and_irqs(&self);
+    }
+}
+
+/// Type to be used as an optional callback after a driver's init() has run.
+pub type DeviceDriverPostInitCallback = unsafe fn() -> Result<(), &'static str>;
+
+/// A descriptor for device drivers that are registered with the driver manager.
+#[derive(Copy, Clone)]
+pub struct DeviceDriverDescriptor {
+    device_driver: &'static (dyn interface::DeviceDriver + Sync),
+    dependencies: &'static [&'static str],
+    post_init_callback: Option<DeviceDriverPostInitCallback>,
+}
+
+/// Type of a function that returns the driver descriptor for a matching device tree node.
+///
+/// `None` is passed if the device tree does not describe the device at all, or if there is no
+/// device tree. The driver then keeps the settings from the board's static memory and IRQ maps.
+pub type DeviceTreeProbeFn =
+    fn(node: Option<&dtb::Node>) -> Result<DeviceDriverDescriptor, &'static str>;
+
+/// Associates device tree `compatible` strings with a driver constructor.
+#[derive(Copy, Clone)]
+pub struct DeviceTreeMatch {
+    compatible: &'static [&'static str],
+    probe: DeviceTreeProbeFn,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl DeviceDriverDescriptor {
+    /// Create an instance.
+    ///
+    /// `dependencies` are the `compatible()` strings of drivers that must be initialized before
+    /// this one.
+    pub const fn new(
+        device_driver: &'static (dyn interface::DeviceDriver + Sync),
+        dependencies: &'static [&'static str],
+        post_init_callback: Option<DeviceDriverPostInitCallback>,
+    ) -> Self {
+        Self {
+            device_driver,
+            dependencies,
+            post_init_callback,
+        }
+    }
+
+    /// The described driver.
+    pub fn device_driver(&self) -> &'static (dyn interface::DeviceDriver + Sync) {
+        self.device_driver
+    }
+
+    /// The `compatible()` strings of the drivers this one depends on.
+    pub fn dependencies(&self) -> &'static [&'static str] {
+        self.dependencies
+    }
+
+    /// The callback to execute after the driver's init() has run, if any.
+    pub fn post_init_callback(&self) -> Option<DeviceDriverPostInitCallback> {
+        self.post_init_callback
+    }
+}
+
+impl DeviceTreeMatch {
+    /// Create an instance.
+    pub const fn new(compatible: &'static [&'static str], probe: DeviceTreeProbeFn) -> Self {
+        Self { compatible, probe }
+    }
+
+    /// Check if the node matches one of the compatible strings.
+    fn matches(&self, node: &dtb::Node) -> bool {
+        self.compatible.iter().any(|c| node.is_compatible(c))
+    }
+
+    /// Return the first enabled node of the tree that matches one of the compatible strings.
+    pub fn find_node<'a>(&self, dt: &dtb::DeviceTree<'a>) -> Option<dtb::Node<'a>> {
+        dt.nodes()
+            .find(|node| node.is_enabled() && self.matches(node))
+    }
+
+    /// Check if the tree has a matching node at all, enabled or not.
+    pub fn is_described(&self, dt: &dtb::DeviceTree) -> bool {
+        dt.nodes().any(|node| self.matches(&node))
+    }
+}
+
+/// Register drivers for the devices of a board, as far as the device tree describes them.
+///
+/// The entries of `table` are processed in order. For each one, the first enabled and compatible
+/// node is handed to the probe function, and the returned descriptor is registered with the driver
+/// manager. Devices that the device tree only describes as disabled are skipped. Devices it does
+/// not describe at all, or all devices if there is no device tree, are probed without a node.
+///
+/// Entries whose probe or registration fails are skipped with a warning, so that a partially
+/// matching device tree still brings up the remaining devices.
+pub fn probe_device_tree(
+    driver_manager: &impl interface::DriverManager,
+    dt: Option<&dtb::DeviceTree>,
+    table: &[DeviceTreeMatch],
+) {
+    for entry in table {
+        let node = dt.and_then(|dt| entry.find_node(dt));
+
+        if node.is_none() && dt.map_or(false, |dt| entry.is_described(dt)) {
+            continue;
+        }
+
+        let result = (entry.probe)(node.as_ref())
+            .and_then(|descriptor| driver_manager.register_driver(descriptor));
+
+        if let Err(x) = result {
+            warn!("Skipping device {:?}: {}", entry.compatible, x);
+        }
+    }
+}
+
+/// Build an MMIO descriptor from the `index`-th entry of a device tree node's `reg` property.
+///
+/// Returns `None`, with a warning, if the entry is missing, can not be translated into a CPU
+/// physical address, or lies outside of the board's physical address space. Drivers then keep the
+/// MMIO descriptor from the board's static memory map.
+pub fn mmio_descriptor_from_dt(node: &dtb::Node, index: usize) -> Option<MMIODescriptor> {
+    let phys_addr_space_end = bsp::memory::phys_addr_space_end_exclusive_addr()
+        .into_inner()
+        .as_usize();
+
+    let descriptor = node.phys_reg(index).and_then(|(addr, size)| {
+        let start = usize::try_from(addr).ok()?;
+        let size = usize::try_from(size).ok().filter(|x| *x > 0)?;
+
+        (start.checked_add(size)? <= phys_addr_space_end)
+            .then(|| MMIODescriptor::new(Address::new(start), size))
+    });
+
+    if descriptor.is_none() {
+        warn!(
+            "{}: Unusable reg entry {}, keeping the static memory map",
+            node.name(),
+            index
+        );
+    }
+
+    descriptor
+}
+
+/// Compute an init order in which every driver comes after the drivers it depends on.
+///
+/// Empty slots in `descriptors` are skipped. Drivers that depend on a driver that is not
+/// registered, or that are part of a dependency cycle, can not be initialized and are left out, as
+/// are all drivers depending on them. On success, `order` is filled with indices into
+/// `descriptors` and the number of valid entries is returned.
+pub fn compute_init_order(
+    descriptors: &[Option<DeviceDriverDescriptor>],
+    order: &mut [usize],
+) -> Result<usize, &'static str> {
+    let find = |compatible: &str| {
+        descriptors
+            .iter()
+            .position(|x| x.map_or(false, |d| d.device_driver().compatible() == compatible))
+    };
+
+    let num_drivers = descriptors.iter().flatten().count();
+    if order.len() < num_drivers {
+        return Err("Init order storage too small");
+    }
+
+    let mut num_ordered = 0;
+    loop {
+        let mut progress = false;
+
+        for (i, descriptor) in descriptors.iter().enumerate() {
+            let descriptor = match descriptor {
+                Some(x) => x,
+                None => continue,
+            };
+
+            if order[..num_ordered].contains(&i) {
+                continue;
+            }
+
+            let ready = descriptor.dependencies().iter().all(|dependency| {
+                find(dependency).map_or(false, |x| order[..num_ordered].contains(&x))
+            });
+
+            if ready {
+                order[num_ordered] = i;
+                num_ordered += 1;
+                progress = true;
+
+                // Restart, so that registration order is kept wherever possible.
+                break;
+            }
+        }
+
+        // Whatever is left has unresolvable dependencies.
+        if !progress {
+            return Ok(num_ordered);
+        }
+    }
+}
+
+/// Log a warning for every driver that was left out of an init order.
+pub fn warn_unordered_drivers(descriptors: &[Option<DeviceDriverDescriptor>], order: &[usize]) {
+    for (i, descriptor) in descriptors.iter().enumerate() {
+        let descriptor = match descriptor {
+            Some(x) if !order.contains(&i) => x,
+            _ => continue,
+        };
+
+        warn!(
+            "Skipping driver with unresolved dependencies: {}",
+            descriptor.device_driver().compatible()
+        );
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
+
+    struct DummyDriver(&'static str);
+
+    impl interface::DeviceDriver for DummyDriver {
+        fn compatible(&self) -> &'static str {
+            self.0
+        }
+    }
+
+    static A: DummyDriver = DummyDriver("A");
+    static B: DummyDriver = DummyDriver("B");
+    static C: DummyDriver = DummyDriver("C");
+
+    /// Check that dependencies are initialized first, and that drivers with missing or cyclic
+    /// dependencies are left out.
+    #[kernel_test]
+    fn init_order_respects_dependencies() {
+        let descriptors = [
+            Some(DeviceDriverDescriptor::new(&A, &["C"], None)),
+            None,
+            Some(DeviceDriverDescriptor::new(&B, &[], None)),
+            Some(DeviceDriverDescriptor::new(&C, &["B"], None)),
+        ];
+        let mut order = [0; 4];
+
+        assert_eq!(compute_init_order(&descriptors, &mut order), Ok(3));
+        assert_eq!(order[..3], [2, 3, 0]);
 
-        /// Return all drivers minus early-print drivers.
-        fn non_early_print_device_drivers(&self) -> &[&'static (dyn DeviceDriver + Sync)];
+        let cyclic = [
+            Some(DeviceDriverDescriptor::new(&A, &["B"], None)),
+            Some(DeviceDriverDescriptor::new(&B, &["A"], None)),
+            Some(DeviceDriverDescriptor::new(&C, &[], None)),
+        ];
+        assert_eq!(compute_init_order(&cyclic, &mut order), Ok(1));
+        assert_eq!(order[0], 2);
 
-        /// Initialization code that runs after the early print driver init.
-        fn post_early_print_device_driver_init(&self);
+        let missing = [
+            Some(DeviceDriverDescriptor::new(&A, &["X"], None)),
+            Some(DeviceDriverDescriptor::new(&B, &["A"], None)),
+            Some(DeviceDriverDescriptor::new(&C, &[], None)),
+        ];
+        assert_eq!(compute_init_order(&missing, &mut order), Ok(1));
+        assert_eq!(order[0], 2);
     }
 }
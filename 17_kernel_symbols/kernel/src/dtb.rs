@@ -0,0 +1,764 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Flattened Device Tree (DTB) parsing.
+//!
+//! The firmware passes the physical address of a DTB in `x0` when it jumps to the kernel. The boot
+//! code preserves it, and `init()` maps the blob read-only into the kernel's address space. The
+//! parser itself works on any byte slice, does not allocate and never panics on malformed input.
+//!
+//! # Resources
+//!
+//! - <https://github.com/devicetree-org/devicetree-specification/releases>
+
+use crate::{
+    memory::{self, Address, Physical},
+    synchronization::OnceCell,
+};
+use core::{
+    slice, str,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const FDT_MAGIC: u32 = 0xD00D_FEED;
+const FDT_HEADER_SIZE: usize = 40;
+
+/// The oldest version this parser is compatible with.
+const FDT_MIN_COMPATIBLE_VERSION: u32 = 16;
+
+const FDT_BEGIN_NODE: u32 = 0x1;
+const FDT_END_NODE: u32 = 0x2;
+const FDT_PROP: u32 = 0x3;
+const FDT_NOP: u32 = 0x4;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A parsed device tree blob.
+#[derive(Copy, Clone)]
+pub struct DeviceTree<'a> {
+    structs: &'a [u8],
+    strings: &'a [u8],
+}
+
+/// A node of the device tree.
+#[derive(Copy, Clone)]
+pub struct Node<'a> {
+    dt: DeviceTree<'a>,
+    name: &'a str,
+
+    /// Offset into the structure block right after the node's name.
+    props_offset: usize,
+}
+
+/// A property of a node.
+#[derive(Copy, Clone)]
+pub struct Property<'a> {
+    name: &'a str,
+    value: &'a [u8],
+}
+
+/// Iterator over the properties of a node.
+pub struct PropertyIter<'a> {
+    dt: DeviceTree<'a>,
+    offset: usize,
+}
+
+/// Iterator over the direct children of a node.
+pub struct ChildIter<'a> {
+    dt: DeviceTree<'a>,
+    offset: Option<usize>,
+}
+
+/// Iterator over all nodes of the tree, in the order they appear in the blob.
+pub struct NodeIter<'a> {
+    dt: DeviceTree<'a>,
+    offset: Option<usize>,
+}
+
+/// Iterator over the `(address, size)` pairs of a `reg` property.
+pub struct RegIter<'a> {
+    value: &'a [u8],
+    address_cells: usize,
+    size_cells: usize,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static BOOT_DTB_PHYS_ADDR: AtomicUsize = AtomicUsize::new(0);
+
+static BOOT_DTB: OnceCell<DeviceTree<'static>> = OnceCell::new();
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn be32(buf: &[u8], offset: usize) -> Option<u32> {
+    let bytes = buf.get(offset..offset.checked_add(4)?)?;
+
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn align4(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+/// Return the NUL-terminated string starting at `offset`, without the terminator.
+fn c_str(buf: &[u8], offset: usize) -> Option<&str> {
+    let rest = buf.get(offset..)?;
+    let len = rest.iter().position(|b| *b == 0)?;
+
+    str::from_utf8(&rest[..len]).ok()
+}
+
+/// Read up to two cells as a big-endian number.
+fn read_cells(buf: &[u8], cells: usize) -> Option<u64> {
+    match cells {
+        0 => Some(0),
+        1 => be32(buf, 0).map(u64::from),
+        2 => Some((u64::from(be32(buf, 0)?) << 32) | u64::from(be32(buf, 4)?)),
+        _ => None,
+    }
+}
+
+impl<'a> DeviceTree<'a> {
+    fn token(&self, offset: usize) -> Option<u32> {
+        be32(self.structs, offset)
+    }
+
+    /// Parse the name of the node whose `FDT_BEGIN_NODE` token is at `offset`.
+    fn node_at(&self, offset: usize) -> Option<Node<'a>> {
+        let name_offset = offset + 4;
+        let name = c_str(self.structs, name_offset)?;
+
+        Some(Node {
+            dt: *self,
+            name,
+            props_offset: align4(name_offset + name.len() + 1),
+        })
+    }
+
+    /// Parse the property whose `FDT_PROP` token is at `offset`. Also returns the offset of the
+    /// next token.
+    fn property_at(&self, offset: usize) -> Option<(Property<'a>, usize)> {
+        let len = be32(self.structs, offset + 4)? as usize;
+        let name_offset = be32(self.structs, offset + 8)? as usize;
+        let value_start = offset + 12;
+        let value = self
+            .structs
+            .get(value_start..value_start.checked_add(len)?)?;
+        let name = c_str(self.strings, name_offset)?;
+
+        Some((Property { name, value }, align4(value_start + len)))
+    }
+
+    /// Starting right after a node's name, skip over everything until after the node's matching
+    /// `FDT_END_NODE` token.
+    fn skip_node(&self, props_offset: usize) -> Option<usize> {
+        let mut offset = props_offset;
+        let mut depth = 1_usize;
+
+        loop {
+            match self.token(offset)? {
+                FDT_BEGIN_NODE => {
+                    offset = self.node_at(offset)?.props_offset;
+                    depth += 1;
+                }
+                FDT_END_NODE => {
+                    offset += 4;
+                    depth -= 1;
+
+                    if depth == 0 {
+                        return Some(offset);
+                    }
+                }
+                FDT_PROP => offset = self.property_at(offset)?.1,
+                FDT_NOP => offset += 4,
+                _ => return None,
+            }
+        }
+    }
+}
+
+impl<'a> Node<'a> {
+    /// Offset of the first token after the node's properties.
+    fn children_offset(&self) -> Option<usize> {
+        let mut offset = self.props_offset;
+
+        loop {
+            match self.dt.token(offset)? {
+                FDT_PROP => offset = self.dt.property_at(offset)?.1,
+                FDT_NOP => offset += 4,
+                _ => return Some(offset),
+            }
+        }
+    }
+
+    /// Search the node's subtree for the parent of the node whose properties start at
+    /// `props_offset`.
+    fn find_parent_of(&self, props_offset: usize) -> Option<Node<'a>> {
+        for child in self.children() {
+            if child.props_offset == props_offset {
+                return Some(*self);
+            }
+
+            // Only descend into the child whose subtree contains the node.
+            let end = self.dt.skip_node(child.props_offset)?;
+            if (child.props_offset..end).contains(&props_offset) {
+                return child.find_parent_of(props_offset);
+            }
+        }
+
+        None
+    }
+
+    /// Translate an address on the bus that the node provides to its children into an address on
+    /// the bus of the node's parent, using the node's `ranges` property.
+    fn translate(&self, addr: u64, parent_address_cells: usize) -> Option<u64> {
+        let ranges = self.property("ranges")?.value();
+
+        // An empty `ranges` property means that both buses use the same addresses.
+        if ranges.is_empty() {
+            return Some(addr);
+        }
+
+        let child_cells = self.address_cells();
+        let size_cells = self.size_cells();
+        let entry_len = (child_cells + parent_address_cells + size_cells) * 4;
+        if entry_len == 0 {
+            return None;
+        }
+
+        ranges.chunks_exact(entry_len).find_map(|entry| {
+            let child_base = read_cells(entry, child_cells)?;
+            let parent_base = read_cells(&entry[child_cells * 4..], parent_address_cells)?;
+            let len = read_cells(
+                &entry[(child_cells + parent_address_cells) * 4..],
+                size_cells,
+            )?;
+
+            (child_base..child_base.checked_add(len)?)
+                .contains(&addr)
+                .then(|| parent_base + (addr - child_base))
+        })
+    }
+
+    /// Compare a path component against the node name. The unit address may be omitted.
+    fn matches_path_component(&self, component: &str) -> bool {
+        if self.name == component {
+            return true;
+        }
+
+        !component.contains('@') && self.name.split('@').next() == Some(component)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl<'a> DeviceTree<'a> {
+    /// Read the total size of a blob from its header.
+    ///
+    /// Only the first `FDT_HEADER_SIZE` bytes need to be accessible. Useful to find out how much
+    /// memory needs to be mapped before the whole blob can be handed to `from_bytes()`.
+    pub fn total_size(header: &[u8]) -> Result<usize, &'static str> {
+        if be32(header, 0) != Some(FDT_MAGIC) {
+            return Err("DTB: Invalid magic");
+        }
+
+        be32(header, 4)
+            .map(|x| x as usize)
+            .ok_or("DTB: Header too short")
+    }
+
+    /// Validate the header and create an instance.
+    pub fn from_bytes(blob: &'a [u8]) -> Result<Self, &'static str> {
+        let total_size = Self::total_size(blob)?;
+        if blob.len() < FDT_HEADER_SIZE || blob.len() < total_size {
+            return Err("DTB: Blob is truncated");
+        }
+
+        let field = |i: usize| be32(blob, i * 4).unwrap();
+        let (off_structs, off_strings) = (field(2) as usize, field(3) as usize);
+        let (size_strings, size_structs) = (field(8) as usize, field(9) as usize);
+
+        if field(6) > FDT_MIN_COMPATIBLE_VERSION + 1 {
+            return Err("DTB: Unsupported version");
+        }
+
+        let structs = off_structs
+            .checked_add(size_structs)
+            .and_then(|end| blob[..total_size].get(off_structs..end))
+            .ok_or("DTB: Structure block out of bounds")?;
+        let strings = off_strings
+            .checked_add(size_strings)
+            .and_then(|end| blob[..total_size].get(off_strings..end))
+            .ok_or("DTB: Strings block out of bounds")?;
+
+        // The tree must start with the root node, whose name is an empty string.
+        let dt = Self { structs, strings };
+        if dt.token(0) != Some(FDT_BEGIN_NODE) || c_str(structs, 4) != Some("") {
+            return Err("DTB: Structure block does not start with the root node");
+        }
+
+        Ok(dt)
+    }
+
+    /// The root node.
+    pub fn root(&self) -> Node<'a> {
+        // Presence of the root node was checked in from_bytes().
+        Node {
+            dt: *self,
+            name: "",
+            props_offset: 8,
+        }
+    }
+
+    /// Iterate over all nodes of the tree.
+    pub fn nodes(&self) -> NodeIter<'a> {
+        NodeIter {
+            dt: *self,
+            offset: Some(0),
+        }
+    }
+
+    /// Look up a node by its absolute path, e.g. `/soc/serial@7e201000`.
+    ///
+    /// The unit address of a path component may be omitted if it is unambiguous, e.g. `/memory`.
+    pub fn find_node(&self, path: &str) -> Option<Node<'a>> {
+        let mut node = self.root();
+
+        for component in path.split('/').filter(|c| !c.is_empty()) {
+            node = node
+                .children()
+                .find(|child| child.matches_path_component(component))?;
+        }
+
+        Some(node)
+    }
+
+    /// Return the first node that is compatible to the given string.
+    pub fn find_compatible(&self, compatible: &str) -> Option<Node<'a>> {
+        self.nodes().find(|node| node.is_compatible(compatible))
+    }
+
+    /// Return the parent of the given node, or `None` for the root node.
+    pub fn parent(&self, node: &Node<'a>) -> Option<Node<'a>> {
+        self.root().find_parent_of(node.props_offset)
+    }
+
+    /// Return the size of the DRAM described by the first `reg` entry of the `/memory` node.
+    pub fn memory_size(&self) -> Option<u64> {
+        let root = self.root();
+        let node = self.find_node("/memory")?;
+
+        node.property("reg")?
+            .reg(root.address_cells(), root.size_cells())
+            .next()
+            .map(|(_, size)| size)
+    }
+}
+
+impl<'a> Node<'a> {
+    /// The node's name, including the unit address.
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Iterate over the node's properties.
+    pub fn properties(&self) -> PropertyIter<'a> {
+        PropertyIter {
+            dt: self.dt,
+            offset: self.props_offset,
+        }
+    }
+
+    /// Look up a property by name.
+    pub fn property(&self, name: &str) -> Option<Property<'a>> {
+        self.properties().find(|prop| prop.name == name)
+    }
+
+    /// Iterate over the direct children of the node.
+    pub fn children(&self) -> ChildIter<'a> {
+        ChildIter {
+            dt: self.dt,
+            offset: self.children_offset(),
+        }
+    }
+
+    /// Check if the node's `compatible` property contains the given string.
+    pub fn is_compatible(&self, compatible: &str) -> bool {
+        self.property("compatible")
+            .map(|prop| prop.as_str_list().any(|x| x == compatible))
+            .unwrap_or(false)
+    }
+
+    /// Check the node's `status` property. Nodes without one are enabled.
+    pub fn is_enabled(&self) -> bool {
+        match self.property("status").and_then(|prop| prop.as_str()) {
+            None | Some("okay") | Some("ok") => true,
+            Some(_) => false,
+        }
+    }
+
+    /// Return the `index`-th `(address, size)` pair of the node's `reg` property, with the address
+    /// translated into a CPU physical address.
+    ///
+    /// `reg` holds addresses of the bus the node sits on, which are translated through the `ranges`
+    /// properties of the node's ancestors. Returns `None` if an ancestor has no `ranges` property
+    /// or none of its ranges covers the address.
+    pub fn phys_reg(&self, index: usize) -> Option<(u64, u64)> {
+        let mut bus = self.dt.parent(self)?;
+        let (mut addr, size) = self
+            .property("reg")?
+            .reg(bus.address_cells(), bus.size_cells())
+            .nth(index)?;
+
+        while let Some(parent) = self.dt.parent(&bus) {
+            addr = bus.translate(addr, parent.address_cells())?;
+            bus = parent;
+        }
+
+        Some((addr, size))
+    }
+
+    /// The `#address-cells` value that applies to the node's children.
+    pub fn address_cells(&self) -> usize {
+        self.property("#address-cells")
+            .and_then(|prop| prop.as_u32())
+            .unwrap_or(2) as usize
+    }
+
+    /// The `#size-cells` value that applies to the node's children.
+    pub fn size_cells(&self) -> usize {
+        self.property("#size-cells")
+            .and_then(|prop| prop.as_u32())
+            .unwrap_or(1) as usize
+    }
+}
+
+impl<'a> Property<'a> {
+    /// The property's name.
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// The raw value.
+    pub fn value(&self) -> &'a [u8] {
+        self.value
+    }
+
+    /// Interpret the value as a single 32 bit cell.
+    pub fn as_u32(&self) -> Option<u32> {
+        if self.value.len() != 4 {
+            return None;
+        }
+
+        be32(self.value, 0)
+    }
+
+    /// Interpret the value as a single 64 bit number, which may be encoded in one or two cells.
+    pub fn as_u64(&self) -> Option<u64> {
+        if self.value.len() % 4 != 0 {
+            return None;
+        }
+
+        read_cells(self.value, self.value.len() / 4)
+    }
+
+    /// Interpret the value as a NUL-terminated string.
+    pub fn as_str(&self) -> Option<&'a str> {
+        self.as_str_list().next()
+    }
+
+    /// Interpret the value as a list of NUL-terminated strings.
+    pub fn as_str_list(&self) -> impl Iterator<Item = &'a str> {
+        let value = self.value.strip_suffix(&[0]).unwrap_or(&[]);
+
+        value
+            .split(|b| *b == 0)
+            .filter_map(|s| str::from_utf8(s).ok())
+    }
+
+    /// Interpret the value as the n-th 32 bit cell of a list.
+    pub fn cell(&self, index: usize) -> Option<u32> {
+        be32(self.value, index.checked_mul(4)?)
+    }
+
+    /// Interpret the value as a `reg` property with the given cell sizes.
+    ///
+    /// The cell sizes come from the `#address-cells` and `#size-cells` properties of the parent.
+    pub fn reg(&self, address_cells: usize, size_cells: usize) -> RegIter<'a> {
+        RegIter {
+            value: self.value,
+            address_cells,
+            size_cells,
+        }
+    }
+}
+
+impl<'a> Iterator for PropertyIter<'a> {
+    type Item = Property<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.dt.token(self.offset)? {
+                FDT_PROP => {
+                    let (prop, next) = self.dt.property_at(self.offset)?;
+                    self.offset = next;
+
+                    return Some(prop);
+                }
+                FDT_NOP => self.offset += 4,
+                _ => return None,
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for ChildIter<'a> {
+    type Item = Node<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let offset = self.offset?;
+
+            match self.dt.token(offset) {
+                Some(FDT_BEGIN_NODE) => {
+                    let child = self.dt.node_at(offset);
+                    self.offset = child.and_then(|c| self.dt.skip_node(c.props_offset));
+
+                    return child;
+                }
+                Some(FDT_NOP) => self.offset = Some(offset + 4),
+                _ => {
+                    self.offset = None;
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for NodeIter<'a> {
+    type Item = Node<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let offset = self.offset?;
+
+            match self.dt.token(offset) {
+                Some(FDT_BEGIN_NODE) => {
+                    let node = self.dt.node_at(offset);
+                    self.offset = node.map(|n| n.props_offset);
+
+                    return node;
+                }
+                Some(FDT_PROP) => {
+                    self.offset = self.dt.property_at(offset).map(|(_, next)| next);
+                }
+                Some(FDT_END_NODE) | Some(FDT_NOP) => self.offset = Some(offset + 4),
+                // FDT_END, or a malformed blob.
+                _ => {
+                    self.offset = None;
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for RegIter<'a> {
+    type Item = (u64, u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let address_len = self.address_cells * 4;
+        let entry_len = address_len + self.size_cells * 4;
+        if entry_len == 0 || self.value.len() < entry_len {
+            return None;
+        }
+
+        let address = read_cells(self.value, self.address_cells)?;
+        let size = read_cells(&self.value[address_len..], self.size_cells)?;
+        self.value = &self.value[entry_len..];
+
+        Some((address, size))
+    }
+}
+
+/// Remember the DTB address that the firmware handed to the kernel.
+///
+/// Called by the boot code, after `.bss` has been initialized.
+pub fn set_boot_dtb_phys_addr(addr: usize) {
+    BOOT_DTB_PHYS_ADDR.store(addr, Ordering::Relaxed);
+}
+
+/// Map the DTB that the firmware handed to the kernel and validate it.
+///
+/// Succeeds without doing anything if no DTB was provided.
+///
+/// # Safety
+///
+/// - Must be called during kernel init, after the MMU subsystem's post-enable init.
+pub unsafe fn init() -> Result<(), &'static str> {
+    let phys_addr = BOOT_DTB_PHYS_ADDR.load(Ordering::Relaxed);
+    if phys_addr == 0 {
+        return Ok(());
+    }
+    let phys_addr: Address<Physical> = Address::new(phys_addr);
+
+    // Map the header first to find out how big the blob is, then map the whole blob.
+    let header = memory::mmu::kernel_map_ro_data("Device Tree Blob", phys_addr, FDT_HEADER_SIZE)?;
+    let size = DeviceTree::total_size(slice::from_raw_parts(
+        header.as_usize() as *const u8,
+        FDT_HEADER_SIZE,
+    ))?;
+
+    let header_end_exclusive = (phys_addr + FDT_HEADER_SIZE).align_up_page();
+    let virt_addr = if (phys_addr + size).as_usize() <= header_end_exclusive.as_usize() {
+        header
+    } else {
+        memory::mmu::kernel_map_ro_data("Device Tree Blob", phys_addr, size)?
+    };
+
+    let blob = slice::from_raw_parts(virt_addr.as_usize() as *const u8, size);
+    let dt = DeviceTree::from_bytes(blob)?;
+
+    BOOT_DTB
+        .set(dt)
+        .map_err(|_| "Device tree already initialized")
+}
+
+/// Return the DTB that the firmware handed to the kernel, if there was a valid one.
+pub fn boot_device_tree() -> Option<DeviceTree<'static>> {
+    BOOT_DTB.get().copied()
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
+
+    /// A minimal hand-assembled blob with a memory node and a UART below `/soc`.
+    #[rustfmt::skip]
+    static TEST_DTB: [u8; 375] = [
+            0xd0, 0x0d, 0xfe, 0xed, 0x00, 0x00, 0x01, 0x77, 0x00, 0x00, 0x00, 0x38, 0x00, 0x00, 0x01, 0x30,
+            0x00, 0x00, 0x00, 0x28, 0x00, 0x00, 0x00, 0x11, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x47, 0x00, 0x00, 0x00, 0xf8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x0f, 0x00, 0x00, 0x00, 0x00, 0x52, 0x61, 0x73, 0x70,
+            0x62, 0x65, 0x72, 0x72, 0x79, 0x20, 0x50, 0x69, 0x20, 0x33, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03,
+            0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x03,
+            0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x15, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01,
+            0x6d, 0x65, 0x6d, 0x6f, 0x72, 0x79, 0x40, 0x30, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03,
+            0x00, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00, 0x21, 0x6d, 0x65, 0x6d, 0x6f, 0x72, 0x79, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x2d, 0x00, 0x00, 0x00, 0x00,
+            0x3b, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x73, 0x6f, 0x63, 0x00,
+            0x00, 0x00, 0x00, 0x01, 0x73, 0x65, 0x72, 0x69, 0x61, 0x6c, 0x40, 0x37, 0x65, 0x32, 0x30, 0x31,
+            0x30, 0x30, 0x30, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x18, 0x00, 0x00, 0x00, 0x31,
+            0x61, 0x72, 0x6d, 0x2c, 0x70, 0x6c, 0x30, 0x31, 0x31, 0x00, 0x61, 0x72, 0x6d, 0x2c, 0x70, 0x72,
+            0x69, 0x6d, 0x65, 0x63, 0x65, 0x6c, 0x6c, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x08,
+            0x00, 0x00, 0x00, 0x2d, 0x7e, 0x20, 0x10, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x03,
+            0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x3c, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x19,
+            0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x09,
+            0x6d, 0x6f, 0x64, 0x65, 0x6c, 0x00, 0x23, 0x61, 0x64, 0x64, 0x72, 0x65, 0x73, 0x73, 0x2d, 0x63,
+            0x65, 0x6c, 0x6c, 0x73, 0x00, 0x23, 0x73, 0x69, 0x7a, 0x65, 0x2d, 0x63, 0x65, 0x6c, 0x6c, 0x73,
+            0x00, 0x64, 0x65, 0x76, 0x69, 0x63, 0x65, 0x5f, 0x74, 0x79, 0x70, 0x65, 0x00, 0x72, 0x65, 0x67,
+            0x00, 0x63, 0x6f, 0x6d, 0x70, 0x61, 0x74, 0x69, 0x62, 0x6c, 0x65, 0x00, 0x69, 0x6e, 0x74, 0x65,
+            0x72, 0x72, 0x75, 0x70, 0x74, 0x73, 0x00,
+    ];
+
+    /// A hand-assembled blob with a UART below a `/soc` bus that remaps its addresses, and a device
+    /// below a nested bus without a `ranges` property.
+    #[rustfmt::skip]
+    static TEST_DTB_RANGES: [u8; 420] = [
+            0xd0, 0x0d, 0xfe, 0xed, 0x00, 0x00, 0x01, 0xa4, 0x00, 0x00, 0x00, 0x38, 0x00, 0x00, 0x01, 0x68,
+            0x00, 0x00, 0x00, 0x28, 0x00, 0x00, 0x00, 0x11, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x3c, 0x00, 0x00, 0x01, 0x30, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x1a, 0x00, 0x00, 0x00, 0x02,
+            0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x29, 0x00, 0x00, 0x00, 0x01,
+            0x00, 0x00, 0x00, 0x01, 0x73, 0x6f, 0x63, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x04,
+            0x00, 0x00, 0x00, 0x1a, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x04,
+            0x00, 0x00, 0x00, 0x29, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x10,
+            0x00, 0x00, 0x00, 0x35, 0x7e, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xfe, 0x00, 0x00, 0x00,
+            0x01, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x73, 0x65, 0x72, 0x69, 0x61, 0x6c, 0x40, 0x37,
+            0x65, 0x32, 0x30, 0x31, 0x30, 0x30, 0x30, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x0a,
+            0x00, 0x00, 0x00, 0x00, 0x61, 0x72, 0x6d, 0x2c, 0x70, 0x6c, 0x30, 0x31, 0x31, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x0b, 0x7e, 0x20, 0x10, 0x00,
+            0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x0c, 0x00, 0x00, 0x00, 0x0f,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x79, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x02,
+            0x00, 0x00, 0x00, 0x01, 0x62, 0x75, 0x73, 0x40, 0x37, 0x65, 0x33, 0x30, 0x30, 0x30, 0x30, 0x30,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x1a,
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x29,
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x64, 0x65, 0x76, 0x40, 0x30, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x0b, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x02,
+            0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x09, 0x63, 0x6f, 0x6d, 0x70, 0x61, 0x74, 0x69, 0x62,
+            0x6c, 0x65, 0x00, 0x72, 0x65, 0x67, 0x00, 0x69, 0x6e, 0x74, 0x65, 0x72, 0x72, 0x75, 0x70, 0x74,
+            0x73, 0x00, 0x23, 0x61, 0x64, 0x64, 0x72, 0x65, 0x73, 0x73, 0x2d, 0x63, 0x65, 0x6c, 0x6c, 0x73,
+            0x00, 0x23, 0x73, 0x69, 0x7a, 0x65, 0x2d, 0x63, 0x65, 0x6c, 0x6c, 0x73, 0x00, 0x72, 0x61, 0x6e,
+            0x67, 0x65, 0x73, 0x00,
+    ];
+
+    /// Check that nodes and properties of a blob are found.
+    #[kernel_test]
+    fn device_tree_parsing_works() {
+        let dt = DeviceTree::from_bytes(&TEST_DTB).unwrap();
+
+        assert_eq!(
+            dt.root().property("model").unwrap().as_str(),
+            Some("Raspberry Pi 3")
+        );
+        assert_eq!(dt.memory_size(), Some(0x3b40_0000));
+        assert_eq!(dt.nodes().count(), 4);
+
+        let uart = dt.find_node("/soc/serial@7e201000").unwrap();
+        assert!(uart.is_compatible("arm,primecell"));
+        assert!(uart.is_enabled());
+        assert_eq!(
+            uart.property("reg").unwrap().reg(1, 1).next(),
+            Some((0x7e20_1000, 0x200))
+        );
+        assert_eq!(uart.property("interrupts").unwrap().cell(1), Some(25));
+        assert_eq!(dt.find_compatible("arm,pl011").unwrap().name(), uart.name());
+
+        assert!(dt.find_node("/soc/serial@7e215040").is_none());
+        assert!(DeviceTree::from_bytes(&TEST_DTB[..100]).is_err());
+    }
+
+    /// Check that `reg` addresses are translated through the `ranges` of the parent buses.
+    #[kernel_test]
+    fn reg_address_translation_works() {
+        let dt = DeviceTree::from_bytes(&TEST_DTB_RANGES).unwrap();
+
+        let uart = dt.find_node("/soc/serial@7e201000").unwrap();
+        assert_eq!(dt.parent(&uart).unwrap().name(), "soc");
+        assert_eq!(dt.parent(&dt.root()).map(|node| node.name()), None);
+        assert_eq!(uart.phys_reg(0), Some((0xfe20_1000, 0x200)));
+        assert_eq!(uart.phys_reg(1), None);
+
+        // The nested bus has no `ranges`, so its children are not reachable from the CPU.
+        let dev = dt.find_node("/soc/bus@7e300000/dev@0").unwrap();
+        assert_eq!(dt.parent(&dev).unwrap().name(), "bus@7e300000");
+        assert_eq!(dev.phys_reg(0), None);
+
+        // Without `ranges` on `/soc`, the blob of the other test is not translatable either.
+        let dt = DeviceTree::from_bytes(&TEST_DTB).unwrap();
+        let uart = dt.find_node("/soc/serial@7e201000").unwrap();
+        assert_eq!(uart.phys_reg(0), None);
+    }
+}
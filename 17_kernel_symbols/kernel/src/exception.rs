@@ -9,12 +9,22 @@
 mod arch_exception;
 
 pub mod asynchronous;
+pub mod syscall;
+
+use crate::{bsp, cpu::PerCpu, info};
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 //--------------------------------------------------------------------------------------------------
 // Architectural Public Reexports
 //--------------------------------------------------------------------------------------------------
 pub use arch_exception::{current_privilege_level, handling_init};
 
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const NUM_SYNC_EXCEPTION_CLASSES: usize = 4;
+
 //--------------------------------------------------------------------------------------------------
 // Public Definitions
 //--------------------------------------------------------------------------------------------------
@@ -29,6 +39,103 @@ pub enum PrivilegeLevel {
     Unknown,
 }
 
+/// Classes of synchronous exceptions that are counted, see [`print_stats()`].
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SyncExceptionClass {
+    DataAbort,
+    InstructionAbort,
+    SystemCall,
+    AlignmentFault,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_SYNC_EXCEPTIONS: [AtomicUsize; NUM_SYNC_EXCEPTION_CLASSES] = [
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+];
+
+/// Counted per core, so that the cores do not contend for the counters.
+static SYNC_EXCEPTION_COUNTS: PerCpu<[AtomicUsize; NUM_SYNC_EXCEPTION_CLASSES]> =
+    PerCpu::new([NO_SYNC_EXCEPTIONS; bsp::cpu::NUM_CORES]);
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl SyncExceptionClass {
+    const ALL: [Self; NUM_SYNC_EXCEPTION_CLASSES] = [
+        Self::DataAbort,
+        Self::InstructionAbort,
+        Self::SystemCall,
+        Self::AlignmentFault,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::DataAbort => "Data aborts",
+            Self::InstructionAbort => "Instruction aborts",
+            Self::SystemCall => "System calls",
+            Self::AlignmentFault => "Alignment faults",
+        }
+    }
+}
+
+/// Count a synchronous exception. Called by the architectural exception handlers.
+pub fn record_sync_exception(class: SyncExceptionClass) {
+    SYNC_EXCEPTION_COUNTS.get()[class as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Return the number of synchronous exceptions of a class since boot, summed over all cores.
+pub fn sync_exception_count(class: SyncExceptionClass) -> usize {
+    SYNC_EXCEPTION_COUNTS
+        .iter()
+        .map(|counts| counts[class as usize].load(Ordering::Relaxed))
+        .sum()
+}
+
+/// Run `f`, and panic unless exactly `expected` synchronous exceptions of `class` happened
+/// meanwhile.
+///
+/// The counts of all cores are compared, so other cores must not cause exceptions of the class at
+/// the same time.
+#[cfg(feature = "test_build")]
+#[track_caller]
+pub fn assert_exceptions<T>(
+    class: SyncExceptionClass,
+    expected: usize,
+    f: impl FnOnce() -> T,
+) -> T {
+    let before = sync_exception_count(class);
+    let ret = f();
+    let actual = sync_exception_count(class) - before;
+
+    assert_eq!(
+        actual, expected,
+        "Unexpected number of {:?} exceptions",
+        class
+    );
+
+    ret
+}
+
+/// Print the number of synchronous exceptions per class since boot.
+pub fn print_stats() {
+    for class in SyncExceptionClass::ALL {
+        info!(
+            "      {: <20} {}",
+            class.name(),
+            sync_exception_count(class)
+        );
+    }
+}
+
 //--------------------------------------------------------------------------------------------------
 // Testing
 //--------------------------------------------------------------------------------------------------
@@ -45,4 +152,28 @@ mod tests {
 
         assert!(level == PrivilegeLevel::Kernel)
     }
+
+    /// Check that recording an exception is reflected in the statistics.
+    #[kernel_test]
+    fn sync_exception_counting_works() {
+        let class = SyncExceptionClass::AlignmentFault;
+        let count = sync_exception_count(class);
+
+        record_sync_exception(class);
+        assert_eq!(sync_exception_count(class), count + 1);
+    }
+
+    /// Exceptions that happen while the closure runs are counted by the assertion.
+    #[kernel_test]
+    fn assert_exceptions_counts_the_closure() {
+        let class = SyncExceptionClass::AlignmentFault;
+
+        assert_exceptions(class, 0, || ());
+        let ret = assert_exceptions(class, 2, || {
+            record_sync_exception(class);
+            record_sync_exception(class);
+            42
+        });
+        assert_eq!(ret, 42);
+    }
 }
@@ -8,14 +8,19 @@
 #[path = "../_arch/aarch64/exception/asynchronous.rs"]
 mod arch_asynchronous;
 
-use core::{fmt, marker::PhantomData};
+use crate::{bsp, cpu::PerCpu};
+use core::{
+    fmt,
+    marker::PhantomData,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 //--------------------------------------------------------------------------------------------------
 // Architectural Public Reexports
 //--------------------------------------------------------------------------------------------------
 pub use arch_asynchronous::{
-    is_local_irq_masked, local_irq_mask, local_irq_mask_save, local_irq_restore, local_irq_unmask,
-    print_state,
+    is_local_irq_masked, local_fiq_unmask, local_irq_fiq_mask_save, local_irq_mask,
+    local_irq_mask_save, local_irq_restore, local_irq_unmask, print_state,
 };
 
 //--------------------------------------------------------------------------------------------------
@@ -71,6 +76,59 @@ pub mod interface {
         /// Enable an interrupt in the controller.
         fn enable(&self, irq_number: Self::IRQNumberType);
 
+        /// Set the priority of an interrupt. Lower values mean higher priority.
+        ///
+        /// A handler can only be preempted by interrupts of higher priority. Controllers that do
+        /// not support priorities return an error.
+        fn set_priority(
+            &self,
+            _irq_number: Self::IRQNumberType,
+            _priority: u8,
+        ) -> Result<(), &'static str> {
+            Err("Interrupt priorities not supported")
+        }
+
+        /// Deliver an interrupt to `target_core` instead of the core it is currently routed to.
+        ///
+        /// Controllers that can not route interrupts return an error.
+        fn set_target_core(
+            &self,
+            _irq_number: Self::IRQNumberType,
+            _target_core: usize,
+        ) -> Result<(), &'static str> {
+            Err("Interrupt routing not supported")
+        }
+
+        /// Deliver an interrupt as FIQ instead of IRQ.
+        ///
+        /// FIQs are not masked by `SpinLock`, so they preempt all IRQ handlers. A handler
+        /// must be registered for the interrupt first. It must protect its data with locks that
+        /// mask FIQs as well, e.g. `FIQSafeSpinLock`.
+        ///
+        /// Controllers that can not generate FIQs return an error.
+        fn route_to_fiq(&self, _irq_number: Self::IRQNumberType) -> Result<(), &'static str> {
+            Err("FIQs not supported")
+        }
+
+        /// Send an inter-processor interrupt carrying `msg` to `target_core`.
+        ///
+        /// Controllers that can not signal other cores return an error.
+        fn send_ipi(
+            &self,
+            _target_core: usize,
+            _msg: super::IPIMessage,
+        ) -> Result<(), &'static str> {
+            Err("Inter-processor interrupts not supported")
+        }
+
+        /// Check that the interrupt that the executing core would take next, if any, has a handler.
+        ///
+        /// Otherwise, taking it would end as a spurious interrupt or in a panic. Controllers that
+        /// can not look at pending interrupts without acknowledging them return `None`.
+        fn check_pending_irq(&self) -> Option<Result<(), &'static str>> {
+            None
+        }
+
         /// Handle pending interrupts.
         ///
         /// This function is called directly from the CPU's IRQ exception vector. On AArch64,
@@ -84,6 +142,16 @@ pub mod interface {
             ic: &super::IRQContext<'irq_context>,
         );
 
+        /// Handle a pending FIQ.
+        ///
+        /// This function is called directly from the CPU's FIQ exception vector.
+        #[allow(clippy::trivially_copy_pass_by_ref)]
+        fn handle_pending_fiq<'irq_context>(
+            &'irq_context self,
+            _ic: &super::IRQContext<'irq_context>,
+        ) {
+        }
+
         /// Print list of registered handlers.
         fn print_handler(&self);
     }
@@ -93,6 +161,22 @@ pub mod interface {
 #[derive(Copy, Clone)]
 pub struct IRQNumber<const MAX_INCLUSIVE: usize>(usize);
 
+/// An inter-processor interrupt message.
+///
+/// Each message is delivered as its own software-generated interrupt, so the receiving core handles
+/// it like any other IRQ, with the handler registered for the message's number.
+pub type IPIMessage = IRQNumber<15>;
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_IRQS: AtomicUsize = AtomicUsize::new(0);
+
+/// Counted per core, so that the cores do not contend for the counters.
+static IRQ_COUNTS: PerCpu<AtomicUsize> = PerCpu::new([NO_IRQS; bsp::cpu::NUM_CORES]);
+
 //--------------------------------------------------------------------------------------------------
 // Public Code
 //--------------------------------------------------------------------------------------------------
@@ -122,6 +206,13 @@ impl<const MAX_INCLUSIVE: usize> IRQNumber<{ MAX_INCLUSIVE }> {
         Self(number)
     }
 
+    /// Creates a new instance if number <= MAX_INCLUSIVE, or returns `None` otherwise.
+    ///
+    /// Useful for numbers that are only known at runtime, e.g. from the device tree.
+    pub fn checked_new(number: usize) -> Option<Self> {
+        (number <= MAX_INCLUSIVE).then(|| Self(number))
+    }
+
     /// Return the wrapped number.
     pub const fn get(self) -> usize {
         self.0
@@ -134,6 +225,23 @@ impl<const MAX_INCLUSIVE: usize> fmt::Display for IRQNumber<{ MAX_INCLUSIVE }> {
     }
 }
 
+/// Executes the provided closure while IRQs and FIQs are masked on the executing core.
+///
+/// While the function temporarily changes the HW state of the executing core, it restores it to the
+/// previous state before returning, so this is deemed safe.
+#[inline(always)]
+pub fn exec_with_irq_fiq_masked<T>(f: impl FnOnce() -> T) -> T {
+    let ret: T;
+
+    unsafe {
+        let saved = local_irq_fiq_mask_save();
+        ret = f();
+        local_irq_restore(saved);
+    }
+
+    ret
+}
+
 /// Executes the provided closure while IRQs are masked on the executing core.
 ///
 /// While the function temporarily changes the HW state of the executing core, it restores it to the
@@ -150,3 +258,25 @@ pub fn exec_with_irq_masked<T>(f: impl FnOnce() -> T) -> T {
 
     ret
 }
+
+/// Count an IRQ exception. Called by the architectural exception handlers.
+pub fn record_irq() {
+    IRQ_COUNTS.get().fetch_add(1, Ordering::Relaxed);
+}
+
+/// Return the number of IRQ exceptions since boot, summed over all cores.
+///
+/// An exception can handle several pending IRQs, so this is a lower bound of the handled IRQs.
+pub fn irq_count() -> usize {
+    IRQ_COUNTS
+        .iter()
+        .map(|count| count.load(Ordering::Relaxed))
+        .sum()
+}
+
+/// Send an inter-processor interrupt to the given core.
+pub fn send_ipi(target_core: usize, msg: IPIMessage) -> Result<(), &'static str> {
+    use interface::IRQManager;
+
+    bsp::exception::asynchronous::irq_manager().send_ipi(target_core, msg)
+}
@@ -0,0 +1,364 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! System call dispatch.
+//!
+//! The calling convention follows the one of Linux on AArch64:
+//!
+//! - `x8` holds the system call number.
+//! - `x0` to `x5` hold the arguments.
+//! - The return value is placed in `x0`. Negative values are errors, see [`Error`].
+
+use crate::{bsp, console, cpu, info, memory, scheduler, task, time};
+use core::time::Duration;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+type SyscallFn = fn(caller: Caller, args: &Args) -> Result<u64, Error>;
+
+/// The syscall table. Indexed by the system call number.
+static SYSCALL_TABLE: [SyscallFn; NUM_SYSCALLS] = [
+    sys_write,
+    sys_sleep,
+    sys_exit,
+    sys_notify_handler,
+    sys_notify_mask,
+    sys_notify_return,
+    sys_set_wall_clock,
+    sys_alarm,
+];
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Number of arguments that can be passed to a system call.
+pub const NUM_ARGS: usize = 6;
+
+/// System call arguments, as extracted from the caller's registers.
+pub type Args = [u64; NUM_ARGS];
+
+/// System call numbers.
+pub mod number {
+    /// `write(buf: *const u8, len: usize) -> usize`
+    ///
+    /// Write a buffer to the console. Returns the number of bytes written.
+    pub const WRITE: u64 = 0;
+
+    /// `sleep(duration_us: u64)`
+    ///
+    /// Pause execution of the caller for the given number of microseconds. Fails with
+    /// `WouldBlock` if the duration is not zero and the scheduler is not running yet.
+    pub const SLEEP: u64 = 1;
+
+    /// `exit(code: u64) -> !`
+    ///
+    /// Stop the caller. A user task returns control to the kernel.
+    pub const EXIT: u64 = 2;
+
+    /// `notify_handler(handler: fn(notification: u32))`
+    ///
+    /// Register the handler for notifications from the kernel. Zero unregisters it.
+    pub const NOTIFY_HANDLER: u64 = 3;
+
+    /// `notify_mask(mask: u32) -> u32`
+    ///
+    /// Set the mask of blocked notifications. Returns the previous mask.
+    pub const NOTIFY_MASK: u64 = 4;
+
+    /// `notify_return() -> !`
+    ///
+    /// Finish a notification handler and continue where the caller was interrupted.
+    pub const NOTIFY_RETURN: u64 = 5;
+
+    /// `set_wall_clock(unix_time_us: u64)`
+    ///
+    /// Set the wall clock to the given number of microseconds since the Unix epoch. Only kernel
+    /// code may set it.
+    pub const SET_WALL_CLOCK: u64 = 6;
+
+    /// `alarm(deadline_us: u64, mode: u64) -> u64`
+    ///
+    /// Act when the uptime reaches the given number of microseconds, as selected by one of the
+    /// [`alarm_mode`](super::alarm_mode)s. Returns the uptime in microseconds when the call
+    /// returns, which tasks can use to compute their next deadline.
+    pub const ALARM: u64 = 7;
+}
+
+/// Modes of the `ALARM` system call.
+pub mod alarm_mode {
+    /// Block the caller until the deadline. Fails with `WouldBlock` if the deadline is in the
+    /// future and the scheduler is not running yet.
+    pub const WAIT: u64 = 0;
+
+    /// Return right away, and send the `TimerExpired` notification at the deadline. Replaces an
+    /// earlier alarm.
+    pub const NOTIFY: u64 = 1;
+
+    /// Cancel a pending notification. The deadline is ignored.
+    pub const CANCEL: u64 = 2;
+}
+
+/// Number of implemented system calls.
+pub const NUM_SYSCALLS: usize = 8;
+
+/// The privilege level that a system call was made from.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Caller {
+    /// Kernel code, e.g. a kernel thread.
+    Kernel,
+
+    /// A user task.
+    User,
+}
+
+/// System call errors. Returned to the caller as the negated value.
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(i64)]
+pub enum Error {
+    NoSuchSyscall = 1,
+    InvalidArgument = 2,
+    BadAddress = 3,
+    OutOfResources = 4,
+    PermissionDenied = 5,
+    WouldBlock = 6,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// Check that all pages of a caller-provided buffer are mapped, and accessible to the caller.
+///
+/// System calls run in the kernel, so they could read kernel memory on behalf of a user task.
+/// Hence, a user task's buffer must only consist of pages that are mapped for user space.
+fn validate_buffer(caller: Caller, start: u64, len: u64) -> Result<(), Error> {
+    use memory::{mmu::PageAddress, Address, Virtual};
+
+    if start == 0 {
+        return Err(Error::BadAddress);
+    }
+
+    let end = start.checked_add(len).ok_or(Error::BadAddress)? as usize;
+    let mut page = Address::<Virtual>::new(start as usize).align_down_page();
+
+    while page.as_usize() < end {
+        let attributes = memory::mmu::try_kernel_page_attributes(PageAddress::from(page))
+            .map_err(|_| Error::BadAddress)?;
+
+        if caller == Caller::User && !attributes.acc_perms.is_user() {
+            return Err(Error::InvalidArgument);
+        }
+
+        page = page + bsp::memory::mmu::KernelGranule::SIZE;
+    }
+
+    Ok(())
+}
+
+fn sys_write(caller: Caller, args: &Args) -> Result<u64, Error> {
+    use console::interface::Write;
+
+    let (start, len) = (args[0], args[1]);
+    validate_buffer(caller, start, len)?;
+
+    let buf = unsafe { core::slice::from_raw_parts(start as *const u8, len as usize) };
+    let console = console::console();
+
+    for b in buf {
+        console.write_char(*b as char);
+    }
+
+    Ok(len)
+}
+
+fn sys_sleep(_caller: Caller, args: &Args) -> Result<u64, Error> {
+    use time::interface::TimeManager;
+
+    let duration = Duration::from_micros(args[0]);
+    if duration.is_zero() {
+        return Ok(0);
+    }
+
+    // Without the scheduler, sleeping would spin with IRQs masked.
+    if !scheduler::is_initialized() {
+        return Err(Error::WouldBlock);
+    }
+
+    // System calls run with IRQs masked, but hold no locks. So the task's thread can sleep.
+    scheduler::sleep_until(time::time_manager().uptime().saturating_add(duration));
+
+    Ok(0)
+}
+
+fn sys_exit(_caller: Caller, args: &Args) -> Result<u64, Error> {
+    // Returns only if the caller is not a user task.
+    unsafe { task::exit_current(args[0]) };
+
+    info!("System call: exit with code {}", args[0]);
+
+    cpu::wait_forever()
+}
+
+fn sys_notify_handler(_caller: Caller, args: &Args) -> Result<u64, Error> {
+    task::set_notification_handler(memory::Address::new(args[0] as usize))
+        .map_err(|_| Error::InvalidArgument)?;
+
+    Ok(0)
+}
+
+fn sys_notify_mask(_caller: Caller, args: &Args) -> Result<u64, Error> {
+    let mask = u32::try_from(args[0]).map_err(|_| Error::InvalidArgument)?;
+
+    Ok(task::set_notification_mask(mask) as u64)
+}
+
+/// The caller's state is restored by the architectural exception code when this succeeds.
+fn sys_notify_return(_caller: Caller, _args: &Args) -> Result<u64, Error> {
+    task::notification_handler_done().map_err(|_| Error::InvalidArgument)?;
+
+    Ok(0)
+}
+
+fn sys_set_wall_clock(caller: Caller, args: &Args) -> Result<u64, Error> {
+    use time::interface::TimeManager;
+
+    // The wall clock is shared by the whole system.
+    if caller == Caller::User {
+        return Err(Error::PermissionDenied);
+    }
+
+    time::time_manager().set_wall_clock(Duration::from_micros(args[0]));
+
+    Ok(0)
+}
+
+fn sys_alarm(_caller: Caller, args: &Args) -> Result<u64, Error> {
+    use time::interface::TimeManager;
+
+    let deadline = Duration::from_micros(args[0]);
+
+    match args[1] {
+        alarm_mode::WAIT => {
+            // Without the scheduler, waiting would spin with IRQs masked.
+            if time::time_manager().uptime() < deadline {
+                if !scheduler::is_initialized() {
+                    return Err(Error::WouldBlock);
+                }
+
+                scheduler::sleep_until(deadline);
+            }
+        }
+        alarm_mode::NOTIFY => {
+            task::set_alarm(Some(deadline)).map_err(|_| Error::OutOfResources)?;
+        }
+        alarm_mode::CANCEL => {
+            let _ = task::set_alarm(None);
+        }
+        _ => return Err(Error::InvalidArgument),
+    }
+
+    Ok(time::time_manager().uptime().as_micros() as u64)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Execute a system call and return the value for the caller's `x0`.
+pub fn dispatch(caller: Caller, number: u64, args: &Args) -> u64 {
+    let result = match SYSCALL_TABLE.get(number as usize) {
+        None => Err(Error::NoSuchSyscall),
+        Some(syscall) => syscall(caller, args),
+    };
+
+    match result {
+        Ok(ret) => ret,
+        Err(e) => (-(e as i64)) as u64,
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
+
+    /// Check the table lookup and error encoding.
+    #[kernel_test]
+    fn dispatch_works() {
+        let mut args: Args = [0; NUM_ARGS];
+        assert_eq!(dispatch(Caller::Kernel, number::SLEEP, &args), 0);
+
+        assert_eq!(
+            dispatch(Caller::Kernel, NUM_SYSCALLS as u64, &args) as i64,
+            -1
+        );
+
+        // An alarm in the past returns right away, with the current uptime.
+        args[1] = alarm_mode::WAIT;
+        assert!((dispatch(Caller::Kernel, number::ALARM, &args) as i64) > 0);
+        args[1] = alarm_mode::CANCEL + 1;
+        assert_eq!(dispatch(Caller::Kernel, number::ALARM, &args) as i64, -2);
+
+        // Null buffer.
+        args[1] = 1;
+        assert_eq!(dispatch(Caller::Kernel, number::WRITE, &args) as i64, -3);
+
+        // Zero-length write from a valid buffer.
+        let buf = [0_u8; 1];
+        args[0] = buf.as_ptr() as u64;
+        args[1] = 0;
+        assert_eq!(dispatch(Caller::Kernel, number::WRITE, &args), 0);
+    }
+
+    /// A user task must not pass kernel memory, even though the kernel itself may.
+    #[kernel_test]
+    fn user_write_rejects_kernel_buffer() {
+        let buf = [b'x'; 1];
+        let args: Args = [buf.as_ptr() as u64, 1, 0, 0, 0, 0];
+
+        assert_eq!(validate_buffer(Caller::Kernel, args[0], args[1]), Ok(()));
+        assert_eq!(
+            validate_buffer(Caller::User, args[0], args[1]),
+            Err(Error::InvalidArgument)
+        );
+        assert_eq!(
+            dispatch(Caller::User, number::WRITE, &args) as i64,
+            -(Error::InvalidArgument as i64)
+        );
+    }
+
+    /// A notification handler must be user-executable code. Zero unregisters the handler.
+    #[kernel_test]
+    fn notify_handler_rejects_kernel_code() {
+        let mut args: Args = [0; NUM_ARGS];
+
+        args[0] = dispatch as *const () as u64;
+        assert_eq!(
+            dispatch(Caller::User, number::NOTIFY_HANDLER, &args) as i64,
+            -(Error::InvalidArgument as i64)
+        );
+
+        args[0] = 0;
+        assert_eq!(dispatch(Caller::User, number::NOTIFY_HANDLER, &args), 0);
+    }
+
+    /// Only kernel code may set the wall clock.
+    #[kernel_test]
+    fn user_can_not_set_wall_clock() {
+        let args: Args = [0; NUM_ARGS];
+
+        assert_eq!(
+            dispatch(Caller::User, number::SET_WALL_CLOCK, &args) as i64,
+            -(Error::PermissionDenied as i64)
+        );
+    }
+}
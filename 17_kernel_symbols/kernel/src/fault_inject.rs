@@ -0,0 +1,176 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Fault injection.
+//!
+//! Enabled with the `fault_inject` feature. Error paths that real hardware rarely takes, e.g. a
+//! firmware that never answers, can be forced with it, so that tests can execute them. Each kind of
+//! [`Fault`] has a hook in the code that it affects:
+//!
+//! - [`Fault::MmioRead`]: Drivers pass selected register reads through [`mmio_read()`], which
+//!   returns junk instead of the read value.
+//! - [`Fault::Alloc`]: The page allocators of `memory::mmu` fail the allocation.
+//! - [`Fault::Timeout`]: `time::with_timeout()` times out without polling.
+//!
+//! A fault is injected into the Nth call of its hook after [`inject()`], and only into that one.
+//!
+//! ```ignore
+//! let result = fault_inject::with_fault(Fault::Timeout, 1, || mailbox.board_revision());
+//! assert!(result.is_err());
+//! ```
+
+use core::{
+    num::NonZeroUsize,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const NUM_FAULTS: usize = 3;
+
+/// What injected MMIO reads return.
+const MMIO_JUNK: u32 = 0xdead_beef;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The kinds of faults that can be injected.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Fault {
+    /// A register read returns junk.
+    MmioRead,
+
+    /// A page allocation fails.
+    Alloc,
+
+    /// A timeout fires.
+    Timeout,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+#[allow(clippy::declare_interior_mutable_const)]
+const DISARMED: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of hook calls until the fault is injected, or zero if none is armed.
+static COUNTDOWNS: [AtomicUsize; NUM_FAULTS] = [DISARMED; NUM_FAULTS];
+
+/// The number of faults injected since boot.
+static INJECTED: [AtomicUsize; NUM_FAULTS] = [DISARMED; NUM_FAULTS];
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Inject `fault` into the `nth` call of its hook, counting from 1.
+pub fn inject(fault: Fault, nth: NonZeroUsize) {
+    COUNTDOWNS[fault as usize].store(nth.get(), Ordering::Relaxed);
+}
+
+/// Disarm `fault`, if it was not injected yet.
+pub fn clear(fault: Fault) {
+    COUNTDOWNS[fault as usize].store(0, Ordering::Relaxed);
+}
+
+/// Run `f` with `fault` injected into the `nth` call of its hook, counting from 1.
+///
+/// # Panics
+///
+/// - If `nth` is zero.
+pub fn with_fault<T>(fault: Fault, nth: usize, f: impl FnOnce() -> T) -> T {
+    inject(
+        fault,
+        NonZeroUsize::new(nth).expect("Faults are counted from 1"),
+    );
+    let ret = f();
+    clear(fault);
+
+    ret
+}
+
+/// Return the number of times `fault` was injected since boot.
+pub fn injected(fault: Fault) -> usize {
+    INJECTED[fault as usize].load(Ordering::Relaxed)
+}
+
+/// The hook of `fault`. Returns whether the caller must fail.
+pub fn should_fail(fault: Fault) -> bool {
+    let result =
+        COUNTDOWNS[fault as usize]
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |x| x.checked_sub(1));
+
+    // The fault is due when the countdown went from 1 to 0.
+    if result != Ok(1) {
+        return false;
+    }
+
+    INJECTED[fault as usize].fetch_add(1, Ordering::Relaxed);
+    true
+}
+
+/// The hook of [`Fault::MmioRead`]. Returns `value`, or junk if the fault is due.
+pub fn mmio_read(value: u32) -> u32 {
+    if should_fail(Fault::MmioRead) {
+        return MMIO_JUNK;
+    }
+
+    value
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        memory::{self, mmu::MMIODescriptor, Address},
+        time,
+    };
+    use core::time::Duration;
+    use test_macros::kernel_test;
+
+    /// Only the Nth call of a hook fails.
+    #[kernel_test]
+    fn fault_is_injected_into_nth_call() {
+        let before = injected(Fault::MmioRead);
+
+        let values = with_fault(Fault::MmioRead, 3, || {
+            [mmio_read(1), mmio_read(2), mmio_read(3), mmio_read(4)]
+        });
+
+        assert_eq!(values, [1, 2, MMIO_JUNK, 4]);
+        assert_eq!(injected(Fault::MmioRead), before + 1);
+        assert!(!should_fail(Fault::MmioRead));
+    }
+
+    /// An injected timeout fires even though the condition holds.
+    #[kernel_test]
+    fn timeout_is_injected() {
+        let result = with_fault(Fault::Timeout, 1, || {
+            time::with_timeout(Duration::from_secs(1), || Some(()))
+        });
+
+        assert!(result.is_err());
+        assert!(time::with_timeout(Duration::from_secs(1), || Some(())).is_ok());
+    }
+
+    /// A failed allocation makes MMIO mapping fail.
+    #[kernel_test]
+    fn alloc_failure_fails_mmio_mapping() {
+        let descriptor = MMIODescriptor::new(Address::new(0), 0x1000);
+
+        let result = with_fault(Fault::Alloc, 1, || unsafe {
+            memory::mmu::kernel_map_mmio("Fault injection", &descriptor)
+        });
+
+        assert_eq!(result, Err("Injected allocation failure"));
+    }
+}
@@ -0,0 +1,598 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! GDB remote stub.
+//!
+//! Lets GDB debug the kernel over the console UART, using the GDB remote serial protocol. Build the
+//! kernel with `GDBSTUB=1`, stop it with the monitor's `gdb` command or a call to [`breakpoint()`],
+//! and attach from the host:
+//!
+//! ```console
+//! $ gdb-multiarch -ex 'target remote /dev/ttyUSB0' <kernel ELF>
+//! ```
+//!
+//! Registers and memory can be read and written, breakpoints set in the kernel's code, and
+//! execution continued or single stepped.
+//!
+//! # Limitations
+//!
+//! - Only the core that stopped is halted. The other cores keep running, and may write to or read
+//!   from the console while GDB is attached.
+//! - Only mapped normal memory is accessible. Device memory is not, because reads can have side
+//!   effects.
+//! - The stack pointer can not be changed.
+
+#[cfg(target_arch = "aarch64")]
+#[path = "_arch/aarch64/gdb.rs"]
+mod arch_gdb;
+
+use crate::{
+    console, cpu,
+    memory::{
+        mmu::{self, AccessPermissions, MemAttributes, PageAddress},
+        Address, Virtual,
+    },
+    synchronization::{interface::Mutex, SpinLock},
+};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+//--------------------------------------------------------------------------------------------------
+// Architectural Public Reexports
+//--------------------------------------------------------------------------------------------------
+pub use arch_gdb::{breakpoint, Registers};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Maximum size of the data of a packet, in either direction. Announced to GDB.
+const PACKET_SIZE: usize = 0x400;
+
+const MAX_BREAKPOINTS: usize = 32;
+
+/// Signal number of the stop reply, SIGTRAP.
+const STOP_REPLY: &str = "S05";
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+#[derive(Copy, Clone)]
+struct Breakpoint {
+    addr: Address<Virtual>,
+    original: u32,
+}
+
+/// A packet that is being assembled for sending.
+struct Reply {
+    buf: [u8; PACKET_SIZE],
+    len: usize,
+}
+
+struct Stub {
+    breakpoints: [Option<Breakpoint>; MAX_BREAKPOINTS],
+    reply: Reply,
+
+    /// Whether GDB is attached, and expects a stop reply when the kernel stops.
+    attached: bool,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// How to resume after a stop.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Resume {
+    /// Continue until the next breakpoint.
+    Continue,
+
+    /// Execute a single instruction, and stop again.
+    Step,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Cores that stop at the same time wait for their turn.
+static STUB: SpinLock<Stub> = SpinLock::new(Stub {
+    breakpoints: [None; MAX_BREAKPOINTS],
+    reply: Reply::new(),
+    attached: false,
+});
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, &b| sum.wrapping_add(b))
+}
+
+fn hex_digit(c: u8) -> Option<u8> {
+    (c as char).to_digit(16).map(|d| d as u8)
+}
+
+/// Parse a big-endian hex number, as used for addresses and lengths.
+fn parse_hex(s: &[u8]) -> Option<u64> {
+    if s.is_empty() || s.len() > 16 {
+        return None;
+    }
+
+    s.iter()
+        .try_fold(0, |value, &c| Some((value << 4) | u64::from(hex_digit(c)?)))
+}
+
+/// Decode pairs of hex digits into `out`. `s` must be exactly twice as long as `out`.
+fn decode_hex(s: &[u8], out: &mut [u8]) -> Option<()> {
+    if s.len() != out.len() * 2 {
+        return None;
+    }
+
+    for (byte, pair) in out.iter_mut().zip(s.chunks_exact(2)) {
+        *byte = (hex_digit(pair[0])? << 4) | hex_digit(pair[1])?;
+    }
+
+    Some(())
+}
+
+/// Decode a register value, which GDB sends in the target's byte order.
+fn decode_register(s: &[u8]) -> Option<u64> {
+    let mut bytes = [0_u8; 8];
+    decode_hex(s, bytes.get_mut(..s.len() / 2)?)?;
+
+    Some(u64::from_le_bytes(bytes))
+}
+
+/// Split `s` at the first `separator`.
+fn split_at_byte(s: &[u8], separator: u8) -> Option<(&[u8], &[u8])> {
+    let i = s.iter().position(|&c| c == separator)?;
+
+    Some((&s[..i], &s[i + 1..]))
+}
+
+/// Parse `addr,len`.
+fn parse_addr_len(s: &[u8]) -> Option<(usize, usize)> {
+    let (addr, len) = split_at_byte(s, b',')?;
+
+    Some((parse_hex(addr)? as usize, parse_hex(len)? as usize))
+}
+
+/// Check that `len` bytes at `addr` are mapped normal memory, and writeable if requested.
+fn check_accessible(addr: usize, len: usize, write: bool) -> Option<()> {
+    let end = addr.checked_add(len)?;
+    let mut page = Address::<Virtual>::new(addr).align_down_page();
+
+    while page.as_usize() < end {
+        let attributes = mmu::try_kernel_page_attributes(PageAddress::from(page)).ok()?;
+
+        if !matches!(attributes.mem_attributes, MemAttributes::CacheableDRAM) {
+            return None;
+        }
+        if write && attributes.acc_perms != AccessPermissions::ReadWrite {
+            return None;
+        }
+
+        page = PageAddress::from(page).checked_offset(1)?.into_inner();
+    }
+
+    Some(())
+}
+
+fn read_byte() -> u8 {
+    use console::interface::Read;
+
+    let mut byte = [0_u8];
+    console::console().read_exact(&mut byte);
+
+    byte[0]
+}
+
+/// Receive the next packet with a valid checksum into `buf`. Returns the length of its data.
+///
+/// Packets that are corrupted or too long are requested again.
+fn receive_packet(buf: &mut [u8]) -> usize {
+    use console::interface::Write;
+
+    loop {
+        // Skip acks and interrupt requests until the start of a packet.
+        while read_byte() != b'$' {}
+
+        let mut len = 0;
+        let mut overflow = false;
+        loop {
+            let c = read_byte();
+            if c == b'#' {
+                break;
+            }
+
+            match buf.get_mut(len) {
+                None => overflow = true,
+                Some(x) => *x = c,
+            }
+            len += 1;
+        }
+
+        let received = [read_byte(), read_byte()];
+        let mut expected = [0_u8];
+        let valid = !overflow
+            && decode_hex(&received, &mut expected).is_some()
+            && checksum(&buf[..len]) == expected[0];
+
+        if valid {
+            console::console().write_bytes(b"+");
+            return len;
+        }
+
+        console::console().write_bytes(b"-");
+    }
+}
+
+/// Send a packet, and repeat it until GDB acknowledges it.
+fn send_packet(data: &[u8]) {
+    use console::interface::Write;
+
+    let sum = usize::from(checksum(data));
+    let trailer = [b'#', HEX_DIGITS[sum >> 4], HEX_DIGITS[sum & 0xf]];
+
+    loop {
+        console::console().write_bytes(b"$");
+        console::console().write_bytes(data);
+        console::console().write_bytes(&trailer);
+
+        if read_byte() != b'-' {
+            break;
+        }
+    }
+}
+
+impl Reply {
+    const fn new() -> Self {
+        Self {
+            buf: [0; PACKET_SIZE],
+            len: 0,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Append bytes. Bytes that do not fit are dropped; callers limit the size of their replies.
+    fn push_bytes(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            if let Some(x) = self.buf.get_mut(self.len) {
+                *x = b;
+                self.len += 1;
+            }
+        }
+    }
+
+    fn push_str(&mut self, s: &str) {
+        self.push_bytes(s.as_bytes());
+    }
+
+    fn push_hex(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.push_bytes(&[
+                HEX_DIGITS[usize::from(b >> 4)],
+                HEX_DIGITS[usize::from(b & 0xf)],
+            ]);
+        }
+    }
+
+    fn push_register(&mut self, regs: &Registers, n: usize) -> Option<()> {
+        let (value, size) = regs.get(n)?;
+        self.push_hex(&value.to_le_bytes()[..size]);
+
+        Some(())
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+impl Stub {
+    fn insert_breakpoint(&mut self, addr: Address<Virtual>) -> Result<(), &'static str> {
+        if self.breakpoints.iter().flatten().any(|bp| bp.addr == addr) {
+            return Ok(());
+        }
+
+        let slot = self
+            .breakpoints
+            .iter_mut()
+            .find(|bp| bp.is_none())
+            .ok_or("No free breakpoint slot")?;
+
+        let original = cpu::code_patch::write_instruction(addr, arch_gdb::BREAKPOINT_INSTRUCTION)?;
+        *slot = Some(Breakpoint { addr, original });
+
+        Ok(())
+    }
+
+    fn remove_breakpoint(&mut self, addr: Address<Virtual>) -> Result<(), &'static str> {
+        let slot = self
+            .breakpoints
+            .iter_mut()
+            .find(|bp| matches!(bp, Some(bp) if bp.addr == addr))
+            .ok_or("No breakpoint at this address")?;
+
+        if let Some(bp) = slot.take() {
+            cpu::code_patch::write_instruction(bp.addr, bp.original)?;
+        }
+
+        Ok(())
+    }
+
+    fn remove_all_breakpoints(&mut self) {
+        for bp in self.breakpoints.iter_mut().filter_map(|bp| bp.take()) {
+            let _ = cpu::code_patch::write_instruction(bp.addr, bp.original);
+        }
+    }
+
+    fn is_breakpoint(&self, addr: Address<Virtual>) -> bool {
+        self.breakpoints.iter().flatten().any(|bp| bp.addr == addr)
+    }
+
+    /// Handle the data of a `Z0` or `z0` packet: `addr,kind`.
+    fn handle_breakpoint_packet(&mut self, args: &[u8], insert: bool) -> Option<()> {
+        let (addr, _kind) = parse_addr_len(args)?;
+        let addr = Address::new(addr);
+
+        let result = if insert {
+            self.insert_breakpoint(addr)
+        } else {
+            self.remove_breakpoint(addr)
+        };
+
+        result.ok()
+    }
+
+    fn read_registers(&mut self, regs: &Registers) {
+        for n in 0..arch_gdb::NUM_REGISTERS {
+            let _ = self.reply.push_register(regs, n);
+        }
+    }
+
+    fn write_registers(regs: &mut Registers, mut data: &[u8]) -> Option<()> {
+        for n in 0..arch_gdb::NUM_REGISTERS {
+            let (current, size) = regs.get(n)?;
+            let value = decode_register(data.get(..size * 2)?)?;
+            data = &data[size * 2..];
+
+            // GDB sends all registers back, including the ones that can not be changed.
+            if value != current && !regs.set(n, value) {
+                return None;
+            }
+        }
+
+        Some(())
+    }
+
+    /// Handle the data of a `P` packet: `n=value`.
+    fn write_register(regs: &mut Registers, args: &[u8]) -> Option<()> {
+        let (n, value) = split_at_byte(args, b'=')?;
+        let n = parse_hex(n)? as usize;
+        let (_, size) = regs.get(n)?;
+
+        if value.len() != size * 2 {
+            return None;
+        }
+
+        regs.set(n, decode_register(value)?).then(|| ())
+    }
+
+    fn read_memory(&mut self, args: &[u8]) -> Option<()> {
+        let (addr, len) = parse_addr_len(args)?;
+        if len > PACKET_SIZE / 2 {
+            return None;
+        }
+        check_accessible(addr, len, false)?;
+
+        for i in 0..len {
+            let byte = unsafe { core::ptr::read_volatile((addr + i) as *const u8) };
+            self.reply.push_hex(&[byte]);
+        }
+
+        Some(())
+    }
+
+    /// Handle the data of an `M` packet: `addr,len:data`.
+    fn write_memory(args: &[u8]) -> Option<()> {
+        let (addr_len, data) = split_at_byte(args, b':')?;
+        let (addr, len) = parse_addr_len(addr_len)?;
+        if data.len() != len * 2 {
+            return None;
+        }
+        check_accessible(addr, len, true)?;
+
+        for (i, pair) in data.chunks_exact(2).enumerate() {
+            let mut byte = [0_u8];
+            decode_hex(pair, &mut byte)?;
+            unsafe { core::ptr::write_volatile((addr + i) as *mut u8, byte[0]) };
+        }
+
+        Some(())
+    }
+
+    /// Handle the optional address argument of `c` and `s`.
+    fn resume_at(regs: &mut Registers, args: &[u8]) -> Option<()> {
+        if !args.is_empty() {
+            regs.pc = parse_hex(args)?;
+        }
+
+        Some(())
+    }
+
+    /// Handle a packet, and prepare the reply.
+    ///
+    /// Returns how to resume, if the packet ends the stop.
+    fn handle_packet(&mut self, regs: &mut Registers, packet: &[u8]) -> Option<Resume> {
+        self.reply.clear();
+        let (command, args) = match packet.split_first() {
+            None => return None,
+            Some((&c, args)) => (c, args),
+        };
+
+        let result = match command {
+            b'?' => {
+                self.reply.push_str(STOP_REPLY);
+                return None;
+            }
+            b'g' => {
+                self.read_registers(regs);
+                return None;
+            }
+            b'G' => Self::write_registers(regs, args),
+            b'p' => {
+                let n = parse_hex(args).map(|n| n as usize);
+                if n.and_then(|n| self.reply.push_register(regs, n)).is_none() {
+                    self.reply.clear();
+                    self.reply.push_str("E01");
+                }
+                return None;
+            }
+            b'P' => Self::write_register(regs, args),
+            b'm' => {
+                if self.read_memory(args).is_none() {
+                    self.reply.clear();
+                    self.reply.push_str("E01");
+                }
+                return None;
+            }
+            b'M' => Self::write_memory(args),
+            b'Z' | b'z' => match args.strip_prefix(b"0,") {
+                // Only software breakpoints are supported. An empty reply tells GDB so.
+                None => return None,
+                Some(args) => self.handle_breakpoint_packet(args, command == b'Z'),
+            },
+            b'c' | b's' => {
+                if Self::resume_at(regs, args).is_none() {
+                    self.reply.push_str("E01");
+                    return None;
+                }
+
+                return Some(if command == b's' {
+                    Resume::Step
+                } else {
+                    Resume::Continue
+                });
+            }
+            b'D' | b'k' => {
+                self.remove_all_breakpoints();
+                self.attached = false;
+                if command == b'D' {
+                    self.reply.push_str("OK");
+                }
+
+                return Some(Resume::Continue);
+            }
+            b'H' => Some(()),
+            b'q' if packet.starts_with(b"qSupported") => {
+                self.reply.push_str("PacketSize=400");
+                return None;
+            }
+            b'q' if packet == b"qAttached" => {
+                self.reply.push_str("1");
+                return None;
+            }
+            // Unsupported packets get an empty reply.
+            _ => return None,
+        };
+
+        self.reply
+            .push_str(if result.is_some() { "OK" } else { "E01" });
+        None
+    }
+
+    fn run(&mut self, regs: &mut Registers) -> Resume {
+        // A compiled-in `BRK` would be hit again right away. Step over it, unlike over the
+        // breakpoints of GDB, which GDB removes before it resumes.
+        let pc = Address::new(regs.pc as usize);
+        if !self.is_breakpoint(pc)
+            && matches!(cpu::code_patch::read_instruction(pc), Ok(i) if arch_gdb::is_breakpoint_instruction(i))
+        {
+            regs.skip_instruction();
+        }
+
+        if self.attached {
+            send_packet(STOP_REPLY.as_bytes());
+        }
+
+        let mut packet = [0_u8; PACKET_SIZE];
+        loop {
+            let len = receive_packet(&mut packet);
+            self.attached = true;
+
+            let resume = self.handle_packet(regs, &packet[..len]);
+
+            // `c` and `s` are answered by the stop reply of the next stop, and `k` not at all.
+            if resume.is_none() || self.reply.len > 0 {
+                send_packet(self.reply.as_bytes());
+            }
+
+            if let Some(resume) = resume {
+                return resume;
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Enable the stub, so that `BRK` instructions in kernel code stop in it.
+///
+/// # Safety
+///
+/// - Must only be called during kernel init.
+pub unsafe fn init() -> Result<(), &'static str> {
+    cpu::code_patch::init()?;
+    ENABLED.store(true, Ordering::Relaxed);
+
+    Ok(())
+}
+
+/// Return whether the stub handles `BRK` instructions.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Talk to GDB until it resumes execution. Called by the exception handler of `BRK` and of the
+/// software step, with the registers of the stopped code.
+pub fn handle_stop(regs: &mut Registers) -> Resume {
+    STUB.lock(|stub| stub.run(regs))
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
+
+    /// Hex numbers, hex data and checksums must be parsed as the protocol defines them.
+    #[kernel_test]
+    fn packet_fields_are_parsed() {
+        assert_eq!(checksum(b"OK"), 0x9a);
+        assert_eq!(parse_addr_len(b"ffff0000,1f"), Some((0xffff_0000, 0x1f)));
+        assert_eq!(parse_hex(b""), None);
+        assert_eq!(parse_hex(b"12g"), None);
+        assert_eq!(decode_register(b"3412"), Some(0x1234));
+
+        let mut out = [0_u8; 2];
+        assert_eq!(decode_hex(b"a0B1", &mut out), Some(()));
+        assert_eq!(out, [0xa0, 0xb1]);
+        assert_eq!(decode_hex(b"a0b", &mut out), None);
+
+        let mut reply = Reply::new();
+        reply.push_hex(&[0x0f, 0xa0]);
+        assert_eq!(reply.as_bytes(), b"0fa0");
+    }
+}
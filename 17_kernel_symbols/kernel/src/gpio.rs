@@ -0,0 +1,101 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! General purpose I/O.
+//!
+//! Pins are handed out by the BSP. A pin can only be owned by a single user at a time, and it is
+//! released again when the owning object is dropped.
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// GPIO interfaces.
+pub mod interface {
+    use super::*;
+
+    /// A pin that is configured as an output.
+    pub trait OutputPin {
+        /// Drive the pin to the given level.
+        fn set(&mut self, level: Level);
+
+        /// The level the pin is currently driven to.
+        fn level(&self) -> Level;
+
+        /// Drive the pin high.
+        fn set_high(&mut self) {
+            self.set(Level::High)
+        }
+
+        /// Drive the pin low.
+        fn set_low(&mut self) {
+            self.set(Level::Low)
+        }
+
+        /// Invert the pin's level.
+        fn toggle(&mut self) {
+            let level = !self.level();
+
+            self.set(level)
+        }
+    }
+
+    /// A pin that is configured as an input.
+    pub trait InputPin {
+        /// Sample the pin.
+        fn read(&self) -> Level;
+
+        /// True if the pin reads high.
+        fn is_high(&self) -> bool {
+            self.read() == Level::High
+        }
+
+        /// True if the pin reads low.
+        fn is_low(&self) -> bool {
+            self.read() == Level::Low
+        }
+    }
+}
+
+/// Logic level of a pin.
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Level {
+    Low,
+    High,
+}
+
+/// Internal pull resistor configuration of an input pin.
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Pull {
+    None,
+    Up,
+    Down,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl core::ops::Not for Level {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        match self {
+            Level::Low => Level::High,
+            Level::High => Level::Low,
+        }
+    }
+}
+
+impl From<bool> for Level {
+    fn from(high: bool) -> Self {
+        if high {
+            Level::High
+        } else {
+            Level::Low
+        }
+    }
+}
@@ -0,0 +1,152 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Kernel assertions.
+//!
+//! [`kassert!`](crate::kassert!) and [`kassert_eq!`](crate::kassert_eq!) work like their `core`
+//! counterparts, but before panicking, they log the function that contains the assertion, a short
+//! backtrace, and the compared values.
+//!
+//! Without the `kassert` feature, which is a default feature, assertions compile to nothing. Their
+//! arguments are still type checked, but not evaluated. Build with `KASSERT=0` for release images.
+
+use crate::{backtrace, error, memory, symbols};
+use core::fmt;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The number of frames of the backtrace that is logged for a failed assertion.
+const SHORT_BACKTRACE_FRAMES: usize = 4;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Whether kernel assertions are compiled in.
+pub const ENABLED: bool = cfg!(feature = "kassert");
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn log_call_site(index: usize, return_addr: usize) {
+    // Return addresses point to the instruction after the call. Look up the call itself.
+    let call_addr = memory::Address::new(return_addr - 4);
+
+    match symbols::lookup_symbol(call_addr) {
+        None => error!(
+            "      {: >2}. {:#018x} - Symbol not found",
+            index, return_addr
+        ),
+        Some(sym) => error!("      {: >2}. {:#018x} - {}", index, return_addr, sym),
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Log the context of a failed assertion, and panic.
+///
+/// Must not be inlined, so that the first return address points into the function that contains
+/// the assertion.
+#[doc(hidden)]
+#[cold]
+#[inline(never)]
+#[track_caller]
+pub fn _fail(message: fmt::Arguments, values: Option<(&dyn fmt::Debug, &dyn fmt::Debug)>) -> ! {
+    error!("{}", message);
+
+    if let Some(sym) = backtrace::return_addresses()
+        .next()
+        .and_then(|addr| symbols::lookup_symbol(memory::Address::new(addr - 4)))
+    {
+        error!("      In: {}", sym);
+    }
+
+    if let Some((left, right)) = values {
+        error!("      Left:  {:?}", left);
+        error!("      Right: {:?}", right);
+    }
+
+    error!("      Backtrace:");
+    for (i, return_addr) in backtrace::return_addresses()
+        .take(SHORT_BACKTRACE_FRAMES)
+        .enumerate()
+    {
+        log_call_site(i, return_addr);
+    }
+
+    panic!("{}", message)
+}
+
+/// Assert that a condition holds. Compiles to nothing without the `kassert` feature.
+#[macro_export]
+macro_rules! kassert {
+    ($cond:expr $(,)?) => ({
+        if $crate::kassert::ENABLED && !$cond {
+            $crate::kassert::_fail(
+                format_args!("{}", concat!("Assertion failed: ", stringify!($cond))),
+                None,
+            );
+        }
+    });
+    ($cond:expr, $($arg:tt)+) => ({
+        if $crate::kassert::ENABLED && !$cond {
+            $crate::kassert::_fail(format_args!($($arg)+), None);
+        }
+    });
+}
+
+/// Assert that two values are equal. Compiles to nothing without the `kassert` feature.
+#[macro_export]
+macro_rules! kassert_eq {
+    ($left:expr, $right:expr $(,)?) => ({
+        $crate::kassert_eq!(
+            $left,
+            $right,
+            "{}",
+            concat!("Assertion failed: ", stringify!($left), " == ", stringify!($right))
+        );
+    });
+    ($left:expr, $right:expr, $($arg:tt)+) => ({
+        if $crate::kassert::ENABLED {
+            let (left, right) = (&$left, &$right);
+
+            if *left != *right {
+                $crate::kassert::_fail(
+                    format_args!($($arg)+),
+                    Some((left as &dyn core::fmt::Debug, right as &dyn core::fmt::Debug)),
+                );
+            }
+        }
+    });
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use test_macros::kernel_test;
+
+    /// Passing assertions must not panic, and must evaluate their arguments exactly once.
+    #[kernel_test]
+    fn passing_assertions_evaluate_once() {
+        let evaluations = AtomicUsize::new(0);
+        let count = || evaluations.fetch_add(1, Ordering::Relaxed) + 1;
+
+        crate::kassert!(count() == 1);
+        crate::kassert_eq!(count(), 2);
+        crate::kassert_eq!(count(), 3, "Custom message {}", 3);
+
+        let expected = if ENABLED { 3 } else { 0 };
+        assert_eq!(evaluations.load(Ordering::Relaxed), expected);
+    }
+}
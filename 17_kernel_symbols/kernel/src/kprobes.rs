@@ -0,0 +1,311 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Dynamic instrumentation of kernel functions.
+//!
+//! [`register()`] replaces the first instruction of a function with a `BRK`. When a core hits it,
+//! the probe's handler runs with the registers at the function's entry. Afterwards, the original
+//! instruction is put back and single stepped, and then the `BRK` is armed again. This allows
+//! tracing driver paths without rebuilding the kernel, e.g. with the monitor's `trace` command.
+//!
+//! # Limitations
+//!
+//! - Cores that execute a probed function while another core steps it are not traced.
+//! - Stepping needs unmasked debug exceptions, see [`cpu::debug::init()`]. In code that runs with
+//!   debug exceptions masked, e.g. exception handlers, a probe fires once and is disarmed.
+//! - Handlers run in the exception handler of the `BRK`. They must not call probed functions, nor
+//!   take locks that the probed code can hold.
+
+#[cfg(target_arch = "aarch64")]
+#[path = "_arch/aarch64/kprobes.rs"]
+mod arch_kprobes;
+
+use crate::{
+    bsp, cpu, info,
+    memory::{Address, Virtual},
+    symbols,
+    synchronization::{interface::Mutex, SpinLock},
+    warn,
+};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+//--------------------------------------------------------------------------------------------------
+// Architectural Public Reexports
+//--------------------------------------------------------------------------------------------------
+pub use arch_kprobes::{Registers, PROBE_COMMENT};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const MAX_PROBES: usize = 8;
+
+#[derive(Copy, Clone)]
+struct Probe {
+    addr: Address<Virtual>,
+    original: u32,
+    handler: Handler,
+    armed: bool,
+    hits: u64,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A function that runs when a probe is hit.
+pub type Handler = fn(&Registers);
+
+/// How the exception handler resumes after a probe was hit.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Resume {
+    /// The original instruction is in place. Step it, and then call [`finish_step()`].
+    Step,
+
+    /// The original instruction is in place. Return and execute it.
+    Retry,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+/// Instructions are only patched with this lock held, so that a probe that is unregistered is
+/// never armed again.
+static PROBES: SpinLock<[Option<Probe>; MAX_PROBES]> = SpinLock::new([None; MAX_PROBES]);
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NOTHING_TO_ARM: AtomicUsize = AtomicUsize::new(0);
+
+/// The address of the probe to arm again after the current step. Each core steps on its own.
+static ARM_AFTER_STEP: cpu::PerCpu<AtomicUsize> =
+    cpu::PerCpu::new([NOTHING_TO_ARM; bsp::cpu::NUM_CORES]);
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn check_probeable(addr: Address<Virtual>) -> Result<(), &'static str> {
+    if !cpu::code_patch::is_initialized() {
+        return Err("Kprobes not initialized");
+    }
+
+    // Without kernel symbols, any instruction can be probed.
+    if matches!(symbols::lookup_symbol(addr), Some(sym) if !sym.is_start()) {
+        return Err("Address is not the start of a function");
+    }
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Prepare for patching the kernel's code.
+///
+/// # Safety
+///
+/// - Must only be called during kernel init.
+pub unsafe fn init() -> Result<(), &'static str> {
+    cpu::code_patch::init()
+}
+
+/// Probe the function at `addr`, and run `handler` whenever it is called.
+pub fn register(addr: Address<Virtual>, handler: Handler) -> Result<(), &'static str> {
+    check_probeable(addr)?;
+
+    PROBES.lock(|probes| {
+        if probes.iter().flatten().any(|probe| probe.addr == addr) {
+            return Err("Address is already probed");
+        }
+
+        let slot = probes
+            .iter_mut()
+            .find(|probe| probe.is_none())
+            .ok_or("No free probe slot")?;
+
+        // Do not stack on top of a breakpoint of the GDB stub.
+        if arch_kprobes::is_brk_instruction(cpu::code_patch::read_instruction(addr)?) {
+            return Err("Address holds a BRK instruction");
+        }
+
+        let original = cpu::code_patch::write_instruction(addr, arch_kprobes::PROBE_INSTRUCTION)?;
+        *slot = Some(Probe {
+            addr,
+            original,
+            handler,
+            armed: true,
+            hits: 0,
+        });
+
+        Ok(())
+    })
+}
+
+/// Remove the probe at `addr`. Returns the number of times it was hit.
+pub fn unregister(addr: Address<Virtual>) -> Result<u64, &'static str> {
+    PROBES.lock(|probes| {
+        let slot = probes
+            .iter_mut()
+            .find(|probe| matches!(probe, Some(probe) if probe.addr == addr))
+            .ok_or("No probe at this address")?;
+
+        let probe = slot.take().unwrap();
+        if probe.armed {
+            cpu::code_patch::write_instruction(probe.addr, probe.original)?;
+        }
+
+        Ok(probe.hits)
+    })
+}
+
+/// Call `f` with the address and the number of hits of each probe.
+pub fn for_each(mut f: impl FnMut(Address<Virtual>, u64)) {
+    PROBES.lock(|probes| {
+        for probe in probes.iter().flatten() {
+            f(probe.addr, probe.hits);
+        }
+    })
+}
+
+/// A handler that logs the probed function, its first four arguments, and its caller.
+pub fn log_call(regs: &Registers) {
+    let pc = Address::new(regs.pc as usize);
+    let lr = Address::new(regs.return_address() as usize);
+    let args = [0, 1, 2, 3].map(|n| regs.argument(n).unwrap_or(0));
+
+    let caller = symbols::lookup_symbol(lr);
+    let caller: &dyn core::fmt::Display = match &caller {
+        None => &"Symbol not found",
+        Some(sym) => sym,
+    };
+
+    match symbols::lookup_symbol(pc) {
+        None => info!(
+            "kprobe: {}({:#x}, {:#x}, {:#x}, {:#x}) from {}",
+            pc, args[0], args[1], args[2], args[3], caller
+        ),
+        Some(sym) => info!(
+            "kprobe: {}({:#x}, {:#x}, {:#x}, {:#x}) from {}",
+            sym.name, args[0], args[1], args[2], args[3], caller
+        ),
+    }
+}
+
+/// Run the handler of the probe that was hit, and put the original instruction back. Called by
+/// the exception handler of a `BRK` with [`PROBE_COMMENT`].
+///
+/// `can_step` tells whether the interrupted code runs with debug exceptions unmasked.
+pub fn handle_hit(regs: &Registers, can_step: bool) -> Resume {
+    let addr = Address::new(regs.pc as usize);
+
+    let handler = PROBES.lock(|probes| {
+        let probe = probes
+            .iter_mut()
+            .flatten()
+            .find(|probe| probe.addr == addr)?;
+        probe.hits += 1;
+
+        Some(probe.handler)
+    });
+
+    // The probe was removed after the hit. The original instruction is back already.
+    let handler = match handler {
+        None => return Resume::Retry,
+        Some(x) => x,
+    };
+
+    handler(regs);
+
+    PROBES.lock(|probes| {
+        let probe = match probes.iter_mut().flatten().find(|probe| probe.addr == addr) {
+            None => return Resume::Retry,
+            Some(x) => x,
+        };
+
+        if cpu::code_patch::write_instruction(addr, probe.original).is_err() {
+            return Resume::Retry;
+        }
+
+        if !can_step {
+            warn!(
+                "kprobe at {} hit with debug exceptions masked. Disarmed",
+                addr
+            );
+            probe.armed = false;
+            return Resume::Retry;
+        }
+
+        ARM_AFTER_STEP
+            .get()
+            .store(addr.as_usize(), Ordering::Relaxed);
+        Resume::Step
+    })
+}
+
+/// Arm the probe that was stepped again. Called by the exception handler of the software step.
+pub fn finish_step() {
+    let addr = ARM_AFTER_STEP.get().swap(0, Ordering::Relaxed);
+    if addr == 0 {
+        return;
+    }
+
+    let addr = Address::new(addr);
+    PROBES.lock(|probes| {
+        if probes
+            .iter()
+            .flatten()
+            .any(|probe| probe.addr == addr && probe.armed)
+        {
+            let _ = cpu::code_patch::write_instruction(addr, arch_kprobes::PROBE_INSTRUCTION);
+        }
+    })
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::AtomicU64;
+    use test_macros::kernel_test;
+
+    static LAST_ARGUMENT: AtomicU64 = AtomicU64::new(0);
+
+    #[inline(never)]
+    fn probed(x: u64) -> u64 {
+        let x = unsafe { core::ptr::read_volatile(&x) };
+
+        x + 1
+    }
+
+    fn record_argument(regs: &Registers) {
+        LAST_ARGUMENT.store(regs.argument(0).unwrap(), Ordering::Relaxed);
+    }
+
+    /// A probed function must run the handler with its arguments, and still return its result.
+    #[kernel_test]
+    fn probe_runs_handler_and_function() {
+        unsafe {
+            cpu::debug::init();
+            init().unwrap();
+        }
+
+        let addr = Address::new(probed as *const () as usize);
+        register(addr, record_argument).unwrap();
+        assert!(register(addr, record_argument).is_err());
+
+        assert_eq!(probed(41), 42);
+        assert_eq!(LAST_ARGUMENT.load(Ordering::Relaxed), 41);
+        assert_eq!(probed(1), 2);
+        assert_eq!(LAST_ARGUMENT.load(Ordering::Relaxed), 1);
+
+        assert_eq!(unregister(addr), Ok(2));
+        assert_eq!(probed(7), 8);
+        assert_eq!(LAST_ARGUMENT.load(Ordering::Relaxed), 1);
+    }
+}
@@ -3,7 +3,9 @@
 // Copyright (c) 2018-2022 Andre Richter <andre.o.richter@gmail.com>
 
 // Rust embedded logo for `make doc`.
-#![doc(html_logo_url = "https://raw.githubusercontent.com/rust-embedded/wg/master/assets/logo/ewg-logo-blue-white-on-transparent.png")]
+#![doc(
+    html_logo_url = "https://raw.githubusercontent.com/rust-embedded/wg/master/assets/logo/ewg-logo-blue-white-on-transparent.png"
+)]
 
 //! The `kernel` library.
 //!
@@ -124,19 +126,44 @@
 #![test_runner(crate::test_runner)]
 
 mod panic_wait;
-mod synchronization;
+mod test_bench;
+mod test_report;
+mod test_should_panic;
+mod test_timeout;
 
+pub mod backtrace;
 pub mod bsp;
 pub mod common;
 pub mod console;
 pub mod cpu;
 pub mod driver;
+pub mod dtb;
 pub mod exception;
+#[cfg(feature = "fault_inject")]
+pub mod fault_inject;
+#[cfg(feature = "gdbstub")]
+pub mod gdb;
+pub mod gpio;
+pub mod kassert;
+pub mod kprobes;
 pub mod memory;
+pub mod monitor;
+pub mod net;
+#[cfg(feature = "post")]
+pub mod post;
 pub mod print;
+#[cfg(feature = "test_build")]
+pub mod qemu_exit;
+pub mod scheduler;
+#[cfg(feature = "semihosting")]
+pub mod semihosting;
 pub mod state;
 pub mod symbols;
+pub mod synchronization;
+pub mod task;
 pub mod time;
+pub mod usb;
+pub mod workqueue;
 
 //--------------------------------------------------------------------------------------------------
 // Public Code
@@ -155,20 +182,90 @@ pub fn version() -> &'static str {
 // Testing
 //--------------------------------------------------------------------------------------------------
 
+/// Comma-separated patterns that select the unit tests to run. Set with `TEST_FILTER` at build
+/// time, so that the test binary needs no arguments.
+const TEST_FILTER: Option<&str> = option_env!("KERNEL_TEST_FILTER");
+
+/// Whether the test's path, e.g. `libkernel::memory::mmu::tests::foo`, contains `pattern`.
+fn test_path_contains(test: &test_types::UnitTest, pattern: &str) -> bool {
+    if test.module.contains(pattern) || test.name.contains(pattern) {
+        return true;
+    }
+
+    // A pattern that spans both parts of the path, e.g. `tests::fo`.
+    match pattern.rsplit_once("::") {
+        None => false,
+        Some((module, name)) => test.module.ends_with(module) && test.name.starts_with(name),
+    }
+}
+
+/// Whether the test matches one of the patterns of the filter. Without a filter, all tests do.
+fn test_selected(test: &test_types::UnitTest) -> bool {
+    match TEST_FILTER {
+        None | Some("") => true,
+        Some(filter) => filter
+            .split(',')
+            .map(str::trim)
+            .filter(|pattern| !pattern.is_empty())
+            .any(|pattern| test_path_contains(test, pattern)),
+    }
+}
+
 /// The default runner for unit tests.
+///
+/// Only the tests selected by `TEST_FILTER` are run. Tests that should panic are run on a stack of
+/// their own, see the `test_should_panic` module. Benchmarks are run repeatedly, see the
+/// `test_bench` module. Each result is also printed as a JSON object, see the `test_report` module.
 pub fn test_runner(tests: &[&test_types::UnitTest]) {
+    use time::interface::TimeManager;
+
+    let num_selected = tests.iter().filter(|test| test_selected(test)).count();
+
     // This line will be printed as the test header.
-    println!("Running {} tests", tests.len());
+    println!("Running {} tests", num_selected);
+    if num_selected != tests.len() {
+        println!("      Filtered out {} tests", tests.len() - num_selected);
+    }
+    test_report::suite_started(num_selected);
 
-    for (i, test) in tests.iter().enumerate() {
+    let start = time::time_manager().uptime();
+    let selected = tests.iter().filter(|test| test_selected(test));
+    for (i, test) in selected.enumerate() {
         print!("{:>3}. {:.<58}", i + 1, test.name);
+        test_report::test_started(i + 1, test.name);
+
+        if let Some(timeout) = test.timeout {
+            test_timeout::arm(test.name, timeout);
+        }
 
         // Run the actual test.
-        (test.test_func)();
+        let bench_result = match test.kind {
+            test_types::TestKind::Test if test.should_panic => {
+                if !test_should_panic::run(test.test_func) {
+                    panic!("Test {} did not panic", test.name);
+                }
+                None
+            }
+            test_types::TestKind::Test => {
+                (test.test_func)();
+                None
+            }
+            test_types::TestKind::Bench { iterations } => {
+                Some(test_bench::run(test.test_func, iterations))
+            }
+        };
+        test_timeout::disarm();
 
         // Failed tests call panic!(). Execution reaches here only if the test has passed.
-        println!("[ok]")
+        println!("[ok]");
+        if let Some(result) = bench_result {
+            println!("       {}", result);
+            test_report::bench_finished(test.name, &result);
+        }
+        test_report::test_passed();
     }
+
+    test_report::suite_passed(num_selected, time::time_manager().uptime() - start);
 }
 
 /// The `kernel_init()` for unit tests.
@@ -179,7 +276,41 @@ unsafe fn kernel_init() -> ! {
     memory::mmu::post_enable_init();
     bsp::console::qemu_bring_up_console();
 
+    // Tests that spawn threads need the scheduler, which can only be initialized once.
+    scheduler::init();
+
     test_main();
 
-    cpu::qemu_exit_success()
+    qemu_exit::exit_success()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
+
+    /// Patterns match the module, the name, or both across the separator.
+    #[kernel_test]
+    fn test_filter_patterns_match_the_path() {
+        let test = test_types::UnitTest {
+            name: "foo_works",
+            module: "libkernel::memory::mmu::tests",
+            test_func: || {},
+            timeout: None,
+            kind: test_types::TestKind::Test,
+            should_panic: false,
+        };
+
+        assert!(test_path_contains(&test, "memory::mmu"));
+        assert!(test_path_contains(&test, "foo"));
+        assert!(test_path_contains(&test, "mmu::tests::foo"));
+        assert!(!test_path_contains(&test, "time"));
+        assert!(!test_path_contains(&test, "memory::foo"));
+    }
+
+    /// A panicking test passes if it should panic, and the runner continues after it.
+    #[kernel_test(should_panic)]
+    fn should_panic_test_passes_by_panicking() {
+        panic!("Expected panic");
+    }
 }
@@ -3,7 +3,9 @@
 // Copyright (c) 2018-2022 Andre Richter <andre.o.richter@gmail.com>
 
 // Rust embedded logo for `make doc`.
-#![doc(html_logo_url = "https://raw.githubusercontent.com/rust-embedded/wg/master/assets/logo/ewg-logo-blue-white-on-transparent.png")]
+#![doc(
+    html_logo_url = "https://raw.githubusercontent.com/rust-embedded/wg/master/assets/logo/ewg-logo-blue-white-on-transparent.png"
+)]
 
 //! The `kernel` binary.
 
@@ -11,7 +13,10 @@
 #![no_main]
 #![no_std]
 
-use libkernel::{bsp, cpu, driver, exception, info, memory, state, time, warn};
+use libkernel::{
+    bsp, cpu, driver, dtb, exception, info, kprobes, memory, monitor, net, scheduler, state, task,
+    time, usb, warn, workqueue,
+};
 
 /// Early init code.
 ///
@@ -27,49 +32,146 @@ unsafe fn kernel_init() -> ! {
 
     exception::handling_init();
     memory::mmu::post_enable_init();
+    time::init();
 
     // Add the mapping records for the precomputed entries first, so that they appear on the top of
     // the list.
     bsp::memory::mmu::kernel_add_mapping_records_for_precomputed();
 
-    // Bring up the drivers needed for printing first.
-    for i in bsp::driver::driver_manager()
-        .early_print_device_drivers()
-        .iter()
-    {
-        // Any encountered errors cannot be printed yet, obviously, so just safely park the CPU.
-        i.init().unwrap_or_else(|_| cpu::wait_forever());
-    }
-    bsp::driver::driver_manager().post_early_print_device_driver_init();
-    // Printing available from here on.
-
-    // Now bring up the remaining drivers.
-    for i in bsp::driver::driver_manager()
-        .non_early_print_device_drivers()
-        .iter()
-    {
-        if let Err(x) = i.init() {
-            panic!("Error loading driver: {}: {}", i.compatible(), x);
-        }
+    // Start the secondary cores now, so that they come up while the boot core continues. They wait
+    // until the boot core concluded its init. Errors are reported once printing works.
+    let smp_result = cpu::smp::start_secondary_cores();
+
+    // A missing or broken device tree is not fatal. kernel_main() reports whether one was found.
+    let _ = dtb::init();
+
+    // Detect the board with the help of the device tree, which selects the MMIO addresses and the
+    // drivers to use. A kernel built for a different board would access the wrong MMIO addresses.
+    // Printing is not available in this case, so just safely park the CPU.
+    bsp::verify_board().unwrap_or_else(|_| cpu::wait_forever());
+
+    // Register the BSP's drivers. Any encountered errors cannot be printed yet, obviously, so just
+    // safely park the CPU.
+    bsp::driver::init().unwrap_or_else(|_| cpu::wait_forever());
+
+    // Bring up all drivers in dependency order and let them register their IRQ handlers.
+    // Printing is available as soon as the UART is up.
+    bsp::driver::driver_manager().init_drivers_and_irqs();
+
+    if let Err(x) = smp_result {
+        warn!("Not all secondary cores started: {}", x);
     }
 
-    // Let device drivers register and enable their handlers with the interrupt controller.
-    for i in bsp::driver::driver_manager().all_device_drivers() {
-        if let Err(msg) = i.register_and_enable_irq_handler() {
-            warn!("Error registering IRQ handler: {}", msg);
-        }
+    // Local IRQs are still masked, so the self test sees pending interrupts before they are taken.
+    #[cfg(feature = "post")]
+    if !libkernel::post::run() {
+        warn!("Power-on self test failed, the hardware might be faulty");
+    }
+
+    if let Err(x) = task::init_demo_task() {
+        warn!("Demo task not available: {}", x);
+    }
+
+    // kernel_main() continues as the main thread.
+    scheduler::init();
+
+    if let Err(x) = workqueue::init() {
+        warn!("Work queue not available: {}", x);
+    }
+
+    if let Err(x) = time::tick::init() {
+        warn!(
+            "Timer tick not available, threads will not be preempted: {}",
+            x
+        );
+    }
+
+    if let Err(x) = cpu::smp::ipi::init() {
+        warn!("Inter-processor interrupts not available: {}", x);
+    }
+
+    // Allow kernel code to set hardware breakpoints and watchpoints.
+    cpu::debug::init();
+
+    if let Err(x) = kprobes::init() {
+        warn!("Kprobes not available: {}", x);
+    }
+
+    #[cfg(feature = "gdbstub")]
+    if let Err(x) = libkernel::gdb::init() {
+        warn!("GDB stub not available: {}", x);
     }
 
     // Unmask interrupts on the boot CPU core.
     exception::asynchronous::local_irq_unmask();
+    exception::asynchronous::local_fiq_unmask();
+
+    cpu::smp::mark_online();
 
     // Announce conclusion of the kernel_init() phase.
     state::state_manager().transition_to_single_core_main();
+    cpu::smp::conclude_boot_core_init();
 
     // Transition from unsafe to safe.
     kernel_main()
 }
 
+/// Init code of the secondary cores.
+///
+/// When this code runs, virtual memory is already enabled.
+///
+/// # Safety
+///
+/// - Must only be called once per secondary core, by the boot code.
+#[no_mangle]
+unsafe fn kernel_init_secondary() -> ! {
+    exception::handling_init();
+
+    // The boot core is still initializing globals.
+    cpu::smp::wait_for_boot_core();
+
+    if let Err(x) = time::init_secondary() {
+        warn!("Core {}: {}", cpu::smp::core_id::<usize>(), x);
+    }
+
+    if let Err(x) = bsp::exception::asynchronous::init_secondary_core() {
+        panic!("Interrupt controller init failed on secondary core: {}", x);
+    }
+
+    // The initial thread of the core becomes its idle thread below.
+    if let Err(x) = scheduler::init_secondary("idle") {
+        panic!("Scheduler init failed on secondary core: {}", x);
+    }
+
+    if let Err(x) = time::tick::init_secondary() {
+        warn!(
+            "Timer tick not available on core {}: {}",
+            cpu::smp::core_id::<usize>(),
+            x
+        );
+    }
+
+    // Without IPIs, the core is just not reachable by messages. The boot core reported why.
+    let _ = cpu::smp::ipi::init_secondary();
+
+    cpu::debug::init();
+
+    exception::asynchronous::local_irq_unmask();
+    exception::asynchronous::local_fiq_unmask();
+
+    cpu::smp::mark_online();
+
+    scheduler::idle()
+}
+
+/// A kernel thread that takes turns with the main thread.
+fn demo_thread() {
+    for i in 1..=3 {
+        info!("      Hello from the demo thread ({}/3)", i);
+        scheduler::yield_now();
+    }
+}
+
 /// The main function running after the early init.
 fn kernel_main() -> ! {
     use driver::interface::DriverManager;
@@ -78,11 +180,34 @@ fn kernel_main() -> ! {
     info!("{}", libkernel::version());
     info!("Booting on: {}", bsp::board_name());
 
+    if let Some(soc) = bsp::detect_soc() {
+        info!("      Detected SoC: {}", soc);
+    }
+
     info!("MMU online:");
-    memory::mmu::kernel_print_mappings();
+    time::measure!(memory::mmu::kernel_print_mappings());
+
+    match dtb::boot_device_tree() {
+        None => {
+            info!("Device tree: Not provided");
+
+            if let Some(size) = bsp::memory::board_dram_size() {
+                info!("      DRAM size: {} MiB (board default)", size >> 20);
+            }
+        }
+        Some(dt) => {
+            let model = dt.root().property("model").and_then(|p| p.as_str());
+            info!("Device tree: {}", model.unwrap_or("Unknown model"));
+
+            if let Some(size) = dt.memory_size() {
+                info!("      DRAM size: {} MiB", size >> 20);
+            }
+        }
+    }
 
     let (_, privilege_level) = exception::current_privilege_level();
     info!("Current privilege level: {}", privilege_level);
+    info!("      Started in: {}", cpu::boot_privilege_level());
 
     info!("Exception handling state:");
     exception::asynchronous::print_state();
@@ -92,18 +217,68 @@ fn kernel_main() -> ! {
         time::time_manager().resolution().as_nanos()
     );
 
+    info!("Waiting for secondary cores");
+    if let Err(x) = cpu::smp::wait_for_secondary_cores() {
+        warn!("      {}", x);
+    }
+    state::state_manager().transition_to_multi_core_main();
+    info!("      {} cores online", cpu::smp::num_online());
+
     info!("Drivers loaded:");
-    for (i, driver) in bsp::driver::driver_manager()
-        .all_device_drivers()
-        .iter()
-        .enumerate()
-    {
-        info!("      {}. {}", i + 1, driver.compatible());
+    let mut i = 0;
+    bsp::driver::driver_manager().for_each_device_driver(|descriptor| {
+        i += 1;
+        info!("      {}. {}", i, descriptor.device_driver().compatible());
+    });
+
+    info!("USB root port:");
+    match bsp::usb::host_controller() {
+        None => info!("      No supported host controller"),
+        Some(hc) => {
+            if let Err(x) = usb::enumerate_root_port(hc) {
+                info!("      {}", x);
+            }
+        }
+    }
+
+    if let Some(eth) = bsp::net::network_device() {
+        use net::interface::NetworkDevice;
+
+        info!(
+            "Ethernet: MAC {}, link {}",
+            eth.mac_address(),
+            eth.link_status()
+        );
     }
 
     info!("Registered IRQ handlers:");
     bsp::exception::asynchronous::irq_manager().print_handler();
 
-    info!("Echoing input now");
-    cpu::wait_forever();
+    info!("Tick callbacks at {} Hz:", time::tick::frequency());
+    time::tick::print_callbacks();
+
+    if let Some(demo) = task::demo_task() {
+        info!("Running the demo task in user space");
+        let exit_code = demo.run();
+        info!("      Demo task exited with code {}", exit_code);
+    }
+
+    info!("Spawning a kernel thread");
+    match scheduler::spawn("demo", demo_thread) {
+        Err(x) => warn!("      {}", x),
+        Ok(thread) => {
+            scheduler::print_threads();
+            scheduler::join(thread);
+        }
+    }
+
+    info!("Synchronous exception statistics:");
+    exception::print_stats();
+
+    info!("Starting the debug monitor");
+    if let Err(x) = scheduler::spawn("monitor", monitor::run) {
+        warn!("      {}", x);
+    }
+
+    scheduler::idle();
 }
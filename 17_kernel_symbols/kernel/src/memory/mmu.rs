@@ -23,6 +23,11 @@ use core::{fmt, num::NonZeroUsize};
 
 pub use types::*;
 
+//--------------------------------------------------------------------------------------------------
+// Architectural Public Reexports
+//--------------------------------------------------------------------------------------------------
+pub use arch_mmu::invalidate_local_tlb;
+
 //--------------------------------------------------------------------------------------------------
 // Public Definitions
 //--------------------------------------------------------------------------------------------------
@@ -112,14 +117,44 @@ unsafe fn kernel_map_at_unchecked(
     Ok(())
 }
 
-/// Try to translate a kernel virtual address to a physical address.
+/// Map a physically contiguous region that is already mapped for the kernel a second time.
 ///
-/// Will only succeed if there exists a valid mapping for the input address.
-fn try_kernel_virt_addr_to_phys_addr(
-    virt_addr: Address<Virtual>,
-) -> Result<Address<Physical>, &'static str> {
-    bsp::memory::mmu::kernel_translation_tables()
-        .read(|tables| tables.try_virt_addr_to_phys_addr(virt_addr))
+/// # Safety
+///
+/// - Same as `kernel_map_at_unchecked()`. Aliasing is the purpose of this function.
+unsafe fn kernel_map_alias(
+    name: &'static str,
+    kernel_virt_region: &MemoryRegion<Virtual>,
+    attr: &AttributeFields,
+) -> Result<MemoryRegion<Virtual>, &'static str> {
+    let num_pages = match NonZeroUsize::new(kernel_virt_region.num_pages()) {
+        None => return Err("Requested 0 pages"),
+        Some(x) => x,
+    };
+
+    // The region must be physically contiguous.
+    let phys_start_page_addr =
+        try_kernel_virt_page_addr_to_phys_page_addr(kernel_virt_region.start_page_addr())?;
+    for (i, virt_page_addr) in kernel_virt_region.into_iter().enumerate() {
+        let phys_page_addr = try_kernel_virt_page_addr_to_phys_page_addr(virt_page_addr)?;
+
+        if Some(phys_page_addr) != phys_start_page_addr.checked_offset(i as isize) {
+            return Err("Region is not physically contiguous");
+        }
+    }
+    let phys_region = MemoryRegion::new(
+        phys_start_page_addr,
+        phys_start_page_addr
+            .checked_offset(num_pages.get() as isize)
+            .ok_or("Physical region overflows")?,
+    );
+
+    let virt_region =
+        alloc::kernel_mmio_va_allocator().lock(|allocator| allocator.alloc(num_pages))?;
+
+    kernel_map_at_unchecked(name, &virt_region, &phys_region, attr)?;
+
+    Ok(virt_region)
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -227,6 +262,96 @@ pub unsafe fn kernel_map_mmio(
     Ok(virt_addr + offset_into_start_page)
 }
 
+/// Map a region of normal memory read-only into the kernel's MMIO remap region.
+///
+/// Used for data that the firmware left in DRAM, like the device tree blob.
+///
+/// # Safety
+///
+/// - Same as `kernel_map_at_unchecked()`, minus the aliasing part.
+pub unsafe fn kernel_map_ro_data(
+    name: &'static str,
+    phys_start_addr: Address<Physical>,
+    size: usize,
+) -> Result<Address<Virtual>, &'static str> {
+    let phys_region = MemoryRegion::new(
+        PageAddress::from(phys_start_addr.align_down_page()),
+        PageAddress::from((phys_start_addr + size).align_up_page()),
+    );
+    let offset_into_start_page = phys_start_addr.offset_into_page();
+
+    let num_pages = match NonZeroUsize::new(phys_region.num_pages()) {
+        None => return Err("Requested 0 pages"),
+        Some(x) => x,
+    };
+
+    let virt_region =
+        alloc::kernel_mmio_va_allocator().lock(|allocator| allocator.alloc(num_pages))?;
+
+    kernel_map_at_unchecked(
+        name,
+        &virt_region,
+        &phys_region,
+        &AttributeFields {
+            mem_attributes: MemAttributes::CacheableDRAM,
+            acc_perms: AccessPermissions::ReadOnly,
+            execute_never: true,
+        },
+    )?;
+
+    Ok(virt_region.start_addr() + offset_into_start_page)
+}
+
+/// Map memory that is already mapped for the kernel a second time, so that it is accessible from
+/// user space.
+///
+/// # Safety
+///
+/// - Same as `kernel_map_at_unchecked()`. Aliasing is the purpose of this function.
+pub unsafe fn kernel_map_user_alias(
+    name: &'static str,
+    kernel_virt_region: &MemoryRegion<Virtual>,
+    attr: &AttributeFields,
+) -> Result<MemoryRegion<Virtual>, &'static str> {
+    if !attr.acc_perms.is_user() {
+        return Err("Access permissions for user space expected");
+    }
+
+    kernel_map_alias(name, kernel_virt_region, attr)
+}
+
+/// Map memory that is already mapped for the kernel a second time, writable and not executable.
+///
+/// Used to modify memory that is mapped read-only, like the kernel's code.
+///
+/// # Safety
+///
+/// - Same as `kernel_map_at_unchecked()`. Aliasing is the purpose of this function.
+pub unsafe fn kernel_map_rw_alias(
+    name: &'static str,
+    kernel_virt_region: &MemoryRegion<Virtual>,
+) -> Result<MemoryRegion<Virtual>, &'static str> {
+    kernel_map_alias(
+        name,
+        kernel_virt_region,
+        &AttributeFields {
+            mem_attributes: MemAttributes::CacheableDRAM,
+            acc_perms: AccessPermissions::ReadWrite,
+            execute_never: true,
+        },
+    )
+}
+
+/// Try to translate a kernel virtual address to a physical address.
+///
+/// Will only succeed if there exists a valid mapping for the input address.
+pub fn try_kernel_virt_addr_to_phys_addr(
+    virt_addr: Address<Virtual>,
+) -> Result<Address<Physical>, &'static str> {
+    bsp::memory::mmu::kernel_translation_tables()
+        .read(|tables| tables.try_virt_addr_to_phys_addr(virt_addr))
+}
+
 /// Try to translate a kernel virtual page address to a physical page address.
 ///
 /// Will only succeed if there exists a valid mapping for the input page.
@@ -264,6 +389,11 @@ pub fn post_enable_init() {
     kernel_init_mmio_va_allocator();
 }
 
+/// Check that the recorded kernel mappings match the translation tables and do not overlap.
+pub fn kernel_check_mappings() -> Result<(), &'static str> {
+    mapping_record::kernel_check()
+}
+
 /// Human-readable print of all recorded kernel mappings.
 pub fn kernel_print_mappings() {
     mapping_record::kernel_print()
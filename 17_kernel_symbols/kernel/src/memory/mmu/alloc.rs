@@ -7,7 +7,7 @@
 use super::MemoryRegion;
 use crate::{
     memory::{AddressType, Virtual},
-    synchronization::IRQSafeNullLock,
+    synchronization::SpinLock,
     warn,
 };
 use core::num::NonZeroUsize;
@@ -25,15 +25,15 @@ pub struct PageAllocator<ATYPE: AddressType> {
 // Global instances
 //--------------------------------------------------------------------------------------------------
 
-static KERNEL_MMIO_VA_ALLOCATOR: IRQSafeNullLock<PageAllocator<Virtual>> =
-    IRQSafeNullLock::new(PageAllocator::new());
+static KERNEL_MMIO_VA_ALLOCATOR: SpinLock<PageAllocator<Virtual>> =
+    SpinLock::new(PageAllocator::new());
 
 //--------------------------------------------------------------------------------------------------
 // Public Code
 //--------------------------------------------------------------------------------------------------
 
 /// Return a reference to the kernel's MMIO virtual address allocator.
-pub fn kernel_mmio_va_allocator() -> &'static IRQSafeNullLock<PageAllocator<Virtual>> {
+pub fn kernel_mmio_va_allocator() -> &'static SpinLock<PageAllocator<Virtual>> {
     &KERNEL_MMIO_VA_ALLOCATOR
 }
 
@@ -62,6 +62,11 @@ impl<ATYPE: AddressType> PageAllocator<ATYPE> {
             return Err("Allocator not initialized");
         }
 
+        #[cfg(feature = "fault_inject")]
+        if crate::fault_inject::should_fail(crate::fault_inject::Fault::Alloc) {
+            return Err("Injected allocation failure");
+        }
+
         self.pool
             .as_mut()
             .unwrap()
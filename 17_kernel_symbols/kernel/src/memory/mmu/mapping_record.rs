@@ -6,7 +6,7 @@
 
 use super::{
     AccessPermissions, Address, AttributeFields, MMIODescriptor, MemAttributes, MemoryRegion,
-    Physical, Virtual,
+    PageAddress, Physical, Virtual,
 };
 use crate::{bsp, info, synchronization, synchronization::InitStateLock, warn};
 
@@ -69,6 +69,46 @@ impl MappingRecordEntry {
         *x = Some(user);
         Ok(())
     }
+
+    fn virt_region(&self) -> MemoryRegion<Virtual> {
+        let start_page_addr = PageAddress::from(self.virt_start_addr);
+
+        MemoryRegion::new(
+            start_page_addr,
+            start_page_addr
+                .checked_offset(self.num_pages as isize)
+                .unwrap(),
+        )
+    }
+
+    /// Check that the translation tables map each page of the entry as recorded.
+    fn check(&self) -> Result<(), &'static str> {
+        let virt_start_page_addr = PageAddress::<Virtual>::from(self.virt_start_addr);
+        let phys_start_page_addr = PageAddress::<Physical>::from(self.phys_start_addr);
+
+        for i in 0..self.num_pages as isize {
+            let virt_page_addr = virt_start_page_addr.checked_offset(i).unwrap();
+            let phys_page_addr = phys_start_page_addr.checked_offset(i).unwrap();
+
+            let (translated, attributes) = match (
+                super::try_kernel_virt_page_addr_to_phys_page_addr(virt_page_addr),
+                super::try_kernel_page_attributes(virt_page_addr),
+            ) {
+                (Ok(x), Ok(y)) => (x, y),
+                _ => return Err("Recorded page is not mapped"),
+            };
+
+            if translated != phys_page_addr {
+                return Err("Recorded page is mapped to a different physical page");
+            }
+
+            if attributes != self.attribute_fields {
+                return Err("Recorded page is mapped with different attributes");
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl MappingRecord {
@@ -124,6 +164,28 @@ impl MappingRecord {
         Ok(())
     }
 
+    /// Check all entries against the translation tables, and against each other.
+    pub fn check(&self) -> Result<(), &'static str> {
+        let entries = || self.inner.iter().flatten();
+
+        for (i, entry) in entries().enumerate() {
+            let virt_region = entry.virt_region();
+
+            // `overlaps()` is not symmetric if one region contains the other.
+            if entries().skip(i + 1).any(|x| {
+                let other = x.virt_region();
+
+                other.overlaps(&virt_region) || virt_region.overlaps(&other)
+            }) {
+                return Err("Recorded mappings overlap");
+            }
+
+            entry.check()?;
+        }
+
+        Ok(())
+    }
+
     pub fn print(&self) {
         const KIB_RSHIFT: u32 = 10; // log2(1024).
         const MIB_RSHIFT: u32 = 20; // log2(1024 * 1024).
@@ -155,9 +217,12 @@ impl MappingRecord {
                 MemAttributes::Device => "Dev",
             };
 
+            // The U-variants are accessible from user space as well.
             let acc_p = match i.attribute_fields.acc_perms {
                 AccessPermissions::ReadOnly => "RO",
                 AccessPermissions::ReadWrite => "RW",
+                AccessPermissions::UserReadOnly => "UR",
+                AccessPermissions::UserReadWrite => "UW",
             };
 
             let xn = if i.attribute_fields.execute_never {
@@ -227,6 +292,11 @@ pub fn kernel_find_and_insert_mmio_duplicate(
     })
 }
 
+/// Check that the recorded kernel mappings match the translation tables and do not overlap.
+pub fn kernel_check() -> Result<(), &'static str> {
+    KERNEL_MAPPING_RECORD.read(|mr| mr.check())
+}
+
 /// Human-readable print of all recorded kernel mappings.
 pub fn kernel_print() {
     KERNEL_MAPPING_RECORD.read(|mr| mr.print());
@@ -41,6 +41,8 @@ pub enum MemAttributes {
 pub enum AccessPermissions {
     ReadOnly,
     ReadWrite,
+    UserReadOnly,
+    UserReadWrite,
 }
 
 /// Collection of memory attributes.
@@ -271,6 +273,16 @@ impl From<MMIODescriptor> for MemoryRegion<Physical> {
     }
 }
 
+//------------------------------------------------------------------------------
+// AccessPermissions
+//------------------------------------------------------------------------------
+impl AccessPermissions {
+    /// True if the memory is also accessible from user space.
+    pub fn is_user(&self) -> bool {
+        matches!(self, Self::UserReadOnly | Self::UserReadWrite)
+    }
+}
+
 //------------------------------------------------------------------------------
 // MMIODescriptor
 //------------------------------------------------------------------------------
@@ -0,0 +1,400 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Interactive debug monitor.
+//!
+//! Reads commands from the console and lets the user inspect the running kernel. Type `help` for a
+//! list of commands.
+
+use crate::{
+    bsp, common, console, driver, kprobes,
+    memory::{
+        mmu::{self, AccessPermissions, PageAddress},
+        Address, Physical, Virtual,
+    },
+    print::{self, TimestampFormat},
+    println, symbols, time,
+};
+use core::time::Duration;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const PROMPT: &str = "monitor> ";
+
+const DEFAULT_DUMP_LEN: usize = 64;
+const MAX_DUMP_LEN: usize = 256;
+
+const HELP: &str = "\
+Commands:
+  help                 Print this help
+  peek <addr>          Read the 64 bit word at <addr>
+  poke <addr> <value>  Write <value> to the 64 bit word at <addr>
+  dump <addr> [<len>]  Hexdump <len> bytes at <addr>, marking changes since the last dump
+  trace [<addr>]       Log calls of the function at <addr>, or list the traced functions
+  untrace <addr>       Stop logging calls of the function at <addr>
+  pagetables [<addr>]  Print the kernel mappings, or translate <addr>
+  symbols <addr> <len> Load the symbols blob of <len> bytes at physical <addr>
+  drivers              List the loaded drivers
+  uptime               Print the time since power on, and the date if known
+  date <unix time>     Set the wall clock, in seconds since 1970
+  timestamps <format>  Log with uptime, ticks, delta or wallclock timestamps
+  gdb                  Stop in the GDB stub and wait for GDB to attach
+  panic                Trigger a kernel panic
+
+Numbers are decimal, or hexadecimal with a 0x prefix.";
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Command {
+    Help,
+    Peek(usize),
+    Poke(usize, u64),
+    Dump(usize, usize),
+    Trace(Option<usize>),
+    Untrace(usize),
+    PageTables(Option<usize>),
+    LoadSymbols(usize, usize),
+    Drivers,
+    Uptime,
+    SetDate(u64),
+    Timestamps(TimestampFormat),
+    Gdb,
+    Panic,
+}
+
+/// The previous `dump`, for marking changes when the same range is dumped again.
+struct LastDump {
+    addr: usize,
+    len: usize,
+    bytes: [u8; MAX_DUMP_LEN],
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn parse_number(s: &str) -> Result<u64, &'static str> {
+    let result = match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+
+    result.map_err(|_| "Invalid number")
+}
+
+fn parse_address(s: Option<&str>) -> Result<usize, &'static str> {
+    let addr = parse_number(s.ok_or("Address missing")?)? as usize;
+
+    if addr % core::mem::size_of::<u64>() != 0 {
+        return Err("Address not 8 byte aligned");
+    }
+
+    Ok(addr)
+}
+
+/// Parse a line. Returns `None` for an empty line.
+fn parse(line: &str) -> Result<Option<Command>, &'static str> {
+    let mut args = line.split_whitespace();
+
+    let command = match args.next() {
+        None => return Ok(None),
+        Some("help") => Command::Help,
+        Some("peek") => Command::Peek(parse_address(args.next())?),
+        Some("poke") => {
+            let addr = parse_address(args.next())?;
+            let value = parse_number(args.next().ok_or("Value missing")?)?;
+
+            Command::Poke(addr, value)
+        }
+        Some("dump") => {
+            let addr = parse_number(args.next().ok_or("Address missing")?)? as usize;
+            let len = match args.next() {
+                None => DEFAULT_DUMP_LEN,
+                Some(len) => parse_number(len)? as usize,
+            };
+
+            if !(1..=MAX_DUMP_LEN).contains(&len) {
+                return Err("Length must be between 1 and 256");
+            }
+
+            Command::Dump(addr, len)
+        }
+        Some("trace") => match args.next() {
+            None => Command::Trace(None),
+            Some(addr) => Command::Trace(Some(parse_number(addr)? as usize)),
+        },
+        Some("untrace") => {
+            Command::Untrace(parse_number(args.next().ok_or("Address missing")?)? as usize)
+        }
+        Some("pagetables") => match args.next() {
+            None => Command::PageTables(None),
+            Some(addr) => Command::PageTables(Some(parse_number(addr)? as usize)),
+        },
+        Some("symbols") => {
+            let addr = parse_number(args.next().ok_or("Address missing")?)? as usize;
+            let len = parse_number(args.next().ok_or("Length missing")?)? as usize;
+
+            Command::LoadSymbols(addr, len)
+        }
+        Some("drivers") => Command::Drivers,
+        Some("uptime") => Command::Uptime,
+        Some("date") => Command::SetDate(parse_number(args.next().ok_or("Time missing")?)?),
+        Some("timestamps") => match args.next() {
+            Some("uptime") => Command::Timestamps(TimestampFormat::Uptime),
+            Some("ticks") => Command::Timestamps(TimestampFormat::Ticks),
+            Some("delta") => Command::Timestamps(TimestampFormat::Delta),
+            Some("wallclock") => Command::Timestamps(TimestampFormat::WallClock),
+            _ => return Err("Format must be uptime, ticks, delta or wallclock"),
+        },
+        Some("gdb") => Command::Gdb,
+        Some("panic") => Command::Panic,
+        Some(_) => return Err("Unknown command. Type `help` for a list of commands"),
+    };
+
+    if args.next().is_some() {
+        return Err("Too many arguments");
+    }
+
+    Ok(Some(command))
+}
+
+/// Check that `addr` is mapped in the kernel translation tables, and writeable if requested.
+fn check_mapped(addr: usize, write: bool) -> Result<(), &'static str> {
+    let page_addr = PageAddress::from(Address::<Virtual>::new(addr).align_down_page());
+    let attributes = mmu::try_kernel_page_attributes(page_addr)?;
+
+    if write && attributes.acc_perms != AccessPermissions::ReadWrite {
+        return Err("Address is not writeable");
+    }
+
+    Ok(())
+}
+
+fn peek(addr: usize) -> Result<(), &'static str> {
+    check_mapped(addr, false)?;
+
+    let value = unsafe { core::ptr::read_volatile(addr as *const u64) };
+    println!("{:#018x}: {:#018x}", addr, value);
+
+    Ok(())
+}
+
+fn poke(addr: usize, value: u64) -> Result<(), &'static str> {
+    check_mapped(addr, true)?;
+
+    unsafe { core::ptr::write_volatile(addr as *mut u64, value) };
+
+    Ok(())
+}
+
+/// Hexdump `len` bytes at `addr`. Device memory is dumped as well, but never snapshotted, so that
+/// device registers are read only once per dump.
+fn dump(addr: usize, len: usize, last_dump: &mut Option<LastDump>) -> Result<(), &'static str> {
+    check_mapped(addr, false)?;
+
+    let previous = match last_dump {
+        Some(x) if x.addr == addr && x.len == len => Some(&x.bytes[..len]),
+        _ => None,
+    };
+
+    let mut dump = common::hexdump(addr, len).allow_device_memory();
+    if let Some(x) = previous {
+        dump = dump.highlight_changes(x);
+    }
+    println!("{}", dump);
+
+    let mut bytes = [0; MAX_DUMP_LEN];
+    *last_dump = common::hexdump(addr, len)
+        .read(&mut bytes[..len])
+        .ok()
+        .map(|_| LastDump { addr, len, bytes });
+
+    Ok(())
+}
+
+fn trace(addr: Option<usize>) -> Result<(), &'static str> {
+    match addr {
+        Some(x) => kprobes::register(Address::new(x), kprobes::log_call),
+        None => {
+            kprobes::for_each(|addr, hits| println!("{}: {} calls", addr, hits));
+            Ok(())
+        }
+    }
+}
+
+fn untrace(addr: usize) -> Result<(), &'static str> {
+    let hits = kprobes::unregister(Address::new(addr))?;
+    println!("{} calls traced", hits);
+
+    Ok(())
+}
+
+fn page_tables(addr: Option<usize>) -> Result<(), &'static str> {
+    let addr = match addr {
+        None => {
+            mmu::kernel_print_mappings();
+            return Ok(());
+        }
+        Some(x) => Address::<Virtual>::new(x),
+    };
+
+    let phys_addr = mmu::try_kernel_virt_addr_to_phys_addr(addr)?;
+    let attributes = mmu::try_kernel_page_attributes(PageAddress::from(addr.align_down_page()))?;
+
+    println!("{} -> {}", addr, phys_addr);
+    println!(
+        "{:?}, {:?}, execute never: {}",
+        attributes.mem_attributes, attributes.acc_perms, attributes.execute_never
+    );
+
+    Ok(())
+}
+
+fn load_symbols(addr: usize, len: usize) -> Result<(), &'static str> {
+    let phys_addr = Address::<Physical>::new(addr);
+
+    unsafe {
+        let virt_addr = mmu::kernel_map_ro_data("Kernel symbols", phys_addr, len)?;
+        symbols::load(virt_addr, len)?;
+    }
+    println!("Symbols loaded");
+
+    Ok(())
+}
+
+fn drivers() {
+    use driver::interface::DriverManager;
+
+    let mut i = 0;
+    bsp::driver::driver_manager().for_each_device_driver(|descriptor| {
+        i += 1;
+        println!("{}. {}", i, descriptor.device_driver().compatible());
+    });
+}
+
+fn uptime() {
+    use time::interface::TimeManager;
+
+    let uptime = time::time_manager().uptime();
+    println!("{}.{:06} s", uptime.as_secs(), uptime.subsec_micros());
+
+    if let Some(date) = time::calendar_time() {
+        println!("{}", date);
+    }
+}
+
+fn set_date(unix_time: u64) {
+    use time::interface::TimeManager;
+
+    time::time_manager().set_wall_clock(Duration::from_secs(unix_time));
+}
+
+#[cfg(feature = "gdbstub")]
+fn gdb() -> Result<(), &'static str> {
+    if !crate::gdb::is_enabled() {
+        return Err("GDB stub not initialized");
+    }
+
+    println!("Waiting for GDB");
+    crate::gdb::breakpoint();
+
+    Ok(())
+}
+
+#[cfg(not(feature = "gdbstub"))]
+fn gdb() -> Result<(), &'static str> {
+    Err("Kernel built without the GDB stub. Rebuild with GDBSTUB=1")
+}
+
+fn execute(command: Command, last_dump: &mut Option<LastDump>) -> Result<(), &'static str> {
+    match command {
+        Command::Help => println!("{}", HELP),
+        Command::Peek(addr) => peek(addr)?,
+        Command::Poke(addr, value) => poke(addr, value)?,
+        Command::Dump(addr, len) => dump(addr, len, last_dump)?,
+        Command::Trace(addr) => trace(addr)?,
+        Command::Untrace(addr) => untrace(addr)?,
+        Command::PageTables(addr) => page_tables(addr)?,
+        Command::LoadSymbols(addr, len) => load_symbols(addr, len)?,
+        Command::Drivers => drivers(),
+        Command::Uptime => uptime(),
+        Command::SetDate(unix_time) => set_date(unix_time),
+        Command::Timestamps(format) => print::set_timestamp_format(format),
+        Command::Gdb => gdb()?,
+        Command::Panic => panic!("Panic requested from the monitor"),
+    }
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Run the monitor. Does not return.
+pub fn run() {
+    let mut buf = [0u8; 128];
+    let mut last_dump = None;
+
+    println!("Debug monitor. Type `help` for a list of commands.");
+
+    loop {
+        let line = console::readline(PROMPT, &mut buf);
+
+        let result = match parse(line) {
+            Ok(None) => continue,
+            Ok(Some(command)) => execute(command, &mut last_dump),
+            Err(x) => Err(x),
+        };
+
+        if let Err(x) = result {
+            println!("Error: {}", x);
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
+
+    /// Commands and their arguments must be parsed, and malformed ones rejected.
+    #[kernel_test]
+    fn commands_are_parsed() {
+        assert_eq!(parse("  "), Ok(None));
+        assert_eq!(parse("uptime"), Ok(Some(Command::Uptime)));
+        assert_eq!(parse("peek 0x80000"), Ok(Some(Command::Peek(0x80000))));
+        assert_eq!(parse("poke 16 0xff"), Ok(Some(Command::Poke(16, 0xff))));
+        assert_eq!(parse("dump 0x80001"), Ok(Some(Command::Dump(0x80001, 64))));
+        assert_eq!(parse("dump 16 0x20"), Ok(Some(Command::Dump(16, 32))));
+        assert_eq!(parse("trace"), Ok(Some(Command::Trace(None))));
+        assert_eq!(
+            parse("untrace 0x80000"),
+            Ok(Some(Command::Untrace(0x80000)))
+        );
+        assert_eq!(parse("pagetables"), Ok(Some(Command::PageTables(None))));
+        assert_eq!(
+            parse("symbols 0x3800000 4096"),
+            Ok(Some(Command::LoadSymbols(0x380_0000, 4096)))
+        );
+        assert_eq!(parse("gdb"), Ok(Some(Command::Gdb)));
+        assert_eq!(
+            parse("timestamps delta"),
+            Ok(Some(Command::Timestamps(TimestampFormat::Delta)))
+        );
+
+        assert!(parse("peek").is_err());
+        assert!(parse("dump 16 0").is_err());
+        assert!(parse("symbols 0x3800000").is_err());
+        assert!(parse("peek 0x3").is_err());
+        assert!(parse("poke 8 zz").is_err());
+        assert!(parse("uptime now").is_err());
+        assert!(parse("reboot").is_err());
+    }
+}
@@ -0,0 +1,99 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Network device support.
+
+use core::fmt;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Networking interfaces.
+pub mod interface {
+    use super::*;
+
+    /// Functions provided by a network device that sends and receives raw Ethernet frames.
+    pub trait NetworkDevice {
+        /// The device's hardware address.
+        fn mac_address(&self) -> MacAddress;
+
+        /// Query the current state of the physical link.
+        fn link_status(&self) -> LinkStatus;
+
+        /// Queue a single Ethernet frame for transmission.
+        ///
+        /// The frame must start with the destination MAC address and must not contain the FCS.
+        fn transmit(&self, frame: &[u8]) -> Result<(), &'static str>;
+
+        /// Copy the next received frame into `buf`, if there is one.
+        ///
+        /// Returns the length of the frame. Frames that do not fit into `buf` are dropped.
+        fn receive(&self, buf: &mut [u8]) -> Option<usize>;
+    }
+}
+
+/// The maximum size of an Ethernet frame without FCS and VLAN tag.
+pub const MAX_FRAME_SIZE: usize = 1514;
+
+/// An Ethernet hardware address.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct MacAddress([u8; 6]);
+
+/// Link state of a network device.
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum LinkStatus {
+    Down,
+    Up { speed_mbps: u16, full_duplex: bool },
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl MacAddress {
+    /// Create an instance.
+    pub const fn new(bytes: [u8; 6]) -> Self {
+        Self(bytes)
+    }
+
+    /// The raw bytes.
+    pub fn as_bytes(&self) -> &[u8; 6] {
+        &self.0
+    }
+
+    /// True for an all-zero address.
+    pub fn is_zero(&self) -> bool {
+        self.0.iter().all(|x| *x == 0)
+    }
+}
+
+impl fmt::Display for MacAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let b = &self.0;
+
+        write!(
+            f,
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            b[0], b[1], b[2], b[3], b[4], b[5]
+        )
+    }
+}
+
+impl fmt::Display for LinkStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LinkStatus::Down => write!(f, "Down"),
+            LinkStatus::Up {
+                speed_mbps,
+                full_duplex,
+            } => {
+                let duplex = if *full_duplex { "full" } else { "half" };
+
+                write!(f, "Up, {} Mbit/s, {} duplex", speed_mbps, duplex)
+            }
+        }
+    }
+}
@@ -4,7 +4,7 @@
 
 //! A panic handler that infinitely waits.
 
-use crate::{bsp, cpu, exception};
+use crate::{backtrace, bsp, cpu, exception, test_report};
 use core::{fmt, panic::PanicInfo};
 
 //--------------------------------------------------------------------------------------------------
@@ -14,6 +14,14 @@ use core::{fmt, panic::PanicInfo};
 fn _panic_print(args: fmt::Arguments) {
     use fmt::Write;
 
+    // Tests may check the panic output. It is still printed, so that failures stay visible.
+    #[cfg(feature = "test_build")]
+    if let Some(x) = crate::console::test_console::active() {
+        use crate::console::interface::Write;
+
+        x.write_fmt(args).unwrap();
+    }
+
     unsafe { bsp::console::panic_console_out().write_fmt(args).unwrap() };
 }
 
@@ -30,7 +38,7 @@ fn _panic_exit() -> ! {
 
     #[cfg(feature = "test_build")]
     {
-        cpu::qemu_exit_failure()
+        crate::qemu_exit::exit_failure()
     }
 }
 
@@ -79,6 +87,10 @@ fn panic(info: &PanicInfo) -> ! {
 
     unsafe { exception::asynchronous::local_irq_mask() };
 
+    // A test that should panic did, so there is nothing to report.
+    #[cfg(feature = "test_build")]
+    crate::test_should_panic::resume_runner_if_expected();
+
     // Protect against panic infinite loops if any of the following code panics itself.
     panic_prevent_reenter();
 
@@ -91,6 +103,7 @@ fn panic(info: &PanicInfo) -> ! {
     panic_println!(
         "[  {:>3}.{:06}] Kernel panic!\n\n\
         Panic location:\n      File '{}', line {}, column {}\n\n\
+        {}\n\n\
         {}",
         timestamp.as_secs(),
         timestamp.subsec_micros(),
@@ -98,7 +111,12 @@ fn panic(info: &PanicInfo) -> ! {
         line,
         column,
         info.message().unwrap_or(&format_args!("")),
+        backtrace::Backtrace::capture(),
     );
 
+    if let Some(result) = test_report::test_failed() {
+        panic_println!("{}", result);
+    }
+
     _panic_exit()
 }
@@ -0,0 +1,192 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Power-on self test (POST).
+//!
+//! Enabled with the `post` feature. After the drivers are up, [`run()`] executes quick sanity
+//! checks of the hardware and prints a summary like:
+//!
+//! ```text
+//! [    0.112345] POST:
+//! [    0.112346]       ---------------------------------------------------------------
+//! [    0.112347]       Check                      Result
+//! [    0.112348]       ---------------------------------------------------------------
+//! [    0.112349]       UART loopback              PASS
+//! [    0.112350]       Timer monotonicity         PASS
+//! [    0.112351]       Spurious IRQs              SKIP (Not supported by the controller)
+//! [    0.112352]       Memory map consistency     FAIL (Recorded page is not mapped)
+//! [    0.112353]       ---------------------------------------------------------------
+//! [    0.112354]       2 passed, 1 failed, 1 skipped
+//! ```
+//!
+//! It helps with telling flaky hardware apart from kernel bugs. A failing check is reported, but
+//! does not stop the boot.
+
+use crate::{bsp, exception, info, memory, time, warn};
+use core::time::Duration;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The number of back-to-back uptime samples that must not go backwards.
+const NUM_TIMER_SAMPLES: usize = 1000;
+
+/// A spin of this duration must advance the uptime by as much.
+const TIMER_SPIN: Duration = Duration::from_millis(1);
+
+/// The outcome of a single check.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Outcome {
+    Pass,
+    Fail(&'static str),
+
+    /// The check does not apply to the board.
+    Skip(&'static str),
+}
+
+struct Check {
+    name: &'static str,
+    run: fn() -> Outcome,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static CHECKS: [Check; 4] = [
+    Check {
+        name: "UART loopback",
+        run: check_uart,
+    },
+    Check {
+        name: "Timer monotonicity",
+        run: check_timer,
+    },
+    Check {
+        name: "Spurious IRQs",
+        run: check_spurious_irqs,
+    },
+    Check {
+        name: "Memory map consistency",
+        run: check_memory_map,
+    },
+];
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl From<Result<(), &'static str>> for Outcome {
+    fn from(result: Result<(), &'static str>) -> Self {
+        match result {
+            Ok(()) => Self::Pass,
+            Err(x) => Self::Fail(x),
+        }
+    }
+}
+
+/// The UART's registers hold what was programmed, and it receives what it sends.
+fn check_uart() -> Outcome {
+    bsp::console::uart_self_test().into()
+}
+
+/// The uptime never goes backwards, and advances while spinning.
+fn check_timer() -> Outcome {
+    use time::interface::TimeManager;
+
+    let mut previous = time::time_manager().uptime();
+    for _ in 0..NUM_TIMER_SAMPLES {
+        let now = time::time_manager().uptime();
+        if now < previous {
+            return Outcome::Fail("Uptime went backwards");
+        }
+
+        previous = now;
+    }
+
+    if let Err(x) = time::time_manager().spin_for(TIMER_SPIN) {
+        return Outcome::Fail(x);
+    }
+
+    if time::time_manager().uptime() < previous + TIMER_SPIN {
+        return Outcome::Fail("Uptime did not advance while spinning");
+    }
+
+    Outcome::Pass
+}
+
+/// The interrupt that the executing core would take next, if any, has a handler.
+fn check_spurious_irqs() -> Outcome {
+    use exception::asynchronous::interface::IRQManager;
+
+    match bsp::exception::asynchronous::irq_manager().check_pending_irq() {
+        None => Outcome::Skip("Not supported by the controller"),
+        Some(x) => x.into(),
+    }
+}
+
+/// The recorded kernel mappings match the translation tables.
+fn check_memory_map() -> Outcome {
+    memory::mmu::kernel_check_mappings().into()
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Run all checks and print the summary. Returns whether no check failed.
+///
+/// Should run with local IRQs masked, so that the interrupt check sees the pending interrupts
+/// before they are handled.
+pub fn run() -> bool {
+    let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+
+    info!("POST:");
+    info!("      ---------------------------------------------------------------");
+    info!("      {:<26} Result", "Check");
+    info!("      ---------------------------------------------------------------");
+
+    for check in CHECKS.iter() {
+        match (check.run)() {
+            Outcome::Pass => {
+                passed += 1;
+                info!("      {:<26} PASS", check.name);
+            }
+            Outcome::Fail(x) => {
+                failed += 1;
+                warn!("      {:<26} FAIL ({})", check.name, x);
+            }
+            Outcome::Skip(x) => {
+                skipped += 1;
+                info!("      {:<26} SKIP ({})", check.name, x);
+            }
+        }
+    }
+
+    info!("      ---------------------------------------------------------------");
+    info!(
+        "      {} passed, {} failed, {} skipped",
+        passed, failed, skipped
+    );
+
+    failed == 0
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
+
+    /// The checks that do not depend on device features that QEMU may lack pass.
+    #[kernel_test]
+    fn timer_and_memory_map_checks_pass() {
+        assert_eq!(check_timer(), Outcome::Pass);
+        assert_eq!(check_memory_map(), Outcome::Pass);
+    }
+}
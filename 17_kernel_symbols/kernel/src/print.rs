@@ -3,19 +3,303 @@
 // Copyright (c) 2018-2022 Andre Richter <andre.o.richter@gmail.com>
 
 //! Printing.
+//!
+//! Log messages have a [`Level`]. Messages above [`MAX_LEVEL`] are compiled out, and messages above
+//! the runtime level set with [`set_level()`] are dropped.
+//!
+//! Log messages are also kept in an in-memory log, which [`dmesg()`] replays. Messages that are
+//! logged before a console sink is registered are only kept there.
+//!
+//! On the console, log messages are colored by level with ANSI escape sequences, unless disabled
+//! with [`set_colors_enabled()`]. The in-memory log never contains colors.
+//!
+//! The timestamp in front of log messages shows the uptime by default, and can be switched to raw
+//! counter ticks, the time since the previous message, or the wall clock time with
+//! [`set_timestamp_format()`].
+//!
+//! The `*_ratelimited!` macros drop messages that are logged too often from the same call site.
 
-use crate::{bsp, console};
-use core::fmt;
+mod log_buffer;
+mod rate_limit;
+
+use crate::{console, time};
+use core::{
+    fmt,
+    sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering},
+    time::Duration,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Public Reexports
+//--------------------------------------------------------------------------------------------------
+pub use rate_limit::RateLimit;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The size of the in-memory log, in bytes.
+const LOG_BUFFER_SIZE: usize = 16 * 1024;
+
+const ANSI_RED: &str = "\x1b[31m";
+const ANSI_YELLOW: &str = "\x1b[33m";
+const ANSI_DIM: &str = "\x1b[2m";
+const ANSI_NORMAL_INTENSITY: &str = "\x1b[22m";
+const ANSI_RESET: &str = "\x1b[0m";
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The severity of a log message, from most to least severe.
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+/// The format of the timestamp in front of log messages.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TimestampFormat {
+    /// Seconds since power-on, with microsecond resolution.
+    Uptime,
+
+    /// The raw value of the architectural counter.
+    Ticks,
+
+    /// The time since the previous log message, on any core.
+    Delta,
+
+    /// The UTC time of day, with millisecond resolution. Falls back to the uptime while the wall
+    /// clock is not set.
+    WallClock,
+}
+
+/// The most verbose level that is compiled in. Trace messages need the `log_trace` feature.
+#[cfg(feature = "log_trace")]
+pub const MAX_LEVEL: Level = Level::Trace;
+
+/// The most verbose level that is compiled in. Trace messages need the `log_trace` feature.
+#[cfg(not(feature = "log_trace"))]
+pub const MAX_LEVEL: Level = Level::Debug;
+
+/// The number of messages a rate limited call site can log in a burst.
+pub const RATELIMIT_BURST: u32 = 10;
+
+/// The time after which a rate limited call site can log one more message.
+pub const RATELIMIT_REFILL_PERIOD: Duration = Duration::from_millis(500);
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+static COLORS_ENABLED: AtomicBool = AtomicBool::new(true);
+
+static TIMESTAMP_FORMAT: AtomicU8 = AtomicU8::new(TimestampFormat::Uptime as u8);
+
+/// The uptime of the previous log message, in nanoseconds.
+static LAST_LOG_NANOS: AtomicU64 = AtomicU64::new(0);
+
+static KERNEL_LOG: log_buffer::LogBuffer<LOG_BUFFER_SIZE> = log_buffer::LogBuffer::new();
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl Level {
+    /// The marker in front of the timestamp.
+    fn prefix(self) -> char {
+        match self {
+            Level::Error => 'E',
+            Level::Warn => 'W',
+            Level::Info => ' ',
+            Level::Debug => 'D',
+            Level::Trace => 'T',
+        }
+    }
+
+    fn color(self) -> &'static str {
+        match self {
+            Level::Error => ANSI_RED,
+            Level::Warn => ANSI_YELLOW,
+            _ => "",
+        }
+    }
+}
+
+/// The timestamp of a log message, formatted to a width of 10 characters, or 12 for the wall clock.
+enum Timestamp {
+    Uptime(Duration),
+    Ticks(u64),
+    Delta(Duration),
+    WallClock(time::DateTime),
+}
+
+impl Timestamp {
+    fn now() -> Self {
+        use time::interface::TimeManager;
+
+        let uptime = time::time_manager().uptime();
+        let last = LAST_LOG_NANOS.swap(uptime.as_nanos() as u64, Ordering::Relaxed);
+
+        match timestamp_format() {
+            TimestampFormat::Uptime => Timestamp::Uptime(uptime),
+            TimestampFormat::Ticks => Timestamp::Ticks(time::counter_ticks()),
+            TimestampFormat::Delta => {
+                Timestamp::Delta(uptime.saturating_sub(Duration::from_nanos(last)))
+            }
+            TimestampFormat::WallClock => match time::calendar_time() {
+                None => Timestamp::Uptime(uptime),
+                Some(x) => Timestamp::WallClock(x),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Timestamp::Uptime(x) => write!(f, "{:>3}.{:06}", x.as_secs(), x.subsec_micros()),
+            Timestamp::Ticks(x) => write!(f, "{:>10}", x),
+            Timestamp::Delta(x) => write!(f, "+{:>2}.{:06}", x.as_secs(), x.subsec_micros()),
+            Timestamp::WallClock(x) => write!(
+                f,
+                "{:02}:{:02}:{:02}.{:03}",
+                x.hour,
+                x.minute,
+                x.second,
+                x.nanosecond / 1_000_000
+            ),
+        }
+    }
+}
+
+/// Prints raw bytes, one character per byte.
+struct Bytes<'a>(&'a [u8]);
+
+impl fmt::Display for Bytes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use fmt::Write;
+
+        self.0.iter().try_for_each(|&b| f.write_char(b as char))
+    }
+}
 
 //--------------------------------------------------------------------------------------------------
 // Public Code
 //--------------------------------------------------------------------------------------------------
 
+/// Set the most verbose level that is printed. Levels above [`MAX_LEVEL`] stay compiled out.
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Return the most verbose level that is printed.
+pub fn level() -> Level {
+    match LEVEL.load(Ordering::Relaxed) {
+        0 => Level::Error,
+        1 => Level::Warn,
+        2 => Level::Info,
+        3 => Level::Debug,
+        _ => Level::Trace,
+    }
+}
+
+/// Return whether messages of the given level are printed.
+#[inline(always)]
+pub fn level_enabled(level: Level) -> bool {
+    level <= MAX_LEVEL && level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
 #[doc(hidden)]
 pub fn _print(args: fmt::Arguments) {
     use console::interface::Write;
 
-    bsp::console::console().write_fmt(args).unwrap();
+    console::console().write_fmt(args).unwrap();
+}
+
+/// Enable or disable colored log messages on the console.
+pub fn set_colors_enabled(enabled: bool) {
+    COLORS_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether colored output is enabled on the console.
+pub fn colors_enabled() -> bool {
+    COLORS_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Set the format of the timestamp in front of log messages.
+pub fn set_timestamp_format(format: TimestampFormat) {
+    TIMESTAMP_FORMAT.store(format as u8, Ordering::Relaxed);
+}
+
+/// Return the format of the timestamp in front of log messages.
+pub fn timestamp_format() -> TimestampFormat {
+    match TIMESTAMP_FORMAT.load(Ordering::Relaxed) {
+        0 => TimestampFormat::Uptime,
+        1 => TimestampFormat::Ticks,
+        2 => TimestampFormat::Delta,
+        _ => TimestampFormat::WallClock,
+    }
+}
+
+#[doc(hidden)]
+pub fn _log(level: Level, args: fmt::Arguments) {
+    let timestamp = Timestamp::now();
+
+    KERNEL_LOG.write_fmt(format_args_nl!(
+        "[{} {}] {}",
+        level.prefix(),
+        timestamp,
+        args
+    ));
+
+    if COLORS_ENABLED.load(Ordering::Relaxed) {
+        _print(format_args_nl!(
+            "{}[{} {}{}{}] {}{}",
+            level.color(),
+            level.prefix(),
+            ANSI_DIM,
+            timestamp,
+            ANSI_NORMAL_INTENSITY,
+            args,
+            ANSI_RESET
+        ));
+    } else {
+        _print(format_args_nl!(
+            "[{} {}] {}",
+            level.prefix(),
+            timestamp,
+            args
+        ));
+    }
+}
+
+#[doc(hidden)]
+pub fn _log_ratelimited(rate_limit: &RateLimit, level: Level, args: fmt::Arguments) {
+    use time::interface::TimeManager;
+
+    match rate_limit.check(time::time_manager().uptime()) {
+        None => (),
+        Some(0) => _log(level, args),
+        Some(suppressed) => {
+            _log(level, format_args!("{} messages suppressed", suppressed));
+            _log(level, args);
+        }
+    }
+}
+
+/// Print the in-memory log, oldest messages first.
+pub fn dmesg() {
+    KERNEL_LOG.replay(|bytes| _print(format_args!("{}", Bytes(bytes))));
 }
 
 /// Prints without a newline.
@@ -37,58 +321,124 @@ macro_rules! println {
     })
 }
 
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log {
+    ($level:ident, $($arg:tt)*) => ({
+        if $crate::print::level_enabled($crate::print::Level::$level) {
+            $crate::print::_log($crate::print::Level::$level, format_args!($($arg)*));
+        }
+    })
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log_ratelimited {
+    ($level:ident, $($arg:tt)*) => ({
+        static RATE_LIMIT: $crate::print::RateLimit = $crate::print::RateLimit::new(
+            $crate::print::RATELIMIT_BURST,
+            $crate::print::RATELIMIT_REFILL_PERIOD,
+        );
+
+        if $crate::print::level_enabled($crate::print::Level::$level) {
+            $crate::print::_log_ratelimited(
+                &RATE_LIMIT,
+                $crate::print::Level::$level,
+                format_args!($($arg)*),
+            );
+        }
+    })
+}
+
+/// Prints an error, with a newline.
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => ($crate::__log!(Error, $($arg)*));
+}
+
+/// Prints a warning, with a newline.
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => ($crate::__log!(Warn, $($arg)*));
+}
+
 /// Prints an info, with a newline.
 #[macro_export]
 macro_rules! info {
-    ($string:expr) => ({
-        use $crate::time::interface::TimeManager;
+    ($($arg:tt)*) => ($crate::__log!(Info, $($arg)*));
+}
 
-        let timestamp = $crate::time::time_manager().uptime();
+/// Prints a debug message, with a newline.
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => ($crate::__log!(Debug, $($arg)*));
+}
 
-        $crate::print::_print(format_args_nl!(
-            concat!("[  {:>3}.{:06}] ", $string),
-            timestamp.as_secs(),
-            timestamp.subsec_micros(),
-        ));
-    });
-    ($format_string:expr, $($arg:tt)*) => ({
-        use $crate::time::interface::TimeManager;
+/// Prints a trace message, with a newline. Compiled out unless the `log_trace` feature is enabled.
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => ($crate::__log!(Trace, $($arg)*));
+}
 
-        let timestamp = $crate::time::time_manager().uptime();
+/// Prints an error, with a newline. Drops messages that this call site logs too often.
+#[macro_export]
+macro_rules! error_ratelimited {
+    ($($arg:tt)*) => ($crate::__log_ratelimited!(Error, $($arg)*));
+}
 
-        $crate::print::_print(format_args_nl!(
-            concat!("[  {:>3}.{:06}] ", $format_string),
-            timestamp.as_secs(),
-            timestamp.subsec_micros(),
-            $($arg)*
-        ));
-    })
+/// Prints a warning, with a newline. Drops messages that this call site logs too often.
+#[macro_export]
+macro_rules! warn_ratelimited {
+    ($($arg:tt)*) => ($crate::__log_ratelimited!(Warn, $($arg)*));
 }
 
-/// Prints a warning, with a newline.
+/// Prints an info, with a newline. Drops messages that this call site logs too often.
 #[macro_export]
-macro_rules! warn {
-    ($string:expr) => ({
-        use $crate::time::interface::TimeManager;
+macro_rules! info_ratelimited {
+    ($($arg:tt)*) => ($crate::__log_ratelimited!(Info, $($arg)*));
+}
 
-        let timestamp = $crate::time::time_manager().uptime();
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
 
-        $crate::print::_print(format_args_nl!(
-            concat!("[W {:>3}.{:06}] ", $string),
-            timestamp.as_secs(),
-            timestamp.subsec_micros(),
-        ));
-    });
-    ($format_string:expr, $($arg:tt)*) => ({
-        use $crate::time::interface::TimeManager;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
 
-        let timestamp = $crate::time::time_manager().uptime();
+    /// The runtime level must filter out more verbose messages only.
+    #[kernel_test]
+    fn runtime_level_filters_verbose_messages() {
+        let saved = level();
 
-        $crate::print::_print(format_args_nl!(
-            concat!("[W {:>3}.{:06}] ", $format_string),
-            timestamp.as_secs(),
-            timestamp.subsec_micros(),
-            $($arg)*
-        ));
-    })
+        set_level(Level::Warn);
+        assert!(level_enabled(Level::Error));
+        assert!(level_enabled(Level::Warn));
+        assert!(!level_enabled(Level::Info));
+
+        set_level(Level::Debug);
+        assert!(level_enabled(Level::Debug));
+        assert!(!level_enabled(Level::Trace));
+
+        set_level(saved);
+    }
+
+    /// The timestamp format must be switchable at runtime.
+    #[kernel_test]
+    fn timestamp_format_is_switchable() {
+        let saved = timestamp_format();
+
+        for format in [
+            TimestampFormat::Ticks,
+            TimestampFormat::Delta,
+            TimestampFormat::WallClock,
+            TimestampFormat::Uptime,
+        ] {
+            set_timestamp_format(format);
+            assert_eq!(timestamp_format(), format);
+        }
+
+        set_timestamp_format(saved);
+    }
 }
@@ -0,0 +1,159 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! In-memory kernel log.
+//!
+//! Keeps the most recent log output, so that it can be replayed later, e.g. messages that were
+//! logged before the console was up. When the buffer is full, the oldest output is overwritten.
+
+use crate::synchronization::{interface::Mutex, SpinLock};
+use core::fmt;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+struct LogBufferInner<const N: usize> {
+    buf: [u8; N],
+
+    /// The total number of bytes ever written.
+    written: usize,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A byte ring buffer for log output.
+pub struct LogBuffer<const N: usize> {
+    inner: SpinLock<LogBufferInner<N>>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl<const N: usize> LogBufferInner<N> {
+    const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            written: 0,
+        }
+    }
+
+    /// The position of the oldest byte that was not overwritten yet.
+    fn oldest(&self) -> usize {
+        self.written.saturating_sub(N)
+    }
+
+    /// Copy the bytes starting at `pos` into `out`. Returns the position of the first copied byte,
+    /// which is later than `pos` if the bytes at `pos` were overwritten, and the number of copied
+    /// bytes.
+    fn copy_from(&self, pos: usize, out: &mut [u8]) -> (usize, usize) {
+        let start = pos.max(self.oldest());
+        let len = out.len().min(self.written.saturating_sub(start));
+
+        for (i, byte) in out[..len].iter_mut().enumerate() {
+            *byte = self.buf[(start + i) % N];
+        }
+
+        (start, len)
+    }
+}
+
+impl<const N: usize> fmt::Write for LogBufferInner<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.buf[self.written % N] = byte;
+            self.written += 1;
+        }
+
+        Ok(())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl<const N: usize> LogBuffer<N> {
+    /// Create an instance.
+    pub const fn new() -> Self {
+        Self {
+            inner: SpinLock::new(LogBufferInner::new()),
+        }
+    }
+
+    /// Append formatted output.
+    pub fn write_fmt(&self, args: fmt::Arguments) {
+        self.inner
+            .lock(|inner| fmt::Write::write_fmt(inner, args))
+            .unwrap();
+    }
+
+    /// Pass the buffered output to `f`, oldest first, in chunks.
+    ///
+    /// The lock is only held while copying a chunk, so `f` can print, and even log.
+    pub fn replay(&self, mut f: impl FnMut(&[u8])) {
+        let mut chunk = [0u8; 128];
+
+        // Output that is logged meanwhile is not replayed, so that this terminates.
+        let (mut pos, end) = self.inner.lock(|inner| (inner.oldest(), inner.written));
+
+        // Skip the first line if its start was overwritten.
+        let mut at_line_start = pos == 0;
+
+        while pos < end {
+            let (start, len) = self.inner.lock(|inner| inner.copy_from(pos, &mut chunk));
+            if start != pos {
+                at_line_start = false;
+            }
+            let len = len.min(end.saturating_sub(start));
+            if len == 0 {
+                break;
+            }
+            pos = start + len;
+
+            let mut bytes = &chunk[..len];
+            if !at_line_start {
+                match bytes.iter().position(|&b| b == b'\n') {
+                    None => continue,
+                    Some(i) => bytes = &bytes[i + 1..],
+                }
+                at_line_start = true;
+            }
+
+            f(bytes);
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
+
+    /// Replay must return complete lines only, once older output was overwritten.
+    #[kernel_test]
+    fn replay_skips_overwritten_line() {
+        let log: LogBuffer<16> = LogBuffer::new();
+
+        log.write_fmt(format_args!("first line\n"));
+        log.write_fmt(format_args!("second\n"));
+        log.write_fmt(format_args!("third\n"));
+
+        let mut replayed = [0u8; 16];
+        let mut len = 0;
+        log.replay(|bytes| {
+            replayed[len..len + bytes.len()].copy_from_slice(bytes);
+            len += bytes.len();
+        });
+
+        assert_eq!(&replayed[..len], b"second\nthird\n");
+    }
+}
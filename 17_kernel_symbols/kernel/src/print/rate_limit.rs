@@ -0,0 +1,122 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Rate limiting for log messages.
+//!
+//! A message that is logged in a loop, e.g. from an IRQ storm or a recurring fault, can keep the
+//! console busy and push the message that explains the cause out of view. The `*_ratelimited!`
+//! macros give each call site a token bucket, and drop messages while it is empty.
+
+use crate::synchronization::{interface::Mutex, SpinLock};
+use core::time::Duration;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+struct RateLimitInner {
+    tokens: u32,
+
+    /// The uptime at which the last token was added.
+    last_refill: Duration,
+
+    /// The number of messages dropped since the last one that passed.
+    suppressed: usize,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A token bucket that holds up to `burst` tokens, and gains one token per `refill_period`.
+pub struct RateLimit {
+    burst: u32,
+    refill_period: Duration,
+    inner: SpinLock<RateLimitInner>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl RateLimitInner {
+    fn refill(&mut self, now: Duration, burst: u32, refill_period: Duration) {
+        let elapsed = now.saturating_sub(self.last_refill);
+        let new_tokens = elapsed.as_nanos() / refill_period.as_nanos().max(1);
+
+        if new_tokens == 0 {
+            return;
+        }
+
+        if new_tokens >= u128::from(burst - self.tokens) {
+            self.tokens = burst;
+            self.last_refill = now;
+        } else {
+            self.tokens += new_tokens as u32;
+            self.last_refill += refill_period * new_tokens as u32;
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl RateLimit {
+    /// Create an instance with a full bucket.
+    pub const fn new(burst: u32, refill_period: Duration) -> Self {
+        Self {
+            burst,
+            refill_period,
+            inner: SpinLock::new(RateLimitInner {
+                tokens: burst,
+                last_refill: Duration::ZERO,
+                suppressed: 0,
+            }),
+        }
+    }
+
+    /// Take a token at uptime `now`.
+    ///
+    /// Returns `None` if the bucket is empty. Otherwise, returns the number of messages that were
+    /// dropped since the last successful call.
+    pub fn check(&self, now: Duration) -> Option<usize> {
+        self.inner.lock(|inner| {
+            inner.refill(now, self.burst, self.refill_period);
+
+            if inner.tokens == 0 {
+                inner.suppressed += 1;
+                return None;
+            }
+            inner.tokens -= 1;
+
+            Some(core::mem::take(&mut inner.suppressed))
+        })
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
+
+    /// A burst must pass, further messages must be dropped and counted until a token was refilled.
+    #[kernel_test]
+    fn rate_limit_drops_and_counts() {
+        let limit = RateLimit::new(2, Duration::from_millis(100));
+        let start = Duration::from_secs(1);
+
+        assert_eq!(limit.check(start), Some(0));
+        assert_eq!(limit.check(start), Some(0));
+        assert_eq!(limit.check(start), None);
+        assert_eq!(limit.check(start + Duration::from_millis(50)), None);
+
+        assert_eq!(limit.check(start + Duration::from_millis(100)), Some(2));
+        assert_eq!(limit.check(start + Duration::from_millis(150)), None);
+    }
+}
@@ -0,0 +1,118 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Exiting QEMU from test builds.
+//!
+//! Tests report their result through the exit code of the host QEMU binary. How the kernel makes
+//! QEMU exit depends on the emulated machine, so the BSP chooses one of the backends:
+//!
+//! - [`Semihosting`]: The `SYS_EXIT` semihosting call. Works on every machine, but QEMU must run
+//!   with `-semihosting`.
+//! - [`MmioExit`]: A write to an exit device, like `isa-debug-exit` or `sifive_test`. The device
+//!   must be mapped by the BSP.
+
+#[cfg(target_arch = "aarch64")]
+#[path = "_arch/aarch64/qemu_exit.rs"]
+mod arch_qemu_exit;
+
+use crate::{
+    bsp, cpu,
+    memory::{Address, Virtual},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// QEMU exit interfaces.
+pub mod interface {
+    /// A way of making the host QEMU binary exit.
+    pub trait Exit {
+        /// Make the host QEMU binary exit with `code`.
+        fn exit(&self, code: u32) -> !;
+
+        /// Make the host QEMU binary exit with a code that the test harness reads as success.
+        fn exit_success(&self) -> ! {
+            self.exit(0)
+        }
+
+        /// Make the host QEMU binary exit with a code that the test harness reads as failure.
+        fn exit_failure(&self) -> ! {
+            self.exit(1)
+        }
+    }
+}
+
+/// Exit with a semihosting call.
+pub struct Semihosting;
+
+/// Exit with a write to a memory-mapped exit device.
+pub struct MmioExit {
+    addr: Address<Virtual>,
+    encode: fn(u32) -> u32,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl interface::Exit for Semihosting {
+    fn exit(&self, code: u32) -> ! {
+        arch_qemu_exit::semihosting_exit(code)
+    }
+}
+
+impl MmioExit {
+    /// An `isa-debug-exit` style device. QEMU exits with `(value << 1) | 1` for a written `value`,
+    /// so it can not exit with zero. The test harness must be told which code means success.
+    pub const fn isa_debug_exit(addr: Address<Virtual>) -> Self {
+        Self {
+            addr,
+            encode: |code| code,
+        }
+    }
+
+    /// A `sifive_test` style device. QEMU exits with zero for `0x5555`, and with `code` for
+    /// `(code << 16) | 0x3333`.
+    pub const fn sifive_test(addr: Address<Virtual>) -> Self {
+        Self {
+            addr,
+            encode: |code| match code {
+                0 => 0x5555,
+                _ => (code << 16) | 0x3333,
+            },
+        }
+    }
+}
+
+impl interface::Exit for MmioExit {
+    fn exit(&self, code: u32) -> ! {
+        let value = (self.encode)(code);
+        unsafe { core::ptr::write_volatile(self.addr.as_usize() as *mut u32, value) };
+
+        // The device may not take effect immediately.
+        cpu::wait_forever()
+    }
+}
+
+/// Make the host QEMU binary exit with `code`.
+pub fn exit(code: u32) -> ! {
+    use interface::Exit;
+
+    bsp::cpu::qemu_exit_backend().exit(code)
+}
+
+/// Make the host QEMU binary exit with a code that the test harness reads as success.
+pub fn exit_success() -> ! {
+    use interface::Exit;
+
+    bsp::cpu::qemu_exit_backend().exit_success()
+}
+
+/// Make the host QEMU binary exit with a code that the test harness reads as failure.
+pub fn exit_failure() -> ! {
+    use interface::Exit;
+
+    bsp::cpu::qemu_exit_backend().exit_failure()
+}
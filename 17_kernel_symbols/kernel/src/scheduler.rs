@@ -0,0 +1,792 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Kernel threads.
+//!
+//! The scheduler runs the runnable thread with the highest priority, and threads of equal priority
+//! round-robin. A thread runs until it calls [`yield_now()`], returns from
+//! its entry function, or is preempted because its time slice of one timer tick ended. Code that
+//! runs with IRQs masked, e.g. while holding a `SpinLock`, is never preempted.
+//!
+//! A blocked thread that is woken with [`unblock()`], usually by an IRQ handler, gets its priority
+//! boosted until it is switched out again. This keeps the latency of driver threads low.
+//!
+//! A thread that calls [`sleep_until()`] is parked until the timer tick finds its deadline expired.
+//!
+//! Each core has its own run queue, which consists of the threads assigned to the core. A thread
+//! is assigned to the core that spawned it, unless it was pinned to a core with [`spawn_on()`]. A
+//! core whose run queue has no runnable thread steals one that is not pinned from another core.
+//! Each core's thread pointer register holds the index of the thread it is running.
+//!
+//! A core that has no runnable thread switches to its idle thread, which sleeps until the next
+//! interrupt. The initial thread of each core becomes the core's idle thread by calling [`idle()`].
+//!
+//! The code that calls [`init()`] becomes the main thread and keeps using its current stack. In
+//! the same way, each secondary core turns its boot code into a thread with [`init_secondary()`].
+//! All other threads get one of the statically allocated thread stacks.
+//!
+//! A thread that was switched out is still executing on its core until its registers are saved. The
+//! thread that is switched to finishes the switch, and only then the previous thread can be stolen
+//! by another core, or the slot of an exited thread can be reused.
+
+use crate::{
+    bsp,
+    cpu::{self, PerCpu, ThreadContext},
+    debug, exception, info,
+    synchronization::{interface::Mutex, SpinLock},
+    time,
+};
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const THREAD_STACK_SIZE: usize = 16 * 1024;
+
+/// The index of the main thread.
+const MAIN_THREAD: usize = 0;
+
+/// Added to the priority of a thread that was woken from being blocked.
+const PRIORITY_BOOST: u8 = 4;
+
+#[repr(C, align(16))]
+struct ThreadStack(UnsafeCell<[u8; THREAD_STACK_SIZE]>);
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum ThreadState {
+    /// The slot is unused.
+    Free,
+    Runnable,
+    Running,
+
+    /// Parked until the uptime reaches the deadline.
+    Sleeping(Duration),
+
+    /// Parked until [`unblock()`] is called for the thread.
+    Blocked,
+
+    /// Exited, but possibly still executing on its stack. Freed when the switch away finished.
+    Zombie,
+}
+
+/// A kernel thread.
+struct Thread {
+    state: ThreadState,
+    name: &'static str,
+    entry: Option<fn()>,
+
+    /// The core whose run queue holds the thread.
+    core: usize,
+
+    /// Pinned threads are never stolen by other cores.
+    pinned: bool,
+
+    /// Idle threads only run if the core has nothing else to do.
+    idle: bool,
+
+    /// Higher values are scheduled first.
+    priority: u8,
+
+    /// Set when the thread was woken from being blocked, cleared when it is switched out.
+    boosted: bool,
+
+    /// Set while a core executes the thread, until the switch away from it finished.
+    on_cpu: bool,
+
+    /// Valid while the thread is not running.
+    context: ThreadContext,
+}
+
+struct Scheduler {
+    threads: [Thread; MAX_THREADS],
+    initialized: bool,
+
+    /// For each core, the thread it is switching away from, until the switch finished.
+    switched_from: [Option<usize>; bsp::cpu::NUM_CORES],
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Identifies a thread.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ThreadId(usize);
+
+/// The priority of newly spawned threads.
+pub const DEFAULT_PRIORITY: u8 = 8;
+
+/// The maximum number of threads, including the main thread.
+pub const MAX_THREADS: usize = 8;
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_RESCHED: AtomicBool = AtomicBool::new(false);
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_STACK: ThreadStack = ThreadStack(UnsafeCell::new([0; THREAD_STACK_SIZE]));
+
+/// Stacks for all threads but the main thread.
+static STACKS: [ThreadStack; MAX_THREADS - 1] = [EMPTY_STACK; MAX_THREADS - 1];
+
+static SCHEDULER: SpinLock<Scheduler> = SpinLock::new(Scheduler::new());
+
+/// Set by the timer tick of each core. The running thread is preempted when returning from the IRQ.
+static NEED_RESCHED: PerCpu<AtomicBool> = PerCpu::new([NO_RESCHED; bsp::cpu::NUM_CORES]);
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+// Each stack is only used by the thread it was handed out to.
+unsafe impl Sync for ThreadStack {}
+
+impl Thread {
+    const fn new() -> Self {
+        Self {
+            state: ThreadState::Free,
+            name: "",
+            entry: None,
+            core: 0,
+            pinned: false,
+            idle: false,
+            priority: DEFAULT_PRIORITY,
+            boosted: false,
+            on_cpu: false,
+            context: ThreadContext::new(),
+        }
+    }
+
+    /// The priority used for scheduling decisions.
+    fn effective_priority(&self) -> u8 {
+        if self.boosted {
+            self.priority.saturating_add(PRIORITY_BOOST)
+        } else {
+            self.priority
+        }
+    }
+}
+
+impl Scheduler {
+    const FREE_THREAD: Thread = Thread::new();
+
+    const fn new() -> Self {
+        Self {
+            threads: [Self::FREE_THREAD; MAX_THREADS],
+            initialized: false,
+            switched_from: [None; bsp::cpu::NUM_CORES],
+        }
+    }
+
+    /// Make the sleeping threads whose deadline expired runnable again.
+    fn wake_sleepers(&mut self, now: Duration) {
+        for thread in self.threads.iter_mut() {
+            if let ThreadState::Sleeping(deadline) = thread.state {
+                if deadline <= now {
+                    thread.state = ThreadState::Runnable;
+                }
+            }
+        }
+    }
+
+    /// Return the earliest deadline of all sleeping threads.
+    fn next_wakeup(&self) -> Option<Duration> {
+        self.threads
+            .iter()
+            .filter_map(|thread| match thread.state {
+                ThreadState::Sleeping(deadline) => Some(deadline),
+                _ => None,
+            })
+            .min()
+    }
+
+    /// Return the first of the candidates with the highest effective priority.
+    fn highest_priority(&self, candidates: impl Iterator<Item = usize>) -> Option<usize> {
+        candidates.fold(None, |best, i| match best {
+            Some(b)
+                if self.threads[b].effective_priority() >= self.threads[i].effective_priority() =>
+            {
+                Some(b)
+            }
+            _ => Some(i),
+        })
+    }
+
+    /// Pick the runnable thread with the highest priority from the executing core's run queue, or
+    /// steal one from another core. Threads of equal priority are picked round-robin. Falls back to
+    /// the core's idle thread if the previous thread can not continue.
+    ///
+    /// Threads that another core did not finish switching away from yet are skipped.
+    fn pick_next(&mut self, prev: usize) -> Option<usize> {
+        let core: usize = cpu::smp::core_id();
+        let candidates = (1..=MAX_THREADS).map(|i| (prev + i) % MAX_THREADS);
+        let runnable = |thread: &Thread| {
+            thread.state == ThreadState::Runnable && !thread.on_cpu && !thread.idle
+        };
+
+        let next = self
+            .highest_priority(
+                candidates
+                    .clone()
+                    .filter(|&i| runnable(&self.threads[i]) && self.threads[i].core == core),
+            )
+            .or_else(|| {
+                self.highest_priority(
+                    candidates
+                        .clone()
+                        .filter(|&i| runnable(&self.threads[i]) && !self.threads[i].pinned),
+                )
+            });
+
+        let prev_continues = self.threads[prev].state == ThreadState::Running;
+        if let Some(next) = next {
+            // A running thread only gives way to threads of at least its own priority.
+            if prev_continues
+                && self.threads[next].effective_priority() < self.threads[prev].effective_priority()
+            {
+                return None;
+            }
+
+            self.threads[next].core = core;
+
+            return Some(next);
+        }
+
+        if prev_continues {
+            return None;
+        }
+
+        candidates.find(|&i| {
+            let thread = &self.threads[i];
+
+            thread.idle
+                && thread.core == core
+                && thread.state == ThreadState::Runnable
+                && !thread.on_cpu
+        })
+    }
+
+    /// Make the running thread runnable and pick the next thread.
+    ///
+    /// Returns the contexts to switch between, or `None` if the running thread should continue.
+    fn switch_to_next(&mut self) -> Option<(*mut ThreadContext, *const ThreadContext)> {
+        if !self.initialized {
+            return None;
+        }
+
+        let prev = current().0;
+        let next = self.pick_next(prev)?;
+
+        if self.threads[prev].state == ThreadState::Running {
+            self.threads[prev].state = ThreadState::Runnable;
+        }
+        self.threads[prev].boosted = false;
+        self.threads[next].state = ThreadState::Running;
+        self.threads[next].on_cpu = true;
+        self.switched_from[cpu::smp::core_id::<usize>()] = Some(prev);
+
+        Some((
+            &mut self.threads[prev].context as *mut _,
+            &self.threads[next].context as *const _,
+        ))
+    }
+
+    /// Release the thread that the executing core switched away from. Its context is saved now, so
+    /// it can run on any core again, and its slot can be reused if it exited.
+    fn finish_switch(&mut self) {
+        let prev = match self.switched_from[cpu::smp::core_id::<usize>()].take() {
+            Some(x) => x,
+            None => return,
+        };
+
+        let thread = &mut self.threads[prev];
+        thread.on_cpu = false;
+        if thread.state == ThreadState::Zombie {
+            thread.state = ThreadState::Free;
+        }
+    }
+}
+
+/// Switch contexts, and finish the switch once the executing thread is switched to again.
+///
+/// # Safety
+///
+/// - Same as [`cpu::switch_to()`].
+unsafe fn switch_to(prev: *mut ThreadContext, next: *const ThreadContext) {
+    cpu::switch_to(prev, next);
+
+    // Executing on the stack of the thread that was switched to now.
+    SCHEDULER.lock(|sched| sched.finish_switch());
+}
+
+/// Park the running thread in the given state and switch to the next runnable thread.
+///
+/// If no other thread is runnable, the running thread continues right away.
+fn park_current(state: ThreadState) {
+    exception::asynchronous::exec_with_irq_masked(|| {
+        let switch = SCHEDULER.lock(|sched| {
+            if !sched.initialized {
+                return None;
+            }
+
+            let current = current().0;
+            sched.threads[current].state = state;
+
+            let switch = sched.switch_to_next();
+            if switch.is_none() {
+                sched.threads[current].state = ThreadState::Running;
+            }
+
+            switch
+        });
+
+        if let Some((prev, next)) = switch {
+            unsafe { switch_to(prev, next) };
+        }
+    });
+}
+
+/// Switch away from the running thread if another thread is runnable.
+fn schedule() {
+    exception::asynchronous::exec_with_irq_masked(|| {
+        if let Some((prev, next)) = SCHEDULER.lock(|sched| sched.switch_to_next()) {
+            unsafe { switch_to(prev, next) };
+        }
+    });
+}
+
+/// Make another core reschedule, so that it picks up a thread that became runnable on its run
+/// queue. The core might be sleeping in its idle thread.
+fn notify_core(core: usize) {
+    if core == cpu::smp::core_id::<usize>() {
+        return;
+    }
+
+    // A full queue means that an IPI is pending already, which wakes the core as well. A core that
+    // is not online yet schedules when it comes up.
+    let _ = cpu::smp::ipi::send(core, cpu::smp::ipi::Message::Reschedule);
+}
+
+/// The first code that a new thread executes.
+extern "C" fn thread_start() -> ! {
+    let entry = SCHEDULER.lock(|sched| {
+        sched.finish_switch();
+        sched.threads[current().0].entry
+    });
+
+    // Threads are switched with IRQs masked.
+    unsafe { exception::asynchronous::local_irq_unmask() };
+
+    if let Some(entry) = entry {
+        entry();
+    }
+
+    exit_current()
+}
+
+/// Terminate the running thread.
+///
+/// The slot stays a zombie until the next thread finished the switch away from the stack.
+fn exit_current() -> ! {
+    SCHEDULER.lock(|sched| {
+        let current = &mut sched.threads[current().0];
+        assert!(
+            current.entry.is_some(),
+            "The initial thread of a core can not exit"
+        );
+
+        current.state = ThreadState::Zombie;
+    });
+
+    // Until the core's initial thread becomes the idle thread, there might be no thread to switch
+    // to, e.g. because the initial thread sleeps. Wait for an interrupt that makes one runnable
+    // then. Like in the idle loop, IRQs are masked so that a wakeup can not slip in before waiting.
+    loop {
+        exception::asynchronous::exec_with_irq_masked(|| {
+            match SCHEDULER.lock(|sched| sched.switch_to_next()) {
+                Some((prev, next)) => unsafe { switch_to(prev, next) },
+                None => cpu::wait_for_interrupt(),
+            }
+        });
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Find a free thread slot. The main thread's slot is never free.
+fn free_slot(sched: &Scheduler) -> Result<usize, &'static str> {
+    (0..MAX_THREADS)
+        .find(|&i| i != MAIN_THREAD && sched.threads[i].state == ThreadState::Free)
+        .ok_or("No free thread slot")
+}
+
+/// Create a thread that executes `entry` and put it on the given core's run queue, waking the core
+/// if it is another one.
+fn spawn_internal(
+    name: &'static str,
+    entry: fn(),
+    core: usize,
+    pinned: bool,
+) -> Result<ThreadId, &'static str> {
+    if core >= bsp::cpu::NUM_CORES {
+        return Err("Core does not exist");
+    }
+
+    SCHEDULER
+        .lock(|sched| {
+            if !sched.initialized {
+                return Err("Scheduler not initialized");
+            }
+
+            let index = free_slot(sched)?;
+            let stack = &STACKS[index - 1];
+            let stack_end_exclusive = stack.0.get() as usize + THREAD_STACK_SIZE;
+
+            let thread = &mut sched.threads[index];
+            thread.state = ThreadState::Runnable;
+            thread.name = name;
+            thread.entry = Some(entry);
+            thread.core = core;
+            thread.pinned = pinned;
+            thread.idle = false;
+            thread.priority = DEFAULT_PRIORITY;
+            thread.boosted = false;
+            thread.on_cpu = false;
+            thread.context =
+                ThreadContext::new_for_start(thread_start, stack_end_exclusive, index as u64);
+
+            Ok(ThreadId(index))
+        })
+        .map(|thread| {
+            debug!("Spawned thread {} ({}) on core {}", thread.0, name, core);
+            notify_core(core);
+            thread
+        })
+}
+
+/// Turn the calling code into the main thread, and allow spawning further threads.
+///
+/// # Safety
+///
+/// - Must be called only once, on the boot core.
+pub unsafe fn init() {
+    // The thread pointer holds the index of the running thread.
+    cpu::set_thread_pointer(MAIN_THREAD as u64);
+
+    SCHEDULER.lock(|sched| {
+        let main = &mut sched.threads[MAIN_THREAD];
+        main.state = ThreadState::Running;
+        main.name = "main";
+        main.core = cpu::smp::core_id();
+        main.pinned = true;
+        main.on_cpu = true;
+
+        sched.initialized = true;
+    });
+}
+
+/// Turn the calling code into the initial thread of a secondary core, which then schedules the
+/// threads of its own run queue.
+///
+/// # Safety
+///
+/// - Must be called only once per secondary core, after [`init()`].
+pub unsafe fn init_secondary(name: &'static str) -> Result<ThreadId, &'static str> {
+    let index = SCHEDULER.lock(|sched| {
+        if !sched.initialized {
+            return Err("Scheduler not initialized");
+        }
+
+        let index = free_slot(sched)?;
+        let thread = &mut sched.threads[index];
+        thread.state = ThreadState::Running;
+        thread.name = name;
+        thread.entry = None;
+        thread.core = cpu::smp::core_id();
+        thread.pinned = true;
+        thread.idle = false;
+        thread.priority = DEFAULT_PRIORITY;
+        thread.boosted = false;
+        thread.on_cpu = true;
+
+        Ok(index)
+    })?;
+
+    cpu::set_thread_pointer(index as u64);
+
+    Ok(ThreadId(index))
+}
+
+/// Create a thread that executes `entry` on the executing core's run queue. It runs for the first
+/// time when another thread yields, and can be stolen by other cores.
+pub fn spawn(name: &'static str, entry: fn()) -> Result<ThreadId, &'static str> {
+    spawn_internal(name, entry, cpu::smp::core_id(), false)
+}
+
+/// Create a thread that executes `entry` and always runs on the given core.
+pub fn spawn_on(core: usize, name: &'static str, entry: fn()) -> Result<ThreadId, &'static str> {
+    spawn_internal(name, entry, core, true)
+}
+
+/// Turn the calling thread into the executing core's idle thread.
+///
+/// The idle thread runs only if no other thread on the core is runnable, and sleeps until the next
+/// interrupt then.
+pub fn idle() -> ! {
+    SCHEDULER.lock(|sched| {
+        let thread = &mut sched.threads[current().0];
+        assert!(
+            thread.entry.is_none(),
+            "Only the initial thread of a core can idle"
+        );
+
+        thread.idle = true;
+    });
+
+    loop {
+        // Check for work with IRQs masked, so that a wakeup can not slip in before sleeping. A
+        // pending IRQ still ends the sleep, and is handled once IRQs are unmasked again.
+        exception::asynchronous::exec_with_irq_masked(|| {
+            match SCHEDULER.lock(|sched| sched.switch_to_next()) {
+                Some((prev, next)) => unsafe { switch_to(prev, next) },
+                // Sleep until the next event, instead of waking up on every tick. Other cores wake
+                // the core with an IPI when they make a thread on its run queue runnable. Without
+                // IPIs, the tick keeps running, so that such threads are picked up within a period.
+                None if bsp::exception::asynchronous::ipi_irq().is_some() => {
+                    time::tick::stop_until(SCHEDULER.lock(|sched| sched.next_wakeup()));
+                    cpu::wait_for_interrupt();
+                    time::tick::resume();
+                }
+                None => cpu::wait_for_interrupt(),
+            }
+        });
+    }
+}
+
+/// Let other runnable threads execute before continuing.
+///
+/// Does nothing if the scheduler is not initialized, or no other thread is runnable.
+pub fn yield_now() {
+    schedule();
+}
+
+/// Wake expired sleepers and request preemption of the running thread. Called on each timer tick
+/// of each core.
+pub fn handle_tick(now: Duration) {
+    SCHEDULER.lock(|sched| sched.wake_sleepers(now));
+
+    NEED_RESCHED.get().store(true, Ordering::Relaxed);
+}
+
+/// Request preemption of the running thread on the executing core, when returning from the IRQ.
+pub fn request_resched() {
+    NEED_RESCHED.get().store(true, Ordering::Relaxed);
+}
+
+/// Switch to the next runnable thread if preemption was requested.
+///
+/// Called by the architectural IRQ handlers after all IRQs were handled. The interrupted thread
+/// continues when it is scheduled again, and returns from the IRQ then.
+pub fn preempt_if_requested() {
+    if NEED_RESCHED.get().swap(false, Ordering::Relaxed) {
+        schedule();
+    }
+}
+
+impl ThreadId {
+    /// Return the thread's index, which is less than [`MAX_THREADS`].
+    pub const fn index(self) -> usize {
+        self.0
+    }
+
+    /// Create the ID of the thread with the given index.
+    pub const fn from_index(index: usize) -> Self {
+        assert!(index < MAX_THREADS);
+
+        Self(index)
+    }
+}
+
+/// Return whether [`init()`] was called.
+pub fn is_initialized() -> bool {
+    SCHEDULER.lock(|sched| sched.initialized)
+}
+
+/// Park the running thread until the uptime reaches `deadline`, and let other threads run.
+///
+/// If no other thread is runnable, the caller spins until the deadline instead.
+///
+/// Sleeping releases the CPU, so the caller must not hold any `SpinLock`.
+pub fn sleep_until(deadline: Duration) {
+    use time::interface::TimeManager;
+
+    while time::time_manager().uptime() < deadline {
+        park_current(ThreadState::Sleeping(deadline));
+    }
+}
+
+/// Park the running thread until [`unblock()`] is called for it, and let other threads run.
+///
+/// Returns right away if no other thread is runnable, so callers must re-check what they wait for.
+/// [`crate::synchronization::WaitQueue`] takes care of that.
+pub fn block_current() {
+    park_current(ThreadState::Blocked);
+}
+
+/// Make a thread that was parked by [`block_current()`] runnable again, with boosted priority.
+///
+/// Does nothing if the thread is not blocked. Can be called from IRQ handlers. If the woken thread
+/// outranks the thread running on the executing core, the latter is preempted when returning from
+/// the IRQ. If the woken thread belongs to another core, that core is told to reschedule.
+pub fn unblock(thread: ThreadId) {
+    let other_core = SCHEDULER.lock(|sched| {
+        let current_priority = sched.threads[current().0].effective_priority();
+        let thread = &mut sched.threads[thread.0];
+
+        if thread.state != ThreadState::Blocked {
+            return None;
+        }
+
+        thread.state = ThreadState::Runnable;
+        thread.boosted = true;
+
+        if thread.core != cpu::smp::core_id::<usize>() {
+            return Some(thread.core);
+        }
+
+        if thread.effective_priority() > current_priority {
+            NEED_RESCHED.get().store(true, Ordering::Relaxed);
+        }
+
+        None
+    });
+
+    if let Some(core) = other_core {
+        notify_core(core);
+    }
+}
+
+/// Change the priority of a thread. Higher values are scheduled first.
+pub fn set_priority(thread: ThreadId, priority: u8) {
+    SCHEDULER.lock(|sched| sched.threads[thread.0].priority = priority);
+}
+
+/// Return the ID of the running thread.
+pub fn current() -> ThreadId {
+    ThreadId(cpu::thread_pointer() as usize)
+}
+
+/// Yield until the given thread exited.
+pub fn join(thread: ThreadId) {
+    while SCHEDULER.lock(|sched| sched.threads[thread.0].state != ThreadState::Free) {
+        yield_now();
+    }
+}
+
+/// Print all threads.
+pub fn print_threads() {
+    SCHEDULER.lock(|sched| {
+        for (i, thread) in sched.threads.iter().enumerate() {
+            if thread.state != ThreadState::Free {
+                info!(
+                    "      {}. {: <16} core {} prio {: >3} {:?}",
+                    i, thread.name, thread.core, thread.priority, thread.state
+                );
+            }
+        }
+    });
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use test_macros::kernel_test;
+
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    fn count_and_yield() {
+        for _ in 0..3 {
+            COUNTER.fetch_add(1, Ordering::Relaxed);
+            yield_now();
+        }
+    }
+
+    /// Check that a spawned thread runs to completion when the main thread yields.
+    #[kernel_test]
+    fn spawned_thread_runs() {
+        let thread = spawn("test", count_and_yield).unwrap();
+        assert_eq!(COUNTER.load(Ordering::Relaxed), 0);
+
+        join(thread);
+        assert_eq!(COUNTER.load(Ordering::Relaxed), 3);
+    }
+
+    /// Check that the slot of an exited thread is only reused after the switch away finished.
+    #[kernel_test]
+    fn exited_thread_slot_is_reused() {
+        let core: usize = cpu::smp::core_id();
+        let mut sched = Scheduler::new();
+        sched.threads[1].state = ThreadState::Zombie;
+        sched.threads[1].on_cpu = true;
+        sched.switched_from[core] = Some(1);
+        assert_ne!(free_slot(&sched), Ok(1));
+
+        sched.finish_switch();
+        assert_eq!(sched.threads[1].state, ThreadState::Free);
+        assert!(!sched.threads[1].on_cpu);
+        assert_eq!(free_slot(&sched), Ok(1));
+
+        let thread = spawn("exit", || {}).unwrap();
+        join(thread);
+
+        SCHEDULER.lock(|sched| {
+            let thread = &sched.threads[thread.0];
+
+            assert_eq!(thread.state, ThreadState::Free);
+            assert!(!thread.on_cpu);
+        });
+    }
+
+    /// Check that unpinned threads of other cores are stolen, but only once the other core
+    /// finished switching away from them.
+    #[kernel_test]
+    fn stealing_waits_for_switch_to_finish() {
+        let core: usize = cpu::smp::core_id();
+        let other_core = core + 1;
+        let mut sched = Scheduler::new();
+
+        // The previous thread is parked, so it does not compete.
+        sched.threads[0].state = ThreadState::Blocked;
+
+        let pinned = &mut sched.threads[1];
+        pinned.state = ThreadState::Runnable;
+        pinned.core = other_core;
+        pinned.pinned = true;
+
+        let switching_out = &mut sched.threads[2];
+        switching_out.state = ThreadState::Runnable;
+        switching_out.core = other_core;
+        switching_out.on_cpu = true;
+        assert_eq!(sched.pick_next(0), None);
+
+        sched.threads[2].on_cpu = false;
+        assert_eq!(sched.pick_next(0), Some(2));
+        assert_eq!(sched.threads[2].core, core);
+    }
+}
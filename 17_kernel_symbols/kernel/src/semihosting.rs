@@ -0,0 +1,204 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Host file access with semihosting.
+//!
+//! Semihosting lets the kernel ask the host, e.g. QEMU, to execute file operations on its behalf.
+//! Tests use it to read test vectors, e.g. golden files, and to write result artifacts, instead of
+//! baking the data into the kernel image.
+//!
+//! Paths are relative to the working directory of QEMU, which is the tutorial folder when run with
+//! `make test`. QEMU must run with `-semihosting`, which the test targets of the Makefile do.
+//!
+//! # Caution
+//!
+//! Without a debugger or emulator that handles semihosting, e.g. on real hardware, the calls halt
+//! the executing core. That is why the module is only compiled with the `semihosting` feature.
+
+#[cfg(target_arch = "aarch64")]
+#[path = "_arch/aarch64/semihosting.rs"]
+mod arch_semihosting;
+
+use core::fmt;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const SYS_OPEN: usize = 0x01;
+const SYS_CLOSE: usize = 0x02;
+const SYS_WRITE: usize = 0x05;
+const SYS_READ: usize = 0x06;
+const SYS_FLEN: usize = 0x0C;
+
+/// The longest supported path, in bytes. The host wants it NUL-terminated, so it is copied.
+const MAX_PATH_LEN: usize = 128;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// How a host file is opened.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Mode {
+    /// Read from the start.
+    Read,
+
+    /// Write from the start, after creating or truncating the file.
+    Write,
+
+    /// Write to the end, after creating the file if needed.
+    Append,
+}
+
+/// An open host file. It is closed when dropped.
+pub struct File {
+    handle: usize,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl Mode {
+    /// The binary variants of the `fopen()` modes, as numbered by the semihosting specification.
+    fn to_semihosting(self) -> usize {
+        match self {
+            Self::Read => 1,
+            Self::Write => 5,
+            Self::Append => 9,
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl File {
+    /// Open the host file at `path`.
+    pub fn open(path: &str, mode: Mode) -> Result<Self, &'static str> {
+        if path.len() > MAX_PATH_LEN {
+            return Err("Semihosting path too long");
+        }
+
+        let mut name = [0_u8; MAX_PATH_LEN + 1];
+        name[..path.len()].copy_from_slice(path.as_bytes());
+
+        let param = [name.as_ptr() as usize, mode.to_semihosting(), path.len()];
+        let handle = unsafe { arch_semihosting::call(SYS_OPEN, param.as_ptr()) };
+
+        if handle < 0 {
+            return Err("Semihosting open failed");
+        }
+
+        Ok(Self {
+            handle: handle as usize,
+        })
+    }
+
+    /// Return the size of the file in bytes.
+    pub fn size(&self) -> Result<usize, &'static str> {
+        let param = [self.handle];
+        let size = unsafe { arch_semihosting::call(SYS_FLEN, param.as_ptr()) };
+
+        if size < 0 {
+            return Err("Semihosting size query failed");
+        }
+
+        Ok(size as usize)
+    }
+
+    /// Read into `buf`, and return the number of bytes read. Zero means the end of the file.
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<usize, &'static str> {
+        let param = [self.handle, buf.as_mut_ptr() as usize, buf.len()];
+
+        // The host returns the number of bytes that were not read.
+        let not_read = unsafe { arch_semihosting::call(SYS_READ, param.as_ptr()) };
+
+        match buf.len().checked_sub(not_read as usize) {
+            Some(x) if not_read >= 0 => Ok(x),
+            _ => Err("Semihosting read failed"),
+        }
+    }
+
+    /// Write all of `bytes`.
+    pub fn write_all(&mut self, bytes: &[u8]) -> Result<(), &'static str> {
+        let param = [self.handle, bytes.as_ptr() as usize, bytes.len()];
+
+        // The host returns the number of bytes that were not written.
+        match unsafe { arch_semihosting::call(SYS_WRITE, param.as_ptr()) } {
+            0 => Ok(()),
+            _ => Err("Semihosting write failed"),
+        }
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        let param = [self.handle];
+
+        unsafe { arch_semihosting::call(SYS_CLOSE, param.as_ptr()) };
+    }
+}
+
+/// Makes `write!()` work for result artifacts.
+impl fmt::Write for File {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_all(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+/// Read the whole host file at `path` into `buf`, and return the part of `buf` that holds it.
+pub fn read_file<'a>(path: &str, buf: &'a mut [u8]) -> Result<&'a [u8], &'static str> {
+    let mut file = File::open(path, Mode::Read)?;
+
+    let size = file.size()?;
+    if size > buf.len() {
+        return Err("Semihosting file does not fit into the buffer");
+    }
+
+    let mut filled = 0;
+    while filled < size {
+        match file.read(&mut buf[filled..size])? {
+            0 => return Err("Semihosting file shrank while reading"),
+            x => filled += x,
+        }
+    }
+
+    Ok(&buf[..size])
+}
+
+/// Write `bytes` to the host file at `path`, replacing its contents.
+pub fn write_file(path: &str, bytes: &[u8]) -> Result<(), &'static str> {
+    File::open(path, Mode::Write)?.write_all(bytes)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
+
+    /// An artifact that is written can be read back.
+    #[kernel_test]
+    fn host_file_round_trip() {
+        use fmt::Write;
+
+        let path = "target/semihosting_round_trip.txt";
+
+        write_file(path, b"Hello").unwrap();
+        let mut file = File::open(path, Mode::Append).unwrap();
+        write!(file, ", host {}", 42).unwrap();
+        drop(file);
+
+        let mut buf = [0; 64];
+        assert_eq!(read_file(path, &mut buf), Ok(&b"Hello, host 42"[..]));
+        assert!(read_file(path, &mut buf[..4]).is_err());
+        assert!(File::open("target/does/not/exist", Mode::Read).is_err());
+    }
+}
@@ -3,10 +3,26 @@
 // Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
 
 //! Debug symbol support.
+//!
+//! To save space, the kernel symbols tool splits each name into a prefix, usually the module path,
+//! and the rest. Symbols with the same prefix share a single copy of it. [`SymbolName`] puts the
+//! parts back together.
+//!
+//! If the kernel was built with `SYMBOL_LINES=1`, the symbols are followed by a table that maps
+//! addresses to source lines, see [`lookup_line()`].
+//!
+//! Kernels that are built with `SYMBOLS_BLOB=1` have no symbols of their own. Instead, the symbols
+//! are written to a separate blob, which can be placed in RAM next to the kernel and registered
+//! with [`load()`].
 
-use crate::memory::{Address, Virtual};
-use core::{cell::UnsafeCell, slice};
-use debug_symbol_types::Symbol;
+mod blob;
+
+use crate::{
+    memory::{Address, Virtual},
+    synchronization::OnceCell,
+};
+use core::{cell::UnsafeCell, fmt, slice};
+use debug_symbol_types::{Line, Symbol};
 
 //--------------------------------------------------------------------------------------------------
 // Private Definitions
@@ -17,6 +33,40 @@ extern "Rust" {
     static __kernel_symbols_start: UnsafeCell<()>;
 }
 
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The name of a symbol, stored as a shared prefix and the rest.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SymbolName {
+    prefix: &'static str,
+    suffix: &'static str,
+}
+
+/// The symbol that contains an address, and where the address is located within it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SymbolLocation {
+    /// The symbol's name.
+    pub name: SymbolName,
+
+    /// The offset of the address from the start of the symbol.
+    pub offset: usize,
+
+    /// The symbol's size in bytes.
+    pub size: usize,
+}
+
+/// A position in the kernel's source code.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SourceLine {
+    /// The source file, relative to the crate it belongs to.
+    pub file: &'static str,
+
+    /// The line number.
+    pub line: u32,
+}
+
 //--------------------------------------------------------------------------------------------------
 // Global instances
 //--------------------------------------------------------------------------------------------------
@@ -26,6 +76,9 @@ extern "Rust" {
 #[no_mangle]
 static NUM_KERNEL_SYMBOLS: u64 = 0;
 
+/// Symbols that were loaded at runtime.
+static LOADED_SYMBOLS: OnceCell<blob::SymbolBlob> = OnceCell::new();
+
 //--------------------------------------------------------------------------------------------------
 // Private Code
 //--------------------------------------------------------------------------------------------------
@@ -34,28 +87,177 @@ fn kernel_symbol_section_virt_start_addr() -> Address<Virtual> {
     Address::new(unsafe { __kernel_symbols_start.get() as usize })
 }
 
+fn num_kernel_symbols() -> usize {
+    unsafe { core::ptr::read_volatile(&NUM_KERNEL_SYMBOLS as *const u64) as usize }
+}
+
 fn kernel_symbols_slice() -> &'static [Symbol] {
     let ptr = kernel_symbol_section_virt_start_addr().as_usize() as *const Symbol;
 
-    unsafe {
-        let num = core::ptr::read_volatile(&NUM_KERNEL_SYMBOLS as *const u64) as usize;
-        slice::from_raw_parts(ptr, num)
+    unsafe { slice::from_raw_parts(ptr, num_kernel_symbols()) }
+}
+
+/// The line table is referenced by a slice that directly follows the symbols. It is empty if the
+/// kernel was built without it.
+fn kernel_lines_slice() -> &'static [Line] {
+    let num_symbols = num_kernel_symbols();
+
+    // The section was not patched.
+    if num_symbols == 0 {
+        return &[];
     }
+
+    let table_ref_addr = kernel_symbol_section_virt_start_addr().as_usize()
+        + num_symbols * core::mem::size_of::<Symbol>();
+
+    unsafe { core::ptr::read_volatile(table_ref_addr as *const &'static [Line]) }
+}
+
+fn lookup_loaded_symbol(addr: usize) -> Option<SymbolLocation> {
+    let sym = LOADED_SYMBOLS.get()?.lookup(addr)?;
+
+    Some(SymbolLocation {
+        name: SymbolName {
+            prefix: "",
+            suffix: sym.name,
+        },
+        offset: addr - sym.start,
+        size: sym.size,
+    })
 }
 
 //--------------------------------------------------------------------------------------------------
 // Public Code
 //--------------------------------------------------------------------------------------------------
 
-/// Retrieve the symbol name corresponding to a virtual address, if any.
-pub fn lookup_symbol(addr: Address<Virtual>) -> Option<&'static str> {
-    for i in kernel_symbols_slice() {
-        if i.contains(addr.as_usize()) {
-            return Some(i.name());
+impl SymbolName {
+    /// The length of the name in bytes.
+    pub fn len(&self) -> usize {
+        self.prefix.len() + self.suffix.len()
+    }
+
+    /// Returns true if the name is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns true if the name ends with `pattern`.
+    pub fn ends_with(&self, pattern: &str) -> bool {
+        match pattern.len().checked_sub(self.suffix.len()) {
+            None => self.suffix.ends_with(pattern),
+            Some(rest) => pattern.ends_with(self.suffix) && self.prefix.ends_with(&pattern[..rest]),
         }
     }
+}
+
+impl PartialEq<&str> for SymbolName {
+    fn eq(&self, other: &&str) -> bool {
+        other.len() == self.len()
+            && other.starts_with(self.prefix)
+            && other[self.prefix.len()..] == *self.suffix
+    }
+}
+
+impl fmt::Display for SymbolName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.prefix, self.suffix)
+    }
+}
+
+impl SymbolLocation {
+    /// Returns true if the address is the first byte of the symbol, e.g. a function's entry point.
+    pub fn is_start(&self) -> bool {
+        self.offset == 0
+    }
+}
 
-    None
+/// Formats as `file:line`.
+impl fmt::Display for SourceLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+/// Formats as `name+0x34`.
+impl fmt::Display for SymbolLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}+{:#x}", self.name, self.offset)
+    }
+}
+
+/// Retrieve the symbol corresponding to a virtual address, if any, along with the address' offset
+/// within it.
+///
+/// The kernel symbols tool emits the symbols sorted by start address, so this is a binary search.
+/// If the kernel has no symbols of its own, the ones registered with [`load()`] are searched.
+pub fn lookup_symbol(addr: Address<Virtual>) -> Option<SymbolLocation> {
+    let addr = addr.as_usize();
+    let symbols = kernel_symbols_slice();
+
+    if symbols.is_empty() {
+        return lookup_loaded_symbol(addr);
+    }
+
+    // The candidate is the last symbol that starts at or below the address.
+    let index = symbols
+        .partition_point(|sym| sym.start() <= addr)
+        .checked_sub(1)?;
+    let sym = &symbols[index];
+
+    if !sym.contains(addr) {
+        return None;
+    }
+
+    Some(SymbolLocation {
+        name: SymbolName {
+            prefix: sym.name_prefix(),
+            suffix: sym.name_suffix(),
+        },
+        offset: addr - sym.start(),
+        size: sym.size(),
+    })
+}
+
+/// Register a symbols blob that was placed at `addr` in the kernel's virtual address space.
+///
+/// The blob is validated first, so that a wrong address or length is reported instead of
+/// producing garbage symbols.
+///
+/// # Safety
+///
+/// - The `len` bytes at `addr` must be mapped and must not be modified afterwards.
+pub unsafe fn load(addr: Address<Virtual>, len: usize) -> Result<(), &'static str> {
+    if LOADED_SYMBOLS.get().is_some() {
+        return Err("Symbols already loaded");
+    }
+
+    let bytes = slice::from_raw_parts(addr.as_usize() as *const u8, len);
+    let symbols = blob::SymbolBlob::parse(bytes)?;
+
+    LOADED_SYMBOLS
+        .set(symbols)
+        .map_err(|_| "Symbols already loaded")
+}
+
+/// Retrieve the source line of the code at a virtual address, if the kernel was built with a line
+/// table and the address is covered by it.
+pub fn lookup_line(addr: Address<Virtual>) -> Option<SourceLine> {
+    let addr = addr.as_usize();
+    let lines = kernel_lines_slice();
+
+    let index = lines
+        .partition_point(|line| line.start() <= addr)
+        .checked_sub(1)?;
+    let line = &lines[index];
+
+    if line.line() == 0 {
+        return None;
+    }
+
+    Some(SourceLine {
+        file: line.file(),
+        line: line.line(),
+    })
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -75,11 +277,65 @@ mod tests {
         ))
         .unwrap();
 
-        assert_eq!(first_sym, "libkernel::common::is_aligned");
+        assert_eq!(first_sym.name, "libkernel::common::is_aligned");
+        assert!(first_sym.is_start());
 
         let second_sym =
             lookup_symbol(Address::new(crate::version as *const usize as usize)).unwrap();
 
-        assert_eq!(second_sym, "libkernel::version");
+        assert_eq!(second_sym.name, "libkernel::version");
+    }
+
+    /// An address within a function must resolve to the function, with the right offset.
+    #[kernel_test]
+    fn symbol_offset_is_reported() {
+        let start = crate::version as *const usize as usize;
+        let entry = lookup_symbol(Address::new(start)).unwrap();
+        assert!(entry.size > 4);
+
+        let sym = lookup_symbol(Address::new(start + 4)).unwrap();
+        assert_eq!(sym.name, entry.name);
+        assert_eq!(sym.offset, 4);
+        assert!(!sym.is_start());
+    }
+
+    /// Names must compare equal no matter where they are split.
+    #[kernel_test]
+    fn symbol_names_compare_across_the_split() {
+        let name = SymbolName {
+            prefix: "libkernel::time::",
+            suffix: "uptime",
+        };
+
+        assert_eq!(name, "libkernel::time::uptime");
+        assert_eq!(name.len(), 23);
+        assert!(name != "libkernel::time::uptim");
+        assert!(name != "libkernel::timer::uptime");
+        assert!(name.ends_with("time"));
+        assert!(name.ends_with("::time::uptime"));
+        assert!(!name.ends_with("::timer::uptime"));
+    }
+
+    /// The binary search relies on the symbols being sorted by start address.
+    #[kernel_test]
+    fn symbols_are_sorted() {
+        let symbols = kernel_symbols_slice();
+
+        assert!(!symbols.is_empty());
+        assert!(symbols.windows(2).all(|w| w[0].start() < w[1].start()));
+    }
+
+    /// If there is a line table, it must be sorted, and resolve code to the file that contains it.
+    #[kernel_test]
+    fn lines_are_resolved() {
+        let lines = kernel_lines_slice();
+        if lines.is_empty() {
+            return;
+        }
+
+        assert!(lines.windows(2).all(|w| w[0].start() < w[1].start()));
+
+        let line = lookup_line(Address::new(lookup_line as *const usize as usize)).unwrap();
+        assert!(line.file.ends_with("symbols.rs"));
     }
 }
@@ -0,0 +1,203 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Symbol tables that are loaded at runtime.
+//!
+//! The kernel symbols tool writes them with `--gen_blob`. All fields are little endian:
+//!
+//! | Offset | Size         | Content                                     |
+//! |--------|--------------|---------------------------------------------|
+//! | 0      | 4            | Magic, `KSYM`                               |
+//! | 4      | 4            | Version, currently 1                        |
+//! | 8      | 4            | Number of symbols                           |
+//! | 12     | 4            | Size of the string table in bytes           |
+//! | 16     | 24 * symbols | Records, sorted by start address            |
+//! | ...    | string table | The UTF-8 names that the records point into |
+//!
+//! Each record holds the symbol's start address and size as `u64`, followed by the offset and the
+//! length of its name in the string table as `u32`.
+
+use core::convert::TryInto;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const MAGIC: &[u8; 4] = b"KSYM";
+const VERSION: u32 = 1;
+
+const HEADER_SIZE: usize = 16;
+const RECORD_SIZE: usize = 24;
+
+struct Record {
+    start: usize,
+    size: usize,
+    name_offset: usize,
+    name_len: usize,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A validated symbol table blob.
+#[derive(Copy, Clone)]
+pub struct SymbolBlob {
+    records: &'static [u8],
+    strings: &'static str,
+}
+
+/// A symbol of a blob.
+pub struct BlobSymbol {
+    /// The symbol's start address.
+    pub start: usize,
+
+    /// The symbol's size in bytes.
+    pub size: usize,
+
+    /// The symbol's demangled name.
+    pub name: &'static str,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+impl SymbolBlob {
+    fn num_symbols(&self) -> usize {
+        self.records.len() / RECORD_SIZE
+    }
+
+    fn record(&self, index: usize) -> Record {
+        let record = &self.records[index * RECORD_SIZE..(index + 1) * RECORD_SIZE];
+
+        Record {
+            start: read_u64(record, 0) as usize,
+            size: read_u64(record, 8) as usize,
+            name_offset: read_u32(record, 16) as usize,
+            name_len: read_u32(record, 20) as usize,
+        }
+    }
+
+    fn name(&self, record: &Record) -> Option<&'static str> {
+        self.strings
+            .get(record.name_offset..record.name_offset.checked_add(record.name_len)?)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl SymbolBlob {
+    /// Validate a blob, so that lookups never fail on malformed data.
+    pub fn parse(blob: &'static [u8]) -> Result<Self, &'static str> {
+        if blob.len() < HEADER_SIZE || &blob[0..4] != MAGIC {
+            return Err("Not a kernel symbols blob");
+        }
+
+        if read_u32(blob, 4) != VERSION {
+            return Err("Unsupported kernel symbols blob version");
+        }
+
+        let num_symbols = read_u32(blob, 8) as usize;
+        let strings_len = read_u32(blob, 12) as usize;
+        let records_end = HEADER_SIZE + num_symbols * RECORD_SIZE;
+
+        if blob.len() != records_end + strings_len {
+            return Err("Kernel symbols blob has the wrong size");
+        }
+
+        let strings = core::str::from_utf8(&blob[records_end..])
+            .map_err(|_| "Kernel symbols blob has invalid names")?;
+
+        let parsed = Self {
+            records: &blob[HEADER_SIZE..records_end],
+            strings,
+        };
+
+        let mut previous_start = None;
+        for index in 0..parsed.num_symbols() {
+            let record = parsed.record(index);
+
+            if parsed.name(&record).is_none() {
+                return Err("Kernel symbols blob has invalid names");
+            }
+
+            if matches!(previous_start, Some(start) if start >= record.start) {
+                return Err("Kernel symbols blob is not sorted");
+            }
+            previous_start = Some(record.start);
+        }
+
+        Ok(parsed)
+    }
+
+    /// Return the symbol that contains `addr`, if any.
+    pub fn lookup(&self, addr: usize) -> Option<BlobSymbol> {
+        // The candidate is the last symbol that starts at or below the address. The records are
+        // sorted, so bisect like `partition_point()` would.
+        let (mut low, mut high) = (0, self.num_symbols());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.record(mid).start <= addr {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+
+        let record = self.record(low.checked_sub(1)?);
+        if addr - record.start >= record.size {
+            return None;
+        }
+
+        Some(BlobSymbol {
+            start: record.start,
+            size: record.size,
+            name: self.name(&record)?,
+        })
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
+
+    /// Two symbols, `foo` at 0x1000 and `bar` at 0x2000, both 0x10 bytes.
+    static BLOB: [u8; HEADER_SIZE + 2 * RECORD_SIZE + 6] = [
+        b'K', b'S', b'Y', b'M', 1, 0, 0, 0, 2, 0, 0, 0, 6, 0, 0, 0, //
+        0x00, 0x10, 0, 0, 0, 0, 0, 0, 0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, //
+        0x00, 0x20, 0, 0, 0, 0, 0, 0, 0x10, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 3, 0, 0, 0, //
+        b'f', b'o', b'o', b'b', b'a', b'r',
+    ];
+
+    /// A valid blob resolves addresses, and broken ones are rejected.
+    #[kernel_test]
+    fn blob_is_parsed_and_searched() {
+        let blob = SymbolBlob::parse(&BLOB).unwrap();
+
+        let sym = blob.lookup(0x2004).unwrap();
+        assert_eq!((sym.start, sym.size, sym.name), (0x2000, 0x10, "bar"));
+        assert_eq!(blob.lookup(0x1000).unwrap().name, "foo");
+        assert!(blob.lookup(0xfff).is_none());
+        assert!(blob.lookup(0x1010).is_none());
+
+        assert!(SymbolBlob::parse(&BLOB[..BLOB.len() - 1]).is_err());
+        assert!(SymbolBlob::parse(&BLOB[4..]).is_err());
+    }
+}
@@ -10,7 +10,33 @@
 //!   - <https://stackoverflow.com/questions/59428096/understanding-the-send-trait>
 //!   - <https://doc.rust-lang.org/std/cell/index.html>
 
-use core::cell::UnsafeCell;
+mod once_cell;
+mod ring_buffer;
+mod wait_queue;
+
+#[cfg(feature = "lockdep")]
+mod lockdep;
+
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Public Reexports
+//--------------------------------------------------------------------------------------------------
+pub use once_cell::{LazyInit, OnceCell};
+pub use ring_buffer::RingBuffer;
+pub use wait_queue::{Event, WaitQueue};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The lock word shared by the spinlocks. Does not care about interrupts.
+struct RawSpinLock {
+    locked: AtomicBool,
+}
 
 //--------------------------------------------------------------------------------------------------
 // Public Definitions
@@ -45,17 +71,63 @@ pub mod interface {
     }
 }
 
-/// A pseudo-lock for teaching purposes.
+/// A spinlock that masks IRQs on the executing core while it is held.
 ///
-/// In contrast to a real Mutex implementation, does not protect against concurrent access from
-/// other cores to the contained data. This part is preserved for later lessons.
+/// Other cores spin until the lock is released. Masking IRQs prevents a deadlock with an IRQ
+/// handler that takes the same lock on the same core. For the same reason, the lock must not be
+/// taken again while it is held.
+pub struct SpinLock<T>
+where
+    T: ?Sized,
+{
+    raw: RawSpinLock,
+    data: UnsafeCell<T>,
+}
+
+/// Like [`SpinLock`], but also masks FIQs.
 ///
-/// The lock will only be used as long as it is safe to do so, i.e. as long as the kernel is
-/// executing on a single core.
-pub struct IRQSafeNullLock<T>
+/// Must be used for data that is accessed from FIQ handlers.
+pub struct FIQSafeSpinLock<T>
 where
     T: ?Sized,
 {
+    raw: RawSpinLock,
+    data: UnsafeCell<T>,
+}
+
+/// A fair spinlock that masks IRQs on the executing core while it is held.
+///
+/// Cores are granted the lock in the order in which they started waiting for it, so under
+/// contention, no core can be starved by others that take the lock over and over. Like with
+/// [`SpinLock`], the lock must not be taken again while it is held.
+pub struct TicketLock<T>
+where
+    T: ?Sized,
+{
+    /// The ticket that is handed out next.
+    next_ticket: AtomicU32,
+
+    /// The ticket whose owner holds the lock.
+    now_serving: AtomicU32,
+
+    data: UnsafeCell<T>,
+}
+
+/// A reader-writer spinlock that masks IRQs on the executing core while it is held.
+///
+/// Any number of readers, or a single writer, can hold the lock. Writers are preferred: Once a
+/// writer waits, new readers wait as well, so that a steady stream of readers can not starve it.
+/// Because of that, a reader must not take the lock again while holding it.
+pub struct RwLock<T>
+where
+    T: ?Sized,
+{
+    /// The number of readers, or [`RwLock::WRITER`] while a writer holds the lock.
+    state: AtomicU32,
+
+    /// The number of writers that wait for the lock.
+    waiting_writers: AtomicU32,
+
     data: UnsafeCell<T>,
 }
 
@@ -69,20 +141,142 @@ where
     data: UnsafeCell<T>,
 }
 
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl RawSpinLock {
+    const fn new() -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+        }
+    }
+
+    fn acquire(&self) {
+        // The compare-exchange compiles to an exclusive load/store pair. While the lock is taken,
+        // spin on plain loads, which do not claim exclusive ownership of the cache line.
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            while self.locked.load(Ordering::Relaxed) {
+                core::hint::spin_loop();
+            }
+        }
+    }
+
+    fn release(&self) {
+        self.locked.store(false, Ordering::Release);
+    }
+
+    /// Execute the closure with the lock held.
+    fn with<R>(&self, f: impl FnOnce() -> R) -> R {
+        #[cfg(feature = "lockdep")]
+        lockdep::acquire(self as *const Self as usize);
+
+        self.acquire();
+        let ret = f();
+        self.release();
+
+        #[cfg(feature = "lockdep")]
+        lockdep::release(self as *const Self as usize);
+
+        ret
+    }
+}
+
 //--------------------------------------------------------------------------------------------------
 // Public Code
 //--------------------------------------------------------------------------------------------------
 
-unsafe impl<T> Send for IRQSafeNullLock<T> where T: ?Sized + Send {}
-unsafe impl<T> Sync for IRQSafeNullLock<T> where T: ?Sized + Send {}
+unsafe impl<T> Send for SpinLock<T> where T: ?Sized + Send {}
+unsafe impl<T> Sync for SpinLock<T> where T: ?Sized + Send {}
+
+impl<T> SpinLock<T> {
+    /// Create an instance.
+    pub const fn new(data: T) -> Self {
+        Self {
+            raw: RawSpinLock::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+}
+
+unsafe impl<T> Send for FIQSafeSpinLock<T> where T: ?Sized + Send {}
+unsafe impl<T> Sync for FIQSafeSpinLock<T> where T: ?Sized + Send {}
+
+impl<T> FIQSafeSpinLock<T> {
+    /// Create an instance.
+    pub const fn new(data: T) -> Self {
+        Self {
+            raw: RawSpinLock::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+}
+
+unsafe impl<T> Send for TicketLock<T> where T: ?Sized + Send {}
+unsafe impl<T> Sync for TicketLock<T> where T: ?Sized + Send {}
+
+impl<T> TicketLock<T> {
+    /// Create an instance.
+    pub const fn new(data: T) -> Self {
+        Self {
+            next_ticket: AtomicU32::new(0),
+            now_serving: AtomicU32::new(0),
+            data: UnsafeCell::new(data),
+        }
+    }
+}
+
+unsafe impl<T> Send for RwLock<T> where T: ?Sized + Send {}
+unsafe impl<T> Sync for RwLock<T> where T: ?Sized + Send + Sync {}
+
+impl<T> RwLock<T> {
+    const WRITER: u32 = u32::MAX;
 
-impl<T> IRQSafeNullLock<T> {
     /// Create an instance.
     pub const fn new(data: T) -> Self {
         Self {
+            state: AtomicU32::new(0),
+            waiting_writers: AtomicU32::new(0),
             data: UnsafeCell::new(data),
         }
     }
+
+    fn acquire_read(&self) {
+        loop {
+            let readers = self.state.load(Ordering::Relaxed);
+
+            if readers == Self::WRITER || self.waiting_writers.load(Ordering::Relaxed) != 0 {
+                core::hint::spin_loop();
+                continue;
+            }
+
+            if self
+                .state
+                .compare_exchange_weak(readers, readers + 1, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+            {
+                return;
+            }
+        }
+    }
+
+    fn acquire_write(&self) {
+        self.waiting_writers.fetch_add(1, Ordering::Relaxed);
+
+        while self
+            .state
+            .compare_exchange_weak(0, Self::WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+
+        self.waiting_writers.fetch_sub(1, Ordering::Relaxed);
+    }
 }
 
 unsafe impl<T> Send for InitStateLock<T> where T: ?Sized + Send {}
@@ -102,16 +296,88 @@ impl<T> InitStateLock<T> {
 //------------------------------------------------------------------------------
 use crate::{exception, state};
 
-impl<T> interface::Mutex for IRQSafeNullLock<T> {
+impl<T> interface::Mutex for SpinLock<T> {
     type Data = T;
 
     fn lock<R>(&self, f: impl FnOnce(&mut Self::Data) -> R) -> R {
-        // In a real lock, there would be code encapsulating this line that ensures that this
-        // mutable reference will ever only be given out once at a time.
-        let data = unsafe { &mut *self.data.get() };
+        // Execute the closure while IRQs are masked and the lock is held. The lock ensures that the
+        // mutable reference is only given out once at a time.
+        exception::asynchronous::exec_with_irq_masked(|| {
+            self.raw.with(|| f(unsafe { &mut *self.data.get() }))
+        })
+    }
+}
+
+impl<T> interface::Mutex for FIQSafeSpinLock<T> {
+    type Data = T;
+
+    fn lock<R>(&self, f: impl FnOnce(&mut Self::Data) -> R) -> R {
+        // Execute the closure while IRQs and FIQs are masked and the lock is held.
+        exception::asynchronous::exec_with_irq_fiq_masked(|| {
+            self.raw.with(|| f(unsafe { &mut *self.data.get() }))
+        })
+    }
+}
+
+impl<T> interface::Mutex for TicketLock<T> {
+    type Data = T;
 
-        // Execute the closure while IRQs are masked.
-        exception::asynchronous::exec_with_irq_masked(|| f(data))
+    fn lock<R>(&self, f: impl FnOnce(&mut Self::Data) -> R) -> R {
+        exception::asynchronous::exec_with_irq_masked(|| {
+            #[cfg(feature = "lockdep")]
+            lockdep::acquire(&self.now_serving as *const AtomicU32 as usize);
+
+            // Tickets wrap around, which is fine as long as there are less than 2^32 waiters.
+            let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+            while self.now_serving.load(Ordering::Acquire) != ticket {
+                core::hint::spin_loop();
+            }
+
+            let ret = f(unsafe { &mut *self.data.get() });
+            self.now_serving
+                .store(ticket.wrapping_add(1), Ordering::Release);
+
+            #[cfg(feature = "lockdep")]
+            lockdep::release(&self.now_serving as *const AtomicU32 as usize);
+
+            ret
+        })
+    }
+}
+
+impl<T> interface::ReadWriteEx for RwLock<T> {
+    type Data = T;
+
+    fn write<R>(&self, f: impl FnOnce(&mut Self::Data) -> R) -> R {
+        exception::asynchronous::exec_with_irq_masked(|| {
+            #[cfg(feature = "lockdep")]
+            lockdep::acquire(&self.state as *const AtomicU32 as usize);
+
+            self.acquire_write();
+            let ret = f(unsafe { &mut *self.data.get() });
+            self.state.store(0, Ordering::Release);
+
+            #[cfg(feature = "lockdep")]
+            lockdep::release(&self.state as *const AtomicU32 as usize);
+
+            ret
+        })
+    }
+
+    fn read<R>(&self, f: impl FnOnce(&Self::Data) -> R) -> R {
+        exception::asynchronous::exec_with_irq_masked(|| {
+            #[cfg(feature = "lockdep")]
+            lockdep::acquire(&self.state as *const AtomicU32 as usize);
+
+            self.acquire_read();
+            let ret = f(unsafe { &*self.data.get() });
+            self.state.fetch_sub(1, Ordering::Release);
+
+            #[cfg(feature = "lockdep")]
+            lockdep::release(&self.state as *const AtomicU32 as usize);
+
+            ret
+        })
     }
 }
 
@@ -147,7 +413,7 @@ impl<T> interface::ReadWriteEx for InitStateLock<T> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use test_macros::kernel_test;
+    use test_macros::{kernel_bench, kernel_test};
 
     /// InitStateLock must be transparent.
     #[kernel_test]
@@ -156,4 +422,66 @@ mod tests {
 
         assert_eq!(size_of::<InitStateLock<u64>>(), size_of::<u64>());
     }
+
+    /// SpinLock must be released after the closure returned.
+    #[kernel_test]
+    fn spin_lock_is_released() {
+        use interface::Mutex;
+
+        let lock = SpinLock::new(0);
+
+        lock.lock(|data| *data += 1);
+        lock.lock(|data| *data += 1);
+
+        assert_eq!(lock.lock(|data| *data), 2);
+        assert!(!lock.raw.locked.load(Ordering::Relaxed));
+    }
+
+    /// TicketLock must serve one ticket per access.
+    #[kernel_test]
+    fn ticket_lock_is_released() {
+        use interface::Mutex;
+
+        let lock = TicketLock::new(0);
+
+        lock.lock(|data| *data += 1);
+        lock.lock(|data| *data += 1);
+
+        assert_eq!(lock.lock(|data| *data), 2);
+        assert_eq!(lock.next_ticket.load(Ordering::Relaxed), 3);
+        assert_eq!(lock.now_serving.load(Ordering::Relaxed), 3);
+    }
+
+    /// RwLock must be released after each access.
+    #[kernel_test]
+    fn rw_lock_is_released() {
+        use interface::ReadWriteEx;
+
+        let lock = RwLock::new(0);
+
+        lock.write(|data| *data = 5);
+        assert_eq!(lock.read(|data| *data), 5);
+
+        assert_eq!(lock.state.load(Ordering::Relaxed), 0);
+        assert_eq!(lock.waiting_writers.load(Ordering::Relaxed), 0);
+    }
+
+    static BENCH_SPIN_LOCK: SpinLock<u64> = SpinLock::new(0);
+    static BENCH_TICKET_LOCK: TicketLock<u64> = TicketLock::new(0);
+
+    /// The cost of an uncontended SpinLock, including masking IRQs.
+    #[kernel_bench]
+    fn spin_lock_uncontended() {
+        use interface::Mutex;
+
+        BENCH_SPIN_LOCK.lock(|data| *data += 1);
+    }
+
+    /// The cost of an uncontended TicketLock, for comparison with the SpinLock.
+    #[kernel_bench]
+    fn ticket_lock_uncontended() {
+        use interface::Mutex;
+
+        BENCH_TICKET_LOCK.lock(|data| *data += 1);
+    }
 }
@@ -0,0 +1,281 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Lock dependency checking.
+//!
+//! Enabled with the `lockdep` feature. Each core records the locks that it holds. Taking a lock
+//! while holding others records the order in which they were taken. Taking two locks in the
+//! opposite order of a recorded one, or taking a held lock again, can deadlock. Both panic, and
+//! show where the conflicting order was first seen and where the lock is taken now.
+//!
+//! Locks are identified by their address. Only inversions between two locks are detected, not
+//! longer cycles.
+
+use super::RawSpinLock;
+use crate::{backtrace, bsp, cpu, exception, memory, symbols};
+use core::{cell::UnsafeCell, fmt};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The maximum number of locks that a core can hold at the same time.
+const MAX_HELD_LOCKS: usize = 16;
+
+/// The maximum number of recorded lock orders. Further orders are not checked.
+const MAX_DEPENDENCIES: usize = 128;
+
+/// The number of return addresses recorded per stack.
+const STACK_DEPTH: usize = 8;
+
+/// The call stack at the time a lock was taken.
+#[derive(Copy, Clone)]
+struct Stack {
+    return_addrs: [usize; STACK_DEPTH],
+    len: usize,
+}
+
+/// A lock held by a core.
+#[derive(Copy, Clone)]
+struct HeldLock {
+    lock: usize,
+    stack: Stack,
+}
+
+/// The locks held by a core, in the order they were taken.
+struct HeldLocks {
+    locks: [Option<HeldLock>; MAX_HELD_LOCKS],
+    len: usize,
+}
+
+/// `first` was held while `second` was taken.
+#[derive(Copy, Clone)]
+struct Dependency {
+    first: usize,
+    second: usize,
+    stack: Stack,
+}
+
+/// All recorded lock orders.
+struct Dependencies {
+    raw: RawSpinLock,
+    deps: UnsafeCell<[Option<Dependency>; MAX_DEPENDENCIES]>,
+}
+
+/// Only ever accessed by its own core, with IRQs and FIQs masked.
+struct HeldLocksCell(UnsafeCell<HeldLocks>);
+
+/// Displays a lock's address and, if available, its symbol.
+struct LockName(usize);
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_HELD_LOCKS: HeldLocksCell = HeldLocksCell(UnsafeCell::new(HeldLocks {
+    locks: [None; MAX_HELD_LOCKS],
+    len: 0,
+}));
+
+static HELD_LOCKS: cpu::PerCpu<HeldLocksCell> =
+    cpu::PerCpu::new([NO_HELD_LOCKS; bsp::cpu::NUM_CORES]);
+
+static DEPENDENCIES: Dependencies = Dependencies {
+    raw: RawSpinLock::new(),
+    deps: UnsafeCell::new([None; MAX_DEPENDENCIES]),
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+unsafe impl Sync for HeldLocksCell {}
+unsafe impl Sync for Dependencies {}
+
+impl Stack {
+    fn capture() -> Self {
+        let mut return_addrs = [0; STACK_DEPTH];
+        let mut len = 0;
+        for (slot, addr) in return_addrs.iter_mut().zip(backtrace::return_addresses()) {
+            *slot = addr;
+            len += 1;
+        }
+
+        Self { return_addrs, len }
+    }
+}
+
+impl fmt::Display for Stack {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, &addr) in self.return_addrs[..self.len].iter().enumerate() {
+            // Return addresses point to the instruction after the call. Look up the call itself.
+            let call_addr = memory::Address::new(addr - 4);
+
+            match symbols::lookup_symbol(call_addr) {
+                None => write!(f, "      {: >2}. {:#018x} - Symbol not found", i, addr)?,
+                Some(sym) => write!(f, "      {: >2}. {:#018x} - {}", i, addr, sym)?,
+            }
+            if let Some(line) = symbols::lookup_line(call_addr) {
+                write!(f, " at {}", line)?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for LockName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match symbols::lookup_symbol(memory::Address::new(self.0)) {
+            None => write!(f, "{:#018x}", self.0),
+            Some(sym) => write!(f, "{:#018x} ({})", self.0, sym.name),
+        }
+    }
+}
+
+impl HeldLocks {
+    fn find(&self, lock: usize) -> Option<&HeldLock> {
+        self.locks[..self.len]
+            .iter()
+            .flatten()
+            .find(|held| held.lock == lock)
+    }
+
+    fn push(&mut self, held: HeldLock) {
+        assert!(self.len < MAX_HELD_LOCKS, "lockdep: Too many locks held");
+
+        self.locks[self.len] = Some(held);
+        self.len += 1;
+    }
+
+    /// Locks need not be released in the reverse order they were taken.
+    fn remove(&mut self, lock: usize) {
+        let index = match (0..self.len)
+            .rev()
+            .find(|&i| self.locks[i].map_or(false, |held| held.lock == lock))
+        {
+            None => return,
+            Some(i) => i,
+        };
+
+        self.locks.copy_within(index + 1..self.len, index);
+        self.len -= 1;
+        self.locks[self.len] = None;
+    }
+}
+
+impl Dependencies {
+    /// Record that `held` was held while `lock` was taken. Returns the opposite order, if it was
+    /// recorded before.
+    fn check_and_record(&self, held: usize, lock: usize, stack: &Stack) -> Option<Dependency> {
+        self.raw.acquire();
+        let deps = unsafe { &mut *self.deps.get() };
+
+        let mut inversion = None;
+        let mut known = false;
+        let mut free_slot = None;
+        for (i, dep) in deps.iter().enumerate() {
+            match dep {
+                None if free_slot.is_none() => free_slot = Some(i),
+                Some(dep) if dep.first == lock && dep.second == held => inversion = Some(*dep),
+                Some(dep) if dep.first == held && dep.second == lock => known = true,
+                _ => (),
+            }
+        }
+
+        if inversion.is_none() && !known {
+            if let Some(i) = free_slot {
+                deps[i] = Some(Dependency {
+                    first: held,
+                    second: lock,
+                    stack: *stack,
+                });
+            }
+        }
+
+        self.raw.release();
+
+        inversion
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Check and record that the executing core is about to take `lock`.
+///
+/// Must be called before spinning on the lock, so that a deadlock is reported instead of hanging.
+pub fn acquire(lock: usize) {
+    exception::asynchronous::exec_with_irq_fiq_masked(|| {
+        let held_locks = unsafe { &mut *HELD_LOCKS.get().0.get() };
+        let stack = Stack::capture();
+
+        if let Some(held) = held_locks.find(lock) {
+            panic!(
+                "lockdep: Recursive acquisition of lock {}\n\n\
+                First taken at:\n{}\n\
+                Taken again at:\n{}",
+                LockName(lock),
+                held.stack,
+                stack
+            );
+        }
+
+        for held in held_locks.locks[..held_locks.len].iter().flatten() {
+            if let Some(dep) = DEPENDENCIES.check_and_record(held.lock, lock, &stack) {
+                panic!(
+                    "lockdep: Lock order inversion\n\n\
+                    Lock {} was taken while holding {} at:\n{}\n\
+                    Lock {} is taken while holding {} at:\n{}",
+                    LockName(dep.second),
+                    LockName(dep.first),
+                    dep.stack,
+                    LockName(lock),
+                    LockName(held.lock),
+                    stack
+                );
+            }
+        }
+
+        held_locks.push(HeldLock { lock, stack });
+    })
+}
+
+/// Record that the executing core released `lock`.
+pub fn release(lock: usize) {
+    exception::asynchronous::exec_with_irq_fiq_masked(|| {
+        let held_locks = unsafe { &mut *HELD_LOCKS.get().0.get() };
+
+        held_locks.remove(lock);
+    })
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
+
+    /// Locks taken in a consistent order must be accepted, and must be forgotten once released.
+    #[kernel_test]
+    fn consistent_order_is_accepted() {
+        let (a, b) = (0x1000, 0x2000);
+
+        for _ in 0..2 {
+            acquire(a);
+            acquire(b);
+            release(b);
+            release(a);
+        }
+
+        let held_locks = unsafe { &*HELD_LOCKS.get().0.get() };
+        assert_eq!(held_locks.len, 0);
+    }
+}
@@ -0,0 +1,186 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! One-time initialization.
+//!
+//! Some statics can only be constructed at runtime, e.g. because they depend on remapped MMIO or on
+//! what the firmware handed over. [`OnceCell`] holds such a value once it was set, and
+//! [`LazyInit`] constructs it on first use.
+
+use core::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    ops::Deref,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const UNINITIALIZED: u8 = 0;
+const INITIALIZING: u8 = 1;
+const INITIALIZED: u8 = 2;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A cell that can be written only once.
+///
+/// Reading does not need a lock once the value is set.
+pub struct OnceCell<T> {
+    state: AtomicU8,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// A value that is constructed by `init` on first access.
+pub struct LazyInit<T> {
+    cell: OnceCell<T>,
+    init: fn() -> T,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl<T> OnceCell<T> {
+    /// Claim the cell for initialization. Returns false if another caller claimed it already.
+    fn try_claim(&self) -> bool {
+        self.state
+            .compare_exchange(
+                UNINITIALIZED,
+                INITIALIZING,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            )
+            .is_ok()
+    }
+
+    fn complete(&self, value: T) -> &T {
+        let value = unsafe { (*self.value.get()).write(value) };
+        self.state.store(INITIALIZED, Ordering::Release);
+
+        value
+    }
+
+    /// Wait until another core completed the initialization.
+    fn wait(&self) -> &T {
+        while self.state.load(Ordering::Acquire) != INITIALIZED {
+            core::hint::spin_loop();
+        }
+
+        unsafe { (*self.value.get()).assume_init_ref() }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+unsafe impl<T> Send for OnceCell<T> where T: Send {}
+unsafe impl<T> Sync for OnceCell<T> where T: Send + Sync {}
+
+impl<T> OnceCell<T> {
+    /// Create an empty instance.
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(UNINITIALIZED),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Return the value, if it was set.
+    pub fn get(&self) -> Option<&T> {
+        if self.state.load(Ordering::Acquire) != INITIALIZED {
+            return None;
+        }
+
+        Some(unsafe { (*self.value.get()).assume_init_ref() })
+    }
+
+    /// Set the value. Returns the value if the cell was set before.
+    pub fn set(&self, value: T) -> Result<(), T> {
+        if !self.try_claim() {
+            return Err(value);
+        }
+
+        self.complete(value);
+
+        Ok(())
+    }
+
+    /// Return the value, and construct it with `f` if it was not set yet.
+    ///
+    /// If another core is constructing the value concurrently, wait for it. `f` must not access the
+    /// cell, because that would wait forever.
+    pub fn get_or_init(&self, f: impl FnOnce() -> T) -> &T {
+        if let Some(value) = self.get() {
+            return value;
+        }
+
+        if self.try_claim() {
+            self.complete(f())
+        } else {
+            self.wait()
+        }
+    }
+}
+
+impl<T> Default for OnceCell<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for OnceCell<T> {
+    fn drop(&mut self) {
+        if *self.state.get_mut() == INITIALIZED {
+            unsafe { self.value.get_mut().assume_init_drop() };
+        }
+    }
+}
+
+impl<T> LazyInit<T> {
+    /// Create an instance.
+    pub const fn new(init: fn() -> T) -> Self {
+        Self {
+            cell: OnceCell::new(),
+            init,
+        }
+    }
+}
+
+impl<T> Deref for LazyInit<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.cell.get_or_init(self.init)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
+
+    /// A OnceCell must only accept the first value, and LazyInit must construct its value once.
+    #[kernel_test]
+    fn once_cell_is_set_once() {
+        let cell = OnceCell::new();
+
+        assert!(cell.get().is_none());
+        assert_eq!(cell.set(1), Ok(()));
+        assert_eq!(cell.set(2), Err(2));
+        assert_eq!(cell.get_or_init(|| 3), &1);
+
+        let lazy = LazyInit::new(|| 42);
+        assert_eq!(*lazy, 42);
+        assert_eq!(*lazy, 42);
+    }
+}
@@ -0,0 +1,211 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! A lock-free ring buffer.
+//!
+//! Intended for handing data from IRQ handlers to threads, and between cores. Any number of
+//! producers and consumers can use the buffer at the same time, without masking IRQs.
+//!
+//! # Memory ordering
+//!
+//! Each slot has a stamp that tells whether it is free or holds a value, and for which round
+//! through the buffer. A producer claims a slot by advancing the shared write position, writes the
+//! value, and then publishes it with a `Release` store of the stamp. A consumer reads the stamp
+//! with `Acquire` before reading the value, so it is guaranteed to see the complete value. Freeing
+//! the slot works the same way in the opposite direction.
+//!
+//! On aarch64, the `Acquire` loads compile to `ldar` and the `Release` stores to `stlr`, which are
+//! ordered against all earlier and later accesses respectively, also across cores. The positions
+//! themselves only need `Relaxed` ordering, because the stamps carry the synchronization.
+//!
+//! A producer that is interrupted after claiming a slot, but before publishing it, holds up the
+//! consumers: They find the buffer empty at this slot until the producer continues.
+
+use core::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A fixed-capacity FIFO of `N` elements that can be shared without a lock.
+pub struct RingBuffer<T, const N: usize> {
+    /// For the `n`-th round through the buffer, a stamp of `2 * n` means free and `2 * n + 1`
+    /// means filled.
+    stamps: [AtomicUsize; N],
+    values: UnsafeCell<MaybeUninit<[T; N]>>,
+
+    /// The number of values pushed, including those whose push is in progress.
+    write_pos: AtomicUsize,
+
+    /// The number of values popped, including those whose pop is in progress.
+    read_pos: AtomicUsize,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl<T, const N: usize> RingBuffer<T, N> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const FREE: AtomicUsize = AtomicUsize::new(0);
+
+    /// Return the slot index and the round through the buffer of a position.
+    fn slot(pos: usize) -> (usize, usize) {
+        (pos % N, pos / N)
+    }
+
+    fn value_ptr(&self, index: usize) -> *mut T {
+        unsafe { (self.values.get() as *mut T).add(index) }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+unsafe impl<T, const N: usize> Send for RingBuffer<T, N> where T: Send {}
+unsafe impl<T, const N: usize> Sync for RingBuffer<T, N> where T: Send {}
+
+impl<T, const N: usize> RingBuffer<T, N> {
+    /// Create an instance.
+    pub const fn new() -> Self {
+        Self {
+            stamps: [Self::FREE; N],
+            values: UnsafeCell::new(MaybeUninit::uninit()),
+            write_pos: AtomicUsize::new(0),
+            read_pos: AtomicUsize::new(0),
+        }
+    }
+
+    /// Append a value. Returns the value if the buffer is full.
+    pub fn push(&self, value: T) -> Result<(), T> {
+        let mut pos = self.write_pos.load(Ordering::Relaxed);
+
+        loop {
+            let (index, round) = Self::slot(pos);
+            let stamp = self.stamps[index].load(Ordering::Acquire);
+
+            if stamp == 2 * round {
+                // The slot is free. Claim it, unless another producer was faster.
+                match self.write_pos.compare_exchange_weak(
+                    pos,
+                    pos + 1,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        unsafe { self.value_ptr(index).write(value) };
+                        self.stamps[index].store(2 * round + 1, Ordering::Release);
+
+                        return Ok(());
+                    }
+                    Err(current) => pos = current,
+                }
+            } else if stamp < 2 * round {
+                // The slot still holds the value of the previous round.
+                return Err(value);
+            } else {
+                // Another producer filled the slot already.
+                pos = self.write_pos.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Remove the oldest value.
+    pub fn pop(&self) -> Option<T> {
+        let mut pos = self.read_pos.load(Ordering::Relaxed);
+
+        loop {
+            let (index, round) = Self::slot(pos);
+            let stamp = self.stamps[index].load(Ordering::Acquire);
+
+            if stamp == 2 * round + 1 {
+                // The slot is filled. Claim it, unless another consumer was faster.
+                match self.read_pos.compare_exchange_weak(
+                    pos,
+                    pos + 1,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        let value = unsafe { self.value_ptr(index).read() };
+                        self.stamps[index].store(2 * (round + 1), Ordering::Release);
+
+                        return Some(value);
+                    }
+                    Err(current) => pos = current,
+                }
+            } else if stamp < 2 * round + 1 {
+                // The slot was not filled yet.
+                return None;
+            } else {
+                // Another consumer emptied the slot already.
+                pos = self.read_pos.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// The number of values in the buffer. Only a snapshot if others use the buffer concurrently.
+    pub fn len(&self) -> usize {
+        let read_pos = self.read_pos.load(Ordering::Relaxed);
+        let write_pos = self.write_pos.load(Ordering::Relaxed);
+
+        write_pos.saturating_sub(read_pos)
+    }
+
+    /// Return whether the buffer is empty. Only a snapshot if others use the buffer concurrently.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The maximum number of values in the buffer.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+}
+
+impl<T, const N: usize> Default for RingBuffer<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Drop for RingBuffer<T, N> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
+
+    /// Values must come out in order, also across rounds, and a full buffer must reject values.
+    #[kernel_test]
+    fn ring_buffer_is_fifo_and_bounded() {
+        let buffer: RingBuffer<usize, 4> = RingBuffer::new();
+
+        for round in 0..3 {
+            for i in 0..4 {
+                buffer.push(round * 4 + i).unwrap();
+            }
+            assert_eq!(buffer.push(99), Err(99));
+            assert_eq!(buffer.len(), 4);
+
+            for i in 0..4 {
+                assert_eq!(buffer.pop(), Some(round * 4 + i));
+            }
+            assert_eq!(buffer.pop(), None);
+        }
+    }
+}
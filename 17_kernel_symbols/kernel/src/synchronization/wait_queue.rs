@@ -0,0 +1,149 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Blocking threads until a condition holds.
+//!
+//! A thread waits on a [`WaitQueue`] until another thread or an IRQ handler wakes it, e.g. because
+//! a driver received data. The waiting thread does not consume CPU time in the meantime.
+
+use crate::{exception, scheduler};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+// Each thread needs a bit in the waiter mask.
+const _: () = assert!(scheduler::MAX_THREADS <= usize::BITS as usize);
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A set of threads that wait for something to happen.
+pub struct WaitQueue {
+    /// Bitmask of the waiting threads' indices.
+    waiters: AtomicUsize,
+}
+
+/// A flag that threads can wait for.
+///
+/// Once signaled, the event stays set until a waiter consumes it.
+pub struct Event {
+    signaled: AtomicBool,
+    queue: WaitQueue,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl WaitQueue {
+    /// Create an instance.
+    pub const fn new() -> Self {
+        Self {
+            waiters: AtomicUsize::new(0),
+        }
+    }
+
+    /// Block the running thread until `condition` returns true.
+    ///
+    /// The condition is checked with IRQs masked, so that a wakeup from an IRQ handler can not get
+    /// lost between the check and blocking. Must not be called with IRQs masked, because the
+    /// wakeup could never arrive then.
+    pub fn wait_until(&self, mut condition: impl FnMut() -> bool) {
+        let bit = 1 << scheduler::current().index();
+
+        loop {
+            let done = exception::asynchronous::exec_with_irq_masked(|| {
+                if condition() {
+                    return true;
+                }
+
+                self.waiters.fetch_or(bit, Ordering::Relaxed);
+                scheduler::block_current();
+                self.waiters.fetch_and(!bit, Ordering::Relaxed);
+
+                false
+            });
+
+            if done {
+                return;
+            }
+        }
+    }
+
+    /// Return whether any thread is waiting.
+    pub fn has_waiters(&self) -> bool {
+        self.waiters.load(Ordering::Relaxed) != 0
+    }
+
+    /// Wake all waiting threads. They re-check their condition when they run next.
+    ///
+    /// Can be called from IRQ handlers.
+    pub fn wake_all(&self) {
+        let mut waiters = self.waiters.swap(0, Ordering::Relaxed);
+
+        while waiters != 0 {
+            let index = waiters.trailing_zeros() as usize;
+            waiters &= !(1 << index);
+
+            scheduler::unblock(scheduler::ThreadId::from_index(index));
+        }
+    }
+}
+
+impl Event {
+    /// Create an instance that is not signaled.
+    pub const fn new() -> Self {
+        Self {
+            signaled: AtomicBool::new(false),
+            queue: WaitQueue::new(),
+        }
+    }
+
+    /// Block the running thread until the event is signaled, and reset it.
+    pub fn wait(&self) {
+        self.queue
+            .wait_until(|| self.signaled.swap(false, Ordering::Relaxed));
+    }
+
+    /// Signal the event and wake the waiting threads. Only one of them consumes the signal.
+    ///
+    /// Can be called from IRQ handlers.
+    pub fn signal(&self) {
+        self.signaled.store(true, Ordering::Relaxed);
+        self.queue.wake_all();
+    }
+
+    /// Return whether the event is signaled.
+    pub fn is_signaled(&self) -> bool {
+        self.signaled.load(Ordering::Relaxed)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
+
+    static EVENT: Event = Event::new();
+
+    fn signal_event() {
+        EVENT.signal();
+    }
+
+    /// Check that a thread blocked on an event continues once another thread signals it.
+    #[kernel_test]
+    fn event_wakes_waiter() {
+        scheduler::spawn("signaler", signal_event).unwrap();
+        EVENT.wait();
+
+        assert!(!EVENT.is_signaled());
+    }
+}
@@ -0,0 +1,326 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! User tasks.
+//!
+//! A task executes in user space and can only interact with the kernel through system calls. For
+//! now, a single task can run at a time, and the kernel waits until it exits.
+
+#[cfg(target_arch = "aarch64")]
+#[path = "_arch/aarch64/task.rs"]
+mod arch_task;
+
+use crate::{
+    bsp, common,
+    memory::{
+        self,
+        mmu::{AccessPermissions, AttributeFields, MemAttributes, MemoryRegion, PageAddress},
+        Address, Virtual,
+    },
+    synchronization::{
+        interface::{Mutex, ReadWriteEx},
+        InitStateLock, SpinLock,
+    },
+    time::Timer,
+};
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const DEMO_STACK_SIZE: usize = bsp::memory::mmu::KernelGranule::SIZE;
+
+/// The stack of the demo task. Aligned to the granule so that it can be mapped on its own.
+#[repr(C, align(65536))]
+struct UserStack(UnsafeCell<[u8; DEMO_STACK_SIZE]>);
+
+/// Notification state of the running task.
+struct NotificationState {
+    /// Bitmask of queued notifications.
+    pending: AtomicU32,
+
+    /// Bitmask of notifications the task does not want to receive right now.
+    mask: AtomicU32,
+
+    /// User space address of the notification handler. Zero if none is registered.
+    handler: AtomicUsize,
+
+    /// True while the handler executes. No further notifications are delivered in the meantime.
+    in_handler: AtomicBool,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Notifications that the kernel sends to a task.
+///
+/// A notification is delivered by redirecting the task to its notification handler the next time
+/// the kernel returns to it. The handler receives the notification number in `x0` and finishes
+/// with the `NOTIFY_RETURN` system call, after which the task continues where it was interrupted.
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum Notification {
+    TimerExpired = 0,
+    CharAvailable = 1,
+}
+
+/// The exit code of a task that the kernel terminated because it caused an exception.
+pub const EXIT_CODE_FAULT: u64 = u64::MAX;
+
+/// A task that executes in user space.
+#[derive(Copy, Clone)]
+pub struct UserTask {
+    entry: Address<Virtual>,
+    stack_end_exclusive: Address<Virtual>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static DEMO_STACK: UserStack = UserStack(UnsafeCell::new([0; DEMO_STACK_SIZE]));
+
+static DEMO_TASK: InitStateLock<Option<UserTask>> = InitStateLock::new(None);
+
+/// True while a task executes.
+static TASK_RUNNING: AtomicBool = AtomicBool::new(false);
+
+static NOTIFICATIONS: NotificationState = NotificationState::new();
+
+/// The software timer that sends [`Notification::TimerExpired`] to the running task.
+static ALARM: SpinLock<Option<Timer>> = SpinLock::new(None);
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+// The kernel itself never accesses the stack.
+unsafe impl Sync for UserStack {}
+
+impl NotificationState {
+    const fn new() -> Self {
+        Self {
+            pending: AtomicU32::new(0),
+            mask: AtomicU32::new(0),
+            handler: AtomicUsize::new(0),
+            in_handler: AtomicBool::new(false),
+        }
+    }
+
+    fn reset(&self) {
+        self.pending.store(0, Ordering::Relaxed);
+        self.mask.store(0, Ordering::Relaxed);
+        self.handler.store(0, Ordering::Relaxed);
+        self.in_handler.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Check if user space can execute the instruction at `addr`.
+fn is_user_executable(addr: Address<Virtual>) -> bool {
+    if !common::is_aligned(addr.as_usize(), 4) {
+        return false;
+    }
+
+    memory::mmu::try_kernel_page_attributes(PageAddress::from(addr.align_down_page()))
+        .map(|attributes| attributes.acc_perms.is_user() && !attributes.execute_never)
+        .unwrap_or(false)
+}
+
+fn alarm_expired() {
+    notify(Notification::TimerExpired);
+}
+
+impl Notification {
+    const fn bit(self) -> u32 {
+        1 << (self as u32)
+    }
+
+    fn from_number(number: u32) -> Option<Self> {
+        match number {
+            0 => Some(Self::TimerExpired),
+            1 => Some(Self::CharAvailable),
+            _ => None,
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl UserTask {
+    /// Create an instance.
+    pub const fn new(entry: Address<Virtual>, stack_end_exclusive: Address<Virtual>) -> Self {
+        Self {
+            entry,
+            stack_end_exclusive,
+        }
+    }
+
+    /// Execute the task until it exits, and return its exit code.
+    pub fn run(&self) -> u64 {
+        assert!(
+            !TASK_RUNNING.swap(true, Ordering::Relaxed),
+            "Only one task can run at a time"
+        );
+
+        NOTIFICATIONS.reset();
+        let exit_code = unsafe { arch_task::enter_user(self.entry, self.stack_end_exclusive) };
+
+        // Free the timer slot of an alarm that did not expire.
+        let _ = set_alarm(None);
+        TASK_RUNNING.store(false, Ordering::Relaxed);
+
+        exit_code
+    }
+}
+
+/// Map the demo program and its stack into user space.
+///
+/// # Safety
+///
+/// - Modifies the kernel translation tables. Only call during kernel init.
+pub unsafe fn init_demo_task() -> Result<(), &'static str> {
+    let (kernel_code_region, kernel_entry) = arch_task::demo_program();
+    let code_region = memory::mmu::kernel_map_user_alias(
+        "Demo task code",
+        &kernel_code_region,
+        &AttributeFields {
+            mem_attributes: MemAttributes::CacheableDRAM,
+            acc_perms: AccessPermissions::UserReadOnly,
+            execute_never: false,
+        },
+    )?;
+
+    let stack_start = DEMO_STACK.0.get() as usize;
+    let kernel_stack_region =
+        MemoryRegion::new(stack_start.into(), (stack_start + DEMO_STACK_SIZE).into());
+    let stack_region = memory::mmu::kernel_map_user_alias(
+        "Demo task stack",
+        &kernel_stack_region,
+        &AttributeFields {
+            mem_attributes: MemAttributes::CacheableDRAM,
+            acc_perms: AccessPermissions::UserReadWrite,
+            execute_never: true,
+        },
+    )?;
+
+    let entry =
+        code_region.start_addr() + (kernel_entry - kernel_code_region.start_addr()).as_usize();
+    let stack_end_exclusive = stack_region.end_exclusive_page_addr().into_inner();
+
+    DEMO_TASK.write(|task| *task = Some(UserTask::new(entry, stack_end_exclusive)));
+
+    Ok(())
+}
+
+/// Return the demo task, if it was set up.
+pub fn demo_task() -> Option<UserTask> {
+    DEMO_TASK.read(|task| *task)
+}
+
+/// Terminate the running task. Returns if no task is running.
+///
+/// # Safety
+///
+/// - Must only be called from a system call, or from the handler of an exception that the task
+///   caused.
+pub unsafe fn exit_current(exit_code: u64) {
+    if TASK_RUNNING.load(Ordering::Relaxed) {
+        arch_task::return_to_kernel(exit_code)
+    }
+}
+
+/// Queue a notification for the running task.
+///
+/// Can be called from IRQ context. Sending the same notification again before it was delivered has
+/// no additional effect.
+pub fn notify(notification: Notification) {
+    if TASK_RUNNING.load(Ordering::Relaxed) {
+        NOTIFICATIONS
+            .pending
+            .fetch_or(notification.bit(), Ordering::Relaxed);
+    }
+}
+
+/// Send [`Notification::TimerExpired`] to the running task when the uptime reaches `deadline`.
+///
+/// Replaces an earlier alarm that did not expire yet. `None` only cancels it.
+pub fn set_alarm(deadline: Option<Duration>) -> Result<(), &'static str> {
+    ALARM.lock(|alarm| {
+        if let Some(timer) = alarm.take() {
+            timer.cancel();
+        }
+
+        if let Some(deadline) = deadline {
+            *alarm = Some(Timer::at(deadline, alarm_expired)?);
+        }
+
+        Ok(())
+    })
+}
+
+/// Set the user space address of the running task's notification handler. Zero unregisters it.
+///
+/// The handler must be executable from user space. This is checked here, because a bad address
+/// would otherwise only fault once a notification is delivered.
+pub fn set_notification_handler(handler: Address<Virtual>) -> Result<(), &'static str> {
+    if handler.as_usize() != 0 && !is_user_executable(handler) {
+        return Err("Notification handler is not executable from user space");
+    }
+
+    NOTIFICATIONS
+        .handler
+        .store(handler.as_usize(), Ordering::Relaxed);
+
+    Ok(())
+}
+
+/// Set the running task's notification mask and return the previous one.
+///
+/// Masked notifications stay queued until they are unmasked.
+pub fn set_notification_mask(mask: u32) -> u32 {
+    NOTIFICATIONS.mask.swap(mask, Ordering::Relaxed)
+}
+
+/// Dequeue the next notification that can be delivered to the running task, along with the address
+/// of its handler.
+///
+/// The caller must redirect the task to the handler.
+pub fn take_deliverable_notification() -> Option<(Address<Virtual>, Notification)> {
+    let state = &NOTIFICATIONS;
+
+    let handler = state.handler.load(Ordering::Relaxed);
+    if handler == 0 || state.in_handler.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    let deliverable = state.pending.load(Ordering::Relaxed) & !state.mask.load(Ordering::Relaxed);
+    let notification = Notification::from_number(deliverable.trailing_zeros())?;
+
+    state
+        .pending
+        .fetch_and(!notification.bit(), Ordering::Relaxed);
+    state.in_handler.store(true, Ordering::Relaxed);
+
+    Some((Address::new(handler), notification))
+}
+
+/// Conclude the running notification handler.
+///
+/// The caller must restore the task's state from before the handler was entered.
+pub fn notification_handler_done() -> Result<(), &'static str> {
+    if !NOTIFICATIONS.in_handler.swap(false, Ordering::Relaxed) {
+        return Err("No notification handler running");
+    }
+
+    Ok(())
+}
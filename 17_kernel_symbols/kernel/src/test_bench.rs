@@ -0,0 +1,133 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Benchmarks, see `#[kernel_bench]`.
+//!
+//! Each run of a benchmark is measured with the PMU's cycle counter, which is far more precise
+//! than the generic timer for short code. Runs are not isolated from interrupts, so the maximum
+//! can include the time spent in IRQ handlers. The median is the number to compare.
+
+use crate::cpu;
+use core::fmt;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Must match `MAX_BENCH_ITERATIONS` of `test_macros`.
+const MAX_ITERATIONS: usize = 1024;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The cycles that the runs of a benchmark took.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct BenchResult {
+    /// The number of runs.
+    pub iterations: usize,
+
+    /// The fastest run.
+    pub min: u64,
+
+    /// The median run.
+    pub median: u64,
+
+    /// The slowest run.
+    pub max: u64,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl BenchResult {
+    /// Summarize the measured cycles of all runs.
+    fn from_samples(samples: &mut [u64]) -> Self {
+        samples.sort_unstable();
+
+        Self {
+            iterations: samples.len(),
+            min: samples[0],
+            median: samples[samples.len() / 2],
+            max: samples[samples.len() - 1],
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Call `f` `iterations` times, and measure each call.
+pub fn run(f: fn(), iterations: usize) -> BenchResult {
+    let iterations = iterations.clamp(1, MAX_ITERATIONS);
+    let mut samples = [0_u64; MAX_ITERATIONS];
+
+    cpu::enable_cycle_counter();
+
+    for sample in samples[..iterations].iter_mut() {
+        let start = cpu::cycle_counter();
+        f();
+        *sample = cpu::cycle_counter().wrapping_sub(start);
+    }
+
+    BenchResult::from_samples(&mut samples[..iterations])
+}
+
+/// Formats as `min 12, median 14, max 80 cycles (100 iterations)`.
+impl fmt::Display for BenchResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "min {}, median {}, max {} cycles ({} iterations)",
+            self.min, self.median, self.max, self.iterations
+        )
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::{kernel_bench, kernel_test};
+
+    /// The summary must pick the fastest, median and slowest run.
+    #[kernel_test]
+    fn samples_are_summarized() {
+        let mut samples = [30, 10, 50, 20, 40];
+
+        assert_eq!(
+            BenchResult::from_samples(&mut samples),
+            BenchResult {
+                iterations: 5,
+                min: 10,
+                median: 30,
+                max: 50,
+            }
+        );
+    }
+
+    /// The cycle counter must count, or benchmarks would measure nothing.
+    #[kernel_test]
+    fn cycle_counter_counts() {
+        cpu::enable_cycle_counter();
+
+        let start = cpu::cycle_counter();
+        for _ in 0..100 {
+            cpu::nop();
+        }
+
+        assert!(cpu::cycle_counter() > start);
+    }
+
+    /// The cost of reading the cycle counter, which is included in every measurement.
+    #[kernel_bench(iterations = 1000)]
+    fn read_cycle_counter() {
+        cpu::cycle_counter();
+    }
+}
@@ -0,0 +1,137 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Machine-readable results of the test runner.
+//!
+//! Besides its human-readable lines, [`crate::test_runner()`] prints one JSON object per line, so
+//! that host tooling can aggregate results across crates. The objects have a `"ktest"` key, which
+//! tells them apart from the other console output:
+//!
+//! ```text
+//! {"ktest":"suite","tests":3}
+//! {"ktest":"result","index":1,"name":"foo","outcome":"ok","duration_us":12}
+//! {"ktest":"bench","name":"baz","iterations":100,"min_cycles":9,"median_cycles":9,"max_cycles":30}
+//! {"ktest":"result","index":2,"name":"baz","outcome":"ok","duration_us":25}
+//! {"ktest":"result","index":3,"name":"bar","outcome":"failed","duration_us":40}
+//! ```
+//!
+//! A failing test panics, so its result is printed by the panic handler, and nothing follows it.
+//! If all tests pass, the suite ends with a `"done"` object. Test names are Rust identifiers, so
+//! they need no escaping.
+
+use crate::{
+    println,
+    synchronization::{interface::Mutex, SpinLock},
+    test_bench::BenchResult,
+    time::{self, interface::TimeManager},
+};
+use core::{fmt, time::Duration};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+#[derive(Copy, Clone)]
+struct RunningTest {
+    index: usize,
+    name: &'static str,
+    start: Duration,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The result of a test, which formats as its JSON object.
+pub struct TestResult {
+    test: RunningTest,
+    outcome: &'static str,
+    duration: Duration,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+/// The test that is running, for reporting it from the panic handler.
+static RUNNING_TEST: SpinLock<Option<RunningTest>> = SpinLock::new(None);
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn finish_running_test(outcome: &'static str) -> Option<TestResult> {
+    let test = RUNNING_TEST.lock(|running| running.take())?;
+
+    Some(TestResult {
+        test,
+        outcome,
+        duration: time::time_manager().uptime().saturating_sub(test.start),
+    })
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Report the start of a suite of `num_tests` tests.
+pub fn suite_started(num_tests: usize) {
+    println!("{{\"ktest\":\"suite\",\"tests\":{}}}", num_tests);
+}
+
+/// Remember the test that is about to run. `index` counts from 1.
+pub fn test_started(index: usize, name: &'static str) {
+    let test = RunningTest {
+        index,
+        name,
+        start: time::time_manager().uptime(),
+    };
+
+    RUNNING_TEST.lock(|running| *running = Some(test));
+}
+
+/// Report the measurements of the running benchmark.
+pub fn bench_finished(name: &str, result: &BenchResult) {
+    println!(
+        "{{\"ktest\":\"bench\",\"name\":\"{}\",\"iterations\":{},\"min_cycles\":{},\
+         \"median_cycles\":{},\"max_cycles\":{}}}",
+        name, result.iterations, result.min, result.median, result.max
+    );
+}
+
+/// Report that the running test passed.
+pub fn test_passed() {
+    if let Some(result) = finish_running_test("ok") {
+        println!("{}", result);
+    }
+}
+
+/// Report that all `num_tests` tests passed, after `duration`.
+pub fn suite_passed(num_tests: usize, duration: Duration) {
+    println!(
+        "{{\"ktest\":\"done\",\"passed\":{},\"failed\":0,\"duration_us\":{}}}",
+        num_tests,
+        duration.as_micros()
+    );
+}
+
+/// The result of the running test, if there is one, which failed. Called by the panic handler,
+/// which prints it on the panic console.
+pub fn test_failed() -> Option<TestResult> {
+    finish_running_test("failed")
+}
+
+impl fmt::Display for TestResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{{\"ktest\":\"result\",\"index\":{},\"name\":\"{}\",\"outcome\":\"{}\",\
+             \"duration_us\":{}}}",
+            self.test.index,
+            self.test.name,
+            self.outcome,
+            self.duration.as_micros()
+        )
+    }
+}
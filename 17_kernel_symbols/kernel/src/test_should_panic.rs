@@ -0,0 +1,114 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Tests that must panic, see `#[kernel_test(should_panic)]`.
+//!
+//! The kernel can not unwind. Instead, a test that should panic runs on a stack of its own, in a
+//! context that the test runner switches to. When the test panics, the panic handler switches back
+//! to the runner before printing anything, which abandons the test's stack, and the runner
+//! continues with the next test. A test that returns instead fails.
+//!
+//! Everything the test held when it panicked stays held, e.g. a lock. Tests should therefore panic
+//! before touching shared state, or not at all.
+
+use crate::{
+    cpu::{self, ThreadContext},
+    exception,
+    synchronization::{interface::Mutex, SpinLock},
+};
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const TEST_STACK_SIZE: usize = 64 * 1024;
+
+#[repr(C, align(16))]
+struct TestStack(UnsafeCell<[u8; TEST_STACK_SIZE]>);
+
+/// The context of the test runner while a test that should panic runs.
+struct RunnerContext(UnsafeCell<ThreadContext>);
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static TEST_STACK: TestStack = TestStack(UnsafeCell::new([0; TEST_STACK_SIZE]));
+
+static RUNNER_CONTEXT: RunnerContext = RunnerContext(UnsafeCell::new(ThreadContext::new()));
+
+/// The test that is about to start.
+static TEST_FUNC: SpinLock<Option<fn()>> = SpinLock::new(None);
+
+/// Set while a test that should panic runs.
+static EXPECTING_PANIC: AtomicBool = AtomicBool::new(false);
+
+/// Set when the running test panicked.
+static PANICKED: AtomicBool = AtomicBool::new(false);
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+// Only the single test that should panic uses the stack, and only the runner uses the context.
+unsafe impl Sync for TestStack {}
+unsafe impl Sync for RunnerContext {}
+
+/// Continue in the test runner. The context of the caller is never resumed.
+fn switch_to_runner() -> ! {
+    EXPECTING_PANIC.store(false, Ordering::Relaxed);
+
+    let mut abandoned = ThreadContext::new();
+    unsafe { cpu::switch_to(&mut abandoned, RUNNER_CONTEXT.0.get()) };
+
+    unreachable!()
+}
+
+/// The first code that the test context executes.
+extern "C" fn test_start() -> ! {
+    let test_func = TEST_FUNC.lock(|f| f.take()).unwrap();
+
+    test_func();
+    switch_to_runner()
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Run `test_func` on the test stack, and return whether it panicked.
+pub fn run(test_func: fn()) -> bool {
+    let stack_end_exclusive = TEST_STACK.0.get() as usize + TEST_STACK_SIZE;
+    let context =
+        ThreadContext::new_for_start(test_start, stack_end_exclusive, cpu::thread_pointer());
+
+    TEST_FUNC.lock(|f| *f = Some(test_func));
+    PANICKED.store(false, Ordering::Relaxed);
+    EXPECTING_PANIC.store(true, Ordering::Relaxed);
+
+    // The panic handler masks IRQs, so they are unmasked again if they were before the test.
+    let irq_masked = exception::asynchronous::is_local_irq_masked();
+    unsafe { cpu::switch_to(RUNNER_CONTEXT.0.get(), &context) };
+    if !irq_masked {
+        unsafe { exception::asynchronous::local_irq_unmask() };
+    }
+
+    PANICKED.load(Ordering::Relaxed)
+}
+
+/// Called by the panic handler. If a test that should panic runs, switch back to the test runner.
+/// Otherwise, return.
+#[cfg(feature = "test_build")]
+pub fn resume_runner_if_expected() {
+    if !EXPECTING_PANIC.load(Ordering::Relaxed) {
+        return;
+    }
+
+    PANICKED.store(true, Ordering::Relaxed);
+    switch_to_runner()
+}
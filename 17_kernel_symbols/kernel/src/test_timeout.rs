@@ -0,0 +1,74 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Timeouts of tests, see `#[kernel_test(timeout = "...")]`.
+//!
+//! While a test with a timeout runs, each timer tick checks its deadline. A test that hangs is
+//! failed from the tick with a panic, which names the test and exits QEMU with a failure code.
+//!
+//! Without the timer tick, e.g. in the unit tests, hangs can not be interrupted. The test runner
+//! then only fails tests that returned after their deadline.
+
+use crate::{
+    synchronization::{interface::Mutex, SpinLock},
+    time::{self, interface::TimeManager},
+};
+use core::time::Duration;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+#[derive(Copy, Clone)]
+struct Deadline {
+    name: &'static str,
+    timeout: Duration,
+    deadline: Duration,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static ARMED: SpinLock<Option<Deadline>> = SpinLock::new(None);
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn check(x: Deadline, now: Duration) {
+    if now > x.deadline {
+        panic!("Test {} timed out after {:?}", x.name, x.timeout);
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Fail the test `name` if it is still running after `timeout`.
+pub fn arm(name: &'static str, timeout: Duration) {
+    let deadline = Deadline {
+        name,
+        timeout,
+        deadline: time::time_manager().uptime() + timeout,
+    };
+
+    ARMED.lock(|armed| *armed = Some(deadline));
+}
+
+/// The test returned. Fail it if it returned too late, which can happen without the timer tick.
+pub fn disarm() {
+    if let Some(x) = ARMED.lock(|armed| armed.take()) {
+        check(x, time::time_manager().uptime());
+    }
+}
+
+/// Check the deadline of the running test. Registered as a tick callback in test builds.
+#[cfg(feature = "test_build")]
+pub fn handle_tick(now: Duration) {
+    if let Some(x) = ARMED.lock(|armed| *armed) {
+        check(x, now);
+    }
+}
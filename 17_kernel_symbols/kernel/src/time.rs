@@ -3,20 +3,46 @@
 // Copyright (c) 2020-2022 Andre Richter <andre.o.richter@gmail.com>
 
 //! Timer primitives.
+//!
+//! [`Stopwatch`] and [`measure!`] measure how long code takes to run.
+//!
+//! Besides the uptime, the kernel keeps a wall clock once it was told the current time, e.g. by a
+//! system call or, in the future, an RTC driver.
 
 #[cfg(target_arch = "aarch64")]
 #[path = "_arch/aarch64/time.rs"]
 mod arch_time;
 
+mod calendar;
+mod stopwatch;
+mod timer;
+
+pub mod tick;
+
+use crate::{exception, scheduler, warn};
+use core::{fmt, time::Duration};
+
 //--------------------------------------------------------------------------------------------------
 // Architectural Public Reexports
 //--------------------------------------------------------------------------------------------------
-pub use arch_time::time_manager;
+pub use arch_time::{counter_ticks, delay_ns, duration_to_ticks, ticks_to_duration, time_manager};
+
+//--------------------------------------------------------------------------------------------------
+// Public Reexports
+//--------------------------------------------------------------------------------------------------
+pub use crate::measure;
+pub use calendar::DateTime;
+pub use stopwatch::Stopwatch;
+pub use timer::Timer;
 
 //--------------------------------------------------------------------------------------------------
 // Public Definitions
 //--------------------------------------------------------------------------------------------------
 
+/// Returned by [`with_timeout()`] if the condition was not met in time.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TimeoutError;
+
 /// Timekeeping interfaces.
 pub mod interface {
     use core::time::Duration;
@@ -32,6 +58,110 @@ pub mod interface {
         fn uptime(&self) -> Duration;
 
         /// Spin for a given duration.
-        fn spin_for(&self, duration: Duration);
+        ///
+        /// Returns an error if the duration is shorter than the resolution, or too long for the
+        /// timer hardware.
+        fn spin_for(&self, duration: Duration) -> Result<(), &'static str>;
+
+        /// Set the wall clock, as the time since the Unix epoch.
+        fn set_wall_clock(&self, unix_time: Duration);
+
+        /// The wall clock, as the time since the Unix epoch. `None` if it was never set.
+        fn wall_clock(&self) -> Option<Duration>;
     }
 }
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Timeout")
+    }
+}
+
+impl From<TimeoutError> for &'static str {
+    fn from(_: TimeoutError) -> Self {
+        "Timeout"
+    }
+}
+
+/// Call `poll` until it returns a value, or until `timeout` has passed.
+///
+/// Meant for waiting on hardware, so that a device that never responds produces an error instead
+/// of a hang. `poll` is called at least once, even with a zero timeout.
+pub fn with_timeout<T>(
+    timeout: Duration,
+    mut poll: impl FnMut() -> Option<T>,
+) -> Result<T, TimeoutError> {
+    use interface::TimeManager;
+
+    let deadline = time_manager().uptime() + timeout;
+
+    loop {
+        #[cfg(feature = "fault_inject")]
+        if crate::fault_inject::should_fail(crate::fault_inject::Fault::Timeout) {
+            return Err(TimeoutError);
+        }
+
+        if let Some(value) = poll() {
+            return Ok(value);
+        }
+
+        if time_manager().uptime() > deadline {
+            return Err(TimeoutError);
+        }
+
+        core::hint::spin_loop();
+    }
+}
+
+/// Sleep for at least the given duration, letting other threads run in the meantime.
+///
+/// Before the scheduler is initialized, and while local IRQs are masked, e.g. because the caller
+/// holds a `SpinLock`, this spins instead.
+pub fn sleep(duration: Duration) {
+    use interface::TimeManager;
+
+    if !scheduler::is_initialized() || exception::asynchronous::is_local_irq_masked() {
+        if let Err(x) = time_manager().spin_for(duration) {
+            warn!("Sleep skipped: {}", x);
+        }
+        return;
+    }
+
+    scheduler::sleep_until(time_manager().uptime().saturating_add(duration));
+}
+
+/// Spin for at least `us` microseconds.
+///
+/// Meant for short, precise delays, e.g. in bit-banging drivers. Like [`delay_ns()`], it is safe to
+/// use before the MMU is on.
+pub fn delay_us(us: u64) {
+    delay_ns(us.saturating_mul(1000));
+}
+
+/// Record the boot core's counter properties, which all cores use for time conversions.
+///
+/// # Safety
+///
+/// - Must only be called by the boot core, during kernel init.
+pub unsafe fn init() {
+    arch_time::init();
+}
+
+/// Check that the counters of the executing secondary core are consistent with the boot core's, so
+/// that uptimes taken on different cores can be compared.
+///
+/// A differing counter frequency is reported, but corrected already.
+pub fn init_secondary() -> Result<(), &'static str> {
+    arch_time::check_counter_consistency()
+}
+
+/// Return the current UTC date and time. `None` if the wall clock was never set.
+pub fn calendar_time() -> Option<DateTime> {
+    use interface::TimeManager;
+
+    time_manager().wall_clock().map(DateTime::from_unix_time)
+}
@@ -0,0 +1,104 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Calendar time.
+
+use core::{fmt, time::Duration};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A UTC date and time, in the proleptic Gregorian calendar.
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DateTime {
+    pub year: u32,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    pub nanosecond: u32,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl DateTime {
+    /// Convert the time since the Unix epoch, 1970-01-01 00:00:00 UTC.
+    pub const fn from_unix_time(unix_time: Duration) -> Self {
+        let secs = unix_time.as_secs();
+        let (days, secs_of_day) = (secs / SECS_PER_DAY, secs % SECS_PER_DAY);
+
+        // See <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>. The era is a
+        // 400 year cycle, and the year is shifted to start in March, so that the leap day is last.
+        let z = days + 719_468;
+        let era = z / 146_097;
+        let day_of_era = z - era * 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        Self {
+            year: year as u32,
+            month: month as u8,
+            day: day as u8,
+            hour: (secs_of_day / 3600) as u8,
+            minute: (secs_of_day / 60 % 60) as u8,
+            second: (secs_of_day % 60) as u8,
+            nanosecond: unix_time.subsec_nanos(),
+        }
+    }
+}
+
+/// Formats as ISO 8601, e.g. `2022-04-15T05:20:00Z`.
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
+
+    /// Conversion must be right at the epoch, on leap days, and at the end of a year.
+    #[kernel_test]
+    fn unix_time_converts_to_date() {
+        let date = |secs| {
+            let d = DateTime::from_unix_time(Duration::from_secs(secs));
+            (d.year, d.month, d.day, d.hour, d.minute, d.second)
+        };
+
+        assert_eq!(date(0), (1970, 1, 1, 0, 0, 0));
+        assert_eq!(date(951_782_400), (2000, 2, 29, 0, 0, 0));
+        assert_eq!(date(1_650_000_000), (2022, 4, 15, 5, 20, 0));
+        assert_eq!(date(4_102_444_799), (2099, 12, 31, 23, 59, 59));
+    }
+}
@@ -0,0 +1,109 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Measuring execution time.
+//!
+//! [`measure!`](crate::measure) times a block of code and logs the result, naming the function that
+//! contains the block:
+//!
+//! ```
+//! measure!(memory::mmu::kernel_print_mappings());
+//! ```
+//!
+//! prints e.g. `kernel::kernel_main: memory::mmu::kernel_print_mappings() took 1234 µs`.
+
+use super::arch_time;
+use crate::{
+    backtrace, info,
+    memory::{Address, Virtual},
+    symbols,
+};
+use core::time::Duration;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Measures the time since it was started, in ticks of the architectural counter.
+#[derive(Copy, Clone, Debug)]
+pub struct Stopwatch {
+    start_ticks: u64,
+
+    /// An address in the function that started the stopwatch.
+    caller: Address<Virtual>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl Stopwatch {
+    /// Start a stopwatch.
+    ///
+    /// Must not be inlined, so that the caller can be found through the return address.
+    #[inline(never)]
+    pub fn start() -> Self {
+        // The first return address points into the caller.
+        let caller = backtrace::return_addresses().next().unwrap_or(0);
+
+        // Read the counter last, so that finding the caller is not measured.
+        Self {
+            start_ticks: super::counter_ticks(),
+            caller: Address::new(caller),
+        }
+    }
+
+    /// The number of counter ticks since the stopwatch was started.
+    pub fn elapsed_ticks(&self) -> u64 {
+        super::counter_ticks().wrapping_sub(self.start_ticks)
+    }
+
+    /// The time since the stopwatch was started.
+    pub fn elapsed(&self) -> Duration {
+        arch_time::ticks_to_duration(self.elapsed_ticks())
+    }
+
+    /// Log the elapsed time of `what`, prefixed with the name of the function that started the
+    /// stopwatch.
+    pub fn print_elapsed(&self, what: &str) {
+        let elapsed = self.elapsed();
+
+        match symbols::lookup_symbol(self.caller) {
+            None => info!("Symbol not found: {} took {} µs", what, elapsed.as_micros()),
+            Some(sym) => info!("{}: {} took {} µs", sym.name, what, elapsed.as_micros()),
+        }
+    }
+}
+
+/// Run a block of code, and log how long it took. Evaluates to the value of the block.
+#[macro_export]
+macro_rules! measure {
+    ($($body:tt)*) => ({
+        let stopwatch = $crate::time::Stopwatch::start();
+        let result = { $($body)* };
+        stopwatch.print_elapsed(stringify!($($body)*));
+
+        result
+    })
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
+
+    /// The stopwatch must see at least the time that was spun for.
+    #[kernel_test]
+    fn stopwatch_measures_elapsed_time() {
+        let stopwatch = Stopwatch::start();
+        super::super::delay_us(100);
+
+        assert!(stopwatch.elapsed() >= Duration::from_micros(100));
+        assert!(stopwatch.elapsed_ticks() > 0);
+    }
+}
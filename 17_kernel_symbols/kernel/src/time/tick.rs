@@ -0,0 +1,271 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Periodic timer tick.
+//!
+//! The tick interrupts each core at a configurable frequency, and calls the registered tick
+//! callbacks, e.g. the software timers and the scheduler. The tick period is the time slice of
+//! preemptive scheduling.
+//!
+//! Tick callbacks run in IRQ context on every core. They must be short and must not block.
+//!
+//! # Tickless idle
+//!
+//! A core that has nothing to do does not need to wake up every period. The idle loop calls
+//! [`stop_until()`] before waiting for an interrupt, which programs the next tick for the nearest
+//! pending deadline. Any interrupt ends the sleep, and [`resume()`] restarts the periodic tick.
+//!
+//! A core that makes a thread on a tickless core's run queue runnable wakes that core with a
+//! reschedule IPI. Boards without IPIs keep the tick running in the idle loop.
+
+use super::arch_time;
+use crate::{
+    bsp,
+    cpu::PerCpu,
+    exception, info,
+    synchronization::{interface::ReadWriteEx, InitStateLock},
+};
+use core::{
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+    time::Duration,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The maximum number of tick callbacks.
+const MAX_CALLBACKS: usize = 8;
+
+#[derive(Copy, Clone)]
+struct Callback {
+    name: &'static str,
+    callback: fn(now: Duration),
+}
+
+struct TickHandler;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const TICKING: AtomicBool = AtomicBool::new(false);
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The tick frequency that is used unless changed with [`set_frequency()`].
+pub const DEFAULT_FREQUENCY_HZ: u32 = 100;
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static TICK_HANDLER: TickHandler = TickHandler;
+
+static CALLBACKS: InitStateLock<[Option<Callback>; MAX_CALLBACKS]> =
+    InitStateLock::new([None; MAX_CALLBACKS]);
+
+static FREQUENCY_HZ: AtomicU32 = AtomicU32::new(DEFAULT_FREQUENCY_HZ);
+
+/// Whether the periodic tick was started on a core.
+static STARTED: PerCpu<AtomicBool> = PerCpu::new([TICKING; bsp::cpu::NUM_CORES]);
+
+/// Whether the periodic tick of a core is stopped by [`stop_until()`].
+static STOPPED: PerCpu<AtomicBool> = PerCpu::new([TICKING; bsp::cpu::NUM_CORES]);
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl exception::asynchronous::interface::IRQHandler for TickHandler {
+    fn handle(&self) -> Result<(), &'static str> {
+        use super::interface::TimeManager;
+
+        arch_time::rearm_tick();
+
+        let now = super::time_manager().uptime();
+        CALLBACKS.read(|callbacks| {
+            for callback in callbacks.iter().flatten() {
+                (callback.callback)(now);
+            }
+        });
+
+        Ok(())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Register a function that is called on each tick of each core, with the current uptime.
+///
+/// Callbacks are called in the order they were registered.
+///
+/// # Safety
+///
+/// - Must only be called during kernel init.
+pub unsafe fn register_callback(
+    name: &'static str,
+    callback: fn(now: Duration),
+) -> Result<(), &'static str> {
+    CALLBACKS.write(|callbacks| {
+        let slot = callbacks
+            .iter_mut()
+            .find(|c| c.is_none())
+            .ok_or("No free tick callback slot")?;
+
+        *slot = Some(Callback { name, callback });
+
+        Ok(())
+    })
+}
+
+/// Print the registered tick callbacks.
+pub fn print_callbacks() {
+    CALLBACKS.read(|callbacks| {
+        for (i, callback) in callbacks.iter().flatten().enumerate() {
+            info!("      {}. {}", i + 1, callback.name);
+        }
+    });
+}
+
+/// The tick frequency.
+pub fn frequency() -> u32 {
+    FREQUENCY_HZ.load(Ordering::Relaxed)
+}
+
+/// The tick period.
+pub fn period() -> Duration {
+    Duration::from_secs(1) / frequency()
+}
+
+/// Change the tick frequency. Takes effect with the next tick of each core.
+pub fn set_frequency(hz: u32) -> Result<(), &'static str> {
+    if hz == 0 {
+        return Err("Tick frequency must not be zero");
+    }
+
+    arch_time::set_tick_period(Duration::from_secs(1) / hz)?;
+    FREQUENCY_HZ.store(hz, Ordering::Relaxed);
+
+    Ok(())
+}
+
+/// Stop the periodic tick on the executing core, and let the next tick arrive at the earlier of
+/// `wakeup` and the next software timer deadline instead.
+///
+/// Meant to be called by the idle loop with IRQs masked, right before waiting for an interrupt.
+pub fn stop_until(wakeup: Option<Duration>) {
+    use super::interface::TimeManager;
+
+    if !STARTED.get().load(Ordering::Relaxed) {
+        return;
+    }
+
+    let next_event = match (wakeup, super::timer::next_deadline()) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    };
+
+    // Without pending deadlines, sleep as long as the hardware allows.
+    let delay = match next_event {
+        None => Duration::MAX,
+        Some(x) => x.saturating_sub(super::time_manager().uptime()),
+    };
+
+    STOPPED.get().store(true, Ordering::Relaxed);
+    arch_time::delay_next_tick(delay);
+}
+
+/// Restart the periodic tick on the executing core, after it was stopped by [`stop_until()`].
+pub fn resume() {
+    if !STOPPED.get().swap(false, Ordering::Relaxed) {
+        return;
+    }
+
+    // If the delayed tick arrived, its handler rearms the periodic tick.
+    if !arch_time::tick_pending() {
+        arch_time::start_tick();
+    }
+}
+
+/// Register the tick IRQ handler, and start the tick on the executing core.
+///
+/// The software timers and the scheduler are registered as the first tick callbacks. Test builds
+/// also check the timeouts of tests.
+///
+/// # Safety
+///
+/// - Must only be called during kernel init, after the interrupt controller was initialized.
+pub unsafe fn init() -> Result<(), &'static str> {
+    use exception::asynchronous::{interface::IRQManager, IRQDescriptor};
+
+    set_frequency(frequency())?;
+
+    register_callback("Software timers", super::timer::handle_tick)?;
+    register_callback("Scheduler", crate::scheduler::handle_tick)?;
+
+    #[cfg(feature = "test_build")]
+    register_callback("Test timeouts", crate::test_timeout::handle_tick)?;
+
+    let irq_manager = bsp::exception::asynchronous::irq_manager();
+    let irq = bsp::exception::asynchronous::tick_irq();
+
+    irq_manager.register_handler(
+        irq,
+        IRQDescriptor {
+            name: "Timer tick",
+            handler: &TICK_HANDLER,
+        },
+    )?;
+    irq_manager.enable(irq);
+
+    arch_time::start_tick();
+    STARTED.get().store(true, Ordering::Relaxed);
+
+    Ok(())
+}
+
+/// Start the tick on a secondary core. The IRQ handler was registered by [`init()`].
+///
+/// # Safety
+///
+/// - Must only be called during the init of a secondary core.
+pub unsafe fn init_secondary() -> Result<(), &'static str> {
+    use exception::asynchronous::interface::IRQManager;
+
+    if !arch_time::tick_period_set() {
+        return Err("Tick not initialized");
+    }
+
+    bsp::exception::asynchronous::irq_manager().enable(bsp::exception::asynchronous::tick_irq());
+    arch_time::start_tick();
+    STARTED.get().store(true, Ordering::Relaxed);
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
+
+    /// The period must follow the frequency, and a zero frequency must be rejected.
+    #[kernel_test]
+    fn period_follows_frequency() {
+        let saved = frequency();
+
+        assert!(set_frequency(0).is_err());
+        assert_eq!(frequency(), saved);
+
+        set_frequency(250).unwrap();
+        assert_eq!(period(), Duration::from_millis(4));
+
+        set_frequency(saved).unwrap();
+    }
+}
@@ -0,0 +1,354 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Software timers.
+//!
+//! A software timer calls a function once its duration expired, either once or periodically. All
+//! timers share the timer tick, so they expire with a resolution of one tick period, and their
+//! callbacks run in IRQ context. Callbacks must be short and must not block. Longer processing can
+//! be deferred to the work queue.
+//!
+//! Active timers are kept in a binary min-heap ordered by deadline, so that starting and cancelling
+//! a timer takes O(log n), and each tick only looks at the timers that actually expired.
+
+use crate::synchronization::{interface::Mutex, SpinLock};
+use core::time::Duration;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The maximum number of active timers.
+const MAX_TIMERS: usize = 256;
+
+#[derive(Copy, Clone)]
+struct TimerSlot {
+    deadline: Duration,
+
+    /// `None` for oneshot timers.
+    period: Option<Duration>,
+    callback: fn(),
+}
+
+#[derive(Copy, Clone)]
+struct Slot {
+    timer: Option<TimerSlot>,
+
+    /// Distinguishes the timer from earlier and later ones in the same slot.
+    generation: u32,
+
+    /// The position of the slot in `TimerQueue::heap`.
+    heap_index: usize,
+}
+
+/// A fixed number of timer slots, ordered by deadline.
+struct TimerQueue<const N: usize> {
+    slots: [Slot; N],
+
+    /// A permutation of all slot indices. The first `len` entries are the active slots, ordered as
+    /// a binary min-heap by deadline. The remaining entries are the free slots.
+    heap: [usize; N],
+    len: usize,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Handle of an active software timer.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Timer {
+    slot: usize,
+
+    /// Distinguishes the timer from later ones in the same slot.
+    generation: u32,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static TIMERS: SpinLock<TimerQueue<MAX_TIMERS>> = SpinLock::new(TimerQueue::new());
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl<const N: usize> TimerQueue<N> {
+    const fn new() -> Self {
+        let mut slots = [Slot {
+            timer: None,
+            generation: 0,
+            heap_index: 0,
+        }; N];
+        let mut heap = [0; N];
+
+        let mut i = 0;
+        while i < N {
+            slots[i].heap_index = i;
+            heap[i] = i;
+            i += 1;
+        }
+
+        Self {
+            slots,
+            heap,
+            len: 0,
+        }
+    }
+
+    fn deadline_at(&self, index: usize) -> Duration {
+        self.slots[self.heap[index]]
+            .timer
+            .map_or(Duration::MAX, |timer| timer.deadline)
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.heap.swap(a, b);
+        self.slots[self.heap[a]].heap_index = a;
+        self.slots[self.heap[b]].heap_index = b;
+    }
+
+    fn sift_up(&mut self, mut index: usize) {
+        while index > 0 {
+            let parent = (index - 1) / 2;
+            if self.deadline_at(parent) <= self.deadline_at(index) {
+                break;
+            }
+
+            self.swap(parent, index);
+            index = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut index: usize) {
+        loop {
+            let mut smallest = index;
+            for child in [2 * index + 1, 2 * index + 2] {
+                if child < self.len && self.deadline_at(child) < self.deadline_at(smallest) {
+                    smallest = child;
+                }
+            }
+
+            if smallest == index {
+                break;
+            }
+
+            self.swap(index, smallest);
+            index = smallest;
+        }
+    }
+
+    /// Add a timer. Returns its slot and generation.
+    fn insert(&mut self, timer: TimerSlot) -> Result<(usize, u32), &'static str> {
+        if self.len == N {
+            return Err("No free timer slot");
+        }
+
+        let slot_index = self.heap[self.len];
+        let slot = &mut self.slots[slot_index];
+        slot.generation = slot.generation.wrapping_add(1);
+        slot.timer = Some(timer);
+        let generation = slot.generation;
+
+        self.len += 1;
+        self.sift_up(self.len - 1);
+
+        Ok((slot_index, generation))
+    }
+
+    /// Remove the timer in the given slot, if it is the given generation and still active.
+    fn remove(&mut self, slot_index: usize, generation: u32) {
+        let slot = &mut self.slots[slot_index];
+        if slot.generation != generation || slot.timer.take().is_none() {
+            return;
+        }
+
+        let index = slot.heap_index;
+        self.len -= 1;
+        self.swap(index, self.len);
+
+        if index < self.len {
+            self.sift_down(index);
+            self.sift_up(index);
+        }
+    }
+
+    fn is_active(&self, slot_index: usize, generation: u32) -> bool {
+        let slot = &self.slots[slot_index];
+
+        slot.generation == generation && slot.timer.is_some()
+    }
+
+    fn next_deadline(&self) -> Option<Duration> {
+        (self.len > 0).then(|| self.deadline_at(0))
+    }
+
+    /// Take the callback of the earliest timer, if it expired at `now`.
+    ///
+    /// Oneshot timers are removed. Periodic timers are moved to their next deadline.
+    fn pop_expired(&mut self, now: Duration) -> Option<fn()> {
+        if self.next_deadline()? > now {
+            return None;
+        }
+
+        let slot_index = self.heap[0];
+        let slot = &mut self.slots[slot_index];
+        let timer = slot.timer.as_mut()?;
+        let callback = timer.callback;
+
+        match timer.period {
+            None => {
+                let generation = slot.generation;
+                self.remove(slot_index, generation);
+            }
+            Some(period) => {
+                // Skip periods that were missed, instead of calling the callback repeatedly.
+                while timer.deadline <= now {
+                    timer.deadline += period;
+                }
+                self.sift_down(0);
+            }
+        }
+
+        Some(callback)
+    }
+}
+
+fn deadline_after(duration: Duration) -> Duration {
+    use super::interface::TimeManager;
+
+    super::time_manager().uptime().saturating_add(duration)
+}
+
+impl Timer {
+    fn start(
+        deadline: Duration,
+        period: Option<Duration>,
+        callback: fn(),
+    ) -> Result<Self, &'static str> {
+        let (slot, generation) = TIMERS.lock(|timers| {
+            timers.insert(TimerSlot {
+                deadline,
+                period,
+                callback,
+            })
+        })?;
+
+        Ok(Self { slot, generation })
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl Timer {
+    /// Call `callback` once, after `duration` expired.
+    pub fn oneshot(duration: Duration, callback: fn()) -> Result<Self, &'static str> {
+        Self::start(deadline_after(duration), None, callback)
+    }
+
+    /// Call `callback` once, when the uptime reaches `deadline`.
+    ///
+    /// A deadline in the past expires with the next tick.
+    pub fn at(deadline: Duration, callback: fn()) -> Result<Self, &'static str> {
+        Self::start(deadline, None, callback)
+    }
+
+    /// Call `callback` every `period`, starting one period from now.
+    pub fn periodic(period: Duration, callback: fn()) -> Result<Self, &'static str> {
+        if period.is_zero() {
+            return Err("Period must not be zero");
+        }
+
+        Self::start(deadline_after(period), Some(period), callback)
+    }
+
+    /// Stop the timer. Does nothing if a oneshot timer already expired.
+    pub fn cancel(self) {
+        TIMERS.lock(|timers| timers.remove(self.slot, self.generation));
+    }
+
+    /// Return whether the timer did not expire yet, or is periodic and was not cancelled.
+    pub fn is_active(&self) -> bool {
+        TIMERS.lock(|timers| timers.is_active(self.slot, self.generation))
+    }
+}
+
+/// Return the earliest deadline of all active timers.
+pub fn next_deadline() -> Option<Duration> {
+    TIMERS.lock(|timers| timers.next_deadline())
+}
+
+/// Call the callbacks of all expired timers. Called on each timer tick.
+pub fn handle_tick(now: Duration) {
+    // Callbacks are called without holding the lock, so that they can start and cancel timers.
+    while let Some(callback) = TIMERS.lock(|timers| timers.pop_expired(now)) {
+        callback();
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
+
+    fn nop() {}
+
+    fn oneshot(deadline_ms: u64) -> TimerSlot {
+        TimerSlot {
+            deadline: Duration::from_millis(deadline_ms),
+            period: None,
+            callback: nop,
+        }
+    }
+
+    /// Timers must expire in deadline order, also after some were cancelled.
+    #[kernel_test]
+    fn timers_expire_in_order() {
+        let mut queue = TimerQueue::<8>::new();
+
+        for ms in [50, 10, 40, 20, 30] {
+            queue.insert(oneshot(ms)).unwrap();
+        }
+        let (slot, generation) = queue.insert(oneshot(5)).unwrap();
+        queue.remove(slot, generation);
+        assert!(!queue.is_active(slot, generation));
+
+        let mut expired = 0;
+        let mut last = Duration::ZERO;
+        while let Some(deadline) = queue.next_deadline() {
+            assert!(deadline >= last);
+            assert!(queue.pop_expired(deadline).is_some());
+            last = deadline;
+            expired += 1;
+        }
+        assert_eq!(expired, 5);
+        assert_eq!(last, Duration::from_millis(50));
+    }
+
+    /// A full queue must reject new timers, and a periodic timer must move to its next deadline.
+    #[kernel_test]
+    fn periodic_timer_is_requeued() {
+        let mut queue = TimerQueue::<2>::new();
+
+        queue
+            .insert(TimerSlot {
+                period: Some(Duration::from_millis(10)),
+                ..oneshot(10)
+            })
+            .unwrap();
+        queue.insert(oneshot(15)).unwrap();
+        assert!(queue.insert(oneshot(20)).is_err());
+
+        assert!(queue.pop_expired(Duration::from_millis(10)).is_some());
+        assert_eq!(queue.next_deadline(), Some(Duration::from_millis(15)));
+        assert!(queue.pop_expired(Duration::from_millis(12)).is_none());
+    }
+}
@@ -0,0 +1,289 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! USB host support.
+//!
+//! Only the bare minimum needed to enumerate a device that is directly attached to the root port
+//! of a host controller is provided: Control transfers on endpoint zero, address assignment and
+//! reading of the device descriptor.
+
+use crate::info;
+use core::fmt;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// USB interfaces.
+pub mod interface {
+    use super::*;
+
+    /// Functions provided by a USB host controller.
+    pub trait HostController {
+        /// Check if a device is connected to the root port.
+        fn root_port_connected(&self) -> bool;
+
+        /// Reset the root port and return the speed of the attached device.
+        fn reset_root_port(&self) -> Result<Speed, &'static str>;
+
+        /// Execute a control transfer on endpoint zero of the given device.
+        ///
+        /// The direction of an eventual data stage is derived from the setup packet. On success,
+        /// the number of bytes transferred in the data stage is returned.
+        fn control_transfer(
+            &self,
+            device: &Device,
+            setup: &SetupPacket,
+            data: &mut [u8],
+        ) -> Result<usize, &'static str>;
+    }
+}
+
+/// Device speeds.
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Speed {
+    Low,
+    Full,
+    High,
+}
+
+/// Addressing information of a USB device.
+#[derive(Copy, Clone, Debug)]
+pub struct Device {
+    address: u8,
+    speed: Speed,
+    ep0_max_packet_size: u16,
+}
+
+/// The 8 byte packet that starts every control transfer.
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+pub struct SetupPacket {
+    pub request_type: u8,
+    pub request: u8,
+    pub value: u16,
+    pub index: u16,
+    pub length: u16,
+}
+
+/// The standard device descriptor.
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DeviceDescriptor {
+    pub usb_version: u16,
+    pub class: u8,
+    pub sub_class: u8,
+    pub protocol: u8,
+    pub ep0_max_packet_size: u8,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub device_version: u16,
+    pub num_configurations: u8,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// The address that gets assigned to the device on the root port.
+const ROOT_PORT_DEVICE_ADDRESS: u8 = 1;
+
+const REQUEST_GET_DESCRIPTOR: u8 = 6;
+const REQUEST_SET_ADDRESS: u8 = 5;
+
+const DESCRIPTOR_TYPE_DEVICE: u16 = 1;
+const DEVICE_DESCRIPTOR_SIZE: usize = 18;
+
+fn get_device_descriptor(
+    hc: &(impl interface::HostController + ?Sized),
+    device: &Device,
+    buf: &mut [u8],
+) -> Result<usize, &'static str> {
+    let setup = SetupPacket {
+        request_type: SetupPacket::DIR_IN,
+        request: REQUEST_GET_DESCRIPTOR,
+        value: DESCRIPTOR_TYPE_DEVICE << 8,
+        index: 0,
+        length: buf.len() as u16,
+    };
+
+    hc.control_transfer(device, &setup, buf)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl fmt::Display for Speed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Speed::Low => "Low-Speed",
+            Speed::Full => "Full-Speed",
+            Speed::High => "High-Speed",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+impl Device {
+    /// Create an instance.
+    pub const fn new(address: u8, speed: Speed, ep0_max_packet_size: u16) -> Self {
+        Self {
+            address,
+            speed,
+            ep0_max_packet_size,
+        }
+    }
+
+    /// The device's bus address.
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    /// The device's speed.
+    pub fn speed(&self) -> Speed {
+        self.speed
+    }
+
+    /// Max packet size of the default control endpoint.
+    pub fn ep0_max_packet_size(&self) -> u16 {
+        self.ep0_max_packet_size
+    }
+}
+
+impl SetupPacket {
+    /// Bit in `request_type` indicating a device-to-host data stage.
+    pub const DIR_IN: u8 = 1 << 7;
+
+    /// Return the packet in its on-the-wire byte representation.
+    pub fn to_bytes(&self) -> [u8; 8] {
+        let value = self.value.to_le_bytes();
+        let index = self.index.to_le_bytes();
+        let length = self.length.to_le_bytes();
+
+        [
+            self.request_type,
+            self.request,
+            value[0],
+            value[1],
+            index[0],
+            index[1],
+            length[0],
+            length[1],
+        ]
+    }
+
+    /// True if the data stage is device-to-host.
+    pub fn is_dir_in(&self) -> bool {
+        (self.request_type & Self::DIR_IN) != 0
+    }
+}
+
+impl DeviceDescriptor {
+    /// Parse a descriptor from its raw byte representation.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, &'static str> {
+        if buf.len() < DEVICE_DESCRIPTOR_SIZE {
+            return Err("Device descriptor too short");
+        }
+
+        if buf[1] as u16 != DESCRIPTOR_TYPE_DEVICE {
+            return Err("Not a device descriptor");
+        }
+
+        let le16 = |i: usize| u16::from_le_bytes([buf[i], buf[i + 1]]);
+
+        Ok(Self {
+            usb_version: le16(2),
+            class: buf[4],
+            sub_class: buf[5],
+            protocol: buf[6],
+            ep0_max_packet_size: buf[7],
+            vendor_id: le16(8),
+            product_id: le16(10),
+            device_version: le16(12),
+            num_configurations: buf[17],
+        })
+    }
+}
+
+/// Enumerate the device attached to the root port of the given host controller.
+///
+/// The device is reset, assigned an address and its device descriptor is read.
+pub fn enumerate_root_port(
+    hc: &(impl interface::HostController + ?Sized),
+) -> Result<DeviceDescriptor, &'static str> {
+    if !hc.root_port_connected() {
+        return Err("No device connected");
+    }
+
+    let speed = hc.reset_root_port()?;
+
+    // Until the real value is known, use the smallest max packet size that is allowed for the
+    // speed and only ask for the first 8 bytes of the descriptor, which contain the real value.
+    let initial_mps = match speed {
+        Speed::Low | Speed::Full => 8,
+        Speed::High => 64,
+    };
+    let mut device = Device::new(0, speed, initial_mps);
+    let mut buf = [0_u8; DEVICE_DESCRIPTOR_SIZE];
+
+    if get_device_descriptor(hc, &device, &mut buf[..8])? < 8 {
+        return Err("Device descriptor reply too short");
+    }
+    if buf[7] == 0 {
+        return Err("Device reported a max packet size of zero");
+    }
+    device.ep0_max_packet_size = buf[7] as u16;
+
+    let setup = SetupPacket {
+        request_type: 0,
+        request: REQUEST_SET_ADDRESS,
+        value: ROOT_PORT_DEVICE_ADDRESS as u16,
+        index: 0,
+        length: 0,
+    };
+    hc.control_transfer(&device, &setup, &mut [])?;
+    device.address = ROOT_PORT_DEVICE_ADDRESS;
+
+    let len = get_device_descriptor(hc, &device, &mut buf)?;
+    let descriptor = DeviceDescriptor::from_bytes(&buf[..len])?;
+
+    info!(
+        "      {} device {:04x}:{:04x} (class {:#04x}) at address {}",
+        speed, descriptor.vendor_id, descriptor.product_id, descriptor.class, device.address
+    );
+
+    Ok(descriptor)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_macros::kernel_test;
+
+    /// Check that a raw device descriptor is parsed correctly.
+    #[kernel_test]
+    fn device_descriptor_parsing_works() {
+        let raw: [u8; DEVICE_DESCRIPTOR_SIZE] = [
+            18, 1, 0x00, 0x02, 0x09, 0x00, 0x01, 64, 0x24, 0x04, 0x14, 0x25, 0x00, 0x02, 0, 0, 0, 1,
+        ];
+
+        let d = DeviceDescriptor::from_bytes(&raw).unwrap();
+        assert_eq!(d.usb_version, 0x0200);
+        assert_eq!(d.class, 0x09);
+        assert_eq!(d.ep0_max_packet_size, 64);
+        assert_eq!(d.vendor_id, 0x0424);
+        assert_eq!(d.product_id, 0x2514);
+        assert_eq!(d.num_configurations, 1);
+
+        assert!(DeviceDescriptor::from_bytes(&raw[..8]).is_err());
+    }
+}
@@ -0,0 +1,155 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Deferred work.
+//!
+//! IRQ handlers should return quickly, and must not sleep or block. Follow-up processing that is
+//! slow or needs to block can be put into a [`Work`] item instead, which an IRQ handler queues with
+//! [`queue()`]. A kernel worker thread then executes it in thread context.
+//!
+//! There is no heap, so work items are statics that wrap a plain function.
+
+use crate::{
+    scheduler,
+    synchronization::{Event, RingBuffer},
+};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The maximum number of queued work items.
+const QUEUE_SIZE: usize = 16;
+
+/// The worker runs before threads of the default priority, so that deferred work is done promptly.
+const WORKER_PRIORITY: u8 = scheduler::DEFAULT_PRIORITY + 2;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A piece of work that is executed in thread context.
+pub struct Work {
+    name: &'static str,
+    func: fn(),
+
+    /// Set while the item is queued.
+    pending: AtomicBool,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static QUEUE: RingBuffer<&'static Work, QUEUE_SIZE> = RingBuffer::new();
+
+/// Signaled when work was queued.
+static WORK_AVAILABLE: Event = Event::new();
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// The worker thread's entry. Executes queued work in FIFO order.
+fn worker() {
+    loop {
+        WORK_AVAILABLE.wait();
+
+        while let Some(work) = QUEUE.pop() {
+            // Cleared before the call, so that the work can queue itself again.
+            work.pending.store(false, Ordering::Release);
+            (work.func)();
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl Work {
+    /// Create an instance.
+    pub const fn new(name: &'static str, func: fn()) -> Self {
+        Self {
+            name,
+            func,
+            pending: AtomicBool::new(false),
+        }
+    }
+
+    /// The name of the work item.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Return whether the item is queued and was not executed yet.
+    pub fn is_pending(&self) -> bool {
+        self.pending.load(Ordering::Acquire)
+    }
+}
+
+/// Spawn the worker thread.
+///
+/// # Safety
+///
+/// - Must be called only once, after the scheduler was initialized.
+pub unsafe fn init() -> Result<(), &'static str> {
+    let thread = scheduler::spawn("worker", worker)?;
+    scheduler::set_priority(thread, WORKER_PRIORITY);
+
+    Ok(())
+}
+
+/// Queue a work item for execution by the worker thread.
+///
+/// Queuing an item that is still pending does nothing, so it executes only once. Can be called
+/// from IRQ handlers.
+pub fn queue(work: &'static Work) -> Result<(), &'static str> {
+    if work.pending.swap(true, Ordering::AcqRel) {
+        return Ok(());
+    }
+
+    if QUEUE.push(work).is_err() {
+        work.pending.store(false, Ordering::Release);
+        return Err("Work queue full");
+    }
+
+    WORK_AVAILABLE.signal();
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::AtomicUsize;
+    use test_macros::kernel_test;
+
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    static WORK: Work = Work::new("count", count);
+
+    fn count() {
+        COUNTER.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Check that work queued twice while pending executes once.
+    #[kernel_test]
+    fn queued_work_executes_once() {
+        unsafe { init().unwrap() };
+
+        queue(&WORK).unwrap();
+        queue(&WORK).unwrap();
+
+        while WORK.is_pending() || COUNTER.load(Ordering::Relaxed) == 0 {
+            scheduler::yield_now();
+        }
+
+        assert_eq!(COUNTER.load(Ordering::Relaxed), 1);
+    }
+}
@@ -15,8 +15,7 @@ use libkernel::{bsp, console, cpu, exception, memory, print};
 
 #[no_mangle]
 unsafe fn kernel_init() -> ! {
-    use bsp::console::console;
-    use console::interface::*;
+    use console::{console, interface::*};
 
     exception::handling_init();
     memory::mmu::post_enable_init();
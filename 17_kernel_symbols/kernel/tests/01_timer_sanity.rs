@@ -11,7 +11,7 @@
 #![test_runner(libkernel::test_runner)]
 
 use core::time::Duration;
-use libkernel::{bsp, cpu, exception, memory, time, time::interface::TimeManager};
+use libkernel::{bsp, exception, memory, qemu_exit, time, time::interface::TimeManager};
 use test_macros::kernel_test;
 
 #[no_mangle]
@@ -24,7 +24,7 @@ unsafe fn kernel_init() -> ! {
 
     test_main();
 
-    cpu::qemu_exit_success()
+    qemu_exit::exit_success()
 }
 
 /// Simple check that the timer is running.
@@ -40,11 +40,69 @@ fn timer_resolution_is_sufficient() {
 }
 
 /// Sanity check spin_for() implementation.
-#[kernel_test]
+#[kernel_test(timeout = "2s")]
 fn spin_accuracy_check_1_second() {
     let t1 = time::time_manager().uptime();
-    time::time_manager().spin_for(Duration::from_secs(1));
+    time::time_manager()
+        .spin_for(Duration::from_secs(1))
+        .unwrap();
     let t2 = time::time_manager().uptime();
 
     assert_eq!((t2 - t1).as_secs(), 1)
 }
+
+/// Out-of-range spins must be rejected instead of wrapping around.
+#[kernel_test]
+fn spin_for_rejects_out_of_range() {
+    assert_eq!(time::time_manager().spin_for(Duration::ZERO), Ok(()));
+    assert!(time::time_manager()
+        .spin_for(Duration::from_nanos(1))
+        .is_err());
+    assert!(time::time_manager().spin_for(Duration::MAX).is_err());
+}
+
+/// The uptime must be monotonic, and match the counter without overflowing the conversion.
+#[kernel_test]
+fn uptime_matches_counter() {
+    let t1 = time::time_manager().uptime();
+    let ticks = time::counter_ticks();
+    let t2 = time::time_manager().uptime();
+
+    let from_ticks = time::ticks_to_duration(ticks);
+    assert!(t1 <= from_ticks && from_ticks <= t2);
+
+    // The largest counter value must not wrap around in the conversion, and convert back.
+    let max = time::ticks_to_duration(u64::MAX);
+    assert!(max > t2);
+    assert!(u64::MAX - time::duration_to_ticks(max).unwrap() <= 1);
+}
+
+/// delay_us() must wait at least as long as requested.
+///
+/// How much longer it takes depends on the host that runs QEMU, so there is no upper bound.
+#[kernel_test]
+fn delay_us_is_accurate() {
+    let t1 = time::time_manager().uptime();
+    time::delay_us(500);
+    let t2 = time::time_manager().uptime();
+
+    assert!(t2 - t1 >= Duration::from_micros(500));
+}
+
+/// with_timeout() must return the polled value, or fail once the timeout has passed.
+#[kernel_test]
+fn with_timeout_works() {
+    let mut polls = 0;
+    let result = time::with_timeout(Duration::from_millis(10), || {
+        polls += 1;
+        (polls == 3).then(|| polls)
+    });
+    assert_eq!(result, Ok(3));
+
+    let t1 = time::time_manager().uptime();
+    let result = time::with_timeout(Duration::from_millis(10), || None::<()>);
+    let t2 = time::time_manager().uptime();
+
+    assert_eq!(result, Err(time::TimeoutError));
+    assert!(t2 - t1 >= Duration::from_millis(10));
+}
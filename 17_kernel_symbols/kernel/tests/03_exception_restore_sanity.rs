@@ -12,13 +12,18 @@
 mod panic_wait_forever;
 
 use core::arch::asm;
-use libkernel::{bsp, cpu, exception, info, memory, println};
+use libkernel::{bsp, cpu, exception, exception::SyncExceptionClass, info, memory, println};
 
 #[inline(never)]
 fn nested_system_call() {
     #[cfg(target_arch = "aarch64")]
     unsafe {
-        asm!("svc #0x1337", options(nomem, nostack, preserves_flags));
+        asm!(
+            "svc #0",
+            in("x8") exception::syscall::number::SLEEP,
+            inout("x0") 0_u64 => _,
+            options(nomem, nostack, preserves_flags)
+        );
     }
 
     #[cfg(not(target_arch = "aarch64"))]
@@ -40,7 +45,7 @@ unsafe fn kernel_init() -> ! {
     info!("Making a dummy system call");
 
     // Calling this inside a function indirectly tests if the link register is restored properly.
-    nested_system_call();
+    exception::assert_exceptions(SyncExceptionClass::SystemCall, 1, nested_system_call);
 
     info!("Back from system call!");
 
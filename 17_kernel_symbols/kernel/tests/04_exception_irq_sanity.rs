@@ -10,7 +10,7 @@
 #![reexport_test_harness_main = "test_main"]
 #![test_runner(libkernel::test_runner)]
 
-use libkernel::{bsp, cpu, exception, memory};
+use libkernel::{bsp, exception, memory, qemu_exit};
 use test_macros::kernel_test;
 
 #[no_mangle]
@@ -23,7 +23,7 @@ unsafe fn kernel_init() -> ! {
 
     test_main();
 
-    cpu::qemu_exit_success()
+    qemu_exit::exit_success()
 }
 
 /// Check that IRQ masking works.
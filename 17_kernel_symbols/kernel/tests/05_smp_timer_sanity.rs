@@ -0,0 +1,113 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Timer sanity tests across cores.
+
+#![feature(custom_test_frameworks)]
+#![no_main]
+#![no_std]
+#![reexport_test_harness_main = "test_main"]
+#![test_runner(libkernel::test_runner)]
+
+use core::{
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::Duration,
+};
+use libkernel::{bsp, cpu, exception, memory, qemu_exit, time, time::interface::TimeManager};
+use test_macros::kernel_test;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_TIMESTAMP: AtomicU64 = AtomicU64::new(0);
+
+/// Set by the boot core to let the secondary cores take their timestamps.
+static TAKE_TIMESTAMPS: AtomicBool = AtomicBool::new(false);
+
+/// The uptime in nanoseconds as seen by each core, or zero.
+static TIMESTAMPS: [AtomicU64; bsp::cpu::NUM_CORES] = [NO_TIMESTAMP; bsp::cpu::NUM_CORES];
+
+/// Whether the counters of a core are consistent with those of the boot core.
+static CONSISTENT: [AtomicBool; bsp::cpu::NUM_CORES] = [
+    AtomicBool::new(false),
+    AtomicBool::new(false),
+    AtomicBool::new(false),
+    AtomicBool::new(false),
+];
+
+#[no_mangle]
+unsafe fn kernel_init() -> ! {
+    exception::handling_init();
+    memory::mmu::post_enable_init();
+    time::init();
+    bsp::console::qemu_bring_up_console();
+
+    let _ = cpu::smp::start_secondary_cores();
+    cpu::smp::mark_online();
+    cpu::smp::conclude_boot_core_init();
+
+    test_main();
+
+    qemu_exit::exit_success()
+}
+
+#[no_mangle]
+unsafe fn kernel_init_secondary() -> ! {
+    exception::handling_init();
+    cpu::smp::wait_for_boot_core();
+
+    let core: usize = cpu::smp::core_id();
+    CONSISTENT[core].store(time::init_secondary().is_ok(), Ordering::Relaxed);
+    cpu::smp::mark_online();
+
+    while !TAKE_TIMESTAMPS.load(Ordering::Acquire) {
+        core::hint::spin_loop();
+    }
+    TIMESTAMPS[core].store(
+        time::time_manager().uptime().as_nanos() as u64,
+        Ordering::Release,
+    );
+
+    cpu::wait_forever()
+}
+
+/// All secondary cores must come online, and find their counters consistent.
+#[kernel_test]
+fn secondary_counters_are_consistent() {
+    assert!(cpu::smp::wait_for_secondary_cores().is_ok());
+    assert_eq!(cpu::smp::num_online(), bsp::cpu::NUM_CORES);
+
+    let boot_core = bsp::cpu::BOOT_CORE_ID as usize;
+    for (core, consistent) in CONSISTENT.iter().enumerate() {
+        assert!(core == boot_core || consistent.load(Ordering::Relaxed));
+    }
+}
+
+/// Timestamps taken on the secondary cores must lie between two timestamps of the boot core.
+#[kernel_test]
+fn timestamps_agree_across_cores() {
+    let boot_core = bsp::cpu::BOOT_CORE_ID as usize;
+
+    let before = time::time_manager().uptime();
+    TAKE_TIMESTAMPS.store(true, Ordering::Release);
+
+    for (core, timestamp) in TIMESTAMPS.iter().enumerate() {
+        if core == boot_core {
+            continue;
+        }
+
+        let taken = time::with_timeout(Duration::from_secs(1), || {
+            (timestamp.load(Ordering::Acquire) != 0).then(|| ())
+        });
+        assert!(taken.is_ok());
+    }
+    let after = time::time_manager().uptime();
+
+    for (core, timestamp) in TIMESTAMPS.iter().enumerate() {
+        if core == boot_core {
+            continue;
+        }
+
+        let t = Duration::from_nanos(timestamp.load(Ordering::Relaxed));
+        assert!(before <= t && t <= after);
+    }
+}
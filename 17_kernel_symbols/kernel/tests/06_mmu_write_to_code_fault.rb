@@ -0,0 +1,31 @@
+# frozen_string_literal: true
+
+# SPDX-License-Identifier: MIT OR Apache-2.0
+#
+# Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+require 'console_io_test'
+
+# Verify that writing to the code causes a permission fault at the written address.
+class WriteToCodeFaultTest < SubtestBase
+    def name
+        'Write to code causes permission fault'
+    end
+
+    def run(qemu_out, _qemu_in)
+        result = qemu_out.expect(/Writing to code at: (0x\h+)/, TIMEOUT_SECONDS)
+        raise ExpectTimeoutError, 'Writing to code at:' if result.nil?
+
+        expect_or_raise(qemu_out, 'Data Abort, current EL')
+        expect_or_raise(qemu_out, 'Permission fault')
+        expect_or_raise(qemu_out, 'Access: Write')
+        expect_or_raise(qemu_out, "FAR_EL1: #{result[1]}")
+    end
+end
+
+##--------------------------------------------------------------------------------------------------
+## Test registration
+##--------------------------------------------------------------------------------------------------
+def subtest_collection
+    [WriteToCodeFaultTest.new]
+end
@@ -0,0 +1,33 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Writes to the kernel's code must be rejected by the MMU.
+
+#![feature(format_args_nl)]
+#![no_main]
+#![no_std]
+
+/// Console tests should time out on the I/O harness in case of panic.
+mod panic_wait_forever;
+
+use libkernel::{bsp, cpu, exception, info, memory, println};
+
+#[no_mangle]
+unsafe fn kernel_init() -> ! {
+    exception::handling_init();
+    memory::mmu::post_enable_init();
+    bsp::console::qemu_bring_up_console();
+
+    // This line will be printed as the test header.
+    println!("Testing MMU permissions by writing to the kernel code");
+
+    // Any function will do. It lives in the code section, which is mapped read-only.
+    let code_addr = kernel_init as usize;
+    info!("Writing to code at: {:#018x}", code_addr);
+    core::ptr::write_volatile(code_addr as *mut u32, 0);
+
+    // If execution reaches here, the write did not cause a permission fault.
+    info!("Code was writable");
+    cpu::wait_forever();
+}
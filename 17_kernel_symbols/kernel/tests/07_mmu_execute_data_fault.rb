@@ -0,0 +1,30 @@
+# frozen_string_literal: true
+
+# SPDX-License-Identifier: MIT OR Apache-2.0
+#
+# Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+require 'console_io_test'
+
+# Verify that executing from the data causes a permission fault at the executed address.
+class ExecuteDataFaultTest < SubtestBase
+    def name
+        'Execute from data causes permission fault'
+    end
+
+    def run(qemu_out, _qemu_in)
+        result = qemu_out.expect(/Executing data at: (0x\h+)/, TIMEOUT_SECONDS)
+        raise ExpectTimeoutError, 'Executing data at:' if result.nil?
+
+        expect_or_raise(qemu_out, 'Instruction Abort, current EL')
+        expect_or_raise(qemu_out, 'Permission fault')
+        expect_or_raise(qemu_out, "ELR_EL1: #{result[1]}")
+    end
+end
+
+##--------------------------------------------------------------------------------------------------
+## Test registration
+##--------------------------------------------------------------------------------------------------
+def subtest_collection
+    [ExecuteDataFaultTest.new]
+end
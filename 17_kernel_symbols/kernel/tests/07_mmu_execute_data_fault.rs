@@ -0,0 +1,37 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Executing from the kernel's data must be rejected by the MMU.
+
+#![feature(format_args_nl)]
+#![no_main]
+#![no_std]
+
+/// Console tests should time out on the I/O harness in case of panic.
+mod panic_wait_forever;
+
+use libkernel::{bsp, cpu, exception, info, memory, println};
+
+/// A valid `ret` instruction. Being mutable puts it into the data section, which is mapped
+/// non-executable. Read-only statics would end up next to the code, which is executable.
+static mut RET_INSTRUCTION: u32 = 0xd65f_03c0;
+
+#[no_mangle]
+unsafe fn kernel_init() -> ! {
+    exception::handling_init();
+    memory::mmu::post_enable_init();
+    bsp::console::qemu_bring_up_console();
+
+    // This line will be printed as the test header.
+    println!("Testing MMU permissions by executing from the kernel data");
+
+    let data_addr = core::ptr::addr_of!(RET_INSTRUCTION) as usize;
+    info!("Executing data at: {:#018x}", data_addr);
+    let f: extern "C" fn() = core::mem::transmute(data_addr);
+    f();
+
+    // If execution reaches here, the instruction fetch did not cause a permission fault.
+    info!("Data was executable");
+    cpu::wait_forever();
+}
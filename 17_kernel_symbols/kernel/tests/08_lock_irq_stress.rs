@@ -0,0 +1,155 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Stress tests of the IRQ-masking locks.
+//!
+//! A hot loop and the timer tick, which runs at a high frequency, update the same counter under a
+//! lock. If a lock failed to mask IRQs, the tick would either see a half-done update of the hot
+//! loop, or deadlock on the lock that the interrupted hot loop holds.
+
+#![feature(custom_test_frameworks)]
+#![no_main]
+#![no_std]
+#![reexport_test_harness_main = "test_main"]
+#![test_runner(libkernel::test_runner)]
+
+use core::{
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::Duration,
+};
+use libkernel::{
+    bsp, cpu, driver, dtb, exception, memory, qemu_exit,
+    synchronization::{interface::Mutex, SpinLock, TicketLock},
+    time,
+};
+use test_macros::kernel_test;
+
+/// Ticks much more often than the default, so that many ticks hit the hot loop.
+const STRESS_TICK_HZ: u32 = 10_000;
+
+/// The number of tick updates that a test waits for.
+const MIN_IRQ_UPDATES: usize = 1000;
+
+/// Two words that must always be equal outside of the lock.
+#[derive(Copy, Clone)]
+struct Counter {
+    a: usize,
+    b: usize,
+    hot_updates: usize,
+    irq_updates: usize,
+}
+
+/// No lock is stressed.
+const TARGET_NONE: usize = 0;
+const TARGET_SPIN_LOCK: usize = 1;
+const TARGET_TICKET_LOCK: usize = 2;
+
+const ZERO: Counter = Counter {
+    a: 0,
+    b: 0,
+    hot_updates: 0,
+    irq_updates: 0,
+};
+
+static SPIN_LOCK_COUNTER: SpinLock<Counter> = SpinLock::new(ZERO);
+static TICKET_LOCK_COUNTER: TicketLock<Counter> = TicketLock::new(ZERO);
+
+/// The lock that the tick updates.
+static TARGET: AtomicUsize = AtomicUsize::new(TARGET_NONE);
+
+/// Set if an update found the two words unequal.
+static TORN: AtomicBool = AtomicBool::new(false);
+
+/// Update both words, with a window in between in which the other side must not get in.
+fn update(counter: &mut Counter, from_irq: bool) {
+    if counter.a != counter.b {
+        TORN.store(true, Ordering::Relaxed);
+    }
+
+    counter.a += 1;
+    for _ in 0..16 {
+        cpu::nop();
+    }
+    counter.b += 1;
+
+    if from_irq {
+        counter.irq_updates += 1;
+    } else {
+        counter.hot_updates += 1;
+    }
+}
+
+/// Tick callback.
+fn stress_tick(_now: Duration) {
+    match TARGET.load(Ordering::Relaxed) {
+        TARGET_SPIN_LOCK => SPIN_LOCK_COUNTER.lock(|c| update(c, true)),
+        TARGET_TICKET_LOCK => TICKET_LOCK_COUNTER.lock(|c| update(c, true)),
+        _ => (),
+    }
+}
+
+/// Hammer the counter behind `lock` from a hot loop until the tick updated it often enough.
+fn stress(lock: &impl Mutex<Data = Counter>, target: usize) {
+    TORN.store(false, Ordering::Relaxed);
+    TARGET.store(target, Ordering::Relaxed);
+
+    let result = time::with_timeout(Duration::from_secs(2), || {
+        let irq_updates = lock.lock(|c| {
+            update(c, false);
+            c.irq_updates
+        });
+
+        (irq_updates >= MIN_IRQ_UPDATES).then(|| ())
+    });
+
+    TARGET.store(TARGET_NONE, Ordering::Relaxed);
+    assert!(
+        result.is_ok(),
+        "Tick did not update the counter often enough"
+    );
+
+    let counter = lock.lock(|c| *c);
+    assert!(!TORN.load(Ordering::Relaxed));
+    assert_eq!(counter.a, counter.b);
+    assert_eq!(counter.a, counter.hot_updates + counter.irq_updates);
+}
+
+#[no_mangle]
+unsafe fn kernel_init() -> ! {
+    use driver::interface::DriverManager;
+
+    exception::handling_init();
+    memory::mmu::post_enable_init();
+    time::init();
+
+    // The tick needs the interrupt controller.
+    let _ = dtb::init();
+    bsp::driver::init().unwrap_or_else(|_| qemu_exit::exit_failure());
+    bsp::driver::driver_manager().init_drivers_and_irqs();
+
+    let tick = time::tick::register_callback("Lock stress", stress_tick)
+        .and_then(|_| time::tick::init())
+        .and_then(|_| time::tick::set_frequency(STRESS_TICK_HZ));
+    if tick.is_err() {
+        qemu_exit::exit_failure()
+    }
+
+    exception::asynchronous::local_irq_unmask();
+
+    test_main();
+
+    qemu_exit::exit_success()
+}
+
+/// A `SpinLock` keeps the tick out while the hot loop updates the counter.
+#[kernel_test(timeout = "5s")]
+fn spin_lock_survives_tick_irqs() {
+    stress(&SPIN_LOCK_COUNTER, TARGET_SPIN_LOCK);
+}
+
+/// A `TicketLock` keeps the tick out while the hot loop updates the counter.
+#[kernel_test(timeout = "5s")]
+fn ticket_lock_survives_tick_irqs() {
+    stress(&TICKET_LOCK_COUNTER, TARGET_TICKET_LOCK);
+}
@@ -1,3 +1,3 @@
 # frozen_string_literal: true
 
-EXPECTED_PRINT = 'Echoing input now'
+EXPECTED_PRINT = 'Starting the debug monitor'
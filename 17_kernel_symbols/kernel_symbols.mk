@@ -32,8 +32,33 @@ endif
 KERNEL_SYMBOLS_MANIFEST      = kernel_symbols/Cargo.toml
 KERNEL_SYMBOLS_LINKER_SCRIPT = kernel_symbols/kernel_symbols.ld
 
-KERNEL_SYMBOLS_RS           = $(KERNEL_SYMBOLS_INPUT_ELF)_symbols.rs
-KERNEL_SYMBOLS_DEMANGLED_RS = $(shell pwd)/$(KERNEL_SYMBOLS_INPUT_ELF)_symbols_demangled.rs
+# The tool runs in the container and needs a relative path. The cargo build needs an absolute one.
+KERNEL_SYMBOLS_RS           = $(KERNEL_SYMBOLS_INPUT_ELF)_symbols_demangled.rs
+KERNEL_SYMBOLS_DEMANGLED_RS = $(shell pwd)/$(KERNEL_SYMBOLS_RS)
+
+# Optionally truncate symbol names to this many bytes, to shrink the symbols section.
+KERNEL_SYMBOLS_MAX_NAME_LEN ?=
+
+# Optionally add a table of source lines, decoded from the DWARF line info by readelf.
+KERNEL_SYMBOLS_LINES     ?= 0
+KERNEL_SYMBOLS_LINES_TXT = $(KERNEL_SYMBOLS_INPUT_ELF)_lines.txt
+
+GEN_SYMBOLS_ARGS =
+ifneq ($(KERNEL_SYMBOLS_MAX_NAME_LEN),)
+    GEN_SYMBOLS_ARGS += --max_name_len=$(KERNEL_SYMBOLS_MAX_NAME_LEN)
+endif
+ifeq ($(KERNEL_SYMBOLS_LINES),1)
+    GEN_SYMBOLS_ARGS += --lines=$(KERNEL_SYMBOLS_LINES_TXT)
+endif
+
+# Optionally write the symbols to a blob for loading at runtime, and leave the ELF unpatched.
+KERNEL_SYMBOLS_BLOB     ?= 0
+KERNEL_SYMBOLS_BLOB_BIN ?= $(KERNEL_SYMBOLS_INPUT_ELF)_symbols.bin
+
+GEN_BLOB_ARGS =
+ifneq ($(KERNEL_SYMBOLS_MAX_NAME_LEN),)
+    GEN_BLOB_ARGS += --max_name_len=$(KERNEL_SYMBOLS_MAX_NAME_LEN)
+endif
 
 KERNEL_SYMBOLS_ELF      = target/$(TARGET)/release/kernel_symbols
 KERNEL_SYMBOLS_STRIPPED = target/$(TARGET)/release/kernel_symbols_stripped
@@ -84,12 +109,17 @@ DOCKER_TOOLS = $(DOCKER_CMD) $(DOCKER_IMAGE)
 all:
 	@cp $(KERNEL_SYMBOLS_INPUT_ELF) $(KERNEL_SYMBOLS_OUTPUT_ELF)
 
-	@$(DOCKER_TOOLS) $(EXEC_SYMBOLS_TOOL) --gen_symbols $(KERNEL_SYMBOLS_OUTPUT_ELF) \
-                $(KERNEL_SYMBOLS_RS)
+ifeq ($(KERNEL_SYMBOLS_BLOB),1)
+	@$(DOCKER_TOOLS) $(EXEC_SYMBOLS_TOOL) --gen_blob $(KERNEL_SYMBOLS_OUTPUT_ELF) \
+                $(KERNEL_SYMBOLS_BLOB_BIN) $(GEN_BLOB_ARGS)
+else
+ifeq ($(KERNEL_SYMBOLS_LINES),1)
+	@$(DOCKER_TOOLS) sh -c '$(READELF_BINARY) --debug-dump=decodedline --wide \
+                $(KERNEL_SYMBOLS_OUTPUT_ELF) > $(KERNEL_SYMBOLS_LINES_TXT)'
+endif
 
-	$(call color_progress_prefix, "Demangling")
-	@echo Symbol names
-	@cat $(KERNEL_SYMBOLS_RS) | rustfilt > $(KERNEL_SYMBOLS_DEMANGLED_RS)
+	@$(DOCKER_TOOLS) $(EXEC_SYMBOLS_TOOL) --gen_symbols $(KERNEL_SYMBOLS_OUTPUT_ELF) \
+                $(KERNEL_SYMBOLS_RS) $(GEN_SYMBOLS_ARGS)
 
 	@RUSTFLAGS="$(RUSTFLAGS_PEDANTIC)" $(RUSTC_CMD)
 
@@ -99,5 +129,6 @@ all:
 
 	@$(DOCKER_TOOLS) $(EXEC_SYMBOLS_TOOL) --patch_data $(KERNEL_SYMBOLS_OUTPUT_ELF) \
                 $(KERNEL_SYMBOLS_STRIPPED)
+endif
 
 	$(call color_progress_prefix, "Finished")
@@ -9,21 +9,31 @@
 use core::ops::Range;
 
 /// A symbol containing a size.
+///
+/// The name is split into a prefix and a suffix. Symbols of the same module share their prefix, so
+/// that it is stored only once.
 #[repr(C)]
 pub struct Symbol {
     addr_range: Range<usize>,
-    name: &'static str,
+    name_prefix: &'static str,
+    name_suffix: &'static str,
 }
 
 impl Symbol {
     /// Create an instance.
-    pub const fn new(start: usize, size: usize, name: &'static str) -> Symbol {
+    pub const fn new(
+        start: usize,
+        size: usize,
+        name_prefix: &'static str,
+        name_suffix: &'static str,
+    ) -> Symbol {
         Symbol {
             addr_range: Range {
                 start,
                 end: start + size,
             },
-            name,
+            name_prefix,
+            name_suffix,
         }
     }
 
@@ -32,8 +42,54 @@ impl Symbol {
         self.addr_range.contains(&addr)
     }
 
-    /// Returns the symbol's name.
-    pub fn name(&self) -> &'static str {
-        self.name
+    /// Returns the symbol's start address.
+    pub fn start(&self) -> usize {
+        self.addr_range.start
+    }
+
+    /// Returns the symbol's size in bytes.
+    pub fn size(&self) -> usize {
+        self.addr_range.end - self.addr_range.start
+    }
+
+    /// Returns the first part of the symbol's name, which may be shared with other symbols.
+    pub fn name_prefix(&self) -> &'static str {
+        self.name_prefix
+    }
+
+    /// Returns the rest of the symbol's name.
+    pub fn name_suffix(&self) -> &'static str {
+        self.name_suffix
+    }
+}
+
+/// The source line of the code that starts at an address. It extends up to the start of the next
+/// entry of a line table.
+#[repr(C)]
+pub struct Line {
+    start: usize,
+    file: &'static str,
+    line: u32,
+}
+
+impl Line {
+    /// Create an instance. Line number zero means that no source line is known.
+    pub const fn new(start: usize, file: &'static str, line: u32) -> Line {
+        Line { start, file, line }
+    }
+
+    /// Returns the entry's start address.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Returns the source file.
+    pub fn file(&self) -> &'static str {
+        self.file
+    }
+
+    /// Returns the line number.
+    pub fn line(&self) -> u32 {
+        self.line
     }
 }
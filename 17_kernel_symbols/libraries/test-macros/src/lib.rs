@@ -5,12 +5,103 @@
 use proc_macro::TokenStream;
 use proc_macro2::Span;
 use quote::quote;
-use syn::{parse_macro_input, Ident, ItemFn};
+use syn::{parse_macro_input, AttributeArgs, Ident, ItemFn, Lit, Meta, NestedMeta};
 
-#[proc_macro_attribute]
-pub fn kernel_test(_attr: TokenStream, input: TokenStream) -> TokenStream {
-    let f = parse_macro_input!(input as ItemFn);
+/// The number of runs of a benchmark, unless given with `iterations = ...`.
+const DEFAULT_BENCH_ITERATIONS: usize = 100;
+
+/// Must match the test runner's buffer for the measurements.
+const MAX_BENCH_ITERATIONS: usize = 1024;
+
+/// Parse a duration like `"500ms"` or `"2s"` into milliseconds.
+fn parse_millis(s: &str) -> Option<u64> {
+    if let Some(ms) = s.strip_suffix("ms") {
+        return ms.parse().ok();
+    }
+
+    s.strip_suffix('s')?.parse::<u64>().ok()?.checked_mul(1000)
+}
+
+/// Parse the attribute's arguments. Returns the timeout in milliseconds, if one was given, and
+/// whether the test should panic.
+fn parse_args(args: AttributeArgs) -> syn::Result<(Option<u64>, bool)> {
+    let mut timeout_ms = None;
+    let mut should_panic = false;
+
+    for arg in args {
+        match arg {
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("should_panic") => {
+                should_panic = true;
+            }
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("timeout") => {
+                let millis = match &nv.lit {
+                    Lit::Str(s) => parse_millis(&s.value()),
+                    _ => None,
+                };
+
+                match millis {
+                    Some(x) if x > 0 => timeout_ms = Some(x),
+                    _ => {
+                        return Err(syn::Error::new_spanned(
+                            nv.lit,
+                            "expected a duration like \"500ms\" or \"2s\"",
+                        ))
+                    }
+                }
+            }
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    arg,
+                    "unknown argument, expected `timeout = \"...\"` or `should_panic`",
+                ))
+            }
+        }
+    }
+
+    Ok((timeout_ms, should_panic))
+}
+
+/// Parse the arguments of a benchmark. Returns the number of iterations.
+fn parse_bench_args(args: AttributeArgs) -> syn::Result<usize> {
+    let mut iterations = DEFAULT_BENCH_ITERATIONS;
+
+    for arg in args {
+        match arg {
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("iterations") => {
+                let value = match &nv.lit {
+                    Lit::Int(x) => x.base10_parse::<usize>().ok(),
+                    _ => None,
+                };
+
+                match value {
+                    Some(x) if (1..=MAX_BENCH_ITERATIONS).contains(&x) => iterations = x,
+                    _ => {
+                        return Err(syn::Error::new_spanned(
+                            nv.lit,
+                            format!("expected a number from 1 to {}", MAX_BENCH_ITERATIONS),
+                        ))
+                    }
+                }
+            }
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    arg,
+                    "unknown argument, expected `iterations = ...`",
+                ))
+            }
+        }
+    }
+
+    Ok(iterations)
+}
 
+/// Wrap a function into a `UnitTest` container for the `custom_test_frameworks`.
+fn unit_test(
+    f: ItemFn,
+    timeout: proc_macro2::TokenStream,
+    kind: proc_macro2::TokenStream,
+    should_panic: bool,
+) -> TokenStream {
     let test_name = &format!("{}", f.sig.ident);
     let test_ident = Ident::new(
         &format!("{}_TEST_CONTAINER", f.sig.ident.to_string().to_uppercase()),
@@ -22,8 +113,60 @@ pub fn kernel_test(_attr: TokenStream, input: TokenStream) -> TokenStream {
         #[test_case]
         const #test_ident: test_types::UnitTest = test_types::UnitTest {
             name: #test_name,
+            module: module_path!(),
             test_func: || #test_code_block,
+            timeout: #timeout,
+            kind: #kind,
+            should_panic: #should_panic,
         };
     )
     .into()
 }
+
+/// Declare a test for the kernel's test runner.
+///
+/// `#[kernel_test(timeout = "2s")]` fails the test if it runs longer than the given time.
+///
+/// `#[kernel_test(should_panic)]` passes the test only if it panics.
+#[proc_macro_attribute]
+pub fn kernel_test(attr: TokenStream, input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as AttributeArgs);
+    let f = parse_macro_input!(input as ItemFn);
+
+    let (timeout_ms, should_panic) = match parse_args(args) {
+        Err(e) => return e.to_compile_error().into(),
+        Ok(x) => x,
+    };
+
+    let timeout = match timeout_ms {
+        None => quote!(None),
+        Some(ms) => quote!(Some(::core::time::Duration::from_millis(#ms))),
+    };
+
+    unit_test(f, timeout, quote!(test_types::TestKind::Test), should_panic)
+}
+
+/// Declare a benchmark for the kernel's test runner.
+///
+/// The runner calls the function repeatedly, 100 times unless given with
+/// `#[kernel_bench(iterations = 500)]`, and prints the minimum, median and maximum number of
+/// cycles that a call took.
+#[proc_macro_attribute]
+pub fn kernel_bench(attr: TokenStream, input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as AttributeArgs);
+    let f = parse_macro_input!(input as ItemFn);
+
+    let iterations = match parse_bench_args(args) {
+        Err(e) => return e.to_compile_error().into(),
+        Ok(x) => x,
+    };
+
+    unit_test(
+        f,
+        quote!(None),
+        quote!(test_types::TestKind::Bench {
+            iterations: #iterations
+        }),
+        false,
+    )
+}
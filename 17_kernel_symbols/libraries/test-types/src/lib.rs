@@ -6,11 +6,35 @@
 
 #![no_std]
 
+/// What the test runner does with a unit test.
+pub enum TestKind {
+    /// Run the test once.
+    Test,
+
+    /// Run the test repeatedly, and print how many cycles the runs took.
+    Bench {
+        /// The number of runs.
+        iterations: usize,
+    },
+}
+
 /// Unit test container.
 pub struct UnitTest {
     /// Name of the test.
     pub name: &'static str,
 
+    /// Path of the module that declares the test.
+    pub module: &'static str,
+
     /// Function pointer to the test.
     pub test_func: fn(),
+
+    /// The test fails if it runs longer than this.
+    pub timeout: Option<core::time::Duration>,
+
+    /// Whether this is a test or a benchmark.
+    pub kind: TestKind,
+
+    /// The test passes only if it panics.
+    pub should_panic: bool,
 }
@@ -4,26 +4,124 @@
 #
 # Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
 
-def generate_symbols(kernel_elf, output_file)
+# Demangle, and optionally truncate, a symbol name.
+def symbol_name(name, max_name_len)
+    Demangle.truncate(Demangle.rust(name), max_name_len)
+end
+
+def rust_string_literal(str)
+    escaped = str.gsub('\\') { '\\\\' }.gsub('"') { '\\"' }
+
+    "\"#{escaped}\""
+end
+
+# Split a name after its last path separator. Functions of the same module or impl share the part
+# before it.
+def split_symbol_name(name)
+    i = name.rindex('::')
+    return ['', name] if i.nil?
+
+    [name[0, i + 2], name[(i + 2)..]]
+end
+
+# Only prefixes that are shared by several symbols are worth storing separately.
+def shared_prefixes(split_names)
+    counts = split_names.each_with_object(Hash.new(0)) { |(prefix, _), h| h[prefix] += 1 }
+
+    counts.select { |prefix, count| count > 1 && !prefix.empty? }.keys
+end
+
+# Write the symbols. Names with a shared prefix refer to its constant.
+#
+# Returns the size of the names as stored.
+def write_symbols(file, kernel_elf, split_names, prefix_ids)
+    prefix_ids.each do |prefix, id|
+        file.write("const P#{id}: &str = #{rust_string_literal(prefix)};\n")
+    end
+
+    file.write(<<~HEREDOC)
+
+        # [no_mangle]
+        # [link_section = ".rodata.symbol_desc"]
+        static KERNEL_SYMBOLS: [Symbol; #{kernel_elf.num_symbols}] = [
+    HEREDOC
+
+    stored_size = 0
+
+    kernel_elf.symbols.zip(split_names).each do |sym, (prefix, suffix)|
+        id = prefix_ids[prefix]
+        prefix_ref = id.nil? ? '""' : "P#{id}"
+        stored_suffix = id.nil? ? prefix + suffix : suffix
+        stored_size += stored_suffix.bytesize
+
+        file.write("    Symbol::new(#{sym.header.st_value}, #{sym.header.st_size}, " \
+                   "#{prefix_ref}, #{rust_string_literal(stored_suffix)}),\n")
+    end
+    file.write("];\n\n")
+
+    stored_size
+end
+
+# Write the line table, or an empty one. Each file name becomes a constant.
+def write_lines(file, line_table)
+    entries = line_table&.entries || []
+    file_ids = (line_table&.files || []).each_with_index.to_h
+
+    file_ids.each do |name, id|
+        file.write("const F#{id}: &str = #{rust_string_literal(name)};\n")
+    end
+
+    file.write(<<~HEREDOC)
+
+        # [no_mangle]
+        # [link_section = ".rodata.symbol_lines"]
+        static KERNEL_LINES: &[Line] = &[
+    HEREDOC
+
+    entries.each do |entry|
+        file_ref = entry.file.nil? ? '""' : "F#{file_ids[entry.file]}"
+        file.write("    Line::new(#{entry.addr}, #{file_ref}, #{entry.line}),\n")
+    end
+    file.write("];\n")
+end
+
+# Generate the symbols source file. Each shared prefix becomes a constant, so that it is stored only
+# once.
+#
+# Returns the size of all names in bytes, before and after compression.
+def generate_symbols(kernel_elf, output_file, max_name_len = nil, line_table = nil)
+    split_names = kernel_elf.symbols.map do |sym|
+        split_symbol_name(symbol_name(sym.name, max_name_len))
+    end
+    prefix_ids = shared_prefixes(split_names).each_with_index.to_h
+    compressed_size = prefix_ids.keys.sum(&:bytesize)
+
     File.open(output_file, 'w') do |file|
-        header = <<~HEREDOC
-            use debug_symbol_types::Symbol;
-
-            # [no_mangle]
-            # [link_section = ".rodata.symbol_desc"]
-            static KERNEL_SYMBOLS: [Symbol; #{kernel_elf.num_symbols}] = [
-        HEREDOC
-
-        file.write(header)
-        kernel_elf.symbols.each do |sym|
-            value = sym.header.st_value
-            size = sym.header.st_size
-            name = sym.name
-
-            file.write("    Symbol::new(#{value}, #{size}, \"#{name}\"),\n")
-        end
-        file.write("];\n")
+        file.write("use debug_symbol_types::{Line, Symbol};\n\n")
+        compressed_size += write_symbols(file, kernel_elf, split_names, prefix_ids)
+        write_lines(file, line_table)
+    end
+
+    [split_names.sum { |prefix, suffix| prefix.bytesize + suffix.bytesize }, compressed_size]
+end
+
+# Generate a symbols blob that the kernel can load at runtime, see `kernel/src/symbols/blob.rs`.
+#
+# Returns the size of the blob in bytes.
+def generate_blob(kernel_elf, output_file, max_name_len = nil)
+    names = kernel_elf.symbols.map { |sym| symbol_name(sym.name, max_name_len) }
+    offsets = names.each_with_object([0]) { |name, o| o << (o.last + name.bytesize) }
+    strings = names.join
+
+    # "L" == uint32_t, "Q" == uint64_t, "<" == little endian
+    blob = ['KSYM', 1, names.size, strings.bytesize].pack('a4L<L<L<')
+    kernel_elf.symbols.zip(names, offsets).each do |sym, name, offset|
+        blob << [sym.header.st_value, sym.header.st_size, offset, name.bytesize].pack('Q<Q<L<L<')
     end
+    blob << strings.b
+
+    File.binwrite(output_file, blob)
+    blob.bytesize
 end
 
 def get_symbols_section_virt_addr(kernel_elf)
@@ -0,0 +1,85 @@
+# frozen_string_literal: true
+
+# SPDX-License-Identifier: MIT OR Apache-2.0
+#
+# Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+# Demangling of Rust symbol names in the legacy mangling scheme, e.g.
+#
+#   _ZN9libkernel6common10is_aligned17h0123456789abcdefE => libkernel::common::is_aligned
+#
+# Names that are not mangled, or that use another scheme, are returned unchanged.
+module Demangle
+    LEGACY_PREFIXES = %w[_ZN ZN __ZN].freeze
+
+    # The last path component of a legacy mangled name is a hash of the symbol's type and crate.
+    HASH_REGEX = /\Ah[0-9a-f]{16}\z/
+
+    ESCAPES = {
+        '$SP$' => '@',
+        '$BP$' => '*',
+        '$RF$' => '&',
+        '$LT$' => '<',
+        '$GT$' => '>',
+        '$LP$' => '(',
+        '$RP$' => ')',
+        '$C$' => ','
+    }.freeze
+
+    # Return the demangled name, without the hash.
+    def self.rust(name)
+        prefix = LEGACY_PREFIXES.find { |p| name.start_with?(p) }
+        return name if prefix.nil?
+
+        components = path_components(name[prefix.size..])
+        return name if components.nil? || components.empty?
+
+        # Only drop the last component if it is verified to be a hash, so that nothing meaningful
+        # is lost from names that merely look mangled.
+        components.pop if components.size > 1 && HASH_REGEX.match?(components.last)
+
+        components.map { |c| unescape(c) }.join('::')
+    end
+
+    # Shorten a name to at most max_len bytes. The end is replaced with "..", because the start of a
+    # path is the more telling part.
+    def self.truncate(name, max_len)
+        return name if max_len.nil? || name.bytesize <= max_len
+
+        "#{name.byteslice(0, max_len - 2).scrub('')}.."
+    end
+
+    # Split the length-prefixed path components, which are terminated by "E".
+    def self.path_components(mangled)
+        components = []
+        pos = 0
+
+        while pos < mangled.size && mangled[pos] != 'E'
+            len_str = mangled[pos..][/\A\d+/]
+            return nil if len_str.nil?
+
+            pos += len_str.size
+            component = mangled[pos, len_str.to_i]
+            return nil if component.nil? || component.size != len_str.to_i
+
+            components << component
+            pos += len_str.to_i
+        end
+
+        return nil if mangled[pos] != 'E'
+
+        components
+    end
+
+    def self.unescape(component)
+        # A leading "_$" is inserted if the component would otherwise start with "$".
+        component = component[1..] if component.start_with?('_$')
+
+        component = component.gsub('..', '::')
+        ESCAPES.each { |escaped, char| component = component.gsub(escaped, char) }
+
+        component.gsub(/\$u([0-9a-f]{2,6})\$/) { [Regexp.last_match(1).hex].pack('U') }
+    end
+
+    private_class_method :path_components, :unescape
+end
@@ -47,15 +47,27 @@ class KernelELF
 
     public
 
+    # The kernel looks up symbols with a binary search, so they must be sorted by start address.
+    # Aliases that start at the same address are dropped, except for the largest one.
     def symbols
-        non_zero_symbols = @symtab_section.symbols.reject { |sym| sym.header.st_size.zero? }
-        non_zero_symbols.sort_by { |sym| sym.header.st_value }
+        @symbols ||= begin
+            non_zero_symbols = @symtab_section.symbols.reject { |sym| sym.header.st_size.zero? }
+            sorted = non_zero_symbols.sort_by { |sym| [sym.header.st_value, -sym.header.st_size] }
+            sorted.uniq { |sym| sym.header.st_value }
+        end
     end
 
     def num_symbols
         symbols.size
     end
 
+    def code_range
+        text = @elf.section_by_name('.text')
+        start = text.header.sh_addr.to_i
+
+        start...(start + text.header.sh_size.to_i)
+    end
+
     def kernel_symbols_section_virt_addr
         @kernel_symbols_section.header.sh_addr.to_i
     end
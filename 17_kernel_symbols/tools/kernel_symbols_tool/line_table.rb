@@ -0,0 +1,70 @@
+# frozen_string_literal: true
+
+# SPDX-License-Identifier: MIT OR Apache-2.0
+#
+# Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+# Source lines of the kernel's code, parsed from the output of
+# `readelf --debug-dump=decodedline --wide`.
+#
+# Each entry maps the addresses from its own up to the next entry's to a source line. Line zero
+# marks addresses without a known line, e.g. after the end of a sequence.
+class LineTable
+    Entry = Struct.new(:addr, :file, :line)
+
+    # "CU: ./src/lib.rs:" or "/rustc/<hash>/library/core/src/fmt/mod.rs:"
+    FILE_REGEX = /\A(?:CU: )?(\S+):\z/
+
+    # "mod.rs    1234    0xffffffffc0081234    x" or "mod.rs    -    0xffffffffc0081240"
+    ROW_REGEX = /\A\S+\s+(\d+|-)\s+0x(\h+)/
+
+    # Prefixes of paths that do not tell anything about the file.
+    PATH_PREFIX_REGEXES = [
+        %r{\A\./},
+        %r{\A/rustc/\h+/},
+        %r{\A.*/\.cargo/registry/src/[^/]+/},
+        %r{\A.*/kernel/}
+    ].freeze
+
+    attr_reader :entries
+
+    def initialize(decoded_line_path, addr_range)
+        rows = parse(File.readlines(decoded_line_path, chomp: true))
+        rows.select! { |row| addr_range.cover?(row.addr) }
+
+        @entries = compact(rows)
+    end
+
+    def files
+        @entries.map(&:file).compact.uniq
+    end
+
+    private
+
+    def short_path(path)
+        PATH_PREFIX_REGEXES.reduce(path) { |p, regex| p.sub(regex, '') }
+    end
+
+    def parse(lines)
+        file = nil
+
+        lines.each_with_object([]) do |l, rows|
+            if (m = ROW_REGEX.match(l))
+                line = m[1] == '-' ? 0 : m[1].to_i
+                rows << Entry.new(m[2].hex, line.zero? ? nil : file, line)
+            elsif (m = FILE_REGEX.match(l))
+                file = short_path(m[1])
+            end
+        end
+    end
+
+    # Keep one entry per address, preferring a known line, and drop entries that do not change the
+    # line.
+    def compact(rows)
+        per_addr = rows.group_by(&:addr).sort.map do |_, group|
+            group.reverse.find { |row| row.line.positive? } || group.last
+        end
+
+        per_addr.chunk_while { |a, b| a.file == b.file && a.line == b.line }.map(&:first)
+    end
+end
@@ -11,6 +11,8 @@ require 'colorize'
 require 'elftools'
 
 require_relative 'kernel_elf'
+require_relative 'demangle'
+require_relative 'line_table'
 require_relative 'cmds'
 
 KERNEL_SYMBOLS_SECTION = '.kernel_symbols'
@@ -24,11 +26,35 @@ kernel_elf = KernelELF.new(kernel_elf_path, KERNEL_SYMBOLS_SECTION, NUM_KERNEL_S
 case cmd
 when '--gen_symbols'
     output_file = ARGV[2]
+    options = ARGV[3..].to_h { |arg| arg.split('=', 2) }
+    max_name_len = options['--max_name_len']&.to_i
+    line_table = options['--lines']&.then { |path| LineTable.new(path, kernel_elf.code_range) }
 
     print 'Generating'.rjust(12).green.bold
-    puts ' Symbols source file'
+    puts ' Symbols source file (demangled)'
 
-    generate_symbols(kernel_elf, output_file)
+    names_size, compressed_size = generate_symbols(kernel_elf, output_file, max_name_len,
+                                                   line_table)
+
+    print 'Compressed'.rjust(12).green.bold
+    puts " Symbol names from #{names_size} to #{compressed_size} bytes"
+
+    unless line_table.nil?
+        print 'Line table'.rjust(12).green.bold
+        puts " #{line_table.entries.size} entries in #{line_table.files.size} files"
+    end
+when '--gen_blob'
+    output_file = ARGV[2]
+    options = ARGV[3..].to_h { |arg| arg.split('=', 2) }
+    max_name_len = options['--max_name_len']&.to_i
+
+    print 'Generating'.rjust(12).green.bold
+    puts ' Symbols blob (demangled)'
+
+    blob_size = generate_blob(kernel_elf, output_file, max_name_len)
+
+    print 'Wrote'.rjust(12).green.bold
+    puts " #{kernel_elf.num_symbols} symbols in #{blob_size} bytes to #{output_file}"
 when '--get_symbols_section_virt_addr'
     addr = get_symbols_section_virt_addr(kernel_elf)
 
@@ -222,6 +222,11 @@ class TranslationTable
         @lvl2_phys_start_addr
     end
 
+    # The level 2 and level 3 table indices of a virtual address.
+    def table_indices(virt_addr)
+        lvl2_lvl3_index_from(virt_addr)
+    end
+
     private
 
     def do_sanity_checks
@@ -4,7 +4,7 @@
 #
 # Copyright (c) 2021-2022 Andre Richter <andre.o.richter@gmail.com>
 
-# Raspberry Pi 3 + 4
+# Raspberry Pi 3 + 4 + 5 + Zero 2 W, the RPi4 optionally with high peripherals
 class RaspberryPi
     attr_reader :kernel_granule, :kernel_virt_addr_space_size, :kernel_virt_start_addr
 
@@ -37,14 +37,29 @@ class RaspberryPi
     def phys_addr_space_end_page
         x = MEMORY_SRC.grep(/pub const END/)
         x = case BSP_TYPE
-            when :rpi3
+            when :rpi3, :rpizero2w
                 x[0]
             when :rpi4
                 x[1]
+            when :rpi4_peri_high
+                x[2]
+            when :rpi5
+                x[3]
             else
                 raise
             end
 
-        x.scan(/\d+/).join.to_i(16)
+        x[/0x\h[\h_]*/].delete('_').to_i(16)
+    end
+end
+
+# QEMU virt machine
+class QEMUVirt < RaspberryPi
+    MEMORY_SRC = File.read('kernel/src/bsp/qemu_virt/memory.rs').split("\n")
+
+    def phys_addr_space_end_page
+        x = MEMORY_SRC.grep(/pub const END/)[0]
+
+        x[/0x\h[\h_]*/].delete('_').to_i(16)
     end
 end
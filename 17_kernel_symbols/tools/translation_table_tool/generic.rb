@@ -118,6 +118,24 @@ class MappingDescriptor
         "#{name} | #{virt_start} | #{phys_start} | #{size} KiB | #{@attributes}"
     end
 
+    # A multi-line description for the mapping report, including the translation table entries that
+    # the mapping occupies.
+    def to_report(translation_tables)
+        granule_size = BSP.kernel_granule::SIZE
+        first_lvl2, first_lvl3 = translation_tables.table_indices(@virt_region.first)
+        last_lvl2, last_lvl3 = translation_tables.table_indices(@virt_region.last)
+
+        [
+            @name,
+            "    Virt:    #{region_to_s(@virt_region, granule_size)}",
+            "    Phys:    #{region_to_s(@phys_region, granule_size)}",
+            "    Size:    #{(@virt_region.size * granule_size) / 1024} KiB",
+            "    Attr:    #{@attributes}",
+            "    Entries: lvl2[#{first_lvl2}] lvl3[#{first_lvl3}] - " \
+            "lvl2[#{last_lvl2}] lvl3[#{last_lvl3}]"
+        ].join("\n")
+    end
+
     def self.print_divider
         print '             '
         print '-' * max_section_name_length
@@ -139,6 +157,15 @@ class MappingDescriptor
         puts
         print_divider
     end
+
+    private
+
+    def region_to_s(region, granule_size)
+        first = region.first.to_hex_underscore(with_leading_zeros: true)
+        last = (region.last + granule_size - 1).to_hex_underscore(with_leading_zeros: true)
+
+        "#{first} - #{last}"
+    end
 end
 
 def kernel_map_binary
@@ -157,6 +184,30 @@ def kernel_map_binary
     end
 
     MappingDescriptor.print_divider
+
+    mapping_descriptors
+end
+
+# Write a report of every precomputed mapping next to the kernel ELF, so that mismatches with the
+# kernel's expectations can be diagnosed.
+def kernel_write_mapping_report(kernel_elf_path, mapping_descriptors)
+    report_path = "#{kernel_elf_path}.mappings.txt"
+
+    print 'Writing'.rjust(12).green.bold
+    puts " Mapping report to #{report_path}"
+
+    File.open(report_path, 'w') do |f|
+        f.puts "BSP:                         #{BSP_TYPE}"
+        f.puts "Kernel virt start address:   #{BSP.kernel_virt_start_addr.to_hex_underscore}"
+        f.puts "Kernel virt addr space size: #{BSP.kernel_virt_addr_space_size.to_hex_underscore}"
+        f.puts "Tables phys base address:    " \
+               "#{TRANSLATION_TABLES.phys_tables_base_addr.to_hex_underscore}"
+
+        mapping_descriptors.each do |i|
+            f.puts
+            f.puts i.to_report(TRANSLATION_TABLES)
+        end
+    end
 end
 
 def kernel_patch_tables(kernel_elf_path)
@@ -23,8 +23,10 @@ start = Time.now
 KERNEL_ELF = KernelELF.new(kernel_elf_path)
 
 BSP = case BSP_TYPE
-      when :rpi3, :rpi4
+      when :rpi3, :rpi4, :rpi4_peri_high, :rpizero2w, :rpi5
           RaspberryPi.new
+      when :qemu_virt
+          QEMUVirt.new
       else
           raise
       end
@@ -36,7 +38,8 @@ TRANSLATION_TABLES = case KERNEL_ELF.machine
                          raise
                      end
 
-kernel_map_binary
+mapping_descriptors = kernel_map_binary
+kernel_write_mapping_report(kernel_elf_path, mapping_descriptors)
 kernel_patch_tables(kernel_elf_path)
 kernel_patch_base_addr(kernel_elf_path)
 
@@ -0,0 +1,173 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! Host-side tasks for working with the real hardware.
+//!
+//! Run with `cargo xtask <task>`. `push` sends a kernel to the chainloader and then continues as a
+//! terminal, `term` is the terminal alone. They replace `minipush.rb` and `miniterm.rb`, so that
+//! the whole workflow needs nothing but `cargo`.
+
+mod push;
+mod serial;
+mod term;
+
+use crossterm::style::Stylize;
+use std::{env, fs, io, process, thread, time::Duration};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const USAGE: &str = "\
+Usage:
+  cargo xtask push [options] <serial device> <kernel image> [<DTB>]
+  cargo xtask term <serial device>
+
+Options of push:
+  --load-addr=ADDR       Where the kernel is loaded (default: 0x80000)
+  --entry-offset=OFFSET  Offset of the entry point (default: 0)
+
+Numbers are decimal, or hexadecimal with a 0x prefix.";
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn parse_number(s: &str) -> Result<u64, String> {
+    let result = match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+
+    result.map_err(|_| format!("Invalid number: {}", s))
+}
+
+fn parse_push_args(args: &[String]) -> Result<(String, push::Payload), String> {
+    let mut load_addr = push::DEFAULT_LOAD_ADDR;
+    let mut entry_offset = 0;
+    let mut paths = Vec::new();
+
+    for arg in args {
+        if let Some(x) = arg.strip_prefix("--load-addr=") {
+            load_addr = parse_number(x)?;
+        } else if let Some(x) = arg.strip_prefix("--entry-offset=") {
+            entry_offset = parse_number(x)?;
+        } else if arg.starts_with("--") {
+            return Err(format!("Unknown option: {}", arg));
+        } else {
+            paths.push(arg.as_str());
+        }
+    }
+
+    let (serial_path, kernel_path, dtb_path) = match paths[..] {
+        [serial, kernel] => (serial, kernel, None),
+        [serial, kernel, dtb] => (serial, kernel, Some(dtb)),
+        _ => return Err(USAGE.to_string()),
+    };
+
+    let read = |path: &str| fs::read(path).map_err(|e| format!("{}: {}", path, e));
+    let payload = push::Payload {
+        kernel: read(kernel_path)?,
+        dtb: dtb_path.map(read).transpose()?,
+        load_addr,
+        entry_offset,
+    };
+
+    // The chainloader receives the sizes as 32 bit numbers.
+    if payload.kernel.is_empty() || payload.kernel.len() > u32::MAX as usize {
+        return Err(format!(
+            "{}: Size not supported by the chainloader",
+            kernel_path
+        ));
+    }
+
+    Ok((serial_path.to_string(), payload))
+}
+
+/// Open the serial device, or explain why that is impossible.
+fn open_serial(tag: &str, path: &str) -> Result<serial::Port, String> {
+    serial::open(tag, path).map_err(|e| match e.kind() {
+        io::ErrorKind::PermissionDenied => format!("[{}] 🚫 {} - Maybe try with 'sudo'", tag, e),
+        _ => format!("[{}] 🚫 {}", tag, e),
+    })
+}
+
+fn run_push(args: &[String]) -> Result<(), String> {
+    let (serial_path, payload) = parse_push_args(args)?;
+
+    loop {
+        let mut port = open_serial(push::TAG, &serial_path)?;
+
+        let result = push::run(&mut port, &payload).and_then(|_| term::run(port));
+        match result {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                println!();
+                println!(
+                    "[{}] ⚡ {}",
+                    push::TAG,
+                    format!(
+                        "Connection or protocol Error: {}. Remove power and USB serial. Reinsert \
+                         serial first, then power",
+                        e
+                    )
+                    .red()
+                );
+
+                while serial::is_connected(&serial_path) {
+                    thread::sleep(Duration::from_secs(1));
+                }
+            }
+        }
+    }
+}
+
+fn run_term(args: &[String]) -> Result<(), String> {
+    let serial_path = match args {
+        [x] => x,
+        _ => return Err(USAGE.to_string()),
+    };
+
+    loop {
+        let port = open_serial(term::TAG, serial_path)?;
+
+        match term::run(port) {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                println!();
+                println!(
+                    "[{}] ⚡ {}",
+                    term::TAG,
+                    format!("Connection Error: {}. Reinsert the USB serial again", e).red()
+                );
+            }
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let (banner, task): (_, fn(&[String]) -> Result<(), String>) =
+        match args.first().map(String::as_str) {
+            Some("push") => ("Minipush 2.0", run_push),
+            Some("term") => ("Miniterm 2.0", run_term),
+            _ => {
+                eprintln!("{}", USAGE);
+                process::exit(1);
+            }
+        };
+
+    println!();
+    println!("{}", banner.cyan());
+    println!();
+
+    if let Err(e) = task(&args[1..]) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+
+    println!();
+    println!("Bye 👋");
+}
@@ -0,0 +1,205 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! The protocol of the chainloader in `06_uart_chainloader`.
+//!
+//! The chainloader requests a kernel by sending `"\x03\x03\x03"`. It gets an image header, the
+//! kernel, and optionally a DTB, all in frames that end with their CRC-32. Each frame is
+//! acknowledged, or rejected if it arrived damaged or incomplete, in which case it is sent again.
+//! Frames whose reply does not arrive are sent again as well.
+
+use crate::serial::{self, Port};
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+// Must match the chainloader.
+const FRAME_SIZE: usize = 1024;
+const FRAME_ACK: u8 = b'A';
+const FRAME_NAK: u8 = b'N';
+
+const MAX_FRAME_RETRIES: usize = 10;
+
+/// The image header in front of the kernel. See the chainloader's `image.rs`.
+const IMAGE_MAGIC: &[u8; 4] = b"RPIK";
+
+/// How long the target gets for requesting the kernel, after the first sign of life.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the target gets for acknowledging a frame. Longer than the chainloader waits for a
+/// missing byte before it rejects a frame.
+const ACK_TIMEOUT: Duration = Duration::from_secs(1);
+
+const REQUEST_TOKEN: u8 = 0x03;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Prefixes the messages of the push.
+pub const TAG: &str = "MP";
+
+/// The address on which the Raspberry firmware loads every binary by default.
+pub const DEFAULT_LOAD_ADDR: u64 = 0x80000;
+
+/// What is pushed to the target.
+pub struct Payload {
+    /// The kernel binary.
+    pub kernel: Vec<u8>,
+
+    /// The DTB for the kernel. Without it, the kernel gets the DTB of the firmware.
+    pub dtb: Option<Vec<u8>>,
+
+    /// Where the kernel is loaded.
+    pub load_addr: u64,
+
+    /// Offset of the entry point from the load address.
+    pub entry_offset: u64,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn protocol_error(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Print the target's output until it sent three request tokens in a row.
+fn wait_for_request(port: &mut Port) -> io::Result<()> {
+    println!("[{}] 🔌 Please power the target now", TAG);
+
+    let mut buf = [0; 4096];
+    let mut deadline = None;
+    let mut count = 0;
+
+    loop {
+        let received = serial::read_available(port, &mut buf)?;
+
+        // The timeout starts after the first sign of life was received.
+        if !received.is_empty() && deadline.is_none() {
+            deadline = Some(Instant::now() + REQUEST_TIMEOUT);
+        }
+
+        for &c in received {
+            if c == REQUEST_TOKEN {
+                count += 1;
+                if count == 3 {
+                    return Ok(());
+                }
+            } else {
+                // A normal character resets token counting.
+                count = 0;
+                print!("{}", c as char);
+            }
+        }
+        io::stdout().flush()?;
+
+        if matches!(deadline, Some(x) if Instant::now() > x) {
+            return Err(protocol_error("No kernel request from the target"));
+        }
+    }
+}
+
+/// Wait for the target's reply to a frame, and return whether it acknowledged the frame. A reply
+/// that does not arrive in time counts as a rejection.
+fn wait_for_ack(port: &mut Port) -> io::Result<bool> {
+    let deadline = Instant::now() + ACK_TIMEOUT;
+    let mut buf = [0; 1];
+
+    while Instant::now() < deadline {
+        match serial::read_available(port, &mut buf)? {
+            [FRAME_ACK] => return Ok(true),
+            [FRAME_NAK] => return Ok(false),
+            [] => continue,
+            _ => return Err(protocol_error("Unexpected reply to a frame")),
+        }
+    }
+
+    Ok(false)
+}
+
+/// Send data followed by its CRC-32, and repeat until the target acknowledges that it arrived
+/// intact and complete.
+fn send_frame(port: &mut Port, data: &[u8]) -> io::Result<()> {
+    let mut frame = data.to_vec();
+    frame.extend_from_slice(&crc32fast::hash(data).to_le_bytes());
+
+    for _ in 0..MAX_FRAME_RETRIES {
+        port.write_all(&frame)?;
+
+        if wait_for_ack(port)? {
+            return Ok(());
+        }
+    }
+
+    Err(protocol_error("Too many damaged or lost frames"))
+}
+
+fn send_header(port: &mut Port, payload: &Payload) -> io::Result<()> {
+    let mut header = IMAGE_MAGIC.to_vec();
+    header.extend_from_slice(&(payload.kernel.len() as u32).to_le_bytes());
+    header.extend_from_slice(&payload.load_addr.to_le_bytes());
+    header.extend_from_slice(&payload.entry_offset.to_le_bytes());
+    header.extend_from_slice(&crc32fast::hash(&payload.kernel).to_le_bytes());
+
+    send_frame(port, &header)
+}
+
+fn send_kernel(port: &mut Port, kernel: &[u8]) -> io::Result<()> {
+    let start = Instant::now();
+    let total_kib = kernel.len() / 1024;
+    let mut sent = 0;
+
+    for part in kernel.chunks(FRAME_SIZE) {
+        send_frame(port, part)?;
+        sent += part.len();
+
+        let rate = sent as f64 / 1024.0 / start.elapsed().as_secs_f64();
+        print!(
+            "\r[{}] ⏩ Pushing {} / {} KiB 🦀 {:3}% {:.0} KiB/s",
+            TAG,
+            sent / 1024,
+            total_kib,
+            sent * 100 / kernel.len(),
+            rate
+        );
+        io::stdout().flush()?;
+    }
+    println!();
+
+    Ok(())
+}
+
+/// An empty DTB tells the chainloader to pass on the DTB from the firmware.
+fn send_dtb(port: &mut Port, dtb: Option<&[u8]>) -> io::Result<()> {
+    let dtb = dtb.unwrap_or_default();
+    if !dtb.is_empty() {
+        println!("[{}] 🌳 Pushing DTB", TAG);
+    }
+
+    send_frame(port, &(dtb.len() as u32).to_le_bytes())?;
+    for part in dtb.chunks(FRAME_SIZE) {
+        send_frame(port, part)?;
+    }
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Wait for the chainloader's request, and push the payload.
+pub fn run(port: &mut Port, payload: &Payload) -> io::Result<()> {
+    wait_for_request(port)?;
+    send_header(port, payload)?;
+    send_kernel(port, &payload.kernel)?;
+    send_dtb(port, payload.dtb.as_deref())
+}
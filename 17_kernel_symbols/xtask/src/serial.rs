@@ -0,0 +1,70 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! The serial connection to the target.
+
+use std::{
+    io::{self, Read},
+    path::Path,
+    thread,
+    time::Duration,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Must match the target's UART configuration.
+const BAUD_RATE: u32 = 921_600;
+
+/// How long a read waits for data before it fails with [`io::ErrorKind::TimedOut`].
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// An open serial device.
+pub type Port = Box<dyn serialport::SerialPort>;
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Returns true if the serial device exists, e.g. the USB serial is plugged in.
+pub fn is_connected(path: &str) -> bool {
+    Path::new(path).exists()
+}
+
+/// Wait for the serial device to appear, and open it.
+pub fn open(tag: &str, path: &str) -> io::Result<Port> {
+    if !is_connected(path) {
+        println!("[{}] ⏳ Waiting for {}", tag, path);
+
+        while !is_connected(path) {
+            thread::sleep(Duration::from_secs(1));
+        }
+    }
+
+    let port = serialport::new(path, BAUD_RATE)
+        .data_bits(serialport::DataBits::Eight)
+        .stop_bits(serialport::StopBits::One)
+        .parity(serialport::Parity::None)
+        .flow_control(serialport::FlowControl::None)
+        .timeout(READ_TIMEOUT)
+        .open()?;
+    println!("[{}] ✅ Serial connected", tag);
+
+    Ok(port)
+}
+
+/// Read whatever is available. Returns an empty slice if nothing arrived in time.
+pub fn read_available<'a>(port: &mut Port, buf: &'a mut [u8]) -> io::Result<&'a [u8]> {
+    match port.read(buf) {
+        Ok(0) => Err(io::ErrorKind::UnexpectedEof.into()),
+        Ok(n) => Ok(&buf[..n]),
+        Err(e) if e.kind() == io::ErrorKind::TimedOut => Ok(&[]),
+        Err(e) => Err(e),
+    }
+}
@@ -0,0 +1,132 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022 Andre Richter <andre.o.richter@gmail.com>
+
+//! A terminal that connects the host console to the target's console.
+
+use crate::serial::{self, Port};
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    terminal,
+};
+use std::{
+    io::{self, Write},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// How often the host console checks whether the connection broke.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Puts the host console into raw mode, and back into cooked mode when dropped.
+struct RawMode;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Prefixes the messages of the terminal.
+pub const TAG: &str = "MT";
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+
+        Ok(Self)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Receive from the target and print on the host console, until the connection breaks.
+fn target_to_host(mut port: Port) -> io::Error {
+    let mut buf = [0; 4096];
+    let mut stdout = io::stdout();
+
+    loop {
+        let received = match serial::read_available(&mut port, &mut buf) {
+            Err(e) => return e,
+            Ok(x) => x,
+        };
+
+        for &c in received {
+            // Translate incoming newline to newline + carriage return.
+            if c == b'\n' {
+                let _ = stdout.write_all(b"\r");
+            }
+            let _ = stdout.write_all(&[c]);
+        }
+        let _ = stdout.flush();
+    }
+}
+
+/// The bytes that a key sends on a classic terminal.
+fn key_to_bytes(key: KeyEvent) -> Vec<u8> {
+    match key.code {
+        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            vec![(c as u8) & 0x1f]
+        }
+        KeyCode::Char(c) => c.to_string().into_bytes(),
+        KeyCode::Enter => vec![b'\r'],
+        KeyCode::Backspace => vec![0x7f],
+        KeyCode::Tab => vec![b'\t'],
+        KeyCode::Esc => vec![0x1b],
+        KeyCode::Up => b"\x1b[A".to_vec(),
+        KeyCode::Down => b"\x1b[B".to_vec(),
+        KeyCode::Right => b"\x1b[C".to_vec(),
+        KeyCode::Left => b"\x1b[D".to_vec(),
+        _ => vec![],
+    }
+}
+
+fn is_ctrl_c(key: &KeyEvent) -> bool {
+    key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Pass the host console through to the target, until CTRL + C is pressed.
+///
+/// Fails if the connection breaks, e.g. because the USB serial was removed.
+pub fn run(mut port: Port) -> io::Result<()> {
+    let _raw_mode = RawMode::enable()?;
+
+    let (error_tx, error_rx) = mpsc::channel();
+    let reader = port.try_clone()?;
+    thread::spawn(move || {
+        let _ = error_tx.send(target_to_host(reader));
+    });
+
+    loop {
+        if let Ok(e) = error_rx.try_recv() {
+            return Err(e);
+        }
+
+        if !event::poll(POLL_INTERVAL)? {
+            continue;
+        }
+
+        if let Event::Key(key) = event::read()? {
+            if is_ctrl_c(&key) {
+                return Ok(());
+            }
+
+            port.write_all(&key_to_bytes(key))?;
+        }
+    }
+}
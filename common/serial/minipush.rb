@@ -8,19 +8,44 @@
 require_relative 'miniterm'
 require 'ruby-progressbar'
 require_relative 'minipush/progressbar_patch'
+require 'optparse'
 require 'timeout'
+require 'zlib'
 
 class ProtocolError < StandardError; end
 
 # The main class
 class MiniPush < MiniTerm
-    def initialize(serial_name, payload_path)
+    # Must match the chainloader.
+    FRAME_SIZE = 1024
+    FRAME_ACK = 'A'
+    FRAME_NAK = 'N'
+
+    MAX_FRAME_RETRIES = 10
+
+    # How long the target gets for replying to a frame. Longer than the chainloader waits for a
+    # missing byte before it rejects a frame.
+    FRAME_REPLY_TIMEOUT = 1
+
+    # The image header in front of the kernel. See the chainloader's `image.rs`.
+    IMAGE_MAGIC = 'RPIK'
+    IMAGE_HEADER_FORMAT = 'a4L<Q<Q<L<'
+
+    # The address on which the Raspberry firmware loads every binary by default.
+    DEFAULT_LOAD_ADDR = 0x80000
+
+    def initialize(serial_name, payload_path, dtb_path = nil, load_addr: DEFAULT_LOAD_ADDR,
+                   entry_offset: 0)
         super(serial_name)
 
         @name_short = 'MP' # override
         @payload_path = payload_path
         @payload_size = nil
         @payload_data = nil
+        @dtb_path = dtb_path
+        @dtb_data = nil
+        @load_addr = load_addr
+        @entry_offset = entry_offset
     end
 
     private
@@ -57,11 +82,39 @@ class MiniPush < MiniTerm
     def load_payload
         @payload_size = File.size(@payload_path)
         @payload_data = File.binread(@payload_path)
+        @dtb_data = @dtb_path.nil? ? '' : File.binread(@dtb_path)
+    end
+
+    # Wait for the target's reply to a frame. A reply that does not arrive in time counts as a
+    # rejection.
+    def read_frame_reply
+        Timeout.timeout(FRAME_REPLY_TIMEOUT) { @target_serial.read(1) }
+    rescue Timeout::Error
+        FRAME_NAK
+    end
+
+    # Send data followed by its CRC-32, and repeat until the target acknowledges that it arrived
+    # intact and complete.
+    def send_frame(data)
+        frame = data + [Zlib.crc32(data)].pack('L<')
+
+        MAX_FRAME_RETRIES.times do
+            @target_serial.write(frame)
+
+            case read_frame_reply
+            when FRAME_ACK then return
+            when FRAME_NAK then next
+            else raise ProtocolError
+            end
+        end
+
+        raise ProtocolError
     end
 
-    def send_size
-        @target_serial.print([@payload_size].pack('L<'))
-        raise ProtocolError if @target_serial.read(2) != 'OK'
+    def send_header
+        header = [IMAGE_MAGIC, @payload_size, @load_addr, @entry_offset, Zlib.crc32(@payload_data)]
+
+        send_frame(header.pack(IMAGE_HEADER_FORMAT))
     end
 
     def send_payload
@@ -73,13 +126,21 @@ class MiniPush < MiniTerm
             output: $stdout
         )
 
-        # Send in 512 byte chunks.
         while pb.progress < pb.total
-            part = @payload_data.slice(pb.progress, 512)
-            pb.progress += @target_serial.write(part)
+            part = @payload_data.slice(pb.progress, FRAME_SIZE)
+            send_frame(part)
+            pb.progress += part.size
         end
     end
 
+    # An empty DTB tells the chainloader to pass on the DTB from the firmware.
+    def send_dtb
+        puts "[#{@name_short}] 🌳 Pushing DTB #{@dtb_path}" unless @dtb_data.empty?
+
+        send_frame([@dtb_data.size].pack('L<'))
+        (0...@dtb_data.size).step(FRAME_SIZE) { |i| send_frame(@dtb_data.byteslice(i, FRAME_SIZE)) }
+    end
+
     # override
     def handle_reconnect(_error)
         connetion_reset
@@ -98,8 +159,9 @@ class MiniPush < MiniTerm
         open_serial
         wait_for_payload_request
         load_payload
-        send_size
+        send_header
         send_payload
+        send_dtb
         terminal
     rescue ConnectionError, EOFError, Errno::EIO, ProtocolError, Timeout::Error => e
         handle_reconnect(e)
@@ -118,7 +180,7 @@ end
 ##--------------------------------------------------------------------------------------------------
 if __FILE__ == $PROGRAM_NAME
     puts
-    puts 'Minipush 1.0'.cyan
+    puts 'Minipush 1.2'.cyan
     puts
 
     # CTRL + C handler. Only here to suppress Ruby's default exception print.
@@ -127,5 +189,17 @@ if __FILE__ == $PROGRAM_NAME
         exit
     end
 
-    MiniPush.new(ARGV[0], ARGV[1]).run
+    options = {}
+    OptionParser.new do |opts|
+        opts.banner = 'Usage: minipush.rb [options] <serial device> <kernel image> [<DTB>]'
+
+        opts.on('--load-addr=ADDR', Integer, 'Where the kernel is loaded (default: 0x80000)') do |x|
+            options[:load_addr] = x
+        end
+        opts.on('--entry-offset=OFFSET', Integer, 'Offset of the entry point (default: 0)') do |x|
+            options[:entry_offset] = x
+        end
+    end.parse!
+
+    MiniPush.new(ARGV[0], ARGV[1], ARGV[2], **options).run
 end
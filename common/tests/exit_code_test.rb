@@ -30,11 +30,31 @@ class ExitCodeTest < Test
         @qemu_serial = IO.popen(@qemu_cmd)
     end
 
+    # Append the structured results of the kernel's test runner to a JSON-lines file, tagged with
+    # the test's name, so that results can be aggregated across tutorials.
+    def save_results(results)
+        path = ENV.fetch('TEST_RESULTS_FILE', nil)
+        return if path.nil? || path.empty?
+
+        File.open(path, 'a') do |file|
+            results.each { |line| file.puts(line.sub('{', "{\"binary\":\"#{@test_name}\",")) }
+        end
+    end
+
     # override
-    # Convert the recorded output to an array of lines, and extract the test description.
+    # Convert the recorded output to an array of lines, and extract the test description. The
+    # structured results are not meant for humans, so they are not printed.
     def finish
         @test_output = @test_output.join.split("\n")
         @test_description = @test_output.shift
+
+        results, @test_output = @test_output.partition { |line| line.start_with?('{"ktest":') }
+        save_results(results)
+    end
+
+    # The exit status that means success. Exit devices like `isa-debug-exit` can not exit with zero.
+    def success_status
+        ENV.fetch('QEMU_EXIT_SUCCESS_STATUS', '0').to_i
     end
 
     # override
@@ -44,7 +64,8 @@ class ExitCodeTest < Test
         end
     rescue EOFError
         @qemu_serial.close
-        @test_error = $CHILD_STATUS.to_i.zero? ? false : 'QEMU exit status != 0'
+        status = success_status
+        @test_error = $CHILD_STATUS.exitstatus == status ? false : "QEMU exit status != #{status}"
     rescue Timeout::Error
         @test_error = 'Timed out waiting for test'
     rescue StandardError => e